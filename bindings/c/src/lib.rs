@@ -0,0 +1,260 @@
+//! C interface for embedding the layout evaluation into other tools (e.g. GUI configurators)
+//! without a Rust toolchain. See `svalboard_optimizer.h` for the declarations.
+//!
+//! An evaluator is configured by a YAML file (see `example_config.yml`) listing the ngrams,
+//! the evaluation config, and the keyboard config. Relative paths are resolved relative to the
+//! directory of that file. Evaluation results are returned as JSON strings that need to be
+//! released with [`sval_string_free`]. On errors, functions return null pointers and
+//! [`sval_last_error`] describes the error.
+
+use keyboard_layout::layout_generator::LayoutGenerator;
+use keyboard_layout_optimizer::common;
+use layout_evaluation::{evaluation::Evaluator, results::EvaluationResult};
+
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    fs,
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// Runs `f`, converting errors and panics (which must not unwind into C code) to the last error.
+fn guarded<T, F: FnOnce() -> Result<T, String>>(f: F) -> Option<T> {
+    let result = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let msg = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "Unknown error".to_string());
+        Err(msg)
+    });
+
+    result.map_err(set_last_error).ok()
+}
+
+fn str_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("Argument '{}' is null", name));
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|e| format!("Argument '{}' is not valid UTF-8: {}", name, e))
+}
+
+fn default_ngrams() -> String {
+    "ngrams/eng_wiki_1m".to_string()
+}
+
+fn default_eval_parameters() -> String {
+    "config/evaluation/sval.yml".to_string()
+}
+
+fn default_layout_config() -> String {
+    "config/keyboard/sval.yml".to_string()
+}
+
+/// Configuration of an embedded evaluator.
+#[derive(Deserialize, Debug)]
+struct EmbedConfig {
+    /// Path to ngram files
+    #[serde(default = "default_ngrams")]
+    ngrams: String,
+    /// Corpus file to use instead of ngram files
+    #[serde(default)]
+    corpus: Option<String>,
+    /// Evaluation configuration file
+    #[serde(default = "default_eval_parameters")]
+    eval_parameters: String,
+    /// Layout configuration file
+    #[serde(default = "default_layout_config")]
+    layout_config: String,
+    /// Further command line options as accepted by the `evaluate` binary
+    #[serde(default)]
+    options: Vec<String>,
+}
+
+/// Opaque evaluator handle.
+pub struct SvalEvaluator {
+    layout_generator: Box<dyn LayoutGenerator>,
+    evaluator: Evaluator,
+}
+
+impl SvalEvaluator {
+    fn from_config(config_path: &str) -> Result<Self, String> {
+        let config_str = fs::read_to_string(config_path)
+            .map_err(|e| format!("Could not read config {}: {}", config_path, e))?;
+        let config: EmbedConfig = serde_yaml::from_str(&config_str)
+            .map_err(|e| format!("Could not parse config {}: {}", config_path, e))?;
+
+        let base_dir = Path::new(config_path)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let resolve = |p: &str| base_dir.join(p).to_string_lossy().to_string();
+
+        let mut args = vec![
+            "svalboard_optimizer".to_string(),
+            "--ngrams".to_string(),
+            resolve(&config.ngrams),
+            "--eval-parameters".to_string(),
+            resolve(&config.eval_parameters),
+            "--layout-config".to_string(),
+            resolve(&config.layout_config),
+        ];
+        if let Some(corpus) = &config.corpus {
+            args.extend(["--corpus".to_string(), resolve(corpus)]);
+        }
+        args.extend(config.options);
+
+        let options = common::Options::try_parse_from(args).map_err(|e| e.to_string())?;
+        let (layout_generator, evaluator) =
+            common::init(&options).map_err(|e| format!("{:#}", e))?;
+
+        Ok(Self {
+            layout_generator,
+            evaluator,
+        })
+    }
+
+    fn evaluate_json(&self, layout_str: &str) -> Result<String, String> {
+        let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
+        let layout = self
+            .layout_generator
+            .generate(&layout_str)
+            .map_err(|e| format!("Could not generate layout: {:?}", e))?;
+        let result = self.evaluator.evaluate_layout(&layout);
+
+        Ok(result_json(&layout_str, &result).to_string())
+    }
+}
+
+fn result_json(layout_str: &str, result: &EvaluationResult) -> serde_json::Value {
+    let metrics: Vec<serde_json::Value> = result
+        .iter()
+        .flat_map(|results| {
+            results.metric_costs.iter().map(move |mc| {
+                json!({
+                    "metric_type": results.metric_type,
+                    "metric": mc.core.name,
+                    "cost": mc.unweighted_cost,
+                    "weighted_cost": mc.weighted_cost,
+                })
+            })
+        })
+        .collect();
+
+    json!({
+        "layout": layout_str,
+        "total_cost": result.total_cost(),
+        "optimization_score": result.optimization_score(),
+        "metrics": metrics,
+        "details": result,
+    })
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    CString::new(s.replace('\0', " ")).unwrap().into_raw()
+}
+
+/// Creates an evaluator from the given config file. Returns null on errors.
+/// The evaluator needs to be released with [`sval_evaluator_free`].
+///
+/// # Safety
+///
+/// `config_path` must be a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sval_evaluator_new(config_path: *const c_char) -> *mut SvalEvaluator {
+    guarded(|| {
+        let config_path = str_arg(config_path, "config_path")?;
+        SvalEvaluator::from_config(config_path)
+    })
+    .map_or(ptr::null_mut(), |evaluator| {
+        Box::into_raw(Box::new(evaluator))
+    })
+}
+
+/// Releases an evaluator created by [`sval_evaluator_new`]. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `evaluator` must have been returned by [`sval_evaluator_new`] and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn sval_evaluator_free(evaluator: *mut SvalEvaluator) {
+    if !evaluator.is_null() {
+        drop(Box::from_raw(evaluator));
+    }
+}
+
+/// Evaluates a layout and returns the result as JSON string. Returns null on errors.
+/// The string needs to be released with [`sval_string_free`].
+///
+/// # Safety
+///
+/// `evaluator` must be a valid evaluator and `layout_str` a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sval_evaluator_evaluate(
+    evaluator: *const SvalEvaluator,
+    layout_str: *const c_char,
+) -> *mut c_char {
+    guarded(|| {
+        let evaluator = evaluator
+            .as_ref()
+            .ok_or_else(|| "Argument 'evaluator' is null".to_string())?;
+        let layout_str = str_arg(layout_str, "layout_str")?;
+        evaluator.evaluate_json(layout_str)
+    })
+    .map_or(ptr::null_mut(), into_c_string)
+}
+
+/// Evaluates a single layout with an evaluator created from the given config file and returns
+/// the result as JSON string (see [`sval_evaluator_evaluate`]). As the corpus is loaded on each
+/// call, use an evaluator handle for evaluating multiple layouts.
+///
+/// # Safety
+///
+/// `layout_str` and `config_path` must be valid null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn sval_evaluate_layout(
+    layout_str: *const c_char,
+    config_path: *const c_char,
+) -> *mut c_char {
+    guarded(|| {
+        let layout_str = str_arg(layout_str, "layout_str")?;
+        let config_path = str_arg(config_path, "config_path")?;
+        SvalEvaluator::from_config(config_path)?.evaluate_json(layout_str)
+    })
+    .map_or(ptr::null_mut(), into_c_string)
+}
+
+/// Releases a string returned by this library. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `s` must have been returned by this library and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn sval_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Description of the last error that occurred in the calling thread (or null). The string is
+/// owned by the library and valid until the next error in the same thread.
+#[no_mangle]
+pub extern "C" fn sval_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
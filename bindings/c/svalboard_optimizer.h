@@ -0,0 +1,49 @@
+/*
+ * C interface of the Svalboard layout evaluation (libsvalboard_optimizer_ffi).
+ *
+ * Build with `cargo build --release -p svalboard_optimizer_ffi` and link against
+ * target/release/libsvalboard_optimizer_ffi.{so,dylib,dll,a}.
+ *
+ * Evaluation results are JSON strings of the form
+ *   {"layout": ..., "total_cost": ..., "optimization_score": ...,
+ *    "metrics": [{"metric_type": ..., "metric": ..., "cost": ..., "weighted_cost": ...}, ...],
+ *    "details": {...}}
+ * that need to be released with sval_string_free. Functions returning pointers return NULL on
+ * errors; sval_last_error then describes the error.
+ */
+
+#ifndef SVALBOARD_OPTIMIZER_H
+#define SVALBOARD_OPTIMIZER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque evaluator handle. */
+typedef struct SvalEvaluator SvalEvaluator;
+
+/* Creates an evaluator from a YAML config file (see example_config.yml). Loading the corpus
+ * takes a while, so keep the evaluator around. Release it with sval_evaluator_free. */
+SvalEvaluator *sval_evaluator_new(const char *config_path);
+
+/* Releases an evaluator. NULL is ignored. */
+void sval_evaluator_free(SvalEvaluator *evaluator);
+
+/* Evaluates a layout string (keys from left to right, top to bottom) and returns the result as
+ * JSON. */
+char *sval_evaluator_evaluate(const SvalEvaluator *evaluator, const char *layout_str);
+
+/* Evaluates a single layout with an evaluator created from the given config file. */
+char *sval_evaluate_layout(const char *layout_str, const char *config_path);
+
+/* Releases a string returned by the library. NULL is ignored. */
+void sval_string_free(char *s);
+
+/* Description of the last error in the calling thread (or NULL). Owned by the library. */
+const char *sval_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SVALBOARD_OPTIMIZER_H */
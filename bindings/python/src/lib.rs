@@ -0,0 +1,416 @@
+//! Python bindings (`svalboard_optimizer` module) for loading corpora, evaluating layouts, and
+//! optimizing layouts, e.g. to drive parameter studies from notebooks.
+//!
+//! ```python
+//! import svalboard_optimizer as so
+//!
+//! evaluator = so.Evaluator(ngrams="ngrams/eng_wiki_1m")
+//! result = evaluator.evaluate(",qczb□-i.y□'e□o□□a□umkhxlgjt□dw□n□pv□s□fr")
+//! df = evaluator.dataframe([layout1, layout2])  # requires pandas
+//! ```
+//!
+//! Paths are interpreted relative to the working directory (as for the binaries).
+
+// triggered by the code generated by pyo3's macros
+#![allow(clippy::useless_conversion)]
+
+use keyboard_layout::layout_generator::LayoutGenerator;
+use keyboard_layout_optimizer::common;
+use layout_evaluation::{
+    cache::Cache,
+    evaluation::Evaluator as LayoutEvaluator,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+    results::EvaluationResult,
+};
+use layout_optimization_aco::optimization as aco_optimization;
+use layout_optimization_sa::optimization as sa_optimization;
+
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+    types::{IntoPyDict, PyDict, PyList},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// The optimization algorithm, selected with the `algorithm` setting of an optimization config.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Algorithm {
+    SimulatedAnnealing,
+    AntColony,
+}
+
+#[derive(Deserialize)]
+struct AlgorithmSetting {
+    algorithm: Option<Algorithm>,
+}
+
+/// Reads the algorithm of an optimization config (simulated annealing if not set).
+fn read_algorithm(optimization_parameters: &str) -> PyResult<Algorithm> {
+    let setting: AlgorithmSetting = fs::read_to_string(optimization_parameters)
+        .map_err(anyhow::Error::from)
+        .and_then(|s| Ok(serde_yaml::from_str(&s)?))
+        .map_err(|e| {
+            PyIOError::new_err(format!(
+                "Could not read optimization parameters {}: {:?}",
+                optimization_parameters, e
+            ))
+        })?;
+
+    Ok(setting.algorithm.unwrap_or(Algorithm::SimulatedAnnealing))
+}
+
+/// Converts a JSON value into the corresponding Python object (dict, list, str, float, ...).
+fn json_to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    let obj = match value {
+        Value::Null => py.None(),
+        Value::Bool(b) => b.into_py(py),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into_py(py),
+            None => n.as_f64().unwrap_or(f64::NAN).into_py(py),
+        },
+        Value::String(s) => s.into_py(py),
+        Value::Array(values) => {
+            let list = PyList::empty_bound(py);
+            for v in values {
+                list.append(json_to_py(py, v)?)?;
+            }
+            list.into_py(py)
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new_bound(py);
+            for (k, v) in map {
+                dict.set_item(k, json_to_py(py, v)?)?;
+            }
+            dict.into_py(py)
+        }
+    };
+
+    Ok(obj)
+}
+
+/// Python representation of an evaluation: the total cost, one record per metric, and the
+/// full (serialized) evaluation result.
+fn result_to_py(py: Python<'_>, layout_str: &str, result: &EvaluationResult) -> PyResult<PyObject> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("layout", layout_str)?;
+    dict.set_item("total_cost", result.total_cost())?;
+    dict.set_item("optimization_score", result.optimization_score())?;
+
+    let metrics = PyList::empty_bound(py);
+    for record in metric_records(layout_str, result) {
+        metrics.append(record.into_py_dict(py)?)?;
+    }
+    dict.set_item("metrics", metrics)?;
+
+    let details = serde_json::to_value(result)
+        .map_err(|e| PyValueError::new_err(format!("Could not serialize result: {}", e)))?;
+    dict.set_item("details", json_to_py(py, &details)?)?;
+
+    Ok(dict.into_py(py))
+}
+
+/// Cost of a single metric for a single layout (one row of a dataframe).
+struct MetricRecord {
+    layout: String,
+    metric_type: String,
+    metric: String,
+    cost: f64,
+    weighted_cost: f64,
+}
+
+impl MetricRecord {
+    fn into_py_dict(self, py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        dict.set_item("layout", self.layout)?;
+        dict.set_item("metric_type", self.metric_type)?;
+        dict.set_item("metric", self.metric)?;
+        dict.set_item("cost", self.cost)?;
+        dict.set_item("weighted_cost", self.weighted_cost)?;
+
+        Ok(dict)
+    }
+}
+
+fn metric_records(layout_str: &str, result: &EvaluationResult) -> Vec<MetricRecord> {
+    result
+        .iter()
+        .flat_map(|results| {
+            results.metric_costs.iter().map(move |mc| MetricRecord {
+                layout: layout_str.to_string(),
+                metric_type: format!("{:?}", results.metric_type),
+                metric: mc.core.name.clone(),
+                cost: mc.unweighted_cost,
+                weighted_cost: mc.weighted_cost,
+            })
+        })
+        .collect()
+}
+
+/// Evaluates (and optimizes) layouts with respect to a corpus and an evaluation config.
+#[pyclass]
+struct Evaluator {
+    layout_generator: Box<dyn LayoutGenerator>,
+    evaluator: LayoutEvaluator,
+}
+
+impl Evaluator {
+    fn evaluate_str(&self, layout_str: &str) -> PyResult<(String, EvaluationResult)> {
+        let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
+        let layout = self
+            .layout_generator
+            .generate(&layout_str)
+            .map_err(|e| PyValueError::new_err(format!("Could not generate layout: {:?}", e)))?;
+        let result = self.evaluator.evaluate_layout(&layout);
+
+        Ok((layout_str, result))
+    }
+}
+
+#[pymethods]
+impl Evaluator {
+    /// Loads the corpus (ngram directory, corpus file, or text) and the configs. Further
+    /// command line options of the `evaluate` binary can be given as `options`, e.g.
+    /// `["--tops", "0.9"]`.
+    #[new]
+    #[pyo3(signature = (
+        ngrams = "ngrams/eng_wiki_1m",
+        eval_parameters = "config/evaluation/sval.yml",
+        layout_config = "config/keyboard/sval.yml",
+        corpus = None,
+        text = None,
+        options = Vec::new(),
+    ))]
+    fn new(
+        ngrams: &str,
+        eval_parameters: &str,
+        layout_config: &str,
+        corpus: Option<&str>,
+        text: Option<&str>,
+        options: Vec<String>,
+    ) -> PyResult<Self> {
+        let mut args: Vec<String> = vec![
+            "svalboard_optimizer".to_string(),
+            "--ngrams".to_string(),
+            ngrams.to_string(),
+            "--eval-parameters".to_string(),
+            eval_parameters.to_string(),
+            "--layout-config".to_string(),
+            layout_config.to_string(),
+        ];
+        if let Some(corpus) = corpus {
+            args.extend(["--corpus".to_string(), corpus.to_string()]);
+        }
+        if let Some(text) = text {
+            args.extend(["--text".to_string(), text.to_string()]);
+        }
+        args.extend(options);
+
+        let options = <common::Options as clap::Parser>::try_parse_from(args)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let (layout_generator, evaluator) =
+            common::init(&options).map_err(|e| PyValueError::new_err(format!("{:#}", e)))?;
+
+        Ok(Self {
+            layout_generator,
+            evaluator,
+        })
+    }
+
+    /// Evaluates a layout and returns a dict with its total cost, the costs of the individual
+    /// metrics (`metrics`), and the full evaluation result (`details`).
+    fn evaluate(&self, py: Python<'_>, layout: &str) -> PyResult<PyObject> {
+        let (layout_str, result) = py.allow_threads(|| self.evaluate_str(layout))?;
+        result_to_py(py, &layout_str, &result)
+    }
+
+    /// Evaluates several layouts and returns one dict (see `evaluate`) per layout.
+    fn evaluate_many(&self, py: Python<'_>, layouts: Vec<String>) -> PyResult<Vec<PyObject>> {
+        layouts
+            .iter()
+            .map(|layout| self.evaluate(py, layout))
+            .collect()
+    }
+
+    /// The metric costs of several layouts as columns (dict of lists), one row per layout and
+    /// metric. Can be passed to `pandas.DataFrame` directly.
+    fn metric_costs(&self, py: Python<'_>, layouts: Vec<String>) -> PyResult<PyObject> {
+        let records: Vec<MetricRecord> = py.allow_threads(|| {
+            layouts
+                .iter()
+                .map(|layout| {
+                    self.evaluate_str(layout)
+                        .map(|(layout_str, result)| metric_records(&layout_str, &result))
+                })
+                .collect::<PyResult<Vec<_>>>()
+                .map(|records| records.into_iter().flatten().collect())
+        })?;
+
+        let columns = PyDict::new_bound(py);
+        columns.set_item(
+            "layout",
+            records.iter().map(|r| &r.layout).collect::<Vec<_>>(),
+        )?;
+        columns.set_item(
+            "metric_type",
+            records.iter().map(|r| &r.metric_type).collect::<Vec<_>>(),
+        )?;
+        columns.set_item(
+            "metric",
+            records.iter().map(|r| &r.metric).collect::<Vec<_>>(),
+        )?;
+        columns.set_item("cost", records.iter().map(|r| r.cost).collect::<Vec<_>>())?;
+        columns.set_item(
+            "weighted_cost",
+            records.iter().map(|r| r.weighted_cost).collect::<Vec<_>>(),
+        )?;
+
+        Ok(columns.into_py(py))
+    }
+
+    /// Same as `metric_costs`, but returns a `pandas.DataFrame` (requires pandas).
+    fn dataframe(&self, py: Python<'_>, layouts: Vec<String>) -> PyResult<PyObject> {
+        let columns = self.metric_costs(py, layouts)?;
+        let pandas = py.import_bound("pandas")?;
+
+        Ok(pandas.getattr("DataFrame")?.call1((columns,))?.into_py(py))
+    }
+
+    /// Optimizes a layout and returns the evaluation (see `evaluate`) of the best layout found.
+    /// Keys in `fix` stay in place. The `algorithm` setting of the optimization config selects
+    /// simulated annealing (`simulated_annealing`, the default) or ant colony optimization
+    /// (`ant_colony`).
+    #[pyo3(signature = (
+        layout,
+        fix = "",
+        start_with_layout = false,
+        optimization_parameters = "config/optimization/sa.yml",
+    ))]
+    fn optimize(
+        &self,
+        py: Python<'_>,
+        layout: &str,
+        fix: &str,
+        start_with_layout: bool,
+        optimization_parameters: &str,
+    ) -> PyResult<PyObject> {
+        let read_error = |e: anyhow::Error| {
+            PyIOError::new_err(format!(
+                "Could not read optimization parameters {}: {:?}",
+                optimization_parameters, e
+            ))
+        };
+        let layout_str: String = layout.chars().filter(|c| !c.is_whitespace()).collect();
+
+        let (layout_str, layout) = match read_algorithm(optimization_parameters)? {
+            Algorithm::SimulatedAnnealing => {
+                let mut params = sa_optimization::Parameters::from_yaml(optimization_parameters)
+                    .map_err(read_error)?;
+                params.apply_preset();
+                params.correct_init_temp();
+
+                let (layout_str, layout, _, _) = py.allow_threads(|| {
+                    sa_optimization::optimize(
+                        "Python",
+                        &params,
+                        &layout_str,
+                        fix,
+                        &self.layout_generator,
+                        start_with_layout,
+                        &self.evaluator,
+                        false,
+                        Some(Cache::new()),
+                        None,
+                        None,
+                    )
+                });
+                (layout_str, layout)
+            }
+            Algorithm::AntColony => {
+                let params = aco_optimization::Parameters::from_yaml(optimization_parameters)
+                    .map_err(read_error)?;
+
+                let (layout_str, layout, _) = py.allow_threads(|| {
+                    aco_optimization::optimize(
+                        &params,
+                        &layout_str,
+                        fix,
+                        self.layout_generator.as_ref(),
+                        start_with_layout,
+                        &self.evaluator,
+                        Some(Cache::new()),
+                        None,
+                    )
+                });
+                (layout_str, layout)
+            }
+        };
+        let result = self.evaluator.evaluate_layout(&layout);
+
+        result_to_py(py, &layout_str, &result)
+    }
+}
+
+/// Reads the unigrams, bigrams, and trigrams of an ngram directory or a text file and returns
+/// them as dicts mapping ngrams (strings) to their weights.
+#[pyfunction]
+fn load_ngrams(py: Python<'_>, path: &str) -> PyResult<PyObject> {
+    let io_err = |e: anyhow::Error| PyIOError::new_err(format!("Could not read {}: {:?}", path, e));
+
+    let (unigrams, bigrams, trigrams) = if Path::new(path).is_dir() {
+        let file = |name: &str| Path::new(path).join(name).to_string_lossy().to_string();
+        (
+            Unigrams::from_file(&file("1-grams.txt")).map_err(io_err)?,
+            Bigrams::from_file(&file("2-grams.txt")).map_err(io_err)?,
+            Trigrams::from_file(&file("3-grams.txt")).map_err(io_err)?,
+        )
+    } else {
+        let text = fs::read_to_string(path).map_err(|e| io_err(e.into()))?;
+        (
+            Unigrams::from_text(&text).map_err(io_err)?,
+            Bigrams::from_text(&text).map_err(io_err)?,
+            Trigrams::from_text(&text).map_err(io_err)?,
+        )
+    };
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item(
+        "unigrams",
+        unigrams
+            .grams
+            .iter()
+            .map(|(c, w)| (c.to_string(), *w))
+            .collect::<Vec<_>>()
+            .into_py_dict_bound(py),
+    )?;
+    dict.set_item(
+        "bigrams",
+        bigrams
+            .grams
+            .iter()
+            .map(|((c1, c2), w)| (format!("{}{}", c1, c2), *w))
+            .collect::<Vec<_>>()
+            .into_py_dict_bound(py),
+    )?;
+    dict.set_item(
+        "trigrams",
+        trigrams
+            .grams
+            .iter()
+            .map(|((c1, c2, c3), w)| (format!("{}{}{}", c1, c2, c3), *w))
+            .collect::<Vec<_>>()
+            .into_py_dict_bound(py),
+    )?;
+
+    Ok(dict.into_py(py))
+}
+
+#[pymodule]
+fn svalboard_optimizer(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Evaluator>()?;
+    m.add_function(wrap_pyfunction!(load_ngrams, m)?)?;
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! The `cluster_plot` module draws a layer of a layout in the terminal as clusters of keys, one
+//! for each finger (e.g. the 5-key crosses of the Svalboard) and thumb, in the board's geometry.
+//! Keys are colored by finger (with ANSI true colors), and the brightness of a key shows how
+//! often it is used.
+//!
+//! The keys of a cluster are arranged in a grid by their distinct horizontal and vertical
+//! positions. The finger clusters of each hand are drawn side by side, and the thumb clusters
+//! below them at the inner side of each hand.
+
+use crate::key::{Finger, Hand};
+use crate::keyboard::Keyboard;
+
+use colored::Colorize;
+use itertools::Itertools;
+
+/// Width of a key (in characters)
+const KEY_WIDTH: usize = 3;
+/// Space between the clusters of a hand
+const CLUSTER_GAP: usize = 1;
+/// Space between the hands
+const HAND_GAP: usize = 4;
+/// Background of unused keys
+const DARK: (u8, u8, u8) = (48, 48, 48);
+
+/// The label of a key in the plot
+#[derive(Clone, Debug)]
+pub struct KeyLabel {
+    pub symbol: char,
+    /// Fixed keys are drawn in regular, non-fixed keys in bold
+    pub is_fixed: bool,
+    /// Usage of the key relative to the most used key (in [0, 1]), if known
+    pub usage: Option<f64>,
+}
+
+fn finger_color(finger: Finger) -> (u8, u8, u8) {
+    match finger {
+        Finger::Pinky => (196, 92, 196),
+        Finger::Ring => (82, 132, 230),
+        Finger::Middle => (64, 176, 96),
+        Finger::Index => (232, 148, 48),
+        Finger::Thumb => (72, 180, 180),
+    }
+}
+
+/// The background color of a key; the brightness grows with the square root of the usage.
+fn background(finger: Finger, usage: f64) -> (u8, u8, u8) {
+    let t = 0.2 + 0.8 * usage.clamp(0.0, 1.0).sqrt();
+    let blend = |dark: u8, color: u8| (dark as f64 + t * (color as f64 - dark as f64)) as u8;
+    let color = finger_color(finger);
+
+    (
+        blend(DARK.0, color.0),
+        blend(DARK.1, color.1),
+        blend(DARK.2, color.2),
+    )
+}
+
+fn key_cell(finger: Finger, label: Option<&KeyLabel>) -> String {
+    let (symbol, usage) = match label {
+        Some(label) => (label.symbol, label.usage.unwrap_or(1.0)),
+        None => (' ', -1.0),
+    };
+    let (r, g, b) = match usage < 0.0 {
+        true => DARK,
+        false => background(finger, usage),
+    };
+    let luminance = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    let foreground = match luminance > 140.0 {
+        true => (0, 0, 0),
+        false => (255, 255, 255),
+    };
+
+    let cell = format!(" {} ", symbol)
+        .truecolor(foreground.0, foreground.1, foreground.2)
+        .on_truecolor(r, g, b);
+    match label {
+        Some(label) if !label.is_fixed => cell.bold().to_string(),
+        _ => cell.to_string(),
+    }
+}
+
+/// The keys of a finger (or thumb) arranged in a grid
+struct Cluster {
+    hand: Hand,
+    finger: Finger,
+    /// Mean horizontal position of the keys
+    center: f64,
+    /// Rows of key indices (`None` for gaps)
+    grid: Vec<Vec<Option<usize>>>,
+}
+
+impl Cluster {
+    fn width(&self) -> usize {
+        self.grid.first().map_or(0, |row| row.len()) * KEY_WIDTH
+    }
+
+    fn lines(&self, labels: &[Option<KeyLabel>]) -> Vec<String> {
+        self.grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|key_index| match key_index {
+                        Some(i) => key_cell(self.finger, labels[*i].as_ref()),
+                        None => " ".repeat(KEY_WIDTH),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Distinct values (with a small tolerance) in ascending order.
+fn distinct(values: impl Iterator<Item = f64>) -> Vec<f64> {
+    values
+        .sorted_by(|a, b| a.partial_cmp(b).unwrap())
+        .dedup_by(|a, b| (a - b).abs() < 1e-6)
+        .collect()
+}
+
+fn clusters(keyboard: &Keyboard) -> Vec<Cluster> {
+    keyboard
+        .keys
+        .iter()
+        .enumerate()
+        .into_group_map_by(|(_, k)| (k.hand, k.finger))
+        .into_iter()
+        .map(|((hand, finger), keys)| {
+            let xs = distinct(keys.iter().map(|(_, k)| k.position.0));
+            let ys = distinct(keys.iter().map(|(_, k)| k.position.1));
+            let index = |values: &[f64], v: f64| {
+                values
+                    .iter()
+                    .position(|value| (value - v).abs() < 1e-6)
+                    .unwrap()
+            };
+
+            let mut grid = vec![vec![None; xs.len()]; ys.len()];
+            for (i, k) in keys.iter() {
+                grid[index(&ys, k.position.1)][index(&xs, k.position.0)] = Some(*i);
+            }
+
+            Cluster {
+                hand,
+                finger,
+                center: keys.iter().map(|(_, k)| k.position.0).sum::<f64>() / keys.len() as f64,
+                grid,
+            }
+        })
+        .sorted_by(|c1, c2| c1.center.partial_cmp(&c2.center).unwrap())
+        .collect()
+}
+
+/// Place the clusters side by side (starting at the given columns) into lines.
+fn place(placed: &[(usize, &Cluster)], labels: &[Option<KeyLabel>]) -> Vec<String> {
+    let height = placed.iter().map(|(_, c)| c.grid.len()).max().unwrap_or(0);
+
+    let cluster_lines: Vec<Vec<String>> = placed.iter().map(|(_, c)| c.lines(labels)).collect();
+
+    (0..height)
+        .map(|row| {
+            let mut line = String::new();
+            let mut width = 0;
+            for ((column, cluster), lines) in placed.iter().zip(cluster_lines.iter()) {
+                line.push_str(&" ".repeat(column.saturating_sub(width)));
+                width = width.max(*column);
+                match lines.get(row) {
+                    Some(cluster_line) => line.push_str(cluster_line),
+                    None => line.push_str(&" ".repeat(cluster.width())),
+                }
+                width += cluster.width();
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// Plot the keyboard with the given labels (one for each key, `None` for keys without symbol).
+pub fn plot(keyboard: &Keyboard, labels: &[Option<KeyLabel>]) -> String {
+    let clusters = clusters(keyboard);
+    let (thumbs, fingers): (Vec<&Cluster>, Vec<&Cluster>) =
+        clusters.iter().partition(|c| c.finger == Finger::Thumb);
+
+    // finger clusters of both hands side by side
+    let mut placed = Vec::new();
+    let mut column = 0;
+    let mut right_start = None;
+    for (i, cluster) in fingers.iter().enumerate() {
+        if i > 0 {
+            column += match cluster.hand != fingers[i - 1].hand {
+                true => HAND_GAP,
+                false => CLUSTER_GAP,
+            };
+        }
+        if cluster.hand == Hand::Right && right_start.is_none() {
+            right_start = Some(column);
+        }
+        placed.push((column, *cluster));
+        column += cluster.width();
+    }
+    let left_end = fingers
+        .iter()
+        .zip(placed.iter())
+        .filter(|(c, _)| c.hand == Hand::Left)
+        .map(|(c, (column, _))| column + c.width())
+        .max()
+        .unwrap_or(0);
+
+    // thumb clusters below the inner clusters of each hand
+    let mut placed_thumbs = Vec::new();
+    let mut column = 0;
+    for cluster in thumbs.iter() {
+        let inner_column = match cluster.hand {
+            Hand::Left => left_end.saturating_sub(cluster.width()),
+            Hand::Right => right_start.unwrap_or(left_end + HAND_GAP),
+        };
+        column = inner_column.max(column);
+        placed_thumbs.push((column, *cluster));
+        column += cluster.width() + HAND_GAP;
+    }
+
+    let mut lines = place(&placed, labels);
+    if !placed_thumbs.is_empty() {
+        lines.push(String::new());
+        lines.extend(place(&placed_thumbs, labels));
+    }
+
+    lines.join("\n")
+}
@@ -1,10 +1,15 @@
 use crate::keyboard::KeyboardYAML;
+use crate::layout::ModifierLocation;
 use crate::neo_layout_generator::BaseLayoutYAML;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use serde::Deserialize;
+use serde_yaml::Value;
 use std::error::Error;
-use std::{fs::File, str::FromStr};
+use std::{fs, path::Path, str::FromStr};
+
+/// Key of a layout config naming the config it is based on (relative to its own directory).
+const EXTENDS_KEY: &str = "extends";
 
 #[derive(Deserialize, Debug)]
 pub struct LayoutConfig {
@@ -13,10 +18,14 @@ pub struct LayoutConfig {
 }
 
 impl LayoutConfig {
+    /// Read a layout config. A config may name the config it is based on with
+    /// `extends: <filename>` and only contain the values it changes (see [`merge`]).
     pub fn from_yaml(filename: &str) -> Result<Self> {
-        let f = File::open(filename)?;
-        let cfg: LayoutConfig = serde_yaml::from_reader(f)?;
+        let value = read_extended(filename, &mut Vec::new())?;
+        let mut cfg: LayoutConfig = serde_yaml::from_value(value)?;
+        cfg.keyboard.infer_missing_geometry();
         cfg.validate()?;
+        cfg.block_positions();
 
         Ok(cfg)
     }
@@ -24,16 +33,146 @@ impl LayoutConfig {
     pub fn validate(&self) -> Result<()> {
         self.keyboard.validate()?;
         self.base_layout.validate()?;
+
+        // Make sure that the base layout has symbols for each key of the keyboard.
+        let n_keys = self.keyboard.n_keys();
+        let n_layout_keys: usize = self.base_layout.keys.iter().map(Vec::len).sum();
+        if n_layout_keys != n_keys {
+            bail!(
+                "Invalid layout config: Not the same number of `keys` in the base layout ({}) as keys on the keyboard ({})",
+                n_layout_keys,
+                n_keys
+            );
+        }
+
+        // Make sure that no modifier is located on a blocked key.
+        let blocked = self.keyboard.blocked_positions();
+        let blocked_modifier = self
+            .base_layout
+            .modifiers
+            .iter()
+            .flat_map(|mods_per_hand| mods_per_hand.values())
+            .flat_map(|mods| mods.iter())
+            .find_map(|location| match location {
+                ModifierLocation::Position(pos) if blocked.contains(pos) => Some(*pos),
+                _ => None,
+            });
+        if let Some(pos) = blocked_modifier {
+            bail!(
+                "Invalid layout config: Modifier on blocked position {:?}",
+                pos
+            );
+        }
+
         Ok(())
     }
+
+    /// Remove the symbols from the keys at the keyboard's `blocked_positions` and fix them, so
+    /// that no symbol is placed on them. They are still part of the keyboard (e.g. for plots).
+    fn block_positions(&mut self) {
+        let blocked = self.keyboard.blocked_keys();
+        self.base_layout.block_keys(|i| blocked[i]);
+    }
 }
 
 impl FromStr for LayoutConfig {
     type Err = Box<dyn Error>;
     fn from_str(layout_config_str: &str) -> Result<Self, Self::Err> {
-        let cfg: LayoutConfig = serde_yaml::from_str(layout_config_str)?;
+        let mut cfg: LayoutConfig = serde_yaml::from_str(layout_config_str)?;
+        cfg.keyboard.infer_missing_geometry();
         cfg.validate()?;
+        cfg.block_positions();
 
         Ok(cfg)
     }
 }
+
+/// Read a layout config file merged on top of the config it extends (if any).
+fn read_extended(filename: &str, chain: &mut Vec<String>) -> Result<Value> {
+    if chain.iter().any(|f| f == filename) {
+        bail!(
+            "Cyclic '{}' in layout config: {} -> {}",
+            EXTENDS_KEY,
+            chain.join(" -> "),
+            filename
+        );
+    }
+    chain.push(filename.to_string());
+
+    let content = fs::read_to_string(filename)
+        .with_context(|| format!("Could not read layout config {}", filename))?;
+    let mut value: Value = serde_yaml::from_str(&content)
+        .with_context(|| format!("Could not parse layout config {}", filename))?;
+
+    if let Some(extends) = value.as_mapping_mut().and_then(|m| m.remove(EXTENDS_KEY)) {
+        let extends = extends
+            .as_str()
+            .with_context(|| format!("'{}' in {} is not a filename", EXTENDS_KEY, filename))?;
+        let extends = Path::new(filename)
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(extends);
+        let mut base = read_extended(&extends.to_string_lossy(), chain)?;
+        merge(&mut base, value);
+        value = base;
+    }
+    chain.pop();
+
+    Ok(value)
+}
+
+/// Merge `overlay` into `base`. Mappings are merged key by key, all other values (including
+/// lists) replace the previous value.
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{Hand, MatrixPosition};
+
+    fn config(filename: &str) -> LayoutConfig {
+        let filename = format!(
+            "{}/../config/keyboard/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            filename
+        );
+        LayoutConfig::from_yaml(&filename).unwrap()
+    }
+
+    #[test]
+    fn an_extending_config_only_changes_its_values() {
+        let base = config("sval.yml");
+        let placed_shift = config("sval_placed_shift.yml");
+
+        let shift = |cfg: &LayoutConfig| -> Vec<ModifierLocation> {
+            cfg.base_layout.modifiers[0][&Hand::Left]
+                .iter()
+                .cloned()
+                .collect()
+        };
+        assert_eq!(
+            shift(&base),
+            vec![ModifierLocation::Position(MatrixPosition(11, 5))]
+        );
+        assert_eq!(shift(&placed_shift), vec![ModifierLocation::Symbol('⇧')]);
+        assert_eq!(placed_shift.base_layout.keys, base.base_layout.keys);
+        assert_eq!(
+            placed_shift.base_layout.fixed_keys,
+            base.base_layout.fixed_keys
+        );
+    }
+}
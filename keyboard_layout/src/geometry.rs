@@ -0,0 +1,523 @@
+//! This module provides heuristics for inferring the hands, fingers, and matrix positions of a
+//! keyboard's keys from their physical positions alone, e.g. for keyboards imported from KLE.
+//!
+//! Keys are grouped into rows by their vertical position and into columns by their horizontal
+//! position (in units of the key pitch). Keys left of the split position belong to the left hand.
+//! The bottom rows are assigned to the thumbs, the remaining keys are assigned to the index, middle,
+//! ring, and pinky finger by counting columns from the inner edge of each hand.
+//!
+//! The remaining properties of the keys are derived from these:
+//! - resting positions: the key of each finger in the home row (the finger row with the most
+//!   keys) closest to the middle finger, and the upper thumb key closest to the index finger
+//! - directions: keys in the row of the finger's resting position are the `Center` key or lie
+//!   `In`(wards) or `Out`(wards) of it, keys in rows above are `North`, and keys in rows below are
+//!   `South`
+//! - key costs: growing with the distance from the finger's resting position
+//! - symmetries: keys at mirrored positions (about the split position) share an index
+//! - plot templates: the keys in their matrix rows and columns
+
+use crate::key::{Direction, Finger, Hand, MatrixPosition, Position};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+
+/// Configurable heuristics for the inference of the keys' hands, fingers, and matrix positions.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(default)]
+pub struct GeometryInferenceYAML {
+    /// Horizontal position separating both hands (defaults to the center of all keys).
+    pub split_position: Option<f64>,
+    /// Distance of neighboring keys (defaults to the smallest distance between any two keys).
+    pub key_pitch: Option<f64>,
+    /// Maximal vertical distance of keys in the same row (defaults to half the key pitch).
+    pub row_tolerance: Option<f64>,
+    /// Number of bottom rows that are typed with the thumbs.
+    pub thumb_rows: usize,
+    /// Number of columns (counted from the inner edge of each hand) typed with the index, middle,
+    /// and ring finger. All remaining outer columns are typed with the pinky.
+    pub finger_columns: [u8; 3],
+    /// Cost of the keys at the fingers' resting positions.
+    pub home_key_cost: f64,
+    /// Additional cost of a key per key pitch of distance from its finger's resting position.
+    pub cost_per_key_pitch: f64,
+}
+
+impl Default for GeometryInferenceYAML {
+    fn default() -> Self {
+        Self {
+            split_position: None,
+            key_pitch: None,
+            row_tolerance: None,
+            thumb_rows: 1,
+            finger_columns: [2, 1, 1],
+            home_key_cost: 3.0,
+            cost_per_key_pitch: 6.0,
+        }
+    }
+}
+
+/// Hands, fingers, and matrix positions inferred from the keys' positions. Each of them is nested
+/// in the same way as the given positions.
+pub struct InferredGeometry {
+    pub hands: Vec<Vec<Hand>>,
+    pub fingers: Vec<Vec<Finger>>,
+    pub matrix_positions: Vec<Vec<MatrixPosition>>,
+    /// Distance of neighboring keys
+    pub key_pitch: f64,
+    /// Horizontal position separating both hands
+    pub split_position: f64,
+}
+
+impl InferredGeometry {
+    pub fn from_positions(positions: &[Vec<Position>], params: &GeometryInferenceYAML) -> Self {
+        let flat_positions: Vec<Position> = positions.concat();
+
+        let key_pitch = params.key_pitch.unwrap_or_else(|| {
+            flat_positions
+                .iter()
+                .enumerate()
+                .flat_map(|(i, p1)| {
+                    flat_positions[i + 1..]
+                        .iter()
+                        .map(move |p2| p1.distance(p2))
+                })
+                .filter(|d| *d > 0.0)
+                .fold(f64::INFINITY, f64::min)
+        });
+        let key_pitch = if key_pitch.is_finite() {
+            key_pitch
+        } else {
+            1.0
+        };
+        let row_tolerance = params.row_tolerance.unwrap_or(0.5 * key_pitch);
+
+        let min_x = flat_positions
+            .iter()
+            .map(|p| p.0)
+            .fold(f64::INFINITY, f64::min);
+        let max_x = flat_positions
+            .iter()
+            .map(|p| p.0)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let split_position = params.split_position.unwrap_or(0.5 * (min_x + max_x));
+
+        // rows: cluster the sorted vertical positions, a new row starts at each larger gap
+        let mut ys: Vec<f64> = flat_positions.iter().map(|p| p.1).collect();
+        ys.sort_by(|y1, y2| y1.partial_cmp(y2).unwrap());
+        let mut row_starts: Vec<f64> = Vec::new();
+        let mut last_y = f64::NEG_INFINITY;
+        for y in ys {
+            if y - last_y > row_tolerance {
+                row_starts.push(y);
+            }
+            last_y = y;
+        }
+        let row = |p: &Position| row_starts.iter().rposition(|y| *y <= p.1).unwrap_or(0);
+        let n_rows = row_starts.len();
+
+        let column = |p: &Position| ((p.0 - min_x) / key_pitch).round() as u8;
+        let hand = |p: &Position| {
+            if p.0 < split_position {
+                Hand::Left
+            } else {
+                Hand::Right
+            }
+        };
+        let is_thumb = |p: &Position| row(p) + params.thumb_rows >= n_rows;
+
+        // inner edges of both hands (without thumb rows)
+        let finger_keys = flat_positions.iter().filter(|p| !is_thumb(p));
+        let left_inner = finger_keys
+            .clone()
+            .filter(|p| hand(p) == Hand::Left)
+            .map(column)
+            .max()
+            .unwrap_or_default();
+        let right_inner = finger_keys
+            .filter(|p| hand(p) == Hand::Right)
+            .map(column)
+            .min()
+            .unwrap_or_default();
+
+        let [index_columns, middle_columns, ring_columns] = params.finger_columns;
+        let finger = |p: &Position| {
+            if is_thumb(p) {
+                return Finger::Thumb;
+            }
+
+            let distance = match hand(p) {
+                Hand::Left => left_inner.saturating_sub(column(p)),
+                Hand::Right => column(p).saturating_sub(right_inner),
+            };
+
+            if distance < index_columns {
+                Finger::Index
+            } else if distance < index_columns + middle_columns {
+                Finger::Middle
+            } else if distance < index_columns + middle_columns + ring_columns {
+                Finger::Ring
+            } else {
+                Finger::Pinky
+            }
+        };
+
+        Self {
+            hands: positions
+                .iter()
+                .map(|row_positions| row_positions.iter().map(hand).collect())
+                .collect(),
+            fingers: positions
+                .iter()
+                .map(|row_positions| row_positions.iter().map(finger).collect())
+                .collect(),
+            matrix_positions: positions
+                .iter()
+                .map(|row_positions| {
+                    row_positions
+                        .iter()
+                        .map(|p| MatrixPosition(column(p), row(p) as u8))
+                        .collect()
+                })
+                .collect(),
+            key_pitch,
+            split_position,
+        }
+    }
+}
+
+/// Nest the flat values like the rows of the keyboard.
+pub fn nest<T: Clone, R>(flat: &[T], rows: &[Vec<R>]) -> Vec<Vec<T>> {
+    let mut values = flat.iter();
+    rows.iter()
+        .map(|row| values.by_ref().take(row.len()).cloned().collect())
+        .collect()
+}
+
+/// Infer the resting positions of the fingers (see module documentation).
+pub fn infer_resting_positions(
+    positions: &[Position],
+    hands: &[Hand],
+    fingers: &[Finger],
+    matrix_positions: &[MatrixPosition],
+) -> AHashMap<Hand, AHashMap<Finger, Position>> {
+    let finger_keys = |hand: Hand, finger: Finger| {
+        (0..positions.len()).filter(move |i| hands[*i] == hand && fingers[*i] == finger)
+    };
+    let mean_x = |keys: &[usize]| -> Option<f64> {
+        (!keys.is_empty())
+            .then(|| keys.iter().map(|i| positions[*i].0).sum::<f64>() / keys.len() as f64)
+    };
+
+    // the finger row with the most keys (ties are broken towards the middle of the finger rows)
+    let finger_rows: Vec<u8> = (0..positions.len())
+        .filter(|i| fingers[*i] != Finger::Thumb)
+        .map(|i| matrix_positions[i].1)
+        .collect();
+    let (min_row, max_row) = (
+        finger_rows.iter().min().cloned().unwrap_or_default() as f64,
+        finger_rows.iter().max().cloned().unwrap_or_default() as f64,
+    );
+    let home_row = finger_rows
+        .iter()
+        .cloned()
+        .max_by(|r1, r2| {
+            let count = |r: &u8| finger_rows.iter().filter(|r2| *r2 == r).count();
+            let centrality = |r: &u8| -(*r as f64 - 0.5 * (min_row + max_row)).abs();
+            count(r1)
+                .cmp(&count(r2))
+                .then(centrality(r1).partial_cmp(&centrality(r2)).unwrap())
+        })
+        .unwrap_or_default();
+
+    let mut resting_positions: AHashMap<Hand, AHashMap<Finger, Position>> = AHashMap::default();
+    for hand in [Hand::Left, Hand::Right] {
+        let home_keys = |finger: Finger| -> Vec<usize> {
+            finger_keys(hand, finger)
+                .filter(|i| matrix_positions[*i].1 == home_row)
+                .collect()
+        };
+        let middle_x = mean_x(&home_keys(Finger::Middle));
+
+        let mut finger_positions = AHashMap::default();
+        for finger in [Finger::Index, Finger::Middle, Finger::Ring, Finger::Pinky] {
+            let keys: Vec<usize> = finger_keys(hand, finger).collect();
+            let target_x = match finger {
+                Finger::Middle => mean_x(&home_keys(finger)),
+                _ => middle_x,
+            }
+            .or_else(|| mean_x(&keys))
+            .unwrap_or_default();
+            let resting_key = keys.into_iter().min_by(|i1, i2| {
+                let row_distance =
+                    |i: &usize| (matrix_positions[*i].1 as i32 - home_row as i32).abs();
+                let x_distance = |i: &usize| (positions[*i].0 - target_x).abs();
+                row_distance(i1)
+                    .cmp(&row_distance(i2))
+                    .then(x_distance(i1).partial_cmp(&x_distance(i2)).unwrap())
+            });
+            if let Some(resting_key) = resting_key {
+                finger_positions.insert(finger, positions[resting_key]);
+            }
+        }
+
+        let index_x = finger_positions.get(&Finger::Index).map(|p| p.0);
+        let resting_thumb = finger_keys(hand, Finger::Thumb).min_by(|i1, i2| {
+            let x_distance =
+                |i: &usize| (positions[*i].0 - index_x.unwrap_or(positions[*i].0)).abs();
+            matrix_positions[*i1]
+                .1
+                .cmp(&matrix_positions[*i2].1)
+                .then(x_distance(i1).partial_cmp(&x_distance(i2)).unwrap())
+        });
+        if let Some(resting_thumb) = resting_thumb {
+            finger_positions.insert(Finger::Thumb, positions[resting_thumb]);
+        }
+
+        resting_positions.insert(hand, finger_positions);
+    }
+
+    resting_positions
+}
+
+/// The key closest to the resting position of each finger.
+fn home_keys(
+    positions: &[Position],
+    hands: &[Hand],
+    fingers: &[Finger],
+    resting_positions: &AHashMap<Hand, AHashMap<Finger, Position>>,
+) -> AHashMap<(Hand, Finger), usize> {
+    let mut home_keys: AHashMap<(Hand, Finger), usize> = AHashMap::default();
+    for (hand, finger_positions) in resting_positions.iter() {
+        for (finger, resting) in finger_positions.iter() {
+            let home_key = (0..positions.len())
+                .filter(|i| hands[*i] == *hand && fingers[*i] == *finger)
+                .min_by(|i1, i2| {
+                    let d1 = positions[*i1].distance(resting);
+                    let d2 = positions[*i2].distance(resting);
+                    d1.partial_cmp(&d2).unwrap()
+                });
+            if let Some(home_key) = home_key {
+                home_keys.insert((*hand, *finger), home_key);
+            }
+        }
+    }
+
+    home_keys
+}
+
+/// Infer the directions of the keys from the rows of the keyboard (see module documentation).
+/// Everything that can not be derived unambiguously is reported in `notes`.
+pub fn infer_directions(
+    positions: &[Position],
+    hands: &[Hand],
+    fingers: &[Finger],
+    matrix_positions: &[MatrixPosition],
+    resting_positions: &AHashMap<Hand, AHashMap<Finger, Position>>,
+    notes: &mut Vec<String>,
+) -> Vec<Direction> {
+    let home_keys = home_keys(positions, hands, fingers, resting_positions);
+
+    let mut guessed_thumbs = Vec::new();
+    let directions = (0..positions.len())
+        .map(|i| {
+            let (hand, finger) = (hands[i], fingers[i]);
+            let home_key = match home_keys.get(&(hand, finger)) {
+                Some(home_key) => *home_key,
+                None => {
+                    notes.push(format!(
+                        "Key {} ({:?} {:?}): no resting position of the finger, set to Center",
+                        i + 1,
+                        hand,
+                        finger
+                    ));
+                    return Direction::Center;
+                }
+            };
+
+            if finger == Finger::Thumb {
+                if i == home_key {
+                    return Direction::Pad;
+                }
+                guessed_thumbs.push((i + 1).to_string());
+                return Direction::Down;
+            }
+
+            let home_row = matrix_positions[home_key].1 as i32;
+            let row = matrix_positions[i].1 as i32;
+            if (row - home_row).abs() > 1 {
+                notes.push(format!(
+                    "Key {} ({:?} {:?}) is {} rows away from the home row, set to {}",
+                    i + 1,
+                    hand,
+                    finger,
+                    (row - home_row).abs(),
+                    if row < home_row { "North" } else { "South" }
+                ));
+            }
+
+            let dx = positions[i].0 - positions[home_key].0;
+            match row.cmp(&home_row) {
+                std::cmp::Ordering::Less => Direction::North,
+                std::cmp::Ordering::Greater => Direction::South,
+                std::cmp::Ordering::Equal if dx.abs() < 1e-6 => Direction::Center,
+                // inwards is towards the other hand
+                std::cmp::Ordering::Equal if (dx > 0.0) == (hand == Hand::Left) => Direction::In,
+                std::cmp::Ordering::Equal => Direction::Out,
+            }
+        })
+        .collect();
+
+    if !guessed_thumbs.is_empty() {
+        notes.push(format!(
+            "Thumb keys {} are not at a thumb's resting position and were set to Down, \
+             assign Svalboard thumb directions (Pad, DoubleDown, Nail, Up, Down, Knuckle) manually",
+            guessed_thumbs.join(", ")
+        ));
+    }
+
+    directions
+}
+
+/// Infer the costs of the keys from their distance to their finger's resting position.
+pub fn infer_key_costs(
+    positions: &[Position],
+    hands: &[Hand],
+    fingers: &[Finger],
+    resting_positions: &AHashMap<Hand, AHashMap<Finger, Position>>,
+    key_pitch: f64,
+    params: &GeometryInferenceYAML,
+) -> Vec<f64> {
+    (0..positions.len())
+        .map(|i| {
+            let distance = resting_positions
+                .get(&hands[i])
+                .and_then(|f| f.get(&fingers[i]))
+                .map(|resting| positions[i].distance(resting) / key_pitch)
+                .unwrap_or_default();
+            (params.home_key_cost + params.cost_per_key_pitch * distance).round()
+        })
+        .collect()
+}
+
+/// Infer the symmetry indices of the keys: keys of opposite hands at mirrored positions (about the
+/// split position) share an index, all other keys get an index of their own.
+pub fn infer_symmetries(
+    positions: &[Position],
+    hands: &[Hand],
+    split_position: f64,
+    key_pitch: f64,
+) -> Vec<u8> {
+    let mut symmetries: Vec<Option<u8>> = vec![None; positions.len()];
+    let mut next_index = 1;
+    for i in 0..positions.len() {
+        if symmetries[i].is_some() {
+            continue;
+        }
+        symmetries[i] = Some(next_index);
+
+        let mirrored = Position(2.0 * split_position - positions[i].0, positions[i].1);
+        let partner = (0..positions.len())
+            .filter(|j| symmetries[*j].is_none() && hands[*j] != hands[i])
+            .map(|j| (j, positions[j].distance(&mirrored)))
+            .filter(|(_, d)| *d < 0.5 * key_pitch)
+            .min_by(|(_, d1), (_, d2)| d1.partial_cmp(d2).unwrap());
+        if let Some((j, _)) = partner {
+            symmetries[j] = Some(next_index);
+        }
+        next_index = next_index.saturating_add(1);
+    }
+
+    symmetries
+        .into_iter()
+        .map(|s| s.unwrap_or_default())
+        .collect()
+}
+
+/// Plot templates (regular and compact) showing the keys in their matrix rows and columns.
+pub fn infer_plot_templates(matrix_positions: &[MatrixPosition]) -> (String, String) {
+    let max_row = matrix_positions
+        .iter()
+        .map(|p| p.1)
+        .max()
+        .unwrap_or_default();
+    let max_column = matrix_positions
+        .iter()
+        .map(|p| p.0)
+        .max()
+        .unwrap_or_default();
+    let lines: Vec<String> = (0..=max_row)
+        .map(|row| {
+            let cells: Vec<String> = (0..=max_column)
+                .map(|column| {
+                    match matrix_positions
+                        .iter()
+                        .position(|p| *p == MatrixPosition(column, row))
+                    {
+                        Some(i) => format!("{{{{{}}}}}", i),
+                        None => " ".to_string(),
+                    }
+                })
+                .collect();
+            cells.join(" ").trim_end().to_string()
+        })
+        .collect();
+    let compact: String = (0..matrix_positions.len())
+        .map(|i| format!("{{{{{}}}}}", i))
+        .collect();
+
+    (format!("{}\n", lines.join("\n")), compact)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::LayoutConfig;
+    use crate::key::{Direction, Finger, Hand, MatrixPosition};
+    use crate::keyboard::Keyboard;
+
+    fn positions_only_keyboard() -> Keyboard {
+        let filename = format!(
+            "{}/../config/keyboard/split_positions_only.yml",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        Keyboard::from_yaml_object(LayoutConfig::from_yaml(&filename).unwrap().keyboard)
+    }
+
+    #[test]
+    fn infers_all_key_properties_from_positions() {
+        let keyboard = positions_only_keyboard();
+        assert_eq!(keyboard.keys.len(), 36);
+
+        // "a" in the home row: left pinky at rest
+        let a = &keyboard.keys[10];
+        assert_eq!((a.hand, a.finger), (Hand::Left, Finger::Pinky));
+        assert_eq!(a.matrix_position, MatrixPosition(0, 1));
+        assert_eq!(a.direction, Direction::Center);
+        // "t" (next to the middle finger) is the left index finger's resting key, "g" lies inwards
+        let (t, g) = (&keyboard.keys[13], &keyboard.keys[14]);
+        assert_eq!((t.finger, t.direction), (Finger::Index, Direction::Center));
+        assert_eq!((g.finger, g.direction), (Finger::Index, Direction::In));
+        assert!(g.cost > t.cost);
+        // "p" above "t"
+        assert_eq!(keyboard.keys[3].direction, Direction::North);
+        // the bottom row belongs to the thumbs
+        assert!(keyboard.keys[30..]
+            .iter()
+            .all(|k| k.finger == Finger::Thumb));
+        // "a" and "o" are mirrored
+        assert_eq!(a.symmetry_index, keyboard.keys[19].symmetry_index);
+        assert_ne!(a.symmetry_index, keyboard.keys[18].symmetry_index);
+    }
+
+    #[test]
+    fn generates_plot_templates() {
+        let keyboard = positions_only_keyboard();
+        let labels: Vec<String> = (0..36)
+            .map(|i| ((b'a' + (i % 26) as u8) as char).to_string())
+            .collect();
+
+        let plot = keyboard.plot(&labels);
+        assert_eq!(plot.lines().count(), 4);
+        assert!(plot.lines().next().unwrap().starts_with("a b c d e"));
+        assert_eq!(keyboard.plot_compact(&labels).trim(), labels.concat());
+    }
+}
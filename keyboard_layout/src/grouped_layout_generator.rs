@@ -1,8 +1,9 @@
 use crate::key::Hand;
 use crate::keyboard::Keyboard;
 use crate::layout::{LayerModifierLocations, Layout};
-use crate::layout_generator::LayoutGenerator;
+use crate::layout_generator::{layout_string_issues, LayoutGenerator};
 use crate::neo_layout_generator::BaseLayoutYAML;
+use crate::symbols;
 
 use ahash::{AHashMap, AHashSet};
 use anyhow::Result;
@@ -13,8 +14,8 @@ use thiserror::Error;
 pub enum LayoutError {
     #[error("Invalid keyboard layout: Unsupported characters in provided layout (not in a level of `base_layout` corresponding to a multiple of `grouped_layers` and `fixed_keys` with value `false`): '{0}'")]
     UnsupportedChars(String),
-    #[error("Invalid keyboard layout: Number of characters in provided layout needs to be a multiple of the number of non-fixed keys. Provided {0} keys, {1} keys are non-fixed.")]
-    WrongKeyNumber(usize, usize),
+    #[error("Invalid keyboard layout '{0}':\n  - {issues}", issues = .1.join("\n  - "))]
+    InvalidLayoutString(String, Vec<String>),
 }
 
 /// Provides functionalities for generating Neo layout variants from given string representations
@@ -36,7 +37,12 @@ impl GroupedLayoutGenerator {
             .keys
             .iter()
             .flatten()
-            .map(|layers| layers.iter().filter_map(|l| l.chars().next()).collect())
+            .map(|layers| {
+                layers
+                    .iter()
+                    .filter_map(|l| symbols::config_symbol(l))
+                    .collect()
+            })
             .collect();
         let fixed_keys: Vec<bool> = base.fixed_keys.iter().flatten().cloned().collect();
 
@@ -87,7 +93,7 @@ impl GroupedLayoutGenerator {
     /// Does not check whether the given string is valid (sufficient, correct and unique characters).
     /// This is useful for plotting unfinished or invalid layouts.
     pub fn generate_unchecked(&self, layout_keys: &str) -> Result<Layout> {
-        let chars: Vec<char> = layout_keys.chars().collect();
+        let chars: Vec<char> = symbols::layout_symbols(layout_keys)?;
 
         // assemble a Vec<Vec<char>> representation of the layer for the given layout string
         let n_fixed = self.fixed_keys.iter().filter(|fixed| !**fixed).count();
@@ -157,29 +163,16 @@ impl GroupedLayoutGenerator {
 impl LayoutGenerator for GroupedLayoutGenerator {
     /// Generate a Neo variant [`Layout`] from a given string representation of its base layer (only non-fixed keys)
     fn generate(&self, layout_keys: &str) -> Result<Layout> {
-        let chars: Vec<char> = layout_keys.chars().collect();
+        let chars: Vec<char> = symbols::layout_symbols(layout_keys)?;
 
         let n_fixed = self.fixed_keys.iter().filter(|fixed| !**fixed).count();
-        if chars.len() % n_fixed != 0 {
-            return Err(LayoutError::WrongKeyNumber(chars.len(), n_fixed).into());
-        }
-
-        let char_set: AHashSet<char> = AHashSet::from_iter(chars);
         let layout_set: AHashSet<char> =
             AHashSet::from_iter(self.permutable_key_map.keys().cloned());
 
-        let mut unsupported_chars: Vec<char> = char_set.difference(&layout_set).cloned().collect();
-        // let mut missing_chars: Vec<char> = layout_set.difference(&char_set).cloned().collect();
-
-        unsupported_chars.sort_unstable();
-        // missing_chars.sort_unstable();
-
-        if !unsupported_chars.is_empty() {
-            return Err(LayoutError::UnsupportedChars(unsupported_chars.iter().collect()).into());
+        let issues = layout_string_issues(&chars, &layout_set, &AHashSet::default(), n_fixed, true);
+        if !issues.is_empty() {
+            return Err(LayoutError::InvalidLayoutString(layout_keys.to_string(), issues).into());
         }
-        // if !missing_chars.is_empty() {
-        //     return Err(LayoutError::MissingChars(missing_chars.iter().collect()).into());
-        // }
 
         self.generate_unchecked(layout_keys)
     }
@@ -1,7 +1,7 @@
 //! This module provides structs for representing physical properties of keys in a keyboard
 
 use ahash::AHashMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::slice;
 
@@ -39,7 +39,7 @@ impl Default for Position {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, Debug)]
 #[repr(u8)]
 pub enum Finger {
     Thumb,  // 0
@@ -69,7 +69,7 @@ impl Finger {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, Debug)]
 #[repr(u8)]
 pub enum Hand {
     Left,  // 0
@@ -345,4 +345,7 @@ pub struct Key {
 
     /// How strongly does the hand need to move away from the home row (start position) horizontally and vertically
     pub unbalancing: Position,
+
+    /// Whether no symbol may be placed on the key (e.g. a broken switch)
+    pub blocked: bool,
 }
@@ -1,5 +1,6 @@
 //! This module provides a struct representing a keyboard.
 
+use crate::geometry::{self, GeometryInferenceYAML, InferredGeometry};
 use crate::key::{Finger, Hand, HandFingerMap, Key, MatrixPosition, Position, Direction};
 
 use ahash::{AHashMap, AHashSet};
@@ -16,6 +17,19 @@ pub enum KeyboardError {
     DuplicateMatrixPositions,
     #[error("Invalid keyboard: Duplicate `positions`.")]
     DuplicatePositions,
+    #[error("Invalid keyboard: `finger_overrides` refers to unknown matrix position {0:?}.")]
+    UnknownOverridePosition(MatrixPosition),
+    #[error("Invalid keyboard: `blocked_positions` refers to unknown matrix position {0:?}.")]
+    UnknownBlockedPosition(MatrixPosition),
+}
+
+/// Overrides the finger (and optionally hand) that types the key at a matrix position,
+/// e.g. for hitting a lower index key with the middle finger ("alt fingering").
+#[derive(Deserialize, Debug)]
+pub struct FingerOverrideYAML {
+    pub matrix_position: MatrixPosition,
+    pub finger: Finger,
+    pub hand: Option<Hand>,
 }
 
 /// The index of a [`Key`] in the `keys` vec of a [`Keyboard`]
@@ -36,17 +50,48 @@ pub struct Keyboard {
 /// Corresponds to (parts of) a YAML configuration file.
 #[derive(Deserialize, Debug)]
 pub struct KeyboardYAML {
+    /// Inferred from `positions` if omitted (see `geometry_inference`)
+    #[serde(default)]
     matrix_positions: Vec<Vec<MatrixPosition>>,
     positions: Vec<Vec<Position>>,
+    /// Inferred from `positions` if omitted (see `geometry_inference`)
+    #[serde(default)]
     hands: Vec<Vec<Hand>>,
+    /// Inferred from `positions` if omitted (see `geometry_inference`)
+    #[serde(default)]
     fingers: Vec<Vec<Finger>>,
+    /// Inferred from the finger resting positions if omitted (see `geometry_inference`)
+    #[serde(default)]
     directions: Vec<Vec<Direction>>,
+    /// Inferred from the distances to the finger resting positions if omitted (see
+    /// `geometry_inference`)
+    #[serde(default)]
     key_costs: Vec<Vec<f64>>,
+    /// Inferred from mirrored `positions` if omitted (see `geometry_inference`)
+    #[serde(default)]
     symmetries: Vec<Vec<u8>>,
+    /// No unbalancing if omitted
+    #[serde(default)]
     unbalancing_positions: Vec<Vec<Position>>,
+    /// Inferred from `positions` if omitted (see `geometry_inference`)
+    #[serde(default)]
     finger_resting_positions: AHashMap<Hand, AHashMap<Finger, Position>>,
+    /// Generated from `matrix_positions` if omitted
+    #[serde(default)]
     plot_template: String,
+    /// Generated from `matrix_positions` if omitted
+    #[serde(default)]
     plot_template_short: String,
+    /// Heuristics for inferring omitted properties of the keys
+    #[serde(default)]
+    geometry_inference: GeometryInferenceYAML,
+    /// Keys that are typed with another finger than given in `fingers`
+    #[serde(default)]
+    finger_overrides: Vec<FingerOverrideYAML>,
+    /// Keys that no symbol is placed on (e.g. a broken switch or a key reserved for hardware
+    /// functions)
+    #[serde(default)]
+    blocked_positions: Vec<MatrixPosition>,
 }
 
 /// Takes a slice of some iterable and checks whether that iterable contains
@@ -63,6 +108,99 @@ fn contains_duplicates<T: PartialEq>(v: &[T]) -> bool {
 }
 
 impl KeyboardYAML {
+    /// Infers omitted properties of the keys from their `positions` (see [`geometry`]).
+    pub fn infer_missing_geometry(&mut self) {
+        let missing: Vec<&str> = [
+            ("hands", self.hands.is_empty()),
+            ("fingers", self.fingers.is_empty()),
+            ("matrix_positions", self.matrix_positions.is_empty()),
+            ("finger_resting_positions", self.finger_resting_positions.is_empty()),
+            ("directions", self.directions.is_empty()),
+            ("key_costs", self.key_costs.is_empty()),
+            ("symmetries", self.symmetries.is_empty()),
+            ("unbalancing_positions", self.unbalancing_positions.is_empty()),
+            ("plot_template", self.plot_template.is_empty()),
+            ("plot_template_short", self.plot_template_short.is_empty()),
+        ]
+        .iter()
+        .filter(|(_, is_missing)| *is_missing)
+        .map(|(name, _)| *name)
+        .collect();
+        if missing.is_empty() {
+            return;
+        }
+
+        log::info!("Inferring omitted {} from key positions", missing.join(", "));
+        let inferred = InferredGeometry::from_positions(&self.positions, &self.geometry_inference);
+        if self.hands.is_empty() {
+            self.hands = inferred.hands;
+        }
+        if self.fingers.is_empty() {
+            self.fingers = inferred.fingers;
+        }
+        if self.matrix_positions.is_empty() {
+            self.matrix_positions = inferred.matrix_positions;
+        }
+
+        let positions = self.positions.concat();
+        let hands = self.hands.concat();
+        let fingers = self.fingers.concat();
+        let matrix_positions = self.matrix_positions.concat();
+        if self.finger_resting_positions.is_empty() {
+            self.finger_resting_positions =
+                geometry::infer_resting_positions(&positions, &hands, &fingers, &matrix_positions);
+        }
+        if self.directions.is_empty() {
+            let mut notes = Vec::new();
+            let directions = geometry::infer_directions(
+                &positions,
+                &hands,
+                &fingers,
+                &matrix_positions,
+                &self.finger_resting_positions,
+                &mut notes,
+            );
+            notes.iter().for_each(|note| log::info!("{}", note));
+            self.directions = geometry::nest(&directions, &self.positions);
+        }
+        if self.key_costs.is_empty() {
+            let key_costs = geometry::infer_key_costs(
+                &positions,
+                &hands,
+                &fingers,
+                &self.finger_resting_positions,
+                inferred.key_pitch,
+                &self.geometry_inference,
+            );
+            self.key_costs = geometry::nest(&key_costs, &self.positions);
+        }
+        if self.symmetries.is_empty() {
+            let symmetries = geometry::infer_symmetries(
+                &positions,
+                &hands,
+                inferred.split_position,
+                inferred.key_pitch,
+            );
+            self.symmetries = geometry::nest(&symmetries, &self.positions);
+        }
+        if self.unbalancing_positions.is_empty() {
+            self.unbalancing_positions = geometry::nest(
+                &vec![Position::default(); positions.len()],
+                &self.positions,
+            );
+        }
+        if self.plot_template.is_empty() || self.plot_template_short.is_empty() {
+            let (plot_template, plot_template_short) =
+                geometry::infer_plot_templates(&matrix_positions);
+            if self.plot_template.is_empty() {
+                self.plot_template = plot_template;
+            }
+            if self.plot_template_short.is_empty() {
+                self.plot_template_short = plot_template_short;
+            }
+        }
+    }
+
     /// Checks the [`KeyboardYAML`] for common errors.
     pub fn validate(&self) -> Result<()> {
         let flat_matrix_positions = self.matrix_positions.concat();
@@ -93,14 +231,54 @@ impl KeyboardYAML {
             return Err(KeyboardError::DuplicatePositions.into());
         }
 
+        // Make sure that all finger overrides refer to existing keys.
+        if let Some(o) = self
+            .finger_overrides
+            .iter()
+            .find(|o| !flat_matrix_positions.contains(&o.matrix_position))
+        {
+            return Err(KeyboardError::UnknownOverridePosition(o.matrix_position).into());
+        }
+
+        // Make sure that all blocked positions refer to existing keys.
+        if let Some(pos) = self
+            .blocked_positions
+            .iter()
+            .find(|pos| !flat_matrix_positions.contains(pos))
+        {
+            return Err(KeyboardError::UnknownBlockedPosition(*pos).into());
+        }
+
         Ok(())
     }
+
+    /// The matrix positions of the keys that no symbol is placed on.
+    pub fn blocked_positions(&self) -> &[MatrixPosition] {
+        &self.blocked_positions
+    }
+
+    /// The number of keys of the keyboard.
+    pub fn n_keys(&self) -> usize {
+        self.positions.iter().map(Vec::len).sum()
+    }
+
+    /// Whether each key (in the order of the flattened key lists) is blocked.
+    pub fn blocked_keys(&self) -> Vec<bool> {
+        self.matrix_positions
+            .iter()
+            .flatten()
+            .map(|pos| self.blocked_positions.contains(pos))
+            .collect()
+    }
 }
 
 impl Keyboard {
     /// Generate a [`Keyboard`] from a [`KeyboardYAML`] object
-    pub fn from_yaml_object(k: KeyboardYAML) -> Self {
-        let keys = k
+    pub fn from_yaml_object(mut k: KeyboardYAML) -> Self {
+        k.infer_missing_geometry();
+
+        let blocked_positions = &k.blocked_positions;
+        let mut keys: Vec<Key> = k
             .hands
             .into_iter()
             .flatten()
@@ -124,10 +302,23 @@ impl Keyboard {
                     symmetry_index,
                     cost,
                     unbalancing,
+                    blocked: blocked_positions.contains(&matrix_position),
                 },
             )
             .collect();
 
+        k.finger_overrides.iter().for_each(|o| {
+            if let Some(key) = keys
+                .iter_mut()
+                .find(|key| key.matrix_position == o.matrix_position)
+            {
+                key.finger = o.finger;
+                if let Some(hand) = o.hand {
+                    key.hand = hand;
+                }
+            }
+        });
+
         Keyboard {
             keys,
             finger_resting_positions: HandFingerMap::with_hashmap(
@@ -174,7 +365,7 @@ impl Keyboard {
 
         self.keys
             .iter()
-            .filter(|k| !exclude_thumbs || k.finger != Finger::Thumb)
+            .filter(|k| !k.blocked && (!exclude_thumbs || k.finger != Finger::Thumb))
             .for_each(|k| {
                 let il = intended_loads.get_mut(&k.hand, &k.finger);
                 *il += 1.0 / (1.0 + k.cost);
@@ -189,7 +380,7 @@ impl Keyboard {
     pub fn estimated_row_loads(&self) -> AHashMap<u8, f64> {
         let mut intended_loads: AHashMap<u8, f64> = AHashMap::default();
 
-        self.keys.iter().for_each(|k| {
+        self.keys.iter().filter(|k| !k.blocked).for_each(|k| {
             let il = intended_loads.entry(k.matrix_position.1).or_insert(0.0);
             *il += 1.0 / (1.0 + k.cost);
         });
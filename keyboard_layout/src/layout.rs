@@ -2,10 +2,11 @@
 //! its relation to the individual keys required to generate the layout's symbols.
 //! These provide the core objects that are evaluated in the `layout_evaluation` crate.
 
-use crate::key::{Hand, Key, MatrixPosition};
+use crate::cluster_plot::{self, KeyLabel};
+use crate::key::{Finger, Hand, Key, MatrixPosition};
 use crate::keyboard::{KeyIndex, Keyboard};
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::Result;
 use colored::Colorize;
 use core::slice;
@@ -13,6 +14,17 @@ use serde::Deserialize;
 use smallmap::Map;
 use std::{fmt, sync::Arc};
 
+/// The character shown for a symbol in plots (whitespace and control characters are replaced)
+fn plot_char(c: char) -> char {
+    match c {
+        ' ' => '␣',
+        '\n' => '\u{23ce}',
+        '\t' => '\u{21e5}',
+        '\u{1b}' => '\u{2327}',
+        normal_char => normal_char,
+    }
+}
+
 /// The index of a [`LayerKey`] in the `layerkeys` vec of a [`Layout`]
 ///
 /// This type is used as the key for hashmaps in unigrams, bigrams, and trigrams and thus
@@ -41,6 +53,9 @@ pub enum LayerModifierType {
     Hold,
     OneShot,
     LongPress,
+    Toggle,
+    CapsWord,
+    Chord,
 }
 
 impl Default for LayerModifierType {
@@ -69,10 +84,33 @@ impl LayerModifierType {
     pub fn is_long_press(&self) -> bool {
         matches!(self, Self::LongPress)
     }
+
+    pub fn is_toggle(&self) -> bool {
+        matches!(self, Self::Toggle)
+    }
+
+    pub fn is_caps_word(&self) -> bool {
+        matches!(self, Self::CapsWord)
+    }
+
+    pub fn is_chord(&self) -> bool {
+        matches!(self, Self::Chord)
+    }
+
+    /// If the modifier is held while typing (hold and chord modifiers)
+    pub fn is_held(&self) -> bool {
+        self.is_hold() || self.is_chord()
+    }
 }
 
 /// Enum for configuring the way how the modifiers shall be used to access a layer.
 /// (e.g. whether the modifiers has to be held or tapped for activating a layer)
+///
+/// In contrast to a one-shot layer (active for the next keystroke only), a toggled layer stays
+/// active until its modifiers are tapped again. A Caps Word layer is activated like a one-shot
+/// layer but stays active for the rest of the word, i.e. until a symbol of another layer follows.
+/// A chorded layer is active while two modifiers are held simultaneously (e.g. both lower thumb
+/// keys), which is a common way to reach a third layer on the Svalboard.
 #[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
 #[serde(tag = "type", content = "value")]
 #[serde(rename_all = "snake_case")]
@@ -80,6 +118,9 @@ pub enum LayerModifierLocations {
     Hold(Vec<ModifierLocation>),
     OneShot(Vec<ModifierLocation>),
     LongPress,
+    Toggle(Vec<ModifierLocation>),
+    CapsWord(Vec<ModifierLocation>),
+    Chord(Vec<ModifierLocation>),
 }
 
 impl LayerModifierLocations {
@@ -88,6 +129,9 @@ impl LayerModifierLocations {
             Self::Hold(v) => v.iter(),
             Self::OneShot(v) => v.iter(),
             Self::LongPress => [].iter(),
+            Self::Toggle(v) => v.iter(),
+            Self::CapsWord(v) => v.iter(),
+            Self::Chord(v) => v.iter(),
         }
     }
     pub fn layer_modifier_type(&self) -> LayerModifierType {
@@ -95,6 +139,9 @@ impl LayerModifierLocations {
             Self::Hold(_) => LayerModifierType::Hold,
             Self::OneShot(_) => LayerModifierType::OneShot,
             Self::LongPress => LayerModifierType::LongPress,
+            Self::Toggle(_) => LayerModifierType::Toggle,
+            Self::CapsWord(_) => LayerModifierType::CapsWord,
+            Self::Chord(_) => LayerModifierType::Chord,
         }
     }
 }
@@ -106,6 +153,9 @@ pub enum LayerModifiers {
     Hold(Vec<LayerKeyIndex>),
     OneShot(Vec<LayerKeyIndex>),
     LongPress,
+    Toggle(Vec<LayerKeyIndex>),
+    CapsWord(Vec<LayerKeyIndex>),
+    Chord(Vec<LayerKeyIndex>),
 }
 
 impl LayerModifiers {
@@ -114,6 +164,17 @@ impl LayerModifiers {
             Self::Hold(v) => v,
             Self::OneShot(v) => v,
             Self::LongPress => &[],
+            Self::Toggle(v) => v,
+            Self::CapsWord(v) => v,
+            Self::Chord(v) => v,
+        }
+    }
+
+    /// The modifiers that are held while typing a symbol (of hold and chorded layers).
+    pub fn held(&self) -> Option<&[LayerKeyIndex]> {
+        match self {
+            Self::Hold(v) | Self::Chord(v) => Some(v),
+            _ => None,
         }
     }
 }
@@ -197,6 +258,8 @@ pub struct Layout {
     key_layers: Vec<Vec<LayerKeyIndex>>,
     /// Map for retrieving the [`LayerKey`] for the symbol it generates
     key_map: Map<char, LayerKeyIndex>,
+    /// Layers activated by each modifier (by its type and position)
+    modifier_layers: AHashMap<(LayerModifierType, MatrixPosition), Vec<u8>>,
 }
 
 impl fmt::Display for Layout {
@@ -270,6 +333,15 @@ impl Layout {
         for mods_per_hand in modifiers.iter() {
             let mut resolved_mods_per_hand = AHashMap::default();
             for (hand, mods) in mods_per_hand.iter() {
+                if let LayerModifierLocations::Chord(locations) = mods {
+                    if locations.len() != 2 {
+                        return Err(anyhow::Error::msg(format!(
+                            "A chord requires exactly two modifiers, got {}",
+                            locations.len()
+                        )));
+                    }
+                }
+
                 let mut resolved_mods_vec = Vec::new();
                 for mp in mods.iter() {
                     let layer_modifier_type = mods.layer_modifier_type();
@@ -333,12 +405,33 @@ impl Layout {
                         LayerModifiers::OneShot(resolved_mods_vec)
                     }
                     LayerModifierLocations::LongPress => LayerModifiers::LongPress,
+                    LayerModifierLocations::Toggle(_) => LayerModifiers::Toggle(resolved_mods_vec),
+                    LayerModifierLocations::CapsWord(_) => {
+                        LayerModifiers::CapsWord(resolved_mods_vec)
+                    }
+                    LayerModifierLocations::Chord(_) => LayerModifiers::Chord(resolved_mods_vec),
                 };
                 resolved_mods_per_hand.insert(*hand, resolved_mods);
             }
             mod_map.push(resolved_mods_per_hand);
         }
 
+        let mut modifier_layers: AHashMap<(LayerModifierType, MatrixPosition), Vec<u8>> =
+            AHashMap::default();
+        for (layer_idx, mods_per_hand) in mod_map.iter().enumerate() {
+            for mods in mods_per_hand.values() {
+                for mod_idx in mods.layerkey_indices() {
+                    let modifier = &layerkeys[*mod_idx as usize];
+                    let layers = modifier_layers
+                        .entry((modifier.is_modifier, modifier.key.matrix_position))
+                        .or_default();
+                    if !layers.contains(&(layer_idx as u8 + 1)) {
+                        layers.push(layer_idx as u8 + 1);
+                    }
+                }
+            }
+        }
+
         // resolve each Single LayerKey's modifiers
         layerkeys.iter_mut().for_each(|k| {
             let mods = if k.layer > 0 && k.layer < (modifiers.len() + 1) as u8 {
@@ -363,6 +456,7 @@ impl Layout {
             keyboard,
             layerkey_to_key_index,
             key_map,
+            modifier_layers,
         })
     }
 
@@ -438,6 +532,33 @@ impl Layout {
         self.key_layers[key_index][0]
     }
 
+    /// If the held modifier activates a layer holding a symbol of the key of the given
+    /// [`LayerKeyIndex`] (e.g. Shift and a letter key)
+    pub fn activates_layer_of_key(
+        &self,
+        modifier: &LayerKey,
+        layerkey_index: &LayerKeyIndex,
+    ) -> bool {
+        let key_index: usize = self.layerkey_to_key_index[*layerkey_index as usize] as usize;
+        self.key_layers[key_index].iter().any(|idx| {
+            self.get_layerkey(idx)
+                .modifiers
+                .held()
+                .map_or(false, |mods| {
+                    mods.iter().any(|m| {
+                        self.get_layerkey(m).key.matrix_position == modifier.key.matrix_position
+                    })
+                })
+        })
+    }
+
+    /// The layers activated by the given modifier (empty for other [`LayerKey`]s)
+    pub fn layers_activated_by(&self, modifier: &LayerKey) -> &[u8] {
+        self.modifier_layers
+            .get(&(modifier.is_modifier, modifier.key.matrix_position))
+            .map_or(&[], |layers| layers.as_slice())
+    }
+
     /// Get a list of modifiers required to generate a given [`LayerKey`] as a Vec of [`LayerKey`]s
     #[inline(always)]
     pub fn resolve_modifiers(&self, k: &LayerKeyIndex) -> (LayerKeyIndex, LayerModifiers) {
@@ -448,16 +569,22 @@ impl Layout {
             return (*k, LayerModifiers::LongPress);
         }
 
+        if lk.is_modifier.is_some() && lk.modifiers.layerkey_indices().is_empty() {
+            // modifiers that are already resolved (e.g. tapped one-shot or toggle modifiers) keep
+            // their modifier type instead of turning into the underlying base LayerKey
+            return (*k, lk.modifiers.clone());
+        }
+
         let base = self.get_base_layerkey_index(k);
         let mods = lk.modifiers.clone();
         (base, mods)
     }
 
-    /// If the layout has at least one layer configured as hold layer
+    /// If the layout has at least one layer configured as hold (or chorded) layer
     pub fn has_hold_layers(&self) -> bool {
         self.layerkeys
             .iter()
-            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::Hold(_)))
+            .any(|lk| lk.modifiers.held().is_some())
     }
 
     /// If the layout has at least one layer configured as one-shot layer
@@ -467,33 +594,45 @@ impl Layout {
             .any(|lk| std::matches!(lk.modifiers, LayerModifiers::OneShot(_)))
     }
 
+    /// If the layout has at least one layer configured as toggled layer
+    pub fn has_toggle_layers(&self) -> bool {
+        self.layerkeys
+            .iter()
+            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::Toggle(_)))
+    }
+
+    /// If the layout has at least one layer configured as Caps Word layer
+    pub fn has_caps_word_layers(&self) -> bool {
+        self.layerkeys
+            .iter()
+            .any(|lk| std::matches!(lk.modifiers, LayerModifiers::CapsWord(_)))
+    }
+
+    /// The [`LayerKey`] of the key with given index shown in a plot of the given layer (if any).
+    fn plotted_layerkey(&self, key_index: usize, layer: usize) -> Option<&LayerKey> {
+        let layers = &self.key_layers[key_index];
+        if layers.is_empty() {
+            return None;
+        }
+        // layers may have less items than given "layer"
+        let k = self.get_layerkey(&layers[layer.min(layers.len() - 1)]);
+
+        if layer >= layers.len() && !k.is_fixed {
+            // for non-fixed, show empty field if no symbol is in layers
+            None
+        } else {
+            // if no symbol is in layers, show last layers value if it is fixed
+            Some(k)
+        }
+    }
+
     /// Plot a graphical representation of a layer
     pub fn plot_layer(&self, layer: usize) -> String {
-        let fmt_char = |c: char| -> char {
-            match c {
-                ' ' => '␣',
-                '\n' => '\u{23ce}',
-                '\t' => '\u{21e5}',
-                '' => '\u{2327}',
-                normal_char => normal_char,
-            }
-        };
-        let key_chars: Vec<String> = self
-            .key_layers
-            .iter()
-            .map(|layers| {
-                if layers.is_empty() {
-                    return " ".to_string();
-                }
-                // layers may have less items than given "layer"
-                let k = self.get_layerkey(&layers[layer.min(layers.len() - 1)]);
-
-                if layer >= layers.len() && !k.is_fixed {
-                    // for non-fixed, show empty field if no symbol is in layers
-                    " ".to_string()
-                } else {
-                    // if no symbol is in layers, show last layers value if it is fixed
-                    let mut s = fmt_char(k.symbol).to_string();
+        let key_chars: Vec<String> = (0..self.key_layers.len())
+            .map(|key_index| match self.plotted_layerkey(key_index, layer) {
+                None => " ".to_string(),
+                Some(k) => {
+                    let mut s = plot_char(k.symbol).to_string();
                     if !k.is_fixed {
                         s = s.yellow().bold().to_string();
                     }
@@ -505,6 +644,37 @@ impl Layout {
         self.keyboard.plot(&key_chars)
     }
 
+    /// Plot a layer in the terminal as colored clusters of keys in the geometry of the keyboard
+    /// (see [`cluster_plot`]). The given usage of the keys on each layer (e.g. their share of
+    /// the unigrams) sets the brightness of the keys relative to the most used key.
+    pub fn plot_layer_clusters(
+        &self,
+        layer: usize,
+        usage: Option<&AHashMap<(u8, MatrixPosition), f64>>,
+    ) -> String {
+        let max_usage = usage.map(|usage| usage.values().cloned().fold(0.0, f64::max));
+        let labels: Vec<Option<KeyLabel>> = (0..self.key_layers.len())
+            .map(|key_index| {
+                self.plotted_layerkey(key_index, layer).map(|k| KeyLabel {
+                    symbol: plot_char(k.symbol),
+                    is_fixed: k.is_fixed,
+                    usage: usage.zip(max_usage).map(|(usage, max_usage)| {
+                        let key_usage = usage
+                            .get(&(k.layer, k.key.matrix_position))
+                            .cloned()
+                            .unwrap_or_default();
+                        match max_usage > 0.0 {
+                            true => key_usage / max_usage,
+                            false => 0.0,
+                        }
+                    }),
+                })
+            })
+            .collect();
+
+        cluster_plot::plot(&self.keyboard, &labels)
+    }
+
     /// Plot a graphical representation of the base (first) layer
     pub fn plot(&self) -> String {
         self.plot_layer(0)
@@ -531,4 +701,114 @@ impl Layout {
             .map(|k| k.symbol.to_string())
             .collect()
     }
+
+    /// Map of the symbols generated by the layout (without modifier keys) to their positions
+    /// (key index and layer).
+    fn symbol_positions(&self) -> AHashMap<char, (KeyIndex, u8)> {
+        self.layerkeys
+            .iter()
+            .zip(self.layerkey_to_key_index.iter())
+            .filter(|(k, _)| k.is_modifier.is_none())
+            .map(|(k, key_index)| (k.symbol, (*key_index, k.layer)))
+            .collect()
+    }
+
+    /// The minimal number of swaps of two symbols that turns this layout into `other`.
+    /// Only symbols that are generated by both layouts are considered.
+    pub fn swap_distance(&self, other: &Layout) -> usize {
+        let positions = self.symbol_positions();
+        let other_positions = other.symbol_positions();
+
+        // each moved symbol is an edge from its position to its new position; these edges form
+        // chains and cycles, where a chain of n edges requires n and a cycle n - 1 swaps
+        let moves: AHashMap<(KeyIndex, u8), (KeyIndex, u8)> = positions
+            .iter()
+            .filter_map(|(c, pos)| match other_positions.get(c) {
+                Some(other_pos) if other_pos != pos => Some((*pos, *other_pos)),
+                _ => None,
+            })
+            .collect();
+
+        let mut visited: AHashSet<(KeyIndex, u8)> = AHashSet::default();
+        let mut n_cycles = 0;
+        for start in moves.keys() {
+            if visited.contains(start) {
+                continue;
+            }
+            let mut pos = *start;
+            while let Some(next) = moves.get(&pos) {
+                visited.insert(pos);
+                if *next == *start {
+                    n_cycles += 1;
+                    break;
+                }
+                if visited.contains(next) {
+                    break;
+                }
+                pos = *next;
+            }
+        }
+
+        moves.len() - n_cycles
+    }
+
+    /// The number of symbols that are typed with a different finger (or hand) in `other`.
+    /// Only symbols that are generated by both layouts are considered.
+    pub fn finger_distance(&self, other: &Layout) -> usize {
+        let fingers: AHashMap<char, (Hand, Finger)> = self
+            .layerkeys
+            .iter()
+            .filter(|k| k.is_modifier.is_none())
+            .map(|k| (k.symbol, (k.key.hand, k.key.finger)))
+            .collect();
+
+        other
+            .layerkeys
+            .iter()
+            .filter(|k| k.is_modifier.is_none())
+            .filter(|k| {
+                fingers
+                    .get(&k.symbol)
+                    .map_or(false, |f| *f != (k.key.hand, k.key.finger))
+            })
+            .count()
+    }
+
+    /// The non-fixed keys in the order of [`Layout::as_text`].
+    pub fn non_fixed_keys(&self) -> Vec<&Key> {
+        self.key_layers
+            .iter()
+            .filter_map(|layerkeys| layerkeys.first().map(|lk| self.get_layerkey(lk)))
+            .filter(|k| !k.is_fixed)
+            .map(|k| &k.key)
+            .collect()
+    }
+
+    /// For each non-fixed key (in the order of [`Layout::as_text`]), the index of its mirror image,
+    /// i.e. the non-fixed key of the other hand with the same symmetry index.
+    /// Keys without such a counterpart are mapped onto themselves. Fails if a hand has more than
+    /// one non-fixed key with the same symmetry index, as the mirror image would be ambiguous.
+    pub fn mirror_indices(&self) -> Result<Vec<usize>> {
+        let keys = self.non_fixed_keys();
+
+        let mut counts: AHashMap<(Hand, u8), usize> = AHashMap::default();
+        keys.iter()
+            .for_each(|k| *counts.entry((k.hand, k.symmetry_index)).or_default() += 1);
+        if let Some(((hand, symmetry_index), count)) = counts.iter().find(|(_, c)| **c > 1) {
+            return Err(anyhow::Error::msg(format!(
+                "Ambiguous mirror image: {} non-fixed keys of hand {:?} have symmetry index {}",
+                count, hand, symmetry_index
+            )));
+        }
+
+        Ok(keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| {
+                keys.iter()
+                    .position(|k2| k2.hand != k.hand && k2.symmetry_index == k.symmetry_index)
+                    .unwrap_or(i)
+            })
+            .collect())
+    }
 }
@@ -4,6 +4,7 @@
 use core::fmt;
 
 use crate::layout::Layout;
+use ahash::AHashSet;
 use anyhow::Result;
 
 pub trait LayoutGenerator: Send + Sync + LayoutGeneratorClone + fmt::Debug {
@@ -29,3 +30,124 @@ where
         Box::new(self.clone())
     }
 }
+
+/// Collect human-readable problems of a layout string (wrong number of symbols, duplicated,
+/// unsupported, and missing symbols) including their positions and suggestions for fixing them.
+///
+/// `layer_size` is the number of non-fixed keys. For grouped layouts, the layout string may
+/// consist of several layers of that size, duplicates are allowed, and not all permutable
+/// symbols need to be present. An empty result means that the layout string is valid.
+pub fn layout_string_issues(
+    chars: &[char],
+    permutable: &AHashSet<char>,
+    placeholders: &AHashSet<char>,
+    layer_size: usize,
+    grouped: bool,
+) -> Vec<String> {
+    let mut issues = Vec::new();
+    let layer_size = layer_size.max(1);
+
+    let position = |i: usize| {
+        if grouped && chars.len() > layer_size {
+            format!(
+                "position {} (layer {}, key {})",
+                i + 1,
+                i / layer_size + 1,
+                i % layer_size + 1
+            )
+        } else {
+            format!("position {}", i + 1)
+        }
+    };
+
+    if grouped && chars.len() % layer_size != 0 {
+        issues.push(format!(
+            "layer {} has {} symbols, expected {}",
+            chars.len() / layer_size + 1,
+            chars.len() % layer_size,
+            layer_size
+        ));
+    }
+
+    let used: AHashSet<char> = chars.iter().cloned().collect();
+    let mut missing: Vec<char> = if grouped {
+        Vec::new()
+    } else {
+        permutable
+            .iter()
+            .filter(|c| !used.contains(c) && !placeholders.contains(c))
+            .cloned()
+            .collect()
+    };
+    missing.sort_unstable();
+
+    // suggest a replacement for a wrong symbol: its lowercase variant or the only missing symbol
+    let suggestion = |c: char| -> Option<char> {
+        let lowercase = c.to_lowercase().next().filter(|l| *l != c);
+        match lowercase {
+            Some(l) if missing.contains(&l) || (grouped && permutable.contains(&l)) => Some(l),
+            _ if missing.len() == 1 => Some(missing[0]),
+            _ => None,
+        }
+    };
+
+    if !grouped {
+        let mut seen: Vec<char> = Vec::new();
+        for c in chars.iter().filter(|c| !placeholders.contains(c)) {
+            if seen.contains(c) {
+                continue;
+            }
+            seen.push(*c);
+
+            let positions: Vec<String> = chars
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| *other == c)
+                .map(|(i, _)| (i + 1).to_string())
+                .collect();
+            if positions.len() > 1 {
+                let mut issue = format!("'{}' appears at positions {}", c, positions.join(", "));
+                if missing.len() == 1 {
+                    issue.push_str(&format!(
+                        " (replace one of them with the missing '{}'?)",
+                        missing[0]
+                    ));
+                }
+                issues.push(issue);
+            }
+        }
+    }
+
+    chars
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| !permutable.contains(c) && !placeholders.contains(c))
+        .for_each(|(i, c)| {
+            let mut issue = format!("'{}' at {} is not a permutable symbol", c, position(i));
+            if let Some(s) = suggestion(*c) {
+                issue.push_str(&format!(" (did you mean '{}'?)", s));
+            }
+            issues.push(issue);
+        });
+
+    if !missing.is_empty() {
+        issues.push(format!(
+            "missing symbols: '{}'",
+            missing.iter().collect::<String>()
+        ));
+    }
+
+    // a differing number of symbols alone is tolerated, but helps explaining other issues
+    if !grouped && !issues.is_empty() && chars.len() != layer_size {
+        issues.insert(
+            0,
+            format!(
+                "layout string has {} symbols, expected {} (one for each non-fixed key)",
+                chars.len(),
+                layer_size
+            ),
+        );
+    }
+
+    issues
+}
@@ -6,13 +6,16 @@
 //! with a given layout. It provides data about the involved key properties, required modifiers,
 //! and other associated properties.
 
+pub mod cluster_plot;
 pub mod config;
+pub mod geometry;
 pub mod grouped_layout_generator;
 pub mod key;
 pub mod keyboard;
 pub mod layout;
 pub mod layout_generator;
 pub mod neo_layout_generator;
+pub mod symbols;
 
 #[cfg(test)]
 mod tests {
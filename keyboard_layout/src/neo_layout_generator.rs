@@ -1,7 +1,8 @@
 use crate::key::Hand;
 use crate::keyboard::Keyboard;
 use crate::layout::{LayerModifierLocations, Layout};
-use crate::layout_generator::LayoutGenerator;
+use crate::layout_generator::{layout_string_issues, LayoutGenerator};
+use crate::symbols;
 
 use ahash::{AHashMap, AHashSet};
 use anyhow::Result;
@@ -11,10 +12,8 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum LayoutError {
-    #[error("Invalid keyboard layout: Duplicate characters in provided layout '{0}': '{1}'")]
-    DuplicateChars(String, String),
-    #[error("Invalid keyboard layout: Missing characters in provided layout: '{0}'")]
-    MissingChars(String),
+    #[error("Invalid keyboard layout '{0}':\n  - {issues}", issues = .1.join("\n  - "))]
+    InvalidLayoutString(String, Vec<String>),
     #[error("Invalid keyboard layout: Unsupported characters in provided layout (not in first level of `base_layout` and `fixed_keys` with value `false`): '{0}'")]
     UnsupportedChars(String),
     #[error(
@@ -51,6 +50,93 @@ impl BaseLayoutYAML {
 
         Ok(())
     }
+
+    /// Replace the symbols of the non-fixed keys with those of the given layout string. As in the
+    /// [`NeoLayoutGenerator`], each symbol brings along the higher layers of its key in the base
+    /// layout.
+    pub fn apply_layout_string(&mut self, layout_keys: &str) -> Result<()> {
+        let chars: Vec<char> = symbols::layout_symbols(layout_keys)?;
+        let mut key_layers: AHashMap<char, Vec<String>> = AHashMap::default();
+        let mut n_permutable = 0;
+        for (layers, fixed) in self
+            .keys
+            .iter()
+            .flatten()
+            .zip(self.fixed_keys.iter().flatten())
+        {
+            if !*fixed {
+                n_permutable += 1;
+                if let Some(c) = layers.first().and_then(|l| symbols::config_symbol(l)) {
+                    key_layers.entry(c).or_insert_with(|| layers.clone());
+                }
+            }
+        }
+
+        let issues = layout_string_issues(
+            &chars,
+            &AHashSet::from_iter(key_layers.keys().cloned()),
+            &AHashSet::from_iter(self.placeholder.chars()),
+            n_permutable,
+            false,
+        );
+        if !issues.is_empty() {
+            return Err(LayoutError::InvalidLayoutString(layout_keys.to_string(), issues).into());
+        }
+
+        let mut given_chars = chars.iter();
+        for (layers, fixed) in self
+            .keys
+            .iter_mut()
+            .flatten()
+            .zip(self.fixed_keys.iter().flatten())
+        {
+            if !*fixed {
+                // can not fail due to above check
+                let c = given_chars.next().unwrap();
+                *layers = key_layers[c].clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove all symbols from the keys (in the order of the flattened `keys`) for which
+    /// `is_blocked` returns `true` and fix them, so that no symbol is placed on them.
+    pub fn block_keys(&mut self, is_blocked: impl Fn(usize) -> bool) {
+        let placeholder = &self.placeholder;
+        for (i, (layers, fixed)) in self
+            .keys
+            .iter_mut()
+            .flatten()
+            .zip(self.fixed_keys.iter_mut().flatten())
+            .enumerate()
+        {
+            if !is_blocked(i) {
+                continue;
+            }
+
+            let symbols: String = layers
+                .iter()
+                .filter_map(|l| symbols::config_symbol(l))
+                .filter(|c| !placeholder.contains(*c))
+                .collect();
+            if !symbols.is_empty() {
+                log::warn!("Removing the symbols '{}' of a blocked key", symbols);
+            }
+            layers.clear();
+            *fixed = true;
+        }
+    }
+
+    /// Fix exactly the keys (in the order of the flattened `keys`) for which `is_fixed` returns
+    /// `true`.
+    pub fn set_fixed_keys(&mut self, is_fixed: impl Fn(usize) -> bool) {
+        self.fixed_keys
+            .iter_mut()
+            .flatten()
+            .enumerate()
+            .for_each(|(i, fixed)| *fixed = is_fixed(i));
+    }
 }
 /// Provides functionalities for generating Neo layout variants from given string representations
 /// of their base layer.
@@ -74,7 +160,12 @@ impl NeoLayoutGenerator {
             .keys
             .iter()
             .flatten()
-            .map(|layers| layers.iter().filter_map(|l| l.chars().next()).collect())
+            .map(|layers| {
+                layers
+                    .iter()
+                    .filter_map(|l| symbols::config_symbol(l))
+                    .collect()
+            })
             .collect();
         let fixed_keys: Vec<bool> = base.fixed_keys.iter().flatten().cloned().collect();
 
@@ -128,7 +219,7 @@ impl NeoLayoutGenerator {
     /// Does not check whether the given string is valid (sufficient, correct and unique charactors).
     /// This is useful for plotting unfinished or invalid layouts.
     pub fn generate_unchecked(&self, layout_keys: &str) -> Result<Layout> {
-        let chars: Vec<char> = layout_keys.chars().collect();
+        let chars: Vec<char> = symbols::layout_symbols(layout_keys)?;
 
         // assemble a Vec<Vec<char>> representation of the layer for the given layout string
         let mut given_chars = chars.iter();
@@ -188,43 +279,16 @@ impl LayoutGenerator for NeoLayoutGenerator {
             return self.generate_base();
         }
 
-        let placeholder = self.placeholder.chars().nth(0).unwrap(); // XXX
-        let chars: Vec<char> = layout_keys.chars().filter(|c| *c != placeholder).collect();
-
+        let chars: Vec<char> = symbols::layout_symbols(layout_keys)?;
         let placeholder_set: AHashSet<char> = AHashSet::from_iter(self.placeholder.chars());
-        let char_set: AHashSet<char> = AHashSet::from_iter(chars.clone()).difference(&placeholder_set).cloned().collect();
         let layout_set: AHashSet<char> =
-            AHashSet::from_iter(self.permutable_key_map.keys().cloned()).difference(&placeholder_set).cloned().collect();
-
-        // Check for duplicate chars
-        if char_set.len() != chars.len() {
-            let mut duplicates = AHashSet::default();
-            let mut seen_chars = AHashSet::default();
-            for char in chars.iter() {
-                if seen_chars.contains(char) {
-                    duplicates.insert(*char);
-                } else {
-                    seen_chars.insert(*char);
-                }
-            }
-            return Err(LayoutError::DuplicateChars(
-                layout_keys.to_string(),
-                duplicates.iter().cloned().collect::<String>(),
-            )
-            .into());
-        }
-
-        let mut unsupported_chars: Vec<char> = char_set.difference(&layout_set).cloned().collect();
-        let mut missing_chars: Vec<char> = layout_set.difference(&char_set).cloned().collect();
+            AHashSet::from_iter(self.permutable_key_map.keys().cloned());
+        let n_permutable = self.fixed_keys.iter().filter(|fixed| !**fixed).count();
 
-        unsupported_chars.sort_unstable();
-        missing_chars.sort_unstable();
-
-        if !unsupported_chars.is_empty() {
-            return Err(LayoutError::UnsupportedChars(unsupported_chars.iter().collect()).into());
-        }
-        if !missing_chars.is_empty() {
-            return Err(LayoutError::MissingChars(missing_chars.iter().collect()).into());
+        let issues =
+            layout_string_issues(&chars, &layout_set, &placeholder_set, n_permutable, false);
+        if !issues.is_empty() {
+            return Err(LayoutError::InvalidLayoutString(layout_keys.to_string(), issues).into());
         }
 
         self.generate_unchecked(layout_keys)
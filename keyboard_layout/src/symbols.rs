@@ -0,0 +1,101 @@
+//! The `symbols` module handles extended Unicode symbols (emoji, arrows, math symbols) that
+//! may be placed on the (higher) layers of a layout.
+//!
+//! Layouts represent each symbol as a single `char`. Many emoji, however, are typed as grapheme
+//! clusters consisting of a base symbol and presentation modifiers, e.g. "❤️" (a heart followed
+//! by the emoji variation selector) or "👍🏽" (a thumb with a skin tone modifier). Layout
+//! strings are therefore split into grapheme clusters, and the presentation modifiers are
+//! dropped (from layout strings as well as from corpus texts), such that each cluster maps to
+//! its base symbol. Clusters of several base symbols (e.g. ZWJ sequences or flags) are not
+//! supported.
+
+use anyhow::{bail, Result};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Categories of extended Unicode symbols
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SymbolCategory {
+    Emoji,
+    Arrow,
+    Math,
+}
+
+impl SymbolCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolCategory::Emoji => "emoji",
+            SymbolCategory::Arrow => "arrows",
+            SymbolCategory::Math => "math symbols",
+        }
+    }
+}
+
+/// Whether the char only modifies the presentation of the preceding symbol (variation selectors
+/// and emoji skin tone modifiers).
+#[inline]
+pub fn is_presentation_modifier(c: char) -> bool {
+    matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}')
+}
+
+/// The category of an extended Unicode symbol (if it is one).
+pub fn symbol_category(c: char) -> Option<SymbolCategory> {
+    match c {
+        '\u{2190}'..='\u{21FF}'
+        | '\u{27F0}'..='\u{27FF}'
+        | '\u{2900}'..='\u{297F}'
+        | '\u{2B00}'..='\u{2BFF}' => Some(SymbolCategory::Arrow),
+        '\u{2200}'..='\u{22FF}'
+        | '\u{27C0}'..='\u{27EF}'
+        | '\u{2980}'..='\u{2AFF}'
+        | '\u{1D400}'..='\u{1D7FF}'
+        | '±'
+        | '×'
+        | '÷'
+        | '¬' => Some(SymbolCategory::Math),
+        '\u{2600}'..='\u{27BF}' | '\u{1F000}'..='\u{1FAFF}' => Some(SymbolCategory::Emoji),
+        _ => None,
+    }
+}
+
+/// The symbol of a grapheme cluster, i.e. its only char after dropping presentation modifiers.
+pub fn grapheme_symbol(grapheme: &str) -> Option<char> {
+    let mut chars = grapheme.chars().filter(|c| !is_presentation_modifier(*c));
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// The symbol of a key's layer in a layout config (its first grapheme cluster).
+pub fn config_symbol(s: &str) -> Option<char> {
+    s.graphemes(true)
+        .next()
+        .and_then(grapheme_symbol)
+        .or_else(|| s.chars().next())
+}
+
+/// Split a layout string into its symbols (one per grapheme cluster).
+pub fn layout_symbols(layout_keys: &str) -> Result<Vec<char>> {
+    let mut unsupported = Vec::new();
+    let symbols: Vec<char> = layout_keys
+        .graphemes(true)
+        .filter_map(|grapheme| {
+            let symbol = grapheme_symbol(grapheme);
+            if symbol.is_none() {
+                unsupported.push(grapheme);
+            }
+
+            symbol
+        })
+        .collect();
+
+    if !unsupported.is_empty() {
+        bail!(
+            "Unsupported grapheme clusters in layout string (only single symbols with optional \
+             variation selectors or skin tone modifiers are supported): '{}'",
+            unsupported.join("', '")
+        );
+    }
+
+    Ok(symbols)
+}
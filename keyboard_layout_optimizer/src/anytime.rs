@@ -0,0 +1,278 @@
+//! The `anytime` module lets optimizations run within a budget (e.g. `--budget 2h`) and be
+//! stopped at any time without losing their results: the schedules of the optimizers are fitted
+//! to the budget using a measured evaluation rate, the best layout found so far is kept in a file,
+//! and Ctrl+C ends the optimizations after their current step (as if the budget was used up).
+
+use keyboard_layout::layout_generator::LayoutGenerator;
+use layout_evaluation::evaluation::Evaluator;
+use layout_optimization_common::{
+    observer::{OptimizationObserver, Progress},
+    stopping::SharedBudget,
+    LayoutPermutator,
+};
+
+use colored::Colorize;
+use serde::Serialize;
+use std::{
+    fs,
+    path::Path,
+    process,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The budget of a run: a wall-clock duration or a number of layout evaluations.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RunBudget {
+    /// Seconds
+    Time(f64),
+    Evaluations(u64),
+}
+
+impl FromStr for RunBudget {
+    type Err = String;
+
+    /// Parse a duration with units (e.g. "90s", "45m", "2h", "1h30m", "1d") or a plain number of
+    /// evaluations (e.g. "200000").
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(evaluations) = s.parse::<u64>() {
+            return Ok(RunBudget::Evaluations(evaluations));
+        }
+
+        let invalid = || {
+            format!(
+                "Invalid budget '{}' (expected a duration like \"2h\" or \"1h30m\", or a number \
+                 of evaluations)",
+                s
+            )
+        };
+        let mut seconds = 0.0;
+        let mut number = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                number.push(c);
+                continue;
+            }
+            let unit = match c {
+                's' => 1.0,
+                'm' => 60.0,
+                'h' => 3600.0,
+                'd' => 86400.0,
+                _ => return Err(invalid()),
+            };
+            let value: f64 = number.parse().map_err(|_| invalid())?;
+            seconds += value * unit;
+            number.clear();
+        }
+        if !number.is_empty() || seconds <= 0.0 {
+            return Err(invalid());
+        }
+
+        Ok(RunBudget::Time(seconds))
+    }
+}
+
+impl RunBudget {
+    /// The shared budget of all optimizations of the run.
+    pub fn shared(budget: Option<RunBudget>) -> SharedBudget {
+        match budget {
+            Some(RunBudget::Time(seconds)) => SharedBudget::new(Some(seconds), None),
+            Some(RunBudget::Evaluations(evaluations)) => SharedBudget::new(None, Some(evaluations)),
+            None => SharedBudget::new(None, None),
+        }
+    }
+}
+
+/// Measures the number of layout evaluations per second of a single thread on random
+/// permutations of the given layout (for about a second).
+pub fn evaluation_rate(
+    evaluator: &Evaluator,
+    layout_generator: &dyn LayoutGenerator,
+    layout_str: &str,
+    fixed_characters: &str,
+) -> f64 {
+    let pm = LayoutPermutator::new(layout_str, fixed_characters);
+    let start = Instant::now();
+    let mut evaluations = 0;
+    while evaluations < 5 || (evaluations < 1000 && start.elapsed() < Duration::from_secs(1)) {
+        let layout = layout_generator
+            .generate(&pm.generate_string(&pm.generate_random()))
+            .unwrap();
+        evaluator.evaluate_layout(&layout);
+        evaluations += 1;
+    }
+    let rate = evaluations as f64 / start.elapsed().as_secs_f64();
+    log::info!(
+        "Measured {:.0} evaluations per second (single thread)",
+        rate
+    );
+
+    rate
+}
+
+/// Make the first Ctrl+C stop all optimizations using the budget after their current step, so
+/// that they report and store their results as usual. A second Ctrl+C calls `on_exit` (e.g. to
+/// print a summary) and exits immediately.
+pub fn handle_interrupts(budget: &SharedBudget, on_exit: impl Fn() + Send + 'static) {
+    let budget = budget.clone();
+    let interrupted = AtomicBool::new(false);
+    ctrlc::set_handler(move || {
+        if interrupted.swap(true, Ordering::Relaxed) {
+            on_exit();
+            process::exit(0);
+        }
+        eprintln!(
+            "\n{} Stopping after the current step (press Ctrl+C again to exit immediately)",
+            "Interrupted:".yellow().bold(),
+        );
+        budget.interrupt();
+    })
+    .expect("Error setting Ctrl-C handler");
+}
+
+#[derive(Serialize)]
+struct BestSoFarDetails<'a> {
+    layout: &'a str,
+}
+
+/// The content of the best-so-far file. It has the fields of the results of `evaluate --json`
+/// that are needed for reading it as warm start file.
+#[derive(Serialize)]
+struct BestSoFar<'a> {
+    details: BestSoFarDetails<'a>,
+    total_cost: f64,
+    run: &'a str,
+    step: u64,
+    elapsed_seconds: f64,
+    timestamp: u64,
+}
+
+/// Keeps the best layout of all optimizations of the process in a JSON file. The file is
+/// replaced atomically (written to a temporary file that is renamed), so that it always holds a
+/// complete result, even if the process is killed.
+pub struct BestSoFarFile {
+    path: String,
+    /// Lowest cost written to the file, shared by all optimizations of the process
+    best_cost: Arc<Mutex<Option<f64>>>,
+}
+
+impl BestSoFarFile {
+    pub fn new(path: &str, best_cost: Arc<Mutex<Option<f64>>>) -> Self {
+        Self {
+            path: path.to_string(),
+            best_cost,
+        }
+    }
+
+    fn write(&self, progress: &Progress, layout: &str) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(&[BestSoFar {
+            details: BestSoFarDetails { layout },
+            total_cost: progress.best_cost,
+            run: &progress.run,
+            step: progress.step,
+            elapsed_seconds: progress.elapsed.as_secs_f64(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        }])?;
+
+        let path = Path::new(&self.path);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+impl OptimizationObserver for BestSoFarFile {
+    fn on_new_best(&mut self, progress: &Progress, layout: &str) {
+        let mut best_cost = self.best_cost.lock().unwrap();
+        if best_cost.map_or(false, |cost| cost <= progress.best_cost) {
+            return;
+        }
+
+        match self.write(progress, layout) {
+            Ok(()) => *best_cost = Some(progress.best_cost),
+            Err(e) => log::error!("Could not write best layout to {}: {}", self.path, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use layout_optimization_common::{observer::Budget, stopping::StopReason};
+
+    fn progress(best_cost: f64) -> Progress {
+        Progress {
+            run: "test".to_string(),
+            step: 1,
+            best_cost,
+            evaluations: 1,
+            elapsed: Duration::from_secs(1),
+            budget: Budget::new(1, &Default::default()),
+        }
+    }
+
+    #[test]
+    fn parses_durations_and_evaluations() {
+        assert_eq!("90s".parse(), Ok(RunBudget::Time(90.0)));
+        assert_eq!("2h".parse(), Ok(RunBudget::Time(7200.0)));
+        assert_eq!(" 1h30m ".parse(), Ok(RunBudget::Time(5400.0)));
+        assert_eq!("1.5d".parse(), Ok(RunBudget::Time(129600.0)));
+        assert_eq!("200000".parse(), Ok(RunBudget::Evaluations(200000)));
+    }
+
+    #[test]
+    fn rejects_invalid_budgets() {
+        for budget in ["", "2x", "h", "30m5", "0s", "1..5h"] {
+            assert!(budget.parse::<RunBudget>().is_err(), "{}", budget);
+        }
+    }
+
+    #[test]
+    fn shared_budget_has_the_limit_of_the_run() {
+        let time = RunBudget::shared(Some(RunBudget::Time(60.0)));
+        assert_eq!((time.time_limit, time.max_evaluations), (Some(60.0), None));
+
+        let evaluations = RunBudget::shared(Some(RunBudget::Evaluations(1000)));
+        assert_eq!(
+            (evaluations.time_limit, evaluations.max_evaluations),
+            (None, Some(1000))
+        );
+
+        let unlimited = RunBudget::shared(None);
+        assert_eq!(unlimited.check(), None);
+        unlimited.interrupt();
+        assert_eq!(unlimited.check(), Some(StopReason::Interrupted));
+    }
+
+    #[test]
+    fn best_so_far_file_keeps_the_lowest_cost() {
+        let path = std::env::temp_dir().join(format!("best_so_far_{}.json", process::id()));
+        let path_str = path.to_str().unwrap();
+        let best_cost = Arc::new(Mutex::new(None));
+        let mut first = BestSoFarFile::new(path_str, best_cost.clone());
+        let mut second = BestSoFarFile::new(path_str, best_cost.clone());
+
+        first.on_new_best(&progress(10.0), "abc");
+        second.on_new_best(&progress(12.0), "bca");
+        let content: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(content[0]["details"]["layout"], "abc");
+        assert_eq!(content[0]["total_cost"], 10.0);
+
+        second.on_new_best(&progress(8.0), "cab");
+        let content: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(content[0]["details"]["layout"], "cab");
+        assert_eq!(*best_cost.lock().unwrap(), Some(8.0));
+        assert!(!path.with_extension("tmp").exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use keyboard_layout_optimizer::{
+    common::{self, MetricEntry},
+    logging,
+};
+use layout_evaluation::evaluation::Evaluator;
+
+use clap::Parser;
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout ablation report")]
+/// Re-evaluate layouts with each enabled metric disabled (and with each metric alone) and report
+/// the marginal contribution of each metric to the total cost.
+///
+/// Marginal contributions differ from the metrics' weighted costs where metrics interact, e.g.
+/// for metrics built upon other metrics (irregularity) or non-linear corpus objectives.
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct MetricContribution {
+    metric: String,
+    config_key: String,
+    /// Weighted cost of the metric in the full evaluation
+    weighted_cost: f64,
+    /// Total cost minus the total cost without the metric
+    marginal_cost: f64,
+    /// Total cost with only this metric enabled
+    standalone_cost: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct AblationReport {
+    layout: String,
+    total_cost: f64,
+    contributions: Vec<MetricContribution>,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let config = common::resolve_eval_params(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not read the evaluation config: {:?}", e))
+        .value;
+    let metrics = config.get("metrics").cloned().unwrap_or_default();
+    let entries = common::enabled_metrics(&metrics);
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    log::info!("Preparing evaluators for {} metrics", entries.len());
+    let ablations: Vec<(MetricEntry, Evaluator, Evaluator)> = entries
+        .iter()
+        .map(|entry| {
+            let label = entry.label();
+            let without = common::metric_parameters(&metrics, &entries, |e| e.label() != label);
+            let alone = common::metric_parameters(&metrics, &entries, |e| e.label() == label);
+            Ok((
+                entry.clone(),
+                evaluator.with_metrics(&without)?,
+                evaluator.with_metrics(&alone)?,
+            ))
+        })
+        .collect::<anyhow::Result<_>>()
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let mut reports = Vec::new();
+    for layout_str in options.layout_str.iter() {
+        let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
+        let layout = match layout_generator.generate(&layout_str) {
+            Ok(layout) => layout,
+            Err(e) => {
+                log::error!("Error in generating layout: {:?}", e);
+                continue;
+            }
+        };
+
+        let result = evaluator.evaluate_layout(&layout);
+        let total_cost = result.total_cost();
+
+        let contributions = ablations
+            .iter()
+            .filter_map(|(entry, without, alone)| {
+                let alone_result = alone.evaluate_layout(&layout);
+                // metrics that are configured but not evaluated by the evaluator
+                let metric = alone_result
+                    .iter()
+                    .flat_map(|results| results.metric_costs.iter())
+                    .map(|mc| mc.core.name.clone())
+                    .next()?;
+                let weighted_cost = result.metric_cost(&metric).unwrap_or_default();
+
+                Some(MetricContribution {
+                    metric,
+                    config_key: entry.label(),
+                    weighted_cost,
+                    marginal_cost: total_cost - without.evaluate_layout(&layout).total_cost(),
+                    standalone_cost: alone_result.total_cost(),
+                })
+            })
+            .collect();
+
+        reports.push(AblationReport {
+            layout: layout_str,
+            total_cost,
+            contributions,
+        });
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string(&reports).unwrap());
+        return;
+    }
+
+    for report in reports.iter_mut() {
+        report
+            .contributions
+            .sort_by(|a, b| b.marginal_cost.partial_cmp(&a.marginal_cost).unwrap());
+
+        println!("Layout: {}", report.layout);
+        println!("Total cost: {:.2}", report.total_cost);
+        println!();
+        println!(
+            "{:<36} {:>12} {:>12} {:>8} {:>12} {:>12}",
+            "Metric", "Weighted", "Marginal", "Share", "Alone", "Interaction"
+        );
+        for c in report.contributions.iter() {
+            println!(
+                "{:<36} {:>12.2} {:>12.2} {:>7.1}% {:>12.2} {:>12.2}",
+                c.metric,
+                c.weighted_cost,
+                c.marginal_cost,
+                100.0 * c.marginal_cost / report.total_cost,
+                c.standalone_cost,
+                c.marginal_cost - c.weighted_cost,
+            );
+        }
+        let sum_marginal: f64 = report.contributions.iter().map(|c| c.marginal_cost).sum();
+        println!(
+            "{:<36} {:>12} {:>12.2} {:>7.1}%",
+            "Sum of marginal costs",
+            "",
+            sum_marginal,
+            100.0 * sum_marginal / report.total_cost
+        );
+        println!();
+    }
+}
@@ -0,0 +1,264 @@
+use keyboard_layout::{
+    config::LayoutConfig,
+    symbols::{self, SymbolCategory},
+};
+use keyboard_layout_optimizer::{common, logging};
+
+use ahash::{AHashMap, AHashSet};
+use clap::Parser;
+use itertools::Itertools;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Corpus analysis")]
+/// Print statistics of the corpus independent of any layout, e.g. for sanity-checking a corpus
+/// before optimizing on it.
+///
+/// The ngrams are prepared like for an evaluation (case handling, excluded characters, ...).
+struct Options {
+    /// Number of entries in the lists of top ngrams
+    #[clap(long, default_value = "20")]
+    top: usize,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+}
+
+fn fmt_char(c: char) -> String {
+    match c {
+        ' ' => "␣".to_string(),
+        '\n' => "⏎".to_string(),
+        '\t' => "⇥".to_string(),
+        c if c.is_control() => c.escape_debug().to_string(),
+        c => c.to_string(),
+    }
+}
+
+fn fmt_ngram(chars: &[char]) -> String {
+    chars.iter().map(|c| fmt_char(*c)).collect()
+}
+
+fn char_class(c: char) -> &'static str {
+    if c.is_lowercase() {
+        "lowercase letters"
+    } else if c.is_uppercase() {
+        "uppercase letters"
+    } else if c.is_alphabetic() {
+        "other letters"
+    } else if c.is_numeric() {
+        "digits"
+    } else if c.is_whitespace() {
+        "whitespace"
+    } else if c.is_ascii_punctuation() {
+        "ASCII punctuation"
+    } else if let Some(category) = symbols::symbol_category(c) {
+        category.name()
+    } else {
+        "other symbols"
+    }
+}
+
+fn print_top(title: &str, grams: Vec<(String, f64)>, total_weight: f64, n: usize) {
+    println!("{}:", title);
+    for (gram, weight) in grams
+        .into_iter()
+        .sorted_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap())
+        .take(n)
+    {
+        println!("  {:<8} {:>6.2}%", gram, 100.0 * weight / total_weight);
+    }
+    println!();
+}
+
+/// All symbols of the layout config (on any layer).
+fn layout_symbols(layout_config: &str) -> AHashSet<char> {
+    let layout_config = LayoutConfig::from_yaml(layout_config)
+        .unwrap_or_else(|e| panic!("Could not load config file {}: {}", layout_config, e));
+    let placeholders: AHashSet<char> = layout_config.base_layout.placeholder.chars().collect();
+
+    layout_config
+        .base_layout
+        .keys
+        .iter()
+        .flatten()
+        .flatten()
+        .filter_map(|symbol| symbols::config_symbol(symbol))
+        .filter(|c| !placeholders.contains(c))
+        .collect()
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let (unigrams, bigrams, trigrams, quadgrams) = common::init_ngrams(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not read the ngrams: {:?}", e));
+
+    println!("Totals:");
+    println!(
+        "  unigrams:  {:>9} distinct, total weight {:.0}",
+        unigrams.grams.len(),
+        unigrams.total_weight()
+    );
+    println!(
+        "  bigrams:   {:>9} distinct, total weight {:.0}",
+        bigrams.grams.len(),
+        bigrams.total_weight()
+    );
+    println!(
+        "  trigrams:  {:>9} distinct, total weight {:.0}",
+        trigrams.grams.len(),
+        trigrams.total_weight()
+    );
+    if !quadgrams.grams.is_empty() {
+        println!(
+            "  quadgrams: {:>9} distinct, total weight {:.0}",
+            quadgrams.grams.len(),
+            quadgrams.total_weight()
+        );
+    }
+    println!();
+
+    // character classes
+    let mut classes: AHashMap<&str, f64> = AHashMap::default();
+    for (c, weight) in unigrams.grams.iter() {
+        *classes.entry(char_class(*c)).or_default() += weight;
+    }
+    println!("Character classes (share of all unigrams):");
+    for (class, weight) in classes
+        .iter()
+        .sorted_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap())
+    {
+        println!(
+            "  {:<18} {:>6.2}%",
+            class,
+            100.0 * weight / unigrams.total_weight()
+        );
+    }
+    println!();
+
+    print_top(
+        "Top unigrams (share of all unigrams)",
+        unigrams
+            .grams
+            .iter()
+            .map(|(c, w)| (fmt_char(*c), *w))
+            .collect(),
+        unigrams.total_weight(),
+        options.top,
+    );
+    print_top(
+        "Top bigrams (share of all bigrams)",
+        bigrams
+            .grams
+            .iter()
+            .map(|((c1, c2), w)| (fmt_ngram(&[*c1, *c2]), *w))
+            .collect(),
+        bigrams.total_weight(),
+        options.top,
+    );
+    print_top(
+        "Top trigrams (share of all trigrams)",
+        trigrams
+            .grams
+            .iter()
+            .map(|((c1, c2, c3), w)| (fmt_ngram(&[*c1, *c2, *c3]), *w))
+            .collect(),
+        trigrams.total_weight(),
+        options.top,
+    );
+
+    // extended Unicode symbols that may be placed on higher layers
+    for category in [
+        SymbolCategory::Emoji,
+        SymbolCategory::Arrow,
+        SymbolCategory::Math,
+    ] {
+        let grams: Vec<(String, f64)> = unigrams
+            .grams
+            .iter()
+            .filter(|(c, _)| symbols::symbol_category(**c) == Some(category))
+            .map(|(c, w)| (fmt_char(*c), *w))
+            .collect();
+        if !grams.is_empty() {
+            print_top(
+                &format!("Top {} (share of all unigrams)", category.name()),
+                grams,
+                unigrams.total_weight(),
+                options.top,
+            );
+        }
+    }
+
+    // pairs of symbols that are typed in sequence (in either order) become same-finger bigrams
+    // if they share a finger
+    let mut pairs: AHashMap<(char, char), f64> = AHashMap::default();
+    let mut repeat_weight = 0.0;
+    for ((c1, c2), weight) in bigrams.grams.iter() {
+        if c1.is_whitespace() || c2.is_whitespace() {
+            continue;
+        }
+        if c1 == c2 {
+            repeat_weight += weight;
+        } else {
+            *pairs.entry((*c1.min(c2), *c1.max(c2))).or_default() += weight;
+        }
+    }
+    println!(
+        "Same-symbol repeats (same finger on any layout): {:.2}% of all bigrams",
+        100.0 * repeat_weight / bigrams.total_weight()
+    );
+    print_top(
+        "Potential same-finger pairs in both orders (share of all bigrams)",
+        pairs
+            .iter()
+            .map(|((c1, c2), w)| (format!("{} {}", fmt_char(*c1), fmt_char(*c2)), *w))
+            .collect(),
+        bigrams.total_weight(),
+        options.top,
+    );
+
+    // coverage of the layout's symbols
+    let symbols = layout_symbols(&options.general_parameters.layout_config);
+    let mut covered_weight = 0.0;
+    let mut shifted_weight = 0.0;
+    let mut missing: Vec<(String, f64)> = Vec::new();
+    for (c, weight) in unigrams.grams.iter() {
+        let lowercase: Vec<char> = c.to_lowercase().collect();
+        if symbols.contains(c) {
+            covered_weight += weight;
+        } else if lowercase.len() == 1 && symbols.contains(&lowercase[0]) {
+            shifted_weight += weight;
+        } else {
+            missing.push((fmt_char(*c), *weight));
+        }
+    }
+    let total_weight = unigrams.total_weight();
+    println!(
+        "Coverage by the symbols of {}:",
+        options.general_parameters.layout_config
+    );
+    println!(
+        "  on the layout:        {:>6.2}%",
+        100.0 * covered_weight / total_weight
+    );
+    println!(
+        "  via Shift:            {:>6.2}%",
+        100.0 * shifted_weight / total_weight
+    );
+    println!(
+        "  missing:              {:>6.2}% ({} symbols)",
+        100.0 * (total_weight - covered_weight - shifted_weight) / total_weight,
+        missing.len()
+    );
+    println!();
+    if !missing.is_empty() {
+        print_top(
+            "Missing symbols (share of all unigrams)",
+            missing,
+            total_weight,
+            options.top,
+        );
+    }
+}
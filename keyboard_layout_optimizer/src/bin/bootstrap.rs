@@ -0,0 +1,310 @@
+use keyboard_layout::layout::Layout;
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::{evaluation::Evaluator, results::EvaluationResult};
+
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout bootstrap evaluation")]
+/// Evaluate layouts on resampled (bootstrapped) ngram sets and report confidence intervals of
+/// the total and per-metric costs as well as a paired significance test for each pair of layouts.
+///
+/// All layouts are evaluated on the same resampled ngram sets, so differences between layouts
+/// are compared sample by sample.
+///
+/// Note that the unigrams, bigrams, and trigrams are resampled independently of each other
+/// (there is no corpus text to resample coherently), so the resampled ngram sets do not stem
+/// from one common resampled corpus. The intervals of metrics that combine several ngram sets
+/// are therefore only approximate.
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Read layouts from file and append to command line layouts
+    #[clap(long)]
+    from_file: Option<String>,
+
+    /// Number of bootstrap samples (at least one)
+    #[clap(long, default_value = "100", parse(try_from_str = parse_samples))]
+    samples: u64,
+
+    /// Confidence level of the reported intervals (and significance level of the test)
+    #[clap(long, default_value = "0.95")]
+    confidence: f64,
+
+    /// Number of ngrams per resampled corpus (defaults to the total ngram weight, i.e. the weights
+    /// are interpreted as ngram counts). Smaller sizes estimate the variation on smaller corpora
+    #[clap(long)]
+    sample_size: Option<f64>,
+
+    /// Seed of the first bootstrap sample
+    #[clap(long, default_value = "0")]
+    seed: u64,
+
+    /// Do not report confidence intervals of the individual metrics
+    #[clap(long)]
+    only_total_costs: bool,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+fn parse_samples(s: &str) -> Result<u64, String> {
+    let samples: u64 = s.parse().map_err(|e| format!("{}", e))?;
+    if samples > 0 {
+        Ok(samples)
+    } else {
+        Err("at least one bootstrap sample is required".to_string())
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ConfidenceInterval {
+    estimate: f64,
+    lower: f64,
+    upper: f64,
+    std_error: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct MetricInterval {
+    metric: String,
+    cost: ConfidenceInterval,
+}
+
+#[derive(Serialize, Debug)]
+struct LayoutIntervals {
+    layout: String,
+    total_cost: ConfidenceInterval,
+    metric_costs: Vec<MetricInterval>,
+}
+
+#[derive(Serialize, Debug)]
+struct LayoutComparison {
+    better: String,
+    worse: String,
+    /// Cost difference `worse - better`
+    difference: ConfidenceInterval,
+    /// Fraction of samples in which `better` is not better than `worse` (one-sided p-value)
+    p_value: f64,
+    significant: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct BootstrapReport {
+    samples: u64,
+    confidence: f64,
+    layouts: Vec<LayoutIntervals>,
+    comparisons: Vec<LayoutComparison>,
+}
+
+/// Total cost followed by the weighted costs of all individual metrics
+fn costs(result: &EvaluationResult) -> Vec<f64> {
+    let mut costs = vec![result.total_cost()];
+    costs.extend(
+        result
+            .iter()
+            .flat_map(|results| results.metric_costs.iter().map(|mc| mc.weighted_cost)),
+    );
+
+    costs
+}
+
+fn metric_names(result: &EvaluationResult) -> Vec<String> {
+    result
+        .iter()
+        .flat_map(|results| results.metric_costs.iter().map(|mc| mc.core.name.clone()))
+        .collect()
+}
+
+/// Percentile interval of the samples around the given point estimate (degenerates to the
+/// estimate itself without samples)
+fn interval(estimate: f64, samples: &[f64], confidence: f64) -> ConfidenceInterval {
+    if samples.is_empty() {
+        return ConfidenceInterval {
+            estimate,
+            lower: estimate,
+            upper: estimate,
+            std_error: 0.0,
+        };
+    }
+
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let quantile = |q: f64| {
+        let i = (q * (sorted.len() - 1) as f64).round() as usize;
+        sorted[i.min(sorted.len() - 1)]
+    };
+    let alpha = 1.0 - confidence;
+
+    let n = samples.len() as f64;
+    let mean = samples.iter().sum::<f64>() / n;
+    let variance = samples.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+
+    ConfidenceInterval {
+        estimate,
+        lower: quantile(0.5 * alpha),
+        upper: quantile(1.0 - 0.5 * alpha),
+        std_error: variance.sqrt(),
+    }
+}
+
+fn evaluate_all(evaluator: &Evaluator, layouts: &[(String, Layout)]) -> Vec<Vec<f64>> {
+    layouts
+        .iter()
+        .map(|(_, layout)| costs(&evaluator.evaluate_layout(layout)))
+        .collect()
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let layout_strings =
+        common::read_layout_strings(&options.layout_str, options.from_file.as_deref(), false);
+    let layouts: Vec<(String, Layout)> = layout_strings
+        .into_iter()
+        .filter_map(|layout_str| match layout_generator.generate(&layout_str) {
+            Ok(layout) => Some((layout_str, layout)),
+            Err(e) => {
+                log::error!("Error in generating layout {}: {:?}", layout_str, e);
+                None
+            }
+        })
+        .collect();
+    if layouts.is_empty() {
+        panic!("No valid layouts given");
+    }
+
+    let metrics = metric_names(&evaluator.evaluate_layout(&layouts[0].1));
+    let estimates = evaluate_all(&evaluator, &layouts);
+
+    log::info!("Evaluating {} bootstrap samples", options.samples);
+    // samples[s][l][m]: cost m (0 = total) of layout l in bootstrap sample s
+    let samples: Vec<Vec<Vec<f64>>> = (0..options.samples)
+        .into_par_iter()
+        .map(|s| {
+            evaluate_all(
+                &evaluator.bootstrap(options.sample_size, options.seed + s),
+                &layouts,
+            )
+        })
+        .collect();
+
+    let sample_costs =
+        |l: usize, m: usize| -> Vec<f64> { samples.iter().map(|s| s[l][m]).collect() };
+
+    let layout_intervals: Vec<LayoutIntervals> = layouts
+        .iter()
+        .enumerate()
+        .map(|(l, (layout_str, _))| LayoutIntervals {
+            layout: layout_str.clone(),
+            total_cost: interval(estimates[l][0], &sample_costs(l, 0), options.confidence),
+            metric_costs: metrics
+                .iter()
+                .enumerate()
+                .map(|(m, metric)| MetricInterval {
+                    metric: metric.clone(),
+                    cost: interval(
+                        estimates[l][m + 1],
+                        &sample_costs(l, m + 1),
+                        options.confidence,
+                    ),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..layouts.len()).collect();
+    order.sort_by(|a, b| estimates[*a][0].partial_cmp(&estimates[*b][0]).unwrap());
+
+    let mut comparisons = Vec::new();
+    for (i, better) in order.iter().enumerate() {
+        for worse in order.iter().skip(i + 1) {
+            let differences: Vec<f64> = samples
+                .iter()
+                .map(|s| s[*worse][0] - s[*better][0])
+                .collect();
+            let p_value =
+                differences.iter().filter(|d| **d <= 0.0).count() as f64 / differences.len() as f64;
+            comparisons.push(LayoutComparison {
+                better: layouts[*better].0.clone(),
+                worse: layouts[*worse].0.clone(),
+                difference: interval(
+                    estimates[*worse][0] - estimates[*better][0],
+                    &differences,
+                    options.confidence,
+                ),
+                p_value,
+                significant: p_value < 1.0 - options.confidence,
+            });
+        }
+    }
+
+    let report = BootstrapReport {
+        samples: options.samples,
+        confidence: options.confidence,
+        layouts: layout_intervals,
+        comparisons,
+    };
+
+    if options.json {
+        println!("{}", serde_json::to_string(&report).unwrap());
+        return;
+    }
+
+    let percent = 100.0 * options.confidence;
+    for l in order.iter() {
+        let intervals = &report.layouts[*l];
+        let total = &intervals.total_cost;
+        println!("Layout: {}", intervals.layout);
+        println!(
+            "  {:<36} {:>10.2}  {:.0}% CI [{:.2}, {:.2}]  (SE {:.2})",
+            "Total cost", total.estimate, percent, total.lower, total.upper, total.std_error
+        );
+        if !options.only_total_costs {
+            for mi in intervals.metric_costs.iter() {
+                println!(
+                    "  {:<36} {:>10.2}  {:.0}% CI [{:.2}, {:.2}]",
+                    mi.metric, mi.cost.estimate, percent, mi.cost.lower, mi.cost.upper
+                );
+            }
+        }
+        println!();
+    }
+
+    if !report.comparisons.is_empty() {
+        println!(
+            "Paired comparisons ({} samples, significance level {:.2}):",
+            options.samples,
+            1.0 - options.confidence
+        );
+        for c in report.comparisons.iter() {
+            println!(
+                "  {} < {}: difference {:.2}, {:.0}% CI [{:.2}, {:.2}], p = {:.3}{}",
+                c.better,
+                c.worse,
+                c.difference.estimate,
+                percent,
+                c.difference.lower,
+                c.difference.upper,
+                c.p_value,
+                if c.significant { " (significant)" } else { "" }
+            );
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use keyboard_layout::{key::Finger, layout::LayerKey};
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::metrics::bigram_metrics::scissoring::ScissorKind;
+
+use clap::Parser;
+use std::{collections::BTreeMap, fs};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Metric calibration from typing logs")]
+/// Fit average inter-key latencies from a log of real typing and suggest parameters for the
+/// `cluster_rolls`, `scissoring`, and `movement_pattern` metrics
+struct Options {
+    /// Layout that was used for typing the log
+    layout_str: String,
+
+    /// Typing log with one keystroke per line: "<timestamp in ms> <symbol>" (use "\n" for Enter)
+    #[clap(long)]
+    log: String,
+
+    /// Filename of layout configuration file to use
+    #[clap(short, long, default_value = "config/keyboard/sval.yml")]
+    layout_config: String,
+
+    /// Interpret the layout string using the "grouped" logic
+    #[clap(long)]
+    grouped_layout_generator: bool,
+
+    /// Ignore transitions slower than this (in ms), e.g. pauses between words or sentences
+    #[clap(long, default_value = "1000")]
+    max_interval: f64,
+
+    /// Only suggest a cost for transitions with at least this many samples
+    #[clap(long, default_value = "5")]
+    min_samples: usize,
+
+    /// Latency (in ms) above the hand switch baseline corresponding to one unit of cost
+    #[clap(long, default_value = "20")]
+    ms_per_cost: f64,
+
+    /// Write the median latency of each transition between two keys to this file (for the
+    /// `timing_matrix` of the typing speed model)
+    #[clap(long)]
+    timing_matrix: Option<String>,
+}
+
+/// A transition between two keys given by their matrix positions
+type Transition = ((u8, u8), (u8, u8));
+
+/// Median of the given latencies
+fn median(latencies: &[f64]) -> f64 {
+    let mut sorted = latencies.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let n = sorted.len();
+    if n % 2 == 0 {
+        0.5 * (sorted[n / 2 - 1] + sorted[n / 2])
+    } else {
+        sorted[n / 2]
+    }
+}
+
+/// Parse a log line of the form "<timestamp> <symbol>"
+fn parse_line(line: &str) -> Option<(f64, char)> {
+    let (timestamp, symbol) = line.split_once(' ')?;
+    let timestamp: f64 = timestamp.trim().parse().ok()?;
+    let symbol = symbol.replace("\\n", "\n").replace("\\t", "\t");
+
+    Some((timestamp, symbol.chars().next()?))
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let layout_generator = common::init_layout_generator(
+        &options.layout_config,
+        options.grouped_layout_generator,
+        &[],
+    )
+    .unwrap_or_else(|e| panic!("Could not load the layout config: {:?}", e));
+    let layout = layout_generator
+        .generate(&options.layout_str)
+        .unwrap_or_else(|e| panic!("Could not generate layout: {:?}", e));
+
+    let data = fs::read_to_string(&options.log)
+        .unwrap_or_else(|e| panic!("Could not read typing log {}: {:?}", options.log, e));
+    let keystrokes: Vec<(f64, &LayerKey)> = data
+        .lines()
+        .filter_map(parse_line)
+        .filter_map(|(t, c)| layout.get_layerkey_for_symbol(&c).map(|k| (t, k)))
+        .collect();
+
+    let mut hand_switches = Vec::new();
+    let mut cluster_rolls: BTreeMap<(String, String), Vec<f64>> = BTreeMap::new();
+    let mut scissors: BTreeMap<ScissorKind, Vec<f64>> = BTreeMap::new();
+    let mut movements: BTreeMap<(String, String, String), Vec<f64>> = BTreeMap::new();
+    let mut transitions: BTreeMap<Transition, Vec<f64>> = BTreeMap::new();
+
+    for window in keystrokes.windows(2) {
+        let (t1, k1) = window[0];
+        let (t2, k2) = window[1];
+        let latency = t2 - t1;
+        if latency <= 0.0 || latency > options.max_interval {
+            continue;
+        }
+
+        let (key1, key2) = (&k1.key, &k2.key);
+        let (p1, p2) = (key1.matrix_position, key2.matrix_position);
+        transitions
+            .entry(((p1.0, p1.1), (p2.0, p2.1)))
+            .or_default()
+            .push(latency);
+
+        if key1.hand != key2.hand {
+            hand_switches.push(latency);
+            continue;
+        }
+        if key1.finger == Finger::Thumb || key2.finger == Finger::Thumb {
+            continue;
+        }
+
+        if key1.finger == key2.finger {
+            if key1.matrix_position != key2.matrix_position {
+                cluster_rolls
+                    .entry((
+                        format!("{:?}", key1.direction),
+                        format!("{:?}", key2.direction),
+                    ))
+                    .or_default()
+                    .push(latency);
+            }
+            continue;
+        }
+
+        movements
+            .entry((
+                format!("{:?}", key1.hand),
+                format!("{:?}", key1.finger),
+                format!("{:?}", key2.finger),
+            ))
+            .or_default()
+            .push(latency);
+
+        if let Some(kind) = ScissorKind::of(k1, k2) {
+            scissors.entry(kind).or_default().push(latency);
+        }
+    }
+
+    if hand_switches.len() < options.min_samples {
+        panic!(
+            "Not enough hand switches in the typing log to determine a baseline ({} < {})",
+            hand_switches.len(),
+            options.min_samples
+        );
+    }
+
+    let baseline = median(&hand_switches);
+    let cost = |latencies: &[f64]| ((median(latencies) - baseline) / options.ms_per_cost).max(0.0);
+    let enough = |latencies: &&Vec<f64>| latencies.len() >= options.min_samples;
+
+    println!(
+        "# Calibrated from {} keystrokes; hand switch baseline: {:.1} ms ({} samples)",
+        keystrokes.len(),
+        baseline,
+        hand_switches.len()
+    );
+    println!(
+        "# One unit of cost corresponds to {} ms above the baseline",
+        options.ms_per_cost
+    );
+    println!(
+        "# Typing speed model: base_ms: {:.1}, ms_per_cost: {}",
+        baseline, options.ms_per_cost
+    );
+    println!();
+
+    if let Some(filename) = &options.timing_matrix {
+        let mut matrix = String::from("# Median latencies of transitions between two keys\n");
+        for ((from, to), latencies) in transitions.iter() {
+            matrix.push_str(&format!(
+                "- {{ from: [{}, {}], to: [{}, {}], ms: {:.1}, samples: {} }}\n",
+                from.0,
+                from.1,
+                to.0,
+                to.1,
+                median(latencies),
+                latencies.len()
+            ));
+        }
+        fs::write(filename, matrix)
+            .unwrap_or_else(|e| panic!("Could not write timing matrix {}: {:?}", filename, e));
+        log::info!(
+            "Wrote {} transitions to timing matrix {}",
+            transitions.len(),
+            filename
+        );
+    }
+
+    println!("cluster_rolls:");
+    println!("  params:");
+    println!("    costs:");
+    let mut last_from = String::new();
+    for ((from, to), latencies) in cluster_rolls.iter().filter(|(_, l)| enough(l)) {
+        if *from != last_from {
+            println!("      {}:", from);
+            last_from = from.clone();
+        }
+        println!(
+            "        {}: {:.1} # {:.1} ms, {} samples",
+            to,
+            cost(latencies),
+            median(latencies),
+            latencies.len()
+        );
+    }
+    println!();
+
+    println!("scissoring:");
+    println!("  params:");
+    for (kind, latencies) in scissors.iter().filter(|(_, l)| enough(l)) {
+        println!(
+            "    {}: {:.1} # {:.1} ms, {} samples",
+            kind.cost_parameter(),
+            cost(latencies),
+            median(latencies),
+            latencies.len()
+        );
+    }
+    println!();
+
+    println!("movement_pattern:");
+    println!("  params:");
+    println!("    finger_switch_factor:");
+    for ((hand, from, to), latencies) in movements.iter().filter(|(_, l)| enough(l)) {
+        println!(
+            "      - {{ from: [{}, {}], to: [{}, {}], cost: {:.1} }} # {:.1} ms, {} samples",
+            hand,
+            from,
+            hand,
+            to,
+            cost(latencies),
+            median(latencies),
+            latencies.len()
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use keyboard_layout_optimizer::{
+    common,
+    corpus_profiles::{CategoryCost, CategoryReport, CorpusProfiles},
+    logging,
+};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout evaluation per application category")]
+/// Evaluate layouts on the corpora of several application categories (e.g. editor, terminal,
+/// chat, browser) and report the costs per category as well as combined
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Filename of the config file tagging the corpus sources with categories
+    #[clap(long, default_value = "config/corpus/categories.yml")]
+    categories: String,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let profiles = CorpusProfiles::from_yaml(&options.categories).unwrap_or_else(|e| {
+        panic!(
+            "Could not read categories file {}: {:?}",
+            options.categories, e
+        )
+    });
+
+    let layout_generator = common::init_layout_generator(
+        &options.general_parameters.layout_config,
+        options.general_parameters.grouped_layout_generator,
+        &options.general_parameters.layers,
+    )
+    .unwrap_or_else(|e| panic!("Could not load the layout config: {:?}", e));
+    let evaluators = common::init_category_evaluators(&options.general_parameters, &profiles)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let reports: Vec<CategoryReport> = options
+        .layout_str
+        .iter()
+        .map(|s| s.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+        .filter_map(|layout_str| match layout_generator.generate(&layout_str) {
+            Ok(layout) => {
+                let categories = evaluators
+                    .iter()
+                    .map(|(category, weight, evaluator)| {
+                        CategoryCost::new(category, *weight, &evaluator.evaluate_layout(&layout))
+                    })
+                    .collect();
+
+                Some(CategoryReport::new(&layout_str, categories))
+            }
+            Err(e) => {
+                log::error!("Error in generating layout {}: {:?}", layout_str, e);
+                None
+            }
+        })
+        .collect();
+
+    if options.json {
+        println!("{}", serde_json::to_string(&reports).unwrap());
+        return;
+    }
+
+    for report in reports {
+        println!("{}", report);
+    }
+}
@@ -0,0 +1,188 @@
+use keyboard_layout::layout::Layout;
+use keyboard_layout_optimizer::{clustering, common, logging};
+
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout clustering")]
+/// Group similar layouts (e.g. the results of many optimization runs) into families and report
+/// a representative (the layout with the lowest cost) of each family.
+///
+/// Layouts are compared by their swap distance (the number of key swaps that turn one layout into
+/// the other) or by their finger-assignment distance (the number of symbols typed with a
+/// different finger).
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Read layouts from file and append to command line layouts
+    #[clap(long)]
+    from_file: Option<String>,
+
+    /// Compare layouts by the number of symbols typed with a different finger
+    #[clap(long)]
+    finger_distance: bool,
+
+    /// Number of families to form
+    #[clap(long)]
+    clusters: Option<usize>,
+
+    /// Largest average distance between layouts of a family (if no number of families is given)
+    #[clap(long, default_value = "6")]
+    max_distance: f64,
+
+    /// Also list all members of each family
+    #[clap(long)]
+    members: bool,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct FamilyMember {
+    layout: String,
+    total_cost: f64,
+    /// Distance to the representative
+    distance: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct Family {
+    representative: String,
+    total_cost: f64,
+    /// The member with the smallest summed distance to all other members
+    medoid: String,
+    /// The largest distance between two members
+    diameter: f64,
+    members: Vec<FamilyMember>,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let mut layout_strings =
+        common::read_layout_strings(&options.layout_str, options.from_file.as_deref(), false);
+    layout_strings.sort();
+    layout_strings.dedup();
+
+    let layouts: Vec<(String, Layout, f64)> = layout_strings
+        .into_par_iter()
+        .filter_map(|layout_str| match layout_generator.generate(&layout_str) {
+            Ok(layout) => {
+                let total_cost = evaluator.evaluate_layout(&layout).total_cost();
+                Some((layout_str, layout, total_cost))
+            }
+            Err(e) => {
+                log::error!("Error in generating layout {}: {:?}", layout_str, e);
+                None
+            }
+        })
+        .collect();
+    if layouts.is_empty() {
+        panic!("No valid layouts given");
+    }
+
+    let distances: Vec<Vec<f64>> = layouts
+        .par_iter()
+        .map(|(_, l1, _)| {
+            layouts
+                .iter()
+                .map(|(_, l2, _)| match options.finger_distance {
+                    true => l1.finger_distance(l2) as f64,
+                    false => l1.swap_distance(l2) as f64,
+                })
+                .collect()
+        })
+        .collect();
+
+    let max_distance = match options.clusters {
+        Some(_) => None,
+        None => Some(options.max_distance),
+    };
+    let clusters = clustering::cluster(&distances, options.clusters, max_distance);
+
+    let mut families: Vec<Family> = clusters
+        .iter()
+        .map(|members| {
+            let representative = *members
+                .iter()
+                .min_by(|i, j| layouts[**i].2.partial_cmp(&layouts[**j].2).unwrap())
+                .unwrap();
+            let mut family_members: Vec<FamilyMember> = members
+                .iter()
+                .map(|i| FamilyMember {
+                    layout: layouts[*i].0.clone(),
+                    total_cost: layouts[*i].2,
+                    distance: distances[representative][*i],
+                })
+                .collect();
+            family_members.sort_by(|a, b| a.total_cost.partial_cmp(&b.total_cost).unwrap());
+
+            Family {
+                representative: layouts[representative].0.clone(),
+                total_cost: layouts[representative].2,
+                medoid: layouts[clustering::medoid(&distances, members)].0.clone(),
+                diameter: clustering::diameter(&distances, members),
+                members: family_members,
+            }
+        })
+        .collect();
+    families.sort_by(|a, b| a.total_cost.partial_cmp(&b.total_cost).unwrap());
+
+    if options.json {
+        println!("{}", serde_json::to_string(&families).unwrap());
+        return;
+    }
+
+    println!(
+        "{} layouts in {} families ({} distance)",
+        layouts.len(),
+        families.len(),
+        if options.finger_distance {
+            "finger-assignment"
+        } else {
+            "swap"
+        }
+    );
+    println!();
+    for (i, family) in families.iter().enumerate() {
+        let worst = family.members.last().map_or(0.0, |m| m.total_cost);
+        println!(
+            "Family {} ({} layouts, diameter {:.0}, cost {:.2} - {:.2})",
+            i + 1,
+            family.members.len(),
+            family.diameter,
+            family.total_cost,
+            worst
+        );
+        println!("  Representative: {}", family.representative);
+        if family.medoid != family.representative {
+            println!("  Medoid:         {}", family.medoid);
+        }
+        if options.members {
+            for m in family.members.iter() {
+                println!(
+                    "    {:>10.2} {:>4.0}  {}",
+                    m.total_cost, m.distance, m.layout
+                );
+            }
+        }
+        println!();
+    }
+}
@@ -0,0 +1,160 @@
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::results::EvaluationResult;
+
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+
+/// A keyboard to evaluate layouts on
+#[derive(Deserialize, Debug)]
+struct KeyboardDefinition {
+    name: String,
+    layout_config: String,
+    eval_parameters: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct KeyboardDefinitions {
+    keyboards: Vec<KeyboardDefinition>,
+}
+
+#[derive(Serialize)]
+struct KeyboardEvaluation {
+    keyboard: String,
+    total_cost: Option<f64>,
+    error: Option<String>,
+    details: Option<EvaluationResult>,
+}
+
+#[derive(Serialize)]
+struct LayoutComparison {
+    layout: String,
+    keyboards: Vec<KeyboardEvaluation>,
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout comparison")]
+/// Evaluate layouts on several keyboards and report the costs for each keyboard
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Filename of the config file defining the keyboards to compare
+    #[clap(long, default_value = "config/comparison/keyboards.yml")]
+    keyboards: String,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+
+    /// Print the detailed evaluation for each keyboard
+    #[clap(long)]
+    details: bool,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let f = File::open(&options.keyboards).unwrap_or_else(|e| {
+        panic!(
+            "Could not open keyboards file {}: {:?}",
+            options.keyboards, e
+        )
+    });
+    let definitions: KeyboardDefinitions = serde_yaml::from_reader(f).unwrap_or_else(|e| {
+        panic!(
+            "Could not read keyboards file {}: {:?}",
+            options.keyboards, e
+        )
+    });
+
+    let layout_strings: Vec<String> = options
+        .layout_str
+        .iter()
+        .map(|s| s.chars().filter(|c| !c.is_whitespace()).collect())
+        .collect();
+
+    let mut comparisons: Vec<LayoutComparison> = layout_strings
+        .iter()
+        .map(|layout_str| LayoutComparison {
+            layout: layout_str.clone(),
+            keyboards: Vec::new(),
+        })
+        .collect();
+
+    for keyboard in definitions.keyboards.iter() {
+        log::info!("Evaluating on keyboard '{}'", keyboard.name);
+        let mut general_parameters = options.general_parameters.clone();
+        general_parameters.layout_config = keyboard.layout_config.clone();
+        if let Some(eval_parameters) = &keyboard.eval_parameters {
+            general_parameters.eval_parameters = eval_parameters.clone();
+        }
+        let (layout_generator, evaluator) = match common::init(&general_parameters) {
+            Ok(init) => init,
+            Err(e) => {
+                log::error!("Could not load keyboard '{}': {:?}", keyboard.name, e);
+                for comparison in comparisons.iter_mut() {
+                    comparison.keyboards.push(KeyboardEvaluation {
+                        keyboard: keyboard.name.clone(),
+                        total_cost: None,
+                        error: Some(format!("could not be loaded: {:#}", e)),
+                        details: None,
+                    });
+                }
+                continue;
+            }
+        };
+
+        for (layout_str, comparison) in layout_strings.iter().zip(comparisons.iter_mut()) {
+            let evaluation = match layout_generator.generate(layout_str) {
+                Ok(layout) => {
+                    let result = evaluator.evaluate_layout(&layout);
+                    KeyboardEvaluation {
+                        keyboard: keyboard.name.clone(),
+                        total_cost: Some(result.total_cost()),
+                        error: None,
+                        details: Some(result),
+                    }
+                }
+                Err(e) => KeyboardEvaluation {
+                    keyboard: keyboard.name.clone(),
+                    total_cost: None,
+                    error: Some(format!("incompatible: {:?}", e)),
+                    details: None,
+                },
+            };
+            comparison.keyboards.push(evaluation);
+        }
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string(&comparisons).unwrap());
+        return;
+    }
+
+    for comparison in comparisons {
+        println!("Layout: {}", comparison.layout);
+        for evaluation in comparison.keyboards {
+            match (evaluation.total_cost, &evaluation.error) {
+                (Some(cost), _) => println!("  {:<40} {:>10.2}", evaluation.keyboard, cost),
+                (None, Some(e)) => println!("  {:<40} {}", evaluation.keyboard, e),
+                _ => {}
+            }
+            if options.details {
+                if let Some(details) = evaluation.details {
+                    println!("{}", details);
+                }
+            }
+        }
+        println!();
+    }
+}
@@ -1,31 +1,64 @@
 use keyboard_layout::layout::Layout;
-use keyboard_layout_optimizer::common;
-use layout_evaluation::{cache::Cache, results::EvaluationResult};
+use keyboard_layout_optimizer::{
+    common::{self, ConfigMetadata},
+    logging,
+};
+use layout_evaluation::{
+    cache::Cache,
+    evaluation::Evaluator,
+    results::EvaluationResult,
+    typing_speed::{TypingSpeedEstimate, TypingSpeedModel},
+};
 
 use clap::Parser;
-use rayon::prelude::*;
+use rayon::{iter::ParallelBridge, prelude::*};
 use serde::Serialize;
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    panic::{self, AssertUnwindSafe},
+    thread,
+    time::{Duration, SystemTime},
 };
 
 #[derive(Serialize)]
-struct LayoutEvaluation {
+struct LayoutEvaluation<'a> {
     details: EvaluationResult,
     total_cost: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    typing_speed: Option<TypingSpeedEstimate>,
+    config: &'a ConfigMetadata,
 }
 
-impl From<EvaluationResult> for LayoutEvaluation {
-    fn from(details: EvaluationResult) -> Self {
+impl<'a> LayoutEvaluation<'a> {
+    fn new(
+        details: EvaluationResult,
+        typing_speed: Option<TypingSpeedEstimate>,
+        config: &'a ConfigMetadata,
+    ) -> Self {
         let total_cost = details.total_cost();
         Self {
             details,
             total_cost,
+            typing_speed,
+            config,
         }
     }
 }
 
+/// A single line of the NDJSON output
+#[derive(Serialize)]
+struct StreamedEvaluation {
+    layout: String,
+    total_cost: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    typing_speed: Option<TypingSpeedEstimate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<EvaluationResult>,
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout evaluation")]
 struct Options {
@@ -55,42 +88,255 @@ struct Options {
     /// Sort results by total costs
     #[clap(long)]
     sort: bool,
+
+    /// Read layouts from stdin (one per line) and write one JSON object per line as soon as
+    /// each layout is evaluated (in parallel, so the output order may differ from the input)
+    #[clap(long)]
+    ndjson: bool,
+
+    /// Re-run the evaluation whenever the configuration files or the layouts file change and
+    /// print the differences to the previous evaluation
+    #[clap(long)]
+    watch: bool,
+
+    /// Interval (in milliseconds) for checking watched files for changes
+    #[clap(long, default_value = "500")]
+    watch_interval: u64,
+
+    /// Also estimate the typing speed (WPM) with the model from this file, e.g.
+    /// "config/typing/speed.yml"
+    #[clap(long)]
+    typing_speed: Option<String>,
+}
+
+/// Read the typing speed model (if requested).
+fn init_typing_speed_model(options: &Options) -> Option<TypingSpeedModel> {
+    let filename = options.typing_speed.as_ref()?;
+    let model = TypingSpeedModel::from_yaml(filename)
+        .unwrap_or_else(|e| panic!("Could not read typing speed model {}: {:?}", filename, e));
+
+    Some(model)
+}
+
+/// Warn about bigram metrics of the typing speed model that are not part of the evaluation.
+fn check_typing_speed_metrics(model: &TypingSpeedModel, result: &EvaluationResult) {
+    let costs = result.metric_costs();
+    for metric in model.params.metrics.iter() {
+        if !costs.iter().any(|(name, _)| name == metric) {
+            log::warn!(
+                "Typing speed model: metric '{}' is not enabled, its costs are not considered",
+                metric
+            );
+        }
+    }
+}
+
+/// Estimate the typing speed of the layout (if a model is given).
+fn typing_speed(
+    model: Option<&TypingSpeedModel>,
+    evaluator: &Evaluator,
+    layout: &Layout,
+) -> Option<TypingSpeedEstimate> {
+    model.map(|model| evaluator.estimate_typing_speed(layout, model))
+}
+
+/// Print the total cost and all metric costs that changed compared to the previous evaluation.
+fn print_diff(layout_str: &str, result: &EvaluationResult, previous: Option<&EvaluationResult>) {
+    let total_cost = result.total_cost();
+    let previous = match previous {
+        Some(previous) => previous,
+        None => {
+            println!("{} {:.2}", layout_str, total_cost);
+            return;
+        }
+    };
+
+    let previous_total_cost = previous.total_cost();
+    println!(
+        "{} {:.2} ({:+.2})",
+        layout_str,
+        total_cost,
+        total_cost - previous_total_cost
+    );
+
+    let previous_costs = previous.metric_costs();
+    let costs = result.metric_costs();
+    for (name, cost) in costs.iter() {
+        match previous_costs.iter().find(|(n, _)| n == name) {
+            Some((_, previous_cost)) if (cost - previous_cost).abs() > 1e-6 => println!(
+                "  {:<35} {:>8.2} -> {:>8.2} ({:+.2})",
+                name,
+                previous_cost,
+                cost,
+                cost - previous_cost
+            ),
+            Some(_) => {}
+            None => println!("  {:<35} {:>8} -> {:>8.2} (added)", name, "", cost),
+        }
+    }
+    for (name, previous_cost) in previous_costs.iter() {
+        if !costs.iter().any(|(n, _)| n == name) {
+            println!(
+                "  {:<35} {:>8.2} -> {:>8} (removed)",
+                name, previous_cost, ""
+            );
+        }
+    }
+}
+
+/// Evaluate the layouts each time one of the configuration files or the layouts file changes.
+fn watch(options: &Options) {
+    let modification_times = || -> Vec<Option<SystemTime>> {
+        // the files extended by the evaluation configuration may change while editing
+        let mut watched_files = common::eval_parameter_files(&options.general_parameters);
+        watched_files.push(options.general_parameters.layout_config.clone());
+        if let Some(filename) = &options.from_file {
+            watched_files.push(filename.clone());
+        }
+
+        watched_files
+            .iter()
+            .map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+            .collect()
+    };
+
+    let mut previous_results: Vec<(String, EvaluationResult)> = Vec::new();
+    let mut last_modified = None;
+    let mut run = 0;
+    loop {
+        let modified = modification_times();
+        if last_modified.as_ref() == Some(&modified) {
+            thread::sleep(Duration::from_millis(options.watch_interval));
+            continue;
+        }
+        last_modified = Some(modified);
+
+        // invalid configurations (e.g. while editing) panic; wait for the next change then
+        let results = panic::catch_unwind(AssertUnwindSafe(|| {
+            let (layout_generator, evaluator) = common::init(&options.general_parameters)
+                .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+            common::read_layout_strings(
+                &options.layout_str,
+                options.from_file.as_deref(),
+                options.do_not_remove_whitespace,
+            )
+            .into_par_iter()
+            .filter_map(|layout_str| match layout_generator.generate(&layout_str) {
+                Ok(layout) => Some((layout_str, evaluator.evaluate_layout(&layout))),
+                Err(e) => {
+                    println!("{}: Error in generating layout: {:?}", layout_str, e);
+                    None
+                }
+            })
+            .collect::<Vec<(String, EvaluationResult)>>()
+        }));
+
+        let results = match results {
+            Ok(results) => results,
+            Err(_) => {
+                println!("Evaluation failed, waiting for changes...\n");
+                continue;
+            }
+        };
+
+        run += 1;
+        println!("=== Evaluation #{} ===", run);
+        for (layout_str, result) in results.iter() {
+            let previous = previous_results
+                .iter()
+                .find(|(l, _)| l == layout_str)
+                .map(|(_, r)| r);
+            print_diff(layout_str, result, previous);
+        }
+        println!();
+
+        previous_results = results;
+    }
 }
 
 fn main() {
     dotenv::dotenv().ok();
     let options = Options::parse();
-    if !options.json {
+    if !options.json && !options.ndjson {
         // if the "json" option is set, we do not want any other log messages
-        env_logger::init();
+        logging::init();
     }
 
-    let (layout_generator, evaluator) = common::init(&options.general_parameters);
+    if options.watch {
+        watch(&options);
+        return;
+    }
 
-    // collect layout strings to a vec
-    let mut layout_strings = options.layout_str.to_vec();
-    if let Some(filename) = &options.from_file {
-        match File::open(filename) {
-            Ok(file) => {
-                layout_strings.append(&mut BufReader::new(file).lines().flatten().collect());
-            }
-            Err(e) => {
-                log::error!("Error reading layouts file {}: {:?}", filename, e);
-                panic!("{:?}", e);
-            }
-        }
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let typing_speed_model = init_typing_speed_model(&options);
+
+    if options.ndjson {
+        // layouts are pulled from stdin as workers become available and are not kept in memory
+        BufReader::new(io::stdin())
+            .lines()
+            .map_while(Result::ok)
+            .par_bridge()
+            .for_each(|line| {
+                let layout_str: String = line
+                    .chars()
+                    .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+                    .collect();
+                if layout_str.is_empty() {
+                    return;
+                }
+
+                let evaluation = match layout_generator.generate(&layout_str) {
+                    Ok(layout) => {
+                        let evaluation_result = evaluator.evaluate_layout(&layout);
+                        StreamedEvaluation {
+                            layout: layout_str,
+                            total_cost: Some(evaluation_result.total_cost()),
+                            typing_speed: typing_speed(
+                                typing_speed_model.as_ref(),
+                                &evaluator,
+                                &layout,
+                            ),
+                            error: None,
+                            details: (!options.only_total_costs).then_some(evaluation_result),
+                        }
+                    }
+                    Err(e) => StreamedEvaluation {
+                        layout: layout_str,
+                        total_cost: None,
+                        typing_speed: None,
+                        error: Some(format!("{:?}", e)),
+                        details: None,
+                    },
+                };
+
+                let line = serde_json::to_string(&evaluation).unwrap();
+                // a closed stdout (e.g. a downstream `head`) ends the stream
+                if writeln!(io::stdout().lock(), "{}", line).is_err() {
+                    std::process::exit(0);
+                }
+            });
+
+        return;
     }
 
-    let result_cache: Cache<EvaluationResult> = Cache::new();
+    // collect layout strings to a vec
+    let layout_strings = common::read_layout_strings(
+        &options.layout_str,
+        options.from_file.as_deref(),
+        options.do_not_remove_whitespace,
+    );
+
+    let result_cache: Cache<EvaluationResult> =
+        match common::init_result_cache(&options.general_parameters) {
+            Some(store) => Cache::new().with_store(store),
+            None => Cache::new(),
+        };
 
     // evaluate layouts
     let mut results: Vec<(String, Layout, EvaluationResult)> = layout_strings
-        .par_iter()
+        .into_par_iter()
         .map(|layout_str| {
-            let layout_str: String = layout_str
-                .chars()
-                .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
-                .collect();
             let layout = match layout_generator.generate(&layout_str) {
                 Ok(layout) => layout,
                 Err(e) => {
@@ -104,6 +350,10 @@ fn main() {
         })
         .collect();
 
+    if let (Some(model), Some((_, _, evaluation_result))) = (&typing_speed_model, results.first()) {
+        check_typing_speed_metrics(model, evaluation_result);
+    }
+
     // sort if required
     if options.sort {
         results.sort_by(|(_, _, c1), (_, _, c2)| {
@@ -113,17 +363,43 @@ fn main() {
 
     // print results
     if options.json {
-        let results: Vec<LayoutEvaluation> =
-            results.into_iter().map(|(_, _, res)| res.into()).collect();
+        let config = ConfigMetadata::new(&options.general_parameters);
+        let results: Vec<LayoutEvaluation> = results
+            .into_iter()
+            .map(|(_, layout, res)| {
+                let speed = typing_speed(typing_speed_model.as_ref(), &evaluator, &layout);
+                LayoutEvaluation::new(res, speed, &config)
+            })
+            .collect();
         println!("{}", serde_json::to_string(&results).unwrap());
     } else {
         for (layout_str, layout, evaluation_result) in results {
             if !options.only_total_costs {
-                println!("Layout (layer 1):\n{}", layout.plot_layer(0));
+                let usage = evaluator.key_usage(&layout);
+                let max_layer = layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0);
+                for layer in 0..max_layer + 1 {
+                    println!(
+                        "Layout (layer {}):\n{}\n",
+                        layer + 1,
+                        layout.plot_layer_clusters(layer as usize, Some(&usage))
+                    );
+                }
                 println!("Layout string (layer 1):\n{}\n", layout);
                 println!("{}", evaluation_result);
+                if let Some(speed) = typing_speed(typing_speed_model.as_ref(), &evaluator, &layout)
+                {
+                    println!("{}\n", speed);
+                }
             } else {
-                println!("{} {:4.2}", layout_str, evaluation_result.total_cost());
+                match typing_speed(typing_speed_model.as_ref(), &evaluator, &layout) {
+                    Some(speed) => println!(
+                        "{} {:4.2} {:.1} WPM",
+                        layout_str,
+                        evaluation_result.total_cost(),
+                        speed.wpm
+                    ),
+                    None => println!("{} {:4.2}", layout_str, evaluation_result.total_cost()),
+                }
             }
         }
     }
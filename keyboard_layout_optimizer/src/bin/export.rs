@@ -0,0 +1,205 @@
+use clap::Parser;
+use std::{fs, path::Path};
+
+use keyboard_layout::layout::Layout;
+use keyboard_layout_optimizer::{
+    common,
+    export::{cheat_sheet, image, kanata, keymap_drawer, xkb, KeycodeMap},
+    logging,
+};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout export")]
+/// Export a layout as configuration of other tools.
+///
+/// Formats:
+/// "xkb" (XKB symbols file with the base and shift layers),
+/// "xkb-keymap" (complete keymap for loading with `xkbcomp`),
+/// "kanata" and "kmonad" (configuration with all layers for software remapping),
+/// "svg" and "png" (picture of the keyboard with the legends of the layers, PNG requires
+/// `rsvg-convert`),
+/// "keymap-drawer" (YAML keymap with all layers for rendering with keymap-drawer),
+/// "cheat-sheet" (printable one-page PDF with all layers and color-coded fingers, or an SVG
+/// picture if the output file ends with ".svg")
+struct Options {
+    /// Layout keys from left to right, top to bottom
+    layout_str: String,
+
+    /// Export format
+    #[clap(short, long, default_value = "xkb")]
+    format: String,
+
+    /// Write the export to this file instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Name of the exported layout (for XKB and as title of pictures)
+    #[clap(long, default_value = "Optimized layout")]
+    name: String,
+
+    /// Layer that is accessed by holding shift (for XKB)
+    #[clap(long, default_value = "1")]
+    shift_layer: u8,
+
+    /// Layers to draw, numbered from 1 (for pictures, default: all)
+    #[clap(long)]
+    layers: Vec<u8>,
+
+    /// Draw the layers as separate boards side by side instead of stacking their legends on
+    /// each key (for pictures)
+    #[clap(long)]
+    side_by_side: bool,
+
+    /// QMK keyboard providing the physical layout (for keymap-drawer, default: write a QMK
+    /// `info.json` with the key positions of the layout config next to the output file)
+    #[clap(long)]
+    qmk_keyboard: Option<String>,
+
+    /// File with the XKB key names of the keyboard's matrix positions (for XKB, Kanata, and KMonad;
+    /// use `config/export/xkb_keycodes.yml` for the standard keyboards)
+    #[clap(long, default_value = "config/export/sval_keycodes.yml")]
+    xkb_keycodes: String,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Filename of layout configuration file to use
+    #[clap(short, long, default_value = "config/keyboard/sval.yml")]
+    layout_config: String,
+
+    /// Interpred given layout string using the "grouped" logic
+    #[clap(long)]
+    pub grouped_layout_generator: bool,
+}
+
+fn export_image(layout: &Layout, options: &Options) {
+    let layers: Vec<u8> = match options.layers.is_empty() {
+        true => {
+            let max_layer = layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0);
+            (0..max_layer + 1).collect()
+        }
+        false => options.layers.iter().map(|l| l.saturating_sub(1)).collect(),
+    };
+    let arrangement = match options.side_by_side {
+        true => image::Arrangement::SideBySide,
+        false => image::Arrangement::Stacked,
+    };
+    let svg = image::svg(layout, &layers, arrangement, &options.name);
+
+    match (options.format.as_str(), &options.output) {
+        ("png", Some(filename)) => {
+            image::png(&svg, filename)
+                .unwrap_or_else(|e| panic!("Could not write PNG to {}: {:?}", filename, e));
+            log::info!("Exported layout to '{}'", filename);
+        }
+        ("png", None) => panic!("PNG export requires an output file"),
+        (_, Some(filename)) => {
+            fs::write(filename, svg)
+                .unwrap_or_else(|e| panic!("Could not write to {}: {:?}", filename, e));
+            log::info!("Exported layout to '{}'", filename);
+        }
+        (_, None) => print!("{}", svg),
+    }
+}
+
+fn export_cheat_sheet(layout: &Layout, options: &Options) {
+    let filename = options
+        .output
+        .as_ref()
+        .expect("Cheat sheet export requires an output file");
+    let result = match filename.ends_with(".svg") {
+        true => fs::write(filename, cheat_sheet::svg(layout, &options.name)).map_err(|e| e.into()),
+        false => cheat_sheet::pdf(layout, &options.name, filename),
+    };
+    result.unwrap_or_else(|e| panic!("Could not write cheat sheet to {}: {:?}", filename, e));
+    log::info!("Exported cheat sheet to '{}'", filename);
+}
+
+fn write_output(exported: &str, options: &Options) {
+    match &options.output {
+        Some(filename) => {
+            fs::write(filename, exported)
+                .unwrap_or_else(|e| panic!("Could not write to {}: {:?}", filename, e));
+            log::info!("Exported layout to '{}'", filename);
+        }
+        None => print!("{}", exported),
+    }
+}
+
+fn export_keymap_drawer(layout: &Layout, options: &Options) -> String {
+    let physical_layout = match (&options.qmk_keyboard, &options.output) {
+        (Some(keyboard), _) => keymap_drawer::PhysicalLayout::QmkKeyboard(keyboard.clone()),
+        (None, Some(filename)) => {
+            let info_filename = Path::new(filename)
+                .with_extension("json")
+                .to_string_lossy()
+                .to_string();
+            fs::write(&info_filename, keymap_drawer::info_json(layout))
+                .unwrap_or_else(|e| panic!("Could not write to {}: {:?}", info_filename, e));
+            log::info!("Exported physical layout to '{}'", info_filename);
+            keymap_drawer::PhysicalLayout::QmkInfoJson(info_filename)
+        }
+        (None, None) => panic!("keymap-drawer export requires an output file or a QMK keyboard"),
+    };
+
+    keymap_drawer::keymap(layout, &physical_layout)
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    logging::init();
+    let options = Options::parse();
+
+    let layout_str: String = options
+        .layout_str
+        .chars()
+        .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+        .collect();
+    let layout_generator = common::init_layout_generator(
+        &options.layout_config,
+        options.grouped_layout_generator,
+        &[],
+    )
+    .unwrap_or_else(|e| panic!("Could not load the layout config: {:?}", e));
+
+    let layout = match layout_generator.generate(&layout_str) {
+        Ok(layout) => layout,
+        Err(e) => {
+            log::error!("{:?}", e);
+            panic!("{:?}", e);
+        }
+    };
+
+    if options.format == "cheat-sheet" {
+        export_cheat_sheet(&layout, &options);
+        return;
+    }
+
+    if matches!(options.format.as_str(), "svg" | "png") {
+        export_image(&layout, &options);
+        return;
+    }
+
+    if options.format == "keymap-drawer" {
+        write_output(&export_keymap_drawer(&layout, &options), &options);
+        return;
+    }
+
+    let keycodes = KeycodeMap::from_yaml(&options.xkb_keycodes).unwrap_or_else(|e| {
+        panic!(
+            "Could not read XKB keycodes from {}: {:?}",
+            options.xkb_keycodes, e
+        )
+    });
+    let exported = match options.format.as_str() {
+        "xkb" => xkb::symbols(&layout, &keycodes, &options.name, options.shift_layer),
+        "xkb-keymap" => xkb::keymap(&layout, &keycodes, &options.name, options.shift_layer),
+        "kanata" => kanata::config(&layout, &keycodes, kanata::Dialect::Kanata),
+        "kmonad" => kanata::config(&layout, &keycodes, kanata::Dialect::KMonad),
+        format => panic!("Unknown export format '{}'", format),
+    }
+    .unwrap_or_else(|e| panic!("Could not export layout: {:?}", e));
+
+    write_output(&exported, &options);
+}
@@ -0,0 +1,135 @@
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::results::KeyCosts;
+
+use clap::Parser;
+use std::fs;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout heatmap data")]
+/// Export the cost of a metric attributed to each physical key (and each key pair for bigram
+/// metrics) as JSON or CSV, e.g. for showing where a metric's cost concentrates on the board.
+///
+/// The cost of each ngram is split evenly among its keys.
+struct Options {
+    /// Layout keys from left to right, top to bottom
+    layout_str: String,
+
+    /// Name of the metric (as shown in the evaluation)
+    #[clap(short, long)]
+    metric: String,
+
+    /// Export the costs of key pairs instead of keys (for CSV, JSON always contains both)
+    #[clap(long)]
+    pairs: bool,
+
+    /// Export as CSV instead of JSON
+    #[clap(long)]
+    csv: bool,
+
+    /// Write the export to this file instead of stdout
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+}
+
+fn csv_field(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+fn to_csv(key_costs: &KeyCosts, pairs: bool) -> String {
+    let mut lines = Vec::new();
+    if pairs {
+        lines.push("from_col,from_row,to_col,to_row,symbols,cost,share".to_string());
+        for p in key_costs.key_pairs.iter() {
+            lines.push(format!(
+                "{},{},{},{},{},{},{}",
+                p.from.0,
+                p.from.1,
+                p.to.0,
+                p.to.1,
+                csv_field(&p.symbols),
+                p.cost,
+                p.share
+            ));
+        }
+    } else {
+        lines.push("col,row,hand,finger,symbols,cost,share".to_string());
+        for k in key_costs.keys.iter() {
+            lines.push(format!(
+                "{},{},{:?},{:?},{},{},{}",
+                k.matrix_position.0,
+                k.matrix_position.1,
+                k.hand,
+                k.finger,
+                csv_field(&k.symbols),
+                k.cost,
+                k.share
+            ));
+        }
+    }
+
+    format!("{}\n", lines.join("\n"))
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let layout_str: String = options
+        .layout_str
+        .chars()
+        .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+        .collect();
+    let layout = match layout_generator.generate(&layout_str) {
+        Ok(layout) => layout,
+        Err(e) => {
+            log::error!("{:?}", e);
+            panic!("{:?}", e);
+        }
+    };
+
+    let key_costs = evaluator
+        .key_costs(&layout, &options.metric)
+        .unwrap_or_else(|| {
+            panic!(
+                "No enabled unigram, bigram, trigram, or quadgram metric '{}'",
+                options.metric
+            )
+        });
+    if (key_costs.attributed_cost - key_costs.metric_cost).abs()
+        > 1e-6 * key_costs.metric_cost.abs().max(1.0)
+    {
+        log::warn!(
+            "The metric's cost ({}) is not the sum of the ngram costs ({}), the key costs only \
+             show part of the picture",
+            key_costs.metric_cost,
+            key_costs.attributed_cost
+        );
+    }
+
+    let exported = if options.csv {
+        to_csv(&key_costs, options.pairs)
+    } else {
+        format!("{}\n", serde_json::to_string_pretty(&key_costs).unwrap())
+    };
+
+    match &options.output {
+        Some(filename) => {
+            fs::write(filename, exported)
+                .unwrap_or_else(|e| panic!("Could not write to {}: {:?}", filename, e));
+            log::info!("Exported key costs to '{}'", filename);
+        }
+        None => print!("{}", exported),
+    }
+}
@@ -0,0 +1,210 @@
+use keyboard_layout::{
+    key::{Finger, Hand},
+    layout::Layout,
+};
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+
+use clap::{Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+#[derive(Parser, Debug)]
+#[clap(name = "KLAnext interoperability")]
+/// Import corpora from and export layouts to the KLAnext keyboard layout analyzer
+struct Options {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate ngram frequency files from a KLAnext JSON corpus
+    ImportCorpus {
+        /// KLAnext corpus file (JSON)
+        filename: String,
+
+        /// Name for resulting ngram frequencies (a directory at that path will be generated)
+        out: String,
+    },
+    /// Export a layout as KLAnext layout JSON
+    ExportLayout {
+        /// Layout keys from left to right, top to bottom
+        layout_str: String,
+
+        /// Name of the layout in KLAnext
+        #[clap(long, default_value = "Optimized layout")]
+        label: String,
+
+        /// Write the JSON to this file instead of stdout
+        #[clap(long)]
+        out: Option<String>,
+
+        /// Filename of layout configuration file to use
+        #[clap(short, long, default_value = "config/keyboard/sval.yml")]
+        layout_config: String,
+
+        /// Interpred given layout string using the "grouped" logic
+        #[clap(long)]
+        grouped_layout_generator: bool,
+    },
+}
+
+/// A document of a KLAnext corpus, either plain text or an object containing the text
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum KlaDocument {
+    Text(String),
+    Entry {
+        #[serde(alias = "content")]
+        text: String,
+    },
+}
+
+/// A KLAnext corpus: a single document or a list of documents
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum KlaCorpus {
+    Documents(Vec<KlaDocument>),
+    Collection { documents: Vec<KlaDocument> },
+    Document(KlaDocument),
+}
+
+impl KlaCorpus {
+    fn text(self) -> String {
+        let documents = match self {
+            KlaCorpus::Documents(documents) => documents,
+            KlaCorpus::Collection { documents } => documents,
+            KlaCorpus::Document(document) => vec![document],
+        };
+
+        documents
+            .into_iter()
+            .map(|d| match d {
+                KlaDocument::Text(text) => text,
+                KlaDocument::Entry { text } => text,
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// A key in KLAnext's layout format
+#[derive(Serialize, Debug)]
+struct KlaKey {
+    id: usize,
+    /// Unicode code point of the base layer symbol
+    primary: u32,
+    /// Unicode code point of the shifted (second layer) symbol
+    shift: u32,
+    /// Finger index from 0 (left pinky) to 9 (right pinky)
+    finger: u8,
+    row: u8,
+    col: u8,
+    x: f64,
+    y: f64,
+}
+
+/// A layout in KLAnext's layout format
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct KlaLayout {
+    label: String,
+    author: String,
+    keyboard_type: String,
+    keys: Vec<KlaKey>,
+}
+
+/// KLAnext numbers the fingers from the left pinky (0) over the thumbs (4, 5) to the right pinky (9).
+fn kla_finger(hand: Hand, finger: Finger) -> u8 {
+    match hand {
+        Hand::Left => 4 - finger.numeric_index(),
+        Hand::Right => 5 + finger.numeric_index(),
+    }
+}
+
+fn kla_layout(layout: &Layout, label: &str) -> KlaLayout {
+    let keys = layout
+        .layerkeys
+        .iter()
+        .filter(|k| k.layer == 0)
+        .enumerate()
+        .map(|(id, base)| {
+            let shift = layout
+                .layerkeys
+                .iter()
+                .find(|k| k.layer == 1 && k.key.matrix_position == base.key.matrix_position)
+                .map_or(base.symbol, |k| k.symbol);
+
+            KlaKey {
+                id,
+                primary: base.symbol as u32,
+                shift: shift as u32,
+                finger: kla_finger(base.key.hand, base.key.finger),
+                row: base.key.matrix_position.1,
+                col: base.key.matrix_position.0,
+                x: base.key.position.0,
+                y: base.key.position.1,
+            }
+        })
+        .collect();
+
+    KlaLayout {
+        label: label.to_string(),
+        author: "keyboard_layout_optimizer".to_string(),
+        keyboard_type: "custom".to_string(),
+        keys,
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    match options.command {
+        Command::ImportCorpus { filename, out } => {
+            let data = fs::read_to_string(&filename)
+                .unwrap_or_else(|e| panic!("Could not read corpus file {}: {:?}", filename, e));
+            let corpus: KlaCorpus = serde_json::from_str(&data)
+                .unwrap_or_else(|e| panic!("Could not parse corpus file {}: {:?}", filename, e));
+            let text = corpus.text();
+
+            let d = Path::new(&out);
+            Unigrams::from_text(&text)
+                .expect("Could not generate unigrams from text.")
+                .save_frequencies(d.join("1-grams.txt"))
+                .unwrap();
+            Bigrams::from_text(&text)
+                .expect("Could not generate bigrams from text.")
+                .save_frequencies(d.join("2-grams.txt"))
+                .unwrap();
+            Trigrams::from_text(&text)
+                .expect("Could not generate trigrams from text.")
+                .save_frequencies(d.join("3-grams.txt"))
+                .unwrap();
+        }
+        Command::ExportLayout {
+            layout_str,
+            label,
+            out,
+            layout_config,
+            grouped_layout_generator,
+        } => {
+            let layout_generator =
+                common::init_layout_generator(&layout_config, grouped_layout_generator, &[])
+                    .unwrap_or_else(|e| panic!("Could not load the layout config: {:?}", e));
+            let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
+            let layout = layout_generator
+                .generate(&layout_str)
+                .unwrap_or_else(|e| panic!("Error in generating layout: {:?}", e));
+
+            let json = serde_json::to_string_pretty(&kla_layout(&layout, &label)).unwrap();
+            match out {
+                Some(filename) => fs::write(&filename, json)
+                    .unwrap_or_else(|e| panic!("Could not write {}: {:?}", filename, e)),
+                None => println!("{}", json),
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use keyboard_layout::layout_generator::LayoutGenerator;
+use keyboard_layout_optimizer::{
+    common,
+    leaderboard::{Leaderboard, LeaderboardEntry},
+    logging,
+};
+use layout_evaluation::evaluation::Evaluator;
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+/// Upper bound for the size of request bodies
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout leaderboard server")]
+/// Minimal HTTP server collecting published layouts (see the `publish` binary).
+///
+/// POST /leaderboard adds an entry, GET /leaderboard?config_hash=<hash>&limit=<n> lists the best
+/// entries (lowest cost first). Published layouts are re-evaluated with the configuration given
+/// by the general parameters; entries for other configurations or with mismatching costs are
+/// rejected.
+struct Options {
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    bind: String,
+
+    /// File to store the leaderboard in (JSON lines)
+    #[clap(long, default_value = "leaderboard.jsonl")]
+    store: String,
+
+    /// Default number of entries returned by GET requests
+    #[clap(long, default_value = "100")]
+    limit: usize,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+}
+
+/// Everything needed to verify published entries
+struct Verifier {
+    config_hash: String,
+    layout_generator: Box<dyn LayoutGenerator>,
+    evaluator: Evaluator,
+}
+
+/// The parts of an HTTP request the server cares about
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => bail!("Malformed request line '{}'", request_line.trim()),
+    };
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        bail!("Request body too large ({} bytes)", content_length);
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query,
+        body,
+    })
+}
+
+fn respond(mut stream: &TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        log::warn!("Could not send response: {:?}", e);
+    }
+}
+
+fn error_body(msg: &str) -> String {
+    serde_json::json!({ "error": msg }).to_string()
+}
+
+fn handle(
+    stream: &TcpStream,
+    leaderboard: &mut Leaderboard,
+    verifier: &Verifier,
+    default_limit: usize,
+) {
+    let request = match read_request(stream) {
+        Ok(request) => request,
+        Err(e) => {
+            respond(stream, "400 Bad Request", &error_body(&e.to_string()));
+            return;
+        }
+    };
+
+    if request.path.trim_end_matches('/') != "/leaderboard" {
+        respond(stream, "404 Not Found", &error_body("Not found"));
+        return;
+    }
+
+    match request.method.as_str() {
+        "GET" => {
+            let param = |name: &str| {
+                request
+                    .query
+                    .iter()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.as_str())
+            };
+            let limit = param("limit")
+                .and_then(|l| l.parse().ok())
+                .unwrap_or(default_limit);
+            let entries = leaderboard.best(param("config_hash"), limit);
+            respond(stream, "200 OK", &serde_json::to_string(&entries).unwrap());
+        }
+        "POST" => {
+            let entry: LeaderboardEntry = match serde_json::from_slice(&request.body) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    respond(stream, "400 Bad Request", &error_body(&e.to_string()));
+                    return;
+                }
+            };
+            let entry = match entry.verify(
+                &verifier.config_hash,
+                verifier.layout_generator.as_ref(),
+                &verifier.evaluator,
+            ) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("Rejected layout '{}': {}", entry.layout, e);
+                    respond(
+                        stream,
+                        "422 Unprocessable Entity",
+                        &error_body(&e.to_string()),
+                    );
+                    return;
+                }
+            };
+            let layout = entry.layout.clone();
+            match leaderboard.add(entry) {
+                Ok(true) => {
+                    log::info!("Added layout '{}'", layout);
+                    respond(stream, "201 Created", "{}");
+                }
+                Ok(false) => respond(stream, "200 OK", "{}"),
+                Err(e) => respond(stream, "400 Bad Request", &error_body(&e.to_string())),
+            }
+        }
+        _ => respond(
+            stream,
+            "405 Method Not Allowed",
+            &error_body("Method not allowed"),
+        ),
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let verifier = Verifier {
+        config_hash: common::config_hash(&options.general_parameters),
+        layout_generator,
+        evaluator,
+    };
+    log::info!("Configuration hash: {}", verifier.config_hash);
+
+    let mut leaderboard = Leaderboard::open(&options.store)
+        .unwrap_or_else(|e| panic!("Could not open leaderboard {}: {:?}", options.store, e));
+
+    let listener = TcpListener::bind(&options.bind)
+        .unwrap_or_else(|e| panic!("Could not listen on {}: {:?}", options.bind, e));
+    log::info!("Serving leaderboard on http://{}/leaderboard", options.bind);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                // a single slow client must not block the (sequential) server
+                stream.set_read_timeout(Some(Duration::from_secs(10))).ok();
+                handle(&stream, &mut leaderboard, &verifier, options.limit);
+            }
+            Err(e) => log::warn!("Connection failed: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use keyboard_layout_optimizer::{
+    common::{self, MetricEntry},
+    logging,
+};
+use layout_evaluation::evaluation::Evaluator;
+
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Learn metric weights from ranked layouts")]
+/// Fit the weights of the enabled metrics such that the evaluation reproduces a ranking of
+/// layouts by real-world feel, and suggest them as an evaluation overlay.
+///
+/// The layouts are given in ranked order, best first. Each metric weight is scaled by a
+/// non-negative factor, fitted by minimizing a hinge loss over all pairs of ranked layouts. The
+/// factors are regularized towards 1 (the current weights), which keeps metrics that the ranking
+/// does not inform at their current weight.
+struct Options {
+    /// List of Layout keys from left to right, top to bottom (best first)
+    layout_str: Vec<String>,
+
+    /// Read ranked layouts from file (best first) and append to command line layouts
+    #[clap(long)]
+    from_file: Option<String>,
+
+    /// Strength of the regularization of the weight factors towards 1
+    #[clap(long, default_value = "0.01")]
+    regularization: f64,
+
+    /// Number of gradient descent iterations
+    #[clap(long, default_value = "20000")]
+    iterations: usize,
+
+    /// Step size of the gradient descent
+    #[clap(long, default_value = "0.05")]
+    learning_rate: f64,
+
+    /// Write the suggested weights as evaluation overlay to this file
+    #[clap(short, long)]
+    output: Option<String>,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct LearnedWeight {
+    metric: String,
+    config_key: String,
+    weight: f64,
+    factor: f64,
+    suggested_weight: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct WeightFit {
+    layouts: Vec<String>,
+    /// Kendall's tau between the given ranking and the ranking by the current weights
+    kendall_tau_before: f64,
+    /// Kendall's tau between the given ranking and the ranking by the suggested weights
+    kendall_tau_after: f64,
+    weights: Vec<LearnedWeight>,
+}
+
+/// Total cost of each layout for the given weight factors.
+fn totals(costs: &[Vec<f64>], factors: &[f64]) -> Vec<f64> {
+    costs
+        .iter()
+        .map(|c| c.iter().zip(factors).map(|(c, f)| c * f).sum())
+        .collect()
+}
+
+/// Kendall's tau between the given ranking (the order of `totals`) and the ranking by cost.
+fn kendall_tau(totals: &[f64]) -> f64 {
+    let n = totals.len();
+    let mut concordance = 0.0;
+    for i in 0..n {
+        for j in (i + 1)..n {
+            concordance += (totals[j] - totals[i]).signum();
+        }
+    }
+    let n_pairs = n * n.saturating_sub(1) / 2;
+
+    match n_pairs {
+        0 => 1.0,
+        _ => concordance / n_pairs as f64,
+    }
+}
+
+/// Fit non-negative weight factors with projected subgradient descent on the pairwise hinge loss
+/// (layout `i` is ranked before layout `j` for `i < j`).
+fn fit_factors(costs: &[Vec<f64>], options: &Options) -> Vec<f64> {
+    let n = costs.len();
+    let n_metrics = costs[0].len();
+    let n_pairs = (n * (n - 1) / 2) as f64;
+
+    // express the cost differences relative to their mean magnitude under the current weights,
+    // such that the learning rate and regularization do not depend on the scale of the costs and
+    // pairs count as correctly ordered with a margin of this magnitude
+    let current = totals(costs, &vec![1.0; n_metrics]);
+    let mut scale = 0.0;
+    for i in 0..n {
+        for j in (i + 1)..n {
+            scale += (current[j] - current[i]).abs() / n_pairs;
+        }
+    }
+    if scale <= 0.0 {
+        scale = 1.0;
+    }
+
+    let mut factors = vec![1.0; n_metrics];
+    for _ in 0..options.iterations {
+        let total_costs = totals(costs, &factors);
+        let mut gradient: Vec<f64> = factors
+            .iter()
+            .map(|f| 2.0 * options.regularization * (f - 1.0))
+            .collect();
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let margin = (total_costs[j] - total_costs[i]) / scale;
+                if margin >= 1.0 {
+                    continue;
+                }
+                gradient
+                    .iter_mut()
+                    .zip(costs[j].iter().zip(costs[i].iter()))
+                    .for_each(|(g, (cj, ci))| *g -= (cj - ci) / scale / n_pairs);
+            }
+        }
+
+        factors
+            .iter_mut()
+            .zip(gradient.iter())
+            .for_each(|(f, g)| *f = (*f - options.learning_rate * g).max(0.0));
+    }
+
+    factors
+}
+
+/// Round to four significant digits.
+fn round_weight(weight: f64) -> f64 {
+    if weight == 0.0 {
+        return 0.0;
+    }
+    let magnitude = 10f64.powi(3 - weight.abs().log10().floor() as i32);
+
+    (weight * magnitude).round() / magnitude
+}
+
+/// An evaluation overlay setting the suggested weights. Lists of metrics are replaced as a whole
+/// by overlays, so they are included completely.
+fn weights_overlay(metrics: &Value, entries: &[MetricEntry], weights: &[LearnedWeight]) -> Value {
+    let mut overlay = Mapping::new();
+    for (entry, weight) in entries.iter().zip(weights) {
+        let key = Value::String(entry.key.clone());
+        match entry.index {
+            Some(i) => {
+                let list = overlay
+                    .entry(key)
+                    .or_insert_with(|| metrics[entry.key.as_str()].clone());
+                list[i]["weight"] = Value::from(weight.suggested_weight);
+            }
+            None => {
+                let mut params = Mapping::new();
+                params.insert(
+                    Value::String("weight".to_string()),
+                    Value::from(weight.suggested_weight),
+                );
+                overlay.insert(key, Value::Mapping(params));
+            }
+        }
+    }
+
+    let mut root = Mapping::new();
+    root.insert(
+        Value::String("metrics".to_string()),
+        Value::Mapping(overlay),
+    );
+
+    Value::Mapping(root)
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let layout_strings =
+        common::read_layout_strings(&options.layout_str, options.from_file.as_deref(), false);
+
+    let config = common::resolve_eval_params(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not read the evaluation config: {:?}", e))
+        .value;
+    let metrics = config.get("metrics").cloned().unwrap_or_default();
+    let entries = common::enabled_metrics(&metrics);
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    if !options.general_parameters.additional_corpora.is_empty() {
+        log::warn!("Only the costs on the main corpus are considered");
+    }
+
+    // evaluate each metric on its own to attribute the costs to the entries of the config
+    let standalone: Vec<(&MetricEntry, Evaluator)> = entries
+        .iter()
+        .map(|entry| {
+            let label = entry.label();
+            let alone = common::metric_parameters(&metrics, &entries, |e| e.label() == label);
+            Ok((entry, evaluator.with_metrics(&alone)?))
+        })
+        .collect::<anyhow::Result<_>>()
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let evaluations: Vec<(String, Vec<(String, f64)>)> = layout_strings
+        .par_iter()
+        .filter_map(|layout_str| match layout_generator.generate(layout_str) {
+            Ok(layout) => Some((
+                layout_str.clone(),
+                standalone
+                    .iter()
+                    .map(|(_, alone)| {
+                        let result = alone.evaluate_layout(&layout);
+                        let name = result
+                            .iter()
+                            .flat_map(|results| results.metric_costs.iter())
+                            .map(|mc| mc.core.name.clone())
+                            .next()
+                            .unwrap_or_default();
+                        (name, result.metrics_total_cost())
+                    })
+                    .collect(),
+            )),
+            Err(e) => {
+                log::error!("Error in generating layout {}: {:?}", layout_str, e);
+                None
+            }
+        })
+        .collect();
+    if evaluations.len() < 2 {
+        panic!("At least two valid layouts are required to learn weights from a ranking");
+    }
+    let n_pairs = evaluations.len() * (evaluations.len() - 1) / 2;
+    if n_pairs < entries.len() {
+        log::warn!(
+            "Only {} ranked pairs for {} metrics, the weights are mostly determined by the regularization",
+            n_pairs,
+            entries.len()
+        );
+    }
+
+    let layouts: Vec<String> = evaluations.iter().map(|(l, _)| l.clone()).collect();
+    let costs: Vec<Vec<f64>> = evaluations
+        .iter()
+        .map(|(_, costs)| costs.iter().map(|(_, c)| *c).collect())
+        .collect();
+
+    log::info!(
+        "Fitting {} metric weights to the ranking of {} layouts",
+        entries.len(),
+        layouts.len()
+    );
+    let factors = fit_factors(&costs, &options);
+
+    let weights: Vec<LearnedWeight> = standalone
+        .iter()
+        .enumerate()
+        .map(|(m, (entry, _))| {
+            let weight = entry
+                .params(&metrics)
+                .and_then(|params| params.get("weight"))
+                .and_then(Value::as_f64)
+                .unwrap_or(1.0);
+            // metrics that were not evaluated keep their name empty
+            let metric = evaluations
+                .iter()
+                .map(|(_, costs)| costs[m].0.clone())
+                .find(|name| !name.is_empty())
+                .unwrap_or_else(|| entry.label());
+
+            LearnedWeight {
+                metric,
+                config_key: entry.label(),
+                weight,
+                factor: factors[m],
+                suggested_weight: round_weight(weight * factors[m]),
+            }
+        })
+        .collect();
+
+    let fit = WeightFit {
+        kendall_tau_before: kendall_tau(&totals(&costs, &vec![1.0; factors.len()])),
+        kendall_tau_after: kendall_tau(&totals(&costs, &factors)),
+        layouts,
+        weights,
+    };
+
+    if let Some(filename) = &options.output {
+        let overlay = serde_yaml::to_string(&weights_overlay(&metrics, &entries, &fit.weights))
+            .unwrap_or_else(|e| panic!("Could not serialize the weights overlay: {:?}", e));
+        std::fs::write(filename, overlay)
+            .unwrap_or_else(|e| panic!("Could not write overlay file {}: {:?}", filename, e));
+        log::info!("Wrote suggested weights to {}", filename);
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string(&fit).unwrap());
+        return;
+    }
+
+    let current = totals(&costs, &vec![1.0; factors.len()]);
+    let learned = totals(&costs, &factors);
+    println!("Given ranking ({} layouts):", fit.layouts.len());
+    println!("  {:>3}  {:>10}  {:>10}  layout", "", "current", "learned");
+    for (i, layout) in fit.layouts.iter().enumerate() {
+        println!(
+            "  {:>3}. {:>10.2}  {:>10.2}  {}",
+            i + 1,
+            current[i],
+            learned[i],
+            layout
+        );
+    }
+    println!();
+
+    println!(
+        "{:<36} {:<28} {:>12} {:>8} {:>12}",
+        "Metric", "Config key", "Weight", "Factor", "Suggested"
+    );
+    for w in fit.weights.iter() {
+        println!(
+            "{:<36} {:<28} {:>12.4} {:>8.3} {:>12.4}",
+            w.metric, w.config_key, w.weight, w.factor, w.suggested_weight
+        );
+    }
+    println!();
+    println!(
+        "Kendall's tau with the given ranking: {:.3} (current weights) -> {:.3} (suggested weights)",
+        fit.kendall_tau_before, fit.kendall_tau_after
+    );
+}
@@ -0,0 +1,121 @@
+use keyboard_layout_optimizer::{
+    common,
+    lessons::{self, Lesson, LessonParameters},
+    logging,
+};
+
+use clap::Parser;
+use std::{fs, path::Path};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Typing lessons")]
+/// Generate progressive practice lessons for learning a layout as plain text for typing trainers
+/// (e.g. KTouch or keybr-style trainers accepting custom text).
+///
+/// The first lesson uses the most frequent keys of the corpus, each following lesson introduces
+/// one new key with drills of its bigrams and words using the keys learned so far. Words are
+/// taken from the corpus file (or text), otherwise frequent trigrams serve as pseudo-words.
+struct Options {
+    /// Layout keys from left to right, top to bottom
+    layout_str: String,
+
+    /// Number of keys of the first lesson
+    #[clap(long, default_value = "4")]
+    initial_keys: usize,
+
+    /// Number of words per lesson
+    #[clap(long, default_value = "60")]
+    words_per_lesson: usize,
+
+    /// Maximal length of the lines of a lesson
+    #[clap(long, default_value = "60")]
+    line_length: usize,
+
+    /// Seed for sampling the words of the lessons
+    #[clap(long, default_value = "0")]
+    seed: u64,
+
+    /// Write each lesson to a file "lesson_<n>.txt" in this directory instead of printing all
+    /// lessons to stdout
+    #[clap(short, long)]
+    output_dir: Option<String>,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the lessons as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+fn write_lessons(lessons: &[Lesson], dir: &str) {
+    fs::create_dir_all(dir).unwrap_or_else(|e| panic!("Could not create {}: {:?}", dir, e));
+    let width = lessons.len().to_string().len();
+    for (i, lesson) in lessons.iter().enumerate() {
+        let filename = Path::new(dir).join(format!("lesson_{:0width$}.txt", i + 1, width = width));
+        fs::write(&filename, lesson.to_string())
+            .unwrap_or_else(|e| panic!("Could not write to {:?}: {:?}", filename, e));
+    }
+    log::info!("Wrote {} lessons to '{}'", lessons.len(), dir);
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let layout_str: String = options
+        .layout_str
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let layout_generator = common::init_layout_generator(
+        &options.general_parameters.layout_config,
+        options.general_parameters.grouped_layout_generator,
+        &options.general_parameters.layers,
+    )
+    .unwrap_or_else(|e| panic!("Could not load the layout config: {:?}", e));
+    let layout = layout_generator
+        .generate(&layout_str)
+        .unwrap_or_else(|e| panic!("Error in generating layout {}: {:?}", layout_str, e));
+
+    let (unigrams, bigrams, trigrams, _) = common::init_ngrams(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not read the ngrams: {:?}", e));
+    let words = match common::optimization_text(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not read the corpus: {:?}", e))
+    {
+        Some(text) => lessons::word_counts(&text),
+        None => {
+            log::warn!("No corpus file or text given, using trigrams instead of words");
+            Vec::new()
+        }
+    };
+
+    let params = LessonParameters {
+        initial_keys: options.initial_keys,
+        words_per_lesson: options.words_per_lesson,
+        line_length: options.line_length,
+        seed: options.seed,
+    };
+    let key_order = lessons::key_order(&layout, &unigrams);
+    let lessons = lessons::generate(&key_order, &bigrams, &trigrams, &words, &params);
+
+    if options.json {
+        println!("{}", serde_json::to_string(&lessons).unwrap());
+        return;
+    }
+
+    match &options.output_dir {
+        Some(dir) => write_lessons(&lessons, dir),
+        None => {
+            for (i, lesson) in lessons.iter().enumerate() {
+                let new_keys: String = lesson.new_keys.iter().collect();
+                println!("# Lesson {}: {}\n{}", i + 1, new_keys, lesson);
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use keyboard_layout_optimizer::{
+    config_migration::{self, Migration},
+    logging,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use std::{fs, str::FromStr, sync::Arc};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Config migration")]
+/// Translate configuration files and layouts of the upstream keyboard layout optimizer
+/// (dariogoetz/keyboard_layout_optimizer) into the Svalboard-aware format of this fork. Fields
+/// that need manual attention are listed on stderr.
+struct Options {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Add key directions (derived from rows), a placeholder, and tagged modifiers to an upstream
+    /// layout config
+    LayoutConfig {
+        /// Upstream layout config file
+        filename: String,
+
+        /// Write the migrated config to this file instead of stdout
+        #[clap(short, long)]
+        out: Option<String>,
+    },
+    /// Check an upstream evaluation config for metrics and parameters unknown to this fork
+    EvalConfig {
+        /// Upstream evaluation config file
+        filename: String,
+    },
+    /// Move the symbols of an upstream layout to the keys of the same hand, finger, and direction
+    /// on another keyboard
+    LayoutString {
+        /// Layout keys from left to right, top to bottom
+        layout_str: String,
+
+        /// Upstream layout config of the given layout
+        #[clap(long, default_value = "config/keyboard/standard.yml")]
+        from: String,
+
+        /// Layout config of the keyboard to translate to
+        #[clap(long, default_value = "config/keyboard/sval.yml")]
+        to: String,
+    },
+}
+
+fn generator(config: LayoutConfig) -> NeoLayoutGenerator {
+    let keyboard = Arc::new(Keyboard::from_yaml_object(config.keyboard));
+    NeoLayoutGenerator::from_object(config.base_layout, keyboard)
+}
+
+fn translate(layout_str: &str, from: &str, to: &str) -> Result<Migration> {
+    let source_yaml =
+        fs::read_to_string(from).with_context(|| format!("Could not read {}", from))?;
+    let source_migration = config_migration::migrate_layout_config(&source_yaml)?;
+    let source_config = LayoutConfig::from_str(&source_migration.result)
+        .map_err(|e| anyhow!("Could not load {}: {}", from, e))?;
+    let source = generator(source_config)
+        .generate(layout_str)
+        .with_context(|| format!("Invalid layout for {}", from))?;
+
+    let target_config =
+        LayoutConfig::from_yaml(to).map_err(|e| anyhow!("Could not load {}: {}", to, e))?;
+    let placeholder = target_config.base_layout.placeholder.chars().next();
+    let target_generator = generator(target_config);
+    let target_base = target_generator.generate_base()?;
+
+    let mut migration = config_migration::translate_layout(&source, &target_base, placeholder);
+    if let Err(e) = target_generator.generate(&migration.result) {
+        migration
+            .notes
+            .push(format!("The translated layout is not valid: {:#}", e));
+    }
+
+    Ok(migration)
+}
+
+fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+    logging::init();
+    let options = Options::parse();
+
+    let (migration, out) = match &options.command {
+        Command::LayoutConfig { filename, out } => {
+            let yaml = fs::read_to_string(filename)
+                .with_context(|| format!("Could not read {}", filename))?;
+            (config_migration::migrate_layout_config(&yaml)?, out.clone())
+        }
+        Command::EvalConfig { filename } => {
+            let yaml = fs::read_to_string(filename)
+                .with_context(|| format!("Could not read {}", filename))?;
+            let mut migration = config_migration::migrate_eval_config(&yaml)?;
+            // the config is used as is, there is nothing to print
+            migration.result.clear();
+            (migration, None)
+        }
+        Command::LayoutString {
+            layout_str,
+            from,
+            to,
+        } => (translate(layout_str, from, to)?, None),
+    };
+
+    for note in migration.notes.iter() {
+        eprintln!("Needs attention: {}", note);
+    }
+    if migration.notes.is_empty() {
+        eprintln!("Nothing needs manual attention");
+    }
+
+    match out {
+        Some(out) => fs::write(&out, &migration.result)
+            .with_context(|| format!("Could not write {}", out))?,
+        None if !migration.result.is_empty() => println!("{}", migration.result),
+        None => {}
+    }
+
+    Ok(())
+}
@@ -0,0 +1,261 @@
+use ahash::AHashMap;
+use clap::Parser;
+use std::{path::Path, str::FromStr};
+
+use keyboard_layout_optimizer::logging;
+use layout_evaluation::{
+    compressed,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
+
+/// Supported formats of public ngram data
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ImportFormat {
+    /// Letter ngram counts with one "<ngram> <count>" per line (e.g. Norvig's count_1l.txt,
+    /// count_2l.txt, count_3l.txt); the length of the ngram determines its kind
+    NorvigLetters,
+    /// Word counts with one "<word> <count>" per line (e.g. Norvig's count_1w.txt)
+    NorvigWords,
+    /// Google Books ngram exports with lines "<ngram> TAB <year> TAB <match_count> TAB <volume_count>"
+    /// or "<ngram> TAB <year>,<match_count>,<volume_count> TAB ..."
+    GoogleBooks,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "norvig-letters" => Ok(ImportFormat::NorvigLetters),
+            "norvig-words" => Ok(ImportFormat::NorvigWords),
+            "google-books" => Ok(ImportFormat::GoogleBooks),
+            _ => Err(format!(
+                "Unknown format '{}' (expected 'norvig-letters', 'norvig-words', or 'google-books')",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Ngram frequency import")]
+/// Import public ngram frequency tables (Norvig, Google Books) into ngram frequency files
+struct Options {
+    /// Directory name for resulting ngram frequency files
+    out: String,
+
+    /// Files to import (may be compressed: .gz, .zst, or .zip)
+    files: Vec<String>,
+
+    /// Format of the files: "norvig-letters", "norvig-words", or "google-books"
+    #[clap(long)]
+    format: ImportFormat,
+
+    /// Only consider Google Books counts from this year on
+    #[clap(long)]
+    min_year: Option<u32>,
+
+    /// Convert all symbols to lowercase (Norvig's and Google's tables are often uppercase or mixed)
+    #[clap(long)]
+    lowercase: bool,
+}
+
+/// Char-based ngram frequencies collected during the import
+#[derive(Default)]
+struct NgramCounts {
+    unigrams: AHashMap<char, f64>,
+    bigrams: AHashMap<(char, char), f64>,
+    trigrams: AHashMap<(char, char, char), f64>,
+}
+
+impl NgramCounts {
+    /// Add a letter ngram of length one to three.
+    fn add_ngram(&mut self, ngram: &[char], count: f64) {
+        match ngram {
+            [c1] => *self.unigrams.entry(*c1).or_default() += count,
+            [c1, c2] => *self.bigrams.entry((*c1, *c2)).or_default() += count,
+            [c1, c2, c3] => *self.trigrams.entry((*c1, *c2, *c3)).or_default() += count,
+            _ => log::warn!(
+                "Skipping ngram '{}' of unsupported length",
+                ngram.iter().collect::<String>()
+            ),
+        }
+    }
+
+    /// Add all letter ngrams of a word (or phrase) of a text of space-separated words, i.e.
+    /// including the spaces before and after the word. Each word is counted with one space as
+    /// unigram (the separators are shared by neighboring words).
+    fn add_word(&mut self, word: &str, count: f64) {
+        let chars: Vec<char> = std::iter::once(' ')
+            .chain(word.chars())
+            .chain(std::iter::once(' '))
+            .collect();
+        chars[..chars.len() - 1]
+            .iter()
+            .for_each(|c| self.add_ngram(&[*c], count));
+        for n in 2..=3 {
+            chars
+                .windows(n)
+                .for_each(|ngram| self.add_ngram(ngram, count));
+        }
+    }
+}
+
+/// Remove part-of-speech annotations (e.g. "book_NOUN" or "_NOUN_") from Google Books ngrams.
+fn strip_pos_tags(ngram: &str) -> String {
+    ngram
+        .split(' ')
+        .filter_map(|token| {
+            if token.starts_with('_') && token.ends_with('_') {
+                return None;
+            }
+            match token.rsplit_once('_') {
+                Some((word, tag))
+                    if !word.is_empty()
+                        && !tag.is_empty()
+                        && tag.chars().all(|c| c.is_ascii_uppercase()) =>
+                {
+                    Some(word)
+                }
+                _ => Some(token),
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Sum of the match counts of a Google Books line (in either of the export formats).
+fn google_books_count(fields: &[&str], min_year: Option<u32>) -> Option<f64> {
+    let in_range = |year: &str| {
+        year.parse::<u32>()
+            .map(|y| min_year.map_or(true, |min| y >= min))
+            .unwrap_or(false)
+    };
+
+    if fields.get(1).map_or(false, |f| f.contains(',')) {
+        // one field "<year>,<match_count>,<volume_count>" per year
+        let count = fields[1..]
+            .iter()
+            .filter_map(|f| {
+                let parts: Vec<&str> = f.split(',').collect();
+                match parts.as_slice() {
+                    [year, matches, ..] if in_range(year) => matches.parse::<f64>().ok(),
+                    _ => None,
+                }
+            })
+            .sum();
+        Some(count)
+    } else {
+        match fields {
+            [_, year, matches, ..] if in_range(year) => matches.parse::<f64>().ok(),
+            _ => None,
+        }
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let mut counts = NgramCounts::default();
+    for filename in options.files.iter() {
+        log::info!("Importing {}...", filename);
+        let data = compressed::read_to_string(filename)
+            .unwrap_or_else(|e| panic!("Could not read file {}: {:?}", filename, e));
+
+        let normalize = |gram: &str| {
+            if options.lowercase {
+                gram.to_lowercase()
+            } else {
+                gram.to_string()
+            }
+        };
+
+        for line in data.lines() {
+            match options.format {
+                ImportFormat::NorvigLetters | ImportFormat::NorvigWords => {
+                    let mut parts = line.split_whitespace();
+                    let (gram, count) = match (parts.next(), parts.next().map(f64::from_str)) {
+                        (Some(gram), Some(Ok(count))) => (normalize(gram), count),
+                        _ => {
+                            log::debug!("Skipping line '{}'", line);
+                            continue;
+                        }
+                    };
+                    if options.format == ImportFormat::NorvigLetters {
+                        counts.add_ngram(&gram.chars().collect::<Vec<char>>(), count);
+                    } else {
+                        counts.add_word(&gram, count);
+                    }
+                }
+                ImportFormat::GoogleBooks => {
+                    let fields: Vec<&str> = line.split('\t').collect();
+                    if let Some(count) = google_books_count(&fields, options.min_year) {
+                        // tags are uppercase, so they need to be stripped before normalizing
+                        let ngram = normalize(&strip_pos_tags(fields[0]));
+                        if !ngram.is_empty() {
+                            counts.add_word(&ngram, count);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    log::info!("Writing result to {}...", options.out);
+    let out = Path::new(&options.out);
+    if !counts.unigrams.is_empty() {
+        Unigrams {
+            grams: counts.unigrams,
+        }
+        .save_frequencies(out.join("1-grams.txt"))
+        .unwrap();
+    }
+    if !counts.bigrams.is_empty() {
+        Bigrams {
+            grams: counts.bigrams,
+        }
+        .save_frequencies(out.join("2-grams.txt"))
+        .unwrap();
+    }
+    if !counts.trigrams.is_empty() {
+        Trigrams {
+            grams: counts.trigrams,
+        }
+        .save_frequencies(out.join("3-grams.txt"))
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_separators_around_words() {
+        let mut counts = NgramCounts::default();
+        counts.add_word("ab", 2.0);
+
+        assert_eq!(counts.unigrams[&' '], 2.0);
+        assert_eq!(counts.unigrams[&'a'], 2.0);
+        assert_eq!(counts.unigrams.values().sum::<f64>(), 6.0);
+        assert_eq!(counts.bigrams[&(' ', 'a')], 2.0);
+        assert_eq!(counts.bigrams[&('b', ' ')], 2.0);
+        assert_eq!(counts.bigrams.len(), 3);
+        assert_eq!(counts.trigrams[&(' ', 'a', 'b')], 2.0);
+        assert_eq!(counts.trigrams[&('a', 'b', ' ')], 2.0);
+        assert_eq!(counts.trigrams.len(), 2);
+    }
+
+    #[test]
+    fn counts_phrases_as_space_separated_words() {
+        let mut counts = NgramCounts::default();
+        counts.add_word("of a", 1.0);
+
+        assert_eq!(counts.unigrams[&' '], 2.0);
+        assert_eq!(counts.bigrams[&('a', ' ')], 1.0);
+        assert_eq!(counts.trigrams[&('f', ' ', 'a')], 1.0);
+        assert_eq!(counts.trigrams[&(' ', 'a', ' ')], 1.0);
+    }
+}
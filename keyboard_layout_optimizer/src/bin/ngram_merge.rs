@@ -1,25 +1,77 @@
 use ahash::AHashMap;
 use clap::Parser;
-use std::{hash::Hash, path::Path, str::FromStr};
+use std::{fs, hash::Hash, path::Path, str::FromStr, time::UNIX_EPOCH};
 
-use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+use keyboard_layout_optimizer::logging;
+use layout_evaluation::{
+    compressed,
+    ngrams::{Bigrams, Trigrams, Unigrams},
+};
 
 #[derive(Debug)]
-struct WeightedComponent(f64, String);
+struct WeightedComponent(f64, String, Option<i64>);
 
 impl FromStr for WeightedComponent {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let components: Vec<&str> = s.splitn(2, ':').collect();
+        let components: Vec<&str> = s.splitn(3, ':').collect();
 
         let path = components[0].to_string();
         let weight = f64::from_str(components[1]).unwrap();
+        let date = components.get(2).map(|d| parse_date(d)).transpose()?;
 
-        Ok(WeightedComponent(weight, path))
+        Ok(WeightedComponent(weight, path, date))
     }
 }
 
+/// Parses a date of the form YYYY-MM-DD into the number of days since 1970-01-01.
+fn parse_date(s: &str) -> Result<i64, String> {
+    let parts: Vec<i64> = s
+        .split('-')
+        .map(i64::from_str)
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid date '{}' (expected YYYY-MM-DD)", s))?;
+    if parts.len() != 3 {
+        return Err(format!("Invalid date '{}' (expected YYYY-MM-DD)", s));
+    }
+
+    let (m, d) = (parts[1], parts[2]);
+    let leap_year = parts[0] % 4 == 0 && (parts[0] % 100 != 0 || parts[0] % 400 == 0);
+    let days_in_month = match m {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap_year => 29,
+        2 => 28,
+        _ => return Err(format!("Invalid month in date '{}'", s)),
+    };
+    if d < 1 || d > days_in_month {
+        return Err(format!("Invalid day in date '{}'", s));
+    }
+
+    // see http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let y = if m <= 2 { parts[0] - 1 } else { parts[0] };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    Ok(era * 146097 + doe - 719468)
+}
+
+/// Date of a component in days since 1970-01-01. Falls back to the modification time
+/// of the unigram file if no date was given explicitly.
+fn component_date(component: &WeightedComponent) -> i64 {
+    component.2.unwrap_or_else(|| {
+        let p = compressed::find_file(&component.1, "1-grams.txt");
+        let modified = fs::metadata(&p)
+            .and_then(|m| m.modified())
+            .unwrap_or_else(|_| panic!("Could not read modification time of '{:?}'.", &p));
+        let secs = modified.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        (secs / 86400) as i64
+    })
+}
+
 #[derive(Parser, Debug)]
 #[clap(name = "Ngram frequency merge")]
 /// Merge multiple ngram frequency files with given weights into a new one
@@ -27,8 +79,14 @@ struct Options {
     /// Directory name for resulting ngram frequency files
     out: String,
 
-    /// Pairs of weight and ngram frequency directory in the form path:weight
+    /// Pairs of weight and ngram frequency directory in the form path:weight or path:weight:YYYY-MM-DD
     components: Vec<WeightedComponent>,
+
+    /// Weight components by their recency: a component that is this many days older than
+    /// the most recent one gets half the weight. Components without an explicit date are
+    /// dated by the modification time of their files
+    #[clap(long)]
+    half_life_days: Option<f64>,
 }
 
 fn add<T: Clone + Eq + Hash>(weight: f64, res: &mut AHashMap<T, f64>, ngrams: &AHashMap<T, f64>) {
@@ -43,7 +101,7 @@ fn add<T: Clone + Eq + Hash>(weight: f64, res: &mut AHashMap<T, f64>, ngrams: &A
 fn main() {
     dotenv::dotenv().ok();
     let options = Options::parse();
-    env_logger::init();
+    logging::init();
 
     let mut res_unigrams = AHashMap::default();
     let mut res_bigrams = AHashMap::default();
@@ -53,10 +111,27 @@ fn main() {
     let mut target_bigrams_total: Option<f64> = None;
     let mut target_trigrams_total: Option<f64> = None;
 
-    for component in options.components {
-        log::info!("Processing {}...", component.1);
+    let recency_factors: Vec<f64> = match options.half_life_days {
+        Some(half_life_days) => {
+            let dates: Vec<i64> = options.components.iter().map(component_date).collect();
+            let most_recent = dates.iter().cloned().max().unwrap_or_default();
+            dates
+                .iter()
+                .map(|date| 0.5f64.powf((most_recent - date) as f64 / half_life_days))
+                .collect()
+        }
+        None => vec![1.0; options.components.len()],
+    };
+
+    for (component, recency_factor) in options.components.iter().zip(recency_factors) {
+        log::info!(
+            "Processing {} (recency factor: {:.3})...",
+            component.1,
+            recency_factor
+        );
+        let weight = component.0 * recency_factor;
 
-        let p = Path::new(&component.1).join("1-grams.txt");
+        let p = compressed::find_file(&component.1, "1-grams.txt");
         let unigrams = Unigrams::from_file(p.to_str().unwrap())
             .unwrap_or_else(|_| panic!("Could not read 1-gramme file from '{:?}'.", &p));
 
@@ -65,12 +140,12 @@ fn main() {
         // first ngram file determines "absolute level"
         target_unigrams_total = target_unigrams_total.or(Some(unigrams_total));
         add(
-            component.0 * target_unigrams_total.unwrap() / unigrams_total,
+            weight * target_unigrams_total.unwrap() / unigrams_total,
             &mut res_unigrams,
             &unigrams.grams,
         );
 
-        let p = Path::new(&component.1).join("2-grams.txt");
+        let p = compressed::find_file(&component.1, "2-grams.txt");
         let bigrams = Bigrams::from_file(p.to_str().unwrap())
             .unwrap_or_else(|_| panic!("Could not read 2-gramme file from '{:?}'.", &p));
 
@@ -79,12 +154,12 @@ fn main() {
         // first ngram file determines "absolute level"
         target_bigrams_total = target_bigrams_total.or(Some(bigrams_total));
         add(
-            component.0 * target_bigrams_total.unwrap() / bigrams_total,
+            weight * target_bigrams_total.unwrap() / bigrams_total,
             &mut res_bigrams,
             &bigrams.grams,
         );
 
-        let p = Path::new(&component.1).join("3-grams.txt");
+        let p = compressed::find_file(&component.1, "3-grams.txt");
         let trigrams = Trigrams::from_file(p.to_str().unwrap())
             .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", &p));
 
@@ -93,7 +168,7 @@ fn main() {
         // first ngram file determines "absolute level"
         target_trigrams_total = target_trigrams_total.or(Some(trigrams_total));
         add(
-            component.0 * target_trigrams_total.unwrap() / trigrams_total,
+            weight * target_trigrams_total.unwrap() / trigrams_total,
             &mut res_trigrams,
             &trigrams.grams,
         );
@@ -115,3 +190,30 @@ fn main() {
     .save_frequencies(out.join("3-grams.txt"))
     .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dates_as_days_since_epoch() {
+        assert_eq!(parse_date("1970-01-01"), Ok(0));
+        assert_eq!(parse_date("2000-03-01"), Ok(11017));
+        assert_eq!(parse_date("2024-02-29"), Ok(19782));
+    }
+
+    #[test]
+    fn rejects_dates_out_of_range() {
+        for date in [
+            "2024-13-01",
+            "2024-00-10",
+            "2024-01-32",
+            "2024-04-31",
+            "2023-02-29",
+        ]
+        .iter()
+        {
+            assert!(parse_date(date).is_err(), "{} was accepted", date);
+        }
+    }
+}
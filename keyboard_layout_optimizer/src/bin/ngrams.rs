@@ -1,38 +1,252 @@
+use ahash::AHashMap;
 use clap::Parser;
-use std::{fs, path::Path};
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::{fs, path::Path, str::FromStr};
 
-use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+use keyboard_layout_optimizer::{
+    corpus_cleaning::{self, Boilerplate},
+    corpus_processing,
+    language_detection::detect_language,
+    logging,
+};
+use layout_evaluation::compressed;
 
 #[derive(Parser, Debug)]
 #[clap(name = "Ngram frequency generator")]
 /// Generate ngram-frequency files from a given text file.
 struct Options {
-    /// Read text from this file
+    /// Read text from this file (or from all files in this directory), which may be compressed
+    /// (.gz, .zst, or .zip)
     filename: String,
 
     /// Name for resulting ngram frequencies (a directory at that path will be generated)
     out: String,
+
+    /// Detect the language of each file and additionally generate ngram frequencies
+    /// for each language in a subdirectory of `out`
+    #[clap(long)]
+    detect_languages: bool,
+
+    /// How to treat whitespace: "keep" (as in the text), "normalize" (Space and Enter only, so
+    /// word boundaries are typed on the thumb cluster), or "drop" (letters and symbols only)
+    #[clap(long, default_value = "keep")]
+    whitespace: WhitespaceHandling,
+
+    /// Additionally generate quadgram frequencies (required by quadgram metrics)
+    #[clap(long)]
+    quadgrams: bool,
+
+    /// Strip boilerplate from each file before counting: "license" (license headers), "quotes"
+    /// (quoted email replies), and/or "generated" (generated code banners), separated by commas
+    #[clap(long, value_delimiter = ',')]
+    strip: Vec<Boilerplate>,
+
+    /// Skip documents (files or messages of mbox files) that are near duplicates of an earlier
+    /// document of the corpus, i.e. whose estimated similarity (share of common word 5-grams) is
+    /// at least this value, e.g. 0.8
+    #[clap(long)]
+    dedup_threshold: Option<f64>,
 }
 
-fn main() {
-    dotenv::dotenv().ok();
-    let options = Options::parse();
-    env_logger::init();
+/// Treatment of whitespace in the text before generating ngrams
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WhitespaceHandling {
+    /// Keep all whitespace as it is
+    Keep,
+    /// Turn each run of whitespace into a single Space (or a single Enter if it contains a line break)
+    Normalize,
+    /// Remove all whitespace
+    Drop,
+}
 
-    let text = fs::read_to_string(&options.filename)
-        .unwrap_or_else(|_| panic!("Could not read corpus file from {}.", options.filename));
+impl FromStr for WhitespaceHandling {
+    type Err = String;
 
-    let d = Path::new(&options.out);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(WhitespaceHandling::Keep),
+            "normalize" => Ok(WhitespaceHandling::Normalize),
+            "drop" => Ok(WhitespaceHandling::Drop),
+            _ => Err(format!(
+                "Unknown whitespace handling '{}' (expected 'keep', 'normalize', or 'drop')",
+                s
+            )),
+        }
+    }
+}
+
+impl WhitespaceHandling {
+    fn apply(&self, text: &str) -> String {
+        match self {
+            WhitespaceHandling::Keep => text.to_string(),
+            WhitespaceHandling::Drop => text.chars().filter(|c| !c.is_whitespace()).collect(),
+            WhitespaceHandling::Normalize => {
+                let mut normalized = String::with_capacity(text.len());
+                let mut whitespace_run: Option<char> = None;
+                for c in text.chars() {
+                    if c.is_whitespace() {
+                        // a line break within the run takes precedence over spaces
+                        if c == '\n' || whitespace_run.is_none() {
+                            whitespace_run = Some(if c == '\n' { '\n' } else { ' ' });
+                        }
+                    } else {
+                        normalized.extend(whitespace_run.take());
+                        normalized.push(c);
+                    }
+                }
+                normalized.extend(whitespace_run);
+
+                normalized
+            }
+        }
+    }
+}
+
+/// Read the given file or all files in the given directory (sorted by name) in parallel.
+fn read_documents(filename: &str) -> Vec<(String, String)> {
+    let path = Path::new(filename);
+    let filenames: Vec<String> = if path.is_dir() {
+        fs::read_dir(path)
+            .unwrap_or_else(|_| panic!("Could not read corpus directory {}.", filename))
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .map(|p| p.to_string_lossy().to_string())
+            .sorted()
+            .collect()
+    } else {
+        vec![filename.to_string()]
+    };
+
+    let progress = corpus_processing::files_progress(filenames.len() as u64, "Reading");
+    let documents = filenames
+        .into_par_iter()
+        .filter_map(|filename| {
+            let document = match compressed::read_to_string(&filename) {
+                Ok(text) => Some((filename, text)),
+                Err(e) => {
+                    progress.suspend(|| log::warn!("Skipping {}: {}", filename, e));
+                    None
+                }
+            };
+            progress.inc(1);
+            document
+        })
+        .collect();
+    progress.finish();
+
+    documents
+}
+
+/// Save the ngrams of the documents, each counted on its own (no ngrams across documents).
+fn save_ngrams(documents: &[&str], d: &Path, with_quadgrams: bool) {
+    let (unigrams, bigrams, trigrams, quadgrams) =
+        corpus_processing::ngrams_from_documents(documents, with_quadgrams);
 
-    let unigrams = Unigrams::from_text(&text).expect("Could not generate unigrams from text.");
     let p = d.join("1-grams.txt");
     unigrams.save_frequencies(p).unwrap();
 
-    let bigrams = Bigrams::from_text(&text).expect("Could not generate bigrams from text.");
     let p = d.join("2-grams.txt");
     bigrams.save_frequencies(p).unwrap();
 
-    let trigrams = Trigrams::from_text(&text).expect("Could not generate trigrams from text.");
     let p = d.join("3-grams.txt");
     trigrams.save_frequencies(p).unwrap();
+
+    if with_quadgrams {
+        let p = d.join("4-grams.txt");
+        quadgrams.save_frequencies(p).unwrap();
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let mut documents: Vec<(String, String)> = read_documents(&options.filename)
+        .into_par_iter()
+        .flat_map_iter(|(filename, text)| {
+            let messages = corpus_cleaning::split_messages(&text);
+            let n_messages = messages.len();
+            messages
+                .into_iter()
+                .enumerate()
+                .map(|(i, message)| {
+                    let name = match n_messages {
+                        1 => filename.clone(),
+                        _ => format!("{} (message {})", filename, i + 1),
+                    };
+                    let message = options
+                        .strip
+                        .iter()
+                        .fold(message.to_string(), |text, boilerplate| {
+                            boilerplate.strip(&text)
+                        });
+                    (name, message)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    if let Some(threshold) = options.dedup_threshold {
+        let texts: Vec<&str> = documents.iter().map(|(_, text)| text.as_str()).collect();
+        let duplicates = corpus_cleaning::find_near_duplicates(&texts, threshold);
+        for ((filename, _), original) in documents.iter().zip(duplicates.iter()) {
+            if let Some(original) = original {
+                log::info!(
+                    "Skipping {} (near duplicate of {})",
+                    filename,
+                    documents[*original].0
+                );
+            }
+        }
+        let n_duplicates = duplicates.iter().filter(|d| d.is_some()).count();
+        log::info!("Skipped {} near duplicate documents", n_duplicates);
+
+        let mut duplicates = duplicates.into_iter();
+        documents.retain(|_| duplicates.next().unwrap().is_none());
+    }
+
+    let documents: Vec<(String, String)> = documents
+        .into_par_iter()
+        .map(|(filename, text)| (filename, options.whitespace.apply(&text)))
+        .collect();
+    let texts: Vec<&str> = documents.iter().map(|(_, text)| text.as_str()).collect();
+
+    let d = Path::new(&options.out);
+    save_ngrams(&texts, d, options.quadgrams);
+
+    if options.detect_languages {
+        let languages: Vec<&str> = documents
+            .par_iter()
+            .map(|(_, text)| detect_language(text).unwrap_or("unknown"))
+            .collect();
+        let mut language_texts: AHashMap<&str, Vec<&str>> = AHashMap::default();
+        for ((filename, text), language) in documents.iter().zip(languages) {
+            log::info!("{}: {}", filename, language);
+            language_texts.entry(language).or_default().push(text);
+        }
+
+        // the combined ngrams already reflect the detected proportions
+        let n_chars = |texts: &[&str]| -> usize { texts.iter().map(|t| t.chars().count()).sum() };
+        let total_chars: usize = language_texts.values().map(|t| n_chars(t)).sum();
+        println!("Detected language mixture:");
+        let mut components = Vec::new();
+        for (language, texts) in language_texts
+            .iter()
+            .sorted_by_key(|(_, texts)| std::cmp::Reverse(n_chars(texts)))
+        {
+            let share = n_chars(texts) as f64 / total_chars.max(1) as f64;
+            println!("  {}: {:>5.1}%", language, 100.0 * share);
+
+            let language_dir = d.join(language);
+            save_ngrams(texts, &language_dir, options.quadgrams);
+            components.push(format!("{}:{:.3}", language_dir.to_string_lossy(), share));
+        }
+
+        println!(
+            "To adjust the mixture, merge the per-language ngrams with different weights, e.g.:\n  ngram_merge <out> {}",
+            components.join(" ")
+        );
+    }
 }
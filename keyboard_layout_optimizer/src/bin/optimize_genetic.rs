@@ -1,9 +1,10 @@
-use keyboard_layout_optimizer::common;
+use keyboard_layout_optimizer::{anytime, common, logging};
 use layout_evaluation::cache::Cache;
+use layout_optimization_common::history::{self, ConvergenceHistory};
 use layout_optimization_genetic::optimization;
 
 use clap::Parser;
-use std::{env, process};
+use std::{env, fs::OpenOptions, io::Write};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout optimization - Genetic Algorithm")]
@@ -40,14 +41,58 @@ struct Options {
     #[clap(long)]
     generation_limit: Option<u64>,
 
+    /// Evaluate mirror-equivalent layouts as their canonical representative, so that they share
+    /// one entry of the result cache (only use with symmetric metrics)
+    #[clap(long)]
+    canonicalize_mirrors: bool,
+
+    /// Swap restriction options
+    #[clap(flatten)]
+    swap_restriction_options: common::SwapRestrictionOptions,
+
+    /// Keep these symbols at their positions as soft constraint: the search may move them at a
+    /// growing penalty (see `soft_constraints` in the optimization config)
+    #[clap(long)]
+    soft_fix: Option<String>,
+
+    /// Treat the swap groups (--same-layer-swaps and --swap-groups) as soft constraints
+    #[clap(long)]
+    soft_swap_groups: bool,
+
     /// Append found layouts to file
     #[clap(long)]
     append_solutions_to: Option<String>,
 
+    /// Track the parentage of all individuals and append the lineage of the found layouts
+    /// (as JSON) to file
+    #[clap(long)]
+    lineage: Option<String>,
+
+    /// Record the best and mean costs of each generation and write them to file (as JSON if
+    /// the filename ends with ".json", otherwise as CSV)
+    #[clap(long)]
+    history: Option<String>,
+
     /// Repeat optimizations indefinitely
     #[clap(long)]
     run_forever: bool,
 
+    /// Monitoring options
+    #[clap(flatten)]
+    monitoring_options: common::MonitoringOptions,
+
+    /// Warm start options
+    #[clap(flatten)]
+    warm_start_options: common::WarmStartOptions,
+
+    /// Stopping options
+    #[clap(flatten)]
+    stopping_options: common::StoppingOptions,
+
+    /// Observer options
+    #[clap(flatten)]
+    observer_options: common::ObserverOptions,
+
     /// Publishing options
     #[clap(flatten)]
     publishing_options: common::PublishingOptions,
@@ -55,7 +100,7 @@ struct Options {
 
 fn main() {
     dotenv::dotenv().ok();
-    env_logger::init();
+    logging::init();
 
     // Disable storing worst ngrams for speed boost
     if env::var("SHOW_WORST").is_err() {
@@ -64,17 +109,15 @@ fn main() {
 
     let final_results: Cache<f64> = Cache::new();
 
+    let options = Options::parse();
+
     // Handle Ctrl+C
+    let shared_budget = options.stopping_options.shared_budget();
     let cloned_final_results = final_results.clone();
-    ctrlc::set_handler(move || {
+    anytime::handle_interrupts(&shared_budget, move || {
         // Display a summary of the optimization.
         println!("\n\n{}\n", cloned_final_results);
-        // Stop execution
-        process::exit(0);
-    })
-    .expect("Error setting Ctrl-C handler");
-
-    let options = Options::parse();
+    });
 
     let fix_from: String = options
         .fix_from
@@ -88,7 +131,14 @@ fn main() {
             .collect::<String>()
     });
 
-    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters);
+    options.monitoring_options.serve_metrics();
+    let live_stream = options.observer_options.serve_live_stream();
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let holdout_evaluator = common::init_holdout_evaluator(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the holdout evaluation: {:?}", e));
+    let result_cache = common::init_result_cache(&options.evaluation_parameters);
 
     let mut optimization_params = optimization::Parameters::from_yaml(
         &options.optimization_parameters,
@@ -103,26 +153,98 @@ fn main() {
     if let Some(generation_limit) = options.generation_limit {
         optimization_params.generation_limit = generation_limit
     }
+    if options.canonicalize_mirrors {
+        optimization_params.canonicalize_mirrors = true;
+    }
+    options
+        .swap_restriction_options
+        .apply(&mut optimization_params.swap_restrictions);
+    if options.soft_fix.is_some() || options.soft_swap_groups {
+        let mut soft_constraints = optimization_params
+            .soft_constraints
+            .clone()
+            .unwrap_or_default();
+        if let Some(soft_fix) = &options.soft_fix {
+            soft_constraints.fix = soft_fix.clone();
+        }
+        if options.soft_swap_groups {
+            soft_constraints.swap_groups = true;
+        }
+        optimization_params.soft_constraints = Some(soft_constraints);
+    }
+    if options.lineage.is_some() {
+        optimization_params.track_lineage = true;
+    }
+    if options.history.is_some() {
+        optimization_params.record_history = true;
+    }
+    options
+        .stopping_options
+        .apply(&mut optimization_params.stopping);
+    optimization_params.stopping.shared = Some(shared_budget.clone());
 
-    let fix_from = start_layout.as_ref().unwrap_or(&fix_from).to_string();
+    let warm_start_layouts = options
+        .warm_start_options
+        .layouts()
+        .unwrap_or_else(|e| panic!("Could not read the warm start layouts: {:?}", e));
+    let fix_from = start_layout
+        .as_ref()
+        .or_else(|| warm_start_layouts.first())
+        .unwrap_or(&fix_from)
+        .to_string();
+    if let Some(seconds) = options.stopping_options.budget_seconds() {
+        // the population is evaluated in parallel
+        let rate = anytime::evaluation_rate(
+            &evaluator,
+            layout_generator.as_ref(),
+            &fix_from,
+            &options.fix.clone().unwrap_or_default(),
+        ) * rayon::current_num_threads() as f64;
+        optimization_params.fit_to_time_budget(seconds, rate);
+    }
 
+    let mut histories: Vec<ConvergenceHistory> = Vec::new();
     loop {
-        let (layout_str, layout) = optimization::optimize(
+        let optimization::GeneticResult {
+            layout_str,
+            layout,
+            lineage_dump,
+            history,
+            stop_reason,
+        } = optimization::optimize(
             &optimization_params,
             &evaluator,
             &fix_from,
             &layout_generator,
             &options.fix.clone().unwrap_or_default(),
             start_layout.is_some(),
-            !options.no_cache_results,
+            &warm_start_layouts,
+            (!options.no_cache_results).then(|| common::cost_cache(&result_cache)),
+            options.observer_options.observer(
+                live_stream.as_ref(),
+                &evaluator,
+                layout_generator.as_ref(),
+            ),
+            None,
         );
+        common::log_bigram_cost_cache(&evaluator);
         let evaluation_result = evaluator.evaluate_layout(&layout);
         let cost = evaluation_result.total_cost();
         let _ = final_results.get_or_insert_with(&layout_str, || cost);
 
+        if let Some(holdout_evaluator) = &holdout_evaluator {
+            common::check_overfitting(
+                &layout,
+                cost,
+                holdout_evaluator,
+                options.evaluation_parameters.holdout_warn_gap,
+            );
+        }
+
         println!(
-            "{}\n\n{}\n",
+            "{}\nStopped: {}\n\n{}\n",
             evaluation_result,
+            stop_reason,
             final_results.highlighted_fmt(Some(&layout_str), 10)
         );
 
@@ -131,18 +253,48 @@ fn main() {
             common::append_to_file(&layout_str, filename);
         }
 
+        if let Some(lineage_dump) = lineage_dump {
+            println!("{}\n", lineage_dump.summary());
+            if let Some(filename) = &options.lineage {
+                let written = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(filename)
+                    .and_then(|mut file| {
+                        writeln!(file, "{}", serde_json::to_string(&lineage_dump).unwrap())
+                    });
+                if let Err(e) = written {
+                    log::error!("Couldn't write lineage to file {}: {}", filename, e);
+                }
+            }
+        }
+
+        if let (Some(mut history), Some(filename)) = (history, &options.history) {
+            history.run = format!("Run {}", histories.len() + 1);
+            histories.push(history);
+            if let Err(e) = history::write(&histories, filename) {
+                log::error!(
+                    "Couldn't write convergence history to file {}: {}",
+                    filename,
+                    e
+                );
+            }
+        }
+
         // Publish to webservice.
         let o = &options.publishing_options;
-        if o.publish_as.is_some() && cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
-            common::publish_to_webservice(
-                &layout_str,
-                o.publish_as.as_ref().unwrap(),
-                &o.publish_to,
-                &o.publish_layout_config,
-            );
+        if let Some(publish_as) = &o.publish_as {
+            if cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
+                common::publish_to_webservice(
+                    &layout_str,
+                    publish_as,
+                    &o.publish_to,
+                    &o.publish_layout_config,
+                );
+            }
         }
 
-        if !options.run_forever {
+        if !options.run_forever || shared_budget.check().is_some() {
             break;
         }
     }
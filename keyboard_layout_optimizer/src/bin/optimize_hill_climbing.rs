@@ -0,0 +1,188 @@
+use keyboard_layout_optimizer::{
+    anytime::{self, RunBudget},
+    common, logging,
+};
+use layout_evaluation::cache::Cache;
+use layout_optimization_hill_climbing::optimization;
+
+use clap::Parser;
+use colored::Colorize;
+use std::env;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization - Multi-start Hill Climbing")]
+/// Climb greedily (by key swaps) from many random or given start layouts in parallel and report
+/// the best layouts together with statistics of the reached local optima.
+struct Options {
+    /// Evaluation parameters
+    #[clap(flatten)]
+    evaluation_parameters: common::Options,
+
+    /// Do not optimize those keys (wrt. --start-layouts or --fix-from)
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Fix the keys from this layout (will be overwritten by --start-layouts)
+    #[clap(long, default_value = "")]
+    fix_from: String,
+
+    /// Filename of optimization configuration file
+    #[clap(short, long, default_value = "config/optimization/hill_climbing.yml")]
+    optimization_parameters: String,
+
+    /// Start climbs from these layouts (keys from left to right, top to bottom). The remaining
+    /// climbs start from random layouts
+    #[clap(short, long)]
+    start_layouts: Vec<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Do not cache intermediate results
+    #[clap(long)]
+    no_cache_results: bool,
+
+    /// Number of climbs
+    #[clap(long)]
+    starts: Option<usize>,
+
+    /// How to choose the next move (steepest, first_improvement)
+    #[clap(long)]
+    strategy: Option<String>,
+
+    /// Swap restriction options
+    #[clap(flatten)]
+    swap_restriction_options: common::SwapRestrictionOptions,
+
+    /// Number of local optima to list (and to append to --append-solutions-to)
+    #[clap(long, default_value = "10")]
+    top: usize,
+
+    /// Budget of the whole run, either a duration (e.g. "90m", "2h", "1h30m") or a number of
+    /// layout evaluations. The climbs end (and the remaining ones are skipped) once it is used up
+    #[clap(long)]
+    budget: Option<RunBudget>,
+
+    /// Append found layouts to file
+    #[clap(long)]
+    append_solutions_to: Option<String>,
+
+    /// Monitoring options
+    #[clap(flatten)]
+    monitoring_options: common::MonitoringOptions,
+
+    /// Warm start options
+    #[clap(flatten)]
+    warm_start_options: common::WarmStartOptions,
+
+    /// Observer options
+    #[clap(flatten)]
+    observer_options: common::ObserverOptions,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    logging::init();
+
+    // Disable storing worst ngrams for speed boost
+    if env::var("SHOW_WORST").is_err() {
+        env::set_var("SHOW_WORST", "false");
+    };
+
+    let options = Options::parse();
+
+    let remove_whitespace = |s: &str| -> String {
+        s.chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect()
+    };
+    let mut start_layouts: Vec<String> = options
+        .start_layouts
+        .iter()
+        .map(|s| remove_whitespace(s))
+        .collect();
+    start_layouts.extend(
+        options
+            .warm_start_options
+            .layouts()
+            .unwrap_or_else(|e| panic!("Could not read the warm start layouts: {:?}", e)),
+    );
+    let fix_from = start_layouts
+        .first()
+        .cloned()
+        .unwrap_or_else(|| remove_whitespace(&options.fix_from));
+
+    options.monitoring_options.serve_metrics();
+    let live_stream = options.observer_options.serve_live_stream();
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let result_cache = common::init_result_cache(&options.evaluation_parameters);
+
+    let mut optimization_params =
+        optimization::Parameters::from_yaml(&options.optimization_parameters).unwrap_or_else(|e| {
+            panic!(
+                "Could not read optimization parameters from {}: {:?}",
+                &options.optimization_parameters, e
+            )
+        });
+    if let Some(starts) = options.starts {
+        optimization_params.starts = starts;
+    }
+    if let Some(strategy) = &options.strategy {
+        optimization_params.strategy = strategy.parse().unwrap_or_else(|e| panic!("{}", e));
+    }
+    options
+        .swap_restriction_options
+        .apply(&mut optimization_params.swap_restrictions);
+    let shared_budget = RunBudget::shared(options.budget);
+    anytime::handle_interrupts(&shared_budget, || {});
+    optimization_params.budget = Some(shared_budget);
+
+    let cache: Option<Cache<f64>> = match !options.no_cache_results {
+        true => Some(common::cost_cache(&result_cache)),
+        false => None,
+    };
+
+    let results = optimization::optimize(
+        &optimization_params,
+        &fix_from,
+        &options.fix.clone().unwrap_or_default(),
+        layout_generator.as_ref(),
+        &start_layouts,
+        &evaluator,
+        cache,
+        options.observer_options.observer(
+            live_stream.as_ref(),
+            &evaluator,
+            layout_generator.as_ref(),
+        ),
+    );
+
+    common::log_bigram_cost_cache(&evaluator);
+    let best = match results.best() {
+        Some(best) => best,
+        None => {
+            log::error!("No climbs were performed");
+            return;
+        }
+    };
+    let layout = layout_generator.generate(&best.layout).unwrap();
+    let evaluation_result = evaluator.evaluate_layout(&layout);
+    if let Some(filename) = &options.append_solutions_to {
+        for basin in results.basins().iter().take(options.top) {
+            common::append_to_file(&basin.layout, filename);
+        }
+    }
+
+    println!(
+        "{}\n\n{}\n\n{}\n{}\n{}\n{}\n",
+        "Final result:".green().bold(),
+        layout,
+        layout.plot_compact(),
+        layout.plot(),
+        evaluation_result,
+        results.summary(options.top),
+    );
+}
@@ -0,0 +1,199 @@
+use keyboard_layout_optimizer::{anytime, common, logging};
+use layout_evaluation::cache::Cache;
+use layout_optimization_lns::optimization;
+
+use clap::Parser;
+use colored::Colorize;
+use std::env;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization - Large Neighborhood Search")]
+/// Repeatedly remove a few symbols from the layout and reinsert them at their best positions,
+/// removing more symbols while the search stagnates.
+struct Options {
+    /// Evaluation parameters
+    #[clap(flatten)]
+    evaluation_parameters: common::Options,
+
+    /// Do not optimize those keys (wrt. --start-layout or --fix-from)
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Fix the keys from this layout (will be overwritten by --start-layout)
+    #[clap(long, default_value = "")]
+    fix_from: String,
+
+    /// Filename of optimization configuration file
+    #[clap(short, long, default_value = "config/optimization/lns.yml")]
+    optimization_parameters: String,
+
+    /// Start the optimization with this layout (keys from left to right, top to bottom)
+    #[clap(short, long)]
+    start_layout: Option<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Do not cache intermediate results
+    #[clap(long)]
+    no_cache_results: bool,
+
+    /// Maximum number of iterations
+    #[clap(long)]
+    iterations: Option<u64>,
+
+    /// Largest number of symbols removed from the layout in an iteration
+    #[clap(long)]
+    max_destroy: Option<usize>,
+
+    /// Swap restriction options
+    #[clap(flatten)]
+    swap_restriction_options: common::SwapRestrictionOptions,
+
+    /// Append found layouts to file
+    #[clap(long)]
+    append_solutions_to: Option<String>,
+
+    /// Monitoring options
+    #[clap(flatten)]
+    monitoring_options: common::MonitoringOptions,
+
+    /// Warm start options
+    #[clap(flatten)]
+    warm_start_options: common::WarmStartOptions,
+
+    /// Stopping options
+    #[clap(flatten)]
+    stopping_options: common::StoppingOptions,
+
+    /// Observer options
+    #[clap(flatten)]
+    observer_options: common::ObserverOptions,
+
+    /// Publishing options
+    #[clap(flatten)]
+    publishing_options: common::PublishingOptions,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    logging::init();
+
+    // Disable storing worst ngrams for speed boost
+    if env::var("SHOW_WORST").is_err() {
+        env::set_var("SHOW_WORST", "false");
+    };
+
+    let options = Options::parse();
+
+    let remove_whitespace = |s: &str| -> String {
+        s.chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect()
+    };
+    let start_layout = options
+        .start_layout
+        .as_ref()
+        .map(|s| remove_whitespace(s))
+        .or_else(|| {
+            options
+                .warm_start_options
+                .layouts()
+                .unwrap_or_else(|e| panic!("Could not read the warm start layouts: {:?}", e))
+                .into_iter()
+                .next()
+        });
+    let fix_from = start_layout
+        .clone()
+        .unwrap_or_else(|| remove_whitespace(&options.fix_from));
+
+    options.monitoring_options.serve_metrics();
+    let live_stream = options.observer_options.serve_live_stream();
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let result_cache = common::init_result_cache(&options.evaluation_parameters);
+
+    let mut optimization_params =
+        optimization::Parameters::from_yaml(&options.optimization_parameters).unwrap_or_else(|e| {
+            panic!(
+                "Could not read optimization parameters from {}: {:?}",
+                &options.optimization_parameters, e
+            )
+        });
+    if let Some(iterations) = options.iterations {
+        optimization_params.iterations = iterations;
+    }
+    if let Some(max_destroy) = options.max_destroy {
+        optimization_params.max_destroy = max_destroy;
+    }
+    options
+        .swap_restriction_options
+        .apply(&mut optimization_params.swap_restrictions);
+    options
+        .stopping_options
+        .apply(&mut optimization_params.stopping);
+    let shared_budget = options.stopping_options.shared_budget();
+    anytime::handle_interrupts(&shared_budget, || {});
+    optimization_params.stopping.shared = Some(shared_budget);
+    if let Some(seconds) = options.stopping_options.budget_seconds() {
+        // the layouts of an iteration are evaluated in parallel
+        let rate = anytime::evaluation_rate(
+            &evaluator,
+            layout_generator.as_ref(),
+            &fix_from,
+            &options.fix.clone().unwrap_or_default(),
+        ) * rayon::current_num_threads() as f64;
+        optimization_params.fit_to_time_budget(seconds, rate);
+    }
+
+    let cache: Option<Cache<f64>> = match !options.no_cache_results {
+        true => Some(common::cost_cache(&result_cache)),
+        false => None,
+    };
+
+    let (layout_str, layout, stop_reason) = optimization::optimize(
+        &optimization_params,
+        &fix_from,
+        &options.fix.clone().unwrap_or_default(),
+        layout_generator.as_ref(),
+        start_layout.is_some(),
+        &evaluator,
+        cache,
+        options.observer_options.observer(
+            live_stream.as_ref(),
+            &evaluator,
+            layout_generator.as_ref(),
+        ),
+    );
+    common::log_bigram_cost_cache(&evaluator);
+    let evaluation_result = evaluator.evaluate_layout(&layout);
+    let cost = evaluation_result.total_cost();
+
+    println!(
+        "{}\n\n{}\n\n{}\n{}\n{}\nStopped: {}\n",
+        "Final result:".green().bold(),
+        layout,
+        layout.plot_compact(),
+        layout.plot(),
+        evaluation_result,
+        stop_reason,
+    );
+
+    if let Some(filename) = &options.append_solutions_to {
+        common::append_to_file(&layout_str, filename);
+    }
+
+    let o = &options.publishing_options;
+    if let Some(publish_as) = &o.publish_as {
+        if cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
+            common::publish_to_webservice(
+                &layout_str,
+                publish_as,
+                &o.publish_to,
+                &o.publish_layout_config,
+            );
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use keyboard_layout_optimizer::{anytime, common, logging, multi_resolution};
+use layout_evaluation::cache::Cache;
+use layout_optimization_sa::optimization as sa;
+
+use clap::Parser;
+use colored::Colorize;
+use std::env;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization - Multi-resolution")]
+/// First assign the symbols (and symbol groups) to the clusters of the fingers on each layer,
+/// evaluating each assignment with the most frequent symbol of a cluster on its cheapest key.
+/// Then arrange the symbols within their clusters with simulated annealing.
+struct Options {
+    /// Evaluation parameters
+    #[clap(flatten)]
+    evaluation_parameters: common::Options,
+
+    /// Do not optimize those keys (wrt. --start-layout or --fix-from)
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Fix the keys from this layout (will be overwritten by --start-layout)
+    #[clap(long, default_value = "")]
+    fix_from: String,
+
+    /// Filename of multi-resolution configuration file
+    #[clap(
+        short,
+        long,
+        default_value = "config/optimization/multi_resolution.yml"
+    )]
+    optimization_parameters: String,
+
+    /// Filename of the simulated annealing's configuration file (for the refine phase)
+    #[clap(long, default_value = "config/optimization/sa.yml")]
+    sa_parameters: String,
+
+    /// Start the optimization with this layout (keys from left to right, top to bottom)
+    #[clap(short, long)]
+    start_layout: Option<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Do not cache intermediate results
+    #[clap(long)]
+    no_cache_results: bool,
+
+    /// Keep the symbols of each of the given groups in one cluster (e.g. "aeiou"). Overrides
+    /// the multi-resolution config
+    #[clap(long)]
+    groups: Vec<String>,
+
+    /// Only run the coarse phase
+    #[clap(long)]
+    no_refine: bool,
+
+    /// Append found layouts to file
+    #[clap(long)]
+    append_solutions_to: Option<String>,
+
+    /// Monitoring options
+    #[clap(flatten)]
+    monitoring_options: common::MonitoringOptions,
+
+    /// Warm start options
+    #[clap(flatten)]
+    warm_start_options: common::WarmStartOptions,
+
+    /// Stopping options (of the refine phase, except for the budget)
+    #[clap(flatten)]
+    stopping_options: common::StoppingOptions,
+
+    /// Observer options
+    #[clap(flatten)]
+    observer_options: common::ObserverOptions,
+
+    /// Publishing options
+    #[clap(flatten)]
+    publishing_options: common::PublishingOptions,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    logging::init();
+
+    // Disable storing worst ngrams for speed boost
+    if env::var("SHOW_WORST").is_err() {
+        env::set_var("SHOW_WORST", "false");
+    };
+
+    let options = Options::parse();
+
+    let remove_whitespace = |s: &str| -> String {
+        s.chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect()
+    };
+    let start_layout = options
+        .start_layout
+        .as_ref()
+        .map(|s| remove_whitespace(s))
+        .or_else(|| {
+            options
+                .warm_start_options
+                .layouts()
+                .unwrap_or_else(|e| panic!("Could not read the warm start layouts: {:?}", e))
+                .into_iter()
+                .next()
+        });
+    let fix_from = start_layout
+        .clone()
+        .unwrap_or_else(|| remove_whitespace(&options.fix_from));
+
+    options.monitoring_options.serve_metrics();
+    let live_stream = options.observer_options.serve_live_stream();
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let result_cache = common::init_result_cache(&options.evaluation_parameters);
+
+    let mut optimization_params = multi_resolution::Parameters::from_yaml(
+        &options.optimization_parameters,
+    )
+    .unwrap_or_else(|e| {
+        panic!(
+            "Could not read multi-resolution parameters from {}: {:?}",
+            &options.optimization_parameters, e
+        )
+    });
+    if !options.groups.is_empty() {
+        optimization_params.groups = options.groups.clone();
+    }
+    if options.no_refine {
+        optimization_params.refine = false;
+    }
+
+    let mut sa_params = sa::Parameters::from_yaml(&options.sa_parameters).unwrap_or_else(|e| {
+        panic!(
+            "Could not read simulated annealing parameters from {}: {:?}",
+            &options.sa_parameters, e
+        )
+    });
+    sa_params.correct_init_temp();
+    options.stopping_options.apply(&mut sa_params.stopping);
+    let shared_budget = options.stopping_options.shared_budget();
+    anytime::handle_interrupts(&shared_budget, || {});
+    if let Some(seconds) = options.stopping_options.budget_seconds() {
+        let rate = anytime::evaluation_rate(
+            &evaluator,
+            layout_generator.as_ref(),
+            &fix_from,
+            &options.fix.clone().unwrap_or_default(),
+        );
+        sa_params.fit_to_time_budget(seconds, rate);
+    }
+
+    let cache: Option<Cache<f64>> = match !options.no_cache_results {
+        true => Some(common::cost_cache(&result_cache)),
+        false => None,
+    };
+
+    let result = multi_resolution::optimize(
+        &optimization_params,
+        &sa_params,
+        &fix_from,
+        &options.fix.clone().unwrap_or_default(),
+        &layout_generator,
+        start_layout.is_some(),
+        &evaluator,
+        cache,
+        Some(&shared_budget),
+        options.observer_options.observer(
+            live_stream.as_ref(),
+            &evaluator,
+            layout_generator.as_ref(),
+        ),
+    );
+    common::log_bigram_cost_cache(&evaluator);
+    let evaluation_result = evaluator.evaluate_layout(&result.layout);
+    let cost = evaluation_result.total_cost();
+
+    println!(
+        "{}\n\n{}\n\n{}\n{}\n{}\nStopped: {}\n\n{}\n",
+        "Final result:".green().bold(),
+        result.layout,
+        result.layout.plot_compact(),
+        result.layout.plot(),
+        evaluation_result,
+        result.stop_reason,
+        result,
+    );
+
+    if let Some(filename) = &options.append_solutions_to {
+        common::append_to_file(&result.layout_str, filename);
+    }
+
+    let o = &options.publishing_options;
+    if let Some(publish_as) = &o.publish_as {
+        if cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
+            common::publish_to_webservice(
+                &result.layout_str,
+                publish_as,
+                &o.publish_to,
+                &o.publish_layout_config,
+            );
+        }
+    }
+}
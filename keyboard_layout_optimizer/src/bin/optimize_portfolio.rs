@@ -0,0 +1,231 @@
+use keyboard_layout_optimizer::{
+    anytime::{self, RunBudget},
+    common, logging, portfolio,
+};
+use layout_evaluation::cache::Cache;
+use layout_optimization_genetic::optimization as genetic;
+use layout_optimization_sa::optimization as sa;
+
+use clap::Parser;
+use colored::Colorize;
+use std::env;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization - Portfolio")]
+/// Run the genetic algorithm and simulated annealing at the same time under one shared budget.
+/// The genetic algorithm receives the best layouts of the annealing as immigrants, and each
+/// annealing chain restarts from the best layout of both.
+struct Options {
+    /// Evaluation parameters
+    #[clap(flatten)]
+    evaluation_parameters: common::Options,
+
+    /// Do not optimize those keys (wrt. --start-layout or --fix-from)
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Fix the keys from this layout (will be overwritten by --start-layout)
+    #[clap(long, default_value = "")]
+    fix_from: String,
+
+    /// Filename of portfolio configuration file
+    #[clap(short, long, default_value = "config/optimization/portfolio.yml")]
+    optimization_parameters: String,
+
+    /// Filename of the genetic algorithm's configuration file
+    #[clap(long, default_value = "config/optimization/genetic.yml")]
+    genetic_parameters: String,
+
+    /// Filename of the simulated annealing's configuration file
+    #[clap(long, default_value = "config/optimization/sa.yml")]
+    sa_parameters: String,
+
+    /// Start optimization from this layout (keys from left to right, top to bottom)
+    #[clap(short, long)]
+    start_layout: Option<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Do not cache intermediate results
+    #[clap(long)]
+    no_cache_results: bool,
+
+    /// Stop both optimizers after this many seconds. Overrides the portfolio config
+    #[clap(long)]
+    time_limit: Option<f64>,
+
+    /// Stop both optimizers after this many layout evaluations in total. Overrides the
+    /// portfolio config
+    #[clap(long)]
+    max_evaluations: Option<u64>,
+
+    /// Budget of both optimizers, either a duration (e.g. "90m", "2h", "1h30m") or a number of
+    /// layout evaluations. The schedules are fitted to a duration. Overrides the portfolio config
+    #[clap(long)]
+    budget: Option<RunBudget>,
+
+    /// Append found layouts to file
+    #[clap(long)]
+    append_solutions_to: Option<String>,
+
+    /// Monitoring options
+    #[clap(flatten)]
+    monitoring_options: common::MonitoringOptions,
+
+    /// Warm start options
+    #[clap(flatten)]
+    warm_start_options: common::WarmStartOptions,
+
+    /// Observer options
+    #[clap(flatten)]
+    observer_options: common::ObserverOptions,
+
+    /// Publishing options
+    #[clap(flatten)]
+    publishing_options: common::PublishingOptions,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    logging::init();
+
+    // Disable storing worst ngrams for speed boost
+    if env::var("SHOW_WORST").is_err() {
+        env::set_var("SHOW_WORST", "false");
+    };
+
+    let options = Options::parse();
+
+    let remove_whitespace = |s: &str| -> String {
+        s.chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect()
+    };
+    let start_layout = options
+        .start_layout
+        .as_ref()
+        .map(|s| remove_whitespace(s))
+        .or_else(|| {
+            options
+                .warm_start_options
+                .layouts()
+                .unwrap_or_else(|e| panic!("Could not read the warm start layouts: {:?}", e))
+                .into_iter()
+                .next()
+        });
+    let fix_from = start_layout
+        .clone()
+        .unwrap_or_else(|| remove_whitespace(&options.fix_from));
+
+    options.monitoring_options.serve_metrics();
+    let live_stream = options.observer_options.serve_live_stream();
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let result_cache = common::init_result_cache(&options.evaluation_parameters);
+
+    let mut portfolio_params = portfolio::Parameters::from_yaml(&options.optimization_parameters)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Could not read portfolio parameters from {}: {:?}",
+                &options.optimization_parameters, e
+            )
+        });
+    if let Some(time_limit) = options.time_limit {
+        portfolio_params.time_limit = Some(time_limit);
+    }
+    if let Some(max_evaluations) = options.max_evaluations {
+        portfolio_params.max_evaluations = Some(max_evaluations);
+    }
+    match options.budget {
+        Some(RunBudget::Time(seconds)) => portfolio_params.time_limit = Some(seconds),
+        Some(RunBudget::Evaluations(evaluations)) => {
+            portfolio_params.max_evaluations = Some(evaluations)
+        }
+        None => {}
+    }
+    let budget = portfolio_params.budget();
+    anytime::handle_interrupts(&budget, || {});
+
+    let mut genetic_params = genetic::Parameters::from_yaml(&options.genetic_parameters)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Could not read genetic parameters from {}: {:?}",
+                &options.genetic_parameters, e
+            )
+        });
+    let mut sa_params = sa::Parameters::from_yaml(&options.sa_parameters).unwrap_or_else(|e| {
+        panic!(
+            "Could not read simulated annealing parameters from {}: {:?}",
+            &options.sa_parameters, e
+        )
+    });
+
+    if let Some(seconds) = portfolio_params.time_limit {
+        let rate = anytime::evaluation_rate(
+            &evaluator,
+            layout_generator.as_ref(),
+            &fix_from,
+            &options.fix.clone().unwrap_or_default(),
+        );
+        // the annealing occupies one thread, the genetic algorithm evaluates on the others
+        let threads = rayon::current_num_threads().saturating_sub(1).max(1);
+        genetic_params.fit_to_time_budget(seconds, rate * threads as f64);
+        sa_params.fit_to_time_budget(seconds, rate);
+    }
+
+    let cache: Option<Cache<f64>> = match !options.no_cache_results {
+        true => Some(common::cost_cache(&result_cache)),
+        false => None,
+    };
+
+    let result = portfolio::optimize(
+        &portfolio_params,
+        &budget,
+        &genetic_params,
+        &sa_params,
+        &fix_from,
+        &options.fix.clone().unwrap_or_default(),
+        &layout_generator,
+        start_layout.is_some(),
+        &evaluator,
+        cache,
+        options.observer_options.observer(
+            live_stream.as_ref(),
+            &evaluator,
+            layout_generator.as_ref(),
+        ),
+    );
+    common::log_bigram_cost_cache(&evaluator);
+    let evaluation_result = evaluator.evaluate_layout(&result.layout);
+    let cost = evaluation_result.total_cost();
+
+    println!(
+        "{}\n\n{}\n\n{}\n{}\n{}\nStopped: {}\n\n{}\n",
+        "Final result:".green().bold(),
+        result.layout,
+        result.layout.plot_compact(),
+        result.layout.plot(),
+        evaluation_result,
+        result.stop_reason,
+        result,
+    );
+
+    if let Some(filename) = &options.append_solutions_to {
+        common::append_to_file(&result.layout_str, filename);
+    }
+
+    let o = &options.publishing_options;
+    if let Some(publish_as) = &o.publish_as {
+        if cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
+            common::publish_to_webservice(
+                &result.layout_str,
+                publish_as,
+                &o.publish_to,
+                &o.publish_layout_config,
+            );
+        }
+    }
+}
@@ -1,11 +1,15 @@
-use keyboard_layout_optimizer::common;
+use keyboard_layout_optimizer::{
+    anytime::{self, RunBudget},
+    common, logging,
+};
 use layout_evaluation::cache::Cache;
-use layout_optimization_sa::optimization;
+use layout_optimization_common::history::{self, ConvergenceHistory};
+use layout_optimization_sa::{optimization, restarts};
 
 use clap::Parser;
 use colored::Colorize;
 use rayon::iter::{ParallelBridge, ParallelIterator};
-use std::{env, process};
+use std::{env, sync::Mutex};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout optimization - Simulated Annealing")]
@@ -46,18 +50,88 @@ struct Options {
     #[clap(short, long)]
     greedy: bool,
 
+    /// Derive the initial temperature and cooling rate from short probe chains (Will be overwritten by --greedy and --init-temp)
+    #[clap(long)]
+    auto_tune: bool,
+
+    /// Use this annealing schedule preset (fast, thorough, overnight) instead of the configured
+    /// schedule (Will be overwritten by --greedy and --init-temp)
+    #[clap(long)]
+    preset: Option<String>,
+
     /// If used, log every single iteration instead of every 100th.
     #[clap(long)]
     log_everything: bool,
 
+    /// Evaluate mirror-equivalent layouts as their canonical representative, so that they share
+    /// one entry of the result cache (only use with symmetric metrics)
+    #[clap(long)]
+    canonicalize_mirrors: bool,
+
+    /// Swap restriction options
+    #[clap(flatten)]
+    swap_restriction_options: common::SwapRestrictionOptions,
+
+    /// Keep these symbols at their positions as soft constraint: the search may move them at a
+    /// growing penalty (see `soft_constraints` in the optimization config)
+    #[clap(long)]
+    soft_fix: Option<String>,
+
+    /// Treat the swap groups (--same-layer-swaps and --swap-groups) as soft constraints
+    #[clap(long)]
+    soft_swap_groups: bool,
+
+    /// Progressively freeze keys whose position stayed the same over this many accepted states
+    /// (see `freezing` in the optimization config)
+    #[clap(long)]
+    freeze_after: Option<u64>,
+
+    /// Run this many annealing chains per start layout and report the best layout of all chains
+    /// (see `restarts` in the optimization config)
+    #[clap(long)]
+    chains: Option<usize>,
+
+    /// Run the chains one after the other instead of in parallel
+    #[clap(long)]
+    sequential_chains: bool,
+
+    /// Start each chain from the best layout of the chains finished so far
+    #[clap(long)]
+    cross_seed: bool,
+
     /// Append found layouts to file
     #[clap(long)]
     append_solutions_to: Option<String>,
 
+    /// Record the best and mean costs of every bucket of iterations and write them to file (as
+    /// JSON if the filename ends with ".json", otherwise as CSV)
+    #[clap(long)]
+    history: Option<String>,
+
+    /// Number of iterations per entry of the convergence history
+    #[clap(long)]
+    history_bucket: Option<u64>,
+
     /// Repeat optimizations indefinitely
     #[clap(long)]
     run_forever: bool,
 
+    /// Monitoring options
+    #[clap(flatten)]
+    monitoring_options: common::MonitoringOptions,
+
+    /// Warm start options
+    #[clap(flatten)]
+    warm_start_options: common::WarmStartOptions,
+
+    /// Stopping options
+    #[clap(flatten)]
+    stopping_options: common::StoppingOptions,
+
+    /// Observer options
+    #[clap(flatten)]
+    observer_options: common::ObserverOptions,
+
     /// Publishing options
     #[clap(flatten)]
     publishing_options: common::PublishingOptions,
@@ -108,7 +182,7 @@ impl Iterator for LayoutIterator {
 
 fn main() {
     dotenv::dotenv().ok();
-    env_logger::init();
+    logging::init();
 
     // Disable storing worst ngrams for speed boost
     if env::var("SHOW_WORST").is_err() {
@@ -117,17 +191,15 @@ fn main() {
 
     let final_results: Cache<f64> = Cache::new();
 
+    let options = Options::parse();
+
     // Handle Ctrl+C
+    let shared_budget = options.stopping_options.shared_budget();
     let cloned_final_results = final_results.clone();
-    ctrlc::set_handler(move || {
+    anytime::handle_interrupts(&shared_budget, move || {
         // Display a summary of the optimization.
         println!("\n\n{}\n", cloned_final_results);
-        // Stop execution
-        process::exit(0);
-    })
-    .expect("Error setting Ctrl-C handler");
-
-    let options = Options::parse();
+    });
 
     let fix_from: String = options
         .fix_from
@@ -135,7 +207,7 @@ fn main() {
         .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
         .collect();
 
-    let start_layouts: Vec<String> = options
+    let mut start_layouts: Vec<String> = options
         .start_layouts
         .iter()
         .map(|s| {
@@ -144,8 +216,21 @@ fn main() {
                 .collect::<String>()
         })
         .collect();
-
-    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters);
+    start_layouts.extend(
+        options
+            .warm_start_options
+            .layouts()
+            .unwrap_or_else(|e| panic!("Could not read the warm start layouts: {:?}", e)),
+    );
+
+    options.monitoring_options.serve_metrics();
+    let live_stream = options.observer_options.serve_live_stream();
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let holdout_evaluator = common::init_holdout_evaluator(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the holdout evaluation: {:?}", e));
+    let result_cache = common::init_result_cache(&options.evaluation_parameters);
 
     let mut optimization_params = optimization::Parameters::from_yaml(
         &options.optimization_parameters,
@@ -156,12 +241,86 @@ fn main() {
             &options.optimization_parameters
         )
     });
+    if let Some(preset) = &options.preset {
+        optimization_params.preset = Some(preset.parse().unwrap_or_else(|e| panic!("{}", e)));
+    }
+    if options.chains.is_some() || options.sequential_chains || options.cross_seed {
+        let mut restart_params = optimization_params.restarts.clone().unwrap_or_default();
+        if let Some(chains) = options.chains {
+            restart_params.chains = chains;
+        }
+        if options.sequential_chains {
+            restart_params.parallel = false;
+        }
+        if options.cross_seed {
+            restart_params.cross_seed = true;
+        }
+        optimization_params.restarts = Some(restart_params);
+    }
+    // an evaluation budget is spread over the schedule of the preset (if any)
+    if let Some(RunBudget::Evaluations(evaluations)) = options.stopping_options.budget {
+        optimization_params.budget = Some(evaluations);
+    }
+    optimization_params.apply_preset();
+    if let Some(seconds) = options.stopping_options.budget_seconds() {
+        let rate = anytime::evaluation_rate(
+            &evaluator,
+            layout_generator.as_ref(),
+            start_layouts.first().unwrap_or(&fix_from),
+            &options.fix.clone().unwrap_or_default(),
+        );
+        // the processes run in parallel, but the chains of a process may run one after another
+        let sequential_chains = match &optimization_params.restarts {
+            Some(restart_params) if !restart_params.parallel => restart_params.chains.max(1),
+            _ => 1,
+        };
+        optimization_params.fit_to_time_budget(seconds / sequential_chains as f64, rate);
+    }
+    if options.auto_tune && optimization_params.auto_tune.is_none() {
+        optimization_params.auto_tune = Some(optimization::AutoTuneParameters::default());
+    }
     if options.greedy {
         optimization_params.init_temp = Some(f64::MIN_POSITIVE);
+        optimization_params.auto_tune = None;
     } else if options.init_temp.is_some() {
         optimization_params.init_temp = options.init_temp;
+        optimization_params.auto_tune = None;
     }
     optimization_params.correct_init_temp();
+    if options.canonicalize_mirrors {
+        optimization_params.canonicalize_mirrors = true;
+    }
+    options
+        .swap_restriction_options
+        .apply(&mut optimization_params.swap_restrictions);
+    if options.soft_fix.is_some() || options.soft_swap_groups {
+        let mut soft_constraints = optimization_params
+            .soft_constraints
+            .clone()
+            .unwrap_or_default();
+        if let Some(soft_fix) = &options.soft_fix {
+            soft_constraints.fix = soft_fix.clone();
+        }
+        if options.soft_swap_groups {
+            soft_constraints.swap_groups = true;
+        }
+        optimization_params.soft_constraints = Some(soft_constraints);
+    }
+    if let Some(stable_states) = options.freeze_after {
+        let mut freezing = optimization_params.freezing.clone().unwrap_or_default();
+        freezing.stable_states = stable_states;
+        optimization_params.freezing = Some(freezing);
+    }
+    if options.history.is_some() {
+        optimization_params.record_history = true;
+    }
+    options
+        .stopping_options
+        .apply(&mut optimization_params.stopping);
+    optimization_params.stopping.shared = Some(shared_budget.clone());
+    if let Some(history_bucket) = options.history_bucket {
+        optimization_params.history_bucket = history_bucket;
+    }
 
     let mut layouts: Vec<String> = start_layouts.to_vec();
     if layouts.is_empty() {
@@ -171,11 +330,14 @@ fn main() {
     let start_from_layout = !start_layouts.is_empty();
 
     let cache: Option<Cache<f64>> = match !options.no_cache_results {
-        true => Some(Cache::new()),
+        true => Some(common::cost_cache(&result_cache)),
         false => None,
     };
 
+    let histories: Mutex<Vec<ConvergenceHistory>> = Mutex::new(Vec::new());
+
     layout_iterator
+        .take_while(|_| shared_budget.check().is_none())
         .enumerate()
         .par_bridge()
         .for_each(|(i, fix_from)| {
@@ -194,31 +356,85 @@ fn main() {
             }
 
             // Perform the optimization.
-            let (layout_str, layout) = optimization::optimize(
-                &process_id,
-                &optimization_params,
-                &fix_from,
-                &options.fix.clone().unwrap_or_default(),
-                &layout_generator,
-                start_from_layout,
+            let observer = options.observer_options.observer(
+                live_stream.as_ref(),
                 &evaluator,
-                options.log_everything,
-                cache.clone(),
-                None,
+                layout_generator.as_ref(),
             );
+            let (layout_str, layout, run_histories, stop_reason) =
+                match &optimization_params.restarts {
+                    Some(restart_params) => {
+                        let result = restarts::optimize_restarts(
+                            restart_params,
+                            &optimization_params,
+                            &fix_from,
+                            &options.fix.clone().unwrap_or_default(),
+                            &layout_generator,
+                            start_from_layout,
+                            &evaluator,
+                            options.log_everything,
+                            cache.clone(),
+                            observer,
+                        );
+                        println!(
+                            "{} {}\n{}\n",
+                            format!("{}:", process_id).yellow().bold(),
+                            "Chains:".green().bold(),
+                            result,
+                        );
+                        (
+                            result.layout_str,
+                            result.layout,
+                            result.histories,
+                            result.stop_reason,
+                        )
+                    }
+                    None => {
+                        let (layout_str, layout, history, stop_reason) = optimization::optimize(
+                            &process_id,
+                            &optimization_params,
+                            &fix_from,
+                            &options.fix.clone().unwrap_or_default(),
+                            &layout_generator,
+                            start_from_layout,
+                            &evaluator,
+                            options.log_everything,
+                            cache.clone(),
+                            None,
+                            observer,
+                        );
+                        (
+                            layout_str,
+                            layout,
+                            history.into_iter().collect(),
+                            stop_reason,
+                        )
+                    }
+                };
+            common::log_bigram_cost_cache(&evaluator);
             let evaluation_result = evaluator.evaluate_layout(&layout);
             let cost = evaluation_result.total_cost();
             let _ = final_results.get_or_insert_with(&layout_str, || cost);
 
+            if let Some(holdout_evaluator) = &holdout_evaluator {
+                common::check_overfitting(
+                    &layout,
+                    cost,
+                    holdout_evaluator,
+                    options.evaluation_parameters.holdout_warn_gap,
+                );
+            }
+
             // Plot some information regarding the layout.
             println!(
-                "{} {}\n\n{}\n\n{}\n{}\n{}\n\n{}\n",
+                "{} {}\n\n{}\n\n{}\n{}\n{}\nStopped: {}\n\n{}\n",
                 format!("{}:", process_id).yellow().bold(),
                 "Final result:".green().bold(),
                 layout,
                 layout.plot_compact(),
                 layout.plot(),
                 evaluation_result,
+                stop_reason,
                 final_results.highlighted_fmt(Some(&layout_str), 10),
             );
 
@@ -227,15 +443,29 @@ fn main() {
                 common::append_to_file(&layout_str, filename);
             }
 
+            if let (false, Some(filename)) = (run_histories.is_empty(), &options.history) {
+                let mut histories = histories.lock().unwrap();
+                histories.extend(run_histories);
+                if let Err(e) = history::write(&histories, filename) {
+                    log::error!(
+                        "Couldn't write convergence history to file {}: {}",
+                        filename,
+                        e
+                    );
+                }
+            }
+
             // Publish to webservice.
             let o = &options.publishing_options;
-            if o.publish_as.is_some() && cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
-                common::publish_to_webservice(
-                    &layout_str,
-                    o.publish_as.as_ref().unwrap(),
-                    &o.publish_to,
-                    &o.publish_layout_config,
-                );
+            if let Some(publish_as) = &o.publish_as {
+                if cost < o.publish_if_cost_below.unwrap_or(f64::INFINITY) {
+                    common::publish_to_webservice(
+                        &layout_str,
+                        publish_as,
+                        &o.publish_to,
+                        &o.publish_layout_config,
+                    );
+                }
             }
         });
 }
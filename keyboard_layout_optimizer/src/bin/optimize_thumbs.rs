@@ -0,0 +1,274 @@
+use keyboard_layout::{
+    config::LayoutConfig, key::Finger, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::cache::Cache;
+use layout_optimization_hill_climbing::optimization;
+
+use clap::Parser;
+use colored::Colorize;
+use rayon::prelude::*;
+use std::{env, sync::Arc};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization - Thumb cluster")]
+/// Keep the finger clusters of a layout and search only the assignment of the thumb keys (space,
+/// enter, backspace, layer keys, modifiers, ...), evaluated with the thumb overlay.
+///
+/// All thumb keys of the layout config are permuted, including those that are fixed for the
+/// regular optimizers. Small search spaces are evaluated exhaustively, larger ones with
+/// multi-start hill climbing.
+struct Options {
+    /// Layout keys from left to right, top to bottom as for the other optimizers (defaults to
+    /// the base layout of the layout config)
+    layout_str: Option<String>,
+
+    /// Do not move those thumb symbols
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Evaluation overlay for the thumb pass (applied before --eval-overlays, "" to disable)
+    #[clap(long, default_value = "config/evaluation/thumbs.yml")]
+    thumb_overlay: String,
+
+    /// Evaluate all distinct thumb assignments if there are at most this many
+    #[clap(long, default_value = "20000")]
+    exhaustive_limit: u64,
+
+    /// Filename of the hill climbing configuration file (for larger search spaces)
+    #[clap(short, long, default_value = "config/optimization/hill_climbing.yml")]
+    optimization_parameters: String,
+
+    /// Number of climbs (for larger search spaces)
+    #[clap(long)]
+    starts: Option<usize>,
+
+    /// Number of thumb assignments to list
+    #[clap(long, default_value = "10")]
+    top: usize,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Do not cache intermediate results
+    #[clap(long)]
+    no_cache_results: bool,
+
+    /// Evaluation parameters
+    #[clap(flatten)]
+    evaluation_parameters: common::Options,
+}
+
+/// Rearrange `chars` into the next lexicographically greater permutation. Returns `false` (and
+/// leaves `chars` unchanged) if there is none.
+fn next_permutation(chars: &mut [char]) -> bool {
+    let i = match (1..chars.len()).rev().find(|i| chars[i - 1] < chars[*i]) {
+        Some(i) => i,
+        None => return false,
+    };
+    let j = (i..chars.len())
+        .rev()
+        .find(|j| chars[*j] > chars[i - 1])
+        .unwrap();
+    chars.swap(i - 1, j);
+    chars[i..].reverse();
+
+    true
+}
+
+/// Number of distinct arrangements of the given symbols.
+fn n_arrangements(chars: &[char]) -> f64 {
+    let factorial = |n: usize| (1..=n).map(|k| k as f64).product::<f64>();
+    let mut sorted = chars.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    let duplicates: f64 = sorted
+        .iter()
+        .map(|c| factorial(chars.iter().filter(|d| *d == c).count()))
+        .product();
+
+    factorial(chars.len()) / duplicates
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    logging::init();
+
+    // Disable storing worst ngrams for speed boost
+    if env::var("SHOW_WORST").is_err() {
+        env::set_var("SHOW_WORST", "false");
+    };
+
+    let mut options = Options::parse();
+    if !options.thumb_overlay.is_empty() {
+        options
+            .evaluation_parameters
+            .eval_overlays
+            .insert(0, options.thumb_overlay.clone());
+    }
+    if options.evaluation_parameters.grouped_layout_generator {
+        log::warn!("The thumb pass does not support the grouped layout generator");
+    }
+
+    let layout_config_file = &options.evaluation_parameters.layout_config;
+    let layout_config = LayoutConfig::from_yaml(layout_config_file)
+        .unwrap_or_else(|e| panic!("Could not load config file {}: {}", layout_config_file, e));
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let mut base_layout = layout_config.base_layout;
+    if let Some(layout_str) = &options.layout_str {
+        let layout_str: String = layout_str
+            .chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect();
+        base_layout
+            .apply_layout_string(&layout_str)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    // only the (not blocked) thumb keys are permutable in this pass
+    let is_thumb = |i: usize| keyboard.keys[i].finger == Finger::Thumb && !keyboard.keys[i].blocked;
+    base_layout.set_fixed_keys(|i| !is_thumb(i));
+    let placeholder = base_layout.placeholder.chars().next().unwrap_or('□');
+    let thumb_str: String = base_layout
+        .keys
+        .iter()
+        .flatten()
+        .enumerate()
+        .filter(|(i, _)| is_thumb(*i))
+        .map(|(_, layers)| {
+            layers
+                .first()
+                .and_then(|l| l.chars().next())
+                .unwrap_or(placeholder)
+        })
+        .collect();
+    if thumb_str.is_empty() {
+        panic!("The layout config {} has no thumb keys", layout_config_file);
+    }
+    let thumb_positions: Vec<_> = keyboard
+        .keys
+        .iter()
+        .filter(|k| k.finger == Finger::Thumb && !k.blocked)
+        .map(|k| k.matrix_position)
+        .collect();
+
+    let layout_generator: Box<dyn LayoutGenerator> = Box::new(NeoLayoutGenerator::from_object(
+        base_layout,
+        keyboard.clone(),
+    ));
+    let evaluator = common::init_evaluator(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let cost = |thumbs: &str| -> f64 {
+        let layout = layout_generator.generate(thumbs).unwrap();
+        evaluator.evaluate_layout(&layout).total_cost()
+    };
+
+    let fixed = options.fix.clone().unwrap_or_default();
+    let permutable: Vec<usize> = thumb_str
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| !fixed.contains(*c))
+        .map(|(i, _)| i)
+        .collect();
+    let mut chars: Vec<char> = thumb_str.chars().filter(|c| !fixed.contains(*c)).collect();
+    let n = n_arrangements(&chars);
+    log::info!(
+        "Thumb keys: '{}' ({} permutable, {} distinct assignments)",
+        thumb_str,
+        permutable.len(),
+        n
+    );
+
+    let mut results: Vec<(String, f64)> = if n <= options.exhaustive_limit as f64 {
+        log::info!("Evaluating all {} thumb assignments", n);
+        let template: Vec<char> = thumb_str.chars().collect();
+        chars.sort_unstable();
+        let mut candidates = Vec::new();
+        loop {
+            let mut thumbs = template.clone();
+            permutable
+                .iter()
+                .zip(chars.iter())
+                .for_each(|(i, c)| thumbs[*i] = *c);
+            candidates.push(thumbs.into_iter().collect::<String>());
+            if !next_permutation(&mut chars) {
+                break;
+            }
+        }
+
+        candidates
+            .into_par_iter()
+            .map(|thumbs| {
+                let c = cost(&thumbs);
+                (thumbs, c)
+            })
+            .collect()
+    } else {
+        log::info!(
+            "More than {} thumb assignments, using hill climbing",
+            options.exhaustive_limit
+        );
+        let mut params = optimization::Parameters::from_yaml(&options.optimization_parameters)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Could not read optimization parameters from {}: {:?}",
+                    &options.optimization_parameters, e
+                )
+            });
+        if let Some(starts) = options.starts {
+            params.starts = starts;
+        }
+        let cache: Option<Cache<f64>> = match !options.no_cache_results {
+            true => Some(Cache::new()),
+            false => None,
+        };
+        let climbs = optimization::optimize(
+            &params,
+            &thumb_str,
+            &fixed,
+            layout_generator.as_ref(),
+            std::slice::from_ref(&thumb_str),
+            &evaluator,
+            cache,
+            None,
+        );
+        climbs
+            .basins()
+            .into_iter()
+            .map(|basin| (basin.layout, basin.cost))
+            .collect()
+    };
+    // prefer the current assignment among equally good ones
+    results.sort_by(|(t1, c1), (t2, c2)| {
+        c1.partial_cmp(c2)
+            .unwrap()
+            .then_with(|| (*t2 == thumb_str).cmp(&(*t1 == thumb_str)))
+    });
+
+    let current_cost = cost(&thumb_str);
+    let (best_thumbs, best_cost) = results[0].clone();
+    let layout = layout_generator.generate(&best_thumbs).unwrap();
+    let evaluation_result = evaluator.evaluate_layout(&layout);
+
+    println!(
+        "{}\n\n{}\n{}\n{}\n",
+        "Final result:".green().bold(),
+        layout.plot_compact(),
+        layout.plot(),
+        evaluation_result,
+    );
+    println!(
+        "Current thumb assignment: '{}' ({:.4})\n",
+        thumb_str, current_cost
+    );
+    println!("{:>12}  {:>10}  thumb keys", "cost", "difference");
+    for (thumbs, c) in results.iter().take(options.top) {
+        println!("{:>12.4}  {:>+10.4}  '{}'", c, c - current_cost, thumbs);
+    }
+    println!("\nBest thumb assignment ({:.4}):", best_cost);
+    for (position, c) in thumb_positions.iter().zip(best_thumbs.chars()) {
+        println!("  {:?}: '{}'", (position.0, position.1), c);
+    }
+}
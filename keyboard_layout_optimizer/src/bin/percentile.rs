@@ -0,0 +1,196 @@
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::results::EvaluationResult;
+use layout_optimization_common::LayoutPermutator;
+
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout percentile ranking")]
+/// Rank layouts within the cost distribution of random layouts (under the same constraints)
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Number of random layouts to generate
+    #[clap(short, long, default_value = "1000")]
+    samples: usize,
+
+    /// Keep those keys at their position in the random layouts
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Only permutate keys within the same layer (segment of the layout string)
+    #[clap(long)]
+    same_layer_swaps: bool,
+
+    /// Only permutate symbols within the same of the given groups (e.g. "aeiou")
+    #[clap(long)]
+    swap_groups: Vec<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+/// Position of a cost value within the distribution of random layouts' costs
+#[derive(Serialize, Debug)]
+struct MetricRank {
+    metric: String,
+    cost: f64,
+    baseline_mean: f64,
+    baseline_std: f64,
+    /// Percentage of random layouts with a higher (worse) cost
+    percentile: f64,
+    z_score: f64,
+}
+
+impl MetricRank {
+    fn new(metric: &str, cost: f64, baseline: &[f64]) -> Self {
+        let n = baseline.len().max(1) as f64;
+        let mean = baseline.iter().sum::<f64>() / n;
+        let std = (baseline.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / n).sqrt();
+
+        // ties count half to keep the percentile of identical layouts at 50%
+        let worse = baseline.iter().filter(|c| **c > cost).count() as f64;
+        let ties = baseline.iter().filter(|c| **c == cost).count() as f64;
+        let percentile = 100.0 * (worse + 0.5 * ties) / n;
+
+        let z_score = if std > 0.0 { (cost - mean) / std } else { 0.0 };
+
+        Self {
+            metric: metric.to_string(),
+            cost,
+            baseline_mean: mean,
+            baseline_std: std,
+            percentile,
+            z_score,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct LayoutRanking {
+    layout: String,
+    samples: usize,
+    ranks: Vec<MetricRank>,
+}
+
+/// Weighted costs of all individual metrics together with the total cost
+fn metric_costs(result: &EvaluationResult) -> Vec<(String, f64)> {
+    let mut costs = result.metric_costs();
+    costs.push(("Total".to_string(), result.total_cost()));
+
+    costs
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let mut rankings = Vec::new();
+    for layout_str in options.layout_str.iter() {
+        let layout_str: String = layout_str
+            .chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect();
+
+        let layout = match layout_generator.generate(&layout_str) {
+            Ok(layout) => layout,
+            Err(e) => {
+                log::error!("Error in generating layout: {:?}", e);
+                continue;
+            }
+        };
+        let costs = metric_costs(&evaluator.evaluate_layout(&layout));
+
+        let layer_size = options
+            .same_layer_swaps
+            .then(|| layout.as_text().chars().count());
+        let pm = LayoutPermutator::new(&layout_str, &options.fix.clone().unwrap_or_default())
+            .with_swap_groups(layer_size, &options.swap_groups);
+
+        let random_costs: Vec<Vec<(String, f64)>> = (0..options.samples)
+            .into_par_iter()
+            .filter_map(|_| {
+                let random_str = pm.generate_string(&pm.generate_random());
+                match layout_generator.generate(&random_str) {
+                    Ok(random_layout) => {
+                        Some(metric_costs(&evaluator.evaluate_layout(&random_layout)))
+                    }
+                    Err(e) => {
+                        log::warn!("Skipping invalid random layout {}: {:?}", random_str, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        if random_costs.is_empty() {
+            log::error!(
+                "Could not generate any valid random layout for {}",
+                layout_str
+            );
+            continue;
+        }
+
+        let ranks = costs
+            .iter()
+            .enumerate()
+            .map(|(i, (metric, cost))| {
+                let baseline: Vec<f64> = random_costs.iter().map(|c| c[i].1).collect();
+                MetricRank::new(metric, *cost, &baseline)
+            })
+            .collect();
+
+        rankings.push(LayoutRanking {
+            layout: layout_str,
+            samples: random_costs.len(),
+            ranks,
+        });
+    }
+
+    if options.json {
+        println!("{}", serde_json::to_string(&rankings).unwrap());
+        return;
+    }
+
+    for ranking in rankings {
+        println!(
+            "Layout: {} (compared to {} random layouts)",
+            ranking.layout, ranking.samples
+        );
+        println!(
+            "  {:<35} {:>10} {:>10} {:>10} {:>11} {:>8}",
+            "Metric", "Cost", "Mean", "Std", "Percentile", "z-score"
+        );
+        for rank in ranking.ranks {
+            println!(
+                "  {:<35} {:>10.2} {:>10.2} {:>10.2} {:>10.1}% {:>8.2}",
+                rank.metric,
+                rank.cost,
+                rank.baseline_mean,
+                rank.baseline_std,
+                rank.percentile,
+                rank.z_score
+            );
+        }
+        println!();
+    }
+}
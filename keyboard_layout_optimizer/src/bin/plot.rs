@@ -1,6 +1,6 @@
 use clap::Parser;
 
-use keyboard_layout_optimizer::common;
+use keyboard_layout_optimizer::{common, logging};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Keyboard layout plotting")]
@@ -19,11 +19,15 @@ struct Options {
     /// Interpred given layout string using the "grouped" logic
     #[clap(long)]
     pub grouped_layout_generator: bool,
+
+    /// Plot the layers with the keyboard's text template instead of colored key clusters
+    #[clap(long)]
+    flat: bool,
 }
 
 fn main() {
     dotenv::dotenv().ok();
-    env_logger::init();
+    logging::init();
     let options = Options::parse();
 
     let layout_str: String = options
@@ -31,8 +35,12 @@ fn main() {
         .chars()
         .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
         .collect();
-    let layout_generator =
-        common::init_layout_generator(&options.layout_config, options.grouped_layout_generator);
+    let layout_generator = common::init_layout_generator(
+        &options.layout_config,
+        options.grouped_layout_generator,
+        &[],
+    )
+    .unwrap_or_else(|e| panic!("Could not load the layout config: {:?}", e));
 
     let layout = match layout_generator.generate(&layout_str) {
         Ok(layout) => layout,
@@ -43,12 +51,11 @@ fn main() {
     };
     let max_layer = layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0);
     for layer in 0..max_layer + 1 {
-        println!(
-            "Layout '{}' (layer {}):\n{}",
-            layout_str,
-            layer + 1,
-            layout.plot_layer(layer as usize)
-        );
+        let plot = match options.flat {
+            true => layout.plot_layer(layer as usize),
+            false => layout.plot_layer_clusters(layer as usize, None),
+        };
+        println!("Layout '{}' (layer {}):\n{}\n", layout_str, layer + 1, plot);
     }
     println!("Layout compact: \n{}", layout.plot_compact());
     println!("Layout as text: \n{}", layout);
@@ -0,0 +1,83 @@
+use keyboard_layout_optimizer::{
+    common,
+    leaderboard::{self, LeaderboardEntry},
+    logging,
+};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout leaderboard publication")]
+/// Evaluate layouts and publish them together with their costs to a leaderboard server
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Publish the layouts under this name
+    #[clap(long)]
+    publish_as: String,
+
+    /// Url of the leaderboard server (see the `leaderboard_server` binary)
+    #[clap(long, default_value = "http://localhost:8080/leaderboard")]
+    publish_to: String,
+
+    /// Only print the entries that would be published as JSON
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let config_hash = common::config_hash(&options.general_parameters);
+    log::info!("Configuration hash: {}", config_hash);
+
+    for layout_str in options.layout_str.iter() {
+        let layout_str: String = layout_str
+            .chars()
+            .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+            .collect();
+
+        let layout = match layout_generator.generate(&layout_str) {
+            Ok(layout) => layout,
+            Err(e) => {
+                log::error!("Error in generating layout: {:?}", e);
+                continue;
+            }
+        };
+        let evaluation_result = evaluator.evaluate_layout(&layout);
+        let entry = LeaderboardEntry::new(
+            &layout_str,
+            &options.publish_as,
+            &config_hash,
+            &evaluation_result,
+        );
+
+        if options.dry_run {
+            println!("{}", serde_json::to_string(&entry).unwrap());
+            continue;
+        }
+
+        match leaderboard::publish(&entry, &options.publish_to) {
+            Ok(()) => log::info!(
+                "Published layout '{}' (cost: {:.2}) to {}",
+                layout_str,
+                entry.total_cost,
+                options.publish_to
+            ),
+            Err(e) => log::error!("Could not publish layout '{}': {:?}", layout_str, e),
+        }
+    }
+}
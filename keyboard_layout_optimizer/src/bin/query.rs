@@ -0,0 +1,50 @@
+use keyboard_layout_optimizer::{
+    logging,
+    results_db::{MetricConstraint, ResultsDb},
+};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Results database query")]
+/// Query the best layouts recorded in a results database (see the `--results-db` option)
+struct Options {
+    /// Filename of the results database
+    db: String,
+
+    /// Constraints on (weighted) metric costs, e.g. "Scissoring<0.5"
+    #[clap(long = "where")]
+    constraints: Vec<MetricConstraint>,
+
+    /// Only consider results recorded with this configuration hash
+    #[clap(long)]
+    config_hash: Option<String>,
+
+    /// Maximum number of layouts to show
+    #[clap(long, default_value = "10")]
+    limit: usize,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let results_db = ResultsDb::open(&options.db)
+        .unwrap_or_else(|e| panic!("Could not open results database {}: {:?}", options.db, e));
+
+    let results = results_db
+        .query(
+            &options.constraints,
+            options.config_hash.as_deref(),
+            options.limit,
+        )
+        .unwrap_or_else(|e| panic!("Could not query results database: {:?}", e));
+
+    for result in results {
+        println!(
+            "{:>8.2} {} (config: {}, recorded: {})",
+            result.total_cost, result.layout, result.config_hash, result.timestamp
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use keyboard_layout_optimizer::{
+    common, logging,
+    radar_chart::{self, MetricGroups, RadarSeries},
+};
+
+use clap::Parser;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout radar chart")]
+/// Draw two or more layouts as radar charts over groups of metrics (e.g. SFB, scissoring, rolls,
+/// balance, effort). The cost of each group is normalized by the largest cost of that group among
+/// the layouts, so a smaller area is better.
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Names of the layouts in the legend (in the order of the layouts, default: layout strings)
+    #[clap(long)]
+    names: Vec<String>,
+
+    /// Filename of the config file defining the metric groups
+    #[clap(long, default_value = "config/charts/radar_groups.yml")]
+    groups: String,
+
+    /// Filename of the SVG chart
+    #[clap(short, long, default_value = "radar_chart.svg")]
+    output: String,
+
+    /// Title of the chart
+    #[clap(long, default_value = "Layout comparison")]
+    title: String,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to also output the (normalized) group costs as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let groups = MetricGroups::from_yaml(&options.groups)
+        .unwrap_or_else(|e| panic!("Could not read metric groups {}: {:?}", options.groups, e));
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+
+    let mut series: Vec<RadarSeries> = options
+        .layout_str
+        .iter()
+        .map(|s| s.chars().filter(|c| !c.is_whitespace()).collect::<String>())
+        .enumerate()
+        .filter_map(
+            |(i, layout_str)| match layout_generator.generate(&layout_str) {
+                Ok(layout) => Some(RadarSeries {
+                    name: options.names.get(i).cloned().unwrap_or(layout_str),
+                    costs: groups.costs(&evaluator.evaluate_layout(&layout)),
+                    normalized: Vec::new(),
+                }),
+                Err(e) => {
+                    log::error!("Error in generating layout {}: {:?}", layout_str, e);
+                    None
+                }
+            },
+        )
+        .collect();
+    if series.len() < 2 {
+        log::warn!("A radar chart is most useful for comparing two or more layouts");
+    }
+    radar_chart::normalize(&mut series);
+
+    let group_names: Vec<String> = groups.groups.iter().map(|g| g.name.clone()).collect();
+    radar_chart::draw(&options.output, &options.title, &group_names, &series)
+        .unwrap_or_else(|e| panic!("Could not draw chart to {}: {:?}", options.output, e));
+
+    if options.json {
+        println!("{}", serde_json::to_string(&series).unwrap());
+    } else {
+        log::info!("Saved radar chart to '{}'", options.output);
+    }
+}
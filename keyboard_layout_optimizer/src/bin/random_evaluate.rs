@@ -1,7 +1,7 @@
 use clap::Parser;
 use rand::{self, seq::SliceRandom};
 
-use keyboard_layout_optimizer::common;
+use keyboard_layout_optimizer::{common, logging};
 
 #[derive(Parser, Debug)]
 #[clap(name = "Random keyboard layout evaluation")]
@@ -16,10 +16,11 @@ struct Options {
 }
 fn main() {
     dotenv::dotenv().ok();
-    env_logger::init();
+    logging::init();
     let options = Options::parse();
 
-    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters);
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
 
     let layout_str = "abcdefghijklmnopqrstuvwxyzäöüß,.";
     let mut best_cost: Option<f64> = None;
@@ -0,0 +1,155 @@
+use keyboard_layout_optimizer::{common, logging};
+use layout_evaluation::{cache::Cache, results::EvaluationResult};
+
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::io::{self, BufRead};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout ranking")]
+/// Read layouts from stdin (one per line), evaluate them, and print them sorted by total cost,
+/// e.g. for a quick triage of submitted layouts. Empty lines and lines starting with '#' are
+/// skipped.
+struct Options {
+    /// Add a column with the weighted cost of each of these metrics (names as shown in the
+    /// evaluation), separated by commas
+    #[clap(long, value_delimiter = ',')]
+    metrics: Vec<String>,
+
+    /// Print only the best N layouts
+    #[clap(long)]
+    top: Option<usize>,
+
+    /// Print the ranking as JSON
+    #[clap(long)]
+    json: bool,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+}
+
+#[derive(Serialize, Debug)]
+struct MetricColumn {
+    name: String,
+    /// `None` if the metric is not enabled
+    weighted_cost: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+struct RankedLayout {
+    rank: usize,
+    layout: String,
+    total_cost: f64,
+    metrics: Vec<MetricColumn>,
+}
+
+fn print_table(ranking: &[RankedLayout], metrics: &[String]) {
+    let widths: Vec<usize> = metrics.iter().map(|m| m.chars().count().max(9)).collect();
+
+    let mut header = format!("{:>4}  {:>10}", "Rank", "Total");
+    for (metric, width) in metrics.iter().zip(widths.iter()) {
+        header.push_str(&format!("  {:>width$}", metric, width = width));
+    }
+    header.push_str("  Layout");
+    println!("{}", header);
+
+    for ranked in ranking {
+        let mut line = format!("{:>4}  {:>10.2}", ranked.rank, ranked.total_cost);
+        for (column, width) in ranked.metrics.iter().zip(widths.iter()) {
+            match column.weighted_cost {
+                Some(cost) => line.push_str(&format!("  {:>width$.2}", cost, width = width)),
+                None => line.push_str(&format!("  {:>width$}", "-", width = width)),
+            }
+        }
+        line.push_str(&format!("  {}", ranked.layout));
+        println!("{}", line);
+    }
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let layout_strings: Vec<String> = io::stdin()
+        .lock()
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| {
+            line.chars()
+                .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+                .collect()
+        })
+        .collect();
+    log::info!("Ranking {} layouts", layout_strings.len());
+
+    // the corpus data is loaded once and shared by all evaluations
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let result_cache: Cache<EvaluationResult> =
+        match common::init_result_cache(&options.general_parameters) {
+            Some(store) => Cache::new().with_store(store),
+            None => Cache::new(),
+        };
+
+    let mut results: Vec<(String, EvaluationResult)> = layout_strings
+        .into_par_iter()
+        .filter_map(|layout_str| match layout_generator.generate(&layout_str) {
+            Ok(layout) => {
+                let result = result_cache
+                    .get_or_insert_with(&layout_str, || evaluator.evaluate_layout(&layout));
+                if result.total_cost().is_nan() {
+                    log::error!("Skipping layout '{}' without a valid cost", layout_str);
+                    return None;
+                }
+                Some((layout_str, result))
+            }
+            Err(e) => {
+                log::error!("Skipping invalid layout '{}': {:#}", layout_str, e);
+                None
+            }
+        })
+        .collect();
+    results.sort_by(|(_, r1), (_, r2)| r1.total_cost().total_cmp(&r2.total_cost()));
+
+    for metric in options.metrics.iter() {
+        if results.iter().all(|(_, r)| r.metric_cost(metric).is_none()) {
+            log::error!("Metric '{}' is not enabled in the evaluation", metric);
+        }
+    }
+
+    let ranking: Vec<RankedLayout> = results
+        .into_iter()
+        .take(options.top.unwrap_or(usize::MAX))
+        .enumerate()
+        .map(|(i, (layout, result))| RankedLayout {
+            rank: i + 1,
+            total_cost: result.total_cost(),
+            metrics: options
+                .metrics
+                .iter()
+                .map(|metric| MetricColumn {
+                    name: metric.clone(),
+                    weighted_cost: result.metric_cost(metric),
+                })
+                .collect(),
+            layout,
+        })
+        .collect();
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&ranking).unwrap());
+    } else {
+        print_table(&ranking, &options.metrics);
+    }
+}
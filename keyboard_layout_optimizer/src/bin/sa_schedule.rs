@@ -0,0 +1,200 @@
+use keyboard_layout_optimizer::{common, logging};
+use layout_optimization_common::LayoutPermutator;
+use layout_optimization_sa::{optimization, schedule};
+
+use clap::Parser;
+use std::{fs, sync::Arc};
+
+#[derive(Parser, Debug)]
+#[clap(name = "Simulated Annealing schedule")]
+/// Print the temperature and the expected acceptance ratio of uphill moves over the iterations of
+/// an annealing schedule, e.g. for checking a preset or a configuration before a long run.
+///
+/// The acceptance ratios are estimated from the uphill moves of short probe chains starting at
+/// the given layout.
+struct Options {
+    /// Layout keys from left to right, top to bottom (start of the probe chains)
+    layout_str: String,
+
+    /// Do not optimize those keys
+    #[clap(short, long)]
+    fix: Option<String>,
+
+    /// Filename of optimization configuration file
+    #[clap(short, long, default_value = "config/optimization/sa.yml")]
+    optimization_parameters: String,
+
+    /// Use this annealing schedule preset (fast, thorough, overnight) instead of the configured
+    /// schedule (Will be overwritten by --init-temp)
+    #[clap(long)]
+    preset: Option<String>,
+
+    /// Evaluation budget of the schedule preset
+    #[clap(long)]
+    budget: Option<u64>,
+
+    /// Set the initial temperature
+    #[clap(long)]
+    init_temp: Option<f64>,
+
+    /// Set the cooling rate
+    #[clap(long)]
+    cooling_rate: Option<f64>,
+
+    /// Number of iterations to print (evenly spaced)
+    #[clap(long, default_value = "21")]
+    points: usize,
+
+    /// Write the curve to this file as CSV
+    #[clap(long)]
+    csv: Option<String>,
+
+    /// Do not remove whitespace from layout strings
+    #[clap(long)]
+    do_not_remove_whitespace: bool,
+
+    /// Evaluation parameters
+    #[clap(flatten)]
+    evaluation_parameters: common::Options,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    logging::init();
+
+    let layout_str: String = options
+        .layout_str
+        .chars()
+        .filter(|c| options.do_not_remove_whitespace || !c.is_whitespace())
+        .collect();
+
+    let mut params = optimization::Parameters::from_yaml(&options.optimization_parameters)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Could not read optimization parameters from {}: {:?}",
+                &options.optimization_parameters, e
+            )
+        });
+    if let Some(preset) = &options.preset {
+        params.preset = Some(preset.parse().unwrap_or_else(|e| panic!("{}", e)));
+    }
+    if options.budget.is_some() {
+        params.budget = options.budget;
+    }
+    params.apply_preset();
+    if options.init_temp.is_some() {
+        params.init_temp = options.init_temp;
+        params.auto_tune = None;
+    }
+    if let Some(cooling_rate) = options.cooling_rate {
+        params.cooling_rate = cooling_rate;
+    }
+    params.correct_init_temp();
+
+    let (layout_generator, evaluator) = common::init(&options.evaluation_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    let layer_size = params.swap_restrictions.same_layer_swaps.then(|| {
+        let layout = layout_generator
+            .generate(&layout_str)
+            .unwrap_or_else(|e| panic!("Invalid layout {}: {:?}", layout_str, e));
+        layout.as_text().chars().count()
+    });
+    let pm = LayoutPermutator::new(&layout_str, &options.fix.clone().unwrap_or_default())
+        .with_swap_groups(layer_size, &params.swap_restrictions.swap_groups);
+    let initial_indices = pm.get_permutable_indices();
+
+    let probe_params = params.auto_tune.clone().unwrap_or_default();
+    let deltas = optimization::probe_uphill_deltas(
+        &probe_params,
+        &initial_indices,
+        &evaluator,
+        &pm,
+        layout_generator.as_ref(),
+        params.key_switches,
+    );
+
+    let tuned_schedule = params.auto_tune.as_ref().and_then(|auto_tune_params| {
+        optimization::tune_schedule(auto_tune_params, &deltas, params.max_iters)
+    });
+    let (init_temp, cooling_rate) = match tuned_schedule {
+        Some(schedule) => schedule,
+        None => {
+            let init_temp = params.init_temp.unwrap_or_else(|| {
+                optimization::get_cost_sd(
+                    &initial_indices,
+                    Arc::new(evaluator.clone()),
+                    &pm,
+                    &layout_generator,
+                    params.key_switches,
+                )
+            });
+            (init_temp, params.cooling_rate)
+        }
+    };
+
+    let mut sorted_deltas = deltas.clone();
+    sorted_deltas.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    println!(
+        "Schedule: {}",
+        match (params.preset, params.auto_tune.is_some()) {
+            (Some(preset), true) => format!("preset '{}'", preset),
+            (_, true) => "auto-tuned".to_string(),
+            (_, false) => "configured".to_string(),
+        }
+    );
+    println!("  iterations:           {}", params.max_iters);
+    println!("  stall_accepted:       {}", params.stall_accepted);
+    println!("  initial temperature:  {:.4}", init_temp);
+    println!("  cooling rate:         {:.6}", cooling_rate);
+    println!(
+        "  final temperature:    {:.6}",
+        init_temp * cooling_rate.powf(params.max_iters as f64)
+    );
+    if sorted_deltas.is_empty() {
+        println!("  uphill moves:         none found in the probe chains");
+    } else {
+        println!(
+            "  uphill moves:         {} in the probe chains, median cost difference {:.4}",
+            sorted_deltas.len(),
+            sorted_deltas[sorted_deltas.len() / 2]
+        );
+    }
+    println!();
+
+    let curve = schedule::schedule_curve(
+        init_temp,
+        cooling_rate,
+        params.max_iters,
+        &deltas,
+        options.points,
+    );
+
+    // uphill acceptance ratios are always below 0.5
+    const BAR_WIDTH: f64 = 40.0;
+    println!(
+        "{:>10}  {:>12}  {:>10}  expected acceptance of uphill moves",
+        "iteration", "temperature", "acceptance"
+    );
+    for point in curve.iter() {
+        println!(
+            "{:>10}  {:>12.4}  {:>9.3}%  {}",
+            point.iteration,
+            point.temperature,
+            100.0 * point.acceptance,
+            "█".repeat((point.acceptance / 0.5 * BAR_WIDTH).round() as usize),
+        );
+    }
+
+    if let Some(filename) = &options.csv {
+        let mut lines = vec!["iteration,temperature,acceptance".to_string()];
+        lines.extend(curve.iter().map(|point| {
+            format!(
+                "{},{},{}",
+                point.iteration, point.temperature, point.acceptance
+            )
+        }));
+        fs::write(filename, format!("{}\n", lines.join("\n")))
+            .unwrap_or_else(|e| panic!("Could not write {}: {}", filename, e));
+    }
+}
@@ -0,0 +1,273 @@
+use keyboard_layout_optimizer::{common, logging};
+
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Metric weight sensitivity analysis")]
+/// Perturb the weight of each metric and report how the ranking of the given layouts changes.
+///
+/// Metrics whose perturbation reorders the layouts (in particular the best one) are decisive for
+/// the comparison, metrics that do not change the ranking are noise with respect to these layouts.
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Read layouts from file and append to command line layouts
+    #[clap(long)]
+    from_file: Option<String>,
+
+    /// Relative perturbation of each weight (0.2 means ±20%)
+    #[clap(long, default_value = "0.2")]
+    perturbation: f64,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+/// Effect of perturbing the weight of a metric in one direction
+#[derive(Serialize, Debug)]
+struct PerturbationEffect {
+    /// Relative change of the weight
+    change: f64,
+    /// Number of pairs of layouts whose order changes
+    swapped_pairs: usize,
+    /// Kendall's tau between the original and the perturbed ranking
+    kendall_tau: f64,
+    /// Largest change of rank of any layout
+    max_rank_shift: usize,
+    /// The best layout under the perturbed weight (if it changed)
+    new_best: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct MetricSensitivity {
+    metric: String,
+    /// Mean weighted cost of the metric relative to the mean total cost
+    cost_share: f64,
+    /// Difference between the largest and smallest weighted cost over the layouts
+    cost_range: f64,
+    decrease: PerturbationEffect,
+    increase: PerturbationEffect,
+    /// Smallest relative weight change (positive or negative) that changes the best layout
+    critical_change: Option<f64>,
+}
+
+/// Rank of each layout (0 = lowest cost)
+fn ranks(totals: &[f64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..totals.len()).collect();
+    order.sort_by(|a, b| totals[*a].partial_cmp(&totals[*b]).unwrap());
+    let mut ranks = vec![0; totals.len()];
+    for (rank, i) in order.into_iter().enumerate() {
+        ranks[i] = rank;
+    }
+
+    ranks
+}
+
+fn perturbation_effect(
+    layouts: &[String],
+    totals: &[f64],
+    costs: &[f64],
+    change: f64,
+) -> PerturbationEffect {
+    let n = totals.len();
+    let perturbed: Vec<f64> = totals
+        .iter()
+        .zip(costs)
+        .map(|(total, cost)| total + change * cost)
+        .collect();
+
+    let original_ranks = ranks(totals);
+    let perturbed_ranks = ranks(&perturbed);
+
+    let mut swapped_pairs = 0;
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let before = original_ranks[i] < original_ranks[j];
+            let after = perturbed_ranks[i] < perturbed_ranks[j];
+            if before != after {
+                swapped_pairs += 1;
+            }
+        }
+    }
+    let n_pairs = n * n.saturating_sub(1) / 2;
+    let kendall_tau = if n_pairs > 0 {
+        1.0 - 2.0 * swapped_pairs as f64 / n_pairs as f64
+    } else {
+        1.0
+    };
+
+    let max_rank_shift = original_ranks
+        .iter()
+        .zip(perturbed_ranks.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .max()
+        .unwrap_or_default();
+
+    let best = |ranks: &[usize]| ranks.iter().position(|r| *r == 0);
+    let new_best = match (best(&original_ranks), best(&perturbed_ranks)) {
+        (Some(a), Some(b)) if a != b => Some(layouts[b].clone()),
+        _ => None,
+    };
+
+    PerturbationEffect {
+        change,
+        swapped_pairs,
+        kendall_tau,
+        max_rank_shift,
+        new_best,
+    }
+}
+
+/// The smallest relative weight change of a metric that lets another layout overtake the best one.
+fn critical_change(totals: &[f64], costs: &[f64]) -> Option<f64> {
+    let best = ranks(totals).iter().position(|r| *r == 0)?;
+
+    // layout j overtakes the best layout b when
+    // totals[b] + change * costs[b] = totals[j] + change * costs[j]
+    (0..totals.len())
+        .filter(|j| *j != best && costs[best] != costs[*j])
+        .map(|j| (totals[j] - totals[best]) / (costs[best] - costs[j]))
+        .filter(|change| *change > -1.0 && change.is_finite())
+        .min_by(|a, b| a.abs().partial_cmp(&b.abs()).unwrap())
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let layout_strings =
+        common::read_layout_strings(&options.layout_str, options.from_file.as_deref(), false);
+
+    let (layout_generator, evaluator) = common::init(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not initialize the evaluation: {:?}", e));
+    if !options.general_parameters.additional_corpora.is_empty() {
+        log::warn!("Only the costs on the main corpus are considered");
+    }
+
+    let evaluations: Vec<(String, Vec<(String, f64)>)> = layout_strings
+        .par_iter()
+        .filter_map(|layout_str| match layout_generator.generate(layout_str) {
+            Ok(layout) => Some((
+                layout_str.clone(),
+                evaluator.evaluate_layout(&layout).metric_costs(),
+            )),
+            Err(e) => {
+                log::error!("Error in generating layout {}: {:?}", layout_str, e);
+                None
+            }
+        })
+        .collect();
+    if evaluations.len() < 2 {
+        panic!("At least two valid layouts are required for a sensitivity analysis");
+    }
+
+    let layouts: Vec<String> = evaluations.iter().map(|(l, _)| l.clone()).collect();
+    let totals: Vec<f64> = evaluations
+        .iter()
+        .map(|(_, costs)| costs.iter().map(|(_, c)| c).sum())
+        .collect();
+    let mean_total = totals.iter().sum::<f64>() / totals.len() as f64;
+    let metric_names: Vec<String> = evaluations[0].1.iter().map(|(m, _)| m.clone()).collect();
+
+    let mut sensitivities: Vec<MetricSensitivity> = metric_names
+        .iter()
+        .enumerate()
+        .map(|(m, metric)| {
+            let costs: Vec<f64> = evaluations.iter().map(|(_, c)| c[m].1).collect();
+            let min = costs.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = costs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+            MetricSensitivity {
+                metric: metric.clone(),
+                cost_share: costs.iter().sum::<f64>() / costs.len() as f64 / mean_total,
+                cost_range: max - min,
+                decrease: perturbation_effect(&layouts, &totals, &costs, -options.perturbation),
+                increase: perturbation_effect(&layouts, &totals, &costs, options.perturbation),
+                critical_change: critical_change(&totals, &costs),
+            }
+        })
+        .collect();
+
+    // most decisive metrics first
+    sensitivities.sort_by(|a, b| {
+        let critical = |s: &MetricSensitivity| s.critical_change.map_or(f64::INFINITY, f64::abs);
+        critical(a)
+            .partial_cmp(&critical(b))
+            .unwrap()
+            .then_with(|| {
+                (b.decrease.swapped_pairs + b.increase.swapped_pairs)
+                    .cmp(&(a.decrease.swapped_pairs + a.increase.swapped_pairs))
+            })
+    });
+
+    if options.json {
+        println!("{}", serde_json::to_string(&sensitivities).unwrap());
+        return;
+    }
+
+    let original_ranks = ranks(&totals);
+    println!("Ranking ({} layouts):", layouts.len());
+    let mut order: Vec<usize> = (0..layouts.len()).collect();
+    order.sort_by_key(|i| original_ranks[*i]);
+    for i in order {
+        println!(
+            "  {:>3}. {:>10.2}  {}",
+            original_ranks[i] + 1,
+            totals[i],
+            layouts[i]
+        );
+    }
+    println!();
+
+    println!(
+        "{:<36} {:>7} {:>10} {:>22} {:>22} {:>10}",
+        "Metric",
+        "Share",
+        "Range",
+        format!("-{:.0}%: swaps/tau", 100.0 * options.perturbation),
+        format!("+{:.0}%: swaps/tau", 100.0 * options.perturbation),
+        "Critical"
+    );
+    for s in sensitivities.iter() {
+        let effect = |e: &PerturbationEffect| {
+            format!(
+                "{}{:>5} / {:>5.2}",
+                if e.new_best.is_some() { "best! " } else { "" },
+                e.swapped_pairs,
+                e.kendall_tau
+            )
+        };
+        let verdict = match s.critical_change {
+            Some(c) if c.abs() <= options.perturbation => "decisive",
+            _ if s.decrease.swapped_pairs + s.increase.swapped_pairs > 0 => "reorders",
+            _ => "noise",
+        };
+        println!(
+            "{:<36} {:>6.1}% {:>10.2} {:>22} {:>22} {:>10} {}",
+            s.metric,
+            100.0 * s.cost_share,
+            s.cost_range,
+            effect(&s.decrease),
+            effect(&s.increase),
+            s.critical_change
+                .map_or("-".to_string(), |c| format!("{:+.1}%", 100.0 * c)),
+            verdict,
+        );
+    }
+    println!();
+    println!(
+        "\"best!\": the best layout changes; \"Critical\": smallest weight change that changes the best layout"
+    );
+}
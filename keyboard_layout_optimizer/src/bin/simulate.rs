@@ -0,0 +1,151 @@
+use keyboard_layout_optimizer::{
+    common, logging,
+    simulation::{self, SimulationParameters, SimulationResult},
+};
+
+use clap::Parser;
+use rayon::prelude::*;
+use serde::Serialize;
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout typing simulation")]
+/// Type passages sampled from the corpus on each layout with random keystroke times and report
+/// the distribution of the completion times.
+///
+/// All layouts type the same passages with the same seed, so differences between layouts are not
+/// due to different samples.
+struct Options {
+    /// List of Layout keys from left to right, top to bottom
+    layout_str: Vec<String>,
+
+    /// Read layouts from file and append to command line layouts
+    #[clap(long)]
+    from_file: Option<String>,
+
+    /// Filename of the simulation configuration file
+    #[clap(short, long, default_value = "config/typing/simulation.yml")]
+    simulation_parameters: String,
+
+    /// Number of sampled passages (overrides the configuration)
+    #[clap(long)]
+    passages: Option<usize>,
+
+    /// Seed for sampling (overrides the configuration)
+    #[clap(long)]
+    seed: Option<u64>,
+
+    /// General parameters
+    #[clap(flatten)]
+    general_parameters: common::Options,
+
+    /// If to only output the results as JSON to stdout
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct LayoutSimulation {
+    layout: String,
+    #[serde(flatten)]
+    result: SimulationResult,
+}
+
+fn main() {
+    dotenv::dotenv().ok();
+    let options = Options::parse();
+    if !options.json {
+        // if the "json" option is set, we do not want any other log messages
+        logging::init();
+    }
+
+    let mut params = SimulationParameters::from_yaml(&options.simulation_parameters)
+        .unwrap_or_else(|e| {
+            panic!(
+                "Could not read simulation parameters from {}: {:?}",
+                &options.simulation_parameters, e
+            )
+        });
+    if let Some(passages) = options.passages {
+        params.passages = passages;
+    }
+    if let Some(seed) = options.seed {
+        params.seed = seed;
+    }
+
+    let passages = match common::optimization_text(&options.general_parameters)
+        .unwrap_or_else(|e| panic!("Could not read the corpus: {:?}", e))
+    {
+        Some(text) => simulation::sample_text_passages(&text, &params),
+        None => {
+            let (_, bigrams, trigrams, _) = common::init_ngrams(&options.general_parameters)
+                .unwrap_or_else(|e| panic!("Could not read the ngrams: {:?}", e));
+            simulation::sample_ngram_passages(&bigrams, &trigrams, &params)
+        }
+    };
+    if passages.is_empty() {
+        panic!("Could not sample any passages from the corpus");
+    }
+    log::info!(
+        "Sampled {} passages, e.g. '{}'",
+        passages.len(),
+        passages[0].iter().collect::<String>().escape_debug()
+    );
+
+    let layout_generator = common::init_layout_generator(
+        &options.general_parameters.layout_config,
+        options.general_parameters.grouped_layout_generator,
+        &options.general_parameters.layers,
+    )
+    .unwrap_or_else(|e| panic!("Could not load the layout config: {:?}", e));
+
+    let layout_strings =
+        common::read_layout_strings(&options.layout_str, options.from_file.as_deref(), false);
+    let simulations: Vec<LayoutSimulation> = layout_strings
+        .par_iter()
+        .filter_map(|layout_str| match layout_generator.generate(layout_str) {
+            Ok(layout) => Some(LayoutSimulation {
+                layout: layout_str.clone(),
+                result: simulation::simulate(&layout, &passages, &params),
+            }),
+            Err(e) => {
+                log::error!("Error in generating layout {}: {:?}", layout_str, e);
+                None
+            }
+        })
+        .collect();
+
+    if options.json {
+        println!("{}", serde_json::to_string(&simulations).unwrap());
+        return;
+    }
+
+    println!(
+        "{} passages of {} characters\n",
+        passages.len(),
+        params.passage_length
+    );
+    println!(
+        "{:>9} {:>9} {:>9} {:>9} {:>9} {:>9}  layout",
+        "mean [s]", "sd [s]", "p50 [s]", "p95 [s]", "WPM", "p95 WPM"
+    );
+    for s in simulations.iter() {
+        let r = &s.result;
+        println!(
+            "{:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.1} {:>9.1}  {}",
+            r.mean_ms / 1000.0,
+            r.sd_ms / 1000.0,
+            r.p50_ms / 1000.0,
+            r.p95_ms / 1000.0,
+            r.mean_wpm,
+            r.p95_wpm,
+            s.layout
+        );
+        if r.skipped_chars > 0 {
+            log::warn!(
+                "{}: skipped {} characters that can not be typed",
+                s.layout,
+                r.skipped_chars
+            );
+        }
+    }
+}
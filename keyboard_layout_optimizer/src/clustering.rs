@@ -0,0 +1,61 @@
+//! The `clustering` module groups similar layouts (e.g. the results of several optimization runs)
+//! into families by agglomerative (average linkage) clustering of their pairwise distances.
+
+/// Cluster items given their pairwise distances. Clusters are merged (closest average distance
+/// first) until `n_clusters` clusters remain or the closest clusters are further apart than
+/// `max_distance`. Returns the indices of the members of each cluster.
+pub fn cluster(
+    distances: &[Vec<f64>],
+    n_clusters: Option<usize>,
+    max_distance: Option<f64>,
+) -> Vec<Vec<usize>> {
+    let mut clusters: Vec<Vec<usize>> = (0..distances.len()).map(|i| vec![i]).collect();
+    let average_distance = |a: &[usize], b: &[usize]| -> f64 {
+        let sum: f64 = a
+            .iter()
+            .flat_map(|i| b.iter().map(move |j| distances[*i][*j]))
+            .sum();
+        sum / (a.len() * b.len()) as f64
+    };
+
+    while clusters.len() > n_clusters.unwrap_or(1).max(1) {
+        let mut closest: Option<(usize, usize, f64)> = None;
+        for a in 0..clusters.len() {
+            for b in (a + 1)..clusters.len() {
+                let d = average_distance(&clusters[a], &clusters[b]);
+                if closest.map_or(true, |(_, _, min_d)| d < min_d) {
+                    closest = Some((a, b, d));
+                }
+            }
+        }
+
+        match closest {
+            Some((a, b, d)) if max_distance.map_or(true, |max_d| d <= max_d) => {
+                let merged = clusters.swap_remove(b);
+                clusters[a].extend(merged);
+            }
+            _ => break,
+        }
+    }
+
+    clusters
+}
+
+/// The member of a cluster with the smallest summed distance to all other members.
+pub fn medoid(distances: &[Vec<f64>], members: &[usize]) -> usize {
+    *members
+        .iter()
+        .min_by(|i, j| {
+            let sum = |k: usize| members.iter().map(|m| distances[k][*m]).sum::<f64>();
+            sum(**i).partial_cmp(&sum(**j)).unwrap()
+        })
+        .unwrap()
+}
+
+/// The largest distance between two members of a cluster.
+pub fn diameter(distances: &[Vec<f64>], members: &[usize]) -> f64 {
+    members
+        .iter()
+        .flat_map(|i| members.iter().map(move |j| distances[*i][*j]))
+        .fold(0.0, f64::max)
+}
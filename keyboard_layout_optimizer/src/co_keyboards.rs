@@ -0,0 +1,54 @@
+//! The `co_keyboards` module reads the configuration for optimizing a single layout for several
+//! keyboards at once (e.g. a Svalboard and a column-staggered keyboard). The layouts are
+//! generated for the main keyboard (the one of `--layout-config`) and transferred to the further
+//! keyboards by hand, finger, and direction (see [`layout_evaluation::co_keyboards`]).
+
+use anyhow::Result;
+use serde::Deserialize;
+use std::fs::File;
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// A further keyboard to evaluate the layouts on
+#[derive(Clone, Deserialize, Debug)]
+pub struct CoKeyboardConfig {
+    pub name: String,
+    /// Filename of the layout configuration file of the keyboard
+    pub layout_config: String,
+    /// Filename of the evaluation configuration file for this keyboard (defaults to the one of
+    /// the main keyboard)
+    #[serde(default)]
+    pub eval_parameters: Option<String>,
+    /// Weight of the keyboard in the combined cost
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Cost of a good layout for this keyboard alone (e.g. of a layout optimized for it)
+    #[serde(default)]
+    pub reference_cost: Option<f64>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct CoKeyboardsConfig {
+    /// Name of the main keyboard
+    pub name: String,
+    /// Weight of the main keyboard in the combined cost
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Cost of a good layout for the main keyboard alone
+    #[serde(default)]
+    pub reference_cost: Option<f64>,
+    /// Penalty per unit of cost above the reference cost of a keyboard
+    #[serde(default)]
+    pub deviation_penalty: f64,
+    pub keyboards: Vec<CoKeyboardConfig>,
+}
+
+impl CoKeyboardsConfig {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let config: CoKeyboardsConfig = serde_yaml::from_reader(f)?;
+        Ok(config)
+    }
+}
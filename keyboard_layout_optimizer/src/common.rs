@@ -1,44 +1,78 @@
+use crate::{
+    anytime::{BestSoFarFile, RunBudget},
+    co_keyboards::CoKeyboardsConfig,
+    corpus_processing,
+    corpus_profiles::CorpusProfiles,
+    live_stream::{self, LiveStream, LiveStreamObserver},
+    monitoring::{self, ProgressLogger},
+    result_cache::ResultCache,
+    results_db::{hash_str, ResultsDb},
+};
+
 use keyboard_layout::{
     config::LayoutConfig, grouped_layout_generator::GroupedLayoutGenerator, keyboard::Keyboard,
-    layout_generator::LayoutGenerator, neo_layout_generator::NeoLayoutGenerator,
+    layout::Layout, layout_generator::LayoutGenerator, neo_layout_generator::NeoLayoutGenerator,
 };
 use layout_evaluation::{
-    config::EvaluationParameters,
-    evaluation::Evaluator,
+    cache::Cache,
+    co_keyboards::{CoKeyboard, CoKeyboards},
+    compressed,
+    config::{EvaluationParameters, ResolvedConfig},
+    evaluation::{Evaluator, MetricParameters},
     ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
-    ngrams::{Bigrams, Trigrams, Unigrams},
+    ngram_mapper::NgramMapper,
+    ngrams::{
+        add_scaled_ngrams, Bigrams, CaseHandling, NgramChannelConfig, Quadgrams, Shortcuts,
+        SmoothingMethod, Trigrams, Unigrams,
+    },
+    results::CorpusObjective,
+};
+use layout_optimization_common::{
+    observer::{OptimizationObserver, SharedObserver},
+    stopping::{SharedBudget, StoppingCriteria},
+    swaps::SwapRestrictions,
 };
 
 use ahash::AHashMap;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::Parser;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
 use std::{
     fs::{self, OpenOptions},
-    io::prelude::*,
+    io::{self, prelude::*},
     path::Path,
-    sync::Arc,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Clone, Debug)]
 #[clap(name = "Keyboard layout evaluation")]
 pub struct Options {
-    /// Path to ngram files
-    #[clap(
-        short,
-        long,
-        default_value = "ngrams/eng_wiki_1m"
-    )]
+    /// Path to ngram files (which may be compressed, e.g. "1-grams.txt.gz")
+    #[clap(short, long, default_value = "ngrams/eng_wiki_1m")]
     pub ngrams: String,
 
     /// Filename of evaluation configuration file to use
     #[clap(short, long, default_value = "config/evaluation/sval.yml")]
     pub eval_parameters: String,
 
+    /// Evaluation configuration files applied on top of the evaluation configuration file in the
+    /// given order, each overriding only the values it contains (e.g. some metric weights)
+    #[clap(long)]
+    pub eval_overlays: Vec<String>,
+
+    /// Override a value of the evaluation configuration, e.g. "metrics.scissoring.weight=3.5"
+    /// (applied after the overlays, list elements are addressed by their index)
+    #[clap(long)]
+    pub set: Vec<String>,
+
     /// Filename of layout configuration file to use
     #[clap(short, long, default_value = "config/keyboard/sval.yml")]
     pub layout_config: String,
 
-    /// Filename of corpus file to use instead of ngram files
+    /// Filename of corpus file to use instead of ngram files (may be a .gz, .zst, or .zip file)
     #[clap(short, long)]
     pub corpus: Option<String>,
 
@@ -46,6 +80,35 @@ pub struct Options {
     #[clap(short, long)]
     pub text: Option<String>,
 
+    /// Filename of shortcut frequency file (lines like "1200 ⎈+c") to evaluate alongside the bigrams
+    #[clap(long)]
+    pub shortcuts: Option<String>,
+
+    /// Additional ngram directories or corpus files to evaluate layouts on
+    #[clap(long)]
+    pub additional_corpora: Vec<String>,
+
+    /// How to combine the total costs of several corpora: "mean", "max", or "cvar:<fraction>" (mean of the worst fraction)
+    #[clap(long, default_value = "mean")]
+    pub corpus_objective: CorpusObjective,
+
+    /// Filename of a co-keyboards configuration file: evaluate layouts on further keyboards as
+    /// well and optimize the combined cost (e.g. "config/evaluation/co_keyboards.yml")
+    #[clap(long)]
+    pub co_keyboards: Option<String>,
+
+    /// Ngram directories or corpus files held out from optimization to detect overfitting
+    #[clap(long)]
+    pub holdout: Vec<String>,
+
+    /// Hold out this fraction of the corpus file's lines from optimization to detect overfitting
+    #[clap(long, requires = "corpus", parse(try_from_str = parse_fraction))]
+    pub holdout_fraction: Option<f64>,
+
+    /// Warn if the cost on the holdout set exceeds the cost on the optimization set by more than this fraction
+    #[clap(long, default_value = "0.05")]
+    pub holdout_warn_gap: f64,
+
     /// Only consider the top ngrams up to the given fraction
     #[clap(long)]
     pub tops: Option<f64>,
@@ -62,9 +125,45 @@ pub struct Options {
     #[clap(long)]
     pub no_increase_common_ngrams: bool,
 
+    /// How to handle capitals: "keep", "fold" (to lowercase), or "shift:<fraction>" (keep only this
+    /// fraction typed with Shift, e.g. to model autocapitalization). Overrides the evaluation config
+    #[clap(long)]
+    pub case_handling: Option<CaseHandling>,
+
     /// Interpred given layout string using the "grouped" logic
     #[clap(long)]
     pub grouped_layout_generator: bool,
+
+    /// Cache the costs of individual bigrams across evaluated layouts (speeds up optimizers that
+    /// change only a few keys per step, not used for reporting the worst bigrams)
+    #[clap(long)]
+    pub bigram_cost_cache: bool,
+
+    /// Evaluate the bigram metrics that only depend on key positions in matrix form (faster full
+    /// evaluations, not used for reporting the worst bigrams)
+    #[clap(long)]
+    pub cost_matrix: bool,
+
+    /// Number of cost matrices (for different placements of the modifiers) kept per thread with
+    /// `--cost-matrix`. Each takes 8 bytes times the squared number of layerkeys times the number
+    /// of position based metrics.
+    #[clap(long, default_value = "8")]
+    pub cost_matrices: usize,
+
+    /// Record evaluated layouts in this SQLite database (see the `query` binary)
+    #[clap(long)]
+    pub results_db: Option<String>,
+
+    /// Reuse the evaluation results of previous runs with the same configuration stored in this
+    /// SQLite file (and add new results to it)
+    #[clap(long)]
+    pub result_cache: Option<String>,
+
+    /// Only permutate these layers, numbered from 0 (the base layer), e.g. "2,3". All other
+    /// layers (including the base layer unless 0 is given) stay in place. Layout strings then
+    /// refer to the selected layers by their base layer symbols
+    #[clap(long, value_delimiter = ',')]
+    pub layers: Vec<u8>,
 }
 
 #[derive(Parser, Debug)]
@@ -88,20 +187,330 @@ pub struct PublishingOptions {
     pub publish_to: String,
 }
 
-pub fn init(options: &Options) -> (Box<dyn LayoutGenerator>, Evaluator) {
-    (
-        init_layout_generator(&options.layout_config, options.grouped_layout_generator),
-        init_evaluator(options),
-    )
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout warm start")]
+pub struct WarmStartOptions {
+    /// Start optimization from the best layouts of a previous run. Accepts the output of
+    /// `evaluate --json` or a file written with `--append-solutions-to`
+    #[clap(long)]
+    pub warm_start: Option<String>,
+
+    /// Maximum number of layouts to take from the --warm-start file
+    #[clap(long, default_value = "10")]
+    pub warm_start_count: usize,
 }
 
-pub fn init_layout_generator(
+impl WarmStartOptions {
+    /// Read the best layouts from the warm start file (if any). Fails if the file can not be
+    /// read.
+    pub fn layouts(&self) -> Result<Vec<String>> {
+        match &self.warm_start {
+            Some(filename) => read_warm_start_layouts(filename, self.warm_start_count),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization monitoring")]
+pub struct MonitoringOptions {
+    /// Serve optimization statistics (generation, best cost, acceptance rate, evaluations per
+    /// second) for Prometheus at http://<address>/metrics, e.g. "127.0.0.1:9184"
+    #[clap(long)]
+    pub metrics_bind: Option<String>,
+}
+
+impl MonitoringOptions {
+    /// Start serving the optimization statistics (if requested).
+    pub fn serve_metrics(&self) {
+        if let Some(bind) = &self.metrics_bind {
+            monitoring::serve(bind);
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization stopping")]
+pub struct StoppingOptions {
+    /// Stop if the best cost did not improve for this many generations (genetic) or iterations
+    /// (simulated annealing). Overrides the optimization config
+    #[clap(long)]
+    pub max_stall: Option<u64>,
+
+    /// Stop as soon as a layout with at most this cost was found. Overrides the optimization config
+    #[clap(long)]
+    pub target_cost: Option<f64>,
+
+    /// Stop each optimization after this many seconds. Overrides the optimization config
+    #[clap(long)]
+    pub time_limit: Option<f64>,
+
+    /// Stop each optimization after this many layout evaluations. Overrides the optimization config
+    #[clap(long)]
+    pub max_evaluations: Option<u64>,
+
+    /// Budget of the whole run, either a duration (e.g. "90m", "2h", "1h30m") or a number of
+    /// layout evaluations. The schedules are fitted to a duration, and all optimizations stop
+    /// once the budget is used up
+    #[clap(long)]
+    pub budget: Option<RunBudget>,
+}
+
+impl StoppingOptions {
+    /// Override the criteria from the optimization config with the given options.
+    pub fn apply(&self, criteria: &mut StoppingCriteria) {
+        criteria.max_stall = self.max_stall.or(criteria.max_stall);
+        criteria.target_cost = self.target_cost.or(criteria.target_cost);
+        criteria.time_limit = self.time_limit.or(criteria.time_limit);
+        criteria.max_evaluations = self.max_evaluations.or(criteria.max_evaluations);
+    }
+
+    /// The budget of the whole run (shared by all of its optimizations).
+    pub fn shared_budget(&self) -> SharedBudget {
+        RunBudget::shared(self.budget)
+    }
+
+    /// Seconds of the budget (if it is a duration).
+    pub fn budget_seconds(&self) -> Option<f64> {
+        match self.budget {
+            Some(RunBudget::Time(seconds)) => Some(seconds),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization swap restrictions")]
+pub struct SwapRestrictionOptions {
+    /// Only exchange keys within the same layer (segment of the layout string). Overrides the
+    /// optimization config
+    #[clap(long)]
+    pub same_layer_swaps: bool,
+
+    /// Only exchange symbols within the same of the given groups (e.g. "aeiou"). Overrides the
+    /// optimization config
+    #[clap(long)]
+    pub swap_groups: Vec<String>,
+}
+
+impl SwapRestrictionOptions {
+    /// Override the swap restrictions from the optimization config with the given options.
+    pub fn apply(&self, restrictions: &mut SwapRestrictions) {
+        if self.same_layer_swaps {
+            restrictions.same_layer_swaps = true;
+        }
+        if !self.swap_groups.is_empty() {
+            restrictions.swap_groups = self.swap_groups.clone();
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "Keyboard layout optimization observers")]
+pub struct ObserverOptions {
+    /// Log the progress (with an estimate of the remaining time) every this many seconds
+    #[clap(long)]
+    pub progress_interval: Option<f64>,
+
+    /// Push every new best layout with its costs over a WebSocket at ws://<address>/ and serve a
+    /// dashboard at http://<address>/, e.g. "127.0.0.1:9185"
+    #[clap(long)]
+    pub live_stream_bind: Option<String>,
+
+    /// Keep the best layout found so far in this JSON file (replaced atomically on every
+    /// improvement, usable as warm start file)
+    #[clap(long)]
+    pub best_so_far: Option<String>,
+
+    /// Lowest cost written to the best-so-far file by any optimization run
+    #[clap(skip)]
+    best_so_far_cost: Arc<Mutex<Option<f64>>>,
+}
+
+impl ObserverOptions {
+    /// Start the live stream (if requested). It is shared by all optimization runs.
+    pub fn serve_live_stream(&self) -> Option<LiveStream> {
+        self.live_stream_bind
+            .as_ref()
+            .map(|bind| live_stream::serve(bind))
+    }
+
+    /// The observer for one optimization run (if any is requested).
+    pub fn observer(
+        &self,
+        live_stream: Option<&LiveStream>,
+        evaluator: &Evaluator,
+        layout_generator: &dyn LayoutGenerator,
+    ) -> Option<SharedObserver> {
+        let mut observers: Vec<Box<dyn OptimizationObserver + Send>> = Vec::new();
+        if let Some(secs) = self.progress_interval {
+            observers.push(Box::new(ProgressLogger::new(Duration::from_secs_f64(secs))));
+        }
+        if let Some(live_stream) = live_stream {
+            observers.push(Box::new(LiveStreamObserver::new(
+                live_stream,
+                evaluator,
+                layout_generator,
+            )));
+        }
+        if let Some(path) = &self.best_so_far {
+            observers.push(Box::new(BestSoFarFile::new(
+                path,
+                self.best_so_far_cost.clone(),
+            )));
+        }
+
+        (!observers.is_empty()).then(|| Arc::new(Mutex::new(observers)) as SharedObserver)
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    let fraction: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    if fraction > 0.0 && fraction < 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!("{} is not between 0 and 1 (exclusive)", fraction))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ResultDetails {
+    layout: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResultEntry {
+    details: ResultDetails,
+    total_cost: f64,
+}
+
+/// Collects the given layout strings and those read (one per line) from the given file. Whitespace
+/// is removed (unless `keep_whitespace`) and empty layout strings are skipped.
+pub fn read_layout_strings(
+    layout_strs: &[String],
+    from_file: Option<&str>,
+    keep_whitespace: bool,
+) -> Vec<String> {
+    let mut layout_strings = layout_strs.to_vec();
+    if let Some(filename) = from_file {
+        let file = fs::File::open(filename)
+            .unwrap_or_else(|e| panic!("Could not read layouts file {}: {:?}", filename, e));
+        layout_strings.extend(io::BufReader::new(file).lines().map_while(|line| line.ok()));
+    }
+
+    layout_strings
+        .iter()
+        .map(|s| {
+            s.chars()
+                .filter(|c| keep_whitespace || !c.is_whitespace())
+                .collect()
+        })
+        .filter(|s: &String| !s.trim().is_empty())
+        .collect()
+}
+
+/// Reads up to `max_layouts` distinct layouts from a result file of a previous run.
+/// JSON results (as written by `evaluate --json`) are sorted by their total cost, plain
+/// files with one layout per line are taken in order. Fails if the file can not be read.
+pub fn read_warm_start_layouts(filename: &str, max_layouts: usize) -> Result<Vec<String>> {
+    let data = fs::read_to_string(filename)
+        .with_context(|| format!("Could not read warm start file {}", filename))?;
+
+    let layouts: Vec<String> = match serde_json::from_str::<Vec<ResultEntry>>(&data) {
+        Ok(results) => results
+            .into_iter()
+            .filter(|r| {
+                if r.total_cost.is_nan() {
+                    log::warn!(
+                        "Skipping warm start layout '{}': cost is NaN",
+                        r.details.layout
+                    );
+                }
+                !r.total_cost.is_nan()
+            })
+            .sorted_by(|r1, r2| r1.total_cost.partial_cmp(&r2.total_cost).unwrap())
+            .map(|r| r.details.layout)
+            .collect(),
+        Err(_) => data
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.to_string())
+            .collect(),
+    };
+
+    let layouts: Vec<String> = layouts.into_iter().unique().take(max_layouts).collect();
+    log::info!(
+        "Read {} warm start layouts from '{}'",
+        layouts.len(),
+        filename
+    );
+
+    Ok(layouts)
+}
+
+/// Generates the layout generator and the evaluator of the options. Fails if a configuration
+/// file or the ngrams can not be read.
+pub fn init(options: &Options) -> Result<(Box<dyn LayoutGenerator>, Evaluator)> {
+    Ok((
+        init_layout_generator(
+            &options.layout_config,
+            options.grouped_layout_generator,
+            &options.layers,
+        )?,
+        init_evaluator(options)?,
+    ))
+}
+
+/// Reads a layout configuration file and fixes all layers not in `layers` (if any, numbered
+/// from 0 for the base layer). Fails if a layer does not exist.
+fn read_layout_config(
     layout_config: &str,
     grouped_layout_generator: bool,
-) -> Box<dyn LayoutGenerator> {
-    let layout_config = LayoutConfig::from_yaml(layout_config)
-        .unwrap_or_else(|e| panic!("Could not load config file {}: {}", layout_config, e));
+    layers: &[u8],
+) -> Result<LayoutConfig> {
+    let mut layout_config = LayoutConfig::from_yaml(layout_config)
+        .map_err(|e| anyhow!("Could not load config file {}: {}", layout_config, e))?;
 
+    if !layers.is_empty() {
+        if grouped_layout_generator {
+            log::warn!("Selecting layers is not supported by the grouped layout generator");
+        } else {
+            let n_layers = layout_config
+                .base_layout
+                .keys
+                .iter()
+                .flatten()
+                .map(|key_layers| key_layers.len())
+                .max()
+                .unwrap_or_default();
+            if let Some(layer) = layers.iter().find(|layer| **layer as usize >= n_layers) {
+                bail!(
+                    "Layer {} does not exist: the layout config has {} layer(s), numbered from 0 \
+                     for the base layer",
+                    layer,
+                    n_layers
+                );
+            }
+            layout_config.base_layout.fixed_layers = (0..n_layers as u8)
+                .filter(|layer| !layers.contains(layer))
+                .collect();
+            log::info!(
+                "Only permutating layers {:?} (fixed layers: {:?})",
+                layers,
+                layout_config.base_layout.fixed_layers
+            );
+        }
+    }
+
+    Ok(layout_config)
+}
+
+pub fn init_layout_generator(
+    layout_config: &str,
+    grouped_layout_generator: bool,
+    layers: &[u8],
+) -> Result<Box<dyn LayoutGenerator>> {
+    let layout_config = read_layout_config(layout_config, grouped_layout_generator, layers)?;
     let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
     log::info!("A-priori estimations from key_costs:");
     log::info!(
@@ -121,96 +530,783 @@ pub fn init_layout_generator(
     log::info!("Row loads: {}", message);
 
     if grouped_layout_generator {
-        Box::new(GroupedLayoutGenerator::from_object(
+        Ok(Box::new(GroupedLayoutGenerator::from_object(
             layout_config.base_layout,
             keyboard,
-        ))
+        )))
     } else {
-        Box::new(NeoLayoutGenerator::from_object(
+        Ok(Box::new(NeoLayoutGenerator::from_object(
             layout_config.base_layout,
             keyboard,
-        ))
+        )))
     }
 }
 
-pub fn init_evaluator(options: &Options) -> Evaluator {
-    let eval_params =
-        EvaluationParameters::from_yaml(&options.eval_parameters).unwrap_or_else(|e| {
-            panic!(
-                "Could not read evaluation yaml file {}: {:?}",
-                options.eval_parameters, e
+/// Resolves the evaluation configuration from the evaluation configuration file, the files it
+/// extends, and the overlays.
+pub fn resolve_eval_params(options: &Options) -> Result<ResolvedConfig> {
+    let mut resolved = ResolvedConfig::from_files(&options.eval_parameters, &options.eval_overlays)
+        .with_context(|| {
+            format!(
+                "Could not read evaluation yaml file {}",
+                options.eval_parameters
             )
-        });
+        })?;
 
-    let text = options.text.as_ref().cloned().or_else(|| {
-        options.corpus.as_ref().map(|corpus_file| {
-            fs::read_to_string(corpus_file)
-                .unwrap_or_else(|_| panic!("Could not read corpus file from {}.", corpus_file))
+    for assignment in options.set.iter() {
+        match resolved.set(assignment) {
+            Ok(Some(previous)) => log::info!(
+                "Set {} (was {})",
+                assignment,
+                serde_yaml::to_string(&previous).unwrap_or_default().trim()
+            ),
+            Ok(None) => log::warn!("Set {} (not present in the configuration)", assignment),
+            Err(e) => return Err(e.context(format!("Could not apply --set {}", assignment))),
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// An enabled metric of the evaluation config, addressed by its key below `metrics` (and its
+/// index for lists of metrics)
+#[derive(Clone, Debug)]
+pub struct MetricEntry {
+    pub key: String,
+    pub index: Option<usize>,
+}
+
+impl MetricEntry {
+    pub fn label(&self) -> String {
+        match self.index {
+            Some(i) => format!("{}[{}]", self.key, i),
+            None => self.key.clone(),
+        }
+    }
+
+    pub fn params<'a>(&self, metrics: &'a Value) -> Option<&'a Value> {
+        let params = metrics.get(self.key.as_str())?;
+        match self.index {
+            Some(i) => params.get(i),
+            None => Some(params),
+        }
+    }
+
+    pub fn params_mut<'a>(&self, metrics: &'a mut Value) -> Option<&'a mut Value> {
+        let params = metrics.get_mut(self.key.as_str())?;
+        match self.index {
+            Some(i) => params.get_mut(i),
+            None => Some(params),
+        }
+    }
+}
+
+fn is_enabled(params: &Value) -> bool {
+    params.get("enabled").and_then(Value::as_bool) == Some(true)
+}
+
+/// All enabled metrics of the `metrics` section of an evaluation config.
+pub fn enabled_metrics(metrics: &Value) -> Vec<MetricEntry> {
+    let mut entries = Vec::new();
+    if let Some(mapping) = metrics.as_mapping() {
+        for (key, params) in mapping.iter() {
+            let key = match key.as_str() {
+                Some(key) => key.to_string(),
+                None => continue,
+            };
+            match params {
+                Value::Sequence(list) => {
+                    for (i, params) in list.iter().enumerate() {
+                        if is_enabled(params) {
+                            entries.push(MetricEntry {
+                                key: key.clone(),
+                                index: Some(i),
+                            });
+                        }
+                    }
+                }
+                _ if is_enabled(params) => entries.push(MetricEntry { key, index: None }),
+                _ => {}
+            }
+        }
+    }
+
+    entries
+}
+
+/// Metric parameters with the given enabled metrics switched on or off.
+pub fn metric_parameters(
+    metrics: &Value,
+    entries: &[MetricEntry],
+    enabled: impl Fn(&MetricEntry) -> bool,
+) -> MetricParameters {
+    let mut metrics = metrics.clone();
+    for entry in entries {
+        if let Some(params) = entry.params_mut(&mut metrics) {
+            params["enabled"] = Value::Bool(enabled(entry));
+        }
+    }
+
+    // round trip through a string as some configs use `null` keys that are
+    // only accepted when deserializing from a string
+    serde_yaml::to_string(&metrics)
+        .and_then(|s| serde_yaml::from_str(&s))
+        .unwrap_or_else(|e| panic!("Could not read metric parameters: {:?}", e))
+}
+
+fn read_eval_params(options: &Options) -> Result<EvaluationParameters> {
+    let resolved = resolve_eval_params(options)?;
+    if resolved.files.len() > 1 {
+        log::info!("Evaluation configuration: {}", resolved.files.join(" + "));
+    }
+
+    resolved.parameters().with_context(|| {
+        format!(
+            "Could not read evaluation yaml file {}",
+            options.eval_parameters
+        )
+    })
+}
+
+fn read_corpus(corpus_file: &str) -> Result<String> {
+    compressed::read_to_string(corpus_file)
+        .with_context(|| format!("Could not read corpus file from {}", corpus_file))
+}
+
+/// Splits a text by lines into an optimization and a holdout part. The held out lines
+/// are spread evenly over the text.
+fn split_text(text: &str, holdout_fraction: f64) -> (String, String) {
+    let mut optimization_text = String::new();
+    let mut holdout_text = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let is_holdout =
+            ((i + 1) as f64 * holdout_fraction).floor() > (i as f64 * holdout_fraction).floor();
+        let target = if is_holdout {
+            &mut holdout_text
+        } else {
+            &mut optimization_text
+        };
+        target.push_str(line);
+        target.push('\n');
+    }
+
+    (optimization_text, holdout_text)
+}
+
+/// The text given directly or as corpus file (without the held out part), if any.
+pub fn optimization_text(options: &Options) -> Result<Option<String>> {
+    if let Some(text) = &options.text {
+        return Ok(Some(text.clone()));
+    }
+
+    options
+        .corpus
+        .as_ref()
+        .map(|corpus_file| {
+            let text = read_corpus(corpus_file)?;
+            Ok(match options.holdout_fraction {
+                Some(holdout_fraction) => split_text(&text, holdout_fraction).0,
+                None => text,
+            })
         })
-    });
+        .transpose()
+}
+
+/// Generates an evaluator with the metrics of the evaluation parameters for the given ngrams.
+fn default_evaluator(
+    ngram_provider: Box<dyn NgramMapper>,
+    eval_params: &EvaluationParameters,
+) -> Result<Evaluator> {
+    let channels = eval_params
+        .ngrams
+        .channels
+        .iter()
+        .map(|channel| Ok((channel.name.clone(), read_channel_unigrams(channel)?)))
+        .collect::<Result<_>>()?;
+
+    Evaluator::default(ngram_provider)
+        .with_ngram_channels(channels)
+        .default_metrics(&eval_params.metrics)
+}
+
+pub fn init_evaluator(options: &Options) -> Result<Evaluator> {
+    let eval_params = read_eval_params(options)?;
+
+    let text = optimization_text(options)?;
+    let ngram_provider = init_ngram_mapper(options, &eval_params, text, &options.ngrams)?;
+    let mut evaluator = default_evaluator(Box::new(ngram_provider), &eval_params)?;
+    if options.bigram_cost_cache {
+        evaluator = evaluator.with_bigram_cost_cache();
+    }
+    if options.cost_matrix {
+        evaluator = evaluator.with_cost_matrix(options.cost_matrices);
+    }
+
+    if !options.additional_corpora.is_empty() {
+        let corpus_name = options
+            .corpus
+            .as_ref()
+            .unwrap_or(&options.ngrams)
+            .to_string();
+        let additional_corpora: Vec<(String, Box<dyn NgramMapper>)> = options
+            .additional_corpora
+            .iter()
+            .map(|path| {
+                let ngram_provider: Box<dyn NgramMapper> =
+                    Box::new(init_path_ngram_mapper(options, &eval_params, path)?);
+
+                Ok((path.to_string(), ngram_provider))
+            })
+            .collect::<Result<_>>()?;
+
+        log::info!(
+            "Combining the costs of {} corpora using {}",
+            additional_corpora.len() + 1,
+            options.corpus_objective
+        );
+        evaluator = evaluator.with_additional_corpora(
+            &corpus_name,
+            additional_corpora,
+            options.corpus_objective.clone(),
+        );
+    }
+
+    evaluator = apply_layer_overrides(&eval_params, evaluator)?;
+
+    if let Some(filename) = &options.co_keyboards {
+        evaluator = evaluator.with_co_keyboards(init_co_keyboards(options, filename)?);
+    }
 
+    if let Some(results_db) = init_results_db(options) {
+        evaluator = evaluator.with_recorder(results_db);
+    }
+
+    Ok(evaluator)
+}
+
+/// Evaluates the ngrams of some layers with the metric parameters of the layer overrides of the
+/// evaluation parameters (if any).
+fn apply_layer_overrides(
+    eval_params: &EvaluationParameters,
+    evaluator: Evaluator,
+) -> Result<Evaluator> {
+    if eval_params.layer_overrides.is_empty() {
+        return Ok(evaluator);
+    }
+
+    evaluator
+        .with_layer_overrides(&eval_params.layer_overrides)
+        .context("Could not apply the layer overrides")
+}
+
+/// Generates the evaluators for the further keyboards of the co-keyboards configuration file.
+/// Layouts are transferred to them by the hand, finger, and direction of their keys, so all
+/// keyboards need to use the [`NeoLayoutGenerator`].
+fn init_co_keyboards(options: &Options, filename: &str) -> Result<CoKeyboards> {
+    let config = CoKeyboardsConfig::from_yaml(filename).with_context(|| {
+        format!(
+            "Could not read co-keyboards configuration from {}",
+            filename
+        )
+    })?;
+    if options.grouped_layout_generator {
+        bail!("Evaluating layouts on several keyboards is not supported by the grouped layout generator");
+    }
+
+    let (_, main_base) = base_layout(&options.layout_config, &options.layers)?;
+    let keyboards: Vec<CoKeyboard> = config
+        .keyboards
+        .iter()
+        .map(|keyboard| {
+            log::info!("Preparing the evaluation on keyboard '{}'", keyboard.name);
+            let (layout_generator, base) = base_layout(&keyboard.layout_config, &options.layers)?;
+
+            let mut keyboard_options = options.clone();
+            keyboard_options.co_keyboards = None;
+            keyboard_options.results_db = None;
+            keyboard_options.layout_config = keyboard.layout_config.clone();
+            if let Some(eval_parameters) = &keyboard.eval_parameters {
+                keyboard_options.eval_parameters = eval_parameters.clone();
+                keyboard_options.eval_overlays.clear();
+                keyboard_options.set.clear();
+            }
+
+            Ok(CoKeyboard::new(
+                &keyboard.name,
+                keyboard.weight,
+                keyboard.reference_cost,
+                Box::new(layout_generator),
+                &base,
+                &main_base,
+                init_evaluator(&keyboard_options)?,
+            ))
+        })
+        .collect::<Result<_>>()?;
+
+    log::info!(
+        "Combining the costs of {} keyboards (deviation penalty: {})",
+        keyboards.len() + 1,
+        config.deviation_penalty
+    );
+
+    Ok(CoKeyboards {
+        name: config.name,
+        weight: config.weight,
+        reference_cost: config.reference_cost,
+        deviation_penalty: config.deviation_penalty,
+        keyboards,
+    })
+}
+
+/// Generates the base layout of the given layout configuration file (together with its layout
+/// generator).
+fn base_layout(filename: &str, layers: &[u8]) -> Result<(NeoLayoutGenerator, Layout)> {
+    let layout_config = read_layout_config(filename, false, layers)?;
+    let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
+    let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
+    let base = layout_generator
+        .generate_base()
+        .with_context(|| format!("Could not generate the base layout of {}", filename))?;
+
+    Ok((layout_generator, base))
+}
+
+/// Log how many individual bigram costs were taken from the cache and how many cost matrices
+/// were built (if enabled).
+pub fn log_bigram_cost_cache(evaluator: &Evaluator) {
+    if let Some(cache) = evaluator.bigram_cost_cache() {
+        log::info!(
+            "Bigram cost cache hit rate: {:.1}%",
+            100.0 * cache.hit_rate()
+        );
+    }
+    if let Some(cost_matrix) = evaluator.cost_matrix() {
+        log::info!("Cost matrices built: {}", cost_matrix.builds());
+    }
+}
+
+/// Generates an evaluator for the data held out from optimization (if any).
+pub fn init_holdout_evaluator(options: &Options) -> Result<Option<Evaluator>> {
+    let eval_params = read_eval_params(options)?;
+
+    let mut holdout_corpora: Vec<(String, Box<dyn NgramMapper>)> = Vec::new();
+    if let (Some(corpus_file), Some(holdout_fraction)) = (&options.corpus, options.holdout_fraction)
+    {
+        let holdout_text = split_text(&read_corpus(corpus_file)?, holdout_fraction).1;
+        let ngram_provider = init_ngram_mapper(options, &eval_params, Some(holdout_text), "")?;
+        holdout_corpora.push((
+            format!("{} (holdout)", corpus_file),
+            Box::new(ngram_provider),
+        ));
+    }
+    for path in &options.holdout {
+        let ngram_provider = init_path_ngram_mapper(options, &eval_params, path)?;
+        holdout_corpora.push((path.to_string(), Box::new(ngram_provider)));
+    }
+
+    if holdout_corpora.is_empty() {
+        return Ok(None);
+    }
+
+    let (name, ngram_provider) = holdout_corpora.remove(0);
+    let mut evaluator = default_evaluator(ngram_provider, &eval_params)?;
+    if !holdout_corpora.is_empty() {
+        evaluator =
+            evaluator.with_additional_corpora(&name, holdout_corpora, CorpusObjective::Mean);
+    }
+
+    apply_layer_overrides(&eval_params, evaluator).map(Some)
+}
+
+/// Compares the cost of a layout on the optimization data with its cost on the holdout data
+/// and warns if the gap is larger than `warn_gap` (relative to the optimization cost).
+pub fn check_overfitting(layout: &Layout, cost: f64, holdout_evaluator: &Evaluator, warn_gap: f64) {
+    let holdout_cost = holdout_evaluator.evaluate_layout(layout).total_cost();
+    let gap = (holdout_cost - cost) / cost;
+    log::info!(
+        "Cost on optimization data: {:.2}, on holdout data: {:.2} (gap: {:.1}%)",
+        cost,
+        holdout_cost,
+        100.0 * gap
+    );
+    if gap > warn_gap {
+        log::warn!(
+            "Layout performs {:.1}% worse on the holdout data than on the optimization data. \
+             It might be overfitted to the optimization corpus.",
+            100.0 * gap
+        );
+    }
+}
+
+/// Reads ngrams from a directory containing ngram files or from a corpus file.
+fn init_path_ngram_mapper(
+    options: &Options,
+    eval_params: &EvaluationParameters,
+    path: &str,
+) -> Result<OnDemandNgramMapper> {
+    let ngrams = read_path_ngrams(options, eval_params, path)?;
+
+    build_ngram_mapper(options, eval_params, ngrams)
+}
+
+/// Reads and prepares the ngrams of a directory containing ngram files or of a corpus file.
+fn read_path_ngrams(
+    options: &Options,
+    eval_params: &EvaluationParameters,
+    path: &str,
+) -> Result<(Unigrams, Bigrams, Trigrams, Quadgrams)> {
+    if Path::new(path).is_dir() {
+        read_ngrams(options, eval_params, None, path)
+    } else {
+        read_ngrams(options, eval_params, Some(read_corpus(path)?), path)
+    }
+}
+
+/// Adds the ngrams of a source, normalized to a total weight of `share`.
+fn add_normalized_ngrams<T: Clone + Eq + std::hash::Hash>(
+    grams: &mut AHashMap<T, f64>,
+    source: &AHashMap<T, f64>,
+    share: f64,
+) {
+    let total_weight: f64 = source.values().sum();
+    if total_weight > 0.0 {
+        add_scaled_ngrams(grams, source, share / total_weight);
+    }
+}
+
+/// Generates an evaluator for each category of the corpus profiles. The sources of a category
+/// are normalized and merged with their shares of the summed weights of all its sources.
+pub fn init_category_evaluators(
+    options: &Options,
+    profiles: &CorpusProfiles,
+) -> Result<Vec<(String, f64, Evaluator)>> {
+    let eval_params = read_eval_params(options)?;
+
+    profiles
+        .categories
+        .iter()
+        .map(|category| {
+            log::info!("Reading the corpus sources of category '{}'", category.name);
+            let total_weight: f64 = category.sources.iter().map(|s| s.weight).sum();
+            if category.sources.is_empty() || total_weight <= 0.0 {
+                bail!("Category '{}' has no weighted sources", category.name);
+            }
+
+            let mut unigrams = Unigrams {
+                grams: AHashMap::default(),
+            };
+            let mut bigrams = Bigrams {
+                grams: AHashMap::default(),
+            };
+            let mut trigrams = Trigrams {
+                grams: AHashMap::default(),
+            };
+            let mut quadgrams = Quadgrams::default();
+            for source in category.sources.iter() {
+                let (u, b, t, q) = read_path_ngrams(options, &eval_params, &source.path)?;
+                let share = source.weight / total_weight;
+                add_normalized_ngrams(&mut unigrams.grams, &u.grams, share);
+                add_normalized_ngrams(&mut bigrams.grams, &b.grams, share);
+                add_normalized_ngrams(&mut trigrams.grams, &t.grams, share);
+                add_normalized_ngrams(&mut quadgrams.grams, &q.grams, share);
+            }
+
+            let ngram_provider = build_ngram_mapper(
+                options,
+                &eval_params,
+                (unigrams, bigrams, trigrams, quadgrams),
+            )?;
+            let evaluator = default_evaluator(Box::new(ngram_provider), &eval_params)?;
+            let evaluator = apply_layer_overrides(&eval_params, evaluator)?;
+
+            Ok((category.name.clone(), category.weight, evaluator))
+        })
+        .collect()
+}
+
+/// Reads the ngrams the evaluator uses (from the text, corpus file, or ngram files) and prepares
+/// them according to the options, e.g. for analyzing the corpus.
+pub fn init_ngrams(options: &Options) -> Result<(Unigrams, Bigrams, Trigrams, Quadgrams)> {
+    let eval_params = read_eval_params(options)?;
+    let text = optimization_text(options)?;
+
+    read_ngrams(options, &eval_params, text, &options.ngrams)
+}
+
+/// Reads ngrams from the given text (or from the ngram files in `ngrams_dir` if no text is given)
+/// and prepares them according to the options.
+fn init_ngram_mapper(
+    options: &Options,
+    eval_params: &EvaluationParameters,
+    text: Option<String>,
+    ngrams_dir: &str,
+) -> Result<OnDemandNgramMapper> {
+    let ngrams = read_ngrams(options, eval_params, text, ngrams_dir)?;
+
+    build_ngram_mapper(options, eval_params, ngrams)
+}
+
+/// Generates an ngram mapper for the prepared ngrams.
+fn build_ngram_mapper(
+    options: &Options,
+    eval_params: &EvaluationParameters,
+    ngrams: (Unigrams, Bigrams, Trigrams, Quadgrams),
+) -> Result<OnDemandNgramMapper> {
     let mut ngram_mapper_config = eval_params.ngram_mapper.clone();
     if options.no_split_modifiers {
         ngram_mapper_config.split_modifiers.enabled = false;
     }
 
+    let (unigrams, bigrams, trigrams, quadgrams) = ngrams;
+    let mut ngram_provider =
+        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config)
+            .with_quadgrams(quadgrams);
+
+    if let Some(shortcuts_file) = &options.shortcuts {
+        log::info!("Reading shortcut file: '{}'", shortcuts_file);
+        let shortcuts = Shortcuts::from_file(shortcuts_file)
+            .with_context(|| format!("Could not read shortcut file from '{}'", shortcuts_file))?;
+        ngram_provider = ngram_provider.with_shortcuts(shortcuts);
+    }
+
+    Ok(ngram_provider)
+}
+
+/// Reads the unigrams of an ngram channel.
+fn read_channel_unigrams(channel: &NgramChannelConfig) -> Result<Unigrams> {
+    let p = compressed::find_file(Path::new(&channel.ngrams), "1-grams.txt");
+    Unigrams::from_file(p.to_str().unwrap())
+        .with_context(|| format!("Could not read 1-gramme file from '{:?}'.", &p))
+}
+
+/// Reads ngrams from the given text (or from the ngram files in `ngrams_dir` if no text is given)
+/// and prepares them according to the evaluation parameters and the options.
+fn read_ngrams(
+    options: &Options,
+    eval_params: &EvaluationParameters,
+    text: Option<String>,
+    ngrams_dir: &str,
+) -> Result<(Unigrams, Bigrams, Trigrams, Quadgrams)> {
     let mut ngrams_config = eval_params.ngrams.clone();
     if options.no_increase_common_ngrams {
         ngrams_config.increase_common_ngrams.enabled = false;
     }
 
-    let (mut unigrams, mut bigrams, mut trigrams) = match text {
-        Some(txt) => {
-            let unigrams =
-                Unigrams::from_text(&txt).expect("Could not generate unigrams from text.");
-            let bigrams = Bigrams::from_text(&txt).expect("Could not generate bigrams from text.");
-            let trigrams =
-                Trigrams::from_text(&txt).expect("Could not generate trigrams from text.");
-
-            (unigrams, bigrams, trigrams)
-        }
+    let (mut unigrams, mut bigrams, mut trigrams, mut quadgrams) = match text {
+        Some(txt) => corpus_processing::ngrams_from_text(&txt, true),
         None => {
-            let p = Path::new(&options.ngrams).join("1-grams.txt");
+            let p = compressed::find_file(ngrams_dir, "1-grams.txt");
             log::info!("Reading unigram file: '{:?}'", p);
             let unigrams = Unigrams::from_file(p.to_str().unwrap())
-                .unwrap_or_else(|_| panic!("Could not read 1-gramme file from '{:?}'.", &p));
-            let p = Path::new(&options.ngrams).join("2-grams.txt");
+                .with_context(|| format!("Could not read 1-gramme file from '{:?}'.", &p))?;
+            let p = compressed::find_file(ngrams_dir, "2-grams.txt");
             log::info!("Reading bigram file: '{:?}'", p);
             let bigrams = Bigrams::from_file(p.to_str().unwrap())
-                .unwrap_or_else(|_| panic!("Could not read 2-gramme file from '{:?}'.", &p));
-            let p = Path::new(&options.ngrams).join("3-grams.txt");
+                .with_context(|| format!("Could not read 2-gramme file from '{:?}'.", &p))?;
+            let p = compressed::find_file(ngrams_dir, "3-grams.txt");
             log::info!("Reading trigram file: '{:?}'", p);
             let trigrams = Trigrams::from_file(p.to_str().unwrap())
-                .unwrap_or_else(|_| panic!("Could not read 3-gramme file from '{:?}'.", &p));
+                .with_context(|| format!("Could not read 3-gramme file from '{:?}'.", &p))?;
+            // quadgrams are optional as they are only required by quadgram metrics
+            let p = compressed::find_file(ngrams_dir, "4-grams.txt");
+            let quadgram_metrics = eval_params.metrics.enabled_quadgram_metrics();
+            let quadgrams = if p.exists() {
+                log::info!("Reading quadgram file: '{:?}'", p);
+                Quadgrams::from_file(p.to_str().unwrap())
+                    .with_context(|| format!("Could not read 4-gramme file from '{:?}'.", &p))?
+            } else if !quadgram_metrics.is_empty() {
+                bail!(
+                    "No 4-gramme file '{:?}', but the quadgram metrics {} are enabled",
+                    &p,
+                    quadgram_metrics.join(", ")
+                );
+            } else {
+                log::info!("No 4-gramme file '{:?}', quadgram metrics are unavailable", &p);
+                Quadgrams::default()
+            };
 
-            (unigrams, bigrams, trigrams)
+            (unigrams, bigrams, trigrams, quadgrams)
         }
     };
 
+    for channel in &ngrams_config.channels {
+        log::info!(
+            "Merging ngram channel '{}' from '{}' with weight {}",
+            channel.name,
+            channel.ngrams,
+            channel.weight
+        );
+        let dir = Path::new(&channel.ngrams);
+
+        unigrams = unigrams.merge_weighted(&read_channel_unigrams(channel)?, channel.weight);
+
+        let p = compressed::find_file(dir, "2-grams.txt");
+        if p.exists() {
+            let channel_bigrams = Bigrams::from_file(p.to_str().unwrap())
+                .with_context(|| format!("Could not read 2-gramme file from '{:?}'.", &p))?;
+            bigrams = bigrams.merge_weighted(&channel_bigrams, channel.weight);
+        }
+
+        let p = compressed::find_file(dir, "3-grams.txt");
+        if p.exists() {
+            let channel_trigrams = Trigrams::from_file(p.to_str().unwrap())
+                .with_context(|| format!("Could not read 3-gramme file from '{:?}'.", &p))?;
+            trigrams = trigrams.merge_weighted(&channel_trigrams, channel.weight);
+        }
+    }
+
+    if let Some(case_handling) = options.case_handling {
+        ngrams_config.case_handling = case_handling;
+    }
+    if ngrams_config.case_handling != CaseHandling::Keep {
+        log::info!("Case handling: {:?}", ngrams_config.case_handling);
+        unigrams = unigrams.with_case_handling(&ngrams_config.case_handling);
+        bigrams = bigrams.with_case_handling(&ngrams_config.case_handling);
+        trigrams = trigrams.with_case_handling(&ngrams_config.case_handling);
+        quadgrams = quadgrams.with_case_handling(&ngrams_config.case_handling);
+    }
+
     if let Some(exclude_chars) = &options.exclude_chars {
         for exclude_char in exclude_chars.chars() {
             unigrams = unigrams.exclude_char(&exclude_char);
             bigrams = bigrams.exclude_char(&exclude_char);
             trigrams = trigrams.exclude_char(&exclude_char);
+            quadgrams = quadgrams.exclude_char(&exclude_char);
         }
     }
 
+    if ngrams_config.smoothing.method != SmoothingMethod::None {
+        log::info!("Smoothing unseen ngrams: {:?}", ngrams_config.smoothing);
+        // trigrams are estimated from the smoothed bigrams, so that trigrams containing an
+        // unseen bigram are added as well
+        bigrams = bigrams.smoothed(&unigrams, &ngrams_config.smoothing);
+        trigrams = trigrams.smoothed(&unigrams, &bigrams, &ngrams_config.smoothing);
+    }
+
     if ngrams_config.increase_common_ngrams.enabled {
         unigrams = unigrams.increase_common(&ngrams_config.increase_common_ngrams);
         bigrams = bigrams.increase_common(&ngrams_config.increase_common_ngrams);
         trigrams = trigrams.increase_common(&ngrams_config.increase_common_ngrams);
+        quadgrams = quadgrams.increase_common(&ngrams_config.increase_common_ngrams);
     }
 
     if let Some(tops) = options.tops {
         unigrams = unigrams.tops(tops);
         bigrams = bigrams.tops(tops);
         trigrams = trigrams.tops(tops);
+        quadgrams = quadgrams.tops(tops);
     }
 
-    let ngram_provider =
-        OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config);
+    Ok((unigrams, bigrams, trigrams, quadgrams))
+}
+
+/// The evaluation configuration files in the order they are applied.
+pub fn eval_parameter_files(options: &Options) -> Vec<String> {
+    ResolvedConfig::from_files(&options.eval_parameters, &options.eval_overlays)
+        .map(|resolved| resolved.files)
+        .unwrap_or_else(|_| vec![options.eval_parameters.clone()])
+}
 
-    Evaluator::default(Box::new(ngram_provider)).default_metrics(&eval_params.metrics)
+/// The configuration a result was evaluated with, for the metadata of reported results.
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigMetadata {
+    /// Evaluation configuration files in the order they were applied
+    pub eval_parameters: Vec<String>,
+    /// Values overridden on the command line
+    pub overrides: Vec<String>,
+    pub layout_config: String,
+    pub config_hash: String,
+}
+
+impl ConfigMetadata {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            eval_parameters: eval_parameter_files(options),
+            overrides: options.set.clone(),
+            layout_config: options.layout_config.clone(),
+            config_hash: config_hash(options),
+        }
+    }
+}
+
+/// Hash of all configuration that influences evaluation results (configuration files, ngram
+/// sources, and preprocessing options).
+pub fn config_hash(options: &Options) -> String {
+    let eval_parameters = eval_parameter_files(options)
+        .iter()
+        .map(|filename| fs::read_to_string(filename).unwrap_or_default())
+        .join("\n");
+    let layout_config = fs::read_to_string(&options.layout_config).unwrap_or_default();
+    let co_keyboards = options
+        .co_keyboards
+        .as_ref()
+        .map(|filename| fs::read_to_string(filename).unwrap_or_default())
+        .unwrap_or_default();
+    let sources = format!(
+        "{:?}",
+        (
+            &options.ngrams,
+            &options.corpus,
+            &options.text,
+            &options.shortcuts,
+            &options.additional_corpora,
+            &options.tops,
+            &options.exclude_chars,
+            options.no_split_modifiers,
+            options.no_increase_common_ngrams,
+            options.grouped_layout_generator,
+            &options.layers,
+            &options.set,
+        )
+    );
+
+    hash_str(&format!(
+        "{}\n{}\n{}\n{}",
+        eval_parameters, layout_config, co_keyboards, sources
+    ))
+}
+
+/// Opens the results database (if requested) for recording the results of the current
+/// configuration.
+fn init_results_db(options: &Options) -> Option<Arc<ResultsDb>> {
+    options.results_db.as_ref().map(|filename| {
+        let config_hash = config_hash(options);
+        let results_db = ResultsDb::open(filename)
+            .unwrap_or_else(|e| panic!("Could not open results database {}: {:?}", filename, e))
+            .with_config_hash(&config_hash);
+        log::info!(
+            "Recording results in '{}' (config hash: {})",
+            filename,
+            config_hash
+        );
+
+        Arc::new(results_db)
+    })
+}
+
+/// Opens the persistent result cache for the current configuration (if requested).
+pub fn init_result_cache(options: &Options) -> Option<Arc<ResultCache>> {
+    options.result_cache.as_ref().map(|filename| {
+        let config_hash = config_hash(options);
+        let result_cache = ResultCache::open(filename, &config_hash)
+            .unwrap_or_else(|e| panic!("Could not open result cache {}: {:?}", filename, e));
+        log::info!(
+            "Using {} cached results from '{}' (config hash: {})",
+            result_cache.len(),
+            filename,
+            config_hash
+        );
+
+        Arc::new(result_cache)
+    })
+}
+
+/// A cache of total costs, backed by the persistent result cache (if any).
+pub fn cost_cache(result_cache: &Option<Arc<ResultCache>>) -> Cache<f64> {
+    match result_cache {
+        Some(result_cache) => Cache::new().with_store(result_cache.clone()),
+        None => Cache::new(),
+    }
 }
 
 /// Appends a layout-string to a file.
@@ -0,0 +1,278 @@
+//! The `config_migration` module translates configuration files and layout strings of the
+//! upstream keyboard layout optimizer (dariogoetz/keyboard_layout_optimizer) into the format of
+//! this fork.
+//!
+//! Layout configs lack the `directions` of the keys within their finger's cluster. They are
+//! derived from the rows of the keyboard (see [`keyboard_layout::geometry::infer_directions`]).
+//! Evaluation configs are checked for fields that this fork does
+//! not know. Everything that can not be translated unambiguously is reported as a note that needs
+//! manual attention.
+
+use keyboard_layout::{
+    config::LayoutConfig,
+    geometry::{infer_directions, nest, GeometryInferenceYAML, InferredGeometry},
+    key::{Finger, Hand, MatrixPosition, Position},
+    layout::Layout,
+};
+use layout_evaluation::config::EvaluationParameters;
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+use std::str::FromStr;
+
+/// A migrated config (or layout string) with notes on what needs manual attention
+#[derive(Clone, Debug)]
+pub struct Migration {
+    pub result: String,
+    pub notes: Vec<String>,
+}
+
+fn field<T: DeserializeOwned>(mapping: &Mapping, name: &str) -> Result<Option<T>> {
+    mapping
+        .get(name)
+        .map(|value| {
+            serde_yaml::from_value(value.clone())
+                .map_err(|e| anyhow!("Could not read '{}': {}", name, e))
+        })
+        .transpose()
+}
+
+/// Migrate an upstream layout config (keyboard and base layout).
+pub fn migrate_layout_config(yaml: &str) -> Result<Migration> {
+    let mut config: Value = serde_yaml::from_str(yaml)?;
+    let mut notes = Vec::new();
+
+    let keyboard = config
+        .get_mut("keyboard")
+        .and_then(|k| k.as_mapping_mut())
+        .ok_or_else(|| anyhow!("The layout config has no 'keyboard' section"))?;
+
+    if keyboard.contains_key("directions") {
+        notes.push("The keyboard already has directions, they were kept".to_string());
+    } else {
+        let rows: Vec<Vec<Position>> = field(keyboard, "positions")?
+            .ok_or_else(|| anyhow!("The keyboard has no positions"))?;
+        let inference: GeometryInferenceYAML =
+            field(keyboard, "geometry_inference")?.unwrap_or_default();
+        let inferred = InferredGeometry::from_positions(&rows, &inference);
+        let hands: Vec<Vec<Hand>> = field(keyboard, "hands")?.unwrap_or(inferred.hands);
+        let fingers: Vec<Vec<Finger>> = field(keyboard, "fingers")?.unwrap_or(inferred.fingers);
+        let matrix_positions: Vec<Vec<MatrixPosition>> =
+            field(keyboard, "matrix_positions")?.unwrap_or(inferred.matrix_positions);
+        let resting_positions: AHashMap<Hand, AHashMap<Finger, Position>> =
+            field(keyboard, "finger_resting_positions")?
+                .ok_or_else(|| anyhow!("The keyboard has no finger resting positions"))?;
+
+        let directions = infer_directions(
+            &rows.concat(),
+            &hands.concat(),
+            &fingers.concat(),
+            &matrix_positions.concat(),
+            &resting_positions,
+            &mut notes,
+        );
+        let directions: Vec<Value> = nest(&directions, &rows)
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|d| Value::from(format!("{:?}", d)))
+                    .collect()
+            })
+            .collect();
+        keyboard.insert("directions".into(), Value::Sequence(directions));
+    }
+
+    if let Some(base_layout) = config
+        .get_mut("base_layout")
+        .and_then(|b| b.as_mapping_mut())
+    {
+        if !base_layout.contains_key("placeholder") {
+            base_layout.insert("placeholder".into(), "".into());
+        }
+
+        // modifiers used to be given as plain lists of keys that are held
+        if let Some(Value::Sequence(layer_modifiers)) = base_layout.get_mut("modifiers") {
+            let mut converted = 0;
+            for hand_modifiers in layer_modifiers
+                .iter_mut()
+                .filter_map(|m| m.as_mapping_mut())
+            {
+                for (_, locations) in hand_modifiers.iter_mut() {
+                    if locations.is_sequence() {
+                        let mut hold = Mapping::new();
+                        hold.insert("type".into(), "hold".into());
+                        hold.insert("value".into(), locations.clone());
+                        *locations = Value::Mapping(hold);
+                        converted += 1;
+                    }
+                }
+            }
+            if converted > 0 {
+                notes.push(format!(
+                    "Converted {} modifier lists to held modifiers ('type: hold')",
+                    converted
+                ));
+            }
+        }
+    }
+
+    let result = serde_yaml::to_string(&config)?;
+    LayoutConfig::from_str(&result)
+        .map_err(|e| anyhow!("The migrated layout config is not valid: {}", e))?;
+
+    Ok(Migration { result, notes })
+}
+
+/// Replace mappings with non-string keys (e.g. `[Left, Pinky]: 1.0`) by empty mappings as
+/// `serde_ignored` can not track paths through them.
+fn without_complex_keys(value: &Value) -> Value {
+    match value {
+        Value::Mapping(mapping) if mapping.keys().any(|k| !k.is_string()) => {
+            Value::Mapping(Mapping::new())
+        }
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .iter()
+                .map(|(k, v)| (k.clone(), without_complex_keys(v)))
+                .collect(),
+        ),
+        Value::Sequence(values) => {
+            Value::Sequence(values.iter().map(without_complex_keys).collect())
+        }
+        _ => value.clone(),
+    }
+}
+
+/// Check an upstream evaluation config for fields that this fork does not know (and would
+/// silently ignore). The config itself needs no translation.
+pub fn migrate_eval_config(yaml: &str) -> Result<Migration> {
+    EvaluationParameters::from_str(yaml)
+        .map_err(|e| anyhow!("The evaluation config is not valid here: {}", e))?;
+
+    let mut ignored = Vec::new();
+    let value = without_complex_keys(&serde_yaml::from_str(yaml)?);
+    let _: EvaluationParameters = serde_ignored::deserialize(value, |path| {
+        // "?" marks the content of an `Option`
+        ignored.push(path.to_string().replace(".?", ""));
+    })?;
+
+    let notes = ignored
+        .iter()
+        .map(|path| {
+            format!(
+                "'{}' is unknown here and ignored (a metric or parameter that is named \
+                 differently or not available)",
+                path
+            )
+        })
+        .collect();
+
+    Ok(Migration {
+        result: yaml.to_string(),
+        notes,
+    })
+}
+
+/// Translate a layout to the layout string of another keyboard (given by its base layout) by
+/// moving each symbol to the key of the same hand, finger, and direction. Symbols without such a
+/// key (or whose key is taken by a symbol on a more comfortable key) are placed on the
+/// remaining keys, the most comfortable ones first.
+pub fn translate_layout(
+    source: &Layout,
+    target_base: &Layout,
+    placeholder: Option<char>,
+) -> Migration {
+    let mut notes = Vec::new();
+    let target_keys: Vec<_> = target_base
+        .layerkeys
+        .iter()
+        .filter(|k| k.layer == 0 && !k.is_fixed)
+        .collect();
+    let target_symbols: AHashSet<char> = target_keys
+        .iter()
+        .map(|k| k.symbol)
+        .filter(|c| Some(*c) != placeholder)
+        .collect();
+
+    // source symbols by the comfort of their keys
+    let source_keys: Vec<_> = source
+        .layerkeys
+        .iter()
+        .filter(|k| k.layer == 0 && target_symbols.contains(&k.symbol))
+        .unique_by(|k| k.symbol)
+        .sorted_by(|k1, k2| k1.key.cost.partial_cmp(&k2.key.cost).unwrap())
+        .collect();
+
+    let mut assigned: Vec<Option<char>> = vec![None; target_keys.len()];
+    let mut leftover = Vec::new();
+    for source_key in source_keys.iter() {
+        let same_key = target_keys.iter().enumerate().position(|(i, k)| {
+            assigned[i].is_none()
+                && k.key.hand == source_key.key.hand
+                && k.key.finger == source_key.key.finger
+                && k.key.direction == source_key.key.direction
+        });
+        match same_key {
+            Some(i) => assigned[i] = Some(source_key.symbol),
+            None => leftover.push(source_key.symbol),
+        }
+    }
+
+    let source_symbols: AHashSet<char> = source_keys.iter().map(|k| k.symbol).collect();
+    let missing: Vec<char> = target_keys
+        .iter()
+        .map(|k| k.symbol)
+        .filter(|c| target_symbols.contains(c) && !source_symbols.contains(c))
+        .unique()
+        .collect();
+    if !missing.is_empty() {
+        notes.push(format!(
+            "Symbols '{}' are not in the source layout",
+            missing.iter().collect::<String>()
+        ));
+    }
+    let dropped: String = source
+        .layerkeys
+        .iter()
+        .filter(|k| k.layer == 0 && !k.is_fixed && !target_symbols.contains(&k.symbol))
+        .map(|k| k.symbol)
+        .unique()
+        .collect();
+    if !dropped.is_empty() {
+        notes.push(format!(
+            "Symbols '{}' are not permutable on the target keyboard and were dropped",
+            dropped
+        ));
+    }
+
+    let free_keys: Vec<usize> = (0..target_keys.len())
+        .filter(|i| assigned[*i].is_none())
+        .sorted_by(|i1, i2| {
+            let c1 = target_keys[*i1].key.cost;
+            let c2 = target_keys[*i2].key.cost;
+            c1.partial_cmp(&c2).unwrap()
+        })
+        .collect();
+    let mut symbols = leftover.iter().chain(missing.iter());
+    for i in free_keys {
+        let key = &target_keys[i].key;
+        match symbols.next() {
+            Some(c) => {
+                notes.push(format!(
+                    "'{}' has no matching key and was placed on the {:?} {:?} {:?} key",
+                    c, key.hand, key.finger, key.direction
+                ));
+                assigned[i] = Some(*c);
+            }
+            None => assigned[i] = placeholder,
+        }
+    }
+
+    Migration {
+        result: assigned.iter().flatten().collect(),
+        notes,
+    }
+}
@@ -0,0 +1,284 @@
+//! The `corpus_cleaning` module provides preprocessing of raw corpus documents before counting
+//! ngrams: stripping common boilerplate and finding near-identical documents. Text that is not
+//! typed by the user (license headers, quoted replies, generated code banners) or that is
+//! contained many times (copies of the same document) badly skews personal corpora.
+//!
+//! Near duplicates are found with MinHash signatures and locality-sensitive hashing: the
+//! signatures are split into bands, and only documents sharing a band are compared, so that the
+//! effort grows about linearly with the number of documents.
+
+use ahash::AHashMap;
+use fnv::FnvHasher;
+use rayon::prelude::*;
+use std::{
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+/// Number of hashes of the MinHash signature of a document.
+const N_HASHES: usize = 64;
+
+/// Possible numbers of hashes per band (divisors of `N_HASHES`).
+const BAND_ROWS: [usize; 7] = [1, 2, 4, 8, 16, 32, 64];
+
+/// Number of consecutive words forming a shingle for the similarity of documents.
+const SHINGLE_WORDS: usize = 5;
+
+/// Prefixes of comment lines in common programming and markup languages.
+const COMMENT_PREFIXES: &[&str] = &[
+    "//", "#", "/*", "*", "--", ";", "%", "<!--", "\"\"\"", "'''", "rem ", "REM ",
+];
+
+/// Words indicating that a comment block is a license header.
+const LICENSE_KEYWORDS: &[&str] = &[
+    "copyright",
+    "license",
+    "licence",
+    "all rights reserved",
+    "permission is hereby granted",
+];
+
+/// Words indicating that a comment marks generated code.
+const GENERATED_KEYWORDS: &[&str] = &[
+    "auto-generated",
+    "autogenerated",
+    "automatically generated",
+    "code generated by",
+    "do not edit",
+];
+
+/// Endings of the attribution lines introducing a quoted reply.
+const ATTRIBUTION_ENDINGS: &[&str] = &["wrote:", "schrieb:", "a écrit :", "a écrit:", "escribió:"];
+
+/// Kinds of boilerplate that can be stripped from a document
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boilerplate {
+    /// Comment blocks at the start of a document that mention a license or copyright
+    License,
+    /// Quoted reply text of emails: "> ..." lines, their "On ... wrote:" attributions, and
+    /// everything after an "-----Original Message-----" separator (up to the next mbox message)
+    Quotes,
+    /// Comment lines marking generated code (e.g. "Code generated by ... DO NOT EDIT.")
+    Generated,
+}
+
+impl FromStr for Boilerplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "license" => Ok(Boilerplate::License),
+            "quotes" => Ok(Boilerplate::Quotes),
+            "generated" => Ok(Boilerplate::Generated),
+            _ => Err(format!(
+                "Unknown boilerplate '{}' (expected 'license', 'quotes', or 'generated')",
+                s
+            )),
+        }
+    }
+}
+
+impl Boilerplate {
+    /// Remove this kind of boilerplate from the text.
+    pub fn strip(&self, text: &str) -> String {
+        match self {
+            Boilerplate::License => strip_license_header(text),
+            Boilerplate::Quotes => strip_quotes(text),
+            Boilerplate::Generated => text
+                .split_inclusive('\n')
+                .filter(|line| {
+                    let trimmed = line.trim();
+                    !(is_comment(trimmed)
+                        && contains_any(&trimmed.to_lowercase(), GENERATED_KEYWORDS))
+                })
+                .collect(),
+        }
+    }
+}
+
+fn is_comment(trimmed_line: &str) -> bool {
+    COMMENT_PREFIXES.iter().any(|p| trimmed_line.starts_with(p))
+}
+
+fn contains_any(text: &str, keywords: &[&str]) -> bool {
+    keywords.iter().any(|k| text.contains(k))
+}
+
+/// Remove the comment block at the start of the text if it mentions a license or copyright.
+fn strip_license_header(text: &str) -> String {
+    let mut header_end = 0;
+    let mut in_block_comment = false;
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let in_header = if in_block_comment {
+            in_block_comment = !trimmed.contains("*/");
+            true
+        } else if let Some(rest) = trimmed.strip_prefix("/*") {
+            in_block_comment = !rest.contains("*/");
+            true
+        } else {
+            trimmed.is_empty() || is_comment(trimmed)
+        };
+
+        if !in_header {
+            break;
+        }
+        header_end += line.len();
+    }
+
+    match contains_any(&text[..header_end].to_lowercase(), LICENSE_KEYWORDS) {
+        true => text[header_end..].to_string(),
+        false => text.to_string(),
+    }
+}
+
+/// Remove quoted reply text of emails.
+fn strip_quotes(text: &str) -> String {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let is_quote = |line: &str| line.trim_start().starts_with('>');
+
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_original_message = false;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if in_original_message {
+            // the next message of an mbox file
+            if !line.starts_with("From ") {
+                continue;
+            }
+            in_original_message = false;
+        }
+
+        if trimmed.starts_with('-') && trimmed.to_lowercase().contains("original message") {
+            in_original_message = true;
+            continue;
+        }
+
+        if is_quote(line) {
+            continue;
+        }
+
+        let is_attribution = contains_any(&trimmed.to_lowercase(), ATTRIBUTION_ENDINGS)
+            && lines[(i + 1)..]
+                .iter()
+                .find(|l| !l.trim().is_empty())
+                .map_or(false, |l| is_quote(l));
+        if is_attribution {
+            continue;
+        }
+
+        stripped.push_str(line);
+    }
+
+    stripped
+}
+
+/// MinHash signature of the word shingles of a text.
+fn signature(text: &str) -> [u64; N_HASHES] {
+    let words: Vec<String> = text.split_whitespace().map(|w| w.to_lowercase()).collect();
+    let shingle_hashes: Vec<u64> = words
+        .windows(SHINGLE_WORDS.min(words.len().max(1)))
+        .map(|shingle| {
+            let mut hasher = FnvHasher::default();
+            shingle.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect();
+
+    let mut signature = [u64::MAX; N_HASHES];
+    for (i, min_hash) in signature.iter_mut().enumerate() {
+        // cheap permutations of the shingle hashes (odd multipliers are bijective)
+        let multiplier = 0x9E37_79B9_7F4A_7C15u64.wrapping_mul(2 * i as u64 + 1) | 1;
+        for h in shingle_hashes.iter() {
+            let mut x = h.wrapping_mul(multiplier);
+            x ^= x >> 31;
+            *min_hash = (*min_hash).min(x);
+        }
+    }
+
+    signature
+}
+
+/// Split an mbox file into its messages (each starting with a "From " line). Other texts are a
+/// single document.
+pub fn split_messages(text: &str) -> Vec<&str> {
+    if !text.starts_with("From ") {
+        return vec![text];
+    }
+
+    let mut messages = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if offset > start && line.starts_with("From ") {
+            messages.push(&text[start..offset]);
+            start = offset;
+        }
+        offset += line.len();
+    }
+    messages.push(&text[start..]);
+
+    messages
+}
+
+/// The largest number of hashes per band for which the bands still find texts at the threshold
+/// similarity. Two texts of similarity `s` share a band of `r` hashes with a probability of
+/// `1 - (1 - s^r)^(N_HASHES / r)`, which is steepest around `(r / N_HASHES)^(1 / r)`.
+fn rows_per_band(threshold: f64) -> usize {
+    BAND_ROWS
+        .iter()
+        .copied()
+        .filter(|&rows| (rows as f64 / N_HASHES as f64).powf(1.0 / rows as f64) <= threshold)
+        .max()
+        .unwrap_or(1)
+}
+
+/// Hashes of the bands of a signature, together with the index of each band.
+fn band_hashes(
+    signature: &[u64; N_HASHES],
+    rows: usize,
+) -> impl Iterator<Item = (usize, u64)> + '_ {
+    signature.chunks(rows).enumerate().map(|(band, hashes)| {
+        let mut hasher = FnvHasher::default();
+        hashes.hash(&mut hasher);
+        (band, hasher.finish())
+    })
+}
+
+/// For each text, the index of an earlier text it is a near duplicate of (if any). Two texts
+/// are near duplicates if the estimated Jaccard similarity of their sets of word 5-grams is at
+/// least the threshold. Texts are only compared with earlier texts that are no duplicates
+/// themselves and that share a band of their signatures.
+pub fn find_near_duplicates(texts: &[&str], threshold: f64) -> Vec<Option<usize>> {
+    let signatures: Vec<[u64; N_HASHES]> = texts.par_iter().map(|t| signature(t)).collect();
+    let rows = rows_per_band(threshold);
+
+    let similarity = |s1: &[u64; N_HASHES], s2: &[u64; N_HASHES]| {
+        let matches = s1.iter().zip(s2.iter()).filter(|(a, b)| a == b).count();
+        matches as f64 / N_HASHES as f64
+    };
+
+    // indices of the originals (no duplicates) by band
+    let mut buckets: AHashMap<(usize, u64), Vec<usize>> = AHashMap::default();
+    signatures
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let bands: Vec<(usize, u64)> = band_hashes(s, rows).collect();
+            let original = bands
+                .iter()
+                .filter_map(|band| buckets.get(band))
+                .flatten()
+                .copied()
+                .filter(|&j| similarity(s, &signatures[j]) >= threshold)
+                .min();
+            if original.is_none() {
+                for band in bands {
+                    buckets.entry(band).or_default().push(i);
+                }
+            }
+
+            original
+        })
+        .collect()
+}
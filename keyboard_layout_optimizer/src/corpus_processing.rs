@@ -0,0 +1,122 @@
+//! The `corpus_processing` module generates ngram frequencies from (large) corpora on all cores.
+//! The text is split into chunks whose ngrams are counted in parallel and then added up. Ngrams
+//! crossing the boundary between two chunks are counted with the chunk they start in, so the
+//! result is identical to counting the whole text at once.
+//!
+//! Progress bars with the throughput are shown on the terminal (and hidden if the output is not
+//! a terminal).
+
+use layout_evaluation::ngrams::{Bigrams, Quadgrams, Trigrams, Unigrams};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+
+/// Size of the chunks of a text that are processed in parallel (in bytes)
+const CHUNK_SIZE: usize = 1 << 22;
+
+/// A progress bar for processing the given number of bytes.
+pub fn bytes_progress(total_bytes: u64, message: &str) -> ProgressBar {
+    let progress = ProgressBar::new(total_bytes).with_message(message.to_string());
+    progress.set_style(
+        ProgressStyle::with_template(
+            "{msg:>10} [{bar:40}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, ETA {eta})",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+
+    progress
+}
+
+/// A progress bar for processing the given number of files.
+pub fn files_progress(total_files: u64, message: &str) -> ProgressBar {
+    let progress = ProgressBar::new(total_files).with_message(message.to_string());
+    progress.set_style(
+        ProgressStyle::with_template(
+            "{msg:>10} [{bar:40}] {pos}/{len} files ({per_sec}, ETA {eta})",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+
+    progress
+}
+
+/// Split the text into chunks of about `chunk_size` bytes (at char boundaries). Each chunk comes
+/// with the text following it.
+pub fn chunks(text: &str, chunk_size: usize) -> Vec<(&str, &str)> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let mut end = (start + chunk_size).min(text.len());
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+        chunks.push((&text[start..end], &text[end..]));
+        start = end;
+    }
+
+    chunks
+}
+
+fn chunk_ngrams(
+    chunk: &str,
+    following: &str,
+    with_quadgrams: bool,
+) -> (Unigrams, Bigrams, Trigrams, Quadgrams) {
+    let unigrams = Unigrams::from_text_chunk(chunk, following)
+        .expect("Could not generate unigrams from text.");
+    let bigrams =
+        Bigrams::from_text_chunk(chunk, following).expect("Could not generate bigrams from text.");
+    let trigrams = Trigrams::from_text_chunk(chunk, following)
+        .expect("Could not generate trigrams from text.");
+    let quadgrams = match with_quadgrams {
+        true => Quadgrams::from_text_chunk(chunk, following)
+            .expect("Could not generate quadgrams from text."),
+        false => Quadgrams::default(),
+    };
+
+    (unigrams, bigrams, trigrams, quadgrams)
+}
+
+/// Count the ngrams of the text in parallel. Quadgrams are only counted if `with_quadgrams` is
+/// set (they are empty otherwise).
+pub fn ngrams_from_text(
+    text: &str,
+    with_quadgrams: bool,
+) -> (Unigrams, Bigrams, Trigrams, Quadgrams) {
+    ngrams_from_documents(&[text], with_quadgrams)
+}
+
+/// Count the ngrams of several documents in parallel and add them up. Ngrams do not cross the
+/// boundary between two documents.
+pub fn ngrams_from_documents(
+    documents: &[&str],
+    with_quadgrams: bool,
+) -> (Unigrams, Bigrams, Trigrams, Quadgrams) {
+    let total_bytes: usize = documents.iter().map(|d| d.len()).sum();
+    let progress = bytes_progress(total_bytes as u64, "Counting");
+
+    let ngrams = documents
+        .iter()
+        .flat_map(|d| chunks(d, CHUNK_SIZE))
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(chunk, following)| {
+            let ngrams = chunk_ngrams(chunk, following, with_quadgrams);
+            progress.inc(chunk.len() as u64);
+            ngrams
+        })
+        .reduce_with(|(mut u1, mut b1, mut t1, mut q1), (u2, b2, t2, q2)| {
+            u1.add(&u2);
+            b1.add(&b2);
+            t1.add(&t2);
+            q1.add(&q2);
+            (u1, b1, t1, q1)
+        })
+        .unwrap_or_else(|| chunk_ngrams("", "", with_quadgrams));
+
+    progress.finish();
+
+    ngrams
+}
@@ -0,0 +1,166 @@
+//! The `corpus_profiles` module tags corpus sources with application categories (e.g. editor,
+//! terminal, chat, browser) and breaks the evaluation of a layout down per category. A layout
+//! that is great for prose may well be mediocre for shell commands, which a single combined
+//! corpus hides.
+
+use layout_evaluation::results::EvaluationResult;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{env, fmt, fs::File};
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// Replace a leading `~` of the path with the home directory (if known).
+fn expand_home(path: &str) -> String {
+    let home = env::var("HOME").or_else(|_| env::var("USERPROFILE"));
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            format!("{}{}", home, rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// A corpus source: a directory containing ngram files or a corpus file
+#[derive(Clone, Deserialize, Debug)]
+pub struct CorpusSource {
+    pub path: String,
+    /// Weight of the source relative to the other sources of its category
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+/// An application category with its corpus sources
+#[derive(Clone, Deserialize, Debug)]
+pub struct CorpusCategory {
+    pub name: String,
+    /// Weight of the category in the combined cost
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    pub sources: Vec<CorpusSource>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct CorpusProfiles {
+    pub categories: Vec<CorpusCategory>,
+}
+
+impl CorpusProfiles {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let mut profiles: CorpusProfiles = serde_yaml::from_reader(f)?;
+        for source in profiles
+            .categories
+            .iter_mut()
+            .flat_map(|c| c.sources.iter_mut())
+        {
+            source.path = expand_home(&source.path);
+        }
+
+        Ok(profiles)
+    }
+}
+
+/// The costs of a layout for a single category
+#[derive(Clone, Serialize, Debug)]
+pub struct CategoryCost {
+    pub category: String,
+    pub weight: f64,
+    pub total_cost: f64,
+    /// Weighted costs of the individual metrics
+    pub metric_costs: Vec<(String, f64)>,
+}
+
+impl CategoryCost {
+    pub fn new(category: &str, weight: f64, result: &EvaluationResult) -> Self {
+        let metric_costs = result.metric_costs();
+
+        Self {
+            category: category.to_string(),
+            weight,
+            total_cost: result.total_cost(),
+            metric_costs,
+        }
+    }
+}
+
+/// The costs of a layout broken down per category and combined (the weighted mean of the
+/// categories)
+#[derive(Clone, Serialize, Debug)]
+pub struct CategoryReport {
+    pub layout: String,
+    pub categories: Vec<CategoryCost>,
+    pub combined_cost: f64,
+    /// Weighted means of the metric costs over the categories
+    pub combined_metric_costs: Vec<(String, f64)>,
+}
+
+impl CategoryReport {
+    pub fn new(layout: &str, categories: Vec<CategoryCost>) -> Self {
+        let total_weight: f64 = categories.iter().map(|c| c.weight).sum();
+        let weighted_mean = |cost: &dyn Fn(&CategoryCost) -> f64| match total_weight > 0.0 {
+            true => categories.iter().map(|c| c.weight * cost(c)).sum::<f64>() / total_weight,
+            false => 0.0,
+        };
+
+        let combined_cost = weighted_mean(&|c| c.total_cost);
+        let combined_metric_costs = categories
+            .first()
+            .map(|first| {
+                first
+                    .metric_costs
+                    .iter()
+                    .map(|(name, _)| {
+                        let cost = weighted_mean(&|c| metric_cost(c, name));
+                        (name.clone(), cost)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            layout: layout.to_string(),
+            categories,
+            combined_cost,
+            combined_metric_costs,
+        }
+    }
+}
+
+fn metric_cost(category: &CategoryCost, name: &str) -> f64 {
+    category
+        .metric_costs
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, cost)| *cost)
+        .unwrap_or(0.0)
+}
+
+impl fmt::Display for CategoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Layout: {}", self.layout)?;
+
+        write!(f, "{:<35}", "")?;
+        for category in self.categories.iter() {
+            write!(f, " {:>10.10}", category.category)?;
+        }
+        writeln!(f, " {:>10}", "combined")?;
+
+        for (name, combined) in self.combined_metric_costs.iter() {
+            write!(f, "{:<35.35}", name)?;
+            for category in self.categories.iter() {
+                write!(f, " {:>10.2}", metric_cost(category, name))?;
+            }
+            writeln!(f, " {:>10.2}", combined)?;
+        }
+
+        write!(f, "{:<35}", "Total")?;
+        for category in self.categories.iter() {
+            write!(f, " {:>10.2}", category.total_cost)?;
+        }
+        writeln!(f, " {:>10.2}", self.combined_cost)
+    }
+}
@@ -0,0 +1,122 @@
+//! The `export` module renders layouts as configuration files of other tools, so that an
+//! optimized layout can be installed (or tried out) outside of the optimizer, and as pictures of
+//! the keyboard.
+//!
+//! Keys are identified by their XKB key names (e.g. `AD01`), which are assigned to the keyboard's
+//! matrix positions by a keycode file (see `config/export/sval_keycodes.yml` and
+//! `config/export/xkb_keycodes.yml`). Exporting a layout with a key missing from the keycode file
+//! is an error, unless the key is fixed.
+
+pub mod cheat_sheet;
+pub mod image;
+pub mod kanata;
+pub mod keymap_drawer;
+pub mod xkb;
+
+use keyboard_layout::{key::MatrixPosition, layout::Layout};
+
+use anyhow::Result;
+use itertools::Itertools;
+use serde::Deserialize;
+use std::fs::File;
+
+/// The XKB key names of matrix positions.
+#[derive(Deserialize, Clone, Debug)]
+pub struct KeycodeMap {
+    matrix_positions: Vec<Vec<MatrixPosition>>,
+    keycodes: Vec<Vec<String>>,
+}
+
+impl KeycodeMap {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let map: Self = serde_yaml::from_reader(f)?;
+        let n_positions: usize = map.matrix_positions.iter().map(|row| row.len()).sum();
+        let n_keycodes: usize = map.keycodes.iter().map(|row| row.len()).sum();
+        if n_positions != n_keycodes {
+            anyhow::bail!(
+                "Number of matrix positions ({}) and keycodes ({}) differ",
+                n_positions,
+                n_keycodes
+            );
+        }
+        if let Some(keycode) = map.keycodes.iter().flatten().duplicates().next() {
+            anyhow::bail!(
+                "Keycode {} is assigned to several matrix positions",
+                keycode
+            );
+        }
+
+        Ok(map)
+    }
+
+    pub fn get(&self, pos: &MatrixPosition) -> Option<&str> {
+        self.matrix_positions
+            .iter()
+            .flatten()
+            .zip(self.keycodes.iter().flatten())
+            .find(|(p, _)| *p == pos)
+            .map(|(_, keycode)| keycode.as_str())
+    }
+
+    /// The XKB key name of a matrix position of the layout that is to be exported. Positions
+    /// without a key name are an error, unless all their keys are fixed (then they are skipped
+    /// with a warning).
+    pub fn exported_keycode(&self, layout: &Layout, pos: &MatrixPosition) -> Result<Option<&str>> {
+        if let Some(keycode) = self.get(pos) {
+            return Ok(Some(keycode));
+        }
+
+        let keys: Vec<_> = layout
+            .layerkeys
+            .iter()
+            .filter(|k| k.key.matrix_position == *pos)
+            .collect();
+        let symbols: String = keys.iter().map(|k| k.symbol).collect();
+        if keys.iter().all(|k| k.is_fixed) {
+            log::warn!(
+                "Skipping fixed key at matrix position {:?} ('{}') without an XKB key name",
+                pos,
+                symbols
+            );
+            return Ok(None);
+        }
+
+        anyhow::bail!(
+            "No XKB key name for matrix position {:?} ('{}') in the keycode file",
+            pos,
+            symbols
+        )
+    }
+}
+
+/// If the symbol is a mere placeholder in the layout configs (for modifiers and unused keys, e.g.
+/// arrows, chess pieces, and boxes) that does not produce any output.
+pub fn is_placeholder(c: char) -> bool {
+    matches!(c, '\u{2190}'..='\u{21FF}' | '\u{2654}'..='\u{265F}' | '□') && !matches!(c, '←' | '⇥')
+        || c.is_control() && c != '\n'
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::KeycodeMap;
+    use crate::common;
+    use keyboard_layout::layout::Layout;
+
+    pub const SVAL_LAYOUT: &str = ",qczb□-i.y□'e□o□□a□umkhxlgjt□dw□n□pv□s□fr";
+
+    pub fn config_path(filename: &str) -> String {
+        format!("{}/../config/{}", env!("CARGO_MANIFEST_DIR"), filename)
+    }
+
+    pub fn sval_layout(layout_str: &str) -> Layout {
+        common::init_layout_generator(&config_path("keyboard/sval.yml"), false, &[])
+            .unwrap()
+            .generate(layout_str)
+            .unwrap()
+    }
+
+    pub fn keycodes(filename: &str) -> KeycodeMap {
+        KeycodeMap::from_yaml(&config_path(filename)).unwrap()
+    }
+}
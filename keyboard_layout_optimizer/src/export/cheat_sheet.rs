@@ -0,0 +1,126 @@
+//! Rendering of a layout as a printable one-page cheat sheet (e.g. for taping next to the monitor
+//! while learning a new layout).
+//!
+//! All layers are drawn as separate boards in a grid that fills an A4 page in landscape
+//! orientation. The keys are colored by the finger that presses them, such that the clusters of
+//! a Svalboard are easy to tell apart.
+
+use super::image::{self, board, board_size, drawn_keys, escape, layer_access};
+use keyboard_layout::{key::Finger, layout::Layout};
+
+use anyhow::Result;
+
+/// Size of the page (in mm)
+const PAGE_SIZE: (f64, f64) = (297.0, 210.0);
+const MARGIN: f64 = 20.0;
+/// Gap between the boards of the grid (in px)
+const BOARD_GAP: f64 = 32.0;
+const CAPTION_HEIGHT: f64 = 24.0;
+
+const FINGERS: [Finger; 5] = [
+    Finger::Pinky,
+    Finger::Ring,
+    Finger::Middle,
+    Finger::Index,
+    Finger::Thumb,
+];
+
+/// Colors of the keys pressed by each finger
+fn finger_color(finger: Finger) -> &'static str {
+    match finger {
+        Finger::Pinky => "#f6c9c9",
+        Finger::Ring => "#f7e0bd",
+        Finger::Middle => "#f3f0b3",
+        Finger::Index => "#cdeec3",
+        Finger::Thumb => "#c8dcf4",
+    }
+}
+
+/// The number of columns of the grid of boards that makes them largest on the page.
+fn n_columns(n_boards: usize, board_width: f64, board_height: f64) -> usize {
+    let scale = |columns: usize| {
+        let rows = (n_boards + columns - 1) / columns;
+        let width = columns as f64 * (board_width + BOARD_GAP);
+        let height = rows as f64 * (board_height + CAPTION_HEIGHT + BOARD_GAP);
+
+        (PAGE_SIZE.0 / width).min(PAGE_SIZE.1 / height)
+    };
+
+    (1..=n_boards.max(1))
+        .max_by(|a, b| scale(*a).partial_cmp(&scale(*b)).unwrap())
+        .unwrap_or(1)
+}
+
+/// An SVG picture of the cheat sheet with all layers of the layout, sized as an A4 page.
+pub fn svg(layout: &Layout, title: &str) -> String {
+    let n_layers = layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0) as usize + 1;
+    let keys = drawn_keys(layout, n_layers);
+    let (board_width, board_height) = board_size(&keys);
+
+    let columns = n_columns(n_layers, board_width, board_height);
+    let rows = (n_layers + columns - 1) / columns;
+
+    let mut elements = vec![format!(
+        r#"<text x="{:.1}" y="{:.1}" font-size="22" font-weight="bold">{}</text>"#,
+        MARGIN,
+        MARGIN + 18.0,
+        escape(title)
+    )];
+    let top = MARGIN + CAPTION_HEIGHT + 16.0;
+    for layer in 0..n_layers {
+        let left = MARGIN + (layer % columns) as f64 * (board_width + BOARD_GAP);
+        let caption_top =
+            top + (layer / columns) as f64 * (board_height + CAPTION_HEIGHT + BOARD_GAP);
+        elements.push(format!(
+            r#"<text x="{:.1}" y="{:.1}" font-size="16">Layer {} ({})</text>"#,
+            left,
+            caption_top + 16.0,
+            layer + 1,
+            escape(&layer_access(layout, layer as u8))
+        ));
+        elements.extend(board(
+            &keys,
+            &[layer as u8],
+            (left, caption_top + CAPTION_HEIGHT),
+            Some(&finger_color),
+        ));
+    }
+
+    // key of the finger colors
+    let bottom = top + rows as f64 * (board_height + CAPTION_HEIGHT + BOARD_GAP);
+    for (i, finger) in FINGERS.iter().enumerate() {
+        let left = MARGIN + i as f64 * 120.0;
+        elements.push(format!(
+            r##"<rect x="{:.1}" y="{:.1}" width="18" height="18" rx="4" fill="{}" stroke="#404040"/>"##,
+            left,
+            bottom,
+            finger_color(*finger)
+        ));
+        elements.push(format!(
+            r#"<text x="{:.1}" y="{:.1}" font-size="16">{:?}</text>"#,
+            left + 26.0,
+            bottom + 15.0,
+            finger
+        ));
+    }
+
+    let width = 2.0 * MARGIN + columns as f64 * (board_width + BOARD_GAP) - BOARD_GAP;
+    let height = bottom + 18.0 + MARGIN;
+
+    // the content is scaled to fit the page and centered on it
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}mm\" height=\"{}mm\" \
+         viewBox=\"0 0 {:.0} {:.0}\" font-family=\"sans-serif\">\n\
+         <rect x=\"-50%\" y=\"-50%\" width=\"200%\" height=\"200%\" fill=\"white\"/>\n{}\n</svg>\n",
+        PAGE_SIZE.0,
+        PAGE_SIZE.1,
+        width,
+        height,
+        elements.join("\n")
+    )
+}
+
+/// Write the cheat sheet as a one-page PDF file.
+pub fn pdf(layout: &Layout, title: &str, filename: &str) -> Result<()> {
+    image::pdf(&svg(layout, title), filename)
+}
@@ -0,0 +1,400 @@
+//! Rendering of a layout as a picture of the keyboard (e.g. for printing as a learning
+//! reference).
+//!
+//! Keys are drawn at their positions from the keyboard config and framed by the cluster of their
+//! finger, so that a Svalboard cluster shows up with its five directional keys. Blocked keys are
+//! drawn dashed and without legends. The legends of
+//! several layers are either stacked on each key (the base layer in the center, the others in the
+//! corners) or the layers are drawn as separate boards side by side.
+
+use super::is_placeholder;
+use keyboard_layout::{
+    key::{Finger, Hand, MatrixPosition, Position},
+    layout::{LayerModifiers, Layout},
+};
+
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    io::Write,
+    process::{Command, Stdio},
+};
+use svg2pdf::usvg::{self, fontdb, PostProcessingSteps, TreeParsing, TreePostProc};
+
+/// Size of a key position (in px)
+const UNIT: f64 = 56.0;
+const MARGIN: f64 = 20.0;
+/// Gap between boards drawn side by side (in px)
+const BOARD_GAP: f64 = 40.0;
+const CAPTION_HEIGHT: f64 = 28.0;
+
+/// Colors of the legends of the drawn layers (in the order of the drawn layers)
+const COLORS: [&str; 7] = [
+    "#000000", "#1f77b4", "#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#8c564b",
+];
+
+/// Positions of the legends of the additional layers on a stacked key (relative to the key size)
+const STACKED_POSITIONS: [(f64, f64); 6] = [
+    (0.27, 0.34),
+    (0.73, 0.34),
+    (0.27, 0.84),
+    (0.73, 0.84),
+    (0.5, 0.34),
+    (0.5, 0.84),
+];
+
+/// How the legends of several layers are arranged.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Arrangement {
+    /// All legends on one board
+    Stacked,
+    /// One board per layer
+    SideBySide,
+}
+
+pub(super) struct DrawnKey {
+    pub(super) matrix_position: MatrixPosition,
+    pub(super) hand: Hand,
+    pub(super) finger: Finger,
+    /// Position in units of the key pitch
+    pub(super) position: Position,
+    /// Legend of each layer of the layout
+    pub(super) legends: Vec<Option<String>>,
+    pub(super) is_modifier: bool,
+    /// Whether no symbol may be placed on the key (see `blocked_positions`)
+    pub(super) is_blocked: bool,
+}
+
+pub(super) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The legend of a symbol (if it is drawn).
+fn legend(c: char, is_modifier: bool) -> Option<String> {
+    match c {
+        ' ' => Some("␣".to_string()),
+        '\n' => Some("⏎".to_string()),
+        c if is_placeholder(c) && !is_modifier => None,
+        c => Some(c.to_string()),
+    }
+}
+
+/// A description of how the layer is accessed, e.g. "hold ⇧".
+pub(super) fn layer_access(layout: &Layout, layer: u8) -> String {
+    let modifiers = layout
+        .layerkeys
+        .iter()
+        .filter(|k| k.layer == layer)
+        .map(|k| &k.modifiers)
+        .find(|m| !m.layerkey_indices().is_empty() || **m == LayerModifiers::LongPress);
+    let (kind, indices) = match modifiers {
+        Some(LayerModifiers::Hold(indices)) => ("hold", indices.as_slice()),
+        Some(LayerModifiers::OneShot(indices)) => ("tap", indices.as_slice()),
+        Some(LayerModifiers::Toggle(indices)) => ("toggle", indices.as_slice()),
+        Some(LayerModifiers::CapsWord(indices)) => ("caps word", indices.as_slice()),
+        Some(LayerModifiers::Chord(indices)) => ("chord", indices.as_slice()),
+        Some(LayerModifiers::LongPress) => return "long press".to_string(),
+        _ => return "base".to_string(),
+    };
+    let keys: Vec<String> = indices
+        .iter()
+        .map(|i| layout.get_layerkey(i).symbol.to_string())
+        .collect();
+
+    format!("{} {}", kind, keys.join(" + "))
+}
+
+/// The keys of the layout with their positions in units of the key pitch (the smallest distance
+/// between two keys), starting at zero.
+pub(super) fn drawn_keys(layout: &Layout, n_layers: usize) -> Vec<DrawnKey> {
+    let mut keys: AHashMap<MatrixPosition, DrawnKey> = AHashMap::default();
+    for k in layout.layerkeys.iter() {
+        let key = keys
+            .entry(k.key.matrix_position)
+            .or_insert_with(|| DrawnKey {
+                matrix_position: k.key.matrix_position,
+                hand: k.key.hand,
+                finger: k.key.finger,
+                position: k.key.position,
+                legends: vec![None; n_layers],
+                is_modifier: false,
+                is_blocked: false,
+            });
+        key.legends[k.layer as usize] = legend(k.symbol, k.is_modifier.is_some());
+        key.is_modifier |= k.is_modifier.is_some();
+    }
+    // blocked keys generate no symbols, but are drawn nonetheless
+    for k in layout.keyboard.keys.iter().filter(|k| k.blocked) {
+        keys.entry(k.matrix_position).or_insert_with(|| DrawnKey {
+            matrix_position: k.matrix_position,
+            hand: k.hand,
+            finger: k.finger,
+            position: k.position,
+            legends: vec![None; n_layers],
+            is_modifier: false,
+            is_blocked: true,
+        });
+    }
+
+    let mut keys: Vec<DrawnKey> = keys.into_iter().map(|(_, key)| key).collect();
+
+    let pitch = keys
+        .iter()
+        .enumerate()
+        .flat_map(|(i, k1)| {
+            keys[i + 1..]
+                .iter()
+                .map(move |k2| k1.position.distance(&k2.position))
+        })
+        .filter(|d| *d > 0.0)
+        .fold(f64::INFINITY, f64::min);
+    let pitch = if pitch.is_finite() { pitch } else { 1.0 };
+    let min = keys.iter().fold((f64::INFINITY, f64::INFINITY), |m, k| {
+        (m.0.min(k.position.0), m.1.min(k.position.1))
+    });
+    for key in keys.iter_mut() {
+        key.position = Position(
+            (key.position.0 - min.0) / pitch,
+            (key.position.1 - min.1) / pitch,
+        );
+    }
+    keys.sort_by(|a, b| {
+        (a.position.1, a.position.0)
+            .partial_cmp(&(b.position.1, b.position.0))
+            .unwrap()
+    });
+
+    keys
+}
+
+/// Width and height of a board with the given keys (in px).
+pub(super) fn board_size(keys: &[DrawnKey]) -> (f64, f64) {
+    let max = keys
+        .iter()
+        .fold((f64::NEG_INFINITY, f64::NEG_INFINITY), |m, k| {
+            (m.0.max(k.position.0), m.1.max(k.position.1))
+        });
+
+    ((max.0 + 1.0) * UNIT, (max.1 + 1.0) * UNIT)
+}
+
+/// Draw one board with the legends of the given layers at the given offset. The keys are filled
+/// with the colors of their fingers given by `finger_fill` (if any).
+pub(super) fn board(
+    keys: &[DrawnKey],
+    layers: &[u8],
+    origin: (f64, f64),
+    finger_fill: Option<&dyn Fn(Finger) -> &'static str>,
+) -> Vec<String> {
+    let x = |p: &Position| origin.0 + p.0 * UNIT;
+    let y = |p: &Position| origin.1 + p.1 * UNIT;
+    let mut elements = Vec::new();
+
+    // cluster frames
+    let mut clusters: AHashMap<(Hand, Finger), (f64, f64, f64, f64)> = AHashMap::default();
+    for key in keys.iter() {
+        let frame = clusters.entry((key.hand, key.finger)).or_insert((
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ));
+        frame.0 = frame.0.min(x(&key.position));
+        frame.1 = frame.1.min(y(&key.position));
+        frame.2 = frame.2.max(x(&key.position) + UNIT);
+        frame.3 = frame.3.max(y(&key.position) + UNIT);
+    }
+    let mut frames: Vec<(f64, f64, f64, f64)> = clusters.into_iter().map(|(_, f)| f).collect();
+    frames.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    // frames of overlapping clusters (e.g. the columns of a staggered keyboard) are confusing
+    let overlaps = |a: &(f64, f64, f64, f64), b: &(f64, f64, f64, f64)| {
+        a != b && a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+    };
+    for (x0, y0, x1, y1) in frames
+        .iter()
+        .filter(|a| !frames.iter().any(|b| overlaps(a, b)))
+    {
+        elements.push(format!(
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="12" fill="#f4f4f4" stroke="#b0b0b0"/>"##,
+            x0 + 2.0,
+            y0 + 2.0,
+            x1 - x0 - 4.0,
+            y1 - y0 - 4.0
+        ));
+    }
+
+    for key in keys.iter() {
+        let (kx, ky) = (x(&key.position), y(&key.position));
+        let fill = match finger_fill {
+            _ if key.is_blocked => "#f4f4f4",
+            _ if key.is_modifier => "#dcdcdc",
+            Some(finger_fill) => finger_fill(key.finger),
+            None => "#ffffff",
+        };
+        let dash = match key.is_blocked {
+            true => r#" stroke-dasharray="4 3""#,
+            false => "",
+        };
+        elements.push(format!(
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="6" fill="{}" stroke="#404040"{}/>"##,
+            kx + 6.0,
+            ky + 6.0,
+            UNIT - 12.0,
+            UNIT - 12.0,
+            fill,
+            dash
+        ));
+
+        for (i, layer) in layers.iter().enumerate() {
+            let legend = match key.legends.get(*layer as usize).cloned().flatten() {
+                Some(legend) => legend,
+                None => continue,
+            };
+            let ((rx, ry), size) = match i {
+                0 => ((0.5, 0.62), 20.0),
+                i => (STACKED_POSITIONS[(i - 1) % STACKED_POSITIONS.len()], 12.0),
+            };
+            elements.push(format!(
+                r#"<text x="{:.1}" y="{:.1}" font-size="{}" fill="{}" text-anchor="middle">{}</text>"#,
+                kx + rx * UNIT,
+                ky + ry * UNIT,
+                size,
+                COLORS[*layer as usize % COLORS.len()],
+                escape(&legend)
+            ));
+        }
+    }
+
+    elements
+}
+
+/// An SVG picture of the keyboard with the legends of the given layers.
+pub fn svg(layout: &Layout, layers: &[u8], arrangement: Arrangement, title: &str) -> String {
+    let n_layers = layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0) as usize + 1;
+    let layers: Vec<u8> = layers
+        .iter()
+        .cloned()
+        .filter(|l| (*l as usize) < n_layers)
+        .collect();
+    if arrangement == Arrangement::Stacked && layers.len() > STACKED_POSITIONS.len() + 1 {
+        log::warn!(
+            "More than {} stacked layers overlap, consider drawing them side by side",
+            STACKED_POSITIONS.len() + 1
+        );
+    }
+
+    let keys = drawn_keys(layout, n_layers);
+    let (board_width, board_height) = board_size(&keys);
+
+    let mut elements = vec![format!(
+        r#"<text x="{:.1}" y="{:.1}" font-size="18" font-weight="bold">{}</text>"#,
+        MARGIN,
+        MARGIN + 14.0,
+        escape(title)
+    )];
+    let top = MARGIN + CAPTION_HEIGHT + 8.0;
+    let boards: Vec<Vec<u8>> = match arrangement {
+        Arrangement::Stacked => vec![layers.clone()],
+        Arrangement::SideBySide => layers.iter().map(|l| vec![*l]).collect(),
+    };
+    for (i, board_layers) in boards.iter().enumerate() {
+        let left = MARGIN + i as f64 * (board_width + BOARD_GAP);
+        if arrangement == Arrangement::SideBySide {
+            elements.push(format!(
+                r#"<text x="{:.1}" y="{:.1}" font-size="14">Layer {} ({})</text>"#,
+                left,
+                top - 6.0,
+                board_layers[0] + 1,
+                escape(&layer_access(layout, board_layers[0]))
+            ));
+        }
+        elements.extend(board(&keys, board_layers, (left, top + 8.0), None));
+    }
+
+    // key of the legend colors
+    let bottom = top + 8.0 + board_height + 16.0;
+    if arrangement == Arrangement::Stacked {
+        for (i, layer) in layers.iter().enumerate() {
+            elements.push(format!(
+                r#"<text x="{:.1}" y="{:.1}" font-size="14" fill="{}">Layer {} ({})</text>"#,
+                MARGIN + i as f64 * 180.0,
+                bottom + 14.0,
+                COLORS[*layer as usize % COLORS.len()],
+                layer + 1,
+                escape(&layer_access(layout, *layer))
+            ));
+        }
+    }
+
+    let width = 2.0 * MARGIN + boards.len() as f64 * (board_width + BOARD_GAP) - BOARD_GAP;
+    let height = bottom + CAPTION_HEIGHT + MARGIN;
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+         font-family=\"sans-serif\">\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n{}\n</svg>\n",
+        width,
+        height,
+        elements.join("\n")
+    )
+}
+
+/// Convert an SVG picture to a PNG file with `rsvg-convert` (from librsvg), which needs to be
+/// installed.
+pub fn png(svg: &str, filename: &str) -> Result<()> {
+    let mut child = Command::new("rsvg-convert")
+        .args(["--format", "png", "--output", filename])
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Could not run rsvg-convert")?;
+    child
+        .stdin
+        .take()
+        .expect("stdin of rsvg-convert is piped")
+        .write_all(svg.as_bytes())?;
+
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("rsvg-convert failed with {}", status);
+    }
+
+    Ok(())
+}
+
+/// Convert an SVG picture to a PDF file (with `svg2pdf`, the text is drawn with the system fonts).
+/// The page has the size of the picture.
+pub fn pdf(svg: &str, filename: &str) -> Result<()> {
+    let mut tree = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .context("Could not parse the SVG picture")?;
+
+    let mut fonts = fontdb::Database::new();
+    fonts.load_system_fonts();
+    let sans_serif = fontdb::Query {
+        families: &[fontdb::Family::SansSerif],
+        ..Default::default()
+    };
+    if fonts.query(&sans_serif).is_none() {
+        // the default sans-serif family of fontdb (Arial) is not installed everywhere
+        let family = fonts
+            .faces()
+            .flat_map(|face| face.families.iter().map(|(name, _)| name))
+            .min_by_key(|name| (!name.contains("Sans"), name.contains("Mono")))
+            .cloned();
+        match family {
+            Some(family) => fonts.set_sans_serif_family(family),
+            None => log::warn!("No system fonts found, the PDF will not contain any legends"),
+        }
+    }
+    tree.postprocess(PostProcessingSteps::default(), &fonts);
+
+    // usvg measures the picture in px at 96 dpi
+    let options = svg2pdf::Options {
+        dpi: 96.0,
+        ..Default::default()
+    };
+    fs::write(filename, svg2pdf::convert_tree(&tree, options))
+        .with_context(|| format!("Could not write {}", filename))
+}
@@ -0,0 +1,404 @@
+//! Export of a layout with all its layers as Kanata or KMonad configuration (for trying out a
+//! layout with software remapping before committing it to firmware).
+//!
+//! The remapped keys are the keys of a regular (US) keyboard at the positions given by their
+//! XKB key names. Layers accessed by holding a modifier key become `layer-while-held` layers
+//! (combined with the key's symbol as a tap-hold if it has one), one-shot layers become one-shot
+//! layers, and long-press layers become tap-holds of the symbols of the base and the long-press layer.
+//! Toggled layers are entered with `layer-switch` and left by tapping the toggle key again.
+//! Caps Word layers holding the capitals of the base layer use the `caps-word` action of Kanata
+//! (which shifts letters until the end of the word instead of switching to a layer). Other Caps
+//! Word layers (and all of them for KMonad) become one-shot layers, with a warning. Keys without
+//! a source key (that are not fixed) are an error, as a partial configuration would silently drop
+//! them.
+
+use super::{is_placeholder, KeycodeMap};
+use keyboard_layout::{
+    key::MatrixPosition,
+    layout::{LayerModifierType, LayerModifiers, Layout},
+};
+
+use ahash::AHashMap;
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+/// Timeout (in ms) of tap-holds and one-shot modifiers
+const TAP_TIMEOUT: u32 = 200;
+const ONE_SHOT_TIMEOUT: u32 = 500;
+/// Timeout (in ms) after which Caps Word ends without further typing
+const CAPS_WORD_TIMEOUT: u32 = 2000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dialect {
+    Kanata,
+    KMonad,
+}
+
+/// The name of the key of a US keyboard with the given XKB key name.
+fn source_key(keycode: &str, dialect: Dialect) -> Option<&'static str> {
+    const ROWS: [(&str, &[&str]); 4] = [
+        (
+            "AE",
+            &["1", "2", "3", "4", "5", "6", "7", "8", "9", "0", "-", "="],
+        ),
+        (
+            "AD",
+            &["q", "w", "e", "r", "t", "y", "u", "i", "o", "p", "[", "]"],
+        ),
+        (
+            "AC",
+            &["a", "s", "d", "f", "g", "h", "j", "k", "l", ";", "'"],
+        ),
+        ("AB", &["z", "x", "c", "v", "b", "n", "m", ",", ".", "/"]),
+    ];
+    for (prefix, keys) in ROWS.iter() {
+        if let Some(i) = keycode
+            .strip_prefix(prefix)
+            .and_then(|i| i.parse::<usize>().ok())
+        {
+            return keys.get(i.checked_sub(1)?).cloned();
+        }
+    }
+
+    let key = match keycode {
+        "TLDE" => "grv",
+        "BKSP" => "bspc",
+        "TAB" => "tab",
+        "BKSL" => backslash(dialect),
+        "CAPS" => "caps",
+        "RTRN" => "ret",
+        "LFSH" => "lsft",
+        "LSGT" => "102d",
+        "RTSH" => "rsft",
+        "LCTL" => "lctl",
+        "LWIN" => "lmet",
+        "LALT" => "lalt",
+        "SPCE" => "spc",
+        "RALT" => "ralt",
+        "RWIN" => "rmet",
+        "MENU" if dialect == Dialect::Kanata => "menu",
+        "MENU" => "cmp",
+        "RCTL" => "rctl",
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+fn backslash(dialect: Dialect) -> &'static str {
+    match dialect {
+        Dialect::Kanata => "\\",
+        Dialect::KMonad => "\\\\",
+    }
+}
+
+/// The action producing the symbol (if it can be produced).
+fn symbol_action(c: char, dialect: Dialect) -> Option<String> {
+    const UNSHIFTED: &str = "`1234567890-=[]\\;',./";
+    const SHIFTED: &str = "~!@#$%^&*()_+{}|:\"<>?";
+    let key = |c: char| match c {
+        '`' => "grv".to_string(),
+        '\\' => backslash(dialect).to_string(),
+        c => c.to_string(),
+    };
+
+    let action = match c {
+        ' ' => "spc".to_string(),
+        '\n' => "ret".to_string(),
+        '⇥' => "tab".to_string(),
+        '←' => "bspc".to_string(),
+        c if c.is_ascii_lowercase() || UNSHIFTED.contains(c) => key(c),
+        c if c.is_ascii_uppercase() => format!("S-{}", c.to_ascii_lowercase()),
+        c if SHIFTED.contains(c) => {
+            let i = SHIFTED.chars().position(|s| s == c).unwrap();
+            format!("S-{}", key(UNSHIFTED.chars().nth(i).unwrap()))
+        }
+        c if is_placeholder(c) => return None,
+        c => match dialect {
+            Dialect::Kanata => format!("(unicode {})", c),
+            Dialect::KMonad => {
+                log::warn!("Symbol '{}' can not be produced with KMonad", c);
+                return None;
+            }
+        },
+    };
+
+    Some(action)
+}
+
+fn layer_name(layer: u8) -> String {
+    match layer {
+        0 => "base".to_string(),
+        _ => format!("layer{}", layer + 1),
+    }
+}
+
+fn hold_action(layer: u8, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::Kanata => format!("(layer-while-held {})", layer_name(layer)),
+        Dialect::KMonad => format!("(layer-toggle {})", layer_name(layer)),
+    }
+}
+
+fn one_shot_action(layer: u8, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::Kanata => format!(
+            "(one-shot {} {})",
+            ONE_SHOT_TIMEOUT,
+            hold_action(layer, dialect)
+        ),
+        Dialect::KMonad => format!(
+            "(sticky-key {} {})",
+            ONE_SHOT_TIMEOUT,
+            hold_action(layer, dialect)
+        ),
+    }
+}
+
+/// Whether the letters of the layer are the capitals of the base layer's letters (at the same
+/// positions).
+fn shifts_letters(layout: &Layout, layer: u8) -> bool {
+    let mut letters = layout
+        .layerkeys
+        .iter()
+        .filter(|k| k.layer == layer && k.symbol.is_alphabetic())
+        .peekable();
+    letters.peek().is_some()
+        && letters.all(|k| {
+            layout.layerkeys.iter().any(|base| {
+                base.layer == 0
+                    && base.key.matrix_position == k.key.matrix_position
+                    && base.symbol.to_uppercase().eq(k.symbol.to_uppercase())
+                    && base.symbol != k.symbol
+            })
+        })
+}
+
+fn caps_word_action(layout: &Layout, layer: u8, dialect: Dialect) -> String {
+    if dialect == Dialect::Kanata && shifts_letters(layout, layer) {
+        return format!("(caps-word {})", CAPS_WORD_TIMEOUT);
+    }
+
+    log::warn!(
+        "Caps Word layer {} is exported as a one-shot layer (active for a single symbol only)",
+        layer + 1
+    );
+    one_shot_action(layer, dialect)
+}
+
+fn toggle_action(layer: u8) -> String {
+    format!("(layer-switch {})", layer_name(layer))
+}
+
+fn tap_hold(tap: &str, hold: &str, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::Kanata => format!(
+            "(tap-hold {} {} {} {})",
+            TAP_TIMEOUT, TAP_TIMEOUT, tap, hold
+        ),
+        Dialect::KMonad => format!("(tap-hold {} {} {})", TAP_TIMEOUT, tap, hold),
+    }
+}
+
+/// Render the layout as configuration of the given dialect.
+pub fn config(layout: &Layout, keycodes: &KeycodeMap, dialect: Dialect) -> Result<String> {
+    // the action of each key on each layer
+    let n_layers = layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0) as usize + 1;
+    let mut actions: AHashMap<MatrixPosition, Vec<Option<String>>> = AHashMap::default();
+    let mut long_press_layers = vec![false; n_layers];
+    let mut layer_modifiers: AHashMap<MatrixPosition, (u8, LayerModifierType)> =
+        AHashMap::default();
+    let mut combined_modifier_layers: BTreeSet<u8> = BTreeSet::new();
+    for k in layout.layerkeys.iter().filter(|k| k.is_modifier.is_none()) {
+        actions
+            .entry(k.key.matrix_position)
+            .or_insert_with(|| vec![None; n_layers])[k.layer as usize] =
+            symbol_action(k.symbol, dialect);
+
+        match &k.modifiers {
+            LayerModifiers::LongPress => long_press_layers[k.layer as usize] = true,
+            LayerModifiers::Hold(mods)
+            | LayerModifiers::OneShot(mods)
+            | LayerModifiers::Toggle(mods)
+            | LayerModifiers::CapsWord(mods)
+                if mods.len() == 1 =>
+            {
+                let modifier = layout.get_layerkey(&mods[0]);
+                layer_modifiers.insert(
+                    modifier.key.matrix_position,
+                    (k.layer, modifier.is_modifier),
+                );
+            }
+            LayerModifiers::Hold(mods)
+            | LayerModifiers::OneShot(mods)
+            | LayerModifiers::Toggle(mods)
+            | LayerModifiers::CapsWord(mods)
+            | LayerModifiers::Chord(mods)
+                if mods.len() > 1 =>
+            {
+                combined_modifier_layers.insert(k.layer);
+            }
+            _ => {}
+        }
+    }
+    for layer in combined_modifier_layers {
+        log::warn!(
+            "Layer {} requires a combination of modifiers, which is not exported",
+            layer + 1
+        );
+    }
+
+    // remapped keys (with an action on any layer) with their source keys in the order of the
+    // keyboard's rows
+    let mut sources: Vec<(MatrixPosition, &'static str)> = Vec::new();
+    for pos in actions
+        .iter()
+        .filter(|(_, key_actions)| key_actions.iter().any(|a| a.is_some()))
+        .map(|(pos, _)| pos)
+        .chain(layer_modifiers.keys())
+    {
+        if let Some(keycode) = keycodes.exported_keycode(layout, pos)? {
+            let source = source_key(keycode, dialect).ok_or_else(|| {
+                anyhow::anyhow!("XKB key name {} is not a key of a US keyboard", keycode)
+            })?;
+            sources.push((*pos, source));
+        }
+    }
+    sources.sort_by_key(|(pos, _)| (pos.1, pos.0));
+    sources.dedup();
+
+    let exported_layers: Vec<u8> = (0..n_layers as u8)
+        .filter(|l| *l == 0 || !long_press_layers[*l as usize])
+        .collect();
+
+    // entries of the defsrc and each exported layer, split into the keyboard's rows
+    let mut blocks: Vec<Vec<Vec<String>>> = vec![Vec::new(); exported_layers.len() + 1];
+    let mut last_row = None;
+    for (pos, source) in sources.iter() {
+        if last_row != Some(pos.1) {
+            blocks.iter_mut().for_each(|b| b.push(Vec::new()));
+            last_row = Some(pos.1);
+        }
+        let key_actions = actions.get(pos).cloned().unwrap_or_default();
+        let action = |layer: usize| key_actions.get(layer).cloned().flatten();
+
+        let mut entries = vec![source.to_string()];
+        for layer in exported_layers.iter() {
+            let entry = match (*layer, layer_modifiers.get(pos)) {
+                (0, Some((modifier_layer, LayerModifierType::OneShot))) => {
+                    one_shot_action(*modifier_layer, dialect)
+                }
+                (0, Some((modifier_layer, LayerModifierType::CapsWord))) => {
+                    caps_word_action(layout, *modifier_layer, dialect)
+                }
+                (0, Some((modifier_layer, LayerModifierType::Toggle))) => {
+                    toggle_action(*modifier_layer)
+                }
+                (0, Some((modifier_layer, _))) => {
+                    let hold = hold_action(*modifier_layer, dialect);
+                    match action(0) {
+                        Some(tap) => tap_hold(&tap, &hold, dialect),
+                        None => hold,
+                    }
+                }
+                (0, None) => {
+                    let base = action(0).unwrap_or_else(|| "XX".to_string());
+                    // symbols of long-press layers are produced by holding the key
+                    match (1..n_layers)
+                        .filter(|l| long_press_layers[*l])
+                        .find_map(action)
+                    {
+                        Some(long_press) => tap_hold(&base, &long_press, dialect),
+                        None => base,
+                    }
+                }
+                // tapping the toggle key again returns to the base layer
+                (layer, Some((modifier_layer, LayerModifierType::Toggle)))
+                    if layer == *modifier_layer =>
+                {
+                    toggle_action(0)
+                }
+                (layer, _) => action(layer as usize).unwrap_or_else(|| "_".to_string()),
+            };
+            entries.push(entry);
+        }
+
+        for (block, entry) in blocks.iter_mut().zip(entries) {
+            block.last_mut().unwrap().push(entry);
+        }
+    }
+    let render = |rows: &[Vec<String>]| -> String {
+        rows.iter()
+            .map(|row| format!("  {}", row.join(" ")))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    let mut config = vec![format!(";; Layout {}", layout.as_text())];
+    match dialect {
+        Dialect::Kanata => config.push("(defcfg\n  process-unmapped-keys no\n)".to_string()),
+        Dialect::KMonad => config.push(
+            ";; add a (defcfg ...) block with the input and output devices of your system"
+                .to_string(),
+        ),
+    }
+    config.push(format!("(defsrc\n{}\n)", render(&blocks[0])));
+    for (layer, rows) in exported_layers.iter().zip(blocks.iter().skip(1)) {
+        config.push(format!(
+            "(deflayer {}\n{}\n)",
+            layer_name(*layer),
+            render(rows)
+        ));
+    }
+
+    Ok(format!("{}\n", config.join("\n\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_utils::{keycodes, sval_layout, SVAL_LAYOUT};
+
+    fn layer_entries(config: &str, block: &str) -> Vec<String> {
+        let start = config.find(block).unwrap() + block.len();
+        let end = start + config[start..].find("\n)").unwrap();
+        config[start..end]
+            .split_whitespace()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn exports_all_keys_of_the_sval() {
+        let layout = sval_layout(SVAL_LAYOUT);
+        let keycodes = keycodes("export/sval_keycodes.yml");
+        let n_symbols = SVAL_LAYOUT.chars().filter(|c| *c != '□').count();
+
+        for dialect in [Dialect::Kanata, Dialect::KMonad] {
+            let exported = config(&layout, &keycodes, dialect).unwrap();
+            let sources = layer_entries(&exported, "(defsrc");
+            let base = layer_entries(&exported, "(deflayer base");
+
+            // all symbols and the fixed space
+            assert_eq!(sources.len(), n_symbols + 1);
+            assert_eq!(base.len(), sources.len());
+            let binding = |source: &str| {
+                let i = sources.iter().position(|s| s == source).unwrap();
+                base[i].clone()
+            };
+            assert_eq!(binding("a"), "c");
+            assert_eq!(binding("f"), "a");
+            assert_eq!(binding("spc"), "spc");
+            assert!(!sources.contains(&"ret".to_string()));
+        }
+    }
+
+    #[test]
+    fn unmapped_keys_are_an_error() {
+        let layout = sval_layout(SVAL_LAYOUT);
+        let keycodes = keycodes("export/xkb_keycodes.yml");
+
+        assert!(config(&layout, &keycodes, Dialect::Kanata).is_err());
+        assert!(config(&layout, &keycodes, Dialect::KMonad).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Export of a layout with all its layers in the YAML keymap format of
+//! [keymap-drawer](https://github.com/caksoylar/keymap-drawer), which renders keymaps as SVG and
+//! is used by shared keymap databases.
+//!
+//! keymap-drawer needs the physical layout of the keyboard. It is either taken from a QMK
+//! keyboard (whose key order must match the order of the keys here) or from a QMK `info.json`
+//! file with the key positions of the keyboard config, see [`info_json`]. Keys are ordered by
+//! rows from top to bottom and from left to right within each row. The keys holding a layer are
+//! marked as held in that layer, blocked keys as ghost keys.
+
+use super::image::{drawn_keys, DrawnKey};
+use keyboard_layout::layout::Layout;
+
+use ahash::AHashSet;
+use serde_json::json;
+use serde_yaml::{Mapping, Value};
+
+/// Name of the layout in the generated `info.json`
+const LAYOUT_NAME: &str = "LAYOUT";
+
+/// Source of the physical layout for keymap-drawer
+#[derive(Clone, Debug)]
+pub enum PhysicalLayout {
+    /// Name of a QMK keyboard
+    QmkKeyboard(String),
+    /// Path of a QMK `info.json` file (e.g. generated by [`info_json`])
+    QmkInfoJson(String),
+}
+
+fn n_layers(layout: &Layout) -> usize {
+    layout.layerkeys.iter().map(|k| k.layer).max().unwrap_or(0) as usize + 1
+}
+
+fn layer_name(layer: usize) -> String {
+    match layer {
+        0 => "Base".to_string(),
+        layer => format!("Layer {}", layer + 1),
+    }
+}
+
+/// A QMK `info.json` with the key positions of the layout's keyboard (in units of the key pitch).
+pub fn info_json(layout: &Layout) -> String {
+    let keys: Vec<serde_json::Value> = drawn_keys(layout, n_layers(layout))
+        .iter()
+        .map(|key| {
+            json!({
+                "x": (key.position.0 * 100.0).round() / 100.0,
+                "y": (key.position.1 * 100.0).round() / 100.0,
+            })
+        })
+        .collect();
+
+    let info = json!({
+        "keyboard_name": "Optimized layout",
+        "layouts": { LAYOUT_NAME: { "layout": keys } },
+    });
+
+    serde_json::to_string_pretty(&info).unwrap()
+}
+
+/// The entry of a key in a layer of the keymap.
+fn key_entry(key: &DrawnKey, layer: usize, held: bool) -> Value {
+    let legend = key.legends[layer].clone().unwrap_or_default();
+    let key_type = match (key.is_blocked, held) {
+        (true, _) => "ghost",
+        (false, true) => "held",
+        (false, false) => return Value::String(legend),
+    };
+
+    let mut entry = Mapping::new();
+    entry.insert("t".into(), legend.into());
+    entry.insert("type".into(), key_type.into());
+
+    Value::Mapping(entry)
+}
+
+/// The keymap-drawer YAML of the layout.
+pub fn keymap(layout: &Layout, physical_layout: &PhysicalLayout) -> String {
+    let n_layers = n_layers(layout);
+    let keys = drawn_keys(layout, n_layers);
+
+    let mut layout_entry = Mapping::new();
+    match physical_layout {
+        PhysicalLayout::QmkKeyboard(keyboard) => {
+            layout_entry.insert("qmk_keyboard".into(), keyboard.as_str().into());
+        }
+        PhysicalLayout::QmkInfoJson(path) => {
+            layout_entry.insert("qmk_info_json".into(), path.as_str().into());
+            layout_entry.insert("layout_name".into(), LAYOUT_NAME.into());
+        }
+    }
+
+    let mut layers = Mapping::new();
+    for layer in 0..n_layers {
+        let held: AHashSet<_> = layout
+            .layerkeys
+            .iter()
+            .filter(|k| k.layer as usize == layer)
+            .flat_map(|k| k.modifiers.held().unwrap_or(&[]))
+            .map(|i| layout.get_layerkey(i).key.matrix_position)
+            .collect();
+
+        let entries: Vec<Value> = keys
+            .iter()
+            .map(|key| key_entry(key, layer, held.contains(&key.matrix_position)))
+            .collect();
+        layers.insert(layer_name(layer).into(), Value::Sequence(entries));
+    }
+
+    let mut keymap = Mapping::new();
+    keymap.insert("layout".into(), Value::Mapping(layout_entry));
+    keymap.insert("layers".into(), Value::Mapping(layers));
+
+    serde_yaml::to_string(&keymap).unwrap()
+}
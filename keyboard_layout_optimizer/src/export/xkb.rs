@@ -0,0 +1,198 @@
+//! Export of the base and shift layers of a layout as XKB symbols (for installing the layout on
+//! regular keyboards with X11 or Wayland).
+//!
+//! Keys used as modifiers and placeholder symbols are not exported. Letters without a symbol on
+//! the shift layer get their capital as shift level.
+
+use super::{is_placeholder, KeycodeMap};
+use keyboard_layout::{key::MatrixPosition, layout::Layout};
+
+use ahash::{AHashMap, AHashSet};
+use anyhow::Result;
+
+/// The XKB keysym name of a symbol (if it is not a placeholder).
+pub fn keysym(c: char) -> Option<String> {
+    let name = match c {
+        ' ' => "space",
+        '\n' => "Return",
+        '⇥' => "Tab",
+        '←' => "BackSpace",
+        '!' => "exclam",
+        '"' => "quotedbl",
+        '#' => "numbersign",
+        '$' => "dollar",
+        '%' => "percent",
+        '&' => "ampersand",
+        '\'' => "apostrophe",
+        '(' => "parenleft",
+        ')' => "parenright",
+        '*' => "asterisk",
+        '+' => "plus",
+        ',' => "comma",
+        '-' => "minus",
+        '.' => "period",
+        '/' => "slash",
+        ':' => "colon",
+        ';' => "semicolon",
+        '<' => "less",
+        '=' => "equal",
+        '>' => "greater",
+        '?' => "question",
+        '@' => "at",
+        '[' => "bracketleft",
+        '\\' => "backslash",
+        ']' => "bracketright",
+        '^' => "asciicircum",
+        '_' => "underscore",
+        '`' => "grave",
+        '{' => "braceleft",
+        '|' => "bar",
+        '}' => "braceright",
+        '~' => "asciitilde",
+        c if c.is_ascii_alphanumeric() => return Some(c.to_string()),
+        c if is_placeholder(c) => return None,
+        c => return Some(format!("U{:04X}", c as u32)),
+    };
+
+    Some(name.to_string())
+}
+
+/// The symbol of the shift level of a key without a symbol on the shift layer: the capital of
+/// a letter (if it is a single char).
+fn capital(c: char) -> Option<char> {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) if c.is_lowercase() => Some(u),
+        _ => None,
+    }
+}
+
+/// The keysyms of the base and shift levels of each exported key (sorted by XKB key name).
+fn key_symbols(
+    layout: &Layout,
+    keycodes: &KeycodeMap,
+    shift_layer: u8,
+) -> Result<Vec<(String, [Option<String>; 2])>> {
+    let modifier_positions: AHashSet<MatrixPosition> = layout
+        .layerkeys
+        .iter()
+        .filter(|k| k.is_modifier.is_some())
+        .map(|k| k.key.matrix_position)
+        .collect();
+
+    let mut keys: AHashMap<MatrixPosition, [Option<char>; 2]> = AHashMap::default();
+    for k in layout.layerkeys.iter().filter(|k| k.is_modifier.is_none()) {
+        let level = match k.layer {
+            0 => 0,
+            l if l == shift_layer => 1,
+            _ => continue,
+        };
+        keys.entry(k.key.matrix_position).or_default()[level] = Some(k.symbol);
+    }
+
+    let mut symbols = Vec::new();
+    for (pos, [base, shifted]) in keys {
+        let shifted = shifted.or_else(|| base.and_then(capital));
+        let syms = [base.and_then(keysym), shifted.and_then(keysym)];
+        if modifier_positions.contains(&pos) || syms.iter().all(|s| s.is_none()) {
+            continue;
+        }
+        if let Some(keycode) = keycodes.exported_keycode(layout, &pos)? {
+            symbols.push((keycode.to_string(), syms));
+        }
+    }
+    symbols.sort();
+
+    Ok(symbols)
+}
+
+fn symbols_body(
+    layout: &Layout,
+    keycodes: &KeycodeMap,
+    name: &str,
+    shift_layer: u8,
+    indent: &str,
+) -> Result<String> {
+    let mut lines = vec![
+        format!("{}name[Group1] = \"{}\";", indent, name),
+        String::new(),
+    ];
+    for (keycode, syms) in key_symbols(layout, keycodes, shift_layer)? {
+        let syms: Vec<String> = syms
+            .iter()
+            .map(|s| s.clone().unwrap_or_else(|| "NoSymbol".to_string()))
+            .collect();
+        lines.push(format!(
+            "{}key <{}> {{ [ {} ] }};",
+            indent,
+            keycode,
+            syms.join(", ")
+        ));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+/// An XKB symbols file (e.g. for `/usr/share/X11/xkb/symbols/`) with the base and shift layers
+/// of the layout.
+pub fn symbols(
+    layout: &Layout,
+    keycodes: &KeycodeMap,
+    name: &str,
+    shift_layer: u8,
+) -> Result<String> {
+    Ok(format!(
+        "// Layout {}\n\ndefault partial alphanumeric_keys\nxkb_symbols \"basic\" {{\n{}\n}};\n",
+        layout.as_text(),
+        symbols_body(layout, keycodes, name, shift_layer, "    ")?
+    ))
+}
+
+/// A complete keymap that can be loaded with `xkbcomp keymap.xkb $DISPLAY`. Keys that are not
+/// exported keep their function from the included US layout.
+pub fn keymap(
+    layout: &Layout,
+    keycodes: &KeycodeMap,
+    name: &str,
+    shift_layer: u8,
+) -> Result<String> {
+    Ok(format!(
+        "// Layout {}\n\nxkb_keymap {{\n    \
+         xkb_keycodes {{ include \"evdev+aliases(qwerty)\" }};\n    \
+         xkb_types {{ include \"complete\" }};\n    \
+         xkb_compat {{ include \"complete\" }};\n    \
+         xkb_symbols {{\n        include \"pc+us+inet(evdev)\"\n\n{}\n    }};\n    \
+         xkb_geometry {{ include \"pc(pc105)\" }};\n}};\n",
+        layout.as_text(),
+        symbols_body(layout, keycodes, name, shift_layer, "        ")?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::test_utils::{keycodes, sval_layout, SVAL_LAYOUT};
+
+    #[test]
+    fn exports_all_keys_of_the_sval_with_both_levels() {
+        let layout = sval_layout(SVAL_LAYOUT);
+        let exported = symbols(&layout, &keycodes("export/sval_keycodes.yml"), "Test", 1).unwrap();
+
+        let n_symbols = SVAL_LAYOUT.chars().filter(|c| *c != '□').count();
+        // the fixed space is exported as well
+        assert_eq!(exported.matches("key <").count(), n_symbols + 1);
+        assert!(exported.contains("key <AC04> { [ a, A ] };"));
+        assert!(exported.contains("key <AD01> { [ comma, NoSymbol ] };"));
+        assert!(exported.contains("key <SPCE> { [ space, NoSymbol ] };"));
+        // the shift modifier is not remapped
+        assert!(!exported.contains("<LFSH>"));
+    }
+
+    #[test]
+    fn unmapped_keys_are_an_error() {
+        let layout = sval_layout(SVAL_LAYOUT);
+        let result = symbols(&layout, &keycodes("export/xkb_keycodes.yml"), "Test", 1);
+
+        assert!(result.is_err());
+    }
+}
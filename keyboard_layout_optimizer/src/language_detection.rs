@@ -0,0 +1,89 @@
+//! The `language_detection` module provides a simple stopword-based language detection
+//! for sorting corpus documents by language.
+
+use ahash::AHashMap;
+
+/// Minimum number of stopwords a text needs to contain to be assigned a language.
+const MIN_STOPWORD_HITS: usize = 3;
+
+/// Frequent words of the supported languages.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "of", "to", "is", "in", "that", "it", "for", "you", "was", "with", "on",
+            "are", "this", "have", "be", "not",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "und", "das", "ist", "nicht", "ich", "mit", "sich", "auf", "ein", "eine",
+            "zu", "den", "von", "es", "dem", "auch",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "les", "et", "est", "des", "une", "un", "que", "pas", "pour", "dans", "qui",
+            "sur", "du", "au", "ce", "il", "je",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "que", "es", "en", "por", "una", "con", "para", "del", "se",
+            "no", "lo", "como", "pero", "más",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "di", "che", "e", "per", "un", "non", "sono", "una", "del", "della", "con",
+            "gli", "è", "anche", "ma", "questo", "nel",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "niet", "dat", "op", "te", "zijn", "met",
+            "voor", "ik", "je", "maar", "ook", "wat",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "que", "de", "não", "uma", "um", "para", "com", "do", "da", "em", "se", "é",
+            "mais", "mas", "como", "ao",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "som", "är", "en", "på", "för", "med", "inte", "jag", "har", "av",
+            "den", "till", "om", "var", "så",
+        ],
+    ),
+];
+
+/// Detect the language of a text by counting frequent words of the supported languages.
+/// Returns `None` if no language could be determined (e.g. for source code).
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut word_counts: AHashMap<String, usize> = AHashMap::default();
+    text.split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .for_each(|w| *word_counts.entry(w.to_lowercase()).or_default() += 1);
+
+    STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits: usize = stopwords
+                .iter()
+                .map(|w| word_counts.get(*w).cloned().unwrap_or_default())
+                .sum();
+            (*language, hits)
+        })
+        .filter(|(_, hits)| *hits >= MIN_STOPWORD_HITS)
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(language, _)| language)
+}
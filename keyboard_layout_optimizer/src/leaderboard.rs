@@ -0,0 +1,212 @@
+//! The `leaderboard` module defines the format of entries of a shared layout leaderboard, a
+//! client for publishing entries to a leaderboard server, and a simple file-backed store used
+//! by the `leaderboard_server` binary.
+//!
+//! Entries are only comparable if they were evaluated with the same configuration, which is
+//! why each entry carries the hash of its configuration (see [`crate::common::config_hash`]).
+//! The server does not trust the published costs: it re-evaluates each layout with its own
+//! configuration (see [`LeaderboardEntry::verify`]) and stores its own results.
+
+use crate::results_db::hash_str;
+
+use keyboard_layout::layout_generator::LayoutGenerator;
+use layout_evaluation::{evaluation::Evaluator, results::EvaluationResult};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Maximal deviation between the published and the re-evaluated total cost
+const COST_TOLERANCE: f64 = 1e-6;
+
+/// Weighted cost of a single metric of a published layout.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublishedMetricCost {
+    pub metric: String,
+    pub cost: f64,
+}
+
+/// A layout together with its evaluation as published to a leaderboard.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeaderboardEntry {
+    pub layout: String,
+    /// SHA-256 hash of the (canonicalized) layout string (set by the server)
+    #[serde(default)]
+    pub layout_hash: String,
+    pub published_by: String,
+    pub config_hash: String,
+    pub total_cost: f64,
+    #[serde(default)]
+    pub metric_costs: Vec<PublishedMetricCost>,
+    /// Unix timestamp of the publication (set by the server)
+    #[serde(default)]
+    pub timestamp: i64,
+}
+
+impl LeaderboardEntry {
+    pub fn new(
+        layout: &str,
+        published_by: &str,
+        config_hash: &str,
+        evaluation_result: &EvaluationResult,
+    ) -> Self {
+        let metric_costs = evaluation_result
+            .iter()
+            .flat_map(|results| {
+                results.metric_costs.iter().map(|mc| PublishedMetricCost {
+                    metric: mc.core.name.clone(),
+                    cost: mc.weighted_cost,
+                })
+            })
+            .collect();
+
+        Self {
+            layout: layout.to_string(),
+            layout_hash: hash_str(layout),
+            published_by: published_by.to_string(),
+            config_hash: config_hash.to_string(),
+            total_cost: evaluation_result.total_cost(),
+            metric_costs,
+            timestamp: 0,
+        }
+    }
+
+    /// Re-evaluates the published layout with the given configuration and returns an entry
+    /// containing the resulting costs. Fails if the entry was published for another
+    /// configuration or if the published total cost does not match the re-evaluated one.
+    pub fn verify(
+        &self,
+        config_hash: &str,
+        layout_generator: &dyn LayoutGenerator,
+        evaluator: &Evaluator,
+    ) -> Result<Self> {
+        if self.config_hash != config_hash {
+            bail!(
+                "Configuration hash {} does not match the server's configuration {}",
+                self.config_hash,
+                config_hash
+            );
+        }
+
+        let layout = layout_generator.generate(&self.layout)?;
+        let evaluation_result = evaluator.evaluate_layout(&layout);
+        let total_cost = evaluation_result.total_cost();
+        if !self.total_cost.is_finite()
+            || (self.total_cost - total_cost).abs() > COST_TOLERANCE * total_cost.abs().max(1.0)
+        {
+            bail!(
+                "Published total cost {} does not match the evaluated cost {}",
+                self.total_cost,
+                total_cost
+            );
+        }
+
+        Ok(Self::new(
+            &layout.as_text(),
+            &self.published_by,
+            config_hash,
+            &evaluation_result,
+        ))
+    }
+}
+
+/// Posts an entry to the leaderboard server at the given url.
+pub fn publish(entry: &LeaderboardEntry, url: &str) -> Result<()> {
+    let client = reqwest::blocking::Client::new();
+    let resp = client.post(url).json(entry).send()?;
+    if !resp.status().is_success() {
+        bail!(
+            "Leaderboard server responded with {}: {}",
+            resp.status(),
+            resp.text().unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+/// Leaderboard entries kept in memory and persisted as JSON lines.
+pub struct Leaderboard {
+    filename: PathBuf,
+    entries: Vec<LeaderboardEntry>,
+}
+
+impl Leaderboard {
+    /// Loads the leaderboard from the given file. A missing file yields an empty leaderboard.
+    pub fn open(filename: &str) -> Result<Self> {
+        let entries = match fs::read_to_string(filename) {
+            Ok(data) => data
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .map(|entry: Result<LeaderboardEntry, _>| {
+                    entry.map(|mut entry| {
+                        // entries stored before the layout hash was introduced
+                        if entry.layout_hash.is_empty() {
+                            entry.layout_hash = hash_str(&entry.layout);
+                        }
+                        entry
+                    })
+                })
+                .collect::<Result<Vec<LeaderboardEntry>, _>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            filename: filename.into(),
+            entries,
+        })
+    }
+
+    /// Adds an entry to the leaderboard. Returns `false` (without storing anything) if the
+    /// layout has already been published for the same configuration.
+    ///
+    /// The entry is stored as is; entries received from clients need to be checked with
+    /// [`LeaderboardEntry::verify`] first.
+    pub fn add(&mut self, mut entry: LeaderboardEntry) -> Result<bool> {
+        if !entry.total_cost.is_finite() {
+            bail!("Invalid total cost {}", entry.total_cost);
+        }
+
+        if self
+            .entries
+            .iter()
+            .any(|e| e.layout_hash == entry.layout_hash && e.config_hash == entry.config_hash)
+        {
+            return Ok(false);
+        }
+
+        entry.timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.filename)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        self.entries.push(entry);
+
+        Ok(true)
+    }
+
+    /// The best entries (lowest cost first), optionally restricted to a configuration hash.
+    pub fn best(&self, config_hash: Option<&str>, limit: usize) -> Vec<&LeaderboardEntry> {
+        let mut entries: Vec<&LeaderboardEntry> = self
+            .entries
+            .iter()
+            .filter(|e| config_hash.map_or(true, |h| e.config_hash == h))
+            .collect();
+        entries.sort_by(|a, b| a.total_cost.partial_cmp(&b.total_cost).unwrap());
+        entries.truncate(limit);
+
+        entries
+    }
+}
@@ -0,0 +1,208 @@
+//! The `lessons` module generates progressive practice lessons for learning a layout with typing
+//! trainers (e.g. KTouch or keybr-style trainers that accept custom text).
+//!
+//! The keys of the layout's base layer are introduced in the order of their frequency in the
+//! corpus: the first lesson uses the most frequent keys, and each following lesson adds one new
+//! key. A lesson drills the most frequent bigrams of the new key with the keys learned so far,
+//! followed by words of the corpus that can be typed with the learned keys (preferring words with
+//! the new key). If too few such words are known, frequent trigrams serve as pseudo-words.
+
+use crate::export::is_placeholder;
+use keyboard_layout::layout::Layout;
+use layout_evaluation::ngrams::{Bigrams, Trigrams, Unigrams};
+
+use ahash::{AHashMap, AHashSet};
+use itertools::Itertools;
+use rand::{distributions::WeightedIndex, prelude::*};
+use serde::Serialize;
+use std::fmt;
+
+/// Maximal length of words taken from the corpus
+const MAX_WORD_LENGTH: usize = 16;
+/// Number of bigrams of the new key drilled in a lesson
+const N_DRILL_BIGRAMS: usize = 6;
+/// Number of repetitions of each drilled bigram
+const DRILL_REPETITIONS: usize = 3;
+/// Minimal number of distinct words that can be typed to use words instead of trigrams
+const MIN_WORDS: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct LessonParameters {
+    /// Number of keys of the first lesson
+    pub initial_keys: usize,
+    /// Number of words (or pseudo-words) per lesson
+    pub words_per_lesson: usize,
+    /// Maximal length of the lines of a lesson
+    pub line_length: usize,
+    /// Seed for sampling the words
+    pub seed: u64,
+}
+
+/// A practice lesson
+#[derive(Clone, Serialize, Debug)]
+pub struct Lesson {
+    /// Keys introduced in this lesson
+    pub new_keys: Vec<char>,
+    /// All keys used in this lesson
+    pub keys: Vec<char>,
+    pub lines: Vec<String>,
+}
+
+impl fmt::Display for Lesson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.lines.iter() {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The symbols of the base layer of the layout, sorted by their frequency in the corpus
+/// (descending). Whitespace, modifiers, and placeholders are not included.
+pub fn key_order(layout: &Layout, unigrams: &Unigrams) -> Vec<char> {
+    layout
+        .layerkeys
+        .iter()
+        .filter(|k| k.layer == 0 && k.is_modifier.is_none())
+        .map(|k| k.symbol)
+        .filter(|c| !c.is_whitespace() && !is_placeholder(*c))
+        .unique()
+        .sorted_by(|c1, c2| {
+            let w1 = unigrams.grams.get(c1).cloned().unwrap_or(0.0);
+            let w2 = unigrams.grams.get(c2).cloned().unwrap_or(0.0);
+            w2.partial_cmp(&w1).unwrap().then(c1.cmp(c2))
+        })
+        .collect()
+}
+
+/// The (lowercased) words of the text with their counts.
+pub fn word_counts(text: &str) -> Vec<(String, f64)> {
+    let mut counts: AHashMap<String, f64> = AHashMap::default();
+    for word in text.split_whitespace() {
+        if word.chars().count() <= MAX_WORD_LENGTH {
+            *counts.entry(word.to_lowercase()).or_default() += 1.0;
+        }
+    }
+
+    counts
+        .into_iter()
+        .sorted_by(|(w1, c1), (w2, c2)| c2.partial_cmp(c1).unwrap().then(w1.cmp(w2)))
+        .collect()
+}
+
+/// Join the words to lines of at most the given length.
+fn wrap(words: &[String], line_length: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in words {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > line_length {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+/// Sample words weighted by their frequency, half of them containing the new keys (if possible).
+fn sample_words<R: Rng>(
+    candidates: &[(String, f64)],
+    new_keys: &[char],
+    n: usize,
+    rng: &mut R,
+) -> Vec<String> {
+    let (with_new, others): (Vec<_>, Vec<_>) = candidates
+        .iter()
+        .partition(|(w, _)| w.chars().any(|c| new_keys.contains(&c)));
+
+    let mut sample = |pool: &[&(String, f64)], n: usize| -> Vec<String> {
+        match WeightedIndex::new(pool.iter().map(|(_, weight)| *weight)) {
+            Ok(dist) => (0..n).map(|_| pool[dist.sample(rng)].0.clone()).collect(),
+            Err(_) => Vec::new(),
+        }
+    };
+
+    let n_with_new = match others.is_empty() {
+        true => n,
+        false => n / 2,
+    };
+    let mut words = sample(&with_new, n_with_new);
+    let n_others = n - words.len();
+    words.extend(sample(&others, n_others));
+    words.shuffle(rng);
+
+    words
+}
+
+/// Generate the lessons for learning the keys in the given order.
+pub fn generate(
+    key_order: &[char],
+    bigrams: &Bigrams,
+    trigrams: &Trigrams,
+    words: &[(String, f64)],
+    params: &LessonParameters,
+) -> Vec<Lesson> {
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    let n_initial = params.initial_keys.max(1).min(key_order.len());
+
+    let mut lessons = Vec::new();
+    let mut learned: AHashSet<char> = AHashSet::default();
+    for (i, new_keys) in std::iter::once(&key_order[..n_initial])
+        .chain(key_order[n_initial..].chunks(1))
+        .enumerate()
+    {
+        if new_keys.is_empty() {
+            break;
+        }
+        learned.extend(new_keys.iter().cloned());
+        let keys: Vec<char> = key_order[..n_initial + i].to_vec();
+
+        // drills of the most frequent bigrams with the new keys
+        let drills: Vec<String> = bigrams
+            .grams
+            .iter()
+            .filter(|((c1, c2), _)| {
+                learned.contains(c1)
+                    && learned.contains(c2)
+                    && (new_keys.contains(c1) || new_keys.contains(c2))
+            })
+            .sorted_by(|(b1, w1), (b2, w2)| w2.partial_cmp(w1).unwrap().then(b1.cmp(b2)))
+            .take(N_DRILL_BIGRAMS)
+            .flat_map(|((c1, c2), _)| vec![format!("{}{}", c1, c2); DRILL_REPETITIONS])
+            .collect();
+
+        let candidates: Vec<(String, f64)> = words
+            .iter()
+            .filter(|(w, _)| w.chars().all(|c| learned.contains(&c)))
+            .cloned()
+            .collect();
+        let candidates = match candidates.len() >= MIN_WORDS {
+            true => candidates,
+            false => trigrams
+                .grams
+                .iter()
+                .filter(|((c1, c2, c3), _)| [c1, c2, c3].iter().all(|c| learned.contains(c)))
+                .map(|((c1, c2, c3), w)| (format!("{}{}{}", c1, c2, c3), *w))
+                .sorted_by(|(t1, w1), (t2, w2)| w2.partial_cmp(w1).unwrap().then(t1.cmp(t2)))
+                .collect(),
+        };
+        let sampled = sample_words(&candidates, new_keys, params.words_per_lesson, &mut rng);
+
+        let mut lines = wrap(&drills, params.line_length);
+        lines.extend(wrap(&sampled, params.line_length));
+        lessons.push(Lesson {
+            new_keys: new_keys.to_vec(),
+            keys,
+            lines,
+        });
+    }
+
+    lessons
+}
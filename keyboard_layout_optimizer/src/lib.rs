@@ -1 +1,21 @@
+pub mod anytime;
+pub mod clustering;
+pub mod co_keyboards;
 pub mod common;
+pub mod config_migration;
+pub mod corpus_cleaning;
+pub mod corpus_processing;
+pub mod corpus_profiles;
+pub mod export;
+pub mod language_detection;
+pub mod leaderboard;
+pub mod lessons;
+pub mod live_stream;
+pub mod logging;
+pub mod monitoring;
+pub mod multi_resolution;
+pub mod portfolio;
+pub mod radar_chart;
+pub mod result_cache;
+pub mod results_db;
+pub mod simulation;
@@ -0,0 +1,374 @@
+//! The `live_stream` module pushes every new best layout of running optimizations (with its
+//! costs) to browsers over a WebSocket, so that long runs can be followed in a dashboard. Opening
+//! the bound address in a browser shows a minimal dashboard.
+//!
+//! Every client has its own writer thread with a bounded queue, so that slow clients miss events
+//! instead of slowing down the optimization. Messages from clients other than pings and close
+//! frames are ignored.
+
+use keyboard_layout::layout_generator::LayoutGenerator;
+use layout_evaluation::evaluation::Evaluator;
+use layout_optimization_common::{
+    observer::{OptimizationObserver, Progress},
+    stopping::StopReason,
+};
+
+use ahash::AHashMap;
+use serde::Serialize;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tungstenite::{Message, WebSocket};
+
+const DASHBOARD: &str = include_str!("live_stream.html");
+
+/// Number of messages queued for a client before further messages to it are dropped
+const CLIENT_QUEUE_SIZE: usize = 64;
+
+/// Interval in which the writer of an idle client checks for messages from the client (e.g. close
+/// frames and pings)
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Time the writer of a client waits for messages from the client after each poll or write
+const READ_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// A message sent to the clients.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    NewBest {
+        run: String,
+        step: u64,
+        /// Seconds since the start of the run
+        elapsed: f64,
+        evaluations: u64,
+        cost: f64,
+        layout: String,
+        plot: String,
+        metric_costs: Vec<(String, f64)>,
+    },
+    Finished {
+        run: String,
+        step: u64,
+        /// Seconds since the start of the run
+        elapsed: f64,
+        cost: f64,
+        layout: String,
+        stop_reason: StopReason,
+    },
+}
+
+#[derive(Default)]
+struct Clients {
+    /// Queues of the writer threads of the connected clients
+    queues: Vec<SyncSender<Arc<str>>>,
+    /// The latest message of each run, sent to clients when they connect
+    latest: AHashMap<String, Arc<str>>,
+}
+
+/// The connected clients of a live stream. Clones share the clients.
+#[derive(Clone, Default)]
+pub struct LiveStream {
+    clients: Arc<Mutex<Clients>>,
+}
+
+impl LiveStream {
+    /// Send an event to all connected clients (and to clients connecting later, until the run
+    /// sends its next event).
+    ///
+    /// The event is only queued for the writer thread of each client, so a slow client cannot
+    /// block the optimization. If a client's queue is full, the event is dropped for it.
+    pub fn send(&self, run: &str, event: &Event) {
+        let message: Arc<str> = serde_json::to_string(event).unwrap().into();
+
+        let mut clients = self.clients.lock().unwrap();
+        // clients that closed the connection are dropped
+        clients
+            .queues
+            .retain(|queue| match queue.try_send(message.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    log::debug!("Live stream client is too slow, dropping event of {}", run);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+        clients.latest.insert(run.to_string(), message);
+    }
+
+    fn add_client(&self, socket: WebSocket<TcpStream>) {
+        let (queue, messages) = mpsc::sync_channel(CLIENT_QUEUE_SIZE);
+
+        let mut clients = self.clients.lock().unwrap();
+        for message in clients.latest.values() {
+            // the queue is large enough for the latest messages of all but very many runs
+            queue.try_send(message.clone()).ok();
+        }
+        clients.queues.push(queue);
+        drop(clients);
+
+        thread::spawn(move || write_to_client(socket, messages));
+    }
+}
+
+/// Write the queued messages to a client until either side closes the connection. In between,
+/// messages from the client are read, so that pings are answered and close frames are noticed.
+fn write_to_client(mut socket: WebSocket<TcpStream>, messages: Receiver<Arc<str>>) {
+    loop {
+        match messages.recv_timeout(POLL_INTERVAL) {
+            Ok(message) => {
+                if socket
+                    .write_message(Message::Text(message.to_string()))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                socket.close(None).ok();
+                socket.write_pending().ok();
+                return;
+            }
+        }
+
+        // the socket has a short read timeout, so this only takes the frames that have arrived
+        match socket.read_message() {
+            Ok(Message::Close(_)) => {
+                // answer the close frame
+                socket.write_pending().ok();
+                return;
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(_) => return,
+        }
+
+        // send pending answers to pings
+        match socket.write_pending() {
+            Ok(()) => {}
+            Err(tungstenite::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(_) => return,
+        }
+    }
+}
+
+/// Whether the request waiting on the stream asks for a WebSocket (without consuming it).
+fn is_websocket_request(stream: &TcpStream) -> bool {
+    let mut buf = [0; 4096];
+    match stream.peek(&mut buf) {
+        Ok(n) => String::from_utf8_lossy(&buf[..n])
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .any(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-key")),
+        Err(_) => false,
+    }
+}
+
+/// Answer a (non-WebSocket) request with the dashboard.
+fn serve_dashboard(mut stream: &TcpStream) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) if line.trim().is_empty() => break,
+            Ok(_) => {}
+        }
+    }
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        DASHBOARD.len(),
+        DASHBOARD
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        log::warn!("Could not answer live stream request: {:?}", e);
+    }
+}
+
+/// Accept WebSocket clients at `ws://<bind>/` (and serve the dashboard at `http://<bind>/`) in
+/// a background thread.
+pub fn serve(bind: &str) -> LiveStream {
+    let listener =
+        TcpListener::bind(bind).unwrap_or_else(|e| panic!("Could not listen on {}: {:?}", bind, e));
+    log::info!("Streaming new best layouts on http://{}/", bind);
+
+    let live_stream = LiveStream::default();
+    let cloned_live_stream = live_stream.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(Duration::from_secs(10))).ok();
+                    stream.set_write_timeout(Some(Duration::from_secs(10))).ok();
+                    if !is_websocket_request(&stream) {
+                        serve_dashboard(&stream);
+                        continue;
+                    }
+                    match tungstenite::accept(stream) {
+                        Ok(socket) => {
+                            // from now on, reads only poll for messages from the client
+                            socket.get_ref().set_read_timeout(Some(READ_TIMEOUT)).ok();
+                            cloned_live_stream.add_client(socket);
+                        }
+                        Err(e) => log::warn!("WebSocket handshake failed: {:?}", e),
+                    }
+                }
+                Err(e) => log::warn!("Connection failed: {:?}", e),
+            }
+        }
+    });
+
+    live_stream
+}
+
+/// A new best layout or the end of an optimization run, waiting to be sent.
+enum Update {
+    NewBest(Progress, String),
+    Finished(Progress, String, StopReason),
+}
+
+/// Sends new best layouts (evaluated with the given evaluator) and the end of the optimization
+/// to a [`LiveStream`].
+///
+/// The layouts are evaluated in a background thread. If the optimization finds new best layouts
+/// faster than they can be evaluated, only the latest one is sent.
+pub struct LiveStreamObserver {
+    updates: Option<mpsc::Sender<Update>>,
+    sender: Option<JoinHandle<()>>,
+}
+
+impl LiveStreamObserver {
+    pub fn new(
+        live_stream: &LiveStream,
+        evaluator: &Evaluator,
+        layout_generator: &dyn LayoutGenerator,
+    ) -> Self {
+        let (updates, receiver) = mpsc::channel();
+        let live_stream = live_stream.clone();
+        let evaluator = evaluator.clone();
+        let layout_generator = layout_generator.clone_box();
+        let sender = thread::spawn(move || {
+            send_updates(
+                receiver,
+                &live_stream,
+                &evaluator,
+                layout_generator.as_ref(),
+            )
+        });
+
+        Self {
+            updates: Some(updates),
+            sender: Some(sender),
+        }
+    }
+
+    fn push(&self, update: Update) {
+        if let Some(updates) = &self.updates {
+            updates.send(update).ok();
+        }
+    }
+}
+
+impl Drop for LiveStreamObserver {
+    /// Wait until all updates of the run have been sent.
+    fn drop(&mut self) {
+        self.updates.take();
+        if let Some(sender) = self.sender.take() {
+            sender.join().ok();
+        }
+    }
+}
+
+/// Evaluate and send the updates of an optimization run until the run's observer is dropped.
+fn send_updates(
+    receiver: Receiver<Update>,
+    live_stream: &LiveStream,
+    evaluator: &Evaluator,
+    layout_generator: &dyn LayoutGenerator,
+) {
+    while let Ok(update) = receiver.recv() {
+        let mut updates = vec![update];
+        updates.extend(receiver.try_iter());
+        // a new best layout that has already been superseded is skipped
+        let latest_best = updates
+            .iter()
+            .rposition(|update| matches!(update, Update::NewBest(..)));
+        for (i, update) in updates.into_iter().enumerate() {
+            match update {
+                Update::NewBest(progress, layout) if Some(i) == latest_best => {
+                    send_new_best(live_stream, evaluator, layout_generator, &progress, &layout)
+                }
+                Update::NewBest(..) => {}
+                Update::Finished(progress, layout, stop_reason) => {
+                    let event = Event::Finished {
+                        run: progress.run.clone(),
+                        step: progress.step,
+                        elapsed: progress.elapsed.as_secs_f64(),
+                        cost: progress.best_cost,
+                        layout,
+                        stop_reason,
+                    };
+                    live_stream.send(&progress.run, &event);
+                }
+            }
+        }
+    }
+}
+
+fn send_new_best(
+    live_stream: &LiveStream,
+    evaluator: &Evaluator,
+    layout_generator: &dyn LayoutGenerator,
+    progress: &Progress,
+    layout: &str,
+) {
+    let layout = match layout_generator.generate(layout) {
+        Ok(layout) => layout,
+        Err(e) => {
+            log::warn!("Could not stream layout {}: {:?}", layout, e);
+            return;
+        }
+    };
+    let evaluation_result = evaluator.evaluate_layout(&layout);
+    let metric_costs = evaluation_result.metric_costs();
+
+    let event = Event::NewBest {
+        run: progress.run.clone(),
+        step: progress.step,
+        elapsed: progress.elapsed.as_secs_f64(),
+        evaluations: progress.evaluations,
+        cost: evaluation_result.total_cost(),
+        layout: layout.as_text(),
+        plot: layout.plot(),
+        metric_costs,
+    };
+    live_stream.send(&progress.run, &event);
+}
+
+impl OptimizationObserver for LiveStreamObserver {
+    fn on_new_best(&mut self, progress: &Progress, layout: &str) {
+        self.push(Update::NewBest(progress.clone(), layout.to_string()));
+    }
+
+    fn on_finish(&mut self, progress: &Progress, layout: &str, stop_reason: StopReason) {
+        self.push(Update::Finished(
+            progress.clone(),
+            layout.to_string(),
+            stop_reason,
+        ));
+    }
+}
@@ -0,0 +1,39 @@
+//! The `logging` module sets up the log output of the binaries. Records of the `log` crate as
+//! well as events and spans of the `tracing` crate (e.g. the evaluation phases and metrics of
+//! `layout_evaluation`) are written to stderr and filtered with `RUST_LOG`, e.g.
+//! `RUST_LOG=info,layout_evaluation=trace` or `RUST_LOG="layout_evaluation[bigram_metrics]=trace"`
+//! for the events within a single span.
+//!
+//! Two environment variables change the output:
+//! - `LOG_FORMAT=json` writes one JSON object per event (with its fields and enclosing spans),
+//!   e.g. for processing the logs of long optimization runs with `jq`.
+//! - `LOG_SPANS=1` additionally logs when a span closes, with the time spent in it.
+
+use std::{env, io};
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+fn flag(name: &str) -> bool {
+    env::var(name).map_or(false, |value| !value.is_empty() && value != "0")
+}
+
+/// Initialize the logging (only errors are logged if `RUST_LOG` is not set).
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error"));
+    let span_events = match flag("LOG_SPANS") {
+        true => FmtSpan::CLOSE,
+        false => FmtSpan::NONE,
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(span_events)
+        .with_writer(io::stderr);
+
+    let result = match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().try_init(),
+        _ => builder.with_ansi(atty::is(atty::Stream::Stderr)).try_init(),
+    };
+    if let Err(e) = result {
+        eprintln!("Could not initialize logging: {}", e);
+    }
+}
@@ -0,0 +1,183 @@
+//! The `monitoring` module serves the statistics of running optimizations (see
+//! [`layout_optimization_common::stats`]) in the Prometheus text format, so that long runs on
+//! headless machines can be monitored with standard tooling. It also provides a
+//! [`ProgressLogger`] that periodically logs the progress of an optimization with an ETA.
+
+use layout_optimization_common::{
+    observer::{OptimizationObserver, Progress},
+    stats::{self, Snapshot},
+    stopping::StopReason,
+};
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+/// Render the statistics in the Prometheus text exposition format.
+pub fn render(snapshot: &Snapshot) -> String {
+    let mut lines = Vec::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+        lines.push(format!("# HELP layout_optimizer_{} {}", name, help));
+        lines.push(format!("# TYPE layout_optimizer_{} {}", name, kind));
+        lines.push(format!("layout_optimizer_{} {}", name, value));
+    };
+
+    metric(
+        "generation",
+        "gauge",
+        "Latest generation (genetic algorithm) or iteration (simulated annealing).",
+        snapshot.generation as f64,
+    );
+    if snapshot.best_cost.is_finite() {
+        metric(
+            "best_cost",
+            "gauge",
+            "Lowest cost of all layouts found so far.",
+            snapshot.best_cost,
+        );
+    }
+    metric(
+        "evaluations_total",
+        "counter",
+        "Number of layout evaluations.",
+        snapshot.evaluations as f64,
+    );
+    metric(
+        "evaluations_per_second",
+        "gauge",
+        "Average number of layout evaluations per second since the start.",
+        snapshot.evaluations_per_second(),
+    );
+    metric(
+        "proposals_total",
+        "counter",
+        "Number of proposed moves (simulated annealing).",
+        snapshot.proposals as f64,
+    );
+    metric(
+        "accepted_total",
+        "counter",
+        "Number of accepted moves (simulated annealing).",
+        snapshot.accepted as f64,
+    );
+    if let Some(acceptance_rate) = snapshot.acceptance_rate() {
+        metric(
+            "acceptance_rate",
+            "gauge",
+            "Fraction of accepted moves since the start (simulated annealing).",
+            acceptance_rate,
+        );
+    }
+    metric(
+        "runs_started_total",
+        "counter",
+        "Number of started optimization runs.",
+        snapshot.runs_started as f64,
+    );
+    metric(
+        "uptime_seconds",
+        "gauge",
+        "Seconds since the first optimization run was started.",
+        snapshot.uptime,
+    );
+
+    format!("{}\n", lines.join("\n"))
+}
+
+fn handle(mut stream: &TcpStream) {
+    let mut request_line = String::new();
+    if BufReader::new(stream).read_line(&mut request_line).is_err() {
+        return;
+    }
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = match path.split('?').next() {
+        Some("/metrics") => ("200 OK", render(&stats::snapshot())),
+        _ => ("404 Not Found", "Not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        log::warn!("Could not send metrics: {:?}", e);
+    }
+}
+
+/// Serve the statistics at `http://<bind>/metrics` in a background thread.
+pub fn serve(bind: &str) {
+    let listener =
+        TcpListener::bind(bind).unwrap_or_else(|e| panic!("Could not listen on {}: {:?}", bind, e));
+    log::info!("Serving optimization metrics on http://{}/metrics", bind);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(Duration::from_secs(10))).ok();
+                    handle(&stream);
+                }
+                Err(e) => log::warn!("Connection failed: {:?}", e),
+            }
+        }
+    });
+}
+
+/// Format a duration as `h:mm:ss`.
+pub fn fmt_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Logs the progress of an optimization (at most once per interval) and its end.
+pub struct ProgressLogger {
+    interval: Duration,
+    last_log: Duration,
+}
+
+impl ProgressLogger {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_log: Duration::ZERO,
+        }
+    }
+}
+
+impl OptimizationObserver for ProgressLogger {
+    fn on_generation(&mut self, progress: &Progress) {
+        if progress.elapsed < self.last_log + self.interval {
+            return;
+        }
+        self.last_log = progress.elapsed;
+
+        log::info!(
+            "{}: step {}/{} ({:.1}%), best cost: {:.4}, {:.0} evaluations/s, elapsed: {}, ETA: {}",
+            progress.run,
+            progress.step,
+            progress.budget.max_steps,
+            100.0 * progress.fraction_done(),
+            progress.best_cost,
+            progress.evaluations_per_second(),
+            fmt_duration(progress.elapsed),
+            progress.eta().map_or("unknown".to_string(), fmt_duration),
+        );
+    }
+
+    fn on_finish(&mut self, progress: &Progress, _layout: &str, stop_reason: StopReason) {
+        log::info!(
+            "{}: finished after {} steps in {} ({}), best cost: {:.4}, {} evaluations",
+            progress.run,
+            progress.step,
+            fmt_duration(progress.elapsed),
+            stop_reason,
+            progress.best_cost,
+            progress.evaluations,
+        );
+    }
+}
@@ -0,0 +1,665 @@
+//! The `multi_resolution` module optimizes a layout in two phases. The coarse phase assigns the
+//! symbols to regions (the cluster of a finger on a layer), keeping each of the configured symbol
+//! groups (e.g. the vowels) together in one region. It evaluates an assignment with a fixed
+//! arrangement within each region: the most frequent symbol on the cheapest key. The refine phase
+//! then arranges the symbols within their regions with simulated annealing. Both phases search
+//! much smaller spaces than an optimization of the whole layout at once, which matters most for
+//! layouts with several layers.
+
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+use layout_optimization_common::{
+    observer::SharedObserver,
+    stats,
+    stopping::{SharedBudget, StopReason},
+    swaps::SwapRestrictions,
+    LayoutPermutator,
+};
+use layout_optimization_sa::optimization as sa;
+
+use ahash::AHashMap;
+use anyhow::Result;
+use colored::Colorize;
+use rand::prelude::*;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{fmt, fs::File};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Parameters {
+    /// Symbols that are kept together in one region by the coarse phase (e.g. "aeiou")
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// Maximum number of improving moves of the coarse phase
+    #[serde(default = "default_coarse_iterations")]
+    pub coarse_iterations: u64,
+
+    /// Arrange the symbols within their regions with simulated annealing after the coarse phase
+    #[serde(default = "default_refine")]
+    pub refine: bool,
+}
+
+fn default_coarse_iterations() -> u64 {
+    500
+}
+
+fn default_refine() -> bool {
+    true
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Parameters {
+            groups: Vec::new(),
+            coarse_iterations: default_coarse_iterations(),
+            refine: default_refine(),
+        }
+    }
+}
+
+impl Parameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        Ok(serde_yaml::from_reader(f)?)
+    }
+}
+
+/// The non-fixed positions of the cluster of a finger on a layer.
+#[derive(Clone, Debug)]
+struct Region {
+    name: String,
+    /// Indices into the layout string, cheapest key first
+    positions: Vec<usize>,
+}
+
+/// The partition of the non-fixed positions of a layout string into regions.
+#[derive(Clone, Debug)]
+struct Regions {
+    regions: Vec<Region>,
+    /// Region of each position of the layout string (`None` for fixed keys)
+    of_position: Vec<Option<usize>>,
+}
+
+impl Regions {
+    /// The regions of the positions of `layout_str` (which starts with the non-fixed keys of
+    /// `layout` and may continue with further layers of the same keys).
+    fn new(layout_str: &str, fixed_characters: &str, layout: &Layout) -> Self {
+        let keys = layout.non_fixed_keys();
+        let mut ids = Vec::new();
+        let mut regions: Vec<Region> = Vec::new();
+        let mut of_position = Vec::new();
+        for (i, c) in layout_str.chars().enumerate() {
+            if fixed_characters.contains(c) || keys.is_empty() {
+                of_position.push(None);
+                continue;
+            }
+
+            let key = keys[i % keys.len()];
+            let layer = i / keys.len();
+            let id = (layer, key.hand, key.finger);
+            let region = match ids.iter().position(|other| *other == id) {
+                Some(region) => region,
+                None => {
+                    ids.push(id);
+                    regions.push(Region {
+                        name: format!("layer {}, {:?} {:?}", layer + 1, key.hand, key.finger)
+                            .to_lowercase(),
+                        positions: Vec::new(),
+                    });
+                    regions.len() - 1
+                }
+            };
+            regions[region].positions.push(i);
+            of_position.push(Some(region));
+        }
+
+        for region in regions.iter_mut() {
+            region.positions.sort_by(|p1, p2| {
+                let cost = |p: &usize| keys[p % keys.len()].cost;
+                cost(p1).partial_cmp(&cost(p2)).unwrap().then(p1.cmp(p2))
+            });
+        }
+
+        Self {
+            regions,
+            of_position,
+        }
+    }
+
+    /// Arrange the symbols within each region by frequency: the most frequent symbol on the
+    /// cheapest key.
+    fn arrange(&self, symbols: &[char], frequencies: &AHashMap<char, f64>) -> Vec<char> {
+        let frequency = |c: &char| frequencies.get(c).copied().unwrap_or_default();
+        let mut arranged = symbols.to_vec();
+        for region in self.regions.iter() {
+            let mut region_symbols: Vec<char> =
+                region.positions.iter().map(|p| symbols[*p]).collect();
+            region_symbols.sort_by(|c1, c2| {
+                frequency(c2)
+                    .partial_cmp(&frequency(c1))
+                    .unwrap()
+                    .then(c1.cmp(c2))
+            });
+            for (p, c) in region.positions.iter().zip(region_symbols) {
+                arranged[*p] = c;
+            }
+        }
+
+        arranged
+    }
+
+    /// The name and symbols of each region.
+    fn contents(&self, symbols: &[char]) -> Vec<(String, String)> {
+        self.regions
+            .iter()
+            .map(|region| {
+                let contents = region.positions.iter().map(|p| symbols[*p]).collect();
+                (region.name.clone(), contents)
+            })
+            .collect()
+    }
+}
+
+/// A move of the coarse phase. All moves keep the symbol groups together.
+#[derive(Clone, Copy, Debug)]
+enum Move {
+    /// Swap two ungrouped symbols (at these positions) of different regions
+    Swap(usize, usize),
+    /// Move a group into a region, in exchange for its least frequent ungrouped symbols
+    Group(usize, usize),
+    /// Swap the symbols of two regions of the same size
+    Regions(usize, usize),
+}
+
+/// The search for the assignment of symbols to regions.
+struct Coarse<'a> {
+    regions: &'a Regions,
+    groups: Vec<Vec<char>>,
+    frequencies: &'a AHashMap<char, f64>,
+}
+
+impl<'a> Coarse<'a> {
+    fn group_of(&self, c: char) -> Option<usize> {
+        self.groups.iter().position(|group| group.contains(&c))
+    }
+
+    /// The ungrouped positions of a region, least frequent symbol first.
+    fn ungrouped_positions(&self, symbols: &[char], region: usize) -> Vec<usize> {
+        let frequency = |p: &usize| {
+            self.frequencies
+                .get(&symbols[*p])
+                .copied()
+                .unwrap_or_default()
+        };
+        let mut positions: Vec<usize> = self.regions.regions[region]
+            .positions
+            .iter()
+            .filter(|p| self.group_of(symbols[**p]).is_none())
+            .copied()
+            .collect();
+        positions.sort_by(|p1, p2| frequency(p1).partial_cmp(&frequency(p2)).unwrap());
+
+        positions
+    }
+
+    /// Move the symbols of each group into one region (the one with the most of them that has
+    /// room for the group). Groups that do not fit into any region are dropped.
+    fn gather_groups(&mut self, symbols: &mut [char]) {
+        let mut gathered: Vec<Vec<char>> = Vec::new();
+        for group in self.groups.clone() {
+            let in_gathered = |c: char| gathered.iter().any(|g| g.contains(&c));
+            let target = self
+                .regions
+                .regions
+                .iter()
+                .enumerate()
+                .filter(|(_, region)| {
+                    region
+                        .positions
+                        .iter()
+                        .filter(|p| !in_gathered(symbols[**p]))
+                        .count()
+                        >= group.len()
+                })
+                .max_by_key(|(_, region)| {
+                    region
+                        .positions
+                        .iter()
+                        .filter(|p| group.contains(&symbols[**p]))
+                        .count()
+                })
+                .map(|(r, _)| r);
+            let target = match target {
+                Some(target) => target,
+                None => {
+                    log::warn!(
+                        "Ignoring group '{}': it does not fit into any region",
+                        group.iter().collect::<String>()
+                    );
+                    continue;
+                }
+            };
+
+            let region = &self.regions.regions[target];
+            let mut displaceable: Vec<usize> = region
+                .positions
+                .iter()
+                .filter(|p| !in_gathered(symbols[**p]) && !group.contains(&symbols[**p]))
+                .copied()
+                .collect();
+            // displace ungrouped symbols first
+            displaceable.sort_by_key(|p| self.group_of(symbols[*p]).is_some());
+            let missing: Vec<usize> = (0..symbols.len())
+                .filter(|p| {
+                    group.contains(&symbols[*p]) && self.regions.of_position[*p] != Some(target)
+                })
+                .collect();
+            for (from, to) in missing.into_iter().zip(displaceable) {
+                symbols.swap(from, to);
+            }
+            gathered.push(group);
+        }
+        self.groups = gathered;
+    }
+
+    /// All moves from the given assignment.
+    fn moves(&self, symbols: &[char]) -> Vec<Move> {
+        let regions = &self.regions.regions;
+        let of_position = &self.regions.of_position;
+        let ungrouped: Vec<usize> = (0..symbols.len())
+            .filter(|p| of_position[*p].is_some() && self.group_of(symbols[*p]).is_none())
+            .collect();
+
+        let mut moves = Vec::new();
+        for (i, p1) in ungrouped.iter().enumerate() {
+            for p2 in ungrouped[i + 1..].iter() {
+                if of_position[*p1] != of_position[*p2] {
+                    moves.push(Move::Swap(*p1, *p2));
+                }
+            }
+        }
+        for (g, group) in self.groups.iter().enumerate() {
+            let current = symbols
+                .iter()
+                .position(|c| *c == group[0])
+                .and_then(|p| of_position[p]);
+            for r in 0..regions.len() {
+                if Some(r) != current && self.ungrouped_positions(symbols, r).len() >= group.len() {
+                    moves.push(Move::Group(g, r));
+                }
+            }
+        }
+        for r1 in 0..regions.len() {
+            for r2 in r1 + 1..regions.len() {
+                if regions[r1].positions.len() == regions[r2].positions.len() {
+                    moves.push(Move::Regions(r1, r2));
+                }
+            }
+        }
+
+        moves
+    }
+
+    fn apply(&self, symbols: &[char], m: Move) -> Vec<char> {
+        let mut symbols = symbols.to_vec();
+        match m {
+            Move::Swap(p1, p2) => symbols.swap(p1, p2),
+            Move::Group(g, r) => {
+                let from: Vec<usize> = (0..symbols.len())
+                    .filter(|p| self.groups[g].contains(&symbols[*p]))
+                    .collect();
+                let to = self.ungrouped_positions(&symbols, r);
+                for (p1, p2) in from.into_iter().zip(to) {
+                    symbols.swap(p1, p2);
+                }
+            }
+            Move::Regions(r1, r2) => {
+                let regions = &self.regions.regions;
+                for (p1, p2) in regions[r1].positions.iter().zip(&regions[r2].positions) {
+                    symbols.swap(*p1, *p2);
+                }
+            }
+        }
+
+        symbols
+    }
+}
+
+/// The share of the unigram weight of each symbol.
+fn symbol_frequencies(evaluator: &Evaluator, layout: &Layout) -> AHashMap<char, f64> {
+    let usage = evaluator.key_usage(layout);
+    let mut frequencies = AHashMap::default();
+    for k in layout.layerkeys.iter().filter(|k| k.is_modifier.is_none()) {
+        frequencies
+            .entry(k.symbol)
+            .or_insert_with(|| usage.get(&(k.layer, k.key.matrix_position)).copied());
+    }
+
+    frequencies
+        .into_iter()
+        .map(|(c, f)| (c, f.unwrap_or_default()))
+        .collect()
+}
+
+/// The result of both phases.
+pub struct MultiResolutionResult {
+    pub layout_str: String,
+    pub layout: Layout,
+    /// Cost of the layout of the coarse phase (before the refinement)
+    pub coarse_cost: f64,
+    /// Number of improving moves of the coarse phase
+    pub coarse_moves: u64,
+    /// Name and symbols of each region
+    pub regions: Vec<(String, String)>,
+    pub stop_reason: StopReason,
+}
+
+impl fmt::Display for MultiResolutionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<28} Symbols", "Region")?;
+        for (name, symbols) in self.regions.iter() {
+            writeln!(f, "{:<28} {}", name, symbols)?;
+        }
+        write!(
+            f,
+            "Coarse phase: {} moves, cost {:.4}",
+            self.coarse_moves, self.coarse_cost
+        )
+    }
+}
+
+/// Assigns the symbols to regions (coarse phase), then arranges them within their regions with
+/// simulated annealing (refine phase, see [`Parameters::refine`]). The budget stops both phases.
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
+pub fn optimize(
+    params: &Parameters,
+    sa_params: &sa::Parameters,
+    layout_str: &str,
+    fixed_characters: &str,
+    layout_generator: &Box<dyn LayoutGenerator>,
+    start_with_layout: bool,
+    evaluator: &Evaluator,
+    result_cache: Option<Cache<f64>>,
+    budget: Option<&SharedBudget>,
+    observer: Option<SharedObserver>,
+) -> MultiResolutionResult {
+    log::info!("Starting multi-resolution optimization with: {:?}", params);
+    let start_layout = layout_generator.generate(layout_str).unwrap();
+    let regions = Regions::new(layout_str, fixed_characters, &start_layout);
+    let frequencies = symbol_frequencies(evaluator, &start_layout);
+    let mut coarse = Coarse {
+        regions: &regions,
+        groups: params
+            .groups
+            .iter()
+            .map(|group| {
+                group
+                    .chars()
+                    .filter(|c| layout_str.contains(*c) && !fixed_characters.contains(*c))
+                    .collect::<Vec<char>>()
+            })
+            .filter(|group| !group.is_empty())
+            .collect(),
+        frequencies: &frequencies,
+    };
+
+    let cost = |symbols: &[char]| -> f64 {
+        stats::record_evaluation();
+        let layout_string: String = regions.arrange(symbols, &frequencies).into_iter().collect();
+        let evaluate = || {
+            let layout = layout_generator.generate(&layout_string).unwrap();
+            evaluator.evaluate_layout(&layout).total_cost()
+        };
+        match &result_cache {
+            Some(result_cache) => result_cache.get_or_insert_with(&layout_string, evaluate),
+            None => evaluate(),
+        }
+    };
+
+    stats::record_start();
+    let mut current: Vec<char> = match start_with_layout {
+        true => layout_str.chars().collect(),
+        false => {
+            let pm = LayoutPermutator::new(layout_str, fixed_characters);
+            pm.generate_string(&pm.generate_random()).chars().collect()
+        }
+    };
+    coarse.gather_groups(&mut current);
+    let mut current_cost = cost(&current);
+    let mut stop_reason = None;
+    let mut coarse_moves = 0;
+
+    let rng = &mut thread_rng();
+    for iteration in 1..=params.coarse_iterations {
+        stop_reason = budget.and_then(|budget| budget.check());
+        if stop_reason.is_some() {
+            break;
+        }
+
+        let mut moves = coarse.moves(&current);
+        moves.shuffle(rng);
+        let improvement = moves.par_iter().find_map_any(|m| {
+            let candidate = coarse.apply(&current, *m);
+            let candidate_cost = cost(&candidate);
+            (candidate_cost < current_cost).then_some((candidate, candidate_cost, *m))
+        });
+        let (candidate, candidate_cost, m) = match improvement {
+            Some(improvement) => improvement,
+            None => {
+                log::info!(
+                    "{} No improving move left after {} moves",
+                    "Coarse phase:".yellow().bold(),
+                    coarse_moves,
+                );
+                break;
+            }
+        };
+
+        current = candidate;
+        current_cost = candidate_cost;
+        coarse_moves += 1;
+        stats::record_generation(iteration);
+        stats::record_cost(current_cost);
+        log::info!(
+            "{} {} ({:.4}, {:?})",
+            format!("Coarse move {}:", iteration).yellow().bold(),
+            regions
+                .arrange(&current, &frequencies)
+                .into_iter()
+                .collect::<String>(),
+            current_cost,
+            m,
+        );
+    }
+
+    let arranged = regions.arrange(&current, &frequencies);
+    let contents = regions.contents(&arranged);
+    let coarse_layout_str: String = arranged.into_iter().collect();
+    let (layout_str, stop_reason) = match (params.refine, stop_reason) {
+        (true, None) => {
+            // restrict the swaps of the annealing to the symbols of each region
+            let mut sa_params = sa_params.clone();
+            sa_params.swap_restrictions = SwapRestrictions {
+                same_layer_swaps: false,
+                swap_groups: contents.iter().map(|(_, s)| s.clone()).collect(),
+            };
+            if let Some(budget) = budget {
+                sa_params.stopping.shared = Some(budget.clone());
+            }
+            let (layout_str, _, _, stop_reason) = sa::optimize(
+                "Refine",
+                &sa_params,
+                &coarse_layout_str,
+                fixed_characters,
+                layout_generator,
+                true,
+                evaluator,
+                false,
+                result_cache.clone(),
+                None,
+                observer,
+            );
+            (layout_str, stop_reason)
+        }
+        (_, stop_reason) => (
+            coarse_layout_str,
+            stop_reason.unwrap_or(StopReason::Completed),
+        ),
+    };
+
+    MultiResolutionResult {
+        layout: layout_generator.generate(&layout_str).unwrap(),
+        layout_str,
+        coarse_cost: current_cost,
+        coarse_moves,
+        regions: contents,
+        stop_reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use layout_evaluation::test_utils::setup;
+
+    /// The region of each symbol of the group (`None` if the symbol is missing)
+    fn regions_of(regions: &Regions, symbols: &[char], group: &str) -> Vec<Option<usize>> {
+        group
+            .chars()
+            .map(|c| {
+                let p = symbols.iter().position(|s| *s == c)?;
+                regions.of_position[p]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn regions_partition_the_keys_by_finger_cheapest_first() {
+        let (generator, layout_str, _) = setup();
+        let layout = generator.generate(&layout_str).unwrap();
+        let keys = layout.non_fixed_keys();
+        let regions = Regions::new(&layout_str, "", &layout);
+
+        let mut positions: Vec<usize> = regions
+            .regions
+            .iter()
+            .flat_map(|region| region.positions.clone())
+            .collect();
+        positions.sort_unstable();
+        assert_eq!(
+            positions,
+            (0..layout_str.chars().count()).collect::<Vec<_>>()
+        );
+
+        for (r, region) in regions.regions.iter().enumerate() {
+            let first = keys[region.positions[0]];
+            for (p1, p2) in region.positions.iter().zip(region.positions.iter().skip(1)) {
+                assert!(keys[*p1].cost <= keys[*p2].cost);
+            }
+            for p in region.positions.iter() {
+                assert_eq!(regions.of_position[*p], Some(r));
+                assert_eq!((keys[*p].hand, keys[*p].finger), (first.hand, first.finger));
+            }
+        }
+    }
+
+    #[test]
+    fn arranges_the_most_frequent_symbol_on_the_cheapest_key() {
+        let (generator, layout_str, evaluator) = setup();
+        let layout = generator.generate(&layout_str).unwrap();
+        let regions = Regions::new(&layout_str, "", &layout);
+        let frequencies = symbol_frequencies(&evaluator, &layout);
+        let symbols: Vec<char> = layout_str.chars().collect();
+
+        let arranged = regions.arrange(&symbols, &frequencies);
+        let frequency = |c: &char| frequencies.get(c).copied().unwrap_or_default();
+        for region in regions.regions.iter() {
+            let mut before: Vec<char> = region.positions.iter().map(|p| symbols[*p]).collect();
+            let after: Vec<char> = region.positions.iter().map(|p| arranged[*p]).collect();
+            for (c1, c2) in after.iter().zip(after.iter().skip(1)) {
+                assert!(frequency(c1) >= frequency(c2));
+            }
+            let mut sorted_after = after.clone();
+            before.sort_unstable();
+            sorted_after.sort_unstable();
+            assert_eq!(before, sorted_after);
+        }
+    }
+
+    #[test]
+    fn moves_keep_the_groups_together() {
+        let (generator, layout_str, evaluator) = setup();
+        let layout = generator.generate(&layout_str).unwrap();
+        let regions = Regions::new(&layout_str, "", &layout);
+        let frequencies = symbol_frequencies(&evaluator, &layout);
+        let mut coarse = Coarse {
+            regions: &regions,
+            groups: vec!["aeiou".chars().collect()],
+            frequencies: &frequencies,
+        };
+
+        let mut symbols: Vec<char> = layout_str.chars().collect();
+        coarse.gather_groups(&mut symbols);
+        assert_eq!(coarse.groups.len(), 1);
+        let together = |symbols: &[char]| {
+            let group_regions = regions_of(&regions, symbols, "aeiou");
+            group_regions[0].is_some() && group_regions.iter().all(|r| *r == group_regions[0])
+        };
+        assert!(together(&symbols));
+
+        let moves = coarse.moves(&symbols);
+        assert!(moves.iter().any(|m| matches!(m, Move::Group(_, _))));
+        for m in moves {
+            let moved = coarse.apply(&symbols, m);
+            assert!(together(&moved), "{:?}", m);
+
+            let mut sorted = moved.clone();
+            let mut original = symbols.clone();
+            sorted.sort_unstable();
+            original.sort_unstable();
+            assert_eq!(sorted, original);
+        }
+    }
+
+    #[test]
+    fn coarse_phase_improves_the_start_layout() {
+        let (generator, layout_str, evaluator) = setup();
+        let params = Parameters {
+            groups: vec!["aeiou".to_string()],
+            coarse_iterations: 20,
+            refine: false,
+        };
+
+        let result = optimize(
+            &params,
+            &sa::Parameters::default(),
+            &layout_str,
+            "",
+            &generator,
+            true,
+            &evaluator,
+            None,
+            None,
+            None,
+        );
+
+        let start = generator.generate(&layout_str).unwrap();
+        let start_cost = evaluator.evaluate_layout(&start).total_cost();
+        let cost = evaluator.evaluate_layout(&result.layout).total_cost();
+        assert!(cost <= start_cost);
+        assert_eq!(cost, result.coarse_cost);
+        assert_eq!(result.stop_reason, StopReason::Completed);
+
+        let mut symbols: Vec<char> = result.layout_str.chars().collect();
+        let regions = Regions::new(&layout_str, "", &start);
+        let group_regions = regions_of(&regions, &symbols, "aeiou");
+        assert!(group_regions.iter().all(|r| *r == group_regions[0]));
+
+        let mut original: Vec<char> = layout_str.chars().collect();
+        symbols.sort_unstable();
+        original.sort_unstable();
+        assert_eq!(symbols, original);
+    }
+}
@@ -0,0 +1,315 @@
+//! The `portfolio` module runs the genetic algorithm and simulated annealing at the same time
+//! under one shared time and evaluation budget. Both offer their best layouts to a
+//! [`SolutionExchange`]: the genetic algorithm periodically receives the best layout of the
+//! annealing as immigrants, and each annealing chain restarts from the best layout found by
+//! either of them so far.
+
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+use layout_optimization_common::{
+    exchange::{Participant, SolutionExchange},
+    observer::{OptimizationObserver, Progress, SharedObserver},
+    stopping::{SharedBudget, StopReason},
+};
+use layout_optimization_genetic::optimization as genetic;
+use layout_optimization_sa::optimization as sa;
+
+use anyhow::Result;
+use colored::Colorize;
+use serde::Deserialize;
+use std::{
+    fmt,
+    fs::File,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+const GENETIC: &str = "genetic";
+const ANNEALING: &str = "annealing";
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Parameters {
+    /// Wall-clock time limit in seconds of both optimizers together
+    #[serde(default)]
+    pub time_limit: Option<f64>,
+
+    /// Number of layout evaluations of both optimizers together
+    #[serde(default)]
+    pub max_evaluations: Option<u64>,
+
+    /// Minimum number of seconds between two layouts received by the genetic algorithm
+    #[serde(default = "default_exchange_interval")]
+    pub exchange_interval: f64,
+
+    /// Number of individuals inserted into the population of the genetic algorithm per received
+    /// layout (the layout itself and single swaps of it)
+    #[serde(default = "default_immigrants")]
+    pub immigrants: usize,
+}
+
+fn default_exchange_interval() -> f64 {
+    10.0
+}
+
+fn default_immigrants() -> usize {
+    5
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Parameters {
+            time_limit: None,
+            max_evaluations: None,
+            exchange_interval: default_exchange_interval(),
+            immigrants: default_immigrants(),
+        }
+    }
+}
+
+impl Parameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        Ok(serde_yaml::from_reader(f)?)
+    }
+
+    /// The budget shared by both optimizers.
+    pub fn budget(&self) -> SharedBudget {
+        SharedBudget::new(self.time_limit, self.max_evaluations)
+    }
+}
+
+/// Offers the new best layouts of an optimizer to the exchange and forwards its progress to the
+/// caller's observer.
+struct PortfolioObserver {
+    participant: Participant,
+    observer: Option<SharedObserver>,
+}
+
+impl OptimizationObserver for PortfolioObserver {
+    fn on_generation(&mut self, progress: &Progress) {
+        if let Some(observer) = &self.observer {
+            observer.lock().unwrap().on_generation(progress);
+        }
+    }
+
+    fn on_new_best(&mut self, progress: &Progress, layout: &str) {
+        self.participant.on_new_best(progress, layout);
+        if let Some(observer) = &self.observer {
+            observer.lock().unwrap().on_new_best(progress, layout);
+        }
+    }
+
+    fn on_finish(&mut self, progress: &Progress, layout: &str, stop_reason: StopReason) {
+        if let Some(observer) = &self.observer {
+            observer
+                .lock()
+                .unwrap()
+                .on_finish(progress, layout, stop_reason);
+        }
+    }
+}
+
+/// The best layout of one optimizer of the portfolio.
+#[derive(Clone, Debug)]
+pub struct OptimizerResult {
+    pub optimizer: String,
+    pub layout: String,
+    pub cost: f64,
+    pub stop_reason: StopReason,
+}
+
+/// The best layout of the portfolio together with the best layout of each optimizer
+pub struct PortfolioResult {
+    pub layout_str: String,
+    pub layout: Layout,
+    pub stop_reason: StopReason,
+    pub optimizers: Vec<OptimizerResult>,
+    /// Number of annealing chains that were run
+    pub annealing_chains: usize,
+}
+
+impl fmt::Display for PortfolioResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<10} {:>10}  Stopped", "Optimizer", "Cost")?;
+        for result in self.optimizers.iter() {
+            let line = format!(
+                "{:<10} {:>10.2}  {}",
+                result.optimizer, result.cost, result.stop_reason,
+            );
+            match result.layout == self.layout_str {
+                true => writeln!(f, "{}", line.green())?,
+                false => writeln!(f, "{}", line)?,
+            }
+        }
+        write!(f, "Annealing chains: {}", self.annealing_chains)
+    }
+}
+
+/// Runs annealing chains until the shared budget is used up (or, without limits, until the
+/// genetic algorithm is done). Each chain starts from the best layout found so far.
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
+fn run_annealing(
+    params: &sa::Parameters,
+    budget: &SharedBudget,
+    exchange: &SolutionExchange,
+    genetic_done: &AtomicBool,
+    layout_str: &str,
+    fixed_characters: &str,
+    layout_generator: &Box<dyn LayoutGenerator>,
+    start_with_layout: bool,
+    evaluator: &Evaluator,
+    result_cache: Option<Cache<f64>>,
+    observer: impl Fn() -> SharedObserver,
+) -> (OptimizerResult, Layout, usize) {
+    let unlimited = budget.time_limit.is_none() && budget.max_evaluations.is_none();
+    let mut best: Option<(OptimizerResult, Layout)> = None;
+    let mut chains = 0;
+    loop {
+        let restart = exchange.best();
+        let (start_layout, start_from_layout) = match &restart {
+            Some(shared) => {
+                log::info!(
+                    "{} Restarting from the best layout of {} ({:.4})",
+                    format!("Annealing {:>3}:", chains).yellow().bold(),
+                    shared.source,
+                    shared.cost,
+                );
+                (shared.layout.as_str(), true)
+            }
+            None => (layout_str, start_with_layout),
+        };
+
+        let (chain_layout_str, chain_layout, _, stop_reason) = sa::optimize(
+            &format!("Annealing {:>3}", chains),
+            params,
+            start_layout,
+            fixed_characters,
+            layout_generator,
+            start_from_layout,
+            evaluator,
+            false,
+            result_cache.clone(),
+            None,
+            Some(observer()),
+        );
+        chains += 1;
+
+        let cost = evaluator.evaluate_layout(&chain_layout).total_cost();
+        if best.as_ref().map_or(true, |(b, _)| cost < b.cost) {
+            let result = OptimizerResult {
+                optimizer: ANNEALING.to_string(),
+                layout: chain_layout_str,
+                cost,
+                stop_reason,
+            };
+            best = Some((result, chain_layout));
+        }
+
+        let done = match budget.check() {
+            Some(_) => true,
+            None => {
+                stop_reason == StopReason::TargetCostReached
+                    || (unlimited && genetic_done.load(Ordering::Relaxed))
+            }
+        };
+        if done {
+            let (mut result, layout) = best.unwrap();
+            result.stop_reason = budget.check().unwrap_or(stop_reason);
+            return (result, layout, chains);
+        }
+    }
+}
+
+/// Runs the genetic algorithm and simulated annealing at the same time until they are done or
+/// the budget (see [`Parameters::budget`]) is used up, then returns the best layout of both.
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
+pub fn optimize(
+    params: &Parameters,
+    budget: &SharedBudget,
+    genetic_params: &genetic::Parameters,
+    sa_params: &sa::Parameters,
+    layout_str: &str,
+    fixed_characters: &str,
+    layout_generator: &Box<dyn LayoutGenerator>,
+    start_with_layout: bool,
+    evaluator: &Evaluator,
+    result_cache: Option<Cache<f64>>,
+    observer: Option<SharedObserver>,
+) -> PortfolioResult {
+    log::info!("Starting portfolio with: {:?}", params);
+    let exchange = SolutionExchange::new();
+    let interval = Duration::from_secs_f64(params.exchange_interval.max(0.0));
+    let genetic_done = AtomicBool::new(false);
+    let portfolio_observer = |name: &str| -> SharedObserver {
+        Arc::new(Mutex::new(PortfolioObserver {
+            participant: exchange.participant(name, interval),
+            observer: observer.clone(),
+        }))
+    };
+
+    let mut genetic_params = genetic_params.clone();
+    genetic_params.stopping.shared = Some(budget.clone());
+    let mut sa_params = sa_params.clone();
+    sa_params.stopping.shared = Some(budget.clone());
+    sa_params.restarts = None;
+
+    let (genetic, annealing) = rayon::join(
+        || {
+            let result = genetic::optimize(
+                &genetic_params,
+                evaluator,
+                layout_str,
+                layout_generator,
+                fixed_characters,
+                start_with_layout,
+                &[],
+                result_cache.clone(),
+                Some(portfolio_observer(GENETIC)),
+                Some(genetic::Immigration {
+                    participant: exchange.participant(GENETIC, interval),
+                    copies: params.immigrants,
+                }),
+            );
+            genetic_done.store(true, Ordering::Relaxed);
+            result
+        },
+        || {
+            run_annealing(
+                &sa_params,
+                budget,
+                &exchange,
+                &genetic_done,
+                layout_str,
+                fixed_characters,
+                layout_generator,
+                start_with_layout,
+                evaluator,
+                result_cache.clone(),
+                || portfolio_observer(ANNEALING),
+            )
+        },
+    );
+    let (annealing_result, annealing_layout, annealing_chains) = annealing;
+
+    let genetic_result = OptimizerResult {
+        optimizer: GENETIC.to_string(),
+        cost: evaluator.evaluate_layout(&genetic.layout).total_cost(),
+        layout: genetic.layout_str,
+        stop_reason: genetic.stop_reason,
+    };
+    let (best, layout) = match genetic_result.cost < annealing_result.cost {
+        true => (&genetic_result, genetic.layout),
+        false => (&annealing_result, annealing_layout),
+    };
+
+    PortfolioResult {
+        layout_str: best.layout.clone(),
+        layout,
+        stop_reason: best.stop_reason,
+        optimizers: vec![genetic_result.clone(), annealing_result.clone()],
+        annealing_chains,
+    }
+}
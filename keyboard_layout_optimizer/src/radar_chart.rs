@@ -0,0 +1,169 @@
+//! The `radar_chart` module draws several layouts as radar (spider) charts over groups of metrics
+//! (e.g. same-finger bigrams, scissoring, rolls, balance, effort), such that the trade-offs
+//! between the layouts are visible at a glance.
+//!
+//! The cost of each group is normalized by the largest cost of that group among the drawn
+//! layouts, so the worst layout of a group reaches the outer ring and a smaller area is better.
+
+use layout_evaluation::results::EvaluationResult;
+
+use anyhow::Result;
+use plotters::{
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::PI, fs::File, path::Path};
+
+/// Size of the (square) chart (in px)
+const SIZE: u32 = 720;
+/// Radius of the outer ring relative to the size of the chart
+const RADIUS: f64 = 0.32;
+const RINGS: usize = 4;
+
+/// A group of metrics forming an axis of the chart
+#[derive(Clone, Deserialize, Debug)]
+pub struct MetricGroup {
+    pub name: String,
+    /// Names of the metrics as in the evaluation results
+    pub metrics: Vec<String>,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct MetricGroups {
+    pub groups: Vec<MetricGroup>,
+}
+
+impl MetricGroups {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let groups: MetricGroups = serde_yaml::from_reader(f)?;
+        if groups.groups.len() < 3 {
+            anyhow::bail!("A radar chart requires at least three metric groups");
+        }
+
+        Ok(groups)
+    }
+
+    /// The summed weighted costs of the metrics of each group.
+    pub fn costs(&self, result: &EvaluationResult) -> Vec<f64> {
+        self.groups
+            .iter()
+            .map(|group| {
+                group
+                    .metrics
+                    .iter()
+                    .filter_map(|metric| result.metric_cost(metric))
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+/// The group costs of a layout
+#[derive(Clone, Serialize, Debug)]
+pub struct RadarSeries {
+    pub name: String,
+    pub costs: Vec<f64>,
+    /// Costs relative to the largest cost of each group among all series (in [0, 1])
+    pub normalized: Vec<f64>,
+}
+
+/// Normalize the costs of each group by the largest cost of that group among the series.
+pub fn normalize(series: &mut [RadarSeries]) {
+    let n_groups = series.first().map(|s| s.costs.len()).unwrap_or(0);
+    let max_costs: Vec<f64> = (0..n_groups)
+        .map(|i| series.iter().map(|s| s.costs[i]).fold(0.0, f64::max))
+        .collect();
+
+    for s in series.iter_mut() {
+        s.normalized = s
+            .costs
+            .iter()
+            .zip(max_costs.iter())
+            .map(|(cost, max)| match *max > 0.0 {
+                true => (cost / max).max(0.0),
+                false => 0.0,
+            })
+            .collect();
+    }
+}
+
+/// Point of the chart at the given radius (relative to the outer ring) on the axis of a group.
+fn point(group: usize, n_groups: usize, radius: f64) -> (i32, i32) {
+    // the first axis points upwards, the others follow clockwise
+    let angle = 2.0 * PI * group as f64 / n_groups as f64 - PI / 2.0;
+    let center = SIZE as f64 / 2.0;
+    let r = radius * RADIUS * SIZE as f64;
+
+    (
+        (center + r * angle.cos()).round() as i32,
+        (center + r * angle.sin()).round() as i32,
+    )
+}
+
+/// Draw the series (normalized with [`normalize`]) as radar chart to an SVG file.
+pub fn draw<P: AsRef<Path>>(
+    filename: P,
+    title: &str,
+    group_names: &[String],
+    series: &[RadarSeries],
+) -> Result<()> {
+    let n = group_names.len();
+    let root = SVGBackend::new(filename.as_ref(), (SIZE, SIZE)).into_drawing_area();
+    root.fill(&WHITE)?;
+
+    let centered = Pos::new(HPos::Center, VPos::Center);
+    root.draw(&Text::new(
+        title.to_string(),
+        (SIZE as i32 / 2, 24),
+        TextStyle::from(("sans-serif", 20).into_font().style(FontStyle::Bold)).pos(centered),
+    ))?;
+
+    // grid and axes
+    let grid = BLACK.mix(0.25);
+    for ring in 1..=RINGS {
+        let radius = ring as f64 / RINGS as f64;
+        let mut ring_points: Vec<(i32, i32)> = (0..n).map(|i| point(i, n, radius)).collect();
+        ring_points.push(ring_points[0]);
+        root.draw(&PathElement::new(ring_points, grid))?;
+    }
+    for (i, name) in group_names.iter().enumerate() {
+        root.draw(&PathElement::new(
+            vec![point(i, n, 0.0), point(i, n, 1.0)],
+            grid,
+        ))?;
+        root.draw(&Text::new(
+            name.clone(),
+            point(i, n, 1.18),
+            TextStyle::from(("sans-serif", 16).into_font()).pos(centered),
+        ))?;
+    }
+
+    for (s_idx, s) in series.iter().enumerate() {
+        let color = Palette99::pick(s_idx);
+        let points: Vec<(i32, i32)> = s
+            .normalized
+            .iter()
+            .enumerate()
+            .map(|(i, value)| point(i, n, *value))
+            .collect();
+        root.draw(&Polygon::new(points.clone(), color.mix(0.15).filled()))?;
+        let mut outline = points;
+        outline.push(outline[0]);
+        root.draw(&PathElement::new(outline, color.stroke_width(2)))?;
+
+        // legend
+        let y = SIZE as i32 - 24 * (series.len() - s_idx) as i32;
+        root.draw(&Rectangle::new([(24, y - 7), (38, y + 7)], color.filled()))?;
+        root.draw(&Text::new(
+            s.name.clone(),
+            (46, y),
+            TextStyle::from(("sans-serif", 14).into_font()).pos(Pos::new(HPos::Left, VPos::Center)),
+        ))?;
+    }
+
+    root.present()?;
+
+    Ok(())
+}
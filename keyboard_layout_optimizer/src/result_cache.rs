@@ -0,0 +1,194 @@
+//! The `result_cache` module provides a persistent SQLite cache of evaluation results, keyed by
+//! the hash of the (canonical) layout string and the hash of the configuration. Optimizers and
+//! batch evaluations skip layouts that were already evaluated with the same configuration in
+//! previous runs, e.g. the many duplicates in the populations of genetic optimizations.
+//!
+//! The total costs of the configuration are loaded into memory when the cache is opened. New
+//! results are written in batches (and when the cache is dropped).
+
+use crate::results_db::hash_str;
+use layout_evaluation::{cache::CacheStore, results::EvaluationResult};
+
+use ahash::AHashMap;
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{fmt, mem, sync::Mutex};
+
+/// Number of new results that are written at once
+const BATCH_SIZE: usize = 256;
+
+/// A new result that is not yet written
+struct PendingResult {
+    layout_hash: String,
+    total_cost: f64,
+    /// Detailed evaluation result as JSON
+    details: Option<String>,
+}
+
+pub struct ResultCache {
+    connection: Mutex<Connection>,
+    config_hash: String,
+    /// Total costs of the configuration by layout hash
+    total_costs: Mutex<AHashMap<String, f64>>,
+    pending: Mutex<Vec<PendingResult>>,
+}
+
+impl fmt::Debug for ResultCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResultCache")
+            .field("config_hash", &self.config_hash)
+            .finish()
+    }
+}
+
+impl ResultCache {
+    /// Open (or create) the cache at the given path for results of the given configuration.
+    pub fn open(filename: &str, config_hash: &str) -> Result<Self> {
+        let connection = Connection::open(filename)?;
+        connection.execute_batch(
+            "PRAGMA journal_mode = WAL;
+            PRAGMA synchronous = NORMAL;
+            CREATE TABLE IF NOT EXISTS cached_results (
+                layout_hash TEXT NOT NULL,
+                config_hash TEXT NOT NULL,
+                total_cost REAL NOT NULL,
+                details TEXT,
+                PRIMARY KEY (layout_hash, config_hash)
+            );",
+        )?;
+
+        let total_costs = {
+            let mut statement = connection.prepare(
+                "SELECT layout_hash, total_cost FROM cached_results WHERE config_hash = ?1",
+            )?;
+            let rows = statement.query_map(params![config_hash], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+            })?;
+            rows.collect::<Result<AHashMap<String, f64>, _>>()?
+        };
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            config_hash: config_hash.to_string(),
+            total_costs: Mutex::new(total_costs),
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Number of cached results of the configuration.
+    pub fn len(&self) -> usize {
+        self.total_costs.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn add(&self, layout_str: &str, total_cost: f64, details: Option<String>) {
+        let layout_hash = hash_str(layout_str);
+        self.total_costs
+            .lock()
+            .unwrap()
+            .insert(layout_hash.clone(), total_cost);
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.push(PendingResult {
+            layout_hash,
+            total_cost,
+            details,
+        });
+        if pending.len() >= BATCH_SIZE {
+            let batch = mem::take(&mut *pending);
+            drop(pending);
+            self.write(batch);
+        }
+    }
+
+    /// Write the new results to the database.
+    pub fn flush(&self) {
+        let batch = mem::take(&mut *self.pending.lock().unwrap());
+        self.write(batch);
+    }
+
+    fn write(&self, batch: Vec<PendingResult>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let write = || -> Result<()> {
+            let mut connection = self.connection.lock().unwrap();
+            let transaction = connection.transaction()?;
+            for result in batch.iter() {
+                // detailed results replace mere total costs, but not the other way round
+                transaction.execute(
+                    "INSERT INTO cached_results (layout_hash, config_hash, total_cost, details)
+                     VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT (layout_hash, config_hash) DO UPDATE SET
+                        total_cost = excluded.total_cost,
+                        details = COALESCE(excluded.details, details)",
+                    params![
+                        result.layout_hash,
+                        self.config_hash,
+                        result.total_cost,
+                        result.details
+                    ],
+                )?;
+            }
+            transaction.commit()?;
+
+            Ok(())
+        };
+
+        if let Err(e) = write() {
+            log::error!("Could not write results to the result cache: {:?}", e);
+        }
+    }
+}
+
+impl Drop for ResultCache {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Total costs of layouts
+impl CacheStore<f64> for ResultCache {
+    fn get(&self, key: &str) -> Option<f64> {
+        self.total_costs
+            .lock()
+            .unwrap()
+            .get(&hash_str(key))
+            .cloned()
+    }
+
+    fn insert(&self, key: &str, value: &f64) {
+        self.add(key, *value, None);
+    }
+}
+
+/// Detailed evaluation results of layouts
+impl CacheStore<EvaluationResult> for ResultCache {
+    fn get(&self, key: &str) -> Option<EvaluationResult> {
+        // results that are not yet written are not needed again within the same run
+        let connection = self.connection.lock().unwrap();
+        let details: Option<Option<String>> = connection
+            .query_row(
+                "SELECT details FROM cached_results WHERE layout_hash = ?1 AND config_hash = ?2",
+                params![hash_str(key), self.config_hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap_or_else(|e| {
+                log::error!("Could not read from the result cache: {:?}", e);
+                None
+            });
+
+        details
+            .flatten()
+            .and_then(|details| serde_json::from_str(&details).ok())
+    }
+
+    fn insert(&self, key: &str, value: &EvaluationResult) {
+        self.add(key, value.total_cost(), serde_json::to_string(value).ok());
+    }
+}
@@ -0,0 +1,247 @@
+//! The `results_db` module provides an SQLite store for evaluation results. It records
+//! evaluated layouts together with their individual metric costs, so that the best layouts
+//! of long optimization campaigns can be queried later on.
+//!
+//! As an [`EvaluationRecorder`], it records every evaluation of an evaluator. New results are
+//! written in batches (and when the database is dropped).
+
+use layout_evaluation::{evaluation::EvaluationRecorder, results::EvaluationResult};
+
+use anyhow::Result;
+use rusqlite::{params, params_from_iter, Connection, ToSql};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt, mem,
+    str::FromStr,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Number of new results that are written at once
+const BATCH_SIZE: usize = 256;
+
+/// A constraint on the (weighted) cost of a metric, e.g. "Scissoring<0.5".
+#[derive(Debug, Clone)]
+pub struct MetricConstraint {
+    pub metric: String,
+    pub less_than: bool,
+    pub value: f64,
+}
+
+impl FromStr for MetricConstraint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let idx = s
+            .rfind(['<', '>'])
+            .ok_or_else(|| format!("Invalid constraint '{}' (expected 'metric<value')", s))?;
+        let value = f64::from_str(s[idx + 1..].trim())
+            .map_err(|_| format!("Invalid value in constraint '{}'", s))?;
+
+        Ok(MetricConstraint {
+            metric: s[..idx].trim().to_string(),
+            less_than: s[idx..].starts_with('<'),
+            value,
+        })
+    }
+}
+
+/// A single row of a query result.
+#[derive(Debug, Clone)]
+pub struct StoredResult {
+    pub layout: String,
+    pub total_cost: f64,
+    pub config_hash: String,
+    pub timestamp: i64,
+}
+
+/// Hash a string (e.g. a layout or the contents of configuration files) into a hex string. The
+/// hash (SHA-256) is stable across runs, platforms, and compiler versions.
+pub fn hash_str(s: &str) -> String {
+    Sha256::digest(s.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A new result that is not yet written
+struct PendingResult {
+    layout: String,
+    total_cost: f64,
+    /// Weighted cost of each metric
+    metric_costs: Vec<(String, f64)>,
+    timestamp: i64,
+}
+
+pub struct ResultsDb {
+    connection: Mutex<Connection>,
+    config_hash: String,
+    pending: Mutex<Vec<PendingResult>>,
+}
+
+impl fmt::Debug for ResultsDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResultsDb")
+            .field("config_hash", &self.config_hash)
+            .finish()
+    }
+}
+
+impl ResultsDb {
+    /// Open (or create) the results database at the given path.
+    pub fn open(filename: &str) -> Result<Self> {
+        let connection = Connection::open(filename)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS results (
+                id INTEGER PRIMARY KEY,
+                layout_hash TEXT NOT NULL,
+                layout TEXT NOT NULL,
+                total_cost REAL NOT NULL,
+                config_hash TEXT NOT NULL,
+                timestamp INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS metric_costs (
+                result_id INTEGER NOT NULL REFERENCES results(id),
+                metric TEXT NOT NULL,
+                cost REAL NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS metric_costs_result ON metric_costs(result_id, metric);
+            CREATE INDEX IF NOT EXISTS results_cost ON results(total_cost);",
+        )?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            config_hash: String::new(),
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Record results as evaluated with the configuration of the given hash.
+    pub fn with_config_hash(mut self, config_hash: &str) -> Self {
+        self.config_hash = config_hash.to_string();
+        self
+    }
+
+    /// Record an evaluated layout together with its individual (weighted) metric costs.
+    pub fn record(&self, evaluation_result: &EvaluationResult) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let metric_costs = evaluation_result.metric_costs();
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.push(PendingResult {
+            layout: evaluation_result.layout().to_string(),
+            total_cost: evaluation_result.total_cost(),
+            metric_costs,
+            timestamp,
+        });
+        if pending.len() >= BATCH_SIZE {
+            let batch = mem::take(&mut *pending);
+            drop(pending);
+            self.write(batch);
+        }
+    }
+
+    /// Write the new results to the database.
+    pub fn flush(&self) {
+        let batch = mem::take(&mut *self.pending.lock().unwrap());
+        self.write(batch);
+    }
+
+    fn write(&self, batch: Vec<PendingResult>) {
+        if batch.is_empty() {
+            return;
+        }
+
+        let write = || -> Result<()> {
+            let mut connection = self.connection.lock().unwrap();
+            let transaction = connection.transaction()?;
+            for result in batch.iter() {
+                transaction.execute(
+                    "INSERT INTO results (layout_hash, layout, total_cost, config_hash, timestamp)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        hash_str(&result.layout),
+                        result.layout,
+                        result.total_cost,
+                        self.config_hash,
+                        result.timestamp
+                    ],
+                )?;
+                let result_id = transaction.last_insert_rowid();
+                for (metric, cost) in result.metric_costs.iter() {
+                    transaction.execute(
+                        "INSERT INTO metric_costs (result_id, metric, cost) VALUES (?1, ?2, ?3)",
+                        params![result_id, metric, cost],
+                    )?;
+                }
+            }
+            transaction.commit()?;
+
+            Ok(())
+        };
+
+        if let Err(e) = write() {
+            log::error!("Could not record results in the results database: {:?}", e);
+        }
+    }
+
+    /// Query the best distinct layouts satisfying all given metric constraints.
+    pub fn query(
+        &self,
+        constraints: &[MetricConstraint],
+        config_hash: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<StoredResult>> {
+        let mut sql = String::from(
+            "SELECT r.layout, MIN(r.total_cost), r.config_hash, MAX(r.timestamp) FROM results r",
+        );
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        for (i, constraint) in constraints.iter().enumerate() {
+            sql.push_str(&format!(
+                " JOIN metric_costs m{i} ON m{i}.result_id = r.id AND m{i}.metric = ?{} AND m{i}.cost {} ?{}",
+                values.len() + 1,
+                if constraint.less_than { "<" } else { ">" },
+                values.len() + 2,
+                i = i,
+            ));
+            values.push(Box::new(constraint.metric.clone()));
+            values.push(Box::new(constraint.value));
+        }
+        if let Some(config_hash) = config_hash {
+            sql.push_str(&format!(" WHERE r.config_hash = ?{}", values.len() + 1));
+            values.push(Box::new(config_hash.to_string()));
+        }
+        sql.push_str(&format!(
+            " GROUP BY r.layout, r.config_hash ORDER BY 2 ASC LIMIT {}",
+            limit
+        ));
+
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&sql)?;
+        let rows = statement.query_map(params_from_iter(values.iter()), |row| {
+            Ok(StoredResult {
+                layout: row.get(0)?,
+                total_cost: row.get(1)?,
+                config_hash: row.get(2)?,
+                timestamp: row.get(3)?,
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+impl Drop for ResultsDb {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+impl EvaluationRecorder for ResultsDb {
+    fn record(&self, evaluation_result: &EvaluationResult) {
+        ResultsDb::record(self, evaluation_result);
+    }
+}
@@ -0,0 +1,286 @@
+//! The `simulation` module provides a Monte Carlo typing simulation. Passages of text are sampled
+//! from the corpus and typed on each layout, drawing the time of each keystroke from a
+//! distribution that depends on the transition from the previous key (hand switch, same finger,
+//! adjacent finger, ...) and on the finger and cluster direction of the key.
+//!
+//! In contrast to the expected costs of the metrics, the distribution of completion times also
+//! shows how much a layout's speed varies between passages, e.g. due to rare but slow transitions.
+
+use keyboard_layout::{
+    key::{Direction, Finger, Key},
+    layout::Layout,
+};
+use layout_evaluation::ngrams::{Bigrams, Trigrams};
+
+use ahash::AHashMap;
+use anyhow::Result;
+use itertools::Itertools;
+use rand::{distributions::WeightedIndex, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{f64::consts::PI, fs::File};
+
+/// Distribution of the time (in ms) of a keystroke.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TimeDistribution {
+    Constant { ms: f64 },
+    Normal { mean: f64, sd: f64 },
+    LogNormal { median: f64, sigma: f64 },
+}
+
+impl TimeDistribution {
+    /// Draw a time (in ms). Negative times of the normal distribution are truncated to zero.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self {
+            Self::Constant { ms } => *ms,
+            Self::Normal { mean, sd } => (mean + sd * standard_normal(rng)).max(0.0),
+            Self::LogNormal { median, sigma } => median * (sigma * standard_normal(rng)).exp(),
+        }
+    }
+}
+
+/// Draw from the standard normal distribution (Box-Muller transform).
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Time distributions of the transitions between two keystrokes.
+#[derive(Clone, Deserialize, Debug)]
+pub struct TransitionDistributions {
+    /// The keys are typed with different hands
+    pub hand_switch: TimeDistribution,
+    /// The same key is typed again
+    pub same_key: TimeDistribution,
+    /// Different keys are typed with the same finger
+    pub same_finger: TimeDistribution,
+    /// The keys are typed with neighboring fingers of the same hand
+    pub adjacent_finger: TimeDistribution,
+    /// The keys are typed with non-neighboring fingers of the same hand
+    pub other_finger: TimeDistribution,
+    /// One of the keys is typed with the thumb of the same hand
+    pub thumb: TimeDistribution,
+}
+
+impl TransitionDistributions {
+    fn get(&self, from: &Key, to: &Key) -> &TimeDistribution {
+        if from.hand != to.hand {
+            &self.hand_switch
+        } else if from.matrix_position == to.matrix_position {
+            &self.same_key
+        } else if from.finger == Finger::Thumb || to.finger == Finger::Thumb {
+            &self.thumb
+        } else if from.finger == to.finger {
+            &self.same_finger
+        } else if from.finger.distance(&to.finger) == 1 {
+            &self.adjacent_finger
+        } else {
+            &self.other_finger
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct SimulationParameters {
+    /// Number of sampled passages
+    pub passages: usize,
+    /// Number of characters per passage
+    pub passage_length: usize,
+    /// Number of characters per word (including the space)
+    pub chars_per_word: f64,
+    /// Seed for sampling the passages and the keystroke times
+    pub seed: u64,
+    pub transitions: TransitionDistributions,
+    /// Factors for the time of keystrokes depending on the direction of the key in its cluster
+    #[serde(default)]
+    pub direction_factors: AHashMap<Direction, f64>,
+    /// Factors for the time of keystrokes depending on the finger typing the key
+    #[serde(default)]
+    pub finger_factors: AHashMap<Finger, f64>,
+}
+
+impl SimulationParameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let params: SimulationParameters = serde_yaml::from_reader(f)?;
+        Ok(params)
+    }
+}
+
+/// Sample passages from a text (at random positions).
+pub fn sample_text_passages(text: &str, params: &SimulationParameters) -> Vec<Vec<char>> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= params.passage_length {
+        return vec![chars];
+    }
+
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    (0..params.passages)
+        .map(|_| {
+            let start = rng.gen_range(0..=(chars.len() - params.passage_length));
+            chars[start..(start + params.passage_length)].to_vec()
+        })
+        .collect()
+}
+
+/// Sample passages from a Markov chain of the trigrams (each symbol depends on the two previous
+/// ones). The chain restarts at a random bigram if a pair of symbols has no continuation.
+pub fn sample_ngram_passages(
+    bigrams: &Bigrams,
+    trigrams: &Trigrams,
+    params: &SimulationParameters,
+) -> Vec<Vec<char>> {
+    // hash maps are iterated in random order, sort for reproducible samples
+    let mut starts: Vec<(&(char, char), &f64)> = bigrams.grams.iter().collect();
+    starts.sort_by_key(|(pair, _)| **pair);
+    let start_dist = match WeightedIndex::new(starts.iter().map(|(_, w)| **w)) {
+        Ok(dist) => dist,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut continuations: AHashMap<(char, char), (Vec<char>, Vec<f64>)> = AHashMap::default();
+    trigrams
+        .grams
+        .iter()
+        .sorted_by_key(|(trigram, _)| **trigram)
+        .for_each(|((c1, c2, c3), w)| {
+            let (chars, weights) = continuations.entry((*c1, *c2)).or_default();
+            chars.push(*c3);
+            weights.push(*w);
+        });
+    let continuations: AHashMap<(char, char), (Vec<char>, WeightedIndex<f64>)> = continuations
+        .into_iter()
+        .filter_map(|(pair, (chars, weights))| {
+            WeightedIndex::new(weights)
+                .ok()
+                .map(|dist| (pair, (chars, dist)))
+        })
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    (0..params.passages)
+        .map(|_| {
+            let mut passage: Vec<char> = Vec::with_capacity(params.passage_length);
+            while passage.len() < params.passage_length {
+                let n = passage.len();
+                let next = match n >= 2 {
+                    true => continuations.get(&(passage[n - 2], passage[n - 1])),
+                    false => None,
+                };
+                match next {
+                    Some((chars, dist)) => passage.push(chars[dist.sample(&mut rng)]),
+                    None => {
+                        let (c1, c2) = starts[start_dist.sample(&mut rng)].0;
+                        passage.push(*c1);
+                        passage.push(*c2);
+                    }
+                }
+            }
+            passage.truncate(params.passage_length);
+
+            passage
+        })
+        .collect()
+}
+
+/// Distribution of the completion times of the passages on a layout.
+#[derive(Clone, Serialize, Debug)]
+pub struct SimulationResult {
+    /// Mean number of typed characters per passage
+    pub chars: f64,
+    /// Mean number of keystrokes (including modifiers) per passage
+    pub keystrokes: f64,
+    /// Mean completion time (in ms) of a passage
+    pub mean_ms: f64,
+    /// Standard deviation of the completion times (in ms)
+    pub sd_ms: f64,
+    /// Median completion time (in ms)
+    pub p50_ms: f64,
+    /// 95th percentile of the completion times (in ms)
+    pub p95_ms: f64,
+    /// Words per minute at the mean completion time
+    pub mean_wpm: f64,
+    /// Words per minute at the 95th percentile of the completion times
+    pub p95_wpm: f64,
+    /// Number of characters of the passages that can not be typed with the layout
+    pub skipped_chars: usize,
+}
+
+/// Value at the given quantile of sorted values (nearest rank).
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+/// Type the passages on the layout and collect the distribution of their completion times. The
+/// keystroke times are drawn with the same seed for all layouts.
+pub fn simulate(
+    layout: &Layout,
+    passages: &[Vec<char>],
+    params: &SimulationParameters,
+) -> SimulationResult {
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    let factor = |key: &Key| {
+        params.direction_factors.get(&key.direction).unwrap_or(&1.0)
+            * params.finger_factors.get(&key.finger).unwrap_or(&1.0)
+    };
+
+    let mut times = Vec::with_capacity(passages.len());
+    let mut total_chars = 0;
+    let mut total_keystrokes = 0;
+    let mut skipped_chars = 0;
+    for passage in passages {
+        let mut previous: Option<&Key> = None;
+        let mut ms = 0.0;
+        for c in passage {
+            let idx = match layout.get_layerkey_index_for_symbol(c) {
+                Some(idx) => idx,
+                None => {
+                    skipped_chars += 1;
+                    continue;
+                }
+            };
+            total_chars += 1;
+
+            // modifiers are pressed before the key itself
+            let (base, modifiers) = layout.resolve_modifiers(&idx);
+            for k in modifiers.layerkey_indices().iter().chain([base].iter()) {
+                let key = &layout.get_layerkey(k).key;
+                if let Some(previous) = previous {
+                    ms += params.transitions.get(previous, key).sample(&mut rng) * factor(key);
+                }
+                previous = Some(key);
+                total_keystrokes += 1;
+            }
+        }
+        times.push(ms);
+    }
+
+    let n = times.len().max(1) as f64;
+    let chars = total_chars as f64 / n;
+    let mean_ms = times.iter().sum::<f64>() / n;
+    let sd_ms = (times.iter().map(|t| (t - mean_ms).powi(2)).sum::<f64>() / n).sqrt();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let (p50_ms, p95_ms) = match times.is_empty() {
+        true => (0.0, 0.0),
+        false => (quantile(&times, 0.5), quantile(&times, 0.95)),
+    };
+    let wpm = |ms: f64| match ms > 0.0 {
+        true => chars / params.chars_per_word / (ms / 60_000.0),
+        false => 0.0,
+    };
+
+    SimulationResult {
+        chars,
+        keystrokes: total_keystrokes as f64 / n,
+        mean_ms,
+        sd_ms,
+        p50_ms,
+        p95_ms,
+        mean_wpm: wpm(mean_ms),
+        p95_wpm: wpm(p95_ms),
+        skipped_chars,
+    }
+}
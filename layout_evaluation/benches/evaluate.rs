@@ -1,21 +1,25 @@
 use keyboard_layout::{
-    config::LayoutConfig, keyboard::Keyboard, layout_generator::LayoutGenerator,
+    config::LayoutConfig,
+    keyboard::Keyboard,
+    layout::{LayerKey, Layout},
+    layout_generator::LayoutGenerator,
     neo_layout_generator::NeoLayoutGenerator,
 };
 use layout_evaluation::{
     config::EvaluationParameters,
     evaluation::Evaluator,
-    ngram_mapper::on_demand_ngram_mapper::OnDemandNgramMapper,
+    ngram_mapper::{on_demand_ngram_mapper::OnDemandNgramMapper, BigramBuffer, NgramMapper},
     ngrams::{Bigrams, Trigrams, Unigrams},
 };
 
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use serde::Deserialize;
 use std::{path::Path, sync::Arc};
 
-const NGRAMS: &str = "../ngrams/deu_mixed_wiki_web_0.6_eng_news_typical_wiki_web_0.4";
-const LAYOUT_CONFIG: &str = "../config/keyboard/standard.yml";
-const EVALUATION_PARAMETERS: &str = "../config/evaluation/default.yml";
+const NGRAMS: &str = "../ngrams/eng_wiki_1m";
+const LAYOUT_CONFIG: &str = "../config/keyboard/sval.yml";
+const EVALUATION_PARAMETERS: &str = "../config/evaluation/sval.yml";
+const LAYOUT: &str = "□□x□uv□i□wl□azkceo□phsn-bg'r□,f□t□.qdy□jm";
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct NGramConfig {
@@ -24,17 +28,16 @@ pub struct NGramConfig {
     pub trigrams: String,
 }
 
-pub fn evaluate_bench(c: &mut Criterion) {
+fn init() -> (OnDemandNgramMapper, Evaluator, Layout) {
     let layout_config = LayoutConfig::from_yaml(LAYOUT_CONFIG)
-        .unwrap_or_else(|e| panic!("Could not load config file '/keyboard/standard.yml': {}", e));
+        .unwrap_or_else(|e| panic!("Could not load config file '/keyboard/sval.yml': {}", e));
 
     let keyboard = Arc::new(Keyboard::from_yaml_object(layout_config.keyboard));
 
     let layout_generator = NeoLayoutGenerator::from_object(layout_config.base_layout, keyboard);
 
-    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS).unwrap_or_else(|_| {
-        panic!("Could not read evaluation yaml file '/evaluation/default.yml'")
-    });
+    let eval_params = EvaluationParameters::from_yaml(EVALUATION_PARAMETERS)
+        .unwrap_or_else(|_| panic!("Could not read evaluation yaml file '/evaluation/sval.yml'"));
 
     let p = Path::new(NGRAMS).join("1-grams.txt");
     log::info!("Reading unigram file: '{:?}'", p);
@@ -63,20 +66,70 @@ pub fn evaluate_bench(c: &mut Criterion) {
     let ngram_provider =
         OnDemandNgramMapper::with_ngrams(unigrams, bigrams, trigrams, ngram_mapper_config);
 
-    let evaluator =
-        Evaluator::default(Box::new(ngram_provider)).default_metrics(&eval_params.metrics);
+    let evaluator = Evaluator::default(Box::new(ngram_provider.clone()))
+        .default_metrics(&eval_params.metrics)
+        .unwrap();
 
-    let layout = match layout_generator.generate("jduaxphlmwqßctieobnrsgfvüäöyz,.k") {
+    let layout = match layout_generator.generate(LAYOUT) {
         Ok(layout) => layout,
         Err(e) => {
             log::error!("Error in generating layout: {:?}", e);
             panic!("{:?}", e);
         }
     };
+
+    (ngram_provider, evaluator, layout)
+}
+
+pub fn evaluate_bench(c: &mut Criterion) {
+    let (_, evaluator, layout) = init();
     c.bench_function("evaluate", |b| {
         b.iter(|| evaluator.evaluate_layout(&layout));
     });
 }
 
-criterion_group!(benches, evaluate_bench);
+/// A position-based cost as computed by typical bigram metrics.
+#[inline(always)]
+fn bigram_cost(k1: &LayerKey, k2: &LayerKey, weight: f64) -> f64 {
+    if k1.key.hand == k2.key.hand && k1.key.finger == k2.key.finger {
+        weight * (k1.key.cost + k2.key.cost)
+    } else {
+        0.0
+    }
+}
+
+/// Compares iterating over the mapped bigrams stored as [`BigramBuffer`] (index arrays resolved
+/// with the layout's key table) with iterating over a vector of `LayerKey` tuples.
+pub fn ngram_buffers_bench(c: &mut Criterion) {
+    let (ngram_provider, _, layout) = init();
+    let bigrams: BigramBuffer = ngram_provider.map_bigrams(&layout).grams;
+    let tuples: Vec<((&LayerKey, &LayerKey), f64)> = bigrams.iter().collect();
+
+    let mut group = c.benchmark_group("ngram_buffers");
+    group.bench_function("bigram_buffer", |b| {
+        b.iter(|| {
+            let table = bigrams.key_table();
+            black_box(&bigrams)
+                .keys1
+                .iter()
+                .zip(bigrams.keys2.iter())
+                .zip(bigrams.weights.iter())
+                .map(|((idx1, idx2), w)| {
+                    bigram_cost(&table[*idx1 as usize], &table[*idx2 as usize], *w)
+                })
+                .sum::<f64>()
+        });
+    });
+    group.bench_function("layerkey_tuples", |b| {
+        b.iter(|| {
+            black_box(&tuples)
+                .iter()
+                .map(|((k1, k2), w)| bigram_cost(k1, k2, *w))
+                .sum::<f64>()
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, evaluate_bench, ngram_buffers_bench);
 criterion_main!(benches);
@@ -0,0 +1,179 @@
+//! The `bigram_cache` module provides a cache for the individual costs of bigrams across the
+//! layouts evaluated during an optimization.
+//!
+//! Consecutive candidates of an optimizer usually differ in only a few keys. The cost of a bigram
+//! only depends on its two [`LayerKey`]s and its weight, so the costs of all bigrams whose symbols
+//! stayed in place can be reused. The entries are stored densely per pair of [`LayerKeyIndex`]s
+//! (i.e. pair of key positions and layers) together with the symbols at these positions, such
+//! that entries involving moved keys are recomputed (and replaced) when they are requested the
+//! next time. If modifiers placed by symbol move, the layers of all keys may be reached
+//! differently, so all entries are recomputed.
+//!
+//! Parallel evaluations each take their own set of caches, so they do not contend for a lock
+//! while evaluating. The caches are freed together with the last handle.
+//!
+//! [`LayerKey`]: keyboard_layout::layout::LayerKey
+
+use crate::{metrics::bigram_metrics::BigramMetric, ngram_mapper::BigramBuffer};
+
+use keyboard_layout::layout::{LayerKeyIndex, Layout};
+
+use ahash::AHashMap;
+use fnv::FnvHasher;
+use parking_lot::Mutex;
+use std::{
+    hash::{Hash, Hasher},
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+#[derive(Clone, Copy, Debug)]
+struct CachedCost {
+    symbols: (char, char),
+    weight: u64,
+    cost: Option<f64>,
+}
+
+#[derive(Default, Debug)]
+struct MetricCache {
+    total_weight: u64,
+    layout_signature: u64,
+    n_layerkeys: usize,
+    entries: Vec<Option<CachedCost>>,
+}
+
+impl MetricCache {
+    #[inline(always)]
+    fn slot(&self, idx1: LayerKeyIndex, idx2: LayerKeyIndex) -> usize {
+        idx1 as usize * self.n_layerkeys + idx2 as usize
+    }
+
+    /// Drop all entries if they were computed for another total weight or arrangement of the
+    /// layerkeys.
+    fn prepare(&mut self, total_weight: f64, layout_signature: u64, n_layerkeys: usize) {
+        // individual costs may be normalized by the total weight
+        if self.total_weight != total_weight.to_bits()
+            || self.layout_signature != layout_signature
+            || self.n_layerkeys != n_layerkeys
+        {
+            self.entries.clear();
+            self.entries.resize(n_layerkeys * n_layerkeys, None);
+            self.total_weight = total_weight.to_bits();
+            self.layout_signature = layout_signature;
+            self.n_layerkeys = n_layerkeys;
+        }
+    }
+}
+
+/// The caches of one evaluation, by the index of their metric among the evaluator's bigram
+/// metrics.
+type MetricCaches = AHashMap<usize, MetricCache>;
+
+/// A hash of everything the individual costs depend on apart from the symbols of the two keys:
+/// the keyboard, the positions and layers of the layerkeys, and the modifiers activating them.
+fn layout_signature(layout: &Layout) -> u64 {
+    let mut hasher = FnvHasher::default();
+    (Arc::as_ptr(&layout.keyboard) as usize).hash(&mut hasher);
+    for k in layout.layerkeys.iter() {
+        k.key.matrix_position.hash(&mut hasher);
+        k.layer.hash(&mut hasher);
+        k.is_modifier.hash(&mut hasher);
+        mem::discriminant(&k.modifiers).hash(&mut hasher);
+        k.modifiers.layerkey_indices().hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// A handle to the caches of the bigram metrics of an evaluator.
+#[derive(Clone, Debug, Default)]
+pub struct BigramCostCache {
+    /// Caches that are not in use by an evaluation (at most one per parallel evaluation)
+    idle: Arc<Mutex<Vec<MetricCaches>>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl BigramCostCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The total costs of the given metrics (with their indices among the evaluator's bigram
+    /// metrics) in the same order, as the sums of the individual costs, which are taken from the
+    /// cache where possible. The metrics need to sum their individual costs (see
+    /// [`BigramMetric::sums_individual_costs`]).
+    pub fn total_costs(
+        &self,
+        metrics: &[(usize, &dyn BigramMetric)],
+        bigrams: &BigramBuffer,
+        total_weight: f64,
+        layout: &Layout,
+    ) -> Vec<f64> {
+        if metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let mut caches = self.idle.lock().pop().unwrap_or_default();
+        let layout_signature = layout_signature(layout);
+        let n_layerkeys = layout.layerkeys.len();
+        let (mut hits, mut misses) = (0, 0);
+
+        let total_costs = metrics
+            .iter()
+            .map(|(metric_idx, metric)| {
+                let cache = caches.entry(*metric_idx).or_default();
+                cache.prepare(total_weight, layout_signature, n_layerkeys);
+
+                let mut total_cost = 0.0;
+                for i in 0..bigrams.len() {
+                    let (idx1, idx2) = (bigrams.keys1[i], bigrams.keys2[i]);
+                    let weight = bigrams.weights[i];
+                    let (k1, k2) = bigrams.keys(i);
+                    let symbols = (k1.symbol, k2.symbol);
+                    let slot = cache.slot(idx1, idx2);
+                    let cost = match cache.entries[slot] {
+                        Some(cached)
+                            if cached.symbols == symbols && cached.weight == weight.to_bits() =>
+                        {
+                            hits += 1;
+                            cached.cost
+                        }
+                        _ => {
+                            misses += 1;
+                            let cost = metric.individual_cost(k1, k2, weight, total_weight, layout);
+                            cache.entries[slot] = Some(CachedCost {
+                                symbols,
+                                weight: weight.to_bits(),
+                                cost,
+                            });
+                            cost
+                        }
+                    };
+                    total_cost += cost.unwrap_or(0.0);
+                }
+
+                total_cost
+            })
+            .collect();
+
+        self.idle.lock().push(caches);
+        self.hits.fetch_add(hits, Ordering::Relaxed);
+        self.misses.fetch_add(misses, Ordering::Relaxed);
+
+        total_costs
+    }
+
+    /// Share of the individual costs that were taken from the cache so far.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits.load(Ordering::Relaxed) as f64;
+        let misses = self.misses.load(Ordering::Relaxed) as f64;
+        match hits + misses > 0.0 {
+            true => hits / (hits + misses),
+            false => 0.0,
+        }
+    }
+}
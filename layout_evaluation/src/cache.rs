@@ -3,9 +3,17 @@ use colored::Colorize;
 use parking_lot::Mutex;
 use std::{fmt, sync::Arc};
 
+/// A persistent store backing a [`Cache`], e.g. for reusing results of previous runs. Values
+/// missing in the cache are looked up in the store, and computed values are added to it.
+pub trait CacheStore<T>: Send + Sync + fmt::Debug {
+    fn get(&self, key: &str) -> Option<T>;
+    fn insert(&self, key: &str, value: &T);
+}
+
 #[derive(Clone, Debug)]
 pub struct Cache<T: Clone> {
     cache: Arc<Mutex<AHashMap<String, T>>>,
+    store: Option<Arc<dyn CacheStore<T>>>,
 }
 
 impl<T: Clone> Default for Cache<T> {
@@ -18,9 +26,16 @@ impl<T: Clone> Cache<T> {
     pub fn new() -> Self {
         Self {
             cache: Arc::new(Mutex::new(AHashMap::default())),
+            store: None,
         }
     }
 
+    /// Back the cache with a persistent store.
+    pub fn with_store(mut self, store: Arc<dyn CacheStore<T>>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
     pub fn get_or_insert_with<F: Fn() -> T>(&self, elem: &str, f: F) -> T {
         let cache_val;
         {
@@ -28,7 +43,17 @@ impl<T: Clone> Cache<T> {
             cache_val = cache.get(elem).cloned();
         }
         cache_val.unwrap_or_else(|| {
-            let res = f();
+            let stored = self.store.as_ref().and_then(|store| store.get(elem));
+            let res = match stored {
+                Some(res) => res,
+                None => {
+                    let res = f();
+                    if let Some(store) = &self.store {
+                        store.insert(elem, &res);
+                    }
+                    res
+                }
+            };
             {
                 let mut cache = self.cache.lock();
                 cache.insert(elem.to_owned(), res.clone());
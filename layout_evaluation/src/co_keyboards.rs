@@ -0,0 +1,315 @@
+//! The `co_keyboards` module evaluates a layout on further keyboards besides the one it is
+//! optimized for, so that a single layout can be optimized for several keyboards at once (e.g.
+//! for users who type on a Svalboard and on a column-staggered keyboard and refuse to learn two
+//! layouts).
+//!
+//! The layout is transferred to each further keyboard by placing every symbol on the key with the
+//! same hand, finger, and direction (see [`CoKeyboard::new`]), so that the same symbols are typed
+//! with the same fingers on all keyboards. The total costs on the individual keyboards are then
+//! combined into a single cost (see [`combine_keyboard_costs`]).
+//!
+//! [`combine_keyboard_costs`]: crate::results::combine_keyboard_costs
+
+use crate::{
+    evaluation::Evaluator,
+    results::{EvaluationResult, KeyboardCost},
+};
+
+use keyboard_layout::{key::Key, layout::Layout, layout_generator::LayoutGenerator};
+
+use ahash::AHashSet;
+
+/// A further keyboard a layout is evaluated on.
+#[derive(Clone, Debug)]
+pub struct CoKeyboard {
+    name: String,
+    weight: f64,
+    reference_cost: Option<f64>,
+    layout_generator: Box<dyn LayoutGenerator>,
+    evaluator: Evaluator,
+    /// Symbols of this keyboard's layout string
+    symbols: Vec<char>,
+    /// For each position of this keyboard's layout string, the position of the main layout string
+    /// whose symbol it receives
+    sources: Vec<usize>,
+}
+
+impl CoKeyboard {
+    /// Prepare the transfer of layouts of the main keyboard (with base layout `main_base`) to
+    /// this keyboard (with base layout `base`, generated by `layout_generator`). Each key of this
+    /// keyboard receives the symbol of the first key of the main keyboard with the same hand,
+    /// finger, and direction. The remaining keys receive the remaining symbols, the cheapest keys
+    /// getting the symbols of the cheapest keys.
+    pub fn new(
+        name: &str,
+        weight: f64,
+        reference_cost: Option<f64>,
+        layout_generator: Box<dyn LayoutGenerator>,
+        base: &Layout,
+        main_base: &Layout,
+        evaluator: Evaluator,
+    ) -> Self {
+        let keys = base.non_fixed_keys();
+        let main_keys = main_base.non_fixed_keys();
+
+        let mut sources: Vec<Option<usize>> = vec![None; keys.len()];
+        let mut taken = vec![false; main_keys.len()];
+        for (key, source) in keys.iter().zip(sources.iter_mut()) {
+            let same = (0..main_keys.len()).find(|i| {
+                !taken[*i]
+                    && main_keys[*i].hand == key.hand
+                    && main_keys[*i].finger == key.finger
+                    && main_keys[*i].direction == key.direction
+            });
+            if let Some(i) = same {
+                taken[i] = true;
+                *source = Some(i);
+            }
+        }
+
+        let free = by_cost(&keys, (0..keys.len()).filter(|j| sources[*j].is_none()));
+        let rest = by_cost(&main_keys, (0..main_keys.len()).filter(|i| !taken[*i]));
+        free.into_iter()
+            .zip(rest)
+            .for_each(|(j, i)| sources[j] = Some(i));
+
+        // keys without any source (if this keyboard has more keys) receive the leftover symbols
+        let sources = sources
+            .into_iter()
+            .map(|s| s.unwrap_or(usize::MAX))
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            weight,
+            reference_cost,
+            layout_generator,
+            evaluator,
+            symbols: base.as_text().chars().collect(),
+            sources,
+        }
+    }
+
+    /// Name of the keyboard.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The layout string on this keyboard for the given layout string of the main keyboard.
+    /// Symbols of the main layout that do not exist on this keyboard are dropped, and the keys
+    /// left empty receive the symbols of this keyboard that did not get placed.
+    pub fn layout_string(&self, main_layout_str: &str) -> String {
+        let main: Vec<char> = main_layout_str.chars().collect();
+        let available: AHashSet<char> = self.symbols.iter().copied().collect();
+
+        let mut used: AHashSet<char> = AHashSet::default();
+        let placed: Vec<Option<char>> = self
+            .sources
+            .iter()
+            .map(|i| {
+                main.get(*i)
+                    .copied()
+                    .filter(|c| available.contains(c) && used.insert(*c))
+            })
+            .collect();
+
+        let mut leftovers = self.symbols.iter().filter(|c| !used.contains(c));
+        placed
+            .into_iter()
+            .map(|c| c.or_else(|| leftovers.next().copied()).unwrap_or(' '))
+            .collect()
+    }
+
+    /// The total cost of the given layout string of the main keyboard on this keyboard.
+    pub fn cost(&self, main_layout_str: &str) -> KeyboardCost {
+        let layout_str = self.layout_string(main_layout_str);
+        let layout = self
+            .layout_generator
+            .generate(&layout_str)
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Could not transfer layout '{}' to keyboard '{}': {:?}",
+                    main_layout_str, self.name, e
+                )
+            });
+        let cost = tracing::debug_span!("keyboard", keyboard = %self.name)
+            .in_scope(|| self.evaluator.evaluate_layout(&layout).total_cost());
+
+        KeyboardCost {
+            name: self.name.clone(),
+            layout: layout_str,
+            cost,
+            weight: self.weight,
+            reference_cost: self.reference_cost,
+        }
+    }
+}
+
+/// The indices of the given keys in the order of increasing key cost.
+fn by_cost(keys: &[&Key], indices: impl Iterator<Item = usize>) -> Vec<usize> {
+    let mut indices: Vec<usize> = indices.collect();
+    indices.sort_by(|a, b| keys[*a].cost.partial_cmp(&keys[*b].cost).unwrap());
+    indices
+}
+
+/// The main keyboard (the one the layouts are generated for) together with the further keyboards
+/// to evaluate its layouts on.
+#[derive(Clone, Debug)]
+pub struct CoKeyboards {
+    /// Name of the main keyboard
+    pub name: String,
+    /// Weight of the main keyboard in the combined cost
+    pub weight: f64,
+    /// Cost of a good layout for the main keyboard alone
+    pub reference_cost: Option<f64>,
+    /// Penalty per unit of cost above the reference cost of a keyboard
+    pub deviation_penalty: f64,
+    /// The further keyboards
+    pub keyboards: Vec<CoKeyboard>,
+}
+
+impl CoKeyboards {
+    /// Attach the total costs of the layout on all keyboards to its evaluation result on the main
+    /// keyboard.
+    pub fn attach(&self, result: EvaluationResult, layout: &Layout) -> EvaluationResult {
+        let layout_str = layout.as_text();
+        let mut keyboard_costs = vec![KeyboardCost {
+            name: self.name.clone(),
+            layout: layout_str.clone(),
+            cost: result.total_cost(),
+            weight: self.weight,
+            reference_cost: self.reference_cost,
+        }];
+        keyboard_costs.extend(self.keyboards.iter().map(|k| k.cost(&layout_str)));
+
+        result.with_keyboard_costs(keyboard_costs, self.deviation_penalty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        results::combine_keyboard_costs,
+        test_utils::{generator, key_cost_evaluator, layout_config},
+    };
+
+    /// The Svalboard as main keyboard and the Corne as further keyboard
+    fn setup() -> (Layout, CoKeyboard) {
+        let main_base = generator(layout_config("sval.yml"))
+            .generate_base()
+            .unwrap();
+        let corne = generator(layout_config("crkbd.yml"));
+        let base = corne.generate_base().unwrap();
+        let co_keyboard = CoKeyboard::new(
+            "Corne",
+            1.0,
+            None,
+            Box::new(corne),
+            &base,
+            &main_base,
+            key_cost_evaluator(),
+        );
+
+        (main_base, co_keyboard)
+    }
+
+    fn sorted(s: &str) -> Vec<char> {
+        let mut chars: Vec<char> = s.chars().collect();
+        chars.sort_unstable();
+        chars
+    }
+
+    #[test]
+    fn transfers_symbols_to_the_same_finger_and_direction() {
+        let (main_base, co_keyboard) = setup();
+        let main_keys = main_base.non_fixed_keys();
+        let main: Vec<char> = main_base.as_text().chars().collect();
+        let keys = co_keyboard
+            .layout_generator
+            .generate(&co_keyboard.symbols.iter().collect::<String>())
+            .unwrap();
+        let keys = keys.non_fixed_keys();
+
+        // swapping two symbols of the main layout swaps them on the further keyboard as well
+        let mut swapped = main.clone();
+        swapped.swap(0, 1);
+        for layout in [main.clone(), swapped] {
+            let layout_str: String = layout.iter().collect();
+            let transferred: Vec<char> = co_keyboard.layout_string(&layout_str).chars().collect();
+            assert_eq!(
+                sorted(&transferred.iter().collect::<String>()),
+                sorted(&co_keyboard.symbols.iter().collect::<String>())
+            );
+
+            let mut matched = 0;
+            for (j, i) in co_keyboard.sources.iter().enumerate() {
+                let (key, main_key) = match main_keys.get(*i) {
+                    Some(main_key) => (keys[j], main_key),
+                    None => continue,
+                };
+                let same = key.hand == main_key.hand
+                    && key.finger == main_key.finger
+                    && key.direction == main_key.direction;
+                if same && co_keyboard.symbols.contains(&layout[*i]) {
+                    assert_eq!(transferred[j], layout[*i]);
+                    matched += 1;
+                }
+            }
+            assert!(matched > 0);
+        }
+    }
+
+    #[test]
+    fn combines_the_costs_of_all_keyboards() {
+        let (main_base, co_keyboard) = setup();
+        let main_evaluator = key_cost_evaluator();
+        let co_cost = co_keyboard.cost(&main_base.as_text());
+        assert_eq!(co_cost.name, "Corne");
+        assert_eq!(
+            co_cost.layout,
+            co_keyboard.layout_string(&main_base.as_text())
+        );
+
+        let co_keyboards = CoKeyboards {
+            name: "Svalboard".to_string(),
+            weight: 3.0,
+            reference_cost: None,
+            deviation_penalty: 0.0,
+            keyboards: vec![co_keyboard],
+        };
+        let result = main_evaluator.evaluate_layout(&main_base);
+        let main_cost = result.total_cost();
+        let result = co_keyboards.attach(result, &main_base);
+
+        let names: Vec<&str> = result
+            .keyboard_costs()
+            .iter()
+            .map(|k| k.name.as_str())
+            .collect();
+        assert_eq!(names, ["Svalboard", "Corne"]);
+        let expected = (3.0 * main_cost + co_cost.cost) / 4.0;
+        assert!((result.total_cost() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn penalizes_costs_above_the_reference_costs() {
+        let keyboard_cost = |cost: f64, weight: f64, reference_cost: Option<f64>| KeyboardCost {
+            name: String::new(),
+            layout: String::new(),
+            cost,
+            weight,
+            reference_cost,
+        };
+        let keyboard_costs = vec![
+            keyboard_cost(10.0, 1.0, Some(8.0)),
+            keyboard_cost(20.0, 3.0, Some(25.0)),
+            keyboard_cost(30.0, 0.0, None),
+        ];
+
+        let mean = (10.0 + 3.0 * 20.0) / 4.0;
+        assert!((combine_keyboard_costs(&keyboard_costs, 0.0) - mean).abs() < 1e-9);
+        assert!((combine_keyboard_costs(&keyboard_costs, 0.5) - (mean + 1.0)).abs() < 1e-9);
+        assert_eq!(combine_keyboard_costs(&keyboard_costs[2..], 1.0), 0.0);
+    }
+}
@@ -0,0 +1,134 @@
+//! The `compressed` module reads corpus and ngram files that may be compressed, such that large
+//! text dumps do not need to be decompressed before building frequencies. The format is derived
+//! from the file extension:
+//! - `.gz`: gzip (also multiple concatenated members)
+//! - `.zst`: zstandard (also multiple concatenated frames)
+//! - `.zip`: all files of the archive, concatenated in the order of their names
+//!
+//! Other files are read as plain text. Compressed files require the `compression` feature (which
+//! `keyboard_layout_optimizer` enables).
+
+use anyhow::Result;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the supported compression formats.
+pub const COMPRESSED_EXTENSIONS: [&str; 3] = ["gz", "zst", "zip"];
+
+/// Whether the file has the extension of a supported compression format.
+pub fn is_compressed<P: AsRef<Path>>(filename: P) -> bool {
+    filename
+        .as_ref()
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| COMPRESSED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Path of the file `name` in the directory `dir`. If it does not exist, but a compressed
+/// version (e.g. `1-grams.txt.gz`) does, that one is returned instead.
+pub fn find_file<P: AsRef<Path>>(dir: P, name: &str) -> PathBuf {
+    let p = dir.as_ref().join(name);
+    if p.exists() {
+        return p;
+    }
+
+    COMPRESSED_EXTENSIONS
+        .iter()
+        .map(|ext| dir.as_ref().join(format!("{}.{}", name, ext)))
+        .find(|p| p.exists())
+        .unwrap_or(p)
+}
+
+/// Read the (possibly compressed) file into a string.
+pub fn read_to_string<P: AsRef<Path>>(filename: P) -> Result<String> {
+    let filename = filename.as_ref();
+    let extension = filename
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    match extension.as_deref() {
+        Some("gz") => decoders::gzip(filename),
+        Some("zst") => decoders::zstd(filename),
+        Some("zip") => decoders::zip(filename),
+        _ => Ok(fs::read_to_string(filename)?),
+    }
+}
+
+#[cfg(feature = "compression")]
+mod decoders {
+    use anyhow::Result;
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader, Read},
+        path::Path,
+    };
+
+    pub fn gzip(filename: &Path) -> Result<String> {
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(BufReader::new(File::open(filename)?))
+            .read_to_string(&mut text)?;
+
+        Ok(text)
+    }
+
+    pub fn zstd(filename: &Path) -> Result<String> {
+        let mut reader = BufReader::new(File::open(filename)?);
+        let mut data = Vec::new();
+        while !reader.fill_buf()?.is_empty() {
+            ruzstd::StreamingDecoder::new(&mut reader)
+                .map_err(|e| anyhow::anyhow!("Invalid zstd frame in {:?}: {}", filename, e))?
+                .read_to_end(&mut data)?;
+        }
+
+        Ok(String::from_utf8(data)?)
+    }
+
+    pub fn zip(filename: &Path) -> Result<String> {
+        let mut archive = zip::ZipArchive::new(BufReader::new(File::open(filename)?))?;
+        let mut names: Vec<String> = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| name.to_string())
+            .collect();
+        names.sort();
+
+        let mut text = String::new();
+        for name in names {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            archive.by_name(&name)?.read_to_string(&mut text)?;
+        }
+
+        Ok(text)
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+mod decoders {
+    use anyhow::{bail, Result};
+    use std::path::Path;
+
+    fn unsupported(filename: &Path) -> Result<String> {
+        bail!(
+            "Can not read {:?}: compressed files require the \"compression\" feature",
+            filename
+        )
+    }
+
+    pub fn gzip(filename: &Path) -> Result<String> {
+        unsupported(filename)
+    }
+
+    pub fn zstd(filename: &Path) -> Result<String> {
+        unsupported(filename)
+    }
+
+    pub fn zip(filename: &Path) -> Result<String> {
+        unsupported(filename)
+    }
+}
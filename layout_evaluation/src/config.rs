@@ -1,33 +1,209 @@
 use crate::{
-    evaluation::MetricParameters, ngram_mapper::on_demand_ngram_mapper::NgramMapperConfig,
+    evaluation::{LayerOverrideParameters, MetricParameters},
+    ngram_mapper::on_demand_ngram_mapper::NgramMapperConfig,
     ngrams::NgramsConfig,
 };
 
-use anyhow::Result;
+use keyboard_layout::config::merge;
+
+use anyhow::{bail, Context, Result};
 use serde::Deserialize;
-use std::{fs::File, str::FromStr};
+use serde_yaml::Value;
+use std::{fs, path::Path, str::FromStr};
+
+/// Key of a configuration file naming the file it is based on (relative to its own directory).
+const EXTENDS_KEY: &str = "extends";
+
+/// Key of the metric parameters that are overridden for some layers.
+const LAYER_OVERRIDES_KEY: &str = "layer_overrides";
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct EvaluationParameters {
     pub metrics: MetricParameters,
     pub ngrams: NgramsConfig,
     pub ngram_mapper: NgramMapperConfig,
+    /// Metric parameters for the ngrams of some layers (see [`LayerOverrideParameters`]). An
+    /// override only needs to contain the values it changes, the remaining values are taken from
+    /// the corresponding metric in `metrics`.
+    #[serde(default)]
+    pub layer_overrides: Vec<LayerOverrideParameters>,
 }
 
 impl EvaluationParameters {
     pub fn from_yaml(filename: &str) -> Result<Self> {
-        let f = File::open(filename)?;
-        let k: EvaluationParameters = serde_yaml::from_reader(f)?;
+        Self::from_yaml_with_overlays(filename, &[])
+    }
 
-        Ok(k)
+    /// Read the parameters from a base file with the given overlay files applied on top (see
+    /// [`ResolvedConfig`]).
+    pub fn from_yaml_with_overlays(filename: &str, overlays: &[String]) -> Result<Self> {
+        ResolvedConfig::from_files(filename, overlays)?.parameters()
     }
 }
 
 impl FromStr for EvaluationParameters {
     type Err = serde_yaml::Error;
     fn from_str(evaluation_params_str: &str) -> Result<Self, Self::Err> {
-        let cfg: EvaluationParameters = serde_yaml::from_str(evaluation_params_str)?;
+        let mut value: Value = serde_yaml::from_str(evaluation_params_str)?;
+        if value.get(LAYER_OVERRIDES_KEY).is_none() {
+            return serde_yaml::from_str(evaluation_params_str);
+        }
+
+        expand_layer_overrides(&mut value);
+        let cfg: EvaluationParameters = serde_yaml::from_str(&serde_yaml::to_string(&value)?)?;
 
         Ok(cfg)
     }
 }
+
+/// An evaluation configuration composed of several files.
+///
+/// A file may name the file it is based on with `extends: <filename>` and only contain the
+/// values it changes. Overlay files are applied in the given order after the base file.
+/// Mappings are merged key by key, all other values (including lists) replace the previous
+/// value, so that e.g. an overlay containing only `metrics: {key_costs: {weight: 80.0}}` changes
+/// a single weight. A file extended by an overlay is skipped if it was already applied.
+#[derive(Clone, Debug)]
+pub struct ResolvedConfig {
+    /// The merged configuration
+    pub value: Value,
+    /// All files that were merged, in the order they were applied
+    pub files: Vec<String>,
+}
+
+impl ResolvedConfig {
+    pub fn from_files(filename: &str, overlays: &[String]) -> Result<Self> {
+        let mut resolved = Self {
+            value: Value::Mapping(Default::default()),
+            files: Vec::new(),
+        };
+        for filename in std::iter::once(filename).chain(overlays.iter().map(|s| s.as_str())) {
+            resolved.apply_file(filename, &mut Vec::new())?;
+        }
+
+        Ok(resolved)
+    }
+
+    fn apply_file(&mut self, filename: &str, chain: &mut Vec<String>) -> Result<()> {
+        if chain.iter().any(|f| same_file(f, filename)) {
+            bail!(
+                "Cyclic 'extends' in evaluation config: {} -> {}",
+                chain.join(" -> "),
+                filename
+            );
+        }
+        chain.push(filename.to_string());
+
+        let content = fs::read_to_string(filename)
+            .with_context(|| format!("Could not read config file {}", filename))?;
+        let mut value: Value = serde_yaml::from_str(&content)
+            .with_context(|| format!("Could not parse config file {}", filename))?;
+
+        if let Some(extends) = value.as_mapping_mut().and_then(|m| m.remove(EXTENDS_KEY)) {
+            let extends = extends
+                .as_str()
+                .with_context(|| format!("'{}' in {} is not a filename", EXTENDS_KEY, filename))?;
+            let extends = Path::new(filename)
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .join(extends);
+            let extends = extends.to_string_lossy();
+            if !self.files.iter().any(|f| same_file(f, &extends)) {
+                self.apply_file(&extends, chain)?;
+            }
+        }
+
+        merge(&mut self.value, value);
+        self.files.push(filename.to_string());
+        chain.pop();
+
+        Ok(())
+    }
+
+    /// Override a single value with an assignment like `metrics.scissoring.weight=3.5`. The
+    /// value is parsed as YAML and list elements are addressed by their index. Returns the
+    /// previous value (if any).
+    pub fn set(&mut self, assignment: &str) -> Result<Option<Value>> {
+        let (path, value) = assignment
+            .split_once('=')
+            .with_context(|| format!("'{}' is not of the form <path>=<value>", assignment))?;
+        let value: Value = serde_yaml::from_str(value)
+            .with_context(|| format!("Could not parse value of '{}'", assignment))?;
+
+        let keys: Vec<&str> = path.trim().split('.').collect();
+        if keys.iter().any(|key| key.is_empty()) {
+            bail!("Invalid path '{}'", path);
+        }
+        let mut target = &mut self.value;
+        for (i, key) in keys.iter().enumerate() {
+            let is_last = i + 1 == keys.len();
+            target = match target {
+                Value::Mapping(m) => {
+                    let key = Value::String(key.to_string());
+                    if is_last {
+                        return Ok(m.insert(key, value));
+                    }
+                    m.entry(key)
+                        .or_insert_with(|| Value::Mapping(Default::default()))
+                }
+                Value::Sequence(seq) => {
+                    let element = key
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(move |index| seq.get_mut(index))
+                        .with_context(|| format!("No list element '{}' in '{}'", key, path))?;
+                    if is_last {
+                        return Ok(Some(std::mem::replace(element, value)));
+                    }
+                    element
+                }
+                _ => bail!("'{}' in '{}' is not a mapping or a list", key, path),
+            };
+        }
+
+        unreachable!("the path contains at least one key")
+    }
+
+    /// The merged configuration as YAML.
+    pub fn to_yaml(&self) -> String {
+        serde_yaml::to_string(&self.value).unwrap_or_default()
+    }
+
+    pub fn parameters(&self) -> Result<EvaluationParameters> {
+        // round trip through a string as some configs use `null` keys that are
+        // only accepted when deserializing from a string
+        let mut value = self.value.clone();
+        expand_layer_overrides(&mut value);
+        Ok(serde_yaml::from_str(&serde_yaml::to_string(&value)?)?)
+    }
+}
+
+fn same_file(filename1: &str, filename2: &str) -> bool {
+    let canonical = |filename: &str| fs::canonicalize(filename).unwrap_or_else(|_| filename.into());
+    canonical(filename1) == canonical(filename2)
+}
+
+/// Complete the metrics of the layer overrides with the values of the corresponding metrics of
+/// the configuration (see [`EvaluationParameters::layer_overrides`]).
+fn expand_layer_overrides(value: &mut Value) {
+    let metrics = match value.get("metrics") {
+        Some(Value::Mapping(metrics)) => metrics.clone(),
+        _ => return,
+    };
+    let overrides = match value.get_mut(LAYER_OVERRIDES_KEY) {
+        Some(Value::Sequence(overrides)) => overrides,
+        _ => return,
+    };
+
+    for layer_override in overrides.iter_mut() {
+        if let Some(Value::Mapping(override_metrics)) = layer_override.get_mut("metrics") {
+            for (name, params) in override_metrics.iter_mut() {
+                if let Some(base) = metrics.get(name) {
+                    let mut expanded = base.clone();
+                    merge(&mut expanded, std::mem::replace(params, Value::Null));
+                    *params = expanded;
+                }
+            }
+        }
+    }
+}
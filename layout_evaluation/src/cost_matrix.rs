@@ -0,0 +1,191 @@
+//! The `cost_matrix` module evaluates bigram metrics in matrix form.
+//!
+//! For most bigram metrics, the cost of a bigram is its weight times a factor that only depends
+//! on the positions (and layers and modifier types) of its two keys, not on the symbols at these
+//! positions (see [`BigramMetric::position_based`]). These factors are computed once per pair of
+//! [`LayerKeyIndex`]s and stored densely, one row of costs (all such metrics side by side) per
+//! pair. The total costs of a layout are then the product of the sparse bigram weights with the
+//! dense cost matrix: a single pass over the bigrams that accumulates `weight * row` into the
+//! totals of all metrics at once. The inner loop is a plain multiply-add over contiguous memory,
+//! which the compiler vectorizes, and no metric code is called during the evaluation.
+//!
+//! The cost factors only change if the layerkeys of a layout lie at other positions, which only
+//! happens for modifiers that are placed by symbol. The matrices of the last few such arrangements
+//! are kept in sets, of which each parallel evaluation takes its own, so they do not contend for a
+//! lock while evaluating. The matrices are freed together with the last handle.
+//!
+//! A matrix takes `8 * layerkeys² * metrics` bytes, e.g. about 7 MB for 300 layerkeys and 10
+//! metrics. With up to [`DEFAULT_MAX_MATRICES`] matrices per set (see [`CostMatrix::new`]) and one
+//! set per parallel evaluation, the matrices of 16 threads can take about 900 MB in that case.
+//! Layouts without modifiers placed by symbol only ever need one matrix per set.
+//!
+//! [`LayerKeyIndex`]: keyboard_layout::layout::LayerKeyIndex
+
+use crate::{metrics::bigram_metrics::BigramMetric, ngram_mapper::BigramBuffer};
+
+use keyboard_layout::{
+    key::MatrixPosition,
+    layout::{LayerModifierType, Layout},
+};
+
+use parking_lot::Mutex;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+/// Default number of matrices (for different arrangements of the layerkeys) kept per set.
+pub const DEFAULT_MAX_MATRICES: usize = 8;
+
+/// What the cost factors of a layerkey depend on.
+type KeySignature = (MatrixPosition, u8, LayerModifierType);
+
+#[derive(Debug)]
+struct Matrix {
+    /// Address of the keyboard the matrix was built for
+    keyboard: usize,
+    /// Signature of each layerkey of the layouts the matrix was built for
+    signature: Vec<KeySignature>,
+    /// Indices of the metrics (among the evaluator's bigram metrics) in the rows
+    metrics: Vec<usize>,
+    /// Cost factors of all metrics for each pair of layerkeys
+    costs: Vec<f64>,
+}
+
+impl Matrix {
+    fn build(metrics: &[(usize, &dyn BigramMetric)], layout: &Layout) -> Self {
+        let n_layerkeys = layout.layerkeys.len();
+        let n_metrics = metrics.len();
+        let mut costs = vec![0.0; n_layerkeys * n_layerkeys * n_metrics];
+        for (idx1, k1) in layout.layerkeys.iter().enumerate() {
+            for (idx2, k2) in layout.layerkeys.iter().enumerate() {
+                let row = (idx1 * n_layerkeys + idx2) * n_metrics;
+                for (m, (_, metric)) in metrics.iter().enumerate() {
+                    costs[row + m] = metric
+                        .individual_cost(k1, k2, 1.0, 1.0, layout)
+                        .unwrap_or(0.0);
+                }
+            }
+        }
+
+        Self {
+            keyboard: keyboard_address(layout),
+            signature: signature(layout),
+            metrics: metrics.iter().map(|(i, _)| *i).collect(),
+            costs,
+        }
+    }
+
+    fn fits(&self, metrics: &[(usize, &dyn BigramMetric)], layout: &Layout) -> bool {
+        self.keyboard == keyboard_address(layout)
+            && self.metrics.len() == metrics.len()
+            && self.metrics.iter().zip(metrics).all(|(i, (j, _))| i == j)
+            && self.signature.len() == layout.layerkeys.len()
+            && self
+                .signature
+                .iter()
+                .zip(layout.layerkeys.iter())
+                .all(|(s, k)| *s == (k.key.matrix_position, k.layer, k.is_modifier))
+    }
+
+    /// The total costs of all metrics of the matrix: the bigram weights times the cost factors.
+    fn total_costs(&self, bigrams: &BigramBuffer) -> Vec<f64> {
+        let n_layerkeys = self.signature.len();
+        let n_metrics = self.metrics.len();
+        let mut totals = vec![0.0; n_metrics];
+        for ((idx1, idx2), weight) in bigrams
+            .keys1
+            .iter()
+            .zip(bigrams.keys2.iter())
+            .zip(bigrams.weights.iter())
+        {
+            let row = (*idx1 as usize * n_layerkeys + *idx2 as usize) * n_metrics;
+            totals
+                .iter_mut()
+                .zip(self.costs[row..row + n_metrics].iter())
+                .for_each(|(total, cost)| *total += weight * cost);
+        }
+
+        totals
+    }
+}
+
+fn keyboard_address(layout: &Layout) -> usize {
+    Arc::as_ptr(&layout.keyboard) as usize
+}
+
+fn signature(layout: &Layout) -> Vec<KeySignature> {
+    layout
+        .layerkeys
+        .iter()
+        .map(|k| (k.key.matrix_position, k.layer, k.is_modifier))
+        .collect()
+}
+
+/// A handle to the cost matrices of the position based bigram metrics of an evaluator.
+#[derive(Clone, Debug)]
+pub struct CostMatrix {
+    /// Sets of matrices that are not in use by an evaluation (at most one per parallel
+    /// evaluation)
+    idle: Arc<Mutex<Vec<Vec<Matrix>>>>,
+    /// Number of matrices kept per set
+    max_matrices: usize,
+    builds: Arc<AtomicU64>,
+}
+
+impl Default for CostMatrix {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_MATRICES)
+    }
+}
+
+impl CostMatrix {
+    /// Keep the matrices of up to `max_matrices` arrangements of the layerkeys per parallel
+    /// evaluation (at least one). Fewer matrices save memory (see the module documentation) but
+    /// need to be rebuilt more often if modifiers are placed by symbol.
+    pub fn new(max_matrices: usize) -> Self {
+        Self {
+            idle: Arc::new(Mutex::new(Vec::new())),
+            max_matrices: max_matrices.max(1),
+            builds: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The total costs of the given position based metrics (with their indices among the
+    /// evaluator's bigram metrics) in the same order. They equal the sums of the individual costs
+    /// up to rounding.
+    pub fn total_costs(
+        &self,
+        metrics: &[(usize, &dyn BigramMetric)],
+        bigrams: &BigramBuffer,
+        layout: &Layout,
+    ) -> Vec<f64> {
+        if metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let mut matrices = self.idle.lock().pop().unwrap_or_default();
+        let pos = match matrices.iter().position(|m| m.fits(metrics, layout)) {
+            Some(pos) => pos,
+            None => {
+                let _span = tracing::debug_span!("build_cost_matrix").entered();
+                if matrices.len() >= self.max_matrices {
+                    matrices.remove(0);
+                }
+                matrices.push(Matrix::build(metrics, layout));
+                self.builds.fetch_add(1, Ordering::Relaxed);
+                matrices.len() - 1
+            }
+        };
+
+        let total_costs = matrices[pos].total_costs(bigrams);
+        self.idle.lock().push(matrices);
+
+        total_costs
+    }
+
+    /// Number of cost matrices built so far (by all parallel evaluations).
+    pub fn builds(&self) -> u64 {
+        self.builds.load(Ordering::Relaxed)
+    }
+}
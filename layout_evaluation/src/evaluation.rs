@@ -2,22 +2,37 @@
 //! layouts with respect to a list of metrics and ngram data.
 //!
 //! It can hold multiple metrics operating on the layout itself, unigrams, bigrams,
-//! or trigrams. These are required to implement the corresponding trait from the `metrics` module.
+//! trigrams, or quadgrams. These are required to implement the corresponding trait from the `metrics` module.
 //!
 //! The ngram mapper is responsible for mapping char-based ngrams (as read from input data)
-//! to singles, pairs, and triplets of [`LayerKey`]s that can then be analysed by the individual metrics.
+//! to singles, pairs, triplets, and quadruplets of [`LayerKey`]s that can then be analysed by the individual metrics.
 
 use crate::results::{
-    EvaluationResult, MetricResult, MetricResults, MetricType, NormalizationType,
+    CorpusCost, CorpusObjective, DetailVerbosity, EvaluationResult, KeyCost, KeyCosts, KeyPairCost,
+    MetricDetails, MetricResult, MetricResults, MetricType, NormalizationType,
 };
 use crate::{
-    metrics::{bigram_metrics::*, layout_metrics::*, trigram_metrics::*, unigram_metrics::*},
-    ngram_mapper::NgramMapper,
+    bigram_cache::BigramCostCache,
+    co_keyboards::CoKeyboards,
+    cost_matrix::CostMatrix,
+    metrics::{
+        bigram_metrics::*, layout_metrics::*, quadgram_metrics::*, scripting, trigram_metrics::*,
+        unigram_metrics::*, worst_ngrams,
+    },
+    ngram_mapper::{BigramBuffer, NgramMapper, QuadgramBuffer, TrigramBuffer},
+    ngrams::Unigrams,
+    typing_speed::{TypingSpeedEstimate, TypingSpeedModel},
 };
 
-use keyboard_layout::layout::{LayerKey, Layout};
+use keyboard_layout::{
+    key::MatrixPosition,
+    layout::{LayerKey, Layout},
+};
 
+use ahash::AHashMap;
+use anyhow::{bail, Result};
 use serde::Deserialize;
+use std::{fmt, sync::Arc};
 
 /// A wrapper around individuals metric's parameters (`T`) specifying
 /// additional generic attributes. This mostly facilitates configuration of
@@ -30,6 +45,9 @@ pub struct WeightedParams<T> {
     pub weight: f64,
     /// The normalization strategy to use.
     pub normalization: NormalizationType,
+    /// Which of the metric's details to report.
+    #[serde(default)]
+    pub verbosity: DetailVerbosity,
     /// The metric's individual parameters.
     pub params: T,
 }
@@ -41,30 +59,49 @@ pub struct MetricParameters {
     pub shortcut_keys: Option<WeightedParams<shortcut_keys::Parameters>>,
     pub similar_letters: Option<WeightedParams<similar_letters::Parameters>>,
     pub similar_letter_groups: Option<WeightedParams<similar_letter_groups::Parameters>>,
+    pub modifier_placement: Option<WeightedParams<modifier_placement::Parameters>>,
+    pub channel_coverage: Option<WeightedParams<channel_coverage::Parameters>>,
 
     pub finger_balance: Option<WeightedParams<finger_balance::Parameters>>,
     pub position_penalties: Option<WeightedParams<position_penalties::Parameters>>,
     pub hand_disbalance: Option<WeightedParams<hand_disbalance::Parameters>>,
     pub row_loads: Option<WeightedParams<row_loads::Parameters>>,
     pub key_costs: Option<WeightedParams<key_costs::Parameters>>,
+    pub learning_cost: Option<WeightedParams<learning_cost::Parameters>>,
     pub modifier_usage: Option<WeightedParams<modifier_usage::Parameters>>,
+    pub modifier_load: Option<WeightedParams<modifier_load::Parameters>>,
+    pub lateral_load: Option<WeightedParams<lateral_load::Parameters>>,
 
     pub cluster_rolls: Option<WeightedParams<cluster_rolls::Parameters>>,
     pub scissoring: Option<WeightedParams<scissoring::Parameters>>,
     pub symmetric_handswitches: Option<WeightedParams<symmetric_handswitches::Parameters>>,
     pub finger_repeats: Option<WeightedParams<finger_repeats::Parameters>>,
+    pub finger_rate: Option<WeightedParams<finger_rate::Parameters>>,
     pub manual_bigram_penalty: Option<WeightedParams<manual_bigram_penalty::Parameters>>,
     pub movement_pattern: Option<WeightedParams<movement_pattern::Parameters>>,
     pub no_handswitch_after_unbalancing_key:
         Option<WeightedParams<no_handswitch_after_unbalancing_key::Parameters>>,
     pub roll_stats: Option<WeightedParams<roll_stats::Parameters>>,
+    pub thumb_alternation: Option<WeightedParams<thumb_alternation::Parameters>>,
+    pub layer_thrash: Option<WeightedParams<layer_thrash::Parameters>>,
+    pub chord_penalty: Option<WeightedParams<chord_penalty::Parameters>>,
+    pub same_hand_shift: Option<WeightedParams<same_hand_shift::Parameters>>,
+    pub same_direction_flow: Option<WeightedParams<same_direction_flow::Parameters>>,
+    pub same_key_repeats: Option<WeightedParams<same_key_repeats::Parameters>>,
+    pub hand_repositioning: Option<WeightedParams<hand_repositioning::Parameters>>,
+    pub toggle_layer_exits: Option<WeightedParams<toggle_layer_exits::Parameters>>,
 
     pub irregularity: Option<WeightedParams<irregularity::Parameters>>,
     pub no_handswitch_in_trigram: Option<WeightedParams<no_handswitch_in_trigram::Parameters>>,
     pub secondary_bigrams: Option<WeightedParams<secondary_bigrams::Parameters>>,
+    pub return_to_home: Option<WeightedParams<return_to_home::Parameters>>,
+    pub same_cluster_sandwich: Option<WeightedParams<same_cluster_sandwich::Parameters>>,
+    pub punctuation_adjacency: Option<WeightedParams<punctuation_adjacency::Parameters>>,
     pub trigram_finger_repeats: Option<WeightedParams<trigram_finger_repeats::Parameters>>,
     pub trigram_rolls: Option<WeightedParams<trigram_rolls::Parameters>>,
 
+    pub double_redirects: Option<WeightedParams<double_redirects::Parameters>>,
+
     pub kla_home_key_words: Option<WeightedParams<kla_home_key_words::Parameters>>,
     pub kla_same_finger_words: Option<WeightedParams<kla_same_finger_words::Parameters>>,
     pub kla_distance: Option<WeightedParams<kla_distance::Parameters>>,
@@ -84,6 +121,96 @@ pub struct MetricParameters {
     pub oxey_alternates_sfs: Option<WeightedParams<oxey_alternates_sfs::Parameters>>,
     pub oxey_redirects: Option<WeightedParams<oxey_redirects::Parameters>>,
     pub oxey_bad_redirects: Option<WeightedParams<oxey_bad_redirects::Parameters>>,
+
+    /// Custom metrics defined by scripts (requires the `scripting` feature).
+    #[serde(default)]
+    pub scripted_unigram_metrics: Vec<WeightedParams<scripting::Parameters>>,
+    #[serde(default)]
+    pub scripted_bigram_metrics: Vec<WeightedParams<scripting::Parameters>>,
+}
+
+impl MetricParameters {
+    /// The names of the enabled metrics that require quadgrams.
+    pub fn enabled_quadgram_metrics(&self) -> Vec<&str> {
+        let mut metrics = Vec::new();
+        if self.double_redirects.as_ref().map_or(false, |p| p.enabled) {
+            metrics.push("double_redirects");
+        }
+
+        metrics
+    }
+}
+
+/// Metric parameters for the ngrams of some layers, e.g. to expect fewer rolls and to penalize
+/// positions more on a symbol layer. An ngram belongs to the highest layer of its symbols in the
+/// evaluated layout. For
+/// the ngrams of the given layers, the overridden metrics replace the corresponding metrics of
+/// the configuration and are reported separately. Only unigram and bigram metrics whose total
+/// cost is the sum of their individual costs can be overridden. To ignore a metric on some
+/// layers, override its weight with 0.
+#[derive(Clone, Deserialize, Debug)]
+pub struct LayerOverrideParameters {
+    /// The layers whose ngrams are evaluated with the overridden metrics (0 is the base layer)
+    pub layers: Vec<u8>,
+    /// The overridden metrics
+    pub metrics: MetricParameters,
+}
+
+/// The overridden metrics for the ngrams of some layers (see [`LayerOverrideParameters`]).
+#[derive(Clone, Debug)]
+struct LayerOverride {
+    layers: Vec<u8>,
+    /// Evaluator with the overridden metrics on the unigrams and bigrams of all corpora
+    evaluator: Evaluator,
+}
+
+impl LayerOverride {
+    /// Whether an ngram with the given symbols belongs to the layers in the given layout.
+    fn contains(&self, layout: &Layout, symbols: &[char]) -> bool {
+        symbols
+            .iter()
+            .filter_map(|c| layout.get_layerkey_for_symbol(c))
+            .map(|k| k.layer)
+            .max()
+            .map_or(false, |layer| self.layers.contains(&layer))
+    }
+
+    /// Take the unigrams and bigrams of the corpora of the given evaluator.
+    fn copy_corpora(&mut self, evaluator: &Evaluator) {
+        self.evaluator.ngram_mapper = evaluator.ngram_mapper.unigrams_and_bigrams();
+        self.evaluator.additional_corpora = evaluator
+            .additional_corpora
+            .iter()
+            .map(|(name, ngram_mapper)| (name.clone(), ngram_mapper.unigrams_and_bigrams()))
+            .collect();
+    }
+
+    /// The ngram mapper of the corpus with the given index with only the ngrams that belong to
+    /// the layers in the given layout. The layers of the symbols may differ between layouts (e.g.
+    /// with the [`GroupedLayoutGenerator`]), so the ngrams are selected for each layout.
+    ///
+    /// [`GroupedLayoutGenerator`]: keyboard_layout::grouped_layout_generator::GroupedLayoutGenerator
+    fn ngram_mapper(&self, layout: &Layout, corpus: usize) -> Box<dyn NgramMapper> {
+        let contains = |symbols: &[char]| self.contains(layout, symbols);
+        self.evaluator
+            .corpus_ngram_mapper(corpus)
+            .filtered(&contains)
+    }
+
+    /// The name of an overridden metric in the evaluation results.
+    fn metric_name(&self, metric_name: &str) -> String {
+        let layers: Vec<String> = self.layers.iter().map(|layer| layer.to_string()).collect();
+        match layers.len() {
+            1 => format!("{} (layer {})", metric_name, layers[0]),
+            _ => format!("{} (layers {})", metric_name, layers.join(", ")),
+        }
+    }
+}
+
+/// Receives the result of every evaluation of an [`Evaluator`], e.g. to store all evaluated
+/// layouts of an optimization in a database.
+pub trait EvaluationRecorder: Send + Sync + fmt::Debug {
+    fn record(&self, evaluation_result: &EvaluationResult);
 }
 
 /// The [`Evaluator`] object is responsible for evaluating multiple metrics with respect to given ngram data.
@@ -94,7 +221,18 @@ pub struct Evaluator {
     unigram_metrics: Vec<(f64, NormalizationType, Box<dyn UnigramMetric>)>,
     bigram_metrics: Vec<(f64, NormalizationType, Box<dyn BigramMetric>)>,
     trigram_metrics: Vec<(f64, NormalizationType, Box<dyn TrigramMetric>)>,
+    quadgram_metrics: Vec<(f64, NormalizationType, Box<dyn QuadgramMetric>)>,
     ngram_mapper: Box<dyn NgramMapper>,
+    corpus_name: String,
+    additional_corpora: Vec<(String, Box<dyn NgramMapper>)>,
+    corpus_objective: CorpusObjective,
+    detail_verbosity: AHashMap<String, DetailVerbosity>,
+    bigram_cost_cache: Option<BigramCostCache>,
+    cost_matrix: Option<CostMatrix>,
+    co_keyboards: Option<CoKeyboards>,
+    layer_overrides: Vec<LayerOverride>,
+    recorder: Option<Arc<dyn EvaluationRecorder>>,
+    ngram_channels: Vec<(String, Unigrams)>,
 }
 
 impl Evaluator {
@@ -105,35 +243,198 @@ impl Evaluator {
             unigram_metrics: Vec::new(),
             bigram_metrics: Vec::new(),
             trigram_metrics: Vec::new(),
+            quadgram_metrics: Vec::new(),
             ngram_mapper,
+            corpus_name: "default".to_string(),
+            additional_corpora: Vec::new(),
+            corpus_objective: CorpusObjective::default(),
+            detail_verbosity: AHashMap::default(),
+            bigram_cost_cache: None,
+            cost_matrix: None,
+            co_keyboards: None,
+            layer_overrides: Vec::new(),
+            recorder: None,
+            ngram_channels: Vec::new(),
         }
     }
 
+    /// Provide the unigrams of the ngram channels (see [`NgramChannelConfig`]) for reporting
+    /// their coverage (see [`channel_coverage::ChannelCoverage`]). Needs to be called before
+    /// adding the metrics.
+    ///
+    /// [`NgramChannelConfig`]: crate::ngrams::NgramChannelConfig
+    pub fn with_ngram_channels(mut self, channels: Vec<(String, Unigrams)>) -> Self {
+        self.ngram_channels = channels;
+        self
+    }
+
+    /// Evaluate layouts on additional corpora as well. The total costs for the individual
+    /// corpora are combined according to the given objective.
+    pub fn with_additional_corpora(
+        mut self,
+        corpus_name: &str,
+        additional_corpora: Vec<(String, Box<dyn NgramMapper>)>,
+        corpus_objective: CorpusObjective,
+    ) -> Self {
+        self.corpus_name = corpus_name.to_string();
+        self.additional_corpora = additional_corpora;
+        self.corpus_objective = corpus_objective;
+        self.copy_layer_override_corpora();
+        self
+    }
+
+    /// Evaluate layouts on further keyboards as well (see [`CoKeyboards`]). The total cost is
+    /// then the combination of the total costs on all keyboards.
+    pub fn with_co_keyboards(mut self, co_keyboards: CoKeyboards) -> Self {
+        self.co_keyboards = Some(co_keyboards);
+        self
+    }
+
+    /// Pass the result of every evaluation of [`Evaluator::evaluate_layout`] to the recorder.
+    /// Evaluators derived from this one (e.g. bootstrapped ones) do not record their results.
+    pub fn with_recorder(mut self, recorder: Arc<dyn EvaluationRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Evaluate the ngrams of some layers with other metric parameters (see
+    /// [`LayerOverrideParameters`]).
+    pub fn with_layer_overrides(mut self, overrides: &[LayerOverrideParameters]) -> Result<Self> {
+        for params in overrides {
+            let layer_override = LayerOverride {
+                layers: params.layers.clone(),
+                evaluator: Evaluator::default(self.ngram_mapper.clone())
+                    .default_metrics(&params.metrics)?,
+            };
+
+            let evaluator = &layer_override.evaluator;
+            if !(evaluator.layout_metrics.is_empty()
+                && evaluator.trigram_metrics.is_empty()
+                && evaluator.quadgram_metrics.is_empty())
+            {
+                bail!(
+                    "Only unigram and bigram metrics can be overridden (layers {:?})",
+                    params.layers
+                );
+            }
+            let not_summing = evaluator
+                .unigram_metrics
+                .iter()
+                .filter(|(_, _, metric)| !metric.sums_individual_costs())
+                .map(|(_, _, metric)| metric.name())
+                .chain(
+                    evaluator
+                        .bigram_metrics
+                        .iter()
+                        .filter(|(_, _, metric)| !metric.sums_individual_costs())
+                        .map(|(_, _, metric)| metric.name()),
+                )
+                .next();
+            if let Some(name) = not_summing {
+                bail!(
+                    "Metric '{}' can not be overridden for some layers as its total cost is not \
+                     the sum of individual costs",
+                    name
+                );
+            }
+
+            self.layer_overrides.push(layer_override);
+        }
+        self.copy_layer_override_corpora();
+
+        Ok(self)
+    }
+
+    /// Provide the unigrams and bigrams of the corpora to the layer overrides.
+    fn copy_layer_override_corpora(&mut self) {
+        let mut layer_overrides = std::mem::take(&mut self.layer_overrides);
+        layer_overrides
+            .iter_mut()
+            .for_each(|layer_override| layer_override.copy_corpora(self));
+        self.layer_overrides = layer_overrides;
+    }
+
+    /// Generate an [`Evaluator`] for the same corpora, but with the "default" metrics configured
+    /// by the given parameters (instead of the current metrics).
+    pub fn with_metrics(&self, params: &MetricParameters) -> Result<Self> {
+        Evaluator::default(self.ngram_mapper.clone())
+            .with_additional_corpora(
+                &self.corpus_name,
+                self.additional_corpora.clone(),
+                self.corpus_objective.clone(),
+            )
+            .with_ngram_channels(self.ngram_channels.clone())
+            .default_metrics(params)
+    }
+
+    /// Cache the individual costs of bigrams across evaluated layouts (see [`BigramCostCache`]).
+    /// The details of bigram metrics (worst bigrams) are not available for cached costs, so the
+    /// cache is only used if `SHOW_WORST` is set to `false` (see [`worst_ngrams`]).
+    pub fn with_bigram_cost_cache(mut self) -> Self {
+        self.bigram_cost_cache = Some(BigramCostCache::new());
+        self
+    }
+
+    /// The bigram cost cache (if enabled).
+    pub fn bigram_cost_cache(&self) -> Option<&BigramCostCache> {
+        self.bigram_cost_cache.as_ref()
+    }
+
+    /// Evaluate the position based bigram metrics in matrix form (see [`CostMatrix`]), keeping up
+    /// to `max_matrices` matrices per parallel evaluation. Like the bigram cost cache, this is only
+    /// used if `SHOW_WORST` is set to `false`. The other bigram metrics are evaluated as before.
+    pub fn with_cost_matrix(mut self, max_matrices: usize) -> Self {
+        self.cost_matrix = Some(CostMatrix::new(max_matrices));
+        self
+    }
+
+    /// The cost matrix (if enabled).
+    pub fn cost_matrix(&self) -> Option<&CostMatrix> {
+        self.cost_matrix.as_ref()
+    }
+
+    /// Generate a copy of the [`Evaluator`] that evaluates on resampled (bootstrapped) corpora
+    /// of `sample_size` ngrams each (see [`NgramMapper::bootstrap`]). The same seed yields the
+    /// same resampled corpora. The ngram sets are resampled independently of each other, so they
+    /// are not consistent with one another.
+    pub fn bootstrap(&self, sample_size: Option<f64>, seed: u64) -> Self {
+        let mut evaluator = self.clone();
+        evaluator.recorder = None;
+        evaluator.ngram_mapper = self.ngram_mapper.bootstrap(sample_size, seed);
+        evaluator
+            .additional_corpora
+            .iter_mut()
+            .for_each(|(_, ngram_mapper)| {
+                *ngram_mapper = ngram_mapper.bootstrap(sample_size, seed)
+            });
+        evaluator.copy_layer_override_corpora();
+
+        evaluator
+    }
+
     /// Add all "default" metrics to the evaluator.
-    pub fn default_metrics(mut self, params: &MetricParameters) -> Self {
+    ///
+    /// Fails if a scripted metric is invalid.
+    pub fn default_metrics(mut self, params: &MetricParameters) -> Result<Self> {
         macro_rules! add_metric {
             ($metric_type:ident, $metric_name:ident, $metric_struct:ident) => {
                 if let Some(p) = &params.$metric_name {
                     if p.enabled {
-                        self.$metric_type(
-                            Box::new($metric_name::$metric_struct::new(&p.params)),
-                            p.weight,
-                            p.normalization.clone(),
-                        );
+                        let metric = Box::new($metric_name::$metric_struct::new(&p.params));
+                        self.set_detail_verbosity(metric.name(), p.verbosity);
+                        self.$metric_type(metric, p.weight, p.normalization.clone());
                     }
                 }
             };
             ($metric_type:ident, $metric_name:ident, $metric_struct:ident, "add_bigram_metrics") => {
                 if let Some(p) = &params.$metric_name {
                     if p.enabled {
-                        self.$metric_type(
-                            Box::new($metric_name::$metric_struct::new(
-                                self.bigram_metrics.clone(),
-                                &p.params,
-                            )),
-                            p.weight,
-                            p.normalization.clone(),
-                        );
+                        let metric = Box::new($metric_name::$metric_struct::new(
+                            self.bigram_metrics.clone(),
+                            &p.params,
+                        ));
+                        self.set_detail_verbosity(metric.name(), p.verbosity);
+                        self.$metric_type(metric, p.weight, p.normalization.clone());
                     }
                 }
             };
@@ -143,6 +444,17 @@ impl Evaluator {
         add_metric!(layout_metric, shortcut_keys, ShortcutKeys);
         add_metric!(layout_metric, similar_letters, SimilarLetters);
         add_metric!(layout_metric, similar_letter_groups, SimilarLetterGroups);
+        add_metric!(layout_metric, modifier_placement, ModifierPlacement);
+        if let Some(p) = &params.channel_coverage {
+            if p.enabled && !self.ngram_channels.is_empty() {
+                let metric = Box::new(channel_coverage::ChannelCoverage::new(
+                    &p.params,
+                    &self.ngram_channels,
+                ));
+                self.set_detail_verbosity(metric.name(), p.verbosity);
+                self.layout_metric(metric, p.weight, p.normalization.clone());
+            }
+        }
 
         // unigram metrics
         add_metric!(unigram_metric, finger_balance, FingerBalance);
@@ -150,15 +462,27 @@ impl Evaluator {
         add_metric!(unigram_metric, hand_disbalance, HandDisbalance);
         //add_metric!(unigram_metric, row_loads, RowLoads);
         //add_metric!(unigram_metric, modifier_usage, ModifierUsage);
+        add_metric!(unigram_metric, modifier_load, ModifierLoad);
         add_metric!(unigram_metric, key_costs, KeyCost);
+        add_metric!(unigram_metric, learning_cost, LearningCost);
+        add_metric!(unigram_metric, lateral_load, LateralLoad);
 
         // bigram metrics
         add_metric!(bigram_metric, cluster_rolls, ClusterRolls);
         add_metric!(bigram_metric, scissoring, Scissoring);
         add_metric!(bigram_metric, finger_repeats, FingerRepeats);
+        add_metric!(bigram_metric, finger_rate, FingerRate);
         add_metric!(bigram_metric, manual_bigram_penalty, ManualBigramPenalty);
         add_metric!(bigram_metric, movement_pattern, MovementPattern);
         add_metric!(bigram_metric, roll_stats, RollStats);
+        add_metric!(bigram_metric, thumb_alternation, ThumbAlternation);
+        add_metric!(bigram_metric, layer_thrash, LayerThrash);
+        add_metric!(bigram_metric, chord_penalty, ChordPenalty);
+        add_metric!(bigram_metric, same_hand_shift, SameHandShift);
+        add_metric!(bigram_metric, same_direction_flow, SameDirectionFlow);
+        add_metric!(bigram_metric, same_key_repeats, SameKeyRepeats);
+        add_metric!(bigram_metric, hand_repositioning, HandRepositioning);
+        add_metric!(bigram_metric, toggle_layer_exits, ToggleLayerExits);
         //add_metric!(
         //    bigram_metric,
         //    no_handswitch_after_unbalancing_key,
@@ -166,6 +490,9 @@ impl Evaluator {
         //);
         //add_metric!(bigram_metric, symmetric_handswitches, SymmetricHandswitches);
 
+        // scripted metrics (before the trigram metrics that build upon bigram metrics)
+        self.add_scripted_metrics(params)?;
+
         // trigram_metrics
         add_metric!(
             trigram_metric,
@@ -186,6 +513,12 @@ impl Evaluator {
             SecondaryBigrams,
             "add_bigram_metrics"
         );
+        add_metric!(trigram_metric, return_to_home, ReturnToHome);
+        add_metric!(trigram_metric, same_cluster_sandwich, SameClusterSandwich);
+        add_metric!(trigram_metric, punctuation_adjacency, PunctuationAdjacency);
+
+        // quadgram_metrics
+        add_metric!(quadgram_metric, double_redirects, DoubleRedirects);
 
         //add_metric!(layout_metric, kla_same_finger_words, KLASameFingerWords);
         //add_metric!(layout_metric, kla_home_key_words, KLAHomeKeyWords);
@@ -208,7 +541,68 @@ impl Evaluator {
         //add_metric!(trigram_metric, oxey_redirects, OxeyRedirects);
         //add_metric!(trigram_metric, oxey_bad_redirects, OxeyBadRedirects);
 
-        self
+        Ok(self)
+    }
+
+    /// Add the enabled custom metrics that are defined by scripts.
+    #[cfg(feature = "scripting")]
+    fn add_scripted_metrics(&mut self, params: &MetricParameters) -> Result<()> {
+        for p in params.scripted_unigram_metrics.iter().filter(|p| p.enabled) {
+            let metric = Box::new(scripting::ScriptedUnigram::new(&p.params).map_err(|e| {
+                anyhow::anyhow!("Invalid scripted metric '{}': {}", p.params.name, e)
+            })?);
+            self.set_detail_verbosity(metric.name(), p.verbosity);
+            self.unigram_metric(metric, p.weight, p.normalization.clone());
+        }
+
+        for p in params.scripted_bigram_metrics.iter().filter(|p| p.enabled) {
+            let metric = Box::new(scripting::ScriptedBigram::new(&p.params).map_err(|e| {
+                anyhow::anyhow!("Invalid scripted metric '{}': {}", p.params.name, e)
+            })?);
+            self.set_detail_verbosity(metric.name(), p.verbosity);
+            self.bigram_metric(metric, p.weight, p.normalization.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Without the `scripting` feature, scripted metrics can not be evaluated.
+    #[cfg(not(feature = "scripting"))]
+    fn add_scripted_metrics(&mut self, params: &MetricParameters) -> Result<()> {
+        params
+            .scripted_unigram_metrics
+            .iter()
+            .chain(params.scripted_bigram_metrics.iter())
+            .filter(|p| p.enabled)
+            .for_each(|p| {
+                log::warn!(
+                    "Ignoring scripted metric '{}': built without the 'scripting' feature",
+                    p.params.name
+                )
+            });
+
+        Ok(())
+    }
+
+    /// Set which details of the metric with the given name shall be reported.
+    pub fn set_detail_verbosity(&mut self, metric_name: &str, verbosity: DetailVerbosity) {
+        self.detail_verbosity
+            .insert(metric_name.to_string(), verbosity);
+    }
+
+    /// Reduce a metric's details according to its configured verbosity.
+    fn reported_details(
+        &self,
+        metric_name: &str,
+        details: Option<MetricDetails>,
+    ) -> Option<MetricDetails> {
+        let verbosity = self
+            .detail_verbosity
+            .get(metric_name)
+            .copied()
+            .unwrap_or_default();
+
+        details.and_then(|d| d.with_verbosity(verbosity))
     }
 
     /// Add a metric that operates only on the layout itself ("layout metric").
@@ -251,6 +645,16 @@ impl Evaluator {
         self.trigram_metrics.push((weight, normalization, metric));
     }
 
+    /// Add a metric that operates on the quadgram data ("quadgram metric").
+    pub fn quadgram_metric(
+        &mut self,
+        metric: Box<dyn QuadgramMetric>,
+        weight: f64,
+        normalization: NormalizationType,
+    ) {
+        self.quadgram_metrics.push((weight, normalization, metric));
+    }
+
     /// Evaluate all layout metrics for a layout.
     fn evaluate_layout_metrics(&self, layout: &Layout) -> Vec<MetricResult> {
         if self.layout_metrics.is_empty() {
@@ -261,13 +665,14 @@ impl Evaluator {
             .layout_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(layout);
+                let _span = tracing::trace_span!("metric", metric = metric.name()).entered();
+                let (cost, details) = metric.total_cost(layout);
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
                     weight: *weight,
                     normalization: normalization.clone(),
-                    message,
+                    details: self.reported_details(metric.name(), details),
                 }
             })
             .collect();
@@ -290,13 +695,14 @@ impl Evaluator {
             .unigram_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
+                let _span = tracing::trace_span!("metric", metric = metric.name()).entered();
+                let (cost, details) = metric.total_cost(keys, Some(total_weight), layout);
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
                     weight: *weight,
                     normalization: normalization.clone(),
-                    message,
+                    details: self.reported_details(metric.name(), details),
                 }
             })
             .collect();
@@ -305,27 +711,62 @@ impl Evaluator {
     }
 
     /// Evaluate all bigram metrics for a layout.
-    fn evaluate_bigram_metrics(
-        &self,
-        layout: &Layout,
-        keys: &[((&LayerKey, &LayerKey), f64)],
-    ) -> Vec<MetricResult> {
+    fn evaluate_bigram_metrics(&self, layout: &Layout, keys: &BigramBuffer) -> Vec<MetricResult> {
         if self.bigram_metrics.is_empty() {
             return Vec::new();
         }
 
-        let total_weight = keys.iter().map(|(_, w)| w).sum();
+        let total_weight = keys.total_weight();
+        let hide_worst = !worst_ngrams().show;
+
+        let mut precomputed_costs: Vec<Option<f64>> = vec![None; self.bigram_metrics.len()];
+        if let Some(cost_matrix) = self.cost_matrix.as_ref().filter(|_| hide_worst) {
+            let _span = tracing::trace_span!("cost_matrix").entered();
+            let matrix_metrics: Vec<(usize, &dyn BigramMetric)> = self
+                .bigram_metrics
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, _, metric))| metric.position_based())
+                .map(|(i, (_, _, metric))| (i, metric.as_ref()))
+                .collect();
+            let costs = cost_matrix.total_costs(&matrix_metrics, keys, layout);
+            for ((i, _), cost) in matrix_metrics.iter().zip(costs) {
+                precomputed_costs[*i] = Some(cost);
+            }
+        }
+        if let Some(cache) = self.bigram_cost_cache.as_ref().filter(|_| hide_worst) {
+            let _span = tracing::trace_span!("bigram_cost_cache").entered();
+            let cached_metrics: Vec<(usize, &dyn BigramMetric)> = self
+                .bigram_metrics
+                .iter()
+                .enumerate()
+                .filter(|(i, (_, _, metric))| {
+                    precomputed_costs[*i].is_none() && metric.sums_individual_costs()
+                })
+                .map(|(i, (_, _, metric))| (i, metric.as_ref()))
+                .collect();
+            let costs = cache.total_costs(&cached_metrics, keys, total_weight, layout);
+            for ((i, _), cost) in cached_metrics.iter().zip(costs) {
+                precomputed_costs[*i] = Some(cost);
+            }
+        }
+
         let metric_costs: Vec<MetricResult> = self
             .bigram_metrics
             .iter()
-            .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
+            .enumerate()
+            .map(|(i, (weight, normalization, metric))| {
+                let _span = tracing::trace_span!("metric", metric = metric.name()).entered();
+                let (cost, details) = match precomputed_costs[i] {
+                    Some(cost) => (cost, None),
+                    None => metric.total_cost(keys, Some(total_weight), layout),
+                };
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
                     weight: *weight,
                     normalization: normalization.clone(),
-                    message,
+                    details: self.reported_details(metric.name(), details),
                 }
             })
             .collect();
@@ -334,27 +775,54 @@ impl Evaluator {
     }
 
     /// Evaluate all trigram metrics for a layout.
-    fn evaluate_trigram_metrics(
+    fn evaluate_trigram_metrics(&self, layout: &Layout, keys: &TrigramBuffer) -> Vec<MetricResult> {
+        if self.trigram_metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let total_weight = keys.total_weight();
+        let metric_costs: Vec<MetricResult> = self
+            .trigram_metrics
+            .iter()
+            .map(|(weight, normalization, metric)| {
+                let _span = tracing::trace_span!("metric", metric = metric.name()).entered();
+                let (cost, details) = metric.total_cost(keys, Some(total_weight), layout);
+                MetricResult {
+                    name: metric.name().to_string(),
+                    cost,
+                    weight: *weight,
+                    normalization: normalization.clone(),
+                    details: self.reported_details(metric.name(), details),
+                }
+            })
+            .collect();
+
+        metric_costs
+    }
+
+    /// Evaluate all quadgram metrics for a layout.
+    fn evaluate_quadgram_metrics(
         &self,
         layout: &Layout,
-        keys: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
+        keys: &QuadgramBuffer,
     ) -> Vec<MetricResult> {
-        if self.trigram_metrics.is_empty() {
+        if self.quadgram_metrics.is_empty() {
             return Vec::new();
         }
 
-        let total_weight = keys.iter().map(|(_, w)| w).sum();
+        let total_weight = keys.total_weight();
         let metric_costs: Vec<MetricResult> = self
-            .trigram_metrics
+            .quadgram_metrics
             .iter()
             .map(|(weight, normalization, metric)| {
-                let (cost, message) = metric.total_cost(keys, Some(total_weight), layout);
+                let _span = tracing::trace_span!("metric", metric = metric.name()).entered();
+                let (cost, details) = metric.total_cost(keys, Some(total_weight), layout);
                 MetricResult {
                     name: metric.name().to_string(),
                     cost,
                     weight: *weight,
                     normalization: normalization.clone(),
-                    message,
+                    details: self.reported_details(metric.name(), details),
                 }
             })
             .collect();
@@ -362,12 +830,155 @@ impl Evaluator {
         metric_costs
     }
 
+    /// The ngram mapper of the corpus with the given index (0 being the main corpus).
+    fn corpus_ngram_mapper(&self, corpus: usize) -> &dyn NgramMapper {
+        match corpus {
+            0 => self.ngram_mapper.as_ref(),
+            i => self.additional_corpora[i - 1].1.as_ref(),
+        }
+    }
+
+    /// Replace the costs of the unigram metrics on the ngrams of the layers with overridden
+    /// metrics with the costs of the overridden metrics (see [`LayerOverrideParameters`]). The
+    /// individual costs are relative to the `total_weight` of all unigrams of the corpus.
+    fn override_unigram_metrics(
+        &self,
+        layout: &Layout,
+        corpus: usize,
+        total_weight: f64,
+        metric_costs: &mut Vec<MetricResult>,
+    ) {
+        for layer_override in self.layer_overrides.iter() {
+            let evaluator = &layer_override.evaluator;
+            if evaluator.unigram_metrics.is_empty() {
+                continue;
+            }
+
+            let unigrams = layer_override
+                .ngram_mapper(layout, corpus)
+                .map_unigrams(layout);
+            for (weight, normalization, metric) in evaluator.unigram_metrics.iter() {
+                let _span = tracing::trace_span!("metric", metric = metric.name()).entered();
+                let base_metric = self
+                    .unigram_metrics
+                    .iter()
+                    .enumerate()
+                    .find(|(_, (_, _, m))| m.name() == metric.name());
+                if let Some((i, (_, _, base_metric))) = base_metric {
+                    let (base_cost, _) =
+                        base_metric.total_cost(&unigrams.grams, Some(total_weight), layout);
+                    metric_costs[i].cost -= base_cost;
+                }
+
+                let (cost, details) =
+                    metric.total_cost(&unigrams.grams, Some(total_weight), layout);
+                metric_costs.push(MetricResult {
+                    name: layer_override.metric_name(metric.name()),
+                    cost,
+                    weight: *weight,
+                    normalization: normalization.clone(),
+                    details: evaluator.reported_details(metric.name(), details),
+                });
+            }
+        }
+    }
+
+    /// Replace the costs of the bigram metrics on the ngrams of the layers with overridden
+    /// metrics with the costs of the overridden metrics (see [`LayerOverrideParameters`]). The
+    /// individual costs are relative to the `total_weight` of all bigrams of the corpus.
+    fn override_bigram_metrics(
+        &self,
+        layout: &Layout,
+        corpus: usize,
+        total_weight: f64,
+        metric_costs: &mut Vec<MetricResult>,
+    ) {
+        for layer_override in self.layer_overrides.iter() {
+            let evaluator = &layer_override.evaluator;
+            if evaluator.bigram_metrics.is_empty() {
+                continue;
+            }
+
+            let bigrams = layer_override
+                .ngram_mapper(layout, corpus)
+                .map_bigrams(layout);
+            for (weight, normalization, metric) in evaluator.bigram_metrics.iter() {
+                let _span = tracing::trace_span!("metric", metric = metric.name()).entered();
+                let base_metric = self
+                    .bigram_metrics
+                    .iter()
+                    .enumerate()
+                    .find(|(_, (_, _, m))| m.name() == metric.name());
+                if let Some((i, (_, _, base_metric))) = base_metric {
+                    let (base_cost, _) =
+                        base_metric.total_cost(&bigrams.grams, Some(total_weight), layout);
+                    metric_costs[i].cost -= base_cost;
+                }
+
+                let (cost, details) = metric.total_cost(&bigrams.grams, Some(total_weight), layout);
+                metric_costs.push(MetricResult {
+                    name: layer_override.metric_name(metric.name()),
+                    cost,
+                    weight: *weight,
+                    normalization: normalization.clone(),
+                    details: evaluator.reported_details(metric.name(), details),
+                });
+            }
+        }
+    }
+
     /// Evaluate all metrics for a layout.
     pub fn evaluate_layout(&self, layout: &Layout) -> EvaluationResult {
+        let result = self.evaluate_layout_on_corpora(layout);
+        let result = match &self.co_keyboards {
+            Some(co_keyboards) => co_keyboards.attach(result, layout),
+            None => result,
+        };
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&result);
+        }
+
+        result
+    }
+
+    /// Evaluate all metrics for a layout on all corpora.
+    fn evaluate_layout_on_corpora(&self, layout: &Layout) -> EvaluationResult {
+        let result = self.evaluate_layout_with_mapper(layout, self.ngram_mapper.as_ref(), 0);
+        if self.additional_corpora.is_empty() {
+            return result;
+        }
+
+        let mut corpus_costs = vec![CorpusCost {
+            name: self.corpus_name.clone(),
+            cost: result.metrics_total_cost(),
+        }];
+        for (i, (name, ngram_mapper)) in self.additional_corpora.iter().enumerate() {
+            let corpus_result = tracing::debug_span!("corpus", corpus = %name).in_scope(|| {
+                self.evaluate_layout_with_mapper(layout, ngram_mapper.as_ref(), i + 1)
+            });
+            corpus_costs.push(CorpusCost {
+                name: name.clone(),
+                cost: corpus_result.metrics_total_cost(),
+            });
+        }
+
+        result.with_corpus_costs(corpus_costs, self.corpus_objective.clone())
+    }
+
+    /// Evaluate all metrics for a layout using the ngrams of the given ngram mapper (of the corpus
+    /// with the given index, 0 being the main corpus).
+    fn evaluate_layout_with_mapper(
+        &self,
+        layout: &Layout,
+        ngram_mapper: &dyn NgramMapper,
+        corpus: usize,
+    ) -> EvaluationResult {
+        let _span = tracing::debug_span!("evaluate_layout", layout = %layout.as_text()).entered();
         let mut results: Vec<MetricResults> = Vec::new();
 
         // Layout metrics
         if !self.layout_metrics.is_empty() {
+            let _phase = tracing::debug_span!("layout_metrics").entered();
             let metric_costs = self.evaluate_layout_metrics(layout);
             let mut layout_costs = MetricResults::new(MetricType::Layout, 1.0, 0.0);
             metric_costs
@@ -378,8 +989,14 @@ impl Evaluator {
 
         // Unigram metrics
         if !self.unigram_metrics.is_empty() {
-            let mapped_unigrams = self.ngram_mapper.map_unigrams(layout);
-            let metric_costs = self.evaluate_unigram_metrics(layout, &mapped_unigrams.grams);
+            let _phase = tracing::debug_span!("unigram_metrics").entered();
+            let mapped_unigrams =
+                tracing::trace_span!("map_ngrams").in_scope(|| ngram_mapper.map_unigrams(layout));
+            let mut metric_costs = self.evaluate_unigram_metrics(layout, &mapped_unigrams.grams);
+            if !self.layer_overrides.is_empty() {
+                let total_weight = mapped_unigrams.grams.iter().map(|(_, w)| w).sum();
+                self.override_unigram_metrics(layout, corpus, total_weight, &mut metric_costs);
+            }
             let mut unigram_costs = MetricResults::new(
                 MetricType::Unigram,
                 mapped_unigrams.weight_found,
@@ -394,8 +1011,14 @@ impl Evaluator {
 
         // Bigram metrics
         if !self.bigram_metrics.is_empty() {
-            let mapped_bigrams = self.ngram_mapper.map_bigrams(layout);
-            let metric_costs = self.evaluate_bigram_metrics(layout, &mapped_bigrams.grams);
+            let _phase = tracing::debug_span!("bigram_metrics").entered();
+            let mapped_bigrams =
+                tracing::trace_span!("map_ngrams").in_scope(|| ngram_mapper.map_bigrams(layout));
+            let mut metric_costs = self.evaluate_bigram_metrics(layout, &mapped_bigrams.grams);
+            if !self.layer_overrides.is_empty() {
+                let total_weight = mapped_bigrams.grams.total_weight();
+                self.override_bigram_metrics(layout, corpus, total_weight, &mut metric_costs);
+            }
             let mut bigram_costs = MetricResults::new(
                 MetricType::Bigram,
                 mapped_bigrams.weight_found,
@@ -410,7 +1033,9 @@ impl Evaluator {
 
         // Trigram metrics
         if !self.trigram_metrics.is_empty() {
-            let mapped_trigrams = self.ngram_mapper.map_trigrams(layout);
+            let _phase = tracing::debug_span!("trigram_metrics").entered();
+            let mapped_trigrams =
+                tracing::trace_span!("map_ngrams").in_scope(|| ngram_mapper.map_trigrams(layout));
             let metric_costs = self.evaluate_trigram_metrics(layout, &mapped_trigrams.grams);
             let mut trigram_costs = MetricResults::new(
                 MetricType::Trigram,
@@ -424,6 +1049,326 @@ impl Evaluator {
             results.push(trigram_costs);
         }
 
+        // Quadgram metrics
+        if !self.quadgram_metrics.is_empty() {
+            let _phase = tracing::debug_span!("quadgram_metrics").entered();
+            let mapped_quadgrams =
+                tracing::trace_span!("map_ngrams").in_scope(|| ngram_mapper.map_quadgrams(layout));
+            let metric_costs = self.evaluate_quadgram_metrics(layout, &mapped_quadgrams.grams);
+            let mut quadgram_costs = MetricResults::new(
+                MetricType::Quadgram,
+                mapped_quadgrams.weight_found,
+                mapped_quadgrams.weight_not_found,
+            );
+            metric_costs
+                .into_iter()
+                .for_each(|mc| quadgram_costs.add_result(mc));
+
+            results.push(quadgram_costs);
+        }
+
         EvaluationResult::new(layout.as_text(), results)
     }
+
+    /// Attribute the costs of the unigram, bigram, trigram, or quadgram metric with the given
+    /// name to the keys of the layout (and for bigram metrics also to key pairs). The cost of
+    /// each ngram is split evenly among its keys. Returns `None` if no such metric is configured.
+    pub fn key_costs(&self, layout: &Layout, metric_name: &str) -> Option<KeyCosts> {
+        let mut key_costs: AHashMap<MatrixPosition, f64> = AHashMap::default();
+        let mut pair_costs: AHashMap<(MatrixPosition, MatrixPosition), f64> = AHashMap::default();
+        let mut attribute = |keys: &[&LayerKey], cost: f64| {
+            for k in keys {
+                *key_costs.entry(k.key.matrix_position).or_default() += cost / keys.len() as f64;
+            }
+            if let [k1, k2] = keys {
+                *pair_costs
+                    .entry((k1.key.matrix_position, k2.key.matrix_position))
+                    .or_default() += cost;
+            }
+        };
+
+        let (metric_type, metric_cost) = if let Some((_, _, metric)) = self
+            .unigram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let mapped = self.ngram_mapper.map_unigrams(layout);
+            let total_weight = mapped.grams.iter().map(|(_, w)| w).sum();
+            for (k, w) in mapped.grams.iter() {
+                if let Some(cost) = metric.individual_cost(k, *w, total_weight, layout) {
+                    attribute(&[k], cost);
+                }
+            }
+
+            let (cost, _) = metric.total_cost(&mapped.grams, Some(total_weight), layout);
+            (MetricType::Unigram, cost)
+        } else if let Some((_, _, metric)) = self
+            .bigram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let mapped = self.ngram_mapper.map_bigrams(layout);
+            let total_weight = mapped.grams.total_weight();
+            for ((k1, k2), w) in mapped.grams.iter() {
+                if let Some(cost) = metric.individual_cost(k1, k2, w, total_weight, layout) {
+                    attribute(&[k1, k2], cost);
+                }
+            }
+
+            let (cost, _) = metric.total_cost(&mapped.grams, Some(total_weight), layout);
+            (MetricType::Bigram, cost)
+        } else if let Some((_, _, metric)) = self
+            .trigram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let mapped = self.ngram_mapper.map_trigrams(layout);
+            let total_weight = mapped.grams.total_weight();
+            for ((k1, k2, k3), w) in mapped.grams.iter() {
+                if let Some(cost) = metric.individual_cost(k1, k2, k3, w, total_weight, layout) {
+                    attribute(&[k1, k2, k3], cost);
+                }
+            }
+
+            let (cost, _) = metric.total_cost(&mapped.grams, Some(total_weight), layout);
+            (MetricType::Trigram, cost)
+        } else if let Some((_, _, metric)) = self
+            .quadgram_metrics
+            .iter()
+            .find(|(_, _, m)| m.name() == metric_name)
+        {
+            let mapped = self.ngram_mapper.map_quadgrams(layout);
+            let total_weight = mapped.grams.total_weight();
+            for ((k1, k2, k3, k4), w) in mapped.grams.iter() {
+                if let Some(cost) = metric.individual_cost(k1, k2, k3, k4, w, total_weight, layout)
+                {
+                    attribute(&[k1, k2, k3, k4], cost);
+                }
+            }
+
+            let (cost, _) = metric.total_cost(&mapped.grams, Some(total_weight), layout);
+            (MetricType::Quadgram, cost)
+        } else {
+            return None;
+        };
+
+        let attributed_cost: f64 = key_costs.values().sum();
+        let share = |cost: f64| {
+            if attributed_cost != 0.0 {
+                cost / attributed_cost
+            } else {
+                0.0
+            }
+        };
+
+        // all keys of the layout with their symbols on all layers
+        let mut symbols: AHashMap<MatrixPosition, Vec<&LayerKey>> = AHashMap::default();
+        layout.layerkeys.iter().for_each(|k| {
+            symbols.entry(k.key.matrix_position).or_default().push(k);
+        });
+        symbols
+            .values_mut()
+            .for_each(|ks| ks.sort_by_key(|k| k.layer));
+        let base_symbol = |pos: &MatrixPosition| symbols.get(pos).map_or(' ', |ks| ks[0].symbol);
+
+        let mut keys: Vec<KeyCost> = symbols
+            .iter()
+            .map(|(pos, ks)| {
+                let cost = key_costs.get(pos).cloned().unwrap_or_default();
+                KeyCost {
+                    matrix_position: (pos.0, pos.1),
+                    hand: ks[0].key.hand,
+                    finger: ks[0].key.finger,
+                    symbols: ks.iter().map(|k| k.symbol).collect(),
+                    cost,
+                    share: share(cost),
+                }
+            })
+            .collect();
+        keys.sort_by_key(|k| (k.matrix_position.1, k.matrix_position.0));
+
+        let mut key_pairs: Vec<KeyPairCost> = pair_costs
+            .iter()
+            .filter(|(_, cost)| **cost != 0.0)
+            .map(|((from, to), cost)| KeyPairCost {
+                from: (from.0, from.1),
+                to: (to.0, to.1),
+                symbols: [base_symbol(from), base_symbol(to)].iter().collect(),
+                cost: *cost,
+                share: share(*cost),
+            })
+            .collect();
+        key_pairs.sort_by(|a, b| b.cost.abs().partial_cmp(&a.cost.abs()).unwrap());
+
+        Some(KeyCosts {
+            metric: metric_name.to_string(),
+            metric_type,
+            metric_cost,
+            attributed_cost,
+            keys,
+            key_pairs,
+        })
+    }
+
+    /// The share of the unigram weight of the (main) corpus for each key on each layer (after
+    /// resolving the modifiers, so layer keys count for the symbols of their layers).
+    pub fn key_usage(&self, layout: &Layout) -> AHashMap<(u8, MatrixPosition), f64> {
+        let mapped = self.ngram_mapper.map_unigrams(layout);
+        let total_weight: f64 = mapped.grams.iter().map(|(_, w)| w).sum();
+
+        let mut usage: AHashMap<(u8, MatrixPosition), f64> = AHashMap::default();
+        if total_weight > 0.0 {
+            for (k, w) in mapped.grams.iter() {
+                *usage.entry((k.layer, k.key.matrix_position)).or_default() += w / total_weight;
+            }
+        }
+
+        usage
+    }
+
+    /// Estimate the typing speed of a layout on the (main) corpus with the given model. The time
+    /// of each bigram is derived from the unweighted costs of the model's bigram metrics (or
+    /// measured transition times).
+    pub fn estimate_typing_speed(
+        &self,
+        layout: &Layout,
+        model: &TypingSpeedModel,
+    ) -> TypingSpeedEstimate {
+        let metrics: Vec<&Box<dyn BigramMetric>> = self
+            .bigram_metrics
+            .iter()
+            .filter(|(_, _, m)| model.uses_metric(m.name()))
+            .map(|(_, _, m)| m)
+            .collect();
+
+        let mapped = self.ngram_mapper.map_bigrams(layout);
+        let total_weight = mapped.grams.total_weight();
+        let mut total_ms = 0.0;
+        let mut measured_weight = 0.0;
+        for ((k1, k2), w) in mapped.grams.iter() {
+            if w <= 0.0 {
+                continue;
+            }
+            let ms = match model.measured_ms(k1.key.matrix_position, k2.key.matrix_position) {
+                Some(ms) => {
+                    measured_weight += w;
+                    ms
+                }
+                None => {
+                    let cost: f64 = metrics
+                        .iter()
+                        .filter_map(|m| m.individual_cost(k1, k2, w, total_weight, layout))
+                        .sum();
+                    model.modeled_ms(cost / w)
+                }
+            };
+            total_ms += w * ms;
+        }
+
+        // modifiers add keystrokes to the characters of higher layers
+        let mapped_unigrams = self.ngram_mapper.map_unigrams(layout);
+        let keystrokes: f64 = mapped_unigrams.grams.iter().map(|(_, w)| w).sum();
+        let keystrokes_per_char = match mapped_unigrams.weight_found > 0.0 {
+            true => keystrokes / mapped_unigrams.weight_found,
+            false => 1.0,
+        };
+
+        match total_weight > 0.0 {
+            true => model.estimate(
+                total_ms / total_weight,
+                keystrokes_per_char,
+                measured_weight / total_weight,
+            ),
+            false => model.estimate(model.params.base_ms, keystrokes_per_char, 0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ngram_mapper::on_demand_ngram_mapper::{NgramMapperConfig, OnDemandNgramMapper},
+        ngrams::{Bigrams, Trigrams},
+        test_utils::layout_config,
+    };
+
+    use keyboard_layout::{
+        grouped_layout_generator::GroupedLayoutGenerator, keyboard::Keyboard,
+        layout_generator::LayoutGenerator, symbols,
+    };
+
+    /// The standard layout's grouped generator and a layout string with its base and third layer
+    fn grouped_generator() -> (GroupedLayoutGenerator, String) {
+        let config = layout_config("standard.yml");
+        let base = &config.base_layout;
+        let layer = |layer: usize| -> String {
+            base.keys
+                .iter()
+                .flatten()
+                .zip(base.fixed_keys.iter().flatten())
+                .filter(|(_, fixed)| !**fixed)
+                .filter_map(|(symbols, _)| symbols::config_symbol(&symbols[layer]))
+                .collect()
+        };
+        let layout_str = format!("{}{}", layer(0), layer(2));
+
+        let keyboard = Arc::new(Keyboard::from_yaml_object(config.keyboard));
+        let generator = GroupedLayoutGenerator::from_object(config.base_layout, keyboard);
+        (generator, layout_str)
+    }
+
+    #[test]
+    fn layer_overrides_follow_the_layers_of_the_evaluated_layout() {
+        let (generator, layout_str) = grouped_generator();
+        let text = "aaaa";
+        let config: NgramMapperConfig = serde_yaml::from_str(
+            "{split_modifiers: {enabled: true, same_key_mod_factor: 1.0}, exclude_line_breaks: true}",
+        )
+        .unwrap();
+        let ngram_mapper = OnDemandNgramMapper::with_ngrams(
+            Unigrams::from_text(text).unwrap(),
+            Bigrams::from_text(text).unwrap(),
+            Trigrams::from_text(text).unwrap(),
+            config,
+        );
+        let metrics = "{key_costs: {enabled: true, weight: 1.0, \
+                       normalization: {type: weight_all, value: 1.0}, params: {}}}";
+        let overrides = vec![LayerOverrideParameters {
+            layers: vec![2, 3],
+            metrics: serde_yaml::from_str(metrics).unwrap(),
+        }];
+        let evaluator = Evaluator::default(Box::new(ngram_mapper))
+            .default_metrics(&serde_yaml::from_str(metrics).unwrap())
+            .unwrap()
+            .with_layer_overrides(&overrides)
+            .unwrap();
+
+        // move "a" from the base layer to the third layer
+        let mut symbols: Vec<char> = layout_str.chars().collect();
+        let n_keys = symbols.len() / 2;
+        let a = symbols.iter().position(|c| *c == 'a').unwrap();
+        symbols.swap(a, n_keys);
+        let moved_str: String = symbols.into_iter().collect();
+
+        let layout = generator.generate(&layout_str).unwrap();
+        let moved = generator.generate(&moved_str).unwrap();
+        assert_eq!(layout.get_layerkey_for_symbol(&'a').unwrap().layer, 0);
+        assert_eq!(moved.get_layerkey_for_symbol(&'a').unwrap().layer, 2);
+
+        let costs = |layout: &Layout| {
+            let result = evaluator.evaluate_layout(layout);
+            (
+                result.metric_cost("Key Costs").unwrap(),
+                result.metric_cost("Key Costs (layers 2, 3)").unwrap(),
+            )
+        };
+        let (base_cost, override_cost) = costs(&layout);
+        assert!(base_cost > 0.0);
+        assert_eq!(override_cost, 0.0);
+
+        let (base_cost, override_cost) = costs(&moved);
+        assert!(base_cost.abs() < 1e-9);
+        assert!(override_cost > 0.0);
+    }
 }
@@ -1,10 +1,18 @@
+pub mod bigram_cache;
 pub mod cache;
+pub mod co_keyboards;
+pub mod compressed;
 pub mod config;
+pub mod cost_matrix;
 pub mod evaluation;
 pub mod metrics;
 pub mod ngram_mapper;
 pub mod ngrams;
 pub mod results;
+pub mod typing_speed;
+
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
 
 #[cfg(test)]
 mod tests {
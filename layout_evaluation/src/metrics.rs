@@ -1,6 +1,95 @@
-//! The `metrics` module provides traits for layout, unigram, bigram, and trigram metrics.
+//! The `metrics` module provides traits for layout, unigram, bigram, trigram, and quadgram metrics.
 
 pub mod bigram_metrics;
 pub mod layout_metrics;
+pub mod quadgram_metrics;
+pub mod scripting;
 pub mod trigram_metrics;
 pub mod unigram_metrics;
+
+use crate::results::MetricDetails;
+
+use once_cell::sync::Lazy;
+use ordered_float::OrderedFloat;
+use priority_queue::DoublePriorityQueue;
+use std::env;
+
+/// Whether the metrics report their worst ngrams in their details (environment variable
+/// `SHOW_WORST`, defaults to `true`) and how many of them (`N_WORST`, defaults to 3).
+#[derive(Clone, Copy, Debug)]
+pub struct WorstNgrams {
+    pub show: bool,
+    pub n: usize,
+}
+
+static WORST_NGRAMS: Lazy<WorstNgrams> = Lazy::new(|| WorstNgrams {
+    show: env::var("SHOW_WORST")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(true),
+    n: env::var("N_WORST")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3),
+});
+
+/// The [`WorstNgrams`] settings. The environment is only read at the first call, so the variables
+/// need to be set before the first evaluation.
+pub fn worst_ngrams() -> WorstNgrams {
+    *WORST_NGRAMS
+}
+
+/// Sum the costs of the ngrams given as `(index, all keys non-fixed, cost)` and collect the
+/// ngrams with the largest absolute costs (see [`WorstNgrams`]) into the details. `ngram` formats
+/// the ngram of an index.
+pub(crate) fn total_cost_with_worst_ngrams(
+    costs: impl Iterator<Item = (usize, bool, f64)>,
+    ngram: impl Fn(usize) -> String,
+) -> (f64, Option<MetricDetails>) {
+    let WorstNgrams {
+        show: show_worst,
+        n: n_worst,
+    } = worst_ngrams();
+
+    if !show_worst {
+        return (costs.map(|(_, _, c)| c).sum(), None);
+    }
+
+    let (total_cost, worst, worst_nonfixed) = costs.fold(
+        (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
+        |(mut total_cost, mut worst, mut worst_nonfixed), (i, nonfixed, cost)| {
+            total_cost += cost;
+
+            if nonfixed {
+                worst_nonfixed.push(i, OrderedFloat(cost.abs()));
+            }
+            worst.push(i, OrderedFloat(cost.abs()));
+
+            if worst.len() > n_worst {
+                worst.pop_min();
+            }
+            if worst_nonfixed.len() > n_worst {
+                worst_nonfixed.pop_min();
+            }
+
+            (total_cost, worst, worst_nonfixed)
+        },
+    );
+
+    let gen_items = |q: DoublePriorityQueue<usize, OrderedFloat<f64>>| {
+        let worst_items: Vec<(String, f64)> = q
+            .into_sorted_iter()
+            .rev()
+            .filter(|(_, cost)| cost.into_inner() > 0.0)
+            .map(|(i, cost)| (ngram(i), 100.0 * cost.into_inner() / total_cost))
+            .collect();
+
+        worst_items
+    };
+
+    let details = MetricDetails::new()
+        .items("Worst", "%", gen_items(worst))
+        .items("Worst non-fixed", "%", gen_items(worst_nonfixed));
+
+    (total_cost, Some(details))
+}
@@ -1,30 +1,61 @@
 //! The `metrics` module provides a trait for bigram metrics.
+use crate::{
+    metrics::total_cost_with_worst_ngrams, ngram_mapper::BigramBuffer, results::MetricDetails,
+};
+
 use keyboard_layout::layout::{LayerKey, Layout};
 
-use ordered_float::OrderedFloat;
-use priority_queue::DoublePriorityQueue;
-use std::{env, fmt};
+use std::fmt;
 
+pub mod chord_penalty;
 pub mod cluster_rolls;
 pub mod scissoring;
+pub mod finger_rate;
 pub mod finger_repeats;
+pub mod hand_repositioning;
 pub mod kla_distance;
 pub mod kla_finger_usage;
 pub mod kla_same_finger;
 pub mod kla_same_hand;
+pub mod layer_thrash;
 pub mod manual_bigram_penalty;
 pub mod movement_pattern;
 pub mod no_handswitch_after_unbalancing_key;
 pub mod oxey_lsbs;
 pub mod oxey_sfbs;
 pub mod roll_stats;
+pub mod same_direction_flow;
+pub mod same_hand_shift;
+pub mod same_key_repeats;
 pub mod symmetric_handswitches;
+pub mod thumb_alternation;
+pub mod toggle_layer_exits;
 
 /// BigramMetric is a trait for metrics that iterates over weighted bigrams.
 pub trait BigramMetric: Send + Sync + BigramMetricClone + fmt::Debug {
     /// Return the name of the metric.
     fn name(&self) -> &str;
 
+    /// Whether the total cost is the sum of the individual costs (metrics need to declare this
+    /// explicitly). Only then the individual costs
+    /// may be cached across layouts (see [`BigramCostCache`]) and the metric may be overridden for
+    /// some layers (see [`LayerOverrideParameters`]).
+    ///
+    /// [`BigramCostCache`]: crate::bigram_cache::BigramCostCache
+    /// [`LayerOverrideParameters`]: crate::evaluation::LayerOverrideParameters
+    fn sums_individual_costs(&self) -> bool {
+        false
+    }
+
+    /// Whether the individual cost of a bigram is its weight times a factor that only depends on
+    /// the positions, layers, and modifier types of its two keys (not on their symbols or the
+    /// total weight). Only then the costs may be evaluated in matrix form (see [`CostMatrix`]).
+    ///
+    /// [`CostMatrix`]: crate::cost_matrix::CostMatrix
+    fn position_based(&self) -> bool {
+        false
+    }
+
     /// Compute the cost of one bigram (if that is possible, otherwise, return `None`).
     #[inline(always)]
     fn individual_cost(
@@ -41,97 +72,32 @@ pub trait BigramMetric: Send + Sync + BigramMetricClone + fmt::Debug {
     /// Compute the total cost for the metric.
     fn total_cost(
         &self,
-        bigrams: &[((&LayerKey, &LayerKey), f64)],
+        bigrams: &BigramBuffer,
         // total_weight is optional for performance reasons (it can be computed from bigrams).
         total_weight: Option<f64>,
         layout: &Layout,
-    ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
-
-        let total_weight = total_weight.unwrap_or_else(|| bigrams.iter().map(|(_, w)| w).sum());
-        let cost_iter = bigrams
+    ) -> (f64, Option<MetricDetails>) {
+        let total_weight = total_weight.unwrap_or_else(|| bigrams.total_weight());
+        let table = bigrams.key_table();
+        let costs = bigrams
+            .keys1
             .iter()
+            .zip(bigrams.keys2.iter())
+            .zip(bigrams.weights.iter())
             .enumerate()
-            .filter_map(|(i, (bigram, weight))| {
+            .filter_map(|(i, ((idx1, idx2), weight))| {
+                let bigram = (&table[*idx1 as usize], &table[*idx2 as usize]);
                 let cost_option =
                     self.individual_cost(bigram.0, bigram.1, *weight, total_weight, layout);
+                let nonfixed = !bigram.0.is_fixed && !bigram.1.is_fixed;
 
-                cost_option.map(|cost| (i, bigram, cost))
+                cost_option.map(|cost| (i, nonfixed, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
-            let (total_cost, worst, worst_nonfixed) = cost_iter.fold(
-                (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
-                |(mut total_cost, mut worst, mut worst_nonfixed), (i, bigram, cost)| {
-                    total_cost += cost;
-
-                    worst.push(i, OrderedFloat(cost));
-                    if !bigram.0.is_fixed && !bigram.1.is_fixed {
-                        worst_nonfixed.push(i, OrderedFloat(cost));
-                    }
-
-                    if worst.len() > n_worst {
-                        worst.pop_min();
-                    }
-                    if worst_nonfixed.len() > n_worst {
-                        worst_nonfixed.pop_min();
-                    }
-
-                    (total_cost, worst, worst_nonfixed)
-                },
-            );
-
-            let gen_msgs = |q: DoublePriorityQueue<usize, OrderedFloat<f64>>| {
-                let worst_msgs: Vec<String> = q
-                    .into_sorted_iter()
-                    .rev()
-                    .filter(|(_, cost)| cost.into_inner() > 0.0)
-                    .map(|(i, cost)| {
-                        let (gram, _) = bigrams[i];
-                        format!(
-                            "{}{} ({:>5.2}%)",
-                            gram.0,
-                            gram.1,
-                            100.0 * cost.into_inner() / total_cost,
-                        )
-                    })
-                    .collect();
-
-                worst_msgs
-            };
-
-            let mut msgs = Vec::new();
-
-            let worst_msgs = gen_msgs(worst);
-            if !worst_msgs.is_empty() {
-                msgs.push(format!("Worst: {}", worst_msgs.join(", ")))
-            }
-
-            let worst_nonfixed_msgs = gen_msgs(worst_nonfixed);
-            if !worst_nonfixed_msgs.is_empty() {
-                msgs.push(format!(
-                    "Worst non-fixed: {}",
-                    worst_nonfixed_msgs.join(", ")
-                ))
-            }
-
-            let msg = Some(msgs.join(";  "));
-
-            (total_cost, msg)
-        } else {
-            let total_cost: f64 = cost_iter.map(|(_, _, c)| c).sum();
-
-            (total_cost, None)
-        };
-
-        (total_cost, msg)
+        total_cost_with_worst_ngrams(costs, |i| {
+            let gram = bigrams.keys(i);
+            format!("{}{}", gram.0, gram.1)
+        })
     }
 }
 
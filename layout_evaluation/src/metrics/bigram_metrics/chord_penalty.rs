@@ -0,0 +1,81 @@
+//! The bigram metric [`ChordPenalty`] incurs a cost for pressing the two modifiers of a chorded
+//! layer simultaneously. Chording both thumbs is easy, chording two fingers of the same hand
+//! is awkward, and chording two keys of the same finger is hardly possible.
+//!
+//! With modifier splitting, the chord of a symbol on a chorded layer shows up as the pairs of its
+//! two modifiers (in both orders, with a reduced weight, see `same_key_mod_factor`). Other
+//! bigram metrics see these pairs as well.
+//!
+//! *Note:* Consecutive symbols on two different chorded layers that share a modifier produce pairs
+//! of the modifiers of both chords, which are counted as well.
+
+use super::BigramMetric;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost of a chord of two keys of different hands (e.g. both thumbs).
+    pub two_hands_cost: f64,
+    /// Cost of a chord of two keys of different fingers of the same hand.
+    pub same_hand_cost: f64,
+    /// Cost of a chord of two keys of the same finger.
+    pub same_finger_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChordPenalty {
+    two_hands_cost: f64,
+    same_hand_cost: f64,
+    same_finger_cost: f64,
+}
+
+impl ChordPenalty {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            two_hands_cost: params.two_hands_cost,
+            same_hand_cost: params.same_hand_cost,
+            same_finger_cost: params.same_finger_cost,
+        }
+    }
+}
+
+impl BigramMetric for ChordPenalty {
+    fn name(&self) -> &str {
+        "Chord Penalty"
+    }
+
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if !(k1.is_modifier.is_chord() && k2.is_modifier.is_chord()) || k1 == k2 {
+            return Some(0.0);
+        }
+
+        let cost = if k1.key.hand != k2.key.hand {
+            self.two_hands_cost
+        } else if k1.key.finger != k2.key.finger {
+            self.same_hand_cost
+        } else {
+            self.same_finger_cost
+        };
+
+        Some(weight * cost)
+    }
+}
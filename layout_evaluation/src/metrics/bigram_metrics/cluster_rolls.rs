@@ -46,6 +46,14 @@ impl BigramMetric for ClusterRolls {
         "Cluster Rolls"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
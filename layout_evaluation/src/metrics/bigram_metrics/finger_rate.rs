@@ -0,0 +1,96 @@
+//! The bigram metric [`FingerRate`] estimates the sustained keystroke rate of each finger and
+//! penalizes fingers that exceed a configurable limit. In contrast to static finger loads, this
+//! models fatigue: a finger that has to type its keys in quick succession (same-finger bigrams)
+//! is busy for longer than its share of the keystrokes suggests.
+//!
+//! The rate of a finger is the overall typing rate times its share of the keystrokes plus its
+//! share of same-finger bigrams (multiplied with `same_finger_factor`). The cost is the sum of
+//! the squared excess rates over the fingers' limits.
+//!
+//! *Note:* Repetitions of the same key are not counted as same-finger bigrams.
+
+use super::BigramMetric;
+use crate::{ngram_mapper::BigramBuffer, results::MetricDetails};
+
+use keyboard_layout::{
+    key::{Finger, HandFingerMap},
+    layout::Layout,
+};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Overall typing rate (keystrokes per second)
+    pub keystrokes_per_second: f64,
+    /// Additional keystrokes a same-finger bigram counts for
+    pub same_finger_factor: f64,
+    /// Sustainable keystroke rate (keystrokes per second) of each finger (no limit if missing)
+    pub limits: AHashMap<Finger, f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FingerRate {
+    keystrokes_per_second: f64,
+    same_finger_factor: f64,
+    limits: AHashMap<Finger, f64>,
+}
+
+impl FingerRate {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            keystrokes_per_second: params.keystrokes_per_second,
+            same_finger_factor: params.same_finger_factor,
+            limits: params.limits.clone(),
+        }
+    }
+}
+
+impl BigramMetric for FingerRate {
+    fn name(&self) -> &str {
+        "Finger Keystroke Rate"
+    }
+
+    fn total_cost(
+        &self,
+        bigrams: &BigramBuffer,
+        total_weight: Option<f64>,
+        _layout: &Layout,
+    ) -> (f64, Option<MetricDetails>) {
+        let total_weight = total_weight.unwrap_or_else(|| bigrams.total_weight());
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
+
+        let mut rates: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
+        bigrams.iter().for_each(|((k1, k2), weight)| {
+            let load = rates.get_mut(&k2.key.hand, &k2.key.finger);
+            *load += weight;
+
+            if k1.key.hand == k2.key.hand
+                && k1.key.finger == k2.key.finger
+                && k1.key.matrix_position != k2.key.matrix_position
+            {
+                *load += self.same_finger_factor * weight;
+            }
+        });
+
+        rates
+            .iter_mut()
+            .for_each(|l| *l *= self.keystrokes_per_second / total_weight);
+
+        let cost = rates
+            .iter()
+            .zip(HandFingerMap::<f64>::keys().iter())
+            .map(|(rate, (_, finger))| match self.limits.get(finger) {
+                Some(limit) if rate > limit => (rate - limit).powi(2),
+                _ => 0.0,
+            })
+            .sum();
+
+        let details = MetricDetails::new().finger_values("Keystrokes per second", "", &rates);
+
+        (cost, Some(details))
+    }
+}
@@ -0,0 +1,94 @@
+//! The bigram metric [`HandRepositioning`] models the hidden cost of regaining the home position
+//! on a contoured board. After a large off-home reach (e.g. an Out key on the pinky), the whole
+//! hand is slightly displaced. If the same hand is needed again immediately on a distant key
+//! (of another finger), it first has to settle back into its home position.
+//!
+//! The reach of a key is the cost of its direction multiplied with the factor of its finger. The
+//! cost of a bigram is its reach times the distance (in key positions) to the second key, if that
+//! distance is at least `min_distance`.
+//!
+//! *Note:* Thumbs, modifiers, and same-finger bigrams (see cluster rolls) are not counted.
+
+use super::BigramMetric;
+
+use ahash::AHashMap;
+use keyboard_layout::{
+    key::{Direction, Finger},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Reach of each direction of a finger cluster (missing directions are no reach)
+    pub direction_costs: AHashMap<Direction, f64>,
+    /// Multiply the reach with these factors depending on the finger (default 1.0)
+    #[serde(default)]
+    pub finger_factors: AHashMap<Finger, f64>,
+    /// Minimal distance of the second key from the reaching key to incur a cost
+    pub min_distance: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct HandRepositioning {
+    direction_costs: AHashMap<Direction, f64>,
+    finger_factors: AHashMap<Finger, f64>,
+    min_distance: f64,
+}
+
+impl HandRepositioning {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            direction_costs: params.direction_costs.clone(),
+            finger_factors: params.finger_factors.clone(),
+            min_distance: params.min_distance,
+        }
+    }
+}
+
+impl BigramMetric for HandRepositioning {
+    fn name(&self) -> &str {
+        "Hand Repositioning"
+    }
+
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if k1.key.hand != k2.key.hand
+            || k1.key.finger == k2.key.finger
+            || k1.key.finger == Finger::Thumb
+            || k2.key.finger == Finger::Thumb
+            || k1.is_modifier.is_some()
+            || k2.is_modifier.is_some()
+        {
+            return Some(0.0);
+        }
+
+        let reach = match self.direction_costs.get(&k1.key.direction) {
+            Some(cost) => cost * self.finger_factors.get(&k1.key.finger).unwrap_or(&1.0),
+            None => return Some(0.0),
+        };
+
+        let distance = k1.key.position.distance(&k2.key.position);
+        if distance < self.min_distance {
+            return Some(0.0);
+        }
+
+        Some(weight * reach * distance)
+    }
+}
@@ -1,6 +1,8 @@
 use core::slice;
 
 use super::BigramMetric;
+use crate::ngram_mapper::BigramBuffer;
+use crate::results::MetricDetails;
 
 use ahash::AHashMap;
 use keyboard_layout::{
@@ -78,10 +80,10 @@ impl BigramMetric for KLADistance {
 
     fn total_cost(
         &self,
-        bigrams: &[((&LayerKey, &LayerKey), f64)],
+        bigrams: &BigramBuffer,
         _total_weight: Option<f64>,
         layout: &Layout,
-    ) -> (f64, Option<String>) {
+    ) -> (f64, Option<MetricDetails>) {
         let mut finger_values: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
         let finger_resting_positions =
@@ -145,19 +147,8 @@ impl BigramMetric for KLADistance {
                 });
         });
 
-        let message = format!(
-            "Per finger (unweighted): {:4.1} {:4.1} {:4.1} {:4.1} | {:>4.1} - {:<4.1} | {:4.1} {:4.1} {:4.1} {:4.1}",
-            finger_values.get(&Hand::Left, &Finger::Pinky),
-            finger_values.get(&Hand::Left, &Finger::Ring),
-            finger_values.get(&Hand::Left, &Finger::Middle),
-            finger_values.get(&Hand::Left, &Finger::Index),
-            finger_values.get(&Hand::Left, &Finger::Thumb),
-            finger_values.get(&Hand::Right, &Finger::Thumb),
-            finger_values.get(&Hand::Right, &Finger::Index),
-            finger_values.get(&Hand::Right, &Finger::Middle),
-            finger_values.get(&Hand::Right, &Finger::Ring),
-            finger_values.get(&Hand::Right, &Finger::Pinky),
-        );
+        let details =
+            MetricDetails::new().finger_values("Per finger (unweighted)", "", &finger_values);
 
         finger_values
             .iter_mut()
@@ -170,6 +161,6 @@ impl BigramMetric for KLADistance {
 
         let cost = finger_values.iter().sum();
 
-        (cost, Some(message))
+        (cost, Some(details))
     }
 }
@@ -1,9 +1,11 @@
 use super::BigramMetric;
+use crate::ngram_mapper::BigramBuffer;
+use crate::results::MetricDetails;
 
 use ahash::{AHashMap, AHashSet};
 use keyboard_layout::{
     key::{Finger, Hand, HandFingerMap, HandMap},
-    layout::{LayerKey, LayerKeyIndex, Layout},
+    layout::{LayerKeyIndex, Layout},
 };
 
 use serde::Deserialize;
@@ -39,14 +41,14 @@ impl BigramMetric for KLAFingerUsage {
 
     fn total_cost(
         &self,
-        bigrams: &[((&LayerKey, &LayerKey), f64)],
+        bigrams: &BigramBuffer,
         _total_weight: Option<f64>,
         layout: &Layout,
-    ) -> (f64, Option<String>) {
+    ) -> (f64, Option<MetricDetails>) {
         let mut finger_values: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
         bigrams.iter().for_each(|((prev_key, curr_key), weight)| {
-            *finger_values.get_mut(&curr_key.key.hand, &curr_key.key.finger) += *weight;
+            *finger_values.get_mut(&curr_key.key.hand, &curr_key.key.finger) += weight;
 
             if !self.ignore_modifiers {
                 let prev_mods: AHashSet<LayerKeyIndex> = prev_key
@@ -67,23 +69,12 @@ impl BigramMetric for KLAFingerUsage {
                     .map(|k| layout.get_layerkey(k));
 
                 pressed_mods
-                    .for_each(|k| *finger_values.get_mut(&k.key.hand, &k.key.finger) += *weight);
+                    .for_each(|k| *finger_values.get_mut(&k.key.hand, &k.key.finger) += weight);
             }
         });
 
-        let message = format!(
-            "Per finger (unweighted): {:4.1} {:4.1} {:4.1} {:4.1} | {:>4.1} - {:<4.1} | {:4.1} {:4.1} {:4.1} {:4.1}",
-            finger_values.get(&Hand::Left, &Finger::Pinky),
-            finger_values.get(&Hand::Left, &Finger::Ring),
-            finger_values.get(&Hand::Left, &Finger::Middle),
-            finger_values.get(&Hand::Left, &Finger::Index),
-            finger_values.get(&Hand::Left, &Finger::Thumb),
-            finger_values.get(&Hand::Right, &Finger::Thumb),
-            finger_values.get(&Hand::Right, &Finger::Index),
-            finger_values.get(&Hand::Right, &Finger::Middle),
-            finger_values.get(&Hand::Right, &Finger::Ring),
-            finger_values.get(&Hand::Right, &Finger::Pinky),
-        );
+        let details =
+            MetricDetails::new().finger_values("Per finger (unweighted)", "", &finger_values);
 
         finger_values
             .iter_mut()
@@ -96,6 +87,6 @@ impl BigramMetric for KLAFingerUsage {
 
         let cost = finger_values.iter().sum();
 
-        (cost, Some(message))
+        (cost, Some(details))
     }
 }
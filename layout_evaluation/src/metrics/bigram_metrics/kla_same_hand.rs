@@ -1,9 +1,11 @@
 use super::BigramMetric;
+use crate::ngram_mapper::BigramBuffer;
+use crate::results::MetricDetails;
 
 use ahash::AHashMap;
 use keyboard_layout::{
     key::{Finger, Hand, HandMap},
-    layout::{LayerKey, Layout},
+    layout::Layout,
 };
 
 use serde::Deserialize;
@@ -39,10 +41,10 @@ impl BigramMetric for KLASameHand {
 
     fn total_cost(
         &self,
-        bigrams: &[((&LayerKey, &LayerKey), f64)],
+        bigrams: &BigramBuffer,
         _total_weight: Option<f64>,
         layout: &Layout,
-    ) -> (f64, Option<String>) {
+    ) -> (f64, Option<MetricDetails>) {
         let mut hand_values: HandMap<f64> = HandMap::with_default(0.0);
 
         bigrams.iter().for_each(|((prev_key, curr_key), weight)| {
@@ -79,15 +81,18 @@ impl BigramMetric for KLASameHand {
                 .zip(HandMap::<f64>::keys())
                 .for_each(|((prev_used, curr_used), hand)| {
                     if *prev_used && *curr_used {
-                        *hand_values.get_mut(&hand) += *weight;
+                        *hand_values.get_mut(&hand) += weight;
                     }
                 });
         });
 
-        let message = format!(
-            "Per hand (unweighted): {:>4.1} - {:<4.1}",
-            hand_values.get(&Hand::Left),
-            hand_values.get(&Hand::Right),
+        let details = MetricDetails::new().values(
+            "Per hand (unweighted)",
+            "",
+            vec![
+                ("Left", *hand_values.get(&Hand::Left)),
+                ("Right", *hand_values.get(&Hand::Right)),
+            ],
         );
 
         hand_values
@@ -100,6 +105,6 @@ impl BigramMetric for KLASameHand {
 
         let cost = hand_values.iter().sum();
 
-        (cost, Some(message))
+        (cost, Some(details))
     }
 }
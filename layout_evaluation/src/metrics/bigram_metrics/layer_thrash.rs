@@ -0,0 +1,134 @@
+//! The bigram metric [`LayerThrash`] incurs a cost for consecutive symbols that live on
+//! different non-base layers. Typing them requires releasing one layer modifier and holding
+//! another one (or switching layers twice), which is slower than staying on a layer.
+//! Frequently adjacent symbols are thus pulled onto the same layer.
+//!
+//! With modifier splitting, such bigrams show up as pairs of two held modifiers of different
+//! layers (identical consecutive modifiers are held and do not occur, and two modifiers of the
+//! same layer, e.g. the left and right Shift, do not switch layers). Without modifier splitting,
+//! the layers of both keys are compared directly. Switching between two toggled layers shows up
+//! as leaving one layer and entering the other one, i.e. a pair of toggle modifiers of different
+//! layers.
+//!
+//! *Note:* Symbols requiring multiple modifiers also produce pairs of different modifiers
+//! (with a reduced weight, see `same_key_mod_factor`), which are counted as well unless both
+//! modifiers activate the same layers (e.g. the two keys of a chord).
+
+use super::BigramMetric;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost of switching between two non-base layers.
+    pub switch_cost: f64,
+    /// Factor for the cost if both layer modifiers are operated by the same finger.
+    pub same_finger_factor: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LayerThrash {
+    switch_cost: f64,
+    same_finger_factor: f64,
+}
+
+impl LayerThrash {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            switch_cost: params.switch_cost,
+            same_finger_factor: params.same_finger_factor,
+        }
+    }
+}
+
+impl BigramMetric for LayerThrash {
+    fn name(&self) -> &str {
+        "Layer Thrash"
+    }
+
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        let is_thrash = if (k1.is_modifier.is_held() && k2.is_modifier.is_held())
+            || (k1.is_modifier.is_toggle() && k2.is_modifier.is_toggle())
+        {
+            // resolved modifier sequences: release one layer modifier and hold another one
+            // (or tap the modifiers for leaving one toggled layer and entering another one)
+            k1 != k2 && layout.layers_activated_by(k1) != layout.layers_activated_by(k2)
+        } else if k1.is_modifier.is_none() && k2.is_modifier.is_none() {
+            // unsplit symbols on two different higher layers
+            k1.layer > 0 && k2.layer > 0 && k1.layer != k2.layer
+        } else {
+            false
+        };
+
+        if !is_thrash {
+            return Some(0.0);
+        }
+
+        let factor = if k1.key.hand == k2.key.hand && k1.key.finger == k2.key.finger {
+            self.same_finger_factor
+        } else {
+            1.0
+        };
+
+        Some(weight * self.switch_cost * factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::test_layout;
+
+    use keyboard_layout::key::Hand;
+
+    /// The first modifier for reaching the given layer on keys of the given hand
+    fn modifier(layer: u8, hand: Hand, layout: &Layout) -> &LayerKey {
+        let k = layout
+            .layerkeys
+            .iter()
+            .find(|k| k.layer == layer && k.key.hand == hand && k.is_modifier.is_none())
+            .unwrap();
+        layout.get_layerkey(&k.modifiers.held().unwrap()[0])
+    }
+
+    #[test]
+    fn switches_between_modifiers_of_different_layers_only() {
+        let layout = test_layout("standard.yml");
+        let metric = LayerThrash::new(&Parameters {
+            switch_cost: 1.0,
+            same_finger_factor: 2.0,
+        });
+        // the Shift keys of both hands reach the same layer
+        let left_shift = modifier(1, Hand::Right, &layout);
+        let right_shift = modifier(1, Hand::Left, &layout);
+        let left_mod3 = modifier(2, Hand::Right, &layout);
+        assert_ne!(left_shift, right_shift);
+        assert_eq!(left_shift.key.hand, left_mod3.key.hand);
+
+        let cost = |k1: &LayerKey, k2: &LayerKey| {
+            metric.individual_cost(k1, k2, 1.0, 1.0, &layout).unwrap()
+        };
+        assert_eq!(cost(left_shift, right_shift), 0.0);
+        assert_eq!(cost(right_shift, left_mod3), 1.0);
+        assert_eq!(cost(left_shift, left_mod3), 2.0);
+    }
+}
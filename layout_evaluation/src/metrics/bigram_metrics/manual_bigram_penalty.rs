@@ -45,6 +45,14 @@ impl BigramMetric for ManualBigramPenalty {
         "Manual Bigram Penalty"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
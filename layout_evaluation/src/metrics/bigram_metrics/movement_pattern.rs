@@ -49,6 +49,14 @@ impl BigramMetric for MovementPattern {
         "Movement Pattern"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -29,6 +29,14 @@ impl BigramMetric for NoHandSwitchAfterUnbalancingKey {
         "No Handswitch After Unbalancing Key"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -37,6 +37,15 @@ impl BigramMetric for OxeySfbs {
         "Sfbs"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        // the excluded characters depend on the symbols
+        self.exclude_chars.is_empty()
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -1,4 +1,6 @@
 use super::BigramMetric;
+use crate::ngram_mapper::BigramBuffer;
+use crate::results::MetricDetails;
 
 use keyboard_layout::{
     key::{Finger, Hand},
@@ -86,18 +88,18 @@ impl BigramMetric for RollStats {
 
     fn total_cost(
         &self,
-        bigrams: &[((&LayerKey, &LayerKey), f64)],
+        bigrams: &BigramBuffer,
         total_weight: Option<f64>,
         _layout: &Layout,
-    ) -> (f64, Option<String>) {
-        let _total_weight = total_weight.unwrap_or_else(|| bigrams.iter().map(|(_, w)| w).sum());
+    ) -> (f64, Option<MetricDetails>) {
+        let _total_weight = total_weight.unwrap_or_else(|| bigrams.total_weight());
 
         let mut inward_rolls_weight = 0.0;
         let mut outward_rolls_weight = 0.0;
         let mut center_south_rolls_weight = 0.0;
         let mut valid_bigrams_weight = 0.0;
 
-        for ((k1, k2), weight) in bigrams {
+        for ((k1, k2), weight) in bigrams.iter() {
             // Skip ignored keys
             if self.should_ignore_key(k1) || self.should_ignore_key(k2) {
                 continue;
@@ -134,12 +136,18 @@ impl BigramMetric for RollStats {
 
         let total_rolls_percentage = inward_percentage + outward_percentage + center_south_percentage;
 
-        let message = format!(
-            "Inward: {:.1}%, Outward: {:.1}%, Center->South: {:.1}%, Total Rolls: {:.1}%",
-            inward_percentage, outward_percentage, center_south_percentage, total_rolls_percentage
+        let details = MetricDetails::new().values(
+            "",
+            "%",
+            vec![
+                ("Inward", inward_percentage),
+                ("Outward", outward_percentage),
+                ("Center->South", center_south_percentage),
+                ("Total Rolls", total_rolls_percentage),
+            ],
         );
 
         // Return 0 cost since this is informational only
-        (0.0, Some(message))
+        (0.0, Some(details))
     }
 }
\ No newline at end of file
@@ -0,0 +1,88 @@
+//! The bigram metric [`SameDirectionFlow`] assigns a negative cost (a bonus) to each same-hand
+//! bigram that is typed with two different fingers moving in the same direction, e.g. two Center
+//! strikes or two South pulls on neighboring fingers. On the Svalboard, these feel like a single
+//! gesture of the hand. The bonus is configurable for each direction.
+
+use super::BigramMetric;
+
+use ahash::AHashMap;
+use keyboard_layout::{
+    key::{Direction, Finger},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Bonus for each direction (missing directions receive no bonus).
+    pub direction_bonuses: AHashMap<Direction, f64>,
+    /// Factor for the bonus if the two fingers are not neighbors.
+    pub non_neighbor_factor: f64,
+    pub ignore_thumbs: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct SameDirectionFlow {
+    direction_bonuses: AHashMap<Direction, f64>,
+    non_neighbor_factor: f64,
+    ignore_thumbs: bool,
+}
+
+impl SameDirectionFlow {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            direction_bonuses: params.direction_bonuses.clone(),
+            non_neighbor_factor: params.non_neighbor_factor,
+            ignore_thumbs: params.ignore_thumbs,
+        }
+    }
+}
+
+impl BigramMetric for SameDirectionFlow {
+    fn name(&self) -> &str {
+        "Same Direction Flow"
+    }
+
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if k1.is_modifier.is_some()
+            || k2.is_modifier.is_some()
+            || k1.key.hand != k2.key.hand
+            || k1.key.finger == k2.key.finger
+            || k1.key.direction != k2.key.direction
+            || (self.ignore_thumbs
+                && (k1.key.finger == Finger::Thumb || k2.key.finger == Finger::Thumb))
+        {
+            return Some(0.0);
+        }
+
+        let bonus = match self.direction_bonuses.get(&k1.key.direction) {
+            Some(bonus) => *bonus,
+            None => return Some(0.0),
+        };
+
+        let factor = if k1.key.finger.distance(&k2.key.finger) > 1 {
+            self.non_neighbor_factor
+        } else {
+            1.0
+        };
+
+        Some(-weight * bonus * factor)
+    }
+}
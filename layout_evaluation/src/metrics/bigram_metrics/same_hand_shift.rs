@@ -0,0 +1,131 @@
+//! The bigram metric [`SameHandShift`] penalizes symbols whose held layer modifier (e.g. Shift)
+//! is on the same hand as the symbol itself. Holding the modifier and striking the key with the
+//! same hand is awkward and slow, in particular if both keys are operated by the same finger
+//! (cluster).
+//!
+//! Without modifier splitting, each symbol is the second symbol of one bigram, so the bigrams
+//! ending in a symbol with held modifiers are counted. With modifier splitting, the pairs of a
+//! held modifier and a key it shifts are counted, i.e. keys holding a symbol on a layer the
+//! modifier activates. Modifiers followed by keys without symbols on their layers (e.g. Shift and
+//! Space) are not charged.
+
+use super::BigramMetric;
+
+use keyboard_layout::{
+    key::Key,
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost for a symbol whose held modifier is on the same hand.
+    pub same_hand_cost: f64,
+    /// Factor for the cost if the modifier is on the same finger as the symbol.
+    pub same_finger_factor: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct SameHandShift {
+    same_hand_cost: f64,
+    same_finger_factor: f64,
+}
+
+impl SameHandShift {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            same_hand_cost: params.same_hand_cost,
+            same_finger_factor: params.same_finger_factor,
+        }
+    }
+
+    fn modifier_cost(&self, modifier: &Key, key: &Key) -> f64 {
+        if modifier.hand != key.hand {
+            0.0
+        } else if modifier.finger == key.finger {
+            self.same_hand_cost * self.same_finger_factor
+        } else {
+            self.same_hand_cost
+        }
+    }
+}
+
+/// If the modifier `k1` activates a layer holding a symbol of the key of `k2`.
+fn shifts(k1: &LayerKey, k2: &LayerKey, layout: &Layout) -> bool {
+    layout
+        .get_layerkey_index_for_symbol(&k2.symbol)
+        .map_or(false, |key| layout.activates_layer_of_key(k1, &key))
+}
+
+impl BigramMetric for SameHandShift {
+    fn name(&self) -> &str {
+        "Same Hand Shift"
+    }
+
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        layout: &Layout,
+    ) -> Option<f64> {
+        if k2.is_modifier.is_some() {
+            return Some(0.0);
+        }
+
+        let cost: f64 = match k2.modifiers.held() {
+            // modifiers were not split
+            Some(modifiers) if !modifiers.is_empty() => modifiers
+                .iter()
+                .map(|idx| self.modifier_cost(&layout.get_layerkey(idx).key, &k2.key))
+                .sum(),
+            // a split held modifier followed by a key it shifts
+            _ if k1.is_modifier.is_held() && shifts(k1, k2, layout) => {
+                self.modifier_cost(&k1.key, &k2.key)
+            }
+            _ => 0.0,
+        };
+
+        Some(weight * cost)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::test_layout;
+
+    fn cost(metric: &SameHandShift, k1: &LayerKey, c2: char, layout: &Layout) -> f64 {
+        let k2 = layout.get_layerkey_for_symbol(&c2).unwrap();
+        metric.individual_cost(k1, k2, 1.0, 1.0, layout).unwrap()
+    }
+
+    #[test]
+    fn charges_held_modifiers_only_for_keys_they_shift() {
+        let layout = test_layout("split_positions_only.yml");
+        let metric = SameHandShift::new(&Parameters {
+            same_hand_cost: 1.0,
+            same_finger_factor: 3.0,
+        });
+        let b = layout.get_layerkey_for_symbol(&'b').unwrap();
+        let capital_a = layout.get_layerkey_for_symbol(&'A').unwrap();
+        let shift = layout.get_layerkey(&capital_a.modifiers.held().unwrap()[0]);
+
+        // the left thumb's Shift with capitals of both hands
+        assert_eq!(cost(&metric, b, 'A', &layout), 1.0);
+        assert_eq!(cost(&metric, b, 'O', &layout), 0.0);
+        // split modifiers: Shift followed by a key with a shifted symbol
+        assert_eq!(cost(&metric, shift, 'a', &layout), 1.0);
+        assert_eq!(cost(&metric, shift, 'o', &layout), 0.0);
+        // Space and Shift are typed with the same thumb, but Space has no shifted symbol
+        assert_eq!(cost(&metric, shift, ' ', &layout), 0.0);
+    }
+}
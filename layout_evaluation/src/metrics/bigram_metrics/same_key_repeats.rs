@@ -0,0 +1,84 @@
+//! The bigram metric [`SameKeyRepeats`] incurs a cost for pressing the exact same key twice in a
+//! row. Generic same-finger metrics treat such repeats like movements between two keys, although
+//! their difficulty mostly depends on the direction of the key: double-tapping a Center key is
+//! trivial, double-tapping a North key requires reaching out twice. The cost is configurable for
+//! each direction and scaled with a factor for each finger.
+//!
+//! *Note:* Repeated modifiers are usually held and are not counted.
+
+use super::BigramMetric;
+
+use ahash::AHashMap;
+use keyboard_layout::{
+    key::{Direction, Finger},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost of repeating a key with each direction (missing directions cost nothing).
+    pub direction_costs: AHashMap<Direction, f64>,
+    /// Factor for the cost of each finger (missing fingers use a factor of 1).
+    #[serde(default)]
+    pub finger_factors: AHashMap<Finger, f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SameKeyRepeats {
+    direction_costs: AHashMap<Direction, f64>,
+    finger_factors: AHashMap<Finger, f64>,
+}
+
+impl SameKeyRepeats {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            direction_costs: params.direction_costs.clone(),
+            finger_factors: params.finger_factors.clone(),
+        }
+    }
+}
+
+impl BigramMetric for SameKeyRepeats {
+    fn name(&self) -> &str {
+        "Same Key Repeats"
+    }
+
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if k1.key.matrix_position != k2.key.matrix_position
+            || k1.is_modifier.is_some()
+            || k2.is_modifier.is_some()
+        {
+            return Some(0.0);
+        }
+
+        let cost = match self.direction_costs.get(&k1.key.direction) {
+            Some(cost) => *cost,
+            None => return Some(0.0),
+        };
+        let factor = self
+            .finger_factors
+            .get(&k1.key.finger)
+            .cloned()
+            .unwrap_or(1.0);
+
+        Some(weight * cost * factor)
+    }
+}
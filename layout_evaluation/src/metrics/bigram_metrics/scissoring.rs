@@ -22,6 +22,72 @@ pub struct Parameters {
     pub lateral_center_cost: f64,
 }
 
+/// The kinds of scissoring movements between keys of adjacent non-thumb fingers
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ScissorKind {
+    SouthNorth,
+    LateralSqueeze,
+    LateralSplay,
+    LateralSeries,
+    LateralCenter,
+}
+
+impl ScissorKind {
+    /// Classify the transition between two keys by their directions. Returns `None` if the keys
+    /// do not belong to adjacent non-thumb fingers of the same hand or the transition is no
+    /// scissoring movement.
+    #[inline(always)]
+    pub fn of(k1: &LayerKey, k2: &LayerKey) -> Option<Self> {
+        // only adjacent non-thumb fingers, please
+        if (k1 == k2 && k1.is_modifier.is_some())
+            || k1.key.hand != k2.key.hand
+            || k1.key.finger.distance(&k2.key.finger) != 1
+            || k1.key.finger == Finger::Thumb
+            || k2.key.finger == Finger::Thumb
+        {
+            return None;
+        }
+
+        let finger_from = k1.key.finger;
+        let finger_to = k2.key.finger;
+
+        let dir_from = k1.key.direction;
+        let dir_to = k2.key.direction;
+
+        match (dir_from, dir_to) {
+            (South, North) | (North, South) => Some(Self::SouthNorth),
+            (In, In) | (Out, Out) => Some(Self::LateralSeries),
+            (In, Center) | (Out, Center) | (Center, In) | (Center, Out) => {
+                Some(Self::LateralCenter)
+            }
+            (In, Out) | (Out, In) => {
+                let inward_motion: bool = finger_from.numeric_index() > finger_to.numeric_index();
+
+                // think about it for a sec
+                let is_squeeze: bool = inward_motion ^ (dir_from == Out);
+
+                if is_squeeze {
+                    Some(Self::LateralSqueeze)
+                } else {
+                    Some(Self::LateralSplay)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The name of the metric parameter holding the cost of this kind of movement.
+    pub fn cost_parameter(&self) -> &'static str {
+        match self {
+            Self::SouthNorth => "south_north_cost",
+            Self::LateralSqueeze => "lateral_squeeze_cost",
+            Self::LateralSplay => "lateral_splay_cost",
+            Self::LateralSeries => "lateral_series_cost",
+            Self::LateralCenter => "lateral_center_cost",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Scissoring {
     south_north_cost: f64,
@@ -48,6 +114,14 @@ impl BigramMetric for Scissoring {
         "Scissoring"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -57,39 +131,13 @@ impl BigramMetric for Scissoring {
         _total_weight: f64,
         _layout: &Layout,
     ) -> Option<f64> {
-        // only adjacent non-thumb fingers, please
-        if (k1 == k2 && k1.is_modifier.is_some())
-            || k1.key.hand != k2.key.hand
-            || k1.key.finger.distance(&k2.key.finger) != 1
-            || k1.key.finger == Finger::Thumb
-            || k2.key.finger == Finger::Thumb
-        {
-            return Some(0.0);
-        }
-
-        let finger_from = k1.key.finger;
-        let finger_to = k2.key.finger;
-
-        let dir_from = k1.key.direction;
-        let dir_to = k2.key.direction;
-
-        let base_cost = match (dir_from, dir_to) {
-            (South, North) | (North, South) => self.south_north_cost,
-            (In, In) | (Out, Out) => self.lateral_series_cost,
-            (In, Center) | (Out, Center) | (Center, In) | (Center, Out) => self.lateral_center_cost,
-            (In, Out) | (Out, In) => {
-                let inward_motion: bool = finger_from.numeric_index() > finger_to.numeric_index();
-
-                // think about it for a sec
-                let is_squeeze: bool = inward_motion ^ (dir_from == Out);
-
-                if is_squeeze {
-                    self.lateral_squeeze_cost
-                } else {
-                    self.lateral_splay_cost
-                }
-            }
-            _ => 0.0,
+        let base_cost = match ScissorKind::of(k1, k2) {
+            Some(ScissorKind::SouthNorth) => self.south_north_cost,
+            Some(ScissorKind::LateralSqueeze) => self.lateral_squeeze_cost,
+            Some(ScissorKind::LateralSplay) => self.lateral_splay_cost,
+            Some(ScissorKind::LateralSeries) => self.lateral_series_cost,
+            Some(ScissorKind::LateralCenter) => self.lateral_center_cost,
+            None => 0.0,
         };
 
         Some(base_cost * weight)
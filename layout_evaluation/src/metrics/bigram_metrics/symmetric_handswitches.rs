@@ -30,6 +30,14 @@ impl BigramMetric for SymmetricHandswitches {
         "Symmetric Handswitches"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    fn position_based(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
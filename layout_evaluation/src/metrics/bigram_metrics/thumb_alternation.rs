@@ -0,0 +1,193 @@
+//! The bigram metric [`ThumbAlternation`] is informational only. It reports how the thumb
+//! presses (space, enter, layer keys, ...) are distributed between both thumbs and how often
+//! directly consecutive thumb presses alternate between the thumbs versus repeat on one thumb.
+//! This helps to assign the thumb clusters, in particular for space-heavy corpora.
+//!
+//! Thumb presses are usually separated by the keys of a word, which single bigrams do not
+//! capture. The bigrams are therefore read as a Markov chain over the keys: for each key, the
+//! probability that the next thumb press is on the left thumb follows from the bigrams starting
+//! at the key. This gives the share of alternations between consecutive thumb presses, including
+//! those separated by other keys.
+
+use super::BigramMetric;
+use crate::{ngram_mapper::BigramBuffer, results::MetricDetails};
+
+use keyboard_layout::{
+    key::{Finger, Hand},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+/// Maximum number of iterations for the probabilities of reaching the left thumb next
+const MAX_ITERATIONS: usize = 200;
+/// Largest change of a probability at which the iterations stop
+const TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Whether thumb modifiers (e.g. layer keys) are ignored
+    pub ignore_modifiers: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ThumbAlternation {
+    ignore_modifiers: bool,
+}
+
+impl ThumbAlternation {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            ignore_modifiers: params.ignore_modifiers,
+        }
+    }
+
+    fn is_thumb_press(&self, key: &LayerKey) -> bool {
+        key.key.finger == Finger::Thumb && !(self.ignore_modifiers && key.is_modifier.is_some())
+    }
+}
+
+/// Weighted share of transitions from thumb presses whose next thumb press (directly or after
+/// other keys) is on the other thumb.
+///
+/// For each key that is not a thumb press, the probability of pressing the left thumb next is
+/// the weighted mean over the bigrams starting at the key, where thumb presses count as zero or
+/// one and other keys with their own probability. These equations are solved by fixed-point
+/// iteration, starting from the share of the left thumb.
+fn chained_alternations(
+    bigrams: &BigramBuffer,
+    is_thumb_press: impl Fn(&LayerKey) -> bool,
+    left_fraction: f64,
+) -> f64 {
+    let key_table = bigrams.key_table();
+    let is_thumb: Vec<bool> = key_table.iter().map(&is_thumb_press).collect();
+    let is_left: Vec<bool> = key_table.iter().map(|k| k.key.hand == Hand::Left).collect();
+
+    let mut outgoing = vec![0.0; key_table.len()];
+    for (idx1, weight) in bigrams.keys1.iter().zip(&bigrams.weights) {
+        outgoing[*idx1 as usize] += weight;
+    }
+
+    // probability of reaching a left thumb press (first) from the second key of a bigram
+    let next_left = |idx: usize, left_next: &[f64]| match is_thumb[idx] {
+        true => is_left[idx] as u8 as f64,
+        false => left_next[idx],
+    };
+
+    let mut left_next = vec![left_fraction; key_table.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut updated = vec![0.0; key_table.len()];
+        for i in 0..bigrams.len() {
+            let (idx1, idx2) = (bigrams.keys1[i] as usize, bigrams.keys2[i] as usize);
+            updated[idx1] += bigrams.weights[i] * next_left(idx2, &left_next);
+        }
+
+        let mut max_change: f64 = 0.0;
+        for (idx, p) in updated.iter_mut().enumerate() {
+            *p = match outgoing[idx] > 0.0 {
+                true => *p / outgoing[idx],
+                false => left_fraction,
+            };
+            max_change = max_change.max((*p - left_next[idx]).abs());
+        }
+        left_next = updated;
+
+        if max_change < TOLERANCE {
+            break;
+        }
+    }
+
+    let mut alternating = 0.0;
+    let mut total = 0.0;
+    for i in 0..bigrams.len() {
+        let (idx1, idx2) = (bigrams.keys1[i] as usize, bigrams.keys2[i] as usize);
+        if !is_thumb[idx1] {
+            continue;
+        }
+
+        let p_left = next_left(idx2, &left_next);
+        let p_other = match is_left[idx1] {
+            true => 1.0 - p_left,
+            false => p_left,
+        };
+        alternating += bigrams.weights[i] * p_other;
+        total += bigrams.weights[i];
+    }
+
+    percentage(alternating, total)
+}
+
+/// Percentage of a weight relative to a total weight (zero if the total is zero).
+fn percentage(weight: f64, total: f64) -> f64 {
+    match total > 0.0 {
+        true => 100.0 * weight / total,
+        false => 0.0,
+    }
+}
+
+impl BigramMetric for ThumbAlternation {
+    fn name(&self) -> &str {
+        "Thumb Alternation"
+    }
+
+    fn total_cost(
+        &self,
+        bigrams: &BigramBuffer,
+        _total_weight: Option<f64>,
+        _layout: &Layout,
+    ) -> (f64, Option<MetricDetails>) {
+        let mut left_presses = 0.0;
+        let mut right_presses = 0.0;
+        let mut alternating = 0.0;
+        let mut same_thumb = 0.0;
+
+        for ((k1, k2), weight) in bigrams.iter() {
+            if !self.is_thumb_press(k2) {
+                continue;
+            }
+
+            match k2.key.hand {
+                Hand::Left => left_presses += weight,
+                Hand::Right => right_presses += weight,
+            }
+
+            if !self.is_thumb_press(k1) {
+                continue;
+            }
+
+            if k1.key.hand == k2.key.hand {
+                same_thumb += weight;
+            } else {
+                alternating += weight;
+            }
+        }
+
+        let thumb_presses = left_presses + right_presses;
+        let left_share = percentage(left_presses, thumb_presses);
+        let right_share = percentage(right_presses, thumb_presses);
+
+        let details = MetricDetails::new().values(
+            "",
+            "%",
+            vec![
+                ("Left Thumb", left_share),
+                ("Right Thumb", right_share),
+                (
+                    "Direct Alternations",
+                    percentage(alternating, alternating + same_thumb),
+                ),
+                (
+                    "Direct Same Thumb",
+                    percentage(same_thumb, alternating + same_thumb),
+                ),
+                (
+                    "Chained Alternations",
+                    chained_alternations(bigrams, |k| self.is_thumb_press(k), left_share / 100.0),
+                ),
+            ],
+        );
+
+        // informational only
+        (0.0, Some(details))
+    }
+}
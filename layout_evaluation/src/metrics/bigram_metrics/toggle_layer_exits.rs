@@ -0,0 +1,65 @@
+//! The bigram metric [`ToggleLayerExits`] incurs a cost for leaving a toggled layer at a word
+//! boundary. A toggled layer stays active until its modifier is tapped again. When a run of
+//! symbols on a toggled layer (e.g. a number) ends a word, the typist easily forgets to leave the
+//! layer before continuing with the next word, which then has to be deleted and typed again.
+//! Leaving a layer in the middle of a word is less error-prone, as the next symbol immediately
+//! reveals the wrong layer.
+//!
+//! The modifiers of toggled layers are resolved during ngram mapping, so leaving a layer at a
+//! word boundary shows up as a toggle modifier followed by whitespace.
+//!
+//! *Note:* Entering a toggled layer for a symbol on the key that produces whitespace on the base
+//! layer looks the same and is counted as well.
+//!
+//! *Note:* For layouts without toggled layers, the cost is always zero.
+
+use super::BigramMetric;
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost of leaving a toggled layer at a word boundary (i.e. the expected cost of forgetting it).
+    pub word_boundary_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ToggleLayerExits {
+    word_boundary_cost: f64,
+}
+
+impl ToggleLayerExits {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            word_boundary_cost: params.word_boundary_cost,
+        }
+    }
+}
+
+impl BigramMetric for ToggleLayerExits {
+    fn name(&self) -> &str {
+        "Toggle Layer Exits"
+    }
+
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if k1.is_modifier.is_toggle() && k2.is_modifier.is_none() && k2.symbol.is_whitespace() {
+            Some(weight * self.word_boundary_cost)
+        } else {
+            Some(0.0)
+        }
+    }
+}
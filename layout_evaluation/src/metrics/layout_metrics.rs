@@ -1,10 +1,14 @@
 //! The `metrics` module provides a trait for layout metrics.
+use crate::results::MetricDetails;
+
 use keyboard_layout::layout::Layout;
 
 use std::fmt;
 
+pub mod channel_coverage;
 pub mod kla_home_key_words;
 pub mod kla_same_finger_words;
+pub mod modifier_placement;
 pub mod shortcut_keys;
 pub mod similar_letter_groups;
 pub mod similar_letters;
@@ -14,7 +18,7 @@ pub trait LayoutMetric: Send + Sync + LayoutMetricClone + fmt::Debug {
     /// Return the name of the metric
     fn name(&self) -> &str;
     /// Compute the total cost for the metric
-    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>);
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>);
 }
 
 // in order to implement clone for Box<dyn LayoutMetric>, the following trick is necessary
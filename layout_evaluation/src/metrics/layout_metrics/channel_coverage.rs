@@ -0,0 +1,111 @@
+//! The layout metric [`ChannelCoverage`] reports how much of each ngram channel (e.g. digits or
+//! navigation keys, see [`NgramChannelConfig`]) the layout covers. The channels' ngrams are
+//! merged into the corpus' ngrams, where symbols that are not on the layout only count as "not
+//! found". This metric instead charges the share of each channel's unigram weight whose symbols
+//! are missing and lists the missing symbols.
+//!
+//! [`NgramChannelConfig`]: crate::ngrams::NgramChannelConfig
+
+use super::LayoutMetric;
+use crate::{ngrams::Unigrams, results::MetricDetails};
+
+use keyboard_layout::layout::Layout;
+
+use ordered_float::OrderedFloat;
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost of a channel whose symbols are all missing (the cost of a channel is proportional to
+    /// the share of its unigram weight that is missing)
+    pub missing_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ChannelCoverage {
+    missing_cost: f64,
+    /// Name and unigrams (sorted by descending weight) of each channel
+    channels: Vec<(String, Vec<(char, f64)>)>,
+}
+
+impl ChannelCoverage {
+    pub fn new(params: &Parameters, channels: &[(String, Unigrams)]) -> Self {
+        let channels = channels
+            .iter()
+            .map(|(name, unigrams)| {
+                let mut grams: Vec<(char, f64)> =
+                    unigrams.grams.iter().map(|(c, w)| (*c, *w)).collect();
+                grams.sort_by_key(|(c, w)| (OrderedFloat(-w), *c));
+                (name.clone(), grams)
+            })
+            .collect();
+
+        Self {
+            missing_cost: params.missing_cost,
+            channels,
+        }
+    }
+}
+
+impl LayoutMetric for ChannelCoverage {
+    fn name(&self) -> &str {
+        "Channel Coverage"
+    }
+
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>) {
+        let mut cost = 0.0;
+        let mut coverage = Vec::new();
+        let mut missing = Vec::new();
+        for (name, grams) in self.channels.iter() {
+            let total_weight: f64 = grams.iter().map(|(_, w)| w).sum();
+            if total_weight <= 0.0 {
+                continue;
+            }
+
+            let mut missing_weight = 0.0;
+            let mut missing_symbols = Vec::new();
+            for (c, weight) in grams.iter() {
+                if layout.get_layerkey_for_symbol(c).is_none() {
+                    missing_weight += weight;
+                    missing_symbols.push(c.escape_debug().to_string());
+                }
+            }
+            cost += self.missing_cost * missing_weight / total_weight;
+            coverage.push((name.clone(), 100.0 * (1.0 - missing_weight / total_weight)));
+            missing.push((format!("Missing {}", name), missing_symbols));
+        }
+
+        let details = missing.into_iter().fold(
+            MetricDetails::new().values("Covered", "%", coverage),
+            |details, (name, symbols)| details.labels(&name, symbols),
+        );
+
+        (cost, Some(details))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::test_layout;
+
+    #[test]
+    fn charges_the_missing_share_of_each_channel() {
+        let layout = test_layout("split_positions_only.yml");
+        assert!(layout.get_layerkey_for_symbol(&'a').is_some());
+        assert!(layout.get_layerkey_for_symbol(&'€').is_none());
+
+        let channels = vec![
+            ("letters".to_string(), Unigrams::from_text("aaa").unwrap()),
+            ("currency".to_string(), Unigrams::from_text("€€€a").unwrap()),
+        ];
+        let metric = ChannelCoverage::new(&Parameters { missing_cost: 2.0 }, &channels);
+        let (cost, details) = metric.total_cost(&layout);
+
+        assert!((cost - 1.5).abs() < 1e-9);
+        let details = details.unwrap();
+        assert_eq!(details.value("letters"), Some(100.0));
+        assert_eq!(details.value("currency"), Some(25.0));
+    }
+}
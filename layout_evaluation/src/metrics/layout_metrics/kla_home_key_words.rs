@@ -1,4 +1,5 @@
 use super::LayoutMetric;
+use crate::results::MetricDetails;
 
 use std::iter::FromIterator;
 
@@ -62,7 +63,7 @@ impl LayoutMetric for KLAHomeKeyWords {
         "Home Key Words"
     }
 
-    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>) {
         let mut found_weight = 0.0;
         let mut found_words = 0;
 
@@ -92,13 +93,14 @@ impl LayoutMetric for KLAHomeKeyWords {
             .map(|(_word, (_chars, _len, weight))| *weight)
             .sum();
 
-        let message = format!(
-            "{} out of {} (distinct lowercase) words",
-            found_words, total_words
+        let details = MetricDetails::new().values(
+            "Distinct lowercase words",
+            "",
+            vec![("found", found_words as f64), ("total", total_words)],
         );
 
         let cost = -(found_weight / total_weight);
 
-        (cost, Some(message))
+        (cost, Some(details))
     }
 }
@@ -1,6 +1,7 @@
 use std::iter::FromIterator;
 
 use super::LayoutMetric;
+use crate::results::MetricDetails;
 
 use ahash::{AHashMap, AHashSet};
 use keyboard_layout::layout::Layout;
@@ -55,7 +56,7 @@ impl LayoutMetric for KLASameFingerWords {
         "Same Finger Words"
     }
 
-    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>) {
         let mut found_char_weight = 0.0;
         let mut found_words = 0;
 
@@ -92,13 +93,14 @@ impl LayoutMetric for KLASameFingerWords {
             .map(|(_word, (_len, weight))| *weight)
             .sum();
 
-        let message = format!(
-            "{} out of {} (distinct lowercase) words",
-            found_words, total_words
+        let details = MetricDetails::new().values(
+            "Distinct lowercase words",
+            "",
+            vec![("found", found_words as f64), ("total", total_words)],
         );
 
         let cost = -(found_char_weight / total_weight);
 
-        (cost, Some(message))
+        (cost, Some(details))
     }
 }
@@ -0,0 +1,121 @@
+//! The layout metric [`ModifierPlacement`] constrains the positions of modifiers (Shift and layer
+//! keys). Modifiers that are located by a symbol (see [`ModifierLocation::Symbol`]) move with that
+//! symbol during an optimization, so their placement becomes part of the search space. This metric
+//! penalizes modifiers outside of the eligible positions and layers whose keys of one hand can not
+//! be reached with a modifier on the other hand.
+//!
+//! [`ModifierLocation::Symbol`]: keyboard_layout::layout::ModifierLocation::Symbol
+
+use super::LayoutMetric;
+use crate::results::MetricDetails;
+
+use keyboard_layout::{
+    key::{Hand, MatrixPosition},
+    layout::{LayerModifiers, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Matrix positions that may hold modifiers (all positions if empty)
+    #[serde(default)]
+    pub eligible_positions: Vec<MatrixPosition>,
+    /// Cost for each modifier outside of the eligible positions
+    pub misplaced_cost: f64,
+    /// Cost for each layer and hand whose keys can not be reached with a modifier on the other hand
+    pub one_hand_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ModifierPlacement {
+    eligible_positions: Vec<MatrixPosition>,
+    misplaced_cost: f64,
+    one_hand_cost: f64,
+}
+
+impl ModifierPlacement {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            eligible_positions: params.eligible_positions.clone(),
+            misplaced_cost: params.misplaced_cost,
+            one_hand_cost: params.one_hand_cost,
+        }
+    }
+}
+
+impl LayoutMetric for ModifierPlacement {
+    fn name(&self) -> &str {
+        "Modifier Placement"
+    }
+
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>) {
+        // a modifier may be used for several layers, consider each position only once
+        let mut modifier_positions: Vec<(char, MatrixPosition)> = Vec::new();
+        layout
+            .layerkeys
+            .iter()
+            .filter(|k| k.is_modifier.is_some())
+            .for_each(|k| {
+                let position = k.key.matrix_position;
+                if !modifier_positions.iter().any(|(_, p)| *p == position) {
+                    modifier_positions.push((k.symbol, position));
+                }
+            });
+
+        let misplaced: Vec<String> = modifier_positions
+            .iter()
+            .filter(|(_, p)| {
+                !self.eligible_positions.is_empty() && !self.eligible_positions.contains(p)
+            })
+            .map(|(c, p)| format!("{} at {:?}", c, (p.0, p.1)))
+            .collect();
+
+        // all keys of a hand on a layer share the same modifiers
+        let mut checked: Vec<(u8, Hand)> = Vec::new();
+        let mut one_handed: Vec<String> = Vec::new();
+        layout
+            .layerkeys
+            .iter()
+            .filter(|k| k.layer > 0 && k.is_modifier.is_none())
+            .for_each(|k| {
+                if checked.contains(&(k.layer, k.key.hand)) {
+                    return;
+                }
+                checked.push((k.layer, k.key.hand));
+
+                // toggled and Caps Word layers are not held, so their modifiers may be on the
+                // same hand
+                let modifiers = match &k.modifiers {
+                    LayerModifiers::LongPress
+                    | LayerModifiers::Toggle(_)
+                    | LayerModifiers::CapsWord(_) => return,
+                    modifiers => modifiers.layerkey_indices(),
+                };
+                let reachable = modifiers
+                    .iter()
+                    .any(|idx| layout.get_layerkey(idx).key.hand != k.key.hand);
+                if !reachable {
+                    tracing::trace!(
+                        layer = k.layer,
+                        hand = ?k.key.hand,
+                        "no modifier on the other hand",
+                    );
+                    one_handed.push(format!("layer {} ({:?} hand)", k.layer, k.key.hand));
+                }
+            });
+
+        let cost = misplaced.len() as f64 * self.misplaced_cost
+            + one_handed.len() as f64 * self.one_hand_cost;
+
+        if misplaced.is_empty() && one_handed.is_empty() {
+            return (cost, None);
+        }
+
+        let details = MetricDetails::new()
+            .labels("Misplaced modifiers", misplaced)
+            .labels("Layers without opposite-hand modifier", one_handed);
+
+        (cost, Some(details))
+    }
+}
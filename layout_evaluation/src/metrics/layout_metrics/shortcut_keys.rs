@@ -3,6 +3,7 @@
 //! The cost to add for each such a letter is configurable.
 
 use super::LayoutMetric;
+use crate::results::MetricDetails;
 
 use keyboard_layout::layout::Layout;
 
@@ -37,7 +38,7 @@ impl LayoutMetric for ShortcutKeys {
         "Badly Positioned Shortcut Keys"
     }
 
-    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>) {
         let mut cost = 0.0;
         let mut bad_keys = Vec::new();
         self.shortcut_chars.iter().for_each(|c| {
@@ -47,27 +48,28 @@ impl LayoutMetric for ShortcutKeys {
                 if k.key.matrix_position.0 > self.within_n_leftmost_cols {
                     cost += self.cost;
                     bad_keys.push(*c);
-                    log::trace!(
-                        "Shorcut: {}, Finger: {:>13}, Matrix Position: {:.0} (is > {}), Cost: {:>2.2}",
-                        c.escape_debug().to_string(),
-                        format!("{:?} {:?}", k.key.hand, k.key.finger),
-                        k.key.matrix_position.0,
-                        self.within_n_leftmost_cols,
-                        self.cost
+                    tracing::trace!(
+                        shortcut = %c.escape_debug(),
+                        hand = ?k.key.hand,
+                        finger = ?k.key.finger,
+                        column = k.key.matrix_position.0,
+                        max_column = self.within_n_leftmost_cols,
+                        cost = self.cost,
+                        "shortcut out of reach",
                     );
                 }
             }
         });
 
-        let message = if !bad_keys.is_empty() {
-            Some(format!(
-                "Bad shortcuts: {}",
-                bad_keys.iter().collect::<String>()
-            ))
-        } else {
-            None
-        };
+        if bad_keys.is_empty() {
+            return (cost, None);
+        }
+
+        let details = MetricDetails::new().labels(
+            "Bad shortcuts",
+            bad_keys.iter().map(|c| c.to_string()).collect(),
+        );
 
-        (cost, message)
+        (cost, Some(details))
     }
 }
@@ -10,6 +10,7 @@
 //! - vertical direction (top to bottom or bottom to top)
 
 use super::LayoutMetric;
+use crate::results::MetricDetails;
 
 use keyboard_layout::{key::Hand, layout::Layout};
 
@@ -39,7 +40,7 @@ fn costs<T: PartialEq>(data: &[T]) -> f64 {
     if data.is_empty() {
         return 0.0;
     }
-    let mut cost = 0.0;
+    let mut cost: f64 = 0.0;
     let mut n = 0.0;
     for (i, d1) in data.iter().enumerate() {
         for d2 in data.iter().skip(i + 1) {
@@ -50,7 +51,7 @@ fn costs<T: PartialEq>(data: &[T]) -> f64 {
         }
     }
 
-    ((cost / n) as f64).ln_1p()
+    (cost / n).ln_1p()
 }
 
 impl LayoutMetric for SimilarLetterGroups {
@@ -58,7 +59,7 @@ impl LayoutMetric for SimilarLetterGroups {
         "Similar Letter-Groups"
     }
 
-    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>) {
         let mut cost = 0.0;
 
         for (s1, s2) in &self.letter_group_pairs {
@@ -101,13 +102,14 @@ impl LayoutMetric for SimilarLetterGroups {
                 + costs(&v_directions);
 
             if cost > 0.0 {
-                log::trace!(
-                    "{} - {}, Hand direction: {:.2}, Finger direction: {:.2}, Column distance: {:.2}, Vertical direction: {:.2}",
-                    s1, s2,
-                    costs(&hand_directions),
-                    costs(&finger_directions),
-                    costs(&column_distances),
-                    costs(&v_directions),
+                tracing::trace!(
+                    group1 = %s1,
+                    group2 = %s2,
+                    hand_direction = costs(&hand_directions),
+                    finger_direction = costs(&finger_directions),
+                    column_distance = costs(&column_distances),
+                    vertical_direction = costs(&v_directions),
+                    "dissimilar groups",
                 )
             }
         }
@@ -8,6 +8,7 @@
 //! - 100% cost if none of the criteria apply
 
 use super::LayoutMetric;
+use crate::results::MetricDetails;
 
 use keyboard_layout::layout::Layout;
 
@@ -45,7 +46,7 @@ impl LayoutMetric for SimilarLetters {
         "Similar Letters"
     }
 
-    fn total_cost(&self, layout: &Layout) -> (f64, Option<String>) {
+    fn total_cost(&self, layout: &Layout) -> (f64, Option<MetricDetails>) {
         let mut cost = 0.0;
         let mut bad_pairs: Vec<String> = Vec::new();
 
@@ -82,30 +83,23 @@ impl LayoutMetric for SimilarLetters {
                 }
                 cost += cost_to_add;
 
-                log::trace!(
-                    "{} {:?} - {} {:?} - Cost: {}",
-                    c1,
-                    key1.matrix_position,
-                    c2,
-                    key2.matrix_position,
-                    cost_to_add
+                tracing::trace!(
+                    symbol1 = %c1,
+                    matrix_position1 = ?key1.matrix_position,
+                    symbol2 = %c2,
+                    matrix_position2 = ?key2.matrix_position,
+                    cost = cost_to_add,
+                    "similar letters",
                 );
             }
         }
 
-        let message = if !bad_pairs.is_empty() {
-            let mut bad_pairs_string = String::new();
-            for (i, pair) in bad_pairs.iter().enumerate() {
-                if i > 0 {
-                    bad_pairs_string.push_str(", ");
-                }
-                bad_pairs_string.push_str(pair);
-            }
-            Some(format!("Poorly placed pairs: {}", bad_pairs_string,))
-        } else {
-            None
-        };
+        if bad_pairs.is_empty() {
+            return (cost, None);
+        }
+
+        let details = MetricDetails::new().labels("Poorly placed pairs", bad_pairs);
 
-        (cost, message)
+        (cost, Some(details))
     }
 }
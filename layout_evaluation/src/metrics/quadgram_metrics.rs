@@ -0,0 +1,100 @@
+//! The `metrics` module provides a trait for quadgram metrics.
+use crate::{
+    metrics::total_cost_with_worst_ngrams, ngram_mapper::QuadgramBuffer, results::MetricDetails,
+};
+
+use keyboard_layout::layout::{LayerKey, Layout};
+
+use std::fmt;
+
+pub mod double_redirects;
+
+/// QuadgramMetric is a trait for metrics that iterates over weighted quadgrams.
+pub trait QuadgramMetric: Send + Sync + QuadgramMetricClone + fmt::Debug {
+    /// Return the name of the metric.
+    fn name(&self) -> &str;
+
+    /// Compute the cost of one quadgram (if that is possible, otherwise, return `None`).
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn individual_cost(
+        &self,
+        _key1: &LayerKey,
+        _key2: &LayerKey,
+        _key3: &LayerKey,
+        _key4: &LayerKey,
+        _weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        None
+    }
+
+    /// Compute the total cost for the metric.
+    fn total_cost(
+        &self,
+        quadgrams: &QuadgramBuffer,
+        // total_weight is optional for performance reasons (it can be computed from quadgrams)
+        total_weight: Option<f64>,
+        layout: &Layout,
+    ) -> (f64, Option<MetricDetails>) {
+        let total_weight = total_weight.unwrap_or_else(|| quadgrams.total_weight());
+        let table = quadgrams.key_table();
+        let costs = quadgrams
+            .keys1
+            .iter()
+            .zip(quadgrams.keys2.iter())
+            .zip(quadgrams.keys3.iter())
+            .zip(quadgrams.keys4.iter())
+            .zip(quadgrams.weights.iter())
+            .enumerate()
+            .filter_map(|(i, ((((idx1, idx2), idx3), idx4), weight))| {
+                let quadgram = (
+                    &table[*idx1 as usize],
+                    &table[*idx2 as usize],
+                    &table[*idx3 as usize],
+                    &table[*idx4 as usize],
+                );
+                let cost_option = self.individual_cost(
+                    quadgram.0,
+                    quadgram.1,
+                    quadgram.2,
+                    quadgram.3,
+                    *weight,
+                    total_weight,
+                    layout,
+                );
+                let nonfixed = !quadgram.0.is_fixed
+                    && !quadgram.1.is_fixed
+                    && !quadgram.2.is_fixed
+                    && !quadgram.3.is_fixed;
+
+                cost_option.map(|cost| (i, nonfixed, cost))
+            });
+
+        total_cost_with_worst_ngrams(costs, |i| {
+            let gram = quadgrams.keys(i);
+            format!("{}{}{}{}", gram.0, gram.1, gram.2, gram.3)
+        })
+    }
+}
+
+impl Clone for Box<dyn QuadgramMetric> {
+    fn clone(&self) -> Box<dyn QuadgramMetric> {
+        self.clone_box()
+    }
+}
+
+/// Helper trait for realizing clonability for `Box<dyn QuadgramMetric>`.
+pub trait QuadgramMetricClone {
+    fn clone_box(&self) -> Box<dyn QuadgramMetric>;
+}
+
+impl<T> QuadgramMetricClone for T
+where
+    T: 'static + QuadgramMetric + Clone,
+{
+    fn clone_box(&self) -> Box<dyn QuadgramMetric> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,108 @@
+//! The quadgram metric [`DoubleRedirects`] penalizes one-hand quadgrams whose finger sequence
+//! changes its direction twice, e.g. index → ring → middle → pinky. Such patterns can not be
+//! detected by trigram metrics, as every contained trigram is a single redirect only.
+//!
+//! The direction is determined by the fingers' order (counting from the thumb), so the
+//! consecutive keys need to be typed with different fingers. Double redirects that do not
+//! involve the index finger are considered "bad" and are weighted with an additional factor.
+
+use super::QuadgramMetric;
+
+use keyboard_layout::{
+    key::Finger,
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost of a double redirect.
+    pub cost: f64,
+    /// Factor for double redirects that do not involve the index finger.
+    pub bad_redirect_factor: f64,
+    /// Ignore quadgrams that contain a thumb key.
+    pub exclude_thumbs: bool,
+    /// Ignore quadgrams that contain a modifier.
+    pub exclude_modifiers: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct DoubleRedirects {
+    cost: f64,
+    bad_redirect_factor: f64,
+    exclude_thumbs: bool,
+    exclude_modifiers: bool,
+}
+
+impl DoubleRedirects {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            cost: params.cost,
+            bad_redirect_factor: params.bad_redirect_factor,
+            exclude_thumbs: params.exclude_thumbs,
+            exclude_modifiers: params.exclude_modifiers,
+        }
+    }
+}
+
+impl QuadgramMetric for DoubleRedirects {
+    fn name(&self) -> &str {
+        "Double Redirects"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        k4: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        let keys = [k1, k2, k3, k4];
+
+        let hand = k1.key.hand;
+        if keys.iter().any(|k| k.key.hand != hand) {
+            return Some(0.0);
+        }
+
+        if self.exclude_modifiers && keys.iter().any(|k| k.is_modifier.is_some()) {
+            return Some(0.0);
+        }
+
+        if self.exclude_thumbs && keys.iter().any(|k| k.key.finger == Finger::Thumb) {
+            return Some(0.0);
+        }
+
+        let direction = |a: &LayerKey, b: &LayerKey| {
+            a.key
+                .finger
+                .numeric_index()
+                .cmp(&b.key.finger.numeric_index())
+        };
+        let d1 = direction(k1, k2);
+        let d2 = direction(k2, k3);
+        let d3 = direction(k3, k4);
+
+        if d1 == Ordering::Equal
+            || d2 == Ordering::Equal
+            || d3 == Ordering::Equal
+            || d1 == d2
+            || d2 == d3
+        {
+            return Some(0.0);
+        }
+
+        let cost = if keys.iter().any(|k| k.key.finger == Finger::Index) {
+            self.cost
+        } else {
+            self.cost * self.bad_redirect_factor
+        };
+
+        Some(weight * cost)
+    }
+}
@@ -0,0 +1,263 @@
+//! The `scripting` module provides the common parts of custom metrics that are defined by
+//! [Rhai](https://rhai.rs) scripts in the evaluation config (see the `scripted_unigram_metrics` and
+//! `scripted_bigram_metrics` entries of the metric parameters). This requires the `scripting` feature.
+//!
+//! A script defines a function `cost` that receives the keys of an ngram and its weight and
+//! returns the (unweighted) cost of the ngram:
+//!
+//! ```rhai
+//! // unigram metric
+//! fn cost(key, weight) { if key.row == 0 { 1.0 } else { 0.0 } }
+//!
+//! // bigram metric
+//! fn cost(key1, key2, weight) {
+//!     if key1.hand == key2.hand && key1.finger != key2.finger && key1.row != key2.row { 1.0 } else { 0.0 }
+//! }
+//! ```
+//!
+//! The returned cost is multiplied with the ngram's weight. Returning `()` counts as zero cost.
+//! Each key is an object map with the following fields:
+//!
+//! | Field          | Type   | Description                                                   |
+//! |----------------|--------|---------------------------------------------------------------|
+//! | `symbol`       | string | the symbol generated by the key                               |
+//! | `layer`        | int    | the layer of the symbol                                       |
+//! | `hand`         | string | "Left" or "Right"                                             |
+//! | `finger`       | string | "Thumb", "Index", "Middle", "Ring", or "Pinky"                |
+//! | `finger_index` | int    | 0 (thumb) to 4 (pinky)                                        |
+//! | `direction`    | string | e.g. "Center", "North", "South", "In", "Out" (see key config) |
+//! | `column`/`row` | int    | the matrix position of the key                                |
+//! | `x`/`y`        | float  | the 2D position of the key                                    |
+//! | `key_cost`     | float  | the configured cost of the key                                |
+//! | `is_fixed`     | bool   | if the key is not permutated during optimization              |
+//! | `is_modifier`  | bool   | if the symbol is a modifier                                   |
+//!
+//! Scripts run sandboxed: they have no access to the file system or the environment and
+//! the number of operations per call is limited. Note that interpreting a script for each
+//! ngram is considerably slower than a compiled metric.
+
+use serde::Deserialize;
+
+fn default_max_operations() -> u64 {
+    100_000
+}
+
+fn default_performance_warning() -> bool {
+    true
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Name of the metric (as shown in reports).
+    pub name: String,
+    /// Rhai script defining the function `cost`.
+    pub script: String,
+    /// Maximum number of operations a single call of `cost` may perform.
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+    /// Warn about the performance impact of the scripted metric.
+    #[serde(default = "default_performance_warning")]
+    pub performance_warning: bool,
+}
+
+#[cfg(feature = "scripting")]
+pub use engine::{ScriptedBigram, ScriptedUnigram};
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use super::*;
+    use crate::metrics::{bigram_metrics::BigramMetric, unigram_metrics::UnigramMetric};
+
+    use keyboard_layout::layout::{LayerKey, Layout};
+    use rhai::{Dynamic, Engine, Map, Scope, AST};
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    /// A compiled script providing the `cost` function of a scripted metric.
+    #[derive(Clone)]
+    struct ScriptedCost {
+        name: String,
+        engine: Arc<Engine>,
+        ast: Arc<AST>,
+        error_reported: Arc<AtomicBool>,
+    }
+
+    impl fmt::Debug for ScriptedCost {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ScriptedCost")
+                .field("name", &self.name)
+                .finish()
+        }
+    }
+
+    impl ScriptedCost {
+        /// Compile the script of a scripted metric. The script needs to define a function `cost`
+        /// with the given number of arguments.
+        fn new(params: &Parameters, n_args: usize) -> Result<Self, String> {
+            let mut engine = Engine::new();
+            engine.set_max_operations(params.max_operations);
+            engine.set_max_expr_depths(64, 32);
+            engine.set_max_call_levels(16);
+            engine.set_max_string_size(10_000);
+            engine.set_max_array_size(10_000);
+            engine.set_max_map_size(1_000);
+            engine.disable_symbol("eval");
+            engine.on_print(|_| {});
+            engine.on_debug(|_, _, _| {});
+
+            let ast = engine
+                .compile(&params.script)
+                .map_err(|e| format!("Could not compile script: {}", e))?;
+            if !ast
+                .iter_functions()
+                .any(|f| f.name == "cost" && f.params.len() == n_args)
+            {
+                return Err(format!(
+                    "The script needs to define a function 'cost' with {} arguments",
+                    n_args
+                ));
+            }
+
+            if params.performance_warning {
+                log::warn!(
+                    "The scripted metric '{}' is interpreted for each ngram and slows down the evaluation considerably \
+                    (set 'performance_warning: false' to silence this warning)",
+                    params.name
+                );
+            }
+
+            Ok(Self {
+                name: params.name.clone(),
+                engine: Arc::new(engine),
+                ast: Arc::new(ast),
+                error_reported: Arc::new(AtomicBool::new(false)),
+            })
+        }
+
+        /// Call the script's `cost` function with the given keys and weight. Errors are reported
+        /// (once) and count as zero cost.
+        fn cost(&self, keys: &[&LayerKey], weight: f64) -> f64 {
+            let mut args: Vec<Dynamic> = keys.iter().map(|k| key_map(k).into()).collect();
+            args.push(weight.into());
+
+            let result = self
+                .engine
+                .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, "cost", args);
+
+            let cost = match result {
+                Ok(r) if r.is_unit() => Ok(0.0),
+                Ok(r) => r
+                    .as_float()
+                    .or_else(|_| r.as_int().map(|i| i as f64))
+                    .map_err(|t| format!("'cost' returned a {} instead of a number", t)),
+                Err(e) => Err(e.to_string()),
+            };
+
+            cost.unwrap_or_else(|e| {
+                if !self.error_reported.swap(true, Ordering::Relaxed) {
+                    log::error!("Error in scripted metric '{}': {}", self.name, e);
+                }
+                0.0
+            })
+        }
+    }
+
+    /// Represent a [`LayerKey`] as an object map for scripts.
+    fn key_map(k: &LayerKey) -> Map {
+        let mut map = Map::new();
+        map.insert("symbol".into(), k.symbol.to_string().into());
+        map.insert("layer".into(), (k.layer as i64).into());
+        map.insert("hand".into(), format!("{:?}", k.key.hand).into());
+        map.insert("finger".into(), format!("{:?}", k.key.finger).into());
+        map.insert(
+            "finger_index".into(),
+            (k.key.finger.numeric_index() as i64).into(),
+        );
+        map.insert("direction".into(), format!("{:?}", k.key.direction).into());
+        map.insert("column".into(), (k.key.matrix_position.0 as i64).into());
+        map.insert("row".into(), (k.key.matrix_position.1 as i64).into());
+        map.insert("x".into(), k.key.position.0.into());
+        map.insert("y".into(), k.key.position.1.into());
+        map.insert("key_cost".into(), k.key.cost.into());
+        map.insert("is_fixed".into(), k.is_fixed.into());
+        map.insert("is_modifier".into(), k.is_modifier.is_some().into());
+
+        map
+    }
+
+    /// A unigram metric whose cost is given by the script's function `cost(key, weight)`.
+    #[derive(Clone, Debug)]
+    pub struct ScriptedUnigram {
+        script: ScriptedCost,
+    }
+
+    impl ScriptedUnigram {
+        pub fn new(params: &Parameters) -> Result<Self, String> {
+            Ok(Self {
+                script: ScriptedCost::new(params, 2)?,
+            })
+        }
+    }
+
+    impl UnigramMetric for ScriptedUnigram {
+        fn name(&self) -> &str {
+            &self.script.name
+        }
+
+        fn sums_individual_costs(&self) -> bool {
+            true
+        }
+
+        #[inline(always)]
+        fn individual_cost(
+            &self,
+            key: &LayerKey,
+            weight: f64,
+            _total_weight: f64,
+            _layout: &Layout,
+        ) -> Option<f64> {
+            Some(weight * self.script.cost(&[key], weight))
+        }
+    }
+
+    /// A bigram metric whose cost is given by the script's function `cost(key1, key2, weight)`.
+    #[derive(Clone, Debug)]
+    pub struct ScriptedBigram {
+        script: ScriptedCost,
+    }
+
+    impl ScriptedBigram {
+        pub fn new(params: &Parameters) -> Result<Self, String> {
+            Ok(Self {
+                script: ScriptedCost::new(params, 3)?,
+            })
+        }
+    }
+
+    impl BigramMetric for ScriptedBigram {
+        fn name(&self) -> &str {
+            &self.script.name
+        }
+
+        fn sums_individual_costs(&self) -> bool {
+            true
+        }
+
+        #[inline(always)]
+        fn individual_cost(
+            &self,
+            k1: &LayerKey,
+            k2: &LayerKey,
+            weight: f64,
+            _total_weight: f64,
+            _layout: &Layout,
+        ) -> Option<f64> {
+            Some(weight * self.script.cost(&[k1, k2], weight))
+        }
+    }
+}
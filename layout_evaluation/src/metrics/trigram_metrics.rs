@@ -1,9 +1,11 @@
 //! The `metrics` module provides a trait for trigram metrics.
+use crate::{
+    metrics::total_cost_with_worst_ngrams, ngram_mapper::TrigramBuffer, results::MetricDetails,
+};
+
 use keyboard_layout::layout::{LayerKey, Layout};
 
-use ordered_float::OrderedFloat;
-use priority_queue::DoublePriorityQueue;
-use std::{env, fmt};
+use std::fmt;
 
 pub mod irregularity;
 pub mod no_handswitch_in_trigram;
@@ -16,6 +18,9 @@ pub mod oxey_inward_rolls;
 pub mod oxey_onehands;
 pub mod oxey_outward_rolls;
 pub mod oxey_redirects;
+pub mod punctuation_adjacency;
+pub mod return_to_home;
+pub mod same_cluster_sandwich;
 pub mod secondary_bigrams;
 pub mod trigram_finger_repeats;
 pub mod trigram_rolls;
@@ -42,25 +47,26 @@ pub trait TrigramMetric: Send + Sync + TrigramMetricClone + fmt::Debug {
     /// Compute the total cost for the metric.
     fn total_cost(
         &self,
-        trigrams: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
+        trigrams: &TrigramBuffer,
         // total_weight is optional for performance reasons (it can be computed from trigrams)
         total_weight: Option<f64>,
         layout: &Layout,
-    ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
-
-        let total_weight = total_weight.unwrap_or_else(|| trigrams.iter().map(|(_, w)| w).sum());
-        let cost_iter = trigrams
+    ) -> (f64, Option<MetricDetails>) {
+        let total_weight = total_weight.unwrap_or_else(|| trigrams.total_weight());
+        let table = trigrams.key_table();
+        let costs = trigrams
+            .keys1
             .iter()
+            .zip(trigrams.keys2.iter())
+            .zip(trigrams.keys3.iter())
+            .zip(trigrams.weights.iter())
             .enumerate()
-            .filter_map(|(i, (trigram, weight))| {
+            .filter_map(|(i, (((idx1, idx2), idx3), weight))| {
+                let trigram = (
+                    &table[*idx1 as usize],
+                    &table[*idx2 as usize],
+                    &table[*idx3 as usize],
+                );
                 let cost_option = self.individual_cost(
                     trigram.0,
                     trigram.1,
@@ -69,77 +75,15 @@ pub trait TrigramMetric: Send + Sync + TrigramMetricClone + fmt::Debug {
                     total_weight,
                     layout,
                 );
+                let nonfixed = !trigram.0.is_fixed && !trigram.1.is_fixed && !trigram.2.is_fixed;
 
-                cost_option.map(|cost| (i, trigram, cost))
+                cost_option.map(|cost| (i, nonfixed, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
-            let (total_cost, worst, worst_nonfixed) = cost_iter.fold(
-                (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
-                |(mut total_cost, mut worst, mut worst_nonfixed), (i, trigram, cost)| {
-                    total_cost += cost;
-
-                    if !trigram.0.is_fixed && !trigram.1.is_fixed && !trigram.2.is_fixed {
-                        worst_nonfixed.push(i, OrderedFloat(cost.abs()));
-                    }
-                    worst.push(i, OrderedFloat(cost.abs()));
-
-                    if worst.len() > n_worst {
-                        worst.pop_min();
-                    }
-                    if worst_nonfixed.len() > n_worst {
-                        worst_nonfixed.pop_min();
-                    }
-
-                    (total_cost, worst, worst_nonfixed)
-                },
-            );
-
-            let gen_msgs = |q: DoublePriorityQueue<usize, OrderedFloat<f64>>| {
-                let worst_msgs: Vec<String> = q
-                    .into_sorted_iter()
-                    .rev()
-                    .filter(|(_, cost)| cost.into_inner() > 0.0)
-                    .map(|(i, cost)| {
-                        let (gram, _) = trigrams[i];
-                        format!(
-                            "{}{}{} ({:>5.2}%)",
-                            gram.0,
-                            gram.1,
-                            gram.2,
-                            100.0 * cost.into_inner() / total_cost,
-                        )
-                    })
-                    .collect();
-
-                worst_msgs
-            };
-
-            let mut msgs = Vec::new();
-
-            let worst_msgs = gen_msgs(worst);
-            if !worst_msgs.is_empty() {
-                msgs.push(format!("Worst: {}", worst_msgs.join(", ")))
-            }
-
-            let worst_nonfixed_msgs = gen_msgs(worst_nonfixed);
-            if !worst_nonfixed_msgs.is_empty() {
-                msgs.push(format!(
-                    "Worst non-fixed: {}",
-                    worst_nonfixed_msgs.join(", ")
-                ))
-            }
-
-            let msg = Some(msgs.join(";  "));
-
-            (total_cost, msg)
-        } else {
-            let total_cost: f64 = cost_iter.map(|(_, _, c)| c).sum();
-
-            (total_cost, None)
-        };
-
-        (total_cost, msg)
+        total_cost_with_worst_ngrams(costs, |i| {
+            let gram = trigrams.keys(i);
+            format!("{}{}{}", gram.0, gram.1, gram.2)
+        })
     }
 }
 
@@ -7,13 +7,12 @@
 
 use super::TrigramMetric;
 use crate::metrics::bigram_metrics::BigramMetric;
-use crate::results::NormalizationType;
+use crate::metrics::total_cost_with_worst_ngrams;
+use crate::ngram_mapper::TrigramBuffer;
+use crate::results::{MetricDetails, NormalizationType};
 use keyboard_layout::layout::{LayerKey, Layout};
 
-use ordered_float::OrderedFloat;
-use priority_queue::DoublePriorityQueue;
 use serde::Deserialize;
-use std::env;
 
 #[derive(Clone, Deserialize, Debug)]
 pub struct Parameters {}
@@ -70,26 +69,27 @@ impl TrigramMetric for Irregularity {
 
     fn total_cost(
         &self,
-        trigrams: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
+        trigrams: &TrigramBuffer,
         total_weight: Option<f64>,
         layout: &Layout,
-    ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
-
+    ) -> (f64, Option<MetricDetails>) {
         // NOTE: ArneBab's solution does not involve all bigram metrics (the asymmetric bigrams metric is missing)
 
-        let total_weight = total_weight.unwrap_or_else(|| trigrams.iter().map(|(_, w)| w).sum());
-        let cost_iter = trigrams
+        let total_weight = total_weight.unwrap_or_else(|| trigrams.total_weight());
+        let table = trigrams.key_table();
+        let costs = trigrams
+            .keys1
             .iter()
+            .zip(trigrams.keys2.iter())
+            .zip(trigrams.keys3.iter())
+            .zip(trigrams.weights.iter())
             .enumerate()
-            .filter_map(|(i, (trigram, weight))| {
+            .filter_map(|(i, (((idx1, idx2), idx3), weight))| {
+                let trigram = (
+                    &table[*idx1 as usize],
+                    &table[*idx2 as usize],
+                    &table[*idx3 as usize],
+                );
                 let cost_option = self.individual_cost(
                     trigram.0,
                     trigram.1,
@@ -98,76 +98,16 @@ impl TrigramMetric for Irregularity {
                     total_weight,
                     layout,
                 );
+                let nonfixed = !trigram.0.is_fixed && !trigram.1.is_fixed && !trigram.2.is_fixed;
 
-                cost_option.map(|cost| (i, trigram, cost))
+                cost_option.map(|cost| (i, nonfixed, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
-            let (total_cost, worst, worst_nonfixed) = cost_iter.fold(
-                (0.0, DoublePriorityQueue::new(), DoublePriorityQueue::new()),
-                |(mut total_cost, mut worst, mut worst_nonfixed), (i, trigram, cost)| {
-                    total_cost += cost;
-
-                    if !trigram.0.is_fixed && !trigram.1.is_fixed && !trigram.2.is_fixed {
-                        worst_nonfixed.push(i, OrderedFloat(cost.abs()));
-                    }
-                    worst.push(i, OrderedFloat(cost.abs()));
-
-                    if worst.len() > n_worst {
-                        worst.pop_min();
-                    }
-                    if worst_nonfixed.len() > n_worst {
-                        worst_nonfixed.pop_min();
-                    }
-
-                    (total_cost, worst, worst_nonfixed)
-                },
-            );
-
-            let gen_msgs = |q: DoublePriorityQueue<usize, OrderedFloat<f64>>| {
-                let worst_msgs: Vec<String> = q
-                    .into_sorted_iter()
-                    .rev()
-                    .filter(|(_, cost)| cost.into_inner() > 0.0)
-                    .map(|(i, cost)| {
-                        let (gram, _) = trigrams[i];
-                        format!(
-                            "{}{}{} ({:>5.2}%)",
-                            gram.0,
-                            gram.1,
-                            gram.2,
-                            100.0 * cost.into_inner() / total_cost,
-                        )
-                    })
-                    .collect();
-
-                worst_msgs
-            };
-
-            let mut msgs = Vec::new();
-
-            let worst_msgs = gen_msgs(worst);
-            if !worst_msgs.is_empty() {
-                msgs.push(format!("Worst: {}", worst_msgs.join(", ")))
-            }
-
-            let worst_nonfixed_msgs = gen_msgs(worst_nonfixed);
-            if !worst_nonfixed_msgs.is_empty() {
-                msgs.push(format!(
-                    "Worst non-fixed: {}",
-                    worst_nonfixed_msgs.join(", ")
-                ))
-            }
-
-            let msg = Some(msgs.join(";  "));
-
-            (total_cost, msg)
-        } else {
-            let total_cost: f64 = cost_iter.map(|(_, _, c)| c).sum();
-
-            (total_cost, None)
-        };
+        let (total_cost, details) = total_cost_with_worst_ngrams(costs, |i| {
+            let gram = trigrams.keys(i);
+            format!("{}{}{}", gram.0, gram.1, gram.2)
+        });
 
-        (total_cost.sqrt(), msg)
+        (total_cost.sqrt(), details)
     }
 }
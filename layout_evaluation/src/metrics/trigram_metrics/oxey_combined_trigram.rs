@@ -1,4 +1,6 @@
 use super::TrigramMetric;
+use crate::ngram_mapper::TrigramBuffer;
+use crate::results::MetricDetails;
 
 use ahash::AHashSet;
 use keyboard_layout::{
@@ -181,11 +183,11 @@ impl TrigramMetric for OxeyCombinedTrigram {
 
     fn total_cost(
         &self,
-        trigrams: &[((&LayerKey, &LayerKey, &LayerKey), f64)],
+        trigrams: &TrigramBuffer,
         // total_weight is optional for performance reasons (it can be computed from trigrams)
         _total_weight: Option<f64>,
         _layout: &Layout,
-    ) -> (f64, Option<String>) {
+    ) -> (f64, Option<MetricDetails>) {
         let mut counts = TrigramTypeCounts::default();
 
         trigrams.iter().for_each(|((k1, k2, k3), weight)| {
@@ -218,29 +220,45 @@ impl TrigramMetric for OxeyCombinedTrigram {
             }
 
             if h1 == h2 && h2 == h3 {
-                self.same_hand(k1, k2, k3, *weight, &mut counts);
+                self.same_hand(k1, k2, k3, weight, &mut counts);
             } else if h1 == h2 || h2 == h3 {
-                self.roll(k1, k2, k3, *weight, &mut counts);
+                self.roll(k1, k2, k3, weight, &mut counts);
             } else {
-                self.alternate(k1, k2, k3, *weight, &mut counts);
+                self.alternate(k1, k2, k3, weight, &mut counts);
             }
         });
 
-        let message = format!(
-            "[SameHand: Onehand: {:.1} 2-Rep: {:.1} 1-Rep: {:.1} Redirect: {:.1} BadRedirect: {:.1}] [Roll: Inward: {:.1} Outward: {:.1} SameFinger: {:.1} Other: {:.1}] [Alternate: Normal: {:.1} SameKey: {:.1} SameFinger: {:.1}]",
-            100.0 * counts.same_hand_roll,
-            100.0 * counts.same_hand_double_finger_repeat,
-            100.0 * counts.same_hand_single_finger_repeat,
-            100.0 * counts.same_hand_redirect,
-            100.0 * counts.same_hand_bad_redirect,
-            100.0 * counts.roll_inwards,
-            100.0 * counts.roll_outwards,
-            100.0 * counts.roll_same_finger,
-            100.0 * counts.roll_other,
-            100.0 * counts.alternate_other_finger,
-            100.0 * counts.alternate_same_key,
-            100.0 * counts.alternate_finger_repeat,
-        );
+        let details = MetricDetails::new()
+            .values(
+                "SameHand",
+                "",
+                vec![
+                    ("Onehand", 100.0 * counts.same_hand_roll),
+                    ("2-Rep", 100.0 * counts.same_hand_double_finger_repeat),
+                    ("1-Rep", 100.0 * counts.same_hand_single_finger_repeat),
+                    ("Redirect", 100.0 * counts.same_hand_redirect),
+                    ("BadRedirect", 100.0 * counts.same_hand_bad_redirect),
+                ],
+            )
+            .values(
+                "Roll",
+                "",
+                vec![
+                    ("Inward", 100.0 * counts.roll_inwards),
+                    ("Outward", 100.0 * counts.roll_outwards),
+                    ("SameFinger", 100.0 * counts.roll_same_finger),
+                    ("Other", 100.0 * counts.roll_other),
+                ],
+            )
+            .values(
+                "Alternate",
+                "",
+                vec![
+                    ("Normal", 100.0 * counts.alternate_other_finger),
+                    ("SameKey", 100.0 * counts.alternate_same_key),
+                    ("SameFinger", 100.0 * counts.alternate_finger_repeat),
+                ],
+            );
 
         let cost_same_hand = counts.same_hand_double_finger_repeat
             * self.same_hand_double_finger_repeat
@@ -256,6 +274,6 @@ impl TrigramMetric for OxeyCombinedTrigram {
             + counts.alternate_same_key * self.alternate_same_key
             + counts.alternate_other_finger * self.alternate_other_finger;
 
-        (cost_same_hand + cost_roll + cost_alternate, Some(message))
+        (cost_same_hand + cost_roll + cost_alternate, Some(details))
     }
 }
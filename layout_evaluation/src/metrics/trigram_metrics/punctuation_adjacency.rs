@@ -0,0 +1,96 @@
+//! The trigram metric [`PunctuationAdjacency`] evaluates the transitions around trailing
+//! punctuation, i.e. trigrams consisting of a letter, a punctuation mark (e.g. `.`, `,`, `?`),
+//! and a whitespace (Space or Enter, usually on a thumb). As punctuation marks tend to follow
+//! specific letters, their placement relative to those letters matters.
+//!
+//! The letter-to-punctuation transition costs depend on whether the same finger, the same hand,
+//! or the other hand is used (negative costs reward a transition). Additionally, a cost is
+//! incurred if the whitespace is typed with the same hand's thumb as the punctuation mark.
+//!
+//! *Note:* Punctuation marks are identified by their symbol. With modifier splitting enabled,
+//! higher-layer punctuation marks are resolved to their base-layer keys and are not considered.
+
+use super::TrigramMetric;
+
+use keyboard_layout::{
+    key::Finger,
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Symbols that are considered trailing punctuation.
+    pub punctuation: String,
+    /// Cost if the punctuation mark is typed with the same finger as the preceding letter.
+    pub same_finger_cost: f64,
+    /// Cost if the punctuation mark is typed with another finger of the same hand.
+    pub same_hand_cost: f64,
+    /// Cost if the punctuation mark is typed with the other hand.
+    pub hand_switch_cost: f64,
+    /// Additional cost if the following whitespace is typed with the thumb of the same hand.
+    pub same_hand_thumb_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct PunctuationAdjacency {
+    punctuation: Vec<char>,
+    same_finger_cost: f64,
+    same_hand_cost: f64,
+    hand_switch_cost: f64,
+    same_hand_thumb_cost: f64,
+}
+
+impl PunctuationAdjacency {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            punctuation: params.punctuation.chars().collect(),
+            same_finger_cost: params.same_finger_cost,
+            same_hand_cost: params.same_hand_cost,
+            hand_switch_cost: params.hand_switch_cost,
+            same_hand_thumb_cost: params.same_hand_thumb_cost,
+        }
+    }
+}
+
+impl TrigramMetric for PunctuationAdjacency {
+    fn name(&self) -> &str {
+        "Punctuation Adjacency"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if !k1.symbol.is_alphabetic()
+            || !self.punctuation.contains(&k2.symbol)
+            || !k3.symbol.is_whitespace()
+            || k1.is_modifier.is_some()
+        {
+            return Some(0.0);
+        }
+
+        let transition_cost = if k1.key.hand != k2.key.hand {
+            self.hand_switch_cost
+        } else if k1.key.finger == k2.key.finger && k1 != k2 {
+            self.same_finger_cost
+        } else {
+            self.same_hand_cost
+        };
+
+        let thumb_cost = if k3.key.finger == Finger::Thumb && k3.key.hand == k2.key.hand {
+            self.same_hand_thumb_cost
+        } else {
+            0.0
+        };
+
+        Some(weight * (transition_cost + thumb_cost))
+    }
+}
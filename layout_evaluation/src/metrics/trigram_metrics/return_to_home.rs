@@ -0,0 +1,88 @@
+//! The trigram metric [`ReturnToHome`] models the time a finger needs to recover to its
+//! Center rest position after striking an off-center key of its cluster. A cost is only
+//! counted if the same finger is needed again within the next one or two keystrokes, because
+//! otherwise there is enough time to return home while other fingers are typing.
+//! The recovery cost depends on the direction of the off-center key (thumbs are excluded).
+
+use super::TrigramMetric;
+
+use ahash::AHashMap;
+use keyboard_layout::{
+    key::{Direction, Finger},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Recovery cost for each direction of a finger cluster (missing directions are free).
+    pub direction_costs: AHashMap<Direction, f64>,
+    /// Factor for the cost if the finger is needed again after one other keystroke (skipgram).
+    pub skipgram_factor: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReturnToHome {
+    direction_costs: AHashMap<Direction, f64>,
+    skipgram_factor: f64,
+}
+
+impl ReturnToHome {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            direction_costs: params.direction_costs.clone(),
+            skipgram_factor: params.skipgram_factor,
+        }
+    }
+}
+
+/// Whether the second key is struck by the same finger as the first (but is another key).
+#[inline(always)]
+fn same_finger_other_key(k1: &LayerKey, k2: &LayerKey) -> bool {
+    k1.key.hand == k2.key.hand
+        && k1.key.finger == k2.key.finger
+        && k1.key.matrix_position != k2.key.matrix_position
+}
+
+impl TrigramMetric for ReturnToHome {
+    fn name(&self) -> &str {
+        "Return to Home"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if k1.is_modifier.is_some() || k1.key.finger == Finger::Thumb {
+            return Some(0.0);
+        }
+
+        let direction_cost = match self.direction_costs.get(&k1.key.direction) {
+            Some(cost) => *cost,
+            None => return Some(0.0),
+        };
+
+        let factor = if same_finger_other_key(k1, k2) {
+            1.0
+        } else if k2.key.hand != k1.key.hand || k2.key.finger != k1.key.finger {
+            // the finger may only be needed again in the third keystroke
+            if same_finger_other_key(k1, k3) {
+                self.skipgram_factor
+            } else {
+                0.0
+            }
+        } else {
+            // the finger stays on the same key
+            0.0
+        };
+
+        Some(weight * direction_cost * factor)
+    }
+}
@@ -0,0 +1,101 @@
+//! The trigram metric [`SameClusterSandwich`] penalizes A-B-A patterns in which the first and
+//! the third key are struck by the same finger while the key in between is typed with the other
+//! hand or another finger. The finger has to leave its cluster position and come back after only
+//! one intervening keystroke, which feels much worse than the individual bigrams suggest.
+//!
+//! The cost depends on the pair of directions of the first and third key (like the costs of
+//! the cluster rolls). Optionally, sandwiches of the exact same key are counted as well.
+//!
+//! *Note:* Trigrams involving modifiers are not counted.
+
+use super::TrigramMetric;
+
+use ahash::AHashMap;
+use keyboard_layout::{
+    key::{Direction, Finger},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Cost for pairs of directions that are not listed in `costs`
+    pub default_cost: f64,
+    pub ignore_thumb: bool,
+    /// Whether the first and the third key may also be the exact same key
+    pub include_same_key: bool,
+    /// Cost for each pair of directions (from the first to the third key)
+    pub costs: AHashMap<Direction, AHashMap<Direction, f64>>,
+    /// Multiply the cost with these factors depending on the sandwiching finger
+    #[serde(default)]
+    pub finger_multipliers: AHashMap<Finger, f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SameClusterSandwich {
+    default_cost: f64,
+    ignore_thumb: bool,
+    include_same_key: bool,
+    costs: AHashMap<Direction, AHashMap<Direction, f64>>,
+    finger_multipliers: AHashMap<Finger, f64>,
+}
+
+impl SameClusterSandwich {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            default_cost: params.default_cost,
+            ignore_thumb: params.ignore_thumb,
+            include_same_key: params.include_same_key,
+            costs: params.costs.clone(),
+            finger_multipliers: params.finger_multipliers.clone(),
+        }
+    }
+}
+
+impl TrigramMetric for SameClusterSandwich {
+    fn name(&self) -> &str {
+        "Same-Cluster Sandwich"
+    }
+
+    #[inline(always)]
+    fn individual_cost(
+        &self,
+        k1: &LayerKey,
+        k2: &LayerKey,
+        k3: &LayerKey,
+        weight: f64,
+        _total_weight: f64,
+        _layout: &Layout,
+    ) -> Option<f64> {
+        if k1.is_modifier.is_some() || k2.is_modifier.is_some() || k3.is_modifier.is_some() {
+            return Some(0.0);
+        }
+
+        let finger = k1.key.finger;
+        if k1.key.hand != k3.key.hand
+            || finger != k3.key.finger
+            || (self.ignore_thumb && finger == Finger::Thumb)
+        {
+            return Some(0.0);
+        }
+
+        // the intervening key needs to be typed by another finger
+        if k2.key.hand == k1.key.hand && k2.key.finger == finger {
+            return Some(0.0);
+        }
+
+        if !self.include_same_key && k1.key.matrix_position == k3.key.matrix_position {
+            return Some(0.0);
+        }
+
+        let cost = self
+            .costs
+            .get(&k1.key.direction)
+            .and_then(|c| c.get(&k3.key.direction))
+            .unwrap_or(&self.default_cost);
+        let multiplier = self.finger_multipliers.get(&finger).unwrap_or(&1.0);
+
+        Some(weight * cost * multiplier)
+    }
+}
@@ -1,14 +1,22 @@
 //! The `metrics` module provides a trait for unigram metrics.
+use crate::{
+    metrics::{worst_ngrams, WorstNgrams},
+    results::MetricDetails,
+};
+
 use keyboard_layout::layout::{LayerKey, Layout};
 use ordered_float::OrderedFloat;
 use priority_queue::DoublePriorityQueue;
 
-use std::{env, fmt};
+use std::fmt;
 
 pub mod finger_balance;
 pub mod position_penalties;
 pub mod hand_disbalance;
 pub mod key_costs;
+pub mod lateral_load;
+pub mod learning_cost;
+pub mod modifier_load;
 pub mod modifier_usage;
 pub mod row_loads;
 
@@ -17,6 +25,15 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
     /// Return the name of the metric
     fn name(&self) -> &str;
 
+    /// Whether the total cost is the sum of the individual costs (metrics need to declare this
+    /// explicitly). Only then the metric may be
+    /// overridden for some layers (see [`LayerOverrideParameters`]).
+    ///
+    /// [`LayerOverrideParameters`]: crate::evaluation::LayerOverrideParameters
+    fn sums_individual_costs(&self) -> bool {
+        false
+    }
+
     /// Compute the cost of one unigram (if that is possible, otherwise, return `None`).
     #[inline(always)]
     fn individual_cost(
@@ -36,15 +53,11 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
         // total_weight is optional for performance reasons (it can be computed from unigrams)
         total_weight: Option<f64>,
         layout: &Layout,
-    ) -> (f64, Option<String>) {
-        let show_worst: bool = env::var("SHOW_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(true);
-        let n_worst: usize = env::var("N_WORST")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(3);
+    ) -> (f64, Option<MetricDetails>) {
+        let WorstNgrams {
+            show: show_worst,
+            n: n_worst,
+        } = worst_ngrams();
 
         let total_weight = total_weight.unwrap_or_else(|| unigrams.iter().map(|(_, w)| w).sum());
         let cost_iter = unigrams
@@ -56,7 +69,7 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
                 cost_option.map(|cost| (i, unigram, cost))
             });
 
-        let (total_cost, msg) = if show_worst {
+        let (total_cost, details) = if show_worst {
             let (total_cost, worst) = cost_iter.fold(
                 (0.0, DoublePriorityQueue::new()),
                 |(mut total_cost, mut worst), (i, _, cost)| {
@@ -70,36 +83,26 @@ pub trait UnigramMetric: Send + Sync + UnigramMetricClone + fmt::Debug {
                 },
             );
 
-            let mut msgs = Vec::new();
-
-            let worst_msgs: Vec<String> = worst
+            let worst_items: Vec<(String, f64)> = worst
                 .into_sorted_iter()
                 .rev()
                 .filter(|(_, cost)| cost.into_inner() > 0.0)
                 .map(|(i, cost)| {
                     let (gram, _) = unigrams[i];
-                    format!(
-                        "{} ({:>5.2}%)",
-                        gram,
-                        100.0 * cost.into_inner() / total_cost,
-                    )
+                    (gram.to_string(), 100.0 * cost.into_inner() / total_cost)
                 })
                 .collect();
 
-            if !worst_msgs.is_empty() {
-                msgs.push(format!("Worst unigrams: {}", worst_msgs.join(", ")))
-            }
-
-            let msg = Some(msgs.join(";  "));
+            let details = MetricDetails::new().items("Worst unigrams", "%", worst_items);
 
-            (total_cost, msg)
+            (total_cost, Some(details))
         } else {
             let total_cost: f64 = cost_iter.map(|(_, _, c)| c).sum();
 
             (total_cost, None)
         };
 
-        (total_cost, msg)
+        (total_cost, details)
     }
 }
 
@@ -6,6 +6,7 @@
 //! from the discrepancy computation.
 
 use super::UnigramMetric;
+use crate::results::MetricDetails;
 
 use keyboard_layout::{
     key::{Finger, Hand, HandFingerMap},
@@ -55,7 +56,7 @@ impl UnigramMetric for FingerBalance {
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
         _layout: &Layout,
-    ) -> (f64, Option<String>) {
+    ) -> (f64, Option<MetricDetails>) {
         let mut finger_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
 
         // NOTE: ArneBab includes the thumb in the computation (in contrast to here). I believe that this is not helpful,
@@ -76,12 +77,13 @@ impl UnigramMetric for FingerBalance {
             .filter(|((_hand, finger), _intended_load)| *finger != Finger::Thumb)
             .map(|((hand, finger), intended_load)| {
                 let load = finger_loads.get(hand, finger) / total_weight;
-                log::trace!(
-                    "Finger: {:>13}, Intended: {:>5.2}, Load: {:>5.2}, Fraction: {:>.4}",
-                    format!("{:?} {:?}", hand, finger),
-                    100.0 * intended_load,
-                    100.0 * load,
-                    load / intended_load,
+                tracing::trace!(
+                    ?hand,
+                    ?finger,
+                    intended_load = 100.0 * intended_load,
+                    load = 100.0 * load,
+                    fraction = load / intended_load,
+                    "finger load",
                 );
                 load / intended_load
             })
@@ -99,19 +101,23 @@ impl UnigramMetric for FingerBalance {
             .sum::<f64>()
             / (fractions.len() - 1) as f64;
 
-        let message = format!(
-            "Finger loads % (no thumb): {:.1} {:.1} {:.1} {:.1} - {:.1} {:.1} {:.1} {:.1}",
-            100.0 * finger_loads.get(&Hand::Left, &Finger::Pinky) / total_weight,
-            100.0 * finger_loads.get(&Hand::Left, &Finger::Ring) / total_weight,
-            100.0 * finger_loads.get(&Hand::Left, &Finger::Middle) / total_weight,
-            100.0 * finger_loads.get(&Hand::Left, &Finger::Index) / total_weight,
-            100.0 * finger_loads.get(&Hand::Right, &Finger::Index) / total_weight,
-            100.0 * finger_loads.get(&Hand::Right, &Finger::Middle) / total_weight,
-            100.0 * finger_loads.get(&Hand::Right, &Finger::Ring) / total_weight,
-            100.0 * finger_loads.get(&Hand::Right, &Finger::Pinky) / total_weight,
+        let load = |hand, finger| 100.0 * finger_loads.get(&hand, &finger) / total_weight;
+        let details = MetricDetails::new().values(
+            "Finger loads (no thumb)",
+            "%",
+            vec![
+                ("LP", load(Hand::Left, Finger::Pinky)),
+                ("LR", load(Hand::Left, Finger::Ring)),
+                ("LM", load(Hand::Left, Finger::Middle)),
+                ("LI", load(Hand::Left, Finger::Index)),
+                ("RI", load(Hand::Right, Finger::Index)),
+                ("RM", load(Hand::Right, Finger::Middle)),
+                ("RR", load(Hand::Right, Finger::Ring)),
+                ("RP", load(Hand::Right, Finger::Pinky)),
+            ],
         );
 
-        (var.sqrt(), Some(message))
+        (var.sqrt(), Some(details))
 
         // A version using the total variation distance instead of standard deviation
         // This is a more uniform approach, i.e. half distance on two fingers equals full distance on one finger
@@ -122,7 +128,7 @@ impl UnigramMetric for FingerBalance {
         //     .filter(|((_hand, finger), _intended_load)| *finger != Finger::Thumb)
         //     .map(|((hand, finger), intended_load)| {
         //         let load = finger_loads.get(hand, finger) / total_weight;
-        //         log::trace!(
+        //         tracing::trace!(
         //             "Finger: {:>13}, Intended: {:>5.2}, Load: {:>5.2}",
         //             format!("{:?} {:?}", hand, finger),
         //             100.0 * intended_load,
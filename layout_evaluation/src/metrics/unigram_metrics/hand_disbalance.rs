@@ -2,6 +2,7 @@
 //! for both hands (excluding thumbs). The resulting cost is the distance of each hand's load to 0.5.
 
 use super::UnigramMetric;
+use crate::results::MetricDetails;
 
 use keyboard_layout::{
     key::{Finger, Hand, HandMap},
@@ -32,7 +33,7 @@ impl UnigramMetric for HandDisbalance {
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
         _layout: &Layout,
-    ) -> (f64, Option<String>) {
+    ) -> (f64, Option<MetricDetails>) {
         let mut hand_loads: HandMap<f64> = HandMap::default();
         let mut total_weight = 0.0;
         unigrams
@@ -46,12 +47,15 @@ impl UnigramMetric for HandDisbalance {
         let left_fraction = hand_loads.get(&Hand::Left) / total_weight;
         let right_fraction = hand_loads.get(&Hand::Right) / total_weight;
 
-        let message = format!(
-            "Hand loads % (no thumb): {:.2} - {:.2}",
-            100.0 * left_fraction,
-            100.0 * right_fraction
+        let details = MetricDetails::new().values(
+            "Hand loads (no thumb)",
+            "%",
+            vec![
+                ("Left", 100.0 * left_fraction),
+                ("Right", 100.0 * right_fraction),
+            ],
         );
 
-        (0.5 * (left_fraction - right_fraction).abs(), Some(message))
+        (0.5 * (left_fraction - right_fraction).abs(), Some(details))
     }
 }
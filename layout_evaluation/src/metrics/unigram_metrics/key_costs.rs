@@ -25,6 +25,10 @@ impl UnigramMetric for KeyCost {
         "Key Costs"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -43,8 +47,15 @@ impl UnigramMetric for KeyCost {
 
         // log the top scorers (with weight > 1%)
         if weight > 0.01 * total_weight {
-            log::trace!("Unigram: {:>3}, Finger: {:<13}, Weight: {:>12.2}, Cost per key: {:>8.4}, Cost: {:>14.4}",
-                        key, format!("{:?} {:?}", key.key.hand, key.key.finger), weight, cost, weight * cost);
+            tracing::trace!(
+                unigram = %key,
+                hand = ?key.key.hand,
+                finger = ?key.key.finger,
+                weight,
+                cost_per_key = cost,
+                cost = weight * cost,
+                "unigram cost",
+            );
         }
 
         Some(weight * cost)
@@ -0,0 +1,82 @@
+//! The unigram metric [`LateralLoad`] sums the lateral (In and Out) deflections of each finger
+//! over the corpus. On the Svalboard, lateral motion is the main source of strain, especially
+//! for the pinky and ring fingers. Each finger's lateral load (relative to all keystrokes) is
+//! compared with a configurable threshold and the squared excess (in percent) is penalized.
+//!
+//! *Note:* Thumb keys have no lateral directions and are thus never counted.
+
+use super::UnigramMetric;
+use crate::results::MetricDetails;
+
+use keyboard_layout::{
+    key::{Direction, Finger, HandFingerMap},
+    layout::{LayerKey, Layout},
+};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Amount of deflection of each lateral direction (other directions are not counted)
+    pub direction_factors: AHashMap<Direction, f64>,
+    /// Lateral load (in percent of all keystrokes) of each finger above which it is penalized
+    /// (no limit if missing)
+    pub thresholds: AHashMap<Finger, f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LateralLoad {
+    direction_factors: AHashMap<Direction, f64>,
+    thresholds: AHashMap<Finger, f64>,
+}
+
+impl LateralLoad {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            direction_factors: params.direction_factors.clone(),
+            thresholds: params.thresholds.clone(),
+        }
+    }
+}
+
+impl UnigramMetric for LateralLoad {
+    fn name(&self) -> &str {
+        "Lateral Load"
+    }
+
+    fn total_cost(
+        &self,
+        unigrams: &[(&LayerKey, f64)],
+        total_weight: Option<f64>,
+        _layout: &Layout,
+    ) -> (f64, Option<MetricDetails>) {
+        let total_weight = total_weight.unwrap_or_else(|| unigrams.iter().map(|(_, w)| w).sum());
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
+
+        let mut lateral_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
+        unigrams.iter().for_each(|(key, weight)| {
+            if let Some(factor) = self.direction_factors.get(&key.key.direction) {
+                *lateral_loads.get_mut(&key.key.hand, &key.key.finger) += factor * weight;
+            }
+        });
+        lateral_loads
+            .iter_mut()
+            .for_each(|l| *l *= 100.0 / total_weight);
+
+        let cost = lateral_loads
+            .iter()
+            .zip(HandFingerMap::<f64>::keys().iter())
+            .map(|(load, (_, finger))| match self.thresholds.get(finger) {
+                Some(threshold) if load > threshold => (load - threshold).powi(2),
+                _ => 0.0,
+            })
+            .sum();
+
+        let details = MetricDetails::new().finger_values("Lateral loads", "%", &lateral_loads);
+
+        (cost, Some(details))
+    }
+}
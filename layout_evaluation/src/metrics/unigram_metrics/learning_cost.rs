@@ -0,0 +1,164 @@
+//! The unigram metric [`LearningCost`] estimates the effort required to retrain from a
+//! reference layout (e.g. QWERTY or the layout currently in use). Each symbol that moved to
+//! another hand, finger, or direction (compared to the reference) incurs a configurable cost
+//! that is weighted with the symbol's frequency.
+//!
+//! The reference layout is given as a layout string in the same format as the evaluated
+//! layouts, i.e. its i-th symbol is located on the i-th non-fixed key of the keyboard.
+
+use super::UnigramMetric;
+use crate::results::MetricDetails;
+
+use keyboard_layout::{
+    key::Key,
+    layout::{LayerKey, Layout},
+};
+
+use ahash::AHashMap;
+use serde::Deserialize;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Layout string of the reference layout (whitespace is ignored).
+    pub reference_layout: String,
+    /// Cost for a symbol that is typed with the other hand than in the reference layout.
+    pub hand_cost: f64,
+    /// Cost for a symbol that is typed with another finger of the same hand.
+    pub finger_cost: f64,
+    /// Cost for a symbol that is typed with the same finger, but in another direction.
+    pub direction_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct LearningCost {
+    reference_layout: Vec<char>,
+    hand_cost: f64,
+    finger_cost: f64,
+    direction_cost: f64,
+    length_mismatch_reported: Arc<AtomicBool>,
+}
+
+impl LearningCost {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            reference_layout: params
+                .reference_layout
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect(),
+            hand_cost: params.hand_cost,
+            finger_cost: params.finger_cost,
+            direction_cost: params.direction_cost,
+            length_mismatch_reported: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Map each symbol of the reference layout to the key it is located on in the given layout's keyboard.
+    ///
+    /// A reference layout with another number of symbols than the layout has keys does not
+    /// describe their positions. This is reported (once), and only the common prefix is compared.
+    fn reference_keys<'a>(&self, layout: &'a Layout) -> AHashMap<char, &'a Key> {
+        let keys: Vec<&LayerKey> = layout
+            .layerkeys
+            .iter()
+            .filter(|k| k.layer == 0 && !k.is_fixed && k.is_modifier.is_none())
+            .collect();
+
+        if keys.len() != self.reference_layout.len()
+            && !self.length_mismatch_reported.swap(true, Ordering::Relaxed)
+        {
+            log::error!(
+                "The reference layout of the learning cost has {} symbols, but the layout has {} \
+                 keys. Only the first {} symbols are compared.",
+                self.reference_layout.len(),
+                keys.len(),
+                keys.len().min(self.reference_layout.len()),
+            );
+        }
+
+        keys.into_iter()
+            .zip(self.reference_layout.iter())
+            .map(|(k, c)| (*c, &k.key))
+            .collect()
+    }
+}
+
+impl UnigramMetric for LearningCost {
+    fn name(&self) -> &str {
+        "Learning Cost"
+    }
+
+    fn total_cost(
+        &self,
+        unigrams: &[(&LayerKey, f64)],
+        total_weight: Option<f64>,
+        layout: &Layout,
+    ) -> (f64, Option<MetricDetails>) {
+        let reference_keys = self.reference_keys(layout);
+        let total_weight = total_weight.unwrap_or_else(|| unigrams.iter().map(|(_, w)| w).sum());
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
+
+        let mut moved_hand = 0.0;
+        let mut moved_finger = 0.0;
+        let mut moved_direction = 0.0;
+        let mut moved_symbols = Vec::new();
+
+        let total_cost: f64 = unigrams
+            .iter()
+            .filter(|(key, _weight)| key.is_modifier.is_none())
+            .filter_map(|(key, weight)| {
+                let reference_key = reference_keys.get(&key.symbol)?;
+
+                let cost = if reference_key.hand != key.key.hand {
+                    moved_hand += *weight;
+                    self.hand_cost
+                } else if reference_key.finger != key.key.finger {
+                    moved_finger += *weight;
+                    self.finger_cost
+                } else if reference_key.direction != key.key.direction {
+                    moved_direction += *weight;
+                    self.direction_cost
+                } else {
+                    return None;
+                };
+
+                if !moved_symbols.contains(&key.symbol) {
+                    moved_symbols.push(key.symbol);
+                }
+
+                tracing::trace!(
+                    symbol = %key,
+                    reference_hand = ?reference_key.hand,
+                    reference_finger = ?reference_key.finger,
+                    hand = ?key.key.hand,
+                    finger = ?key.key.finger,
+                    weight,
+                    cost = weight * cost,
+                    "moved symbol",
+                );
+
+                Some(weight * cost)
+            })
+            .sum();
+
+        let details = MetricDetails::new()
+            .values("", "", vec![("Moved symbols", moved_symbols.len() as f64)])
+            .values(
+                "Typed with other",
+                "%",
+                vec![
+                    ("hand", 100.0 * moved_hand / total_weight),
+                    ("finger", 100.0 * moved_finger / total_weight),
+                    ("direction", 100.0 * moved_direction / total_weight),
+                ],
+            );
+
+        (total_cost, Some(details))
+    }
+}
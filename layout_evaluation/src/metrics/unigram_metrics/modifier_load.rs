@@ -0,0 +1,101 @@
+//! The unigram metric [`ModifierLoad`] measures how the duty of holding modifiers (Shift,
+//! layer holds, long-press layer taps) is distributed across the fingers and hands. The load of a
+//! finger is the fraction of all keystrokes during which it holds a modifier. Each finger's load
+//! exceeding a threshold incurs a cost, as does a disbalance between the hands' loads.
+
+use super::UnigramMetric;
+use crate::results::MetricDetails;
+
+use keyboard_layout::{
+    key::{Hand, HandFingerMap, HandMap},
+    layout::{LayerKey, Layout},
+};
+
+use serde::Deserialize;
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Parameters {
+    /// Fraction of all keystrokes a single finger may hold modifiers without incurring costs.
+    pub max_finger_load: f64,
+    /// Cost factor for the difference of the two hands' modifier loads.
+    pub hand_disbalance_cost: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ModifierLoad {
+    max_finger_load: f64,
+    hand_disbalance_cost: f64,
+}
+
+impl ModifierLoad {
+    pub fn new(params: &Parameters) -> Self {
+        Self {
+            max_finger_load: params.max_finger_load,
+            hand_disbalance_cost: params.hand_disbalance_cost,
+        }
+    }
+}
+
+impl UnigramMetric for ModifierLoad {
+    fn name(&self) -> &str {
+        "Modifier Load"
+    }
+
+    fn total_cost(
+        &self,
+        unigrams: &[(&LayerKey, f64)],
+        total_weight: Option<f64>,
+        layout: &Layout,
+    ) -> (f64, Option<MetricDetails>) {
+        let total_weight = total_weight.unwrap_or_else(|| unigrams.iter().map(|(_, w)| w).sum());
+        if total_weight <= 0.0 {
+            return (0.0, None);
+        }
+
+        let mut finger_loads: HandFingerMap<f64> = HandFingerMap::with_default(0.0);
+        unigrams.iter().for_each(|(key, weight)| {
+            // the key itself is a held modifier (after splitting modifiers in ngram splitting)
+            if key.is_modifier.is_held() || key.is_modifier.is_long_press() {
+                *finger_loads.get_mut(&key.key.hand, &key.key.finger) += *weight;
+            }
+
+            // modifiers that were not split in ngram splitting
+            if let Some(indices) = key.modifiers.held() {
+                indices.iter().for_each(|idx| {
+                    let modifier = layout.get_layerkey(idx);
+                    *finger_loads.get_mut(&modifier.key.hand, &modifier.key.finger) += *weight;
+                });
+            }
+        });
+
+        finger_loads
+            .iter_mut()
+            .for_each(|load| *load /= total_weight);
+
+        let mut hand_loads: HandMap<f64> = HandMap::default();
+        let finger_cost: f64 = HandFingerMap::<f64>::keys()
+            .iter()
+            .map(|(hand, finger)| {
+                let load = *finger_loads.get(hand, finger);
+                *hand_loads.get_mut(hand) += load;
+                (load - self.max_finger_load).max(0.0)
+            })
+            .sum();
+
+        let left_load = *hand_loads.get(&Hand::Left);
+        let right_load = *hand_loads.get(&Hand::Right);
+        let cost = finger_cost + self.hand_disbalance_cost * (left_load - right_load).abs();
+
+        let mut percentages = finger_loads;
+        percentages.iter_mut().for_each(|load| *load *= 100.0);
+        let details = MetricDetails::new()
+            .finger_values("Modifier hold load", "%", &percentages)
+            .values(
+                "Hands",
+                "%",
+                vec![("Left", 100.0 * left_load), ("Right", 100.0 * right_load)],
+            );
+
+        (cost, Some(details))
+    }
+}
@@ -11,6 +11,10 @@ pub struct Parameters {
     pub hold_cost: f64,
     pub one_shot_cost: f64,
     pub long_press_cost: f64,
+    #[serde(default)]
+    pub toggle_cost: f64,
+    #[serde(default)]
+    pub caps_word_cost: f64,
 }
 
 #[derive(Clone, Debug)]
@@ -18,6 +22,8 @@ pub struct ModifierUsage {
     pub hold_cost: f64,
     pub one_shot_cost: f64,
     pub long_press_cost: f64,
+    pub toggle_cost: f64,
+    pub caps_word_cost: f64,
 }
 
 impl ModifierUsage {
@@ -26,6 +32,8 @@ impl ModifierUsage {
             hold_cost: params.hold_cost,
             one_shot_cost: params.one_shot_cost,
             long_press_cost: params.long_press_cost,
+            toggle_cost: params.toggle_cost,
+            caps_word_cost: params.caps_word_cost,
         }
     }
 }
@@ -35,6 +43,10 @@ impl UnigramMetric for ModifierUsage {
         "Modifier Usage"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -45,17 +57,21 @@ impl UnigramMetric for ModifierUsage {
     ) -> Option<f64> {
         // costs if this key is a modifier
         let key_cost = match key.is_modifier {
-            LayerModifierType::Hold => self.hold_cost,
+            LayerModifierType::Hold | LayerModifierType::Chord => self.hold_cost,
             LayerModifierType::OneShot => self.one_shot_cost,
             LayerModifierType::LongPress => self.long_press_cost,
+            LayerModifierType::Toggle => self.toggle_cost,
+            LayerModifierType::CapsWord => self.caps_word_cost,
             _ => 0.0,
         };
 
         // costs if this key relies on modifiers (that were not split in ngram splitting)
         let modifier_costs = match &key.modifiers {
-            LayerModifiers::Hold(v) => self.hold_cost * v.len() as f64,
+            LayerModifiers::Hold(v) | LayerModifiers::Chord(v) => self.hold_cost * v.len() as f64,
             LayerModifiers::OneShot(v) => self.one_shot_cost * v.len() as f64,
             LayerModifiers::LongPress => self.long_press_cost,
+            LayerModifiers::Toggle(v) => self.toggle_cost * v.len() as f64,
+            LayerModifiers::CapsWord(v) => self.caps_word_cost * v.len() as f64,
         };
 
         Some(weight * (key_cost + modifier_costs))
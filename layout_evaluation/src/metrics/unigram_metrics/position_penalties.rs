@@ -36,6 +36,10 @@ impl UnigramMetric for PositionPenalties {
         "Position Penalties"
     }
 
+    fn sums_individual_costs(&self) -> bool {
+        true
+    }
+
     #[inline(always)]
     fn individual_cost(
         &self,
@@ -50,9 +54,13 @@ impl UnigramMetric for PositionPenalties {
             let matrix_pos = (key.key.matrix_position.0, key.key.matrix_position.1);
 
             if let Some(penalty_cost) = penalty_map.get(&matrix_pos) {
-                log::trace!(
-                    "Penalty: Symbol '{}' at position {:?}, Weight: {:>12.2}, Penalty: {:>8.4}, Cost: {:>14.4}",
-                    symbol, matrix_pos, weight, penalty_cost, weight * penalty_cost
+                tracing::trace!(
+                    %symbol,
+                    matrix_position = ?matrix_pos,
+                    weight,
+                    penalty = penalty_cost,
+                    cost = weight * penalty_cost,
+                    "position penalty",
                 );
                 return Some(weight * penalty_cost);
             }
@@ -3,6 +3,7 @@
 //! fixed and thumb keys).
 
 use super::UnigramMetric;
+use crate::results::MetricDetails;
 
 use itertools::Itertools;
 use keyboard_layout::{
@@ -35,7 +36,7 @@ impl UnigramMetric for RowLoads {
         unigrams: &[(&LayerKey, f64)],
         _total_weight: Option<f64>,
         _layout: &Layout,
-    ) -> (f64, Option<String>) {
+    ) -> (f64, Option<MetricDetails>) {
         let mut row_load: AHashMap<u8, f64> = AHashMap::default();
         let mut total_weight = 0.0;
         unigrams
@@ -46,18 +47,12 @@ impl UnigramMetric for RowLoads {
                 total_weight += *weight;
             });
 
-        let mut messages = Vec::new();
-
-        row_load
+        let loads = row_load
             .into_iter()
             .sorted_by_key(|(row, _)| *row)
-            .for_each(|(row, load)| {
-                let msg = format!("Row {}: {:>.1}%", row, 100.0 * load / total_weight);
-                messages.push(msg);
-            });
-
-        let message = messages.join("; ");
+            .map(|(row, load)| (format!("Row {}", row), 100.0 * load / total_weight))
+            .collect();
 
-        (0.0, Some(message))
+        (0.0, Some(MetricDetails::new().values("", "%", loads)))
     }
 }
@@ -30,15 +30,19 @@
 //! of the involved base-keys and modifiers. Keys from the latter parts of the trigram will always be after
 //! former ones and modifers always come before their base key. The number of generated trigrams from a single
 //! trigram can be large (tens of trigrams) if multiple symbols of the trigram are accessed using multiple modifiers.
+//!
+//! Each quadgram of higher-layer symbols is expanded into the sequence of its keys, each preceded by its
+//! modifiers, and transforms into all quadgrams of that sequence.
 
 pub mod bigram_mapper;
 pub mod common;
+pub mod quadgram_mapper;
 pub mod trigram_mapper;
 pub mod unigram_mapper;
 
 pub mod on_demand_ngram_mapper;
 
-use keyboard_layout::layout::{LayerKey, Layout};
+use keyboard_layout::layout::{LayerKey, LayerKeyIndex, Layout};
 
 use std::fmt;
 
@@ -53,25 +57,298 @@ pub struct MappedUnigrams<'s> {
     pub weight_found: f64,
 }
 
+/// Bigrams in terms of a [`Layout`]'s [`LayerKey`]s, stored as parallel arrays of
+/// [`LayerKeyIndex`]s and weights (struct-of-arrays).
+///
+/// The index arrays are used directly by index-based consumers (e.g. [`CostMatrix`] and
+/// [`BigramCostCache`]); metrics resolve the indices with the layout's key table (see
+/// [`BigramBuffer::key_table`]). The buffers do not make iterating over [`LayerKey`]s any
+/// faster than a vector of `LayerKey` tuples (see the `ngram_buffers` benchmark).
+///
+/// [`CostMatrix`]: crate::cost_matrix::CostMatrix
+/// [`BigramCostCache`]: crate::bigram_cache::BigramCostCache
+#[derive(Clone, Debug)]
+pub struct BigramBuffer<'s> {
+    layout: &'s Layout,
+    /// First keys of the bigrams
+    pub keys1: Vec<LayerKeyIndex>,
+    /// Second keys of the bigrams
+    pub keys2: Vec<LayerKeyIndex>,
+    /// Weights (frequencies) of the bigrams
+    pub weights: Vec<f64>,
+}
+
+impl<'s> BigramBuffer<'s> {
+    pub fn with_capacity(layout: &'s Layout, capacity: usize) -> Self {
+        Self {
+            layout,
+            keys1: Vec::with_capacity(capacity),
+            keys2: Vec::with_capacity(capacity),
+            weights: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(&mut self, idx1: LayerKeyIndex, idx2: LayerKeyIndex, weight: f64) {
+        self.keys1.push(idx1);
+        self.keys2.push(idx2);
+        self.weights.push(weight);
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// Sum of all bigrams' weights.
+    pub fn total_weight(&self) -> f64 {
+        self.weights.iter().sum()
+    }
+
+    /// The layout's [`LayerKey`]s, indexed by the [`LayerKeyIndex`]s of the buffer.
+    #[inline(always)]
+    pub fn key_table(&self) -> &'s [LayerKey] {
+        &self.layout.layerkeys
+    }
+
+    /// The [`LayerKey`]s of the `i`-th bigram.
+    #[inline(always)]
+    pub fn keys(&self, i: usize) -> (&'s LayerKey, &'s LayerKey) {
+        let table = self.key_table();
+        (
+            &table[self.keys1[i] as usize],
+            &table[self.keys2[i] as usize],
+        )
+    }
+
+    /// Iterate over all bigrams in terms of [`LayerKey`]s together with their weights.
+    pub fn iter(&self) -> impl Iterator<Item = ((&'s LayerKey, &'s LayerKey), f64)> + '_ {
+        let table = self.key_table();
+        self.keys1
+            .iter()
+            .zip(self.keys2.iter())
+            .zip(self.weights.iter())
+            .map(move |((idx1, idx2), w)| ((&table[*idx1 as usize], &table[*idx2 as usize]), *w))
+    }
+}
+
+/// Trigrams in terms of a [`Layout`]'s [`LayerKey`]s, stored as parallel arrays of
+/// [`LayerKeyIndex`]s and weights (struct-of-arrays, see [`BigramBuffer`]).
+#[derive(Clone, Debug)]
+pub struct TrigramBuffer<'s> {
+    layout: &'s Layout,
+    /// First keys of the trigrams
+    pub keys1: Vec<LayerKeyIndex>,
+    /// Second keys of the trigrams
+    pub keys2: Vec<LayerKeyIndex>,
+    /// Third keys of the trigrams
+    pub keys3: Vec<LayerKeyIndex>,
+    /// Weights (frequencies) of the trigrams
+    pub weights: Vec<f64>,
+}
+
+impl<'s> TrigramBuffer<'s> {
+    pub fn with_capacity(layout: &'s Layout, capacity: usize) -> Self {
+        Self {
+            layout,
+            keys1: Vec::with_capacity(capacity),
+            keys2: Vec::with_capacity(capacity),
+            keys3: Vec::with_capacity(capacity),
+            weights: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(
+        &mut self,
+        idx1: LayerKeyIndex,
+        idx2: LayerKeyIndex,
+        idx3: LayerKeyIndex,
+        weight: f64,
+    ) {
+        self.keys1.push(idx1);
+        self.keys2.push(idx2);
+        self.keys3.push(idx3);
+        self.weights.push(weight);
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// Sum of all trigrams' weights.
+    pub fn total_weight(&self) -> f64 {
+        self.weights.iter().sum()
+    }
+
+    /// The layout's [`LayerKey`]s, indexed by the [`LayerKeyIndex`]s of the buffer.
+    #[inline(always)]
+    pub fn key_table(&self) -> &'s [LayerKey] {
+        &self.layout.layerkeys
+    }
+
+    /// The [`LayerKey`]s of the `i`-th trigram.
+    #[inline(always)]
+    pub fn keys(&self, i: usize) -> (&'s LayerKey, &'s LayerKey, &'s LayerKey) {
+        let table = self.key_table();
+        (
+            &table[self.keys1[i] as usize],
+            &table[self.keys2[i] as usize],
+            &table[self.keys3[i] as usize],
+        )
+    }
+
+    /// Iterate over all trigrams in terms of [`LayerKey`]s together with their weights.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = ((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)> + '_ {
+        let table = self.key_table();
+        self.keys1
+            .iter()
+            .zip(self.keys2.iter())
+            .zip(self.keys3.iter())
+            .zip(self.weights.iter())
+            .map(move |(((idx1, idx2), idx3), w)| {
+                (
+                    (
+                        &table[*idx1 as usize],
+                        &table[*idx2 as usize],
+                        &table[*idx3 as usize],
+                    ),
+                    *w,
+                )
+            })
+    }
+}
+
+/// Quadgrams in terms of a [`Layout`]'s [`LayerKey`]s, stored as parallel arrays of
+/// [`LayerKeyIndex`]s and weights (struct-of-arrays, see [`BigramBuffer`]).
+#[derive(Clone, Debug)]
+pub struct QuadgramBuffer<'s> {
+    layout: &'s Layout,
+    /// First keys of the quadgrams
+    pub keys1: Vec<LayerKeyIndex>,
+    /// Second keys of the quadgrams
+    pub keys2: Vec<LayerKeyIndex>,
+    /// Third keys of the quadgrams
+    pub keys3: Vec<LayerKeyIndex>,
+    /// Fourth keys of the quadgrams
+    pub keys4: Vec<LayerKeyIndex>,
+    /// Weights (frequencies) of the quadgrams
+    pub weights: Vec<f64>,
+}
+
+impl<'s> QuadgramBuffer<'s> {
+    pub fn with_capacity(layout: &'s Layout, capacity: usize) -> Self {
+        Self {
+            layout,
+            keys1: Vec::with_capacity(capacity),
+            keys2: Vec::with_capacity(capacity),
+            keys3: Vec::with_capacity(capacity),
+            keys4: Vec::with_capacity(capacity),
+            weights: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline(always)]
+    pub fn push(
+        &mut self,
+        idx1: LayerKeyIndex,
+        idx2: LayerKeyIndex,
+        idx3: LayerKeyIndex,
+        idx4: LayerKeyIndex,
+        weight: f64,
+    ) {
+        self.keys1.push(idx1);
+        self.keys2.push(idx2);
+        self.keys3.push(idx3);
+        self.keys4.push(idx4);
+        self.weights.push(weight);
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// Sum of all quadgrams' weights.
+    pub fn total_weight(&self) -> f64 {
+        self.weights.iter().sum()
+    }
+
+    /// The layout's [`LayerKey`]s, indexed by the [`LayerKeyIndex`]s of the buffer.
+    #[inline(always)]
+    pub fn key_table(&self) -> &'s [LayerKey] {
+        &self.layout.layerkeys
+    }
+
+    /// The [`LayerKey`]s of the `i`-th quadgram.
+    #[inline(always)]
+    pub fn keys(&self, i: usize) -> LayerKeyQuadgram<'s> {
+        let table = self.key_table();
+        (
+            &table[self.keys1[i] as usize],
+            &table[self.keys2[i] as usize],
+            &table[self.keys3[i] as usize],
+            &table[self.keys4[i] as usize],
+        )
+    }
+
+    /// Iterate over all quadgrams in terms of [`LayerKey`]s together with their weights.
+    pub fn iter(&self) -> impl Iterator<Item = (LayerKeyQuadgram<'s>, f64)> + '_ {
+        (0..self.len()).map(move |i| (self.keys(i), self.weights[i]))
+    }
+}
+
 /// Bigrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
 /// can not be generated by the layout.
 pub struct MappedBigrams<'s> {
-    /// Unigrams in terms of [`LayerKey`]s
-    pub grams: Vec<((&'s LayerKey, &'s LayerKey), f64)>,
-    /// Total weight (frequencies) of unigrams that can not be generated by the layout
+    /// Bigrams in terms of [`LayerKey`]s
+    pub grams: BigramBuffer<'s>,
+    /// Total weight (frequencies) of bigrams that can not be generated by the layout
     pub weight_not_found: f64,
-    /// Total weight (frequencies) of unigrams that can be generated by the layout
+    /// Total weight (frequencies) of bigrams that can be generated by the layout
     pub weight_found: f64,
 }
 
 /// Trigrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
 /// can not be generated by the layout.
 pub struct MappedTrigrams<'s> {
-    /// Unigrams in terms of [`LayerKey`]s
-    pub grams: Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)>,
-    /// Total weight (frequencies) of unigrams that can not be generated by the layout
+    /// Trigrams in terms of [`LayerKey`]s
+    pub grams: TrigramBuffer<'s>,
+    /// Total weight (frequencies) of trigrams that can not be generated by the layout
     pub weight_not_found: f64,
-    /// Total weight (frequencies) of unigrams that can be generated by the layout
+    /// Total weight (frequencies) of trigrams that can be generated by the layout
+    pub weight_found: f64,
+}
+
+/// Four consecutive [`LayerKey`]s.
+pub type LayerKeyQuadgram<'s> = (&'s LayerKey, &'s LayerKey, &'s LayerKey, &'s LayerKey);
+
+/// Quadgrams in terms of a [`Layout`]'s [`LayerKey`]s and statistics about ngrams that
+/// can not be generated by the layout.
+pub struct MappedQuadgrams<'s> {
+    /// Quadgrams in terms of [`LayerKey`]s
+    pub grams: QuadgramBuffer<'s>,
+    /// Total weight (frequencies) of quadgrams that can not be generated by the layout
+    pub weight_not_found: f64,
+    /// Total weight (frequencies) of quadgrams that can be generated by the layout
     pub weight_found: f64,
 }
 
@@ -80,6 +357,17 @@ pub trait NgramMapper: Send + Sync + NgramMapperClone + fmt::Debug {
     fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s>;
     fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s>;
     fn map_trigrams<'s>(&self, layout: &'s Layout) -> MappedTrigrams<'s>;
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s>;
+    /// A copy of the mapper with resampled (bootstrapped) ngrams. Without a `sample_size`, the
+    /// ngram weights are interpreted as counts. Each ngram set is resampled independently, i.e.
+    /// the resampled unigrams, bigrams, and trigrams do not stem from one common corpus.
+    fn bootstrap(&self, sample_size: Option<f64>, seed: u64) -> Box<dyn NgramMapper>;
+    /// A copy of the mapper with only those char-based ngrams (and shortcuts) whose symbols
+    /// satisfy `keep`. The weights of the kept ngrams remain unchanged.
+    fn filtered(&self, keep: &dyn Fn(&[char]) -> bool) -> Box<dyn NgramMapper>;
+    /// A copy of the mapper without trigrams and quadgrams, e.g. for evaluating only unigram and
+    /// bigram metrics.
+    fn unigrams_and_bigrams(&self) -> Box<dyn NgramMapper>;
 }
 
 // in order to implement clone for Box<dyn LayoutMetric>, the following trick is necessary
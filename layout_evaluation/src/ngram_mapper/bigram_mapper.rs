@@ -4,11 +4,11 @@
 //! Note: In contrast to ArneBab's algorithm, here all trigrams will be used
 //! for secondary bigrams. Not only those that lead to same-hand bigrams.
 
-use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
+use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig, BigramBuffer};
 
-use crate::ngrams::Bigrams;
+use crate::ngrams::{Bigrams, Shortcuts};
 
-use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
+use keyboard_layout::layout::{LayerKeyIndex, LayerModifiers, Layout};
 
 use ahash::AHashMap;
 
@@ -60,7 +60,7 @@ fn map_bigrams(
     (bigrams_vec, not_found_weight)
 }
 
-/// Generates [`LayerKey`]-based [Bigrams] from char-based unigrams. Optionally resolves modifiers
+/// Generates [`LayerKey`](keyboard_layout::layout::LayerKey)-based [Bigrams] from char-based unigrams. Optionally resolves modifiers
 /// for higher-layer symbols of the layout.
 #[derive(Clone, Debug)]
 pub struct OnDemandBigramMapper {
@@ -82,6 +82,10 @@ impl OnDemandBigramMapper {
         let (mut bigram_keys_vec, not_found_weight) =
             map_bigrams(bigrams, layout, exclude_line_breaks);
 
+        if layout.has_toggle_layers() || layout.has_caps_word_layers() {
+            bigram_keys_vec = Self::process_toggle_modifiers(bigram_keys_vec, layout);
+        }
+
         if layout.has_one_shot_layers() {
             bigram_keys_vec = self.process_one_shot_modifiers(bigram_keys_vec, layout);
         }
@@ -102,32 +106,60 @@ impl OnDemandBigramMapper {
         (bigram_keys, not_found_weight)
     }
 
-    /// Resolves &[`LayerKey`] references for [`LayerKeyIndex`] and filters bigrams that contain
+    /// Add the bigrams resulting from pressing the symbols of each shortcut in sequence (modifiers
+    /// first, then the key) to the given bigrams. The shortcuts' weights are multiplied with `factor`.
+    ///
+    /// Returns the (scaled) weights of shortcuts that could and could not be mapped by the layout.
+    pub fn add_shortcut_indices(
+        bigrams: &mut BigramIndices,
+        shortcuts: &Shortcuts,
+        layout: &Layout,
+        factor: f64,
+    ) -> (f64, f64) {
+        let mut found_weight = 0.0;
+        let mut not_found_weight = 0.0;
+
+        shortcuts.grams.iter().for_each(|(symbols, weight)| {
+            let weight = factor * weight;
+            let indices: Option<Vec<LayerKeyIndex>> = symbols
+                .iter()
+                .map(|c| layout.get_layerkey_index_for_symbol(c))
+                .collect();
+
+            match indices {
+                Some(indices) => {
+                    indices
+                        .iter()
+                        .zip(indices.iter().skip(1))
+                        .for_each(|(idx1, idx2)| {
+                            bigrams.insert_or_add_weight((*idx1, *idx2), weight);
+                        });
+                    found_weight += weight;
+                }
+                None => not_found_weight += weight,
+            }
+        });
+
+        (found_weight, not_found_weight)
+    }
+
+    /// Collects the bigrams into a [`BigramBuffer`] and filters bigrams that contain
     /// repeating identical modifiers.
-    pub fn get_filtered_layerkeys<'s>(
+    pub fn get_filtered_buffer<'s>(
         bigrams: &BigramIndices,
         layout: &'s Layout,
-    ) -> Vec<((&'s LayerKey, &'s LayerKey), f64)> {
-        let mut layerkeys = Vec::with_capacity(bigrams.len());
-
-        layerkeys.extend(bigrams.iter().filter_map(|((idx1, idx2), w)| {
-            let k1 = layout.get_layerkey(idx1);
+    ) -> BigramBuffer<'s> {
+        let mut buffer = BigramBuffer::with_capacity(layout, bigrams.len());
 
+        bigrams.iter().for_each(|((idx1, idx2), w)| {
             // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
             // --> remove
-            match k1.is_modifier.is_hold() && idx1 == idx2 {
-                false => Some((
-                    (
-                        k1,                        // LayerKey 1
-                        layout.get_layerkey(idx2), // LayerKey 2
-                    ),
-                    *w,
-                )),
-                true => None,
+            if !(layout.get_layerkey(idx1).is_modifier.is_held() && idx1 == idx2) {
+                buffer.push(*idx1, *idx2, *w);
             }
-        }));
+        });
 
-        layerkeys
+        buffer
     }
 
     /// Map all bigrams to base-layer bigrams, potentially generating multiple bigrams
@@ -143,53 +175,68 @@ impl OnDemandBigramMapper {
             let (base2, mods2) = layout.resolve_modifiers(&k2);
 
             let (key1, mods1) = match mods1 {
-                LayerModifiers::Hold(mods) => (base1, mods),
+                LayerModifiers::Hold(mods) | LayerModifiers::Chord(mods) => (base1, mods),
                 _ => (k1, Vec::new()),
             };
 
             let (key2, mods2) = match mods2 {
-                LayerModifiers::Hold(mods) => (base2, mods),
+                LayerModifiers::Hold(mods) | LayerModifiers::Chord(mods) => (base2, mods),
                 _ => (k2, Vec::new()),
             };
 
             bigram_w_map.insert_or_add_weight((key1, key2), w);
-            // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&base2).symbol);
+            // tracing::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&base2).symbol);
 
             mods1.iter().for_each(|mod1| {
                 // mix mods of k1 with base of k2
                 bigram_w_map.insert_or_add_weight((*mod1, key2), w);
-                // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&base2).symbol);
+                // tracing::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&base2).symbol);
 
                 // mix mods of k1 and k2
                 mods2.iter().for_each(|mod2| {
                     bigram_w_map.insert_or_add_weight((*mod1, *mod2), w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&mod2).symbol);
+                    // tracing::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&mod1).symbol, layout.get_layerkey(&mod2).symbol);
                 });
             });
 
             mods2.iter().for_each(|mod2| {
                 // mix mods of k2 with base of k1
                 bigram_w_map.insert_or_add_weight((key1, *mod2), w);
-                // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&mod2).symbol);
+                // tracing::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&base1).symbol, layout.get_layerkey(&mod2).symbol);
             });
 
             // same key mods
             TakeTwoLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor)
                 .for_each(|(e, w)| {
                     bigram_w_map.insert_or_add_weight(e, w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
+                    // tracing::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
                 });
 
             TakeTwoLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor)
                 .for_each(|(e, w)| {
                     bigram_w_map.insert_or_add_weight(e, w);
-                    // log::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
+                    // tracing::trace!("{:>3}{:<3} -> {:>3}{:<3}", layout.get_layerkey(&k1).symbol, layout.get_layerkey(&k2).symbol, layout.get_layerkey(&e.0).symbol, layout.get_layerkey(&e.1).symbol);
                 });
         });
 
         bigram_w_map
     }
 
+    /// Map bigrams with symbols of toggled or Caps Word layers to bigrams of the base keys and the
+    /// modifiers that are tapped for entering or leaving a layer between the symbols.
+    fn process_toggle_modifiers(bigrams: BigramIndicesVec, layout: &Layout) -> BigramIndicesVec {
+        let mut processed_bigrams = Vec::with_capacity(bigrams.len());
+        let mut keys = Vec::new();
+
+        bigrams.into_iter().for_each(|((k1, k2), w)| {
+            expand_toggle_layers(&[k1, k2], 2, layout, &mut keys, |window| {
+                processed_bigrams.push(((window[0], window[1]), w));
+            });
+        });
+
+        processed_bigrams
+    }
+
     fn process_one_shot_modifiers(
         &self,
         bigrams: BigramIndicesVec,
@@ -225,3 +272,57 @@ impl OnDemandBigramMapper {
         processed_bigrams
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{generator, layout_config};
+
+    use keyboard_layout::{
+        key::MatrixPosition,
+        layout::{LayerModifierLocations, ModifierLocation},
+    };
+
+    /// The standard layout with its second layer reached by chording both Mod3 keys
+    fn layout() -> Layout {
+        let mut config = layout_config("standard.yml");
+        let chord = vec![
+            ModifierLocation::Position(MatrixPosition(0, 2)),
+            ModifierLocation::Position(MatrixPosition(18, 2)),
+        ];
+        config.base_layout.modifiers[1]
+            .values_mut()
+            .for_each(|mods| *mods = LayerModifierLocations::Chord(chord.clone()));
+        generator(config).generate_base().unwrap()
+    }
+
+    #[test]
+    fn splits_chorded_symbols_into_both_modifiers_and_the_base_key() {
+        let layout = layout();
+        let k1 = layout.get_layerkey_index_for_symbol(&'a').unwrap();
+        let k2 = layout.get_layerkey_index_for_symbol(&'[').unwrap();
+        let (base, mods) = layout.resolve_modifiers(&k2);
+        let chord = match mods {
+            LayerModifiers::Chord(mods) => mods,
+            mods => panic!("Unexpected modifiers {:?}", mods),
+        };
+        assert_eq!(chord.len(), 2);
+
+        let mapper = OnDemandBigramMapper::new(SplitModifiersConfig {
+            enabled: true,
+            same_key_mod_factor: 1.0,
+        });
+        let bigrams = Bigrams::from_text("a[").unwrap();
+        let (bigram_keys, not_found_weight) = mapper.layerkey_indices(&bigrams, &layout, false);
+
+        assert_eq!(not_found_weight, 0.0);
+        assert!(bigram_keys.contains_key(&(k1, base)));
+        // both modifiers of the chord are held before the base key
+        for m in chord.iter() {
+            assert!(bigram_keys.contains_key(&(k1, *m)));
+            assert!(bigram_keys.contains_key(&(*m, base)));
+        }
+        assert!(!bigram_keys.contains_key(&(k1, k2)));
+    }
+}
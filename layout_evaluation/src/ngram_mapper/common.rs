@@ -1,5 +1,5 @@
 /// The `common` module provides utility functions for resolving modifiers in ngrams.
-use keyboard_layout::layout::LayerKeyIndex;
+use keyboard_layout::layout::{LayerKeyIndex, LayerModifiers, Layout};
 
 use ahash::AHashMap;
 use std::{cmp::Eq, hash::Hash, slice};
@@ -426,6 +426,116 @@ impl<'a> TakeThreeLayerKey<'a> {
 //     res
 // }
 
+/// A layer that stays active across several symbols once its modifiers were tapped.
+#[derive(Clone, Debug)]
+struct LatchedLayer {
+    layer: u8,
+    modifiers: Vec<LayerKeyIndex>,
+    /// Whether the modifiers are tapped again for leaving the layer (toggled layers) or the
+    /// layer is left automatically at the end of the word (Caps Word layers)
+    tap_to_leave: bool,
+}
+
+/// Tracks the active toggled (or Caps Word) layer while expanding a sequence of symbols into
+/// keystrokes.
+///
+/// A toggled layer stays active until its modifiers are tapped again, so the modifiers are only
+/// pressed when a symbol of another layer follows (leaving the layer) or a symbol of the layer
+/// follows a symbol of another layer (entering it). A Caps Word layer is entered the same way,
+/// but ends without a keystroke at the end of the word (whitespace or punctuation). Digits and
+/// underscores continue the word, and Caps Word stays active while they are typed. A letter of
+/// another layer within the word requires cancelling Caps Word, which costs a tap of its
+/// modifiers like leaving a toggled layer. The layer of the first symbol of an ngram is assumed
+/// to be active already, such that each change of layers is counted once over all ngrams.
+#[derive(Clone, Debug)]
+pub struct ToggleState {
+    active: Option<LatchedLayer>,
+}
+
+impl ToggleState {
+    /// Start a sequence with the (already active) layer of the given symbol.
+    pub fn new(first: LayerKeyIndex, layout: &Layout) -> Self {
+        Self {
+            active: Self::latched_layer(first, layout),
+        }
+    }
+
+    fn latched_layer(k: LayerKeyIndex, layout: &Layout) -> Option<LatchedLayer> {
+        let lk = layout.get_layerkey(&k);
+        let (modifiers, tap_to_leave) = match &lk.modifiers {
+            LayerModifiers::Toggle(mods) => (mods, true),
+            LayerModifiers::CapsWord(mods) => (mods, false),
+            _ => return None,
+        };
+
+        Some(LatchedLayer {
+            layer: lk.layer,
+            modifiers: modifiers.clone(),
+            tap_to_leave,
+        })
+    }
+
+    /// Push the modifiers that are tapped before the symbol `k` to `keys` and return the key to
+    /// press for the symbol itself.
+    pub fn transition(
+        &mut self,
+        k: LayerKeyIndex,
+        layout: &Layout,
+        keys: &mut Vec<LayerKeyIndex>,
+    ) -> LayerKeyIndex {
+        let target = Self::latched_layer(k, layout);
+        let active_layer = self.active.as_ref().map(|l| l.layer);
+        let target_layer = target.as_ref().map(|l| l.layer);
+        if active_layer != target_layer {
+            if let Some(active) = &self.active {
+                let symbol = layout.get_layerkey(&k).symbol;
+                if active.tap_to_leave || symbol.is_alphabetic() {
+                    // leaving a toggled layer or cancelling Caps Word within a word
+                    keys.extend(&active.modifiers);
+                } else if !ends_word(symbol) && target.is_none() {
+                    // Caps Word continues across digits and underscores
+                    return k;
+                }
+            }
+            if let Some(target) = &target {
+                keys.extend(&target.modifiers);
+            }
+        }
+
+        let key = match target {
+            Some(_) => layout.get_base_layerkey_index(&k),
+            None => k,
+        };
+        self.active = target;
+
+        key
+    }
+}
+
+/// Whether the symbol ends a word (and with it Caps Word).
+fn ends_word(symbol: char) -> bool {
+    !(symbol.is_alphanumeric() || symbol == '_')
+}
+
+/// Expand each ngram (given as a sequence of symbols) into the keystrokes required for toggled
+/// and Caps Word layers and call `f` for each window of `n` consecutive keystrokes.
+pub fn expand_toggle_layers<F: FnMut(&[LayerKeyIndex])>(
+    ngram: &[LayerKeyIndex],
+    n: usize,
+    layout: &Layout,
+    keys: &mut Vec<LayerKeyIndex>,
+    f: F,
+) {
+    keys.clear();
+    let mut state = ToggleState::new(ngram[0], layout);
+    ngram.iter().for_each(|k| {
+        let key = state.transition(*k, layout, keys);
+        keys.push(key);
+    });
+
+    keys.windows(n).for_each(f);
+}
+
 pub trait NgramMap<Ngram: Eq + Hash> {
     /// Adds the ngram to the HashMap if it does not already exist.
     /// If it does exist, simply add its weight to the preexisting weight.
@@ -438,3 +548,86 @@ impl<Ngram: Eq + Hash> NgramMap<Ngram> for AHashMap<Ngram, f64> {
         *self.entry(k).or_insert(0.0) += w;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{generator, layout_config};
+
+    use keyboard_layout::layout::{LayerModifierLocations, ModifierLocation};
+
+    /// The standard layout with the modifiers of the given layer turned into another type
+    fn layout(layer: usize, to: fn(Vec<ModifierLocation>) -> LayerModifierLocations) -> Layout {
+        let mut config = layout_config("standard.yml");
+        config.base_layout.modifiers[layer - 1]
+            .values_mut()
+            .for_each(|mods| *mods = to(mods.iter().cloned().collect()));
+        generator(config).generate_base().unwrap()
+    }
+
+    fn expand(text: &str, layout: &Layout) -> Vec<LayerKeyIndex> {
+        let ngram: Vec<LayerKeyIndex> = text.chars().map(|c| key(c, layout)).collect();
+        let mut keys = Vec::new();
+        expand_toggle_layers(&ngram, 1, layout, &mut keys, |_| ());
+
+        keys
+    }
+
+    fn key(c: char, layout: &Layout) -> LayerKeyIndex {
+        layout.get_layerkey_index_for_symbol(&c).unwrap()
+    }
+
+    fn base_key(c: char, layout: &Layout) -> LayerKeyIndex {
+        layout.get_base_layerkey_index(&key(c, layout))
+    }
+
+    #[test]
+    fn taps_toggle_modifiers_only_when_entering_and_leaving_the_layer() {
+        let layout = layout(2, LayerModifierLocations::Toggle);
+        assert_eq!(layout.get_layerkey_for_symbol(&'[').unwrap().layer, 2);
+        assert_eq!(layout.get_layerkey_for_symbol(&']').unwrap().layer, 2);
+        let toggle = match &layout.get_layerkey_for_symbol(&'[').unwrap().modifiers {
+            LayerModifiers::Toggle(mods) => mods.clone(),
+            mods => panic!("Unexpected modifiers {:?}", mods),
+        };
+
+        let mut expected = vec![key('a', &layout)];
+        expected.extend(&toggle);
+        expected.extend(&[base_key('[', &layout), base_key(']', &layout)]);
+        expected.extend(&toggle);
+        expected.push(key('b', &layout));
+        assert_eq!(expand("a[]b", &layout), expected);
+
+        // the layer of the first symbol is active already
+        assert_eq!(
+            expand("[]", &layout),
+            vec![base_key('[', &layout), base_key(']', &layout)]
+        );
+    }
+
+    #[test]
+    fn ends_caps_word_at_word_boundaries_only() {
+        let layout = layout(1, LayerModifierLocations::CapsWord);
+        let caps_word = match &layout.get_layerkey_for_symbol(&'A').unwrap().modifiers {
+            LayerModifiers::CapsWord(mods) => mods.clone(),
+            mods => panic!("Unexpected modifiers {:?}", mods),
+        };
+        let (a, b) = (key('a', &layout), key('b', &layout));
+
+        // entering Caps Word taps its modifiers, the end of the word leaves it without a keystroke
+        let mut expected = vec![key('x', &layout)];
+        expected.extend(&caps_word);
+        expected.extend(&[a, b, key(',', &layout), key('c', &layout)]);
+        assert_eq!(expand("xAB,c", &layout), expected);
+
+        // digits continue the word
+        assert_eq!(expand("A1B", &layout), vec![a, key('1', &layout), b]);
+
+        // a lowercase letter within the word requires cancelling Caps Word
+        let mut expected = vec![a];
+        expected.extend(&caps_word);
+        expected.push(b);
+        assert_eq!(expand("Ab", &layout), expected);
+    }
+}
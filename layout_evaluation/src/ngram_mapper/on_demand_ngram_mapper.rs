@@ -1,11 +1,12 @@
 //! This module provides an implementation of the [`NgramMapper`] trait.
 
 use super::bigram_mapper::OnDemandBigramMapper;
+use super::quadgram_mapper::OnDemandQuadgramMapper;
 use super::trigram_mapper::OnDemandTrigramMapper;
 use super::unigram_mapper::OnDemandUnigramMapper;
-use super::{MappedBigrams, MappedTrigrams, MappedUnigrams, NgramMapper};
+use super::{MappedBigrams, MappedQuadgrams, MappedTrigrams, MappedUnigrams, NgramMapper};
 
-use crate::ngrams::{Bigrams, Trigrams, Unigrams};
+use crate::ngrams::{Bigrams, Quadgrams, Shortcuts, Trigrams, Unigrams};
 
 use keyboard_layout::layout::Layout;
 
@@ -27,6 +28,14 @@ pub struct NgramMapperConfig {
     pub split_modifiers: SplitModifiersConfig,
     /// Exclude ngrams that contain a line break, followed by a non-line-break character
     pub exclude_line_breaks: bool,
+    /// Total weight of the bigrams generated from shortcuts (if any are given) relative to the
+    /// total weight of the corpus' bigrams.
+    #[serde(default = "default_shortcuts_weight")]
+    pub shortcuts_weight: f64,
+}
+
+fn default_shortcuts_weight() -> f64 {
+    0.05
 }
 
 /// Implements the [`NgramMapper`] trait for generating ngrams in terms of [`LayerKey`]s for a given [`Layout`].
@@ -35,9 +44,12 @@ pub struct OnDemandNgramMapper {
     unigrams: Unigrams,
     bigrams: Bigrams,
     trigrams: Trigrams,
+    quadgrams: Quadgrams,
+    shortcuts: Shortcuts,
     unigram_mapper: OnDemandUnigramMapper,
     bigram_mapper: OnDemandBigramMapper,
     trigram_mapper: OnDemandTrigramMapper,
+    quadgram_mapper: OnDemandQuadgramMapper,
     config: NgramMapperConfig,
 }
 
@@ -53,19 +65,84 @@ impl OnDemandNgramMapper {
             unigrams,
             bigrams,
             trigrams,
+            quadgrams: Quadgrams::default(),
+            shortcuts: Shortcuts::default(),
             unigram_mapper: OnDemandUnigramMapper::new(config.split_modifiers.clone()),
             bigram_mapper: OnDemandBigramMapper::new(config.split_modifiers.clone()),
             trigram_mapper: OnDemandTrigramMapper::new(config.split_modifiers.clone()),
+            quadgram_mapper: OnDemandQuadgramMapper::new(config.split_modifiers.clone()),
             config,
         }
     }
+
+    /// Add shortcuts (chorded keystrokes) whose resulting bigrams are evaluated alongside
+    /// the corpus' bigrams.
+    pub fn with_shortcuts(mut self, shortcuts: Shortcuts) -> Self {
+        self.shortcuts = shortcuts;
+        self
+    }
+
+    /// Add quadgrams for evaluating metrics that consider four consecutive keystrokes.
+    pub fn with_quadgrams(mut self, quadgrams: Quadgrams) -> Self {
+        self.quadgrams = quadgrams;
+        self
+    }
 }
 
 impl NgramMapper for OnDemandNgramMapper {
+    fn bootstrap(&self, sample_size: Option<f64>, seed: u64) -> Box<dyn NgramMapper> {
+        let mut mapper = self.clone();
+        mapper.unigrams = self.unigrams.bootstrap(sample_size, seed);
+        mapper.bigrams = self.bigrams.bootstrap(sample_size, seed);
+        mapper.trigrams = self.trigrams.bootstrap(sample_size, seed);
+        mapper.quadgrams = self.quadgrams.bootstrap(sample_size, seed);
+
+        Box::new(mapper)
+    }
+
+    fn filtered(&self, keep: &dyn Fn(&[char]) -> bool) -> Box<dyn NgramMapper> {
+        let mut mapper = self.clone();
+        mapper.unigrams.grams.retain(|c, _| keep(&[*c]));
+        mapper.bigrams.grams.retain(|(c1, c2), _| keep(&[*c1, *c2]));
+        mapper
+            .trigrams
+            .grams
+            .retain(|(c1, c2, c3), _| keep(&[*c1, *c2, *c3]));
+        mapper
+            .quadgrams
+            .grams
+            .retain(|(c1, c2, c3, c4), _| keep(&[*c1, *c2, *c3, *c4]));
+        mapper.shortcuts.grams.retain(|symbols, _| keep(symbols));
+
+        // the shortcuts' weights are relative to the total weight of the bigrams, so keep the
+        // factor between them (see `map_bigrams`)
+        let bigrams_weight = mapper.bigrams.total_weight();
+        let shortcuts_weight = mapper.shortcuts.total_weight();
+        mapper.config.shortcuts_weight = match bigrams_weight > 0.0 && shortcuts_weight > 0.0 {
+            true => {
+                self.config.shortcuts_weight
+                    * (self.bigrams.total_weight() / self.shortcuts.total_weight())
+                    * (shortcuts_weight / bigrams_weight)
+            }
+            false => 0.0,
+        };
+
+        Box::new(mapper)
+    }
+
+    fn unigrams_and_bigrams(&self) -> Box<dyn NgramMapper> {
+        let mut mapper = self.clone();
+        mapper.trigrams.grams = Default::default();
+        mapper.quadgrams.grams = Default::default();
+
+        Box::new(mapper)
+    }
+
     fn map_unigrams<'s>(&self, layout: &'s Layout) -> MappedUnigrams<'s> {
         // map char-based unigrams to LayerKeyIndex
         let (key_indices, weight_not_found) =
-            self.unigram_mapper.layerkey_indices(&self.unigrams, layout);
+            self.unigram_mapper
+                .layerkey_indices(&self.unigrams, &self.bigrams, layout);
         let weight_found = self.unigrams.total_weight() - weight_not_found;
         // map LayerKeyIndex to &LayerKey
         let grams = OnDemandUnigramMapper::get_layerkeys(&key_indices, layout);
@@ -79,14 +156,30 @@ impl NgramMapper for OnDemandNgramMapper {
 
     fn map_bigrams<'s>(&self, layout: &'s Layout) -> MappedBigrams<'s> {
         // map char-based bigrams to LayerKeyIndex
-        let (key_indices, weight_not_found) = self.bigram_mapper.layerkey_indices(
+        let (mut key_indices, mut weight_not_found) = self.bigram_mapper.layerkey_indices(
             &self.bigrams,
             layout,
             self.config.exclude_line_breaks,
         );
-        let weight_found = self.bigrams.total_weight() - weight_not_found;
-        // map LayerKeyIndex to &LayerKey
-        let grams = OnDemandBigramMapper::get_filtered_layerkeys(&key_indices, layout);
+        let total_weight = self.bigrams.total_weight();
+        let mut weight_found = total_weight - weight_not_found;
+
+        // add bigrams resulting from shortcuts
+        if !self.shortcuts.grams.is_empty() {
+            let factor =
+                self.config.shortcuts_weight * total_weight / self.shortcuts.total_weight();
+            let (shortcuts_found, shortcuts_not_found) = OnDemandBigramMapper::add_shortcut_indices(
+                &mut key_indices,
+                &self.shortcuts,
+                layout,
+                factor,
+            );
+            weight_found += shortcuts_found;
+            weight_not_found += shortcuts_not_found;
+        }
+
+        // collect LayerKeyIndex-based bigrams into dense arrays
+        let grams = OnDemandBigramMapper::get_filtered_buffer(&key_indices, layout);
 
         MappedBigrams {
             grams,
@@ -103,8 +196,8 @@ impl NgramMapper for OnDemandNgramMapper {
             self.config.exclude_line_breaks,
         );
         let weight_found = self.trigrams.total_weight() - weight_not_found;
-        // map LayerKeyIndex to &LayerKey
-        let grams = OnDemandTrigramMapper::get_filtered_layerkeys(&key_indices, layout);
+        // collect LayerKeyIndex-based trigrams into dense arrays
+        let grams = OnDemandTrigramMapper::get_filtered_buffer(&key_indices, layout);
 
         MappedTrigrams {
             grams,
@@ -112,4 +205,22 @@ impl NgramMapper for OnDemandNgramMapper {
             weight_found,
         }
     }
+
+    fn map_quadgrams<'s>(&self, layout: &'s Layout) -> MappedQuadgrams<'s> {
+        // map char-based quadgrams to LayerKeyIndex
+        let (key_indices, weight_not_found) = self.quadgram_mapper.layerkey_indices(
+            &self.quadgrams,
+            layout,
+            self.config.exclude_line_breaks,
+        );
+        let weight_found = self.quadgrams.total_weight() - weight_not_found;
+        // collect LayerKeyIndex-based quadgrams into dense arrays
+        let grams = OnDemandQuadgramMapper::get_buffer(&key_indices, layout);
+
+        MappedQuadgrams {
+            grams,
+            weight_not_found,
+            weight_found,
+        }
+    }
 }
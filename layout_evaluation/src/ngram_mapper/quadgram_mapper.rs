@@ -0,0 +1,254 @@
+//! This module provides an implementation of quadgram mapping functionalities
+//! used by the [`OnDemandNgramMapper`].
+
+use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig, QuadgramBuffer};
+
+use crate::ngrams::Quadgrams;
+
+use ahash::AHashMap;
+use keyboard_layout::layout::{LayerKeyIndex, LayerModifiers, Layout};
+
+// Before passing the resulting LayerKey-based ngrams as a result, smaller LayerKeyIndex-based
+// ones are used because they are smaller than a reference (u16 vs usize) and yield better
+// hashing performance.
+pub type QuadgramIndices =
+    AHashMap<(LayerKeyIndex, LayerKeyIndex, LayerKeyIndex, LayerKeyIndex), f64>;
+type QuadgramIndicesVec = Vec<([LayerKeyIndex; 4], f64)>;
+
+/// Turns the [`Quadgrams`]'s characters into their indices, returning a [`QuadgramIndicesVec`].
+fn map_quadgrams(
+    quadgrams: &Quadgrams,
+    layout: &Layout,
+    exclude_line_breaks: bool,
+) -> (QuadgramIndicesVec, f64) {
+    let mut not_found_weight = 0.0;
+    let mut quadgrams_vec = Vec::with_capacity(quadgrams.grams.len());
+
+    quadgrams_vec.extend(
+        quadgrams
+            .grams
+            .iter()
+            .filter_map(|((c1, c2, c3, c4), weight)| {
+                let chars = [c1, c2, c3, c4];
+
+                // Exclude quadgrams that contain a line break, followed by a non-line-break character
+                if exclude_line_breaks && chars.windows(2).any(|w| *w[0] == '\n' && *w[1] != '\n') {
+                    return None;
+                }
+
+                let mut indices = [0; 4];
+                for (idx, c) in indices.iter_mut().zip(chars.iter()) {
+                    *idx = match layout.get_layerkey_index_for_symbol(c) {
+                        Some(idx) => idx,
+                        None => {
+                            not_found_weight += *weight;
+                            return None;
+                        }
+                    };
+                }
+
+                Some((indices, *weight))
+            }),
+    );
+
+    (quadgrams_vec, not_found_weight)
+}
+
+/// Generates [`LayerKey`]-based quadgrams from char-based quadgrams. Optionally resolves modifiers
+/// for higher-layer symbols of the layout.
+#[derive(Clone, Debug)]
+pub struct OnDemandQuadgramMapper {
+    split_modifiers: SplitModifiersConfig,
+}
+
+impl OnDemandQuadgramMapper {
+    pub fn new(split_modifiers: SplitModifiersConfig) -> Self {
+        Self { split_modifiers }
+    }
+
+    /// For a given [`Layout`] generate [`LayerKeyIndex`]-based quadgrams, optionally resolving modifiers for higer-layer symbols.
+    pub fn layerkey_indices(
+        &self,
+        quadgrams: &Quadgrams,
+        layout: &Layout,
+        exclude_line_breaks: bool,
+    ) -> (QuadgramIndices, f64) {
+        let (quadgram_keys_vec, not_found_weight) =
+            map_quadgrams(quadgrams, layout, exclude_line_breaks);
+
+        let split_hold = self.split_modifiers.enabled && layout.has_hold_layers();
+        let quadgram_keys = if split_hold
+            || layout.has_one_shot_layers()
+            || layout.has_toggle_layers()
+            || layout.has_caps_word_layers()
+        {
+            self.process_modifiers(quadgram_keys_vec, layout, split_hold)
+        } else {
+            quadgram_keys_vec
+                .into_iter()
+                .map(|([k1, k2, k3, k4], w)| ((k1, k2, k3, k4), w))
+                .collect()
+        };
+
+        (quadgram_keys, not_found_weight)
+    }
+
+    /// Collects the quadgrams into a [`QuadgramBuffer`].
+    pub fn get_buffer<'s>(quadgrams: &QuadgramIndices, layout: &'s Layout) -> QuadgramBuffer<'s> {
+        let mut buffer = QuadgramBuffer::with_capacity(layout, quadgrams.len());
+
+        quadgrams.iter().for_each(|((idx1, idx2, idx3, idx4), w)| {
+            buffer.push(*idx1, *idx2, *idx3, *idx4, *w);
+        });
+
+        buffer
+    }
+
+    /// Map all quadgrams to base-layer quadgrams, potentially generating multiple quadgrams
+    /// with modifiers for those with higher-layer keys.
+    ///
+    /// Each symbol is expanded into the sequence of its modifiers followed by its base key and all
+    /// quadgrams of the resulting key sequence are collected. A hold-modifier that is already held
+    /// for the preceding symbol is not pressed again and therefore not repeated in the sequence.
+    /// Toggle modifiers are only tapped when entering or leaving a toggled layer, Caps Word
+    /// modifiers only when entering a Caps Word layer.
+    fn process_modifiers(
+        &self,
+        quadgrams: QuadgramIndicesVec,
+        layout: &Layout,
+        split_hold: bool,
+    ) -> QuadgramIndices {
+        let mut quadgram_w_map = AHashMap::with_capacity(quadgrams.len());
+        let mut keys = Vec::new();
+
+        quadgrams.into_iter().for_each(|(quadgram, w)| {
+            keys.clear();
+            let mut held: Vec<LayerKeyIndex> = Vec::new();
+            let mut toggled = ToggleState::new(quadgram[0], layout);
+
+            quadgram.iter().for_each(|k| {
+                let key = toggled.transition(*k, layout, &mut keys);
+                let (base, mods) = layout.resolve_modifiers(k);
+                match mods {
+                    LayerModifiers::Toggle(_) | LayerModifiers::CapsWord(_) => {
+                        keys.push(key);
+                        held.clear();
+                    }
+                    LayerModifiers::OneShot(mods) => {
+                        keys.extend(mods);
+                        keys.push(base);
+                        held.clear();
+                    }
+                    LayerModifiers::Hold(mods) | LayerModifiers::Chord(mods) if split_hold => {
+                        keys.extend(mods.iter().filter(|m| !held.contains(m)));
+                        keys.push(base);
+                        held = mods;
+                    }
+                    _ => {
+                        keys.push(*k);
+                        held.clear();
+                    }
+                }
+            });
+
+            keys.windows(4).for_each(|window| {
+                quadgram_w_map
+                    .insert_or_add_weight((window[0], window[1], window[2], window[3]), w);
+            });
+        });
+
+        quadgram_w_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::test_layout;
+
+    fn mapper(enabled: bool) -> OnDemandQuadgramMapper {
+        OnDemandQuadgramMapper::new(SplitModifiersConfig {
+            enabled,
+            same_key_mod_factor: 1.0,
+        })
+    }
+
+    /// The base key and the held modifiers of a symbol
+    fn resolve(layout: &Layout, c: char) -> (LayerKeyIndex, Vec<LayerKeyIndex>) {
+        let k = layout.get_layerkey_index_for_symbol(&c).unwrap();
+        match layout.resolve_modifiers(&k) {
+            (base, LayerModifiers::Hold(mods)) => (base, mods),
+            (_, mods) => panic!("Unexpected modifiers {:?} of '{}'", mods, c),
+        }
+    }
+
+    #[test]
+    fn expands_the_modifiers_of_higher_layer_symbols() {
+        let layout = test_layout("standard.yml");
+        let a = layout.get_layerkey_index_for_symbol(&'a').unwrap();
+        let n = layout.get_layerkey_index_for_symbol(&'n').unwrap();
+        let (e, e_mods) = resolve(&layout, 'E');
+        assert!(!e_mods.is_empty());
+
+        let quadgrams = Quadgrams::from_text("anEa").unwrap();
+        let (quadgram_keys, not_found_weight) =
+            mapper(true).layerkey_indices(&quadgrams, &layout, false);
+
+        let mut keys = vec![a, n];
+        keys.extend(e_mods);
+        keys.extend([e, a]);
+        let expected: Vec<_> = keys.windows(4).map(|w| (w[0], w[1], w[2], w[3])).collect();
+        assert_eq!(not_found_weight, 0.0);
+        assert_eq!(quadgram_keys.len(), expected.len());
+        for quadgram in expected.iter() {
+            assert_eq!(quadgram_keys.get(quadgram), Some(&1.0));
+        }
+
+        // without splitting, the higher-layer key is kept as it is
+        let (unsplit, _) = mapper(false).layerkey_indices(&quadgrams, &layout, false);
+        let e_upper = layout.get_layerkey_index_for_symbol(&'E').unwrap();
+        assert_eq!(unsplit.len(), 1);
+        assert!(unsplit.contains_key(&(a, n, e_upper, a)));
+    }
+
+    #[test]
+    fn does_not_repeat_modifiers_that_are_still_held() {
+        let layout = test_layout("standard.yml");
+        let n = layout.get_layerkey_index_for_symbol(&'n').unwrap();
+        let (a, a_mods) = resolve(&layout, 'A');
+        let (e, e_mods) = resolve(&layout, 'E');
+        assert_eq!(a_mods, e_mods);
+
+        let quadgrams = Quadgrams::from_text("nAEn").unwrap();
+        let (quadgram_keys, _) = mapper(true).layerkey_indices(&quadgrams, &layout, false);
+
+        let mut keys = vec![n];
+        keys.extend(a_mods);
+        keys.extend([a, e, n]);
+        let expected: AHashMap<_, f64> = keys
+            .windows(4)
+            .map(|w| ((w[0], w[1], w[2], w[3]), 1.0))
+            .collect();
+        assert_eq!(quadgram_keys, expected);
+    }
+
+    #[test]
+    fn adds_up_the_weights_of_identical_expanded_quadgrams() {
+        let layout = test_layout("standard.yml");
+        let a = layout.get_layerkey_index_for_symbol(&'a').unwrap();
+        let n = layout.get_layerkey_index_for_symbol(&'n').unwrap();
+        let (e, e_mods) = resolve(&layout, 'E');
+        assert_eq!(e_mods.len(), 1);
+        let shift = e_mods[0];
+
+        // "anEa" and "nEan" both contain "n", Shift, "e", "a" after the expansion
+        let quadgrams = Quadgrams::from_text("anEan").unwrap();
+        let (quadgram_keys, _) = mapper(true).layerkey_indices(&quadgrams, &layout, false);
+
+        assert_eq!(quadgram_keys.len(), 3);
+        assert_eq!(quadgram_keys[&(a, n, shift, e)], 1.0);
+        assert_eq!(quadgram_keys[&(n, shift, e, a)], 2.0);
+        assert_eq!(quadgram_keys[&(shift, e, a, n)], 1.0);
+    }
+}
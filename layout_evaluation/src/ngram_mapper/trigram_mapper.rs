@@ -1,12 +1,12 @@
 //! This module provides an implementation of trigram mapping functionalities
 //! used by the [`OnDemandNgramMapper`].
 
-use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
+use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig, TrigramBuffer};
 
 use crate::ngrams::Trigrams;
 
 use ahash::AHashMap;
-use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
+use keyboard_layout::layout::{LayerKeyIndex, LayerModifiers, Layout};
 
 // Before passing the resulting LayerKey-based ngrams as a result, smaller LayerKeyIndex-based
 // ones are used because they are smaller than a reference (u16 vs usize) and yield better
@@ -67,7 +67,7 @@ fn map_trigrams(
     (trigrams_vec, not_found_weight)
 }
 
-/// Generates [`LayerKey`]-based trigrams from char-based unigrams. Optionally resolves modifiers
+/// Generates [`LayerKey`](keyboard_layout::layout::LayerKey)-based trigrams from char-based unigrams. Optionally resolves modifiers
 /// for higher-layer symbols of the layout.
 #[derive(Clone, Debug)]
 pub struct OnDemandTrigramMapper {
@@ -89,6 +89,10 @@ impl OnDemandTrigramMapper {
         let (mut trigram_keys_vec, not_found_weight) =
             map_trigrams(trigrams, layout, exclude_line_breaks);
 
+        if layout.has_toggle_layers() || layout.has_caps_word_layers() {
+            trigram_keys_vec = Self::process_toggle_modifiers(trigram_keys_vec, layout);
+        }
+
         if layout.has_one_shot_layers() {
             trigram_keys_vec = self.process_one_shot_modifiers(trigram_keys_vec, layout);
         }
@@ -102,33 +106,24 @@ impl OnDemandTrigramMapper {
         (trigram_keys, not_found_weight)
     }
 
-    /// Resolve &[`LayerKey`] references for [`LayerKeyIndex`] and filters trigrams that contain
+    /// Collects the trigrams into a [`TrigramBuffer`] and filters trigrams that contain
     /// repeating identical modifiers.
-    pub fn get_filtered_layerkeys<'s>(
+    pub fn get_filtered_buffer<'s>(
         trigrams: &TrigramIndices,
         layout: &'s Layout,
-    ) -> Vec<((&'s LayerKey, &'s LayerKey, &'s LayerKey), f64)> {
-        let mut layerkeys = Vec::with_capacity(trigrams.len());
-
-        layerkeys.extend(trigrams.iter().filter_map(|((idx1, idx2, idx3), w)| {
-            let k2 = layout.get_layerkey(idx2);
+    ) -> TrigramBuffer<'s> {
+        let mut buffer = TrigramBuffer::with_capacity(layout, trigrams.len());
 
+        trigrams.iter().for_each(|((idx1, idx2, idx3), w)| {
             // If the same modifier appears consecutively, it is usually "hold" instead of repeatedly pressed
             // --> remove
-            match k2.is_modifier.is_hold() && (idx1 == idx2 || idx2 == idx3) {
-                false => Some((
-                    (
-                        layout.get_layerkey(idx1), // LayerKey 1
-                        k2,                        // LayerKey 2
-                        layout.get_layerkey(idx3), // LayerKey 3
-                    ),
-                    *w,
-                )),
-                true => None,
+            if !(layout.get_layerkey(idx2).is_modifier.is_held() && (idx1 == idx2 || idx2 == idx3))
+            {
+                buffer.push(*idx1, *idx2, *idx3, *w);
             }
-        }));
+        });
 
-        layerkeys
+        buffer
     }
 
     /// Map all trigrams to base-layer trigrams, potentially generating multiple trigrams
@@ -138,7 +133,6 @@ impl OnDemandTrigramMapper {
     /// of the involved base-keys and modifiers. Keys from the latter parts of the trigram will always be after
     /// former ones and modifers always come before their base key. The number of generated trigrams from a single
     /// trigram can be large (tens of trigrams) if multiple symbols of the trigram are accessed using multiple modifiers.
-
     // this is one of the most intensive functions of the layout evaluation
     fn process_hold_modifiers(
         &self,
@@ -152,17 +146,17 @@ impl OnDemandTrigramMapper {
             let (base3, mods3) = layout.resolve_modifiers(&k3);
 
             let (key1, mods1) = match mods1 {
-                LayerModifiers::Hold(mods) => (base1, mods),
+                LayerModifiers::Hold(mods) | LayerModifiers::Chord(mods) => (base1, mods),
                 _ => (k1, Vec::new()),
             };
 
             let (key2, mods2) = match mods2 {
-                LayerModifiers::Hold(mods) => (base2, mods),
+                LayerModifiers::Hold(mods) | LayerModifiers::Chord(mods) => (base2, mods),
                 _ => (k2, Vec::new()),
             };
 
             let (key3, mods3) = match mods3 {
-                LayerModifiers::Hold(mods) => (base3, mods),
+                LayerModifiers::Hold(mods) | LayerModifiers::Chord(mods) => (base3, mods),
                 _ => (k3, Vec::new()),
             };
 
@@ -180,7 +174,7 @@ impl OnDemandTrigramMapper {
             k1_take_one.clone().for_each(|(e1, _)| {
                 k2_take_one.clone().for_each(|(e2, _)| {
                     k3_take_one.clone().for_each(|(e3, _)| {
-                        // log::trace!(
+                        // tracing::trace!(
                         //     "one each:                    {}{}{}",
                         //     layout.get_layerkey(&e1).symbol,
                         //     layout.get_layerkey(&e2).symbol,
@@ -193,7 +187,7 @@ impl OnDemandTrigramMapper {
 
             k1_take_two.for_each(|((e1, e2), w1)| {
                 k2_take_one.clone().for_each(|(e3, _)| {
-                    // log::trace!(
+                    // tracing::trace!(
                     //     "two of first, one of second: {}{}{}",
                     //     layout.get_layerkey(&e1).symbol,
                     //     layout.get_layerkey(&e2).symbol,
@@ -205,7 +199,7 @@ impl OnDemandTrigramMapper {
 
             k1_take_one.for_each(|(e1, _)| {
                 k2_take_two.clone().for_each(|((e2, e3), w1)| {
-                    // log::trace!(
+                    // tracing::trace!(
                     //     "one of first, two of second: {}{}{}",
                     //     layout.get_layerkey(&e1).symbol,
                     //     layout.get_layerkey(&e2).symbol,
@@ -217,7 +211,7 @@ impl OnDemandTrigramMapper {
 
             k2_take_two.for_each(|((e1, e2), w1)| {
                 k3_take_one.clone().for_each(|(e3, _)| {
-                    // log::trace!(
+                    // tracing::trace!(
                     //     "two of second, one of third: {}{}{}",
                     //     layout.get_layerkey(&e1).symbol,
                     //     layout.get_layerkey(&e2).symbol,
@@ -229,7 +223,7 @@ impl OnDemandTrigramMapper {
 
             k2_take_one.for_each(|(e1, _)| {
                 k3_take_two.clone().for_each(|((e2, e3), w1)| {
-                    // log::trace!(
+                    // tracing::trace!(
                     //     "one of second, two of third: {}{}{}",
                     //     layout.get_layerkey(&e1).symbol,
                     //     layout.get_layerkey(&e2).symbol,
@@ -241,7 +235,7 @@ impl OnDemandTrigramMapper {
 
             TakeThreeLayerKey::new(key1, &mods1, w, self.split_modifiers.same_key_mod_factor)
                 .for_each(|(e, w)| {
-                    // log::trace!(
+                    // tracing::trace!(
                     //     "three of first:              {}{}{}",
                     //     layout.get_layerkey(&e.0).symbol,
                     //     layout.get_layerkey(&e.1).symbol,
@@ -252,7 +246,7 @@ impl OnDemandTrigramMapper {
 
             TakeThreeLayerKey::new(key2, &mods2, w, self.split_modifiers.same_key_mod_factor)
                 .for_each(|(e, w)| {
-                    // log::trace!(
+                    // tracing::trace!(
                     //     "three of second:             {}{}{}",
                     //     layout.get_layerkey(&e.0).symbol,
                     //     layout.get_layerkey(&e.1).symbol,
@@ -263,7 +257,7 @@ impl OnDemandTrigramMapper {
 
             TakeThreeLayerKey::new(key3, &mods3, w, self.split_modifiers.same_key_mod_factor)
                 .for_each(|(e, w)| {
-                    // log::trace!(
+                    // tracing::trace!(
                     //     "three of third:              {}{}{}",
                     //     layout.get_layerkey(&e.0).symbol,
                     //     layout.get_layerkey(&e.1).symbol,
@@ -276,6 +270,21 @@ impl OnDemandTrigramMapper {
         trigram_w_map
     }
 
+    /// Map trigrams with symbols of toggled or Caps Word layers to trigrams of the base keys and the
+    /// modifiers that are tapped for entering or leaving a layer between the symbols.
+    fn process_toggle_modifiers(trigrams: TrigramIndicesVec, layout: &Layout) -> TrigramIndicesVec {
+        let mut processed_trigrams = Vec::with_capacity(trigrams.len());
+        let mut keys = Vec::new();
+
+        trigrams.into_iter().for_each(|((k1, k2, k3), w)| {
+            expand_toggle_layers(&[k1, k2, k3], 3, layout, &mut keys, |window| {
+                processed_trigrams.push(((window[0], window[1], window[2]), w));
+            });
+        });
+
+        processed_trigrams
+    }
+
     fn process_one_shot_modifiers(
         &self,
         trigrams: TrigramIndicesVec,
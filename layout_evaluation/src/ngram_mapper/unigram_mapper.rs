@@ -3,7 +3,7 @@
 
 use super::{common::*, on_demand_ngram_mapper::SplitModifiersConfig};
 
-use crate::ngrams::Unigrams;
+use crate::ngrams::{Bigrams, Unigrams};
 
 use ahash::AHashMap;
 use keyboard_layout::layout::{LayerKey, LayerKeyIndex, LayerModifiers, Layout};
@@ -53,9 +53,26 @@ impl OnDemandUnigramMapper {
     }
 
     /// For a given [`Layout`] generate [`LayerKeyIndex`]-based unigrams, optionally resolving modifiers for higer-layer symbols.
-    pub fn layerkey_indices(&self, unigrams: &Unigrams, layout: &Layout) -> (UnigramIndices, f64) {
+    ///
+    /// The bigrams are only required for counting the taps of modifiers of toggled and Caps Word
+    /// layers.
+    pub fn layerkey_indices(
+        &self,
+        unigrams: &Unigrams,
+        bigrams: &Bigrams,
+        layout: &Layout,
+    ) -> (UnigramIndices, f64) {
         let (mut unigram_keys_vec, not_found_weight) = map_unigrams(unigrams, layout);
 
+        if layout.has_toggle_layers() || layout.has_caps_word_layers() {
+            let factor = match bigrams.total_weight() > 0.0 {
+                true => unigrams.total_weight() / bigrams.total_weight(),
+                false => 0.0,
+            };
+            unigram_keys_vec =
+                Self::process_toggle_modifiers(unigram_keys_vec, bigrams, factor, layout);
+        }
+
         if layout.has_one_shot_layers() {
             unigram_keys_vec = self.process_one_shot_modifiers(unigram_keys_vec, layout);
         }
@@ -91,7 +108,7 @@ impl OnDemandUnigramMapper {
             let (base, mods) = layout.resolve_modifiers(&k);
 
             let (key, mods) = match mods {
-                LayerModifiers::Hold(mods) => (base, mods),
+                LayerModifiers::Hold(mods) | LayerModifiers::Chord(mods) => (base, mods),
                 _ => (k, Vec::new()),
             };
 
@@ -117,6 +134,43 @@ impl OnDemandUnigramMapper {
         idx_w_map
     }
 
+    /// Map unigrams of symbols on toggled or Caps Word layers to their base keys and add the taps
+    /// of their modifiers. These are only tapped when the layer changes between two symbols, so
+    /// they are counted from the bigrams (whose weights are multiplied with `factor`).
+    fn process_toggle_modifiers(
+        unigrams: UnigramIndicesVec,
+        bigrams: &Bigrams,
+        factor: f64,
+        layout: &Layout,
+    ) -> UnigramIndicesVec {
+        let mut processed_unigrams: UnigramIndicesVec = unigrams
+            .into_iter()
+            .map(|(k, w)| match layout.resolve_modifiers(&k) {
+                (base, LayerModifiers::Toggle(_)) | (base, LayerModifiers::CapsWord(_)) => {
+                    (base, w)
+                }
+                _ => (k, w),
+            })
+            .collect();
+
+        let mut keys = Vec::new();
+        bigrams.grams.iter().for_each(|((c1, c2), w)| {
+            let (idx1, idx2) = match (
+                layout.get_layerkey_index_for_symbol(c1),
+                layout.get_layerkey_index_for_symbol(c2),
+            ) {
+                (Some(idx1), Some(idx2)) => (idx1, idx2),
+                _ => return,
+            };
+
+            keys.clear();
+            ToggleState::new(idx1, layout).transition(idx2, layout, &mut keys);
+            processed_unigrams.extend(keys.iter().map(|m| (*m, factor * w)));
+        });
+
+        processed_unigrams
+    }
+
     fn process_one_shot_modifiers(
         &self,
         unigrams: UnigramIndicesVec,
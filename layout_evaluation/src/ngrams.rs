@@ -2,15 +2,19 @@
 //! ngram (unigram, bigram, trigram) data that serve as the underlying data for layout
 //! evaluations.
 
-use crate::ngram_mapper::common::NgramMap;
+use crate::{compressed, ngram_mapper::common::NgramMap};
+use keyboard_layout::symbols;
 
 use ahash::AHashMap;
 use anyhow::Result;
+use fnv::FnvHasher;
 use serde::Deserialize;
 use std::{
-    fs::{self, create_dir_all, File},
+    fs::{create_dir_all, File},
+    hash::{Hash, Hasher},
     io::{BufWriter, Write},
     path::Path,
+    str::FromStr,
 };
 
 /// Configuration parameters for ngram processing
@@ -18,6 +22,159 @@ use std::{
 pub struct NgramsConfig {
     /// Parameters for the increase in weight of common ngrams (with already high frequency).
     pub increase_common_ngrams: IncreaseCommonNgramsConfig,
+    /// Additional ngram sources ("channels"), e.g. for digits or navigation keys, that are
+    /// merged into the corpus' ngrams with their own weights.
+    #[serde(default)]
+    pub channels: Vec<NgramChannelConfig>,
+    /// Parameters for smoothing the frequencies of bigrams and trigrams that do not occur in the corpus.
+    #[serde(default)]
+    pub smoothing: SmoothingConfig,
+    /// How to handle capital letters.
+    #[serde(default)]
+    pub case_handling: CaseHandling,
+}
+
+/// The [`CaseHandling`] specifies how capital letters in the corpus are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "type", content = "value")]
+#[serde(rename_all = "snake_case")]
+pub enum CaseHandling {
+    /// Keep all capitals (each one is typed with Shift).
+    Keep,
+    /// Fold all capitals to lowercase.
+    Fold,
+    /// Keep the given fraction of capitals (typed with Shift) and fold the rest, e.g. to model
+    /// capitals that result from autocapitalization.
+    Shift(f64),
+}
+
+impl Default for CaseHandling {
+    fn default() -> Self {
+        CaseHandling::Keep
+    }
+}
+
+impl FromStr for CaseHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "keep" => Ok(CaseHandling::Keep),
+            None if s == "fold" => Ok(CaseHandling::Fold),
+            Some(("shift", fraction)) => match f64::from_str(fraction) {
+                Ok(fraction) if (0.0..=1.0).contains(&fraction) => {
+                    Ok(CaseHandling::Shift(fraction))
+                }
+                _ => Err(format!("Invalid shift fraction '{}'", fraction)),
+            },
+            _ => Err(format!(
+                "Unknown case handling '{}' (expected 'keep', 'fold', or 'shift:<fraction>')",
+                s
+            )),
+        }
+    }
+}
+
+impl CaseHandling {
+    /// Fraction of capitals that are typed with Shift.
+    pub fn shifted_fraction(&self) -> f64 {
+        match self {
+            CaseHandling::Keep => 1.0,
+            CaseHandling::Fold => 0.0,
+            CaseHandling::Shift(fraction) => *fraction,
+        }
+    }
+}
+
+/// The lowercase variant of a capital letter (if it consists of a single char).
+fn single_lowercase(c: char) -> Option<char> {
+    if !c.is_uppercase() {
+        return None;
+    }
+
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => Some(l),
+        _ => None,
+    }
+}
+
+/// All variants of the given symbols with each capital either kept or folded to lowercase,
+/// together with their share of the ngram's weight.
+fn case_variants(symbols: &[char], shifted_fraction: f64) -> Vec<(Vec<char>, f64)> {
+    let mut variants = vec![(Vec::with_capacity(symbols.len()), 1.0)];
+    for c in symbols {
+        variants = match single_lowercase(*c) {
+            Some(lower) => variants
+                .into_iter()
+                .flat_map(|(v, share)| {
+                    let mut shifted = v.clone();
+                    shifted.push(*c);
+                    let mut folded = v;
+                    folded.push(lower);
+                    vec![
+                        (shifted, share * shifted_fraction),
+                        (folded, share * (1.0 - shifted_fraction)),
+                    ]
+                })
+                .filter(|(_, share)| *share > 0.0)
+                .collect(),
+            None => {
+                variants.iter_mut().for_each(|(v, _)| v.push(*c));
+                variants
+            }
+        };
+    }
+
+    variants
+}
+
+/// Methods for estimating the weights of unseen ngrams.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmoothingMethod {
+    /// No smoothing.
+    None,
+    /// All unseen ngrams receive the same weight.
+    AddK,
+    /// Unseen ngrams receive weights estimated from lower-order ngrams, i.e.
+    /// `p(ab) ~ p(a) p(b)` and `p(abc) ~ p(ab) p(bc) / p(b)` (with `p(ab) ~ p(a) p(b)` for
+    /// bigrams that are not given either).
+    Backoff,
+}
+
+/// Configuration parameters for the smoothing of unseen ngrams.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmoothingConfig {
+    /// The smoothing method.
+    pub method: SmoothingMethod,
+    /// Total weight of all added ngrams relative to the total weight of the observed ngrams.
+    pub mass: f64,
+    /// Only ngrams consisting of the most frequent unigrams up to this number are added.
+    pub max_symbols: usize,
+}
+
+impl Default for SmoothingConfig {
+    fn default() -> Self {
+        Self {
+            method: SmoothingMethod::None,
+            mass: 0.01,
+            max_symbols: 40,
+        }
+    }
+}
+
+/// Configuration of an additional ngram source that is merged into the corpus' ngrams.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NgramChannelConfig {
+    /// Name of the channel (used for logging).
+    pub name: String,
+    /// Directory containing the channel's ngram frequency files. Only `1-grams.txt` is required,
+    /// missing `2-grams.txt` or `3-grams.txt` files are skipped.
+    pub ngrams: String,
+    /// Total weight of each of the channel's ngram types relative to the total weight of the
+    /// corpus' ngrams of the same type.
+    pub weight: f64,
 }
 
 /// Configuration parameters for process of increasing the weight of common ngrams.
@@ -47,6 +204,116 @@ impl Default for IncreaseCommonNgramsConfig {
     }
 }
 
+/// Add the weights of `other` (multiplied with `factor`) to the `symbol_weights`.
+pub fn add_scaled_ngrams<T: Clone + Eq + std::hash::Hash>(
+    symbol_weights: &mut AHashMap<T, f64>,
+    other: &AHashMap<T, f64>,
+    factor: f64,
+) {
+    other.iter().for_each(|(gram, weight)| {
+        symbol_weights.insert_or_add_weight(gram.clone(), factor * weight);
+    });
+}
+
+/// Merge the weights of `other` into a copy of the `symbol_weights`, scaled such that their total
+/// weight amounts to `weight` times the total weight of the `symbol_weights`.
+pub fn merge_weighted_ngrams<T: Clone + Eq + std::hash::Hash>(
+    symbol_weights: &AHashMap<T, f64>,
+    other: &AHashMap<T, f64>,
+    weight: f64,
+) -> AHashMap<T, f64> {
+    let mut merged = symbol_weights.clone();
+    let other_total: f64 = other.values().sum();
+    if other_total > 0.0 {
+        let total: f64 = symbol_weights.values().sum();
+        add_scaled_ngrams(&mut merged, other, weight * total / other_total);
+    }
+
+    merged
+}
+
+/// Distribute the smoothing mass over the given unseen ngrams, either uniformly or proportional
+/// to their estimated probabilities (depending on the smoothing method).
+fn add_smoothing_weights<T: Eq + std::hash::Hash>(
+    symbol_weights: &mut AHashMap<T, f64>,
+    unseen: Vec<(T, f64)>,
+    config: &SmoothingConfig,
+    total_weight: f64,
+) {
+    let mass = config.mass * total_weight;
+    let n_unseen = unseen.len() as f64;
+    let total_estimate: f64 = unseen.iter().map(|(_, p)| p).sum();
+
+    unseen.into_iter().for_each(|(gram, p)| {
+        let weight = match config.method {
+            SmoothingMethod::Backoff if total_estimate > 0.0 => mass * p / total_estimate,
+            SmoothingMethod::None => 0.0,
+            _ => mass / n_unseen,
+        };
+        if weight > 0.0 {
+            symbol_weights.insert_or_add_weight(gram, weight);
+        }
+    });
+}
+
+/// Uniformly distributed number in [0, 1) derived from the hash of `seed`, `draw`, and `gram`.
+/// The hash function (FNV-1a) is fixed, so that a seed yields the same sample across runs and
+/// compiler versions.
+fn hashed_uniform<T: Hash>(seed: u64, draw: u64, gram: &T) -> f64 {
+    let mut hasher = FnvHasher::default();
+    (seed, draw).hash(&mut hasher);
+    gram.hash(&mut hasher);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Poisson-distributed number with mean `lambda` (normal approximation for large means).
+fn hashed_poisson<T: Hash>(lambda: f64, seed: u64, gram: &T) -> u64 {
+    if lambda > 30.0 {
+        // Box-Muller transform
+        let u1 = 1.0 - hashed_uniform(seed, 0, gram);
+        let u2 = hashed_uniform(seed, 1, gram);
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        return (lambda + z * lambda.sqrt()).round().max(0.0) as u64;
+    }
+
+    // Knuth's algorithm
+    let threshold = (-lambda).exp();
+    let mut draws = 0;
+    let mut p = hashed_uniform(seed, 0, gram);
+    while p > threshold {
+        draws += 1;
+        p *= hashed_uniform(seed, draws, gram);
+    }
+
+    draws
+}
+
+/// Resample a weighted ngram set (bootstrap) by drawing `sample_size` ngrams with replacement
+/// (approximately, the number of draws of each ngram is Poisson-distributed). Without a
+/// `sample_size`, the total weight is used, i.e. the weights are interpreted as ngram counts.
+/// The resampled weights are scaled to the original total weight. The draws only depend on the
+/// `seed` and the ngram itself, so they are reproducible.
+pub fn bootstrap_ngrams<T: Clone + Eq + Hash>(
+    symbol_weights: &AHashMap<T, f64>,
+    sample_size: Option<f64>,
+    seed: u64,
+) -> AHashMap<T, f64> {
+    let total_weight: f64 = symbol_weights.values().sum();
+    let sample_size = sample_size.unwrap_or(total_weight);
+    if total_weight <= 0.0 || sample_size <= 0.0 {
+        return symbol_weights.clone();
+    }
+    let scale = sample_size / total_weight;
+
+    symbol_weights
+        .iter()
+        .filter_map(|(gram, weight)| {
+            let draws = hashed_poisson(weight * scale, seed, gram);
+            (draws > 0).then(|| (gram.clone(), draws as f64 / scale))
+        })
+        .collect()
+}
+
 pub fn increase_common_ngrams<T>(
     symbol_weights: &mut AHashMap<T, f64>,
     config: &IncreaseCommonNgramsConfig,
@@ -71,6 +338,12 @@ pub struct Unigrams {
     pub grams: AHashMap<char, f64>,
 }
 
+/// The symbols of the text that are counted in ngrams.
+fn text_symbols(text: &str) -> impl Iterator<Item = char> + Clone + '_ {
+    text.chars()
+        .filter(|c| *c != '\r' && !symbols::is_presentation_modifier(*c))
+}
+
 fn process_special_characters(s: &str) -> String {
     s.replace("\\n", "\n").replace("\\\\", "\\")
 }
@@ -82,8 +355,14 @@ fn process_special_characters_inverse(s: &str) -> String {
 impl Unigrams {
     /// Collect unigrams from given text.
     pub fn from_text(text: &str) -> Result<Self> {
+        Self::from_text_chunk(text, "")
+    }
+
+    /// Collect the unigrams of a chunk of a text. Unigrams do not extend beyond the chunk, so the
+    /// following text is ignored (it is accepted for consistency with the other ngrams).
+    pub fn from_text_chunk(chunk: &str, _following: &str) -> Result<Self> {
         let mut grams = AHashMap::default();
-        let chars = text.chars().filter(|c| *c != '\r');
+        let chars = text_symbols(chunk);
         chars
             //.filter(|c| !c.is_whitespace())
             .for_each(|c| {
@@ -112,9 +391,10 @@ impl Unigrams {
         Ok(Unigrams { grams })
     }
 
-    /// Read unigrams and weights from a file containing lines with unigrams and their weights.
+    /// Read unigrams and weights from a (possibly compressed) file containing lines with unigrams
+    /// and their weights.
     pub fn from_file(filename: &str) -> Result<Self> {
-        let data = fs::read_to_string(filename)?;
+        let data = compressed::read_to_string(filename)?;
         Unigrams::from_frequencies_str(&data)
     }
 
@@ -123,6 +403,20 @@ impl Unigrams {
         self.grams.values().sum()
     }
 
+    /// The `n` most frequent symbols with their relative frequencies.
+    fn top_symbols(&self, n: usize) -> Vec<(char, f64)> {
+        let total_weight = self.total_weight();
+        let mut symbols: Vec<(char, f64)> = self
+            .grams
+            .iter()
+            .map(|(c, w)| (*c, w / total_weight))
+            .collect();
+        symbols.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+        symbols.truncate(n);
+
+        symbols
+    }
+
     /// Return a reduced set of the unigrams containing only the most common unigrams up to a
     /// given combined fraction.
     pub fn tops(&self, fraction: f64) -> Self {
@@ -149,7 +443,19 @@ impl Unigrams {
         Self { grams }
     }
 
-    // Return a reduced set of unigrams filtering out those containing a given character
+    /// Apply the given handling of capital letters.
+    pub fn with_case_handling(&self, case_handling: &CaseHandling) -> Self {
+        let shifted_fraction = case_handling.shifted_fraction();
+        let mut grams = AHashMap::default();
+        self.grams.iter().for_each(|(c, w)| {
+            case_variants(&[*c], shifted_fraction)
+                .into_iter()
+                .for_each(|(v, share)| grams.insert_or_add_weight(v[0], w * share));
+        });
+        Self { grams }
+    }
+
+    /// Return a reduced set of unigrams filtering out those containing a given character.
     pub fn exclude_char(&self, exclude: &char) -> Self {
         let grams: AHashMap<char, f64> = self
             .grams
@@ -189,6 +495,25 @@ impl Unigrams {
         increase_common_ngrams(&mut grams, params);
         Self { grams }
     }
+
+    /// Resample the ngrams (see [`bootstrap_ngrams`]).
+    pub fn bootstrap(&self, sample_size: Option<f64>, seed: u64) -> Self {
+        Self {
+            grams: bootstrap_ngrams(&self.grams, sample_size, seed),
+        }
+    }
+
+    /// Add the (unscaled) weights of the ngrams of `other`, e.g. of another chunk of the text.
+    pub fn add(&mut self, other: &Self) {
+        add_scaled_ngrams(&mut self.grams, &other.grams, 1.0);
+    }
+
+    /// Merge the ngrams of `other` (see [`merge_weighted_ngrams`]).
+    pub fn merge_weighted(&self, other: &Self, weight: f64) -> Self {
+        Self {
+            grams: merge_weighted_ngrams(&self.grams, &other.grams, weight),
+        }
+    }
 }
 
 /// Holds a hashmap of bigrams (two chars) with corresponding frequency (here often called "weight").
@@ -200,8 +525,14 @@ pub struct Bigrams {
 impl Bigrams {
     /// Collect bigrams from given text.
     pub fn from_text(text: &str) -> Result<Self> {
+        Self::from_text_chunk(text, "")
+    }
+
+    /// Collect the bigrams starting within a chunk of a text, continued by the text following
+    /// the chunk. The bigrams of consecutive chunks add up to the bigrams of the whole text.
+    pub fn from_text_chunk(chunk: &str, following: &str) -> Result<Self> {
         let mut grams = AHashMap::default();
-        let chars = text.chars().filter(|c| *c != '\r');
+        let chars = text_symbols(chunk).chain(text_symbols(following).take(1));
         chars
             .clone()
             .zip(chars.clone().skip(1))
@@ -231,9 +562,10 @@ impl Bigrams {
         Ok(Bigrams { grams })
     }
 
-    /// Read bigrams and weights from a file containing lines with bigrams and their weights.
+    /// Read bigrams and weights from a (possibly compressed) file containing lines with bigrams
+    /// and their weights.
     pub fn from_file(filename: &str) -> Result<Self> {
-        let data = fs::read_to_string(filename)?;
+        let data = compressed::read_to_string(filename)?;
         Bigrams::from_frequencies_str(&data)
     }
 
@@ -242,6 +574,24 @@ impl Bigrams {
         self.grams.values().sum()
     }
 
+    /// Add weights for bigrams of frequent symbols that do not occur in the corpus.
+    pub fn smoothed(&self, unigrams: &Unigrams, config: &SmoothingConfig) -> Self {
+        let mut grams = self.grams.clone();
+        if config.method == SmoothingMethod::None {
+            return Self { grams };
+        }
+
+        let symbols = unigrams.top_symbols(config.max_symbols);
+        let unseen: Vec<((char, char), f64)> = symbols
+            .iter()
+            .flat_map(|(c1, p1)| symbols.iter().map(move |(c2, p2)| ((*c1, *c2), p1 * p2)))
+            .filter(|(bigram, _)| !self.grams.contains_key(bigram))
+            .collect();
+
+        add_smoothing_weights(&mut grams, unseen, config, self.total_weight());
+        Self { grams }
+    }
+
     /// Return a reduced set of the bigrams containing only the most common bigrams up to a
     /// given combined fraction.
     pub fn tops(&self, fraction: f64) -> Self {
@@ -268,7 +618,19 @@ impl Bigrams {
         Self { grams }
     }
 
-    // Return a reduced set of bigrams filtering out those containing a given character
+    /// Apply the given handling of capital letters.
+    pub fn with_case_handling(&self, case_handling: &CaseHandling) -> Self {
+        let shifted_fraction = case_handling.shifted_fraction();
+        let mut grams = AHashMap::default();
+        self.grams.iter().for_each(|((c1, c2), w)| {
+            case_variants(&[*c1, *c2], shifted_fraction)
+                .into_iter()
+                .for_each(|(v, share)| grams.insert_or_add_weight((v[0], v[1]), w * share));
+        });
+        Self { grams }
+    }
+
+    /// Return a reduced set of bigrams filtering out those containing a given character.
     pub fn exclude_char(&self, exclude: &char) -> Self {
         let grams: AHashMap<(char, char), f64> = self
             .grams
@@ -316,6 +678,25 @@ impl Bigrams {
         increase_common_ngrams(&mut grams, params);
         Self { grams }
     }
+
+    /// Resample the ngrams (see [`bootstrap_ngrams`]).
+    pub fn bootstrap(&self, sample_size: Option<f64>, seed: u64) -> Self {
+        Self {
+            grams: bootstrap_ngrams(&self.grams, sample_size, seed),
+        }
+    }
+
+    /// Add the (unscaled) weights of the ngrams of `other`, e.g. of another chunk of the text.
+    pub fn add(&mut self, other: &Self) {
+        add_scaled_ngrams(&mut self.grams, &other.grams, 1.0);
+    }
+
+    /// Merge the ngrams of `other` (see [`merge_weighted_ngrams`]).
+    pub fn merge_weighted(&self, other: &Self, weight: f64) -> Self {
+        Self {
+            grams: merge_weighted_ngrams(&self.grams, &other.grams, weight),
+        }
+    }
 }
 
 /// Holds a hashmap of trigrams (three chars) with corresponding frequency (here often called "weight").
@@ -327,8 +708,14 @@ pub struct Trigrams {
 impl Trigrams {
     /// Collect trigrams from given text.
     pub fn from_text(text: &str) -> Result<Self> {
+        Self::from_text_chunk(text, "")
+    }
+
+    /// Collect the trigrams starting within a chunk of a text, continued by the text following
+    /// the chunk. The trigrams of consecutive chunks add up to the trigrams of the whole text.
+    pub fn from_text_chunk(chunk: &str, following: &str) -> Result<Self> {
         let mut grams = AHashMap::default();
-        let chars = text.chars().filter(|c| *c != '\r');
+        let chars = text_symbols(chunk).chain(text_symbols(following).take(2));
         chars
             .clone()
             .zip(chars.clone().skip(1))
@@ -361,9 +748,10 @@ impl Trigrams {
         Ok(Trigrams { grams })
     }
 
-    /// Read trigrams and weights from a file containing lines with trigrams and their weights.
+    /// Read trigrams and weights from a (possibly compressed) file containing lines with trigrams
+    /// and their weights.
     pub fn from_file(filename: &str) -> Result<Self> {
-        let data = fs::read_to_string(filename)?;
+        let data = compressed::read_to_string(filename)?;
         Trigrams::from_frequencies_str(&data)
     }
 
@@ -372,6 +760,45 @@ impl Trigrams {
         self.grams.values().sum()
     }
 
+    /// Add weights for trigrams of frequent symbols that do not occur in the corpus.
+    pub fn smoothed(
+        &self,
+        unigrams: &Unigrams,
+        bigrams: &Bigrams,
+        config: &SmoothingConfig,
+    ) -> Self {
+        let mut grams = self.grams.clone();
+        if config.method == SmoothingMethod::None {
+            return Self { grams };
+        }
+
+        let symbols = unigrams.top_symbols(config.max_symbols);
+        let bigram_total = bigrams.total_weight();
+        // bigrams that are not given (e.g. if the bigrams are not smoothed) are estimated from
+        // the unigrams, otherwise all trigrams containing them would be dropped
+        let p_bigram =
+            |(c1, p1): (char, f64), (c2, p2): (char, f64)| match bigrams.grams.get(&(c1, c2)) {
+                Some(w) if bigram_total > 0.0 => w / bigram_total,
+                _ => p1 * p2,
+            };
+
+        let mut unseen = Vec::new();
+        for s1 in symbols.iter() {
+            for s2 in symbols.iter() {
+                for s3 in symbols.iter() {
+                    let trigram = (s1.0, s2.0, s3.0);
+                    if !self.grams.contains_key(&trigram) {
+                        let p = p_bigram(*s1, *s2) * p_bigram(*s2, *s3) / s2.1;
+                        unseen.push((trigram, p));
+                    }
+                }
+            }
+        }
+
+        add_smoothing_weights(&mut grams, unseen, config, self.total_weight());
+        Self { grams }
+    }
+
     /// Return a reduced set of the trigrams containing only the most common trigrams up to a
     /// given combined fraction.
     pub fn tops(&self, fraction: f64) -> Self {
@@ -399,7 +826,19 @@ impl Trigrams {
         Self { grams }
     }
 
-    // Return a reduced set of trigrams filtering out those containing a given character
+    /// Apply the given handling of capital letters.
+    pub fn with_case_handling(&self, case_handling: &CaseHandling) -> Self {
+        let shifted_fraction = case_handling.shifted_fraction();
+        let mut grams = AHashMap::default();
+        self.grams.iter().for_each(|((c1, c2, c3), w)| {
+            case_variants(&[*c1, *c2, *c3], shifted_fraction)
+                .into_iter()
+                .for_each(|(v, share)| grams.insert_or_add_weight((v[0], v[1], v[2]), w * share));
+        });
+        Self { grams }
+    }
+
+    /// Return a reduced set of trigrams filtering out those containing a given character.
     pub fn exclude_char(&self, exclude: &char) -> Self {
         let grams: AHashMap<(char, char, char), f64> = self
             .grams
@@ -453,4 +892,380 @@ impl Trigrams {
         increase_common_ngrams(&mut grams, params);
         Self { grams }
     }
+
+    /// Resample the ngrams (see [`bootstrap_ngrams`]).
+    pub fn bootstrap(&self, sample_size: Option<f64>, seed: u64) -> Self {
+        Self {
+            grams: bootstrap_ngrams(&self.grams, sample_size, seed),
+        }
+    }
+
+    /// Add the (unscaled) weights of the ngrams of `other`, e.g. of another chunk of the text.
+    pub fn add(&mut self, other: &Self) {
+        add_scaled_ngrams(&mut self.grams, &other.grams, 1.0);
+    }
+
+    /// Merge the ngrams of `other` (see [`merge_weighted_ngrams`]).
+    pub fn merge_weighted(&self, other: &Self, weight: f64) -> Self {
+        Self {
+            grams: merge_weighted_ngrams(&self.grams, &other.grams, weight),
+        }
+    }
+}
+
+/// Holds a hashmap of quadgrams (four chars) with corresponding frequency (here often called "weight").
+#[derive(Clone, Debug, Default)]
+pub struct Quadgrams {
+    pub grams: AHashMap<(char, char, char, char), f64>,
+}
+
+impl Quadgrams {
+    /// Collect quadgrams from given text.
+    pub fn from_text(text: &str) -> Result<Self> {
+        Self::from_text_chunk(text, "")
+    }
+
+    /// Collect the quadgrams starting within a chunk of a text, continued by the text following
+    /// the chunk. The quadgrams of consecutive chunks add up to the quadgrams of the whole text.
+    pub fn from_text_chunk(chunk: &str, following: &str) -> Result<Self> {
+        let mut grams = AHashMap::default();
+        let chars = text_symbols(chunk).chain(text_symbols(following).take(3));
+        chars
+            .clone()
+            .zip(chars.clone().skip(1))
+            .zip(chars.clone().skip(2))
+            .zip(chars.clone().skip(3))
+            .for_each(|(((c1, c2), c3), c4)| {
+                grams.insert_or_add_weight((c1, c2, c3, c4), 1.0);
+            });
+
+        Ok(Self { grams })
+    }
+
+    /// Read quadgrams and weights from a string containing lines with quadgrams and their weights.
+    pub fn from_frequencies_str(data: &str) -> Result<Self> {
+        let mut grams = AHashMap::default();
+        for line in data.lines() {
+            let mut parts = line.trim_start().splitn(2, ' ');
+            let weight: f64 = parts.next().unwrap().parse().unwrap();
+            let quadgram = parts.next().unwrap();
+            let quadgram = process_special_characters(quadgram);
+            let c: Vec<char> = quadgram.chars().collect();
+            if c.len() != 4 {
+                log::info!("Len of quadgram {} is unequal four: {:?}", quadgram, c);
+                continue;
+            }
+            grams.insert_or_add_weight((c[0], c[1], c[2], c[3]), weight);
+        }
+
+        Ok(Quadgrams { grams })
+    }
+
+    /// Read quadgrams and weights from a (possibly compressed) file containing lines with quadgrams
+    /// and their weights.
+    pub fn from_file(filename: &str) -> Result<Self> {
+        let data = compressed::read_to_string(filename)?;
+        Quadgrams::from_frequencies_str(&data)
+    }
+
+    /// Total weight of all combined quadgrams
+    pub fn total_weight(&self) -> f64 {
+        self.grams.values().sum()
+    }
+
+    /// Return a reduced set of the quadgrams containing only the most common quadgrams up to a
+    /// given combined fraction.
+    pub fn tops(&self, fraction: f64) -> Self {
+        let target_weight = fraction * self.total_weight();
+        let mut total_weight = 0.0;
+        let mut sorted_grams: Vec<((char, char, char, char), f64)> =
+            self.grams.clone().into_iter().collect();
+        sorted_grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+        let grams: AHashMap<(char, char, char, char), f64> = sorted_grams
+            .iter()
+            .take_while(|(_c, w)| {
+                let res = total_weight < target_weight;
+                total_weight += *w;
+
+                res
+            })
+            .cloned()
+            .collect();
+
+        log::info!(
+            "Quadgrams: Reducing from originally {} to the top {} ngrams.",
+            self.grams.len(),
+            grams.len()
+        );
+        Self { grams }
+    }
+
+    /// Apply the given handling of capital letters.
+    pub fn with_case_handling(&self, case_handling: &CaseHandling) -> Self {
+        let shifted_fraction = case_handling.shifted_fraction();
+        let mut grams = AHashMap::default();
+        self.grams.iter().for_each(|((c1, c2, c3, c4), w)| {
+            case_variants(&[*c1, *c2, *c3, *c4], shifted_fraction)
+                .into_iter()
+                .for_each(|(v, share)| {
+                    grams.insert_or_add_weight((v[0], v[1], v[2], v[3]), w * share)
+                });
+        });
+        Self { grams }
+    }
+
+    /// Return a reduced set of quadgrams filtering out those containing a given character.
+    pub fn exclude_char(&self, exclude: &char) -> Self {
+        let grams: AHashMap<(char, char, char, char), f64> = self
+            .grams
+            .iter()
+            .filter(|((c1, c2, c3, c4), _w)| {
+                *c1 != *exclude && *c2 != *exclude && *c3 != *exclude && *c4 != *exclude
+            })
+            .map(|(c, w)| (*c, *w))
+            .collect();
+        Self { grams }
+    }
+
+    /// Save frequencies to file
+    pub fn save_frequencies<T: AsRef<Path>>(&self, filename: T) -> Result<(), String> {
+        let p = filename.as_ref();
+        create_dir_all(p.parent().unwrap()).map_err(|e| {
+            format!(
+                "Unable to create directory '{}': {}",
+                p.to_str().unwrap(),
+                e
+            )
+        })?;
+
+        let mut grams: Vec<((char, char, char, char), f64)> =
+            self.grams.iter().map(|(c, w)| (*c, *w)).collect();
+        grams.sort_by(|(_, w1), (_, w2)| w2.partial_cmp(w1).unwrap());
+
+        let file = File::create(&filename)
+            .map_err(|e| format!("Unable to create file '{}': {}", p.to_str().unwrap(), e))?;
+        let mut buf_writer = BufWriter::new(file);
+        grams.iter().for_each(|((c1, c2, c3, c4), w)| {
+            let processed: String = [c1, c2, c3, c4]
+                .iter()
+                .map(|c| process_special_characters_inverse(&c.to_string()))
+                .collect();
+            writeln!(&mut buf_writer, "{} {}", w, processed).unwrap();
+        });
+
+        Ok(())
+    }
+
+    pub fn increase_common(&self, params: &IncreaseCommonNgramsConfig) -> Self {
+        let mut grams = self.grams.clone();
+        increase_common_ngrams(&mut grams, params);
+        Self { grams }
+    }
+
+    /// Resample the ngrams (see [`bootstrap_ngrams`]).
+    pub fn bootstrap(&self, sample_size: Option<f64>, seed: u64) -> Self {
+        Self {
+            grams: bootstrap_ngrams(&self.grams, sample_size, seed),
+        }
+    }
+
+    /// Add the (unscaled) weights of the ngrams of `other`, e.g. of another chunk of the text.
+    pub fn add(&mut self, other: &Self) {
+        add_scaled_ngrams(&mut self.grams, &other.grams, 1.0);
+    }
+
+    /// Merge the ngrams of `other` (see [`merge_weighted_ngrams`]).
+    pub fn merge_weighted(&self, other: &Self, weight: f64) -> Self {
+        Self {
+            grams: merge_weighted_ngrams(&self.grams, &other.grams, weight),
+        }
+    }
+}
+
+/// Holds a hashmap of shortcuts (chorded keystrokes, e.g. Ctrl+c or Ctrl+Shift+t) with corresponding
+/// frequency (here often called "weight"). Each shortcut is represented by the sequence of its
+/// symbols: the held modifiers in the order they are pressed, followed by the key to tap.
+#[derive(Clone, Debug, Default)]
+pub struct Shortcuts {
+    pub grams: AHashMap<Vec<char>, f64>,
+}
+
+impl Shortcuts {
+    /// Parse a shortcut of the form `⎈+⇧+t` into its symbols. A trailing `++` denotes the `+` key.
+    fn parse_shortcut(s: &str) -> Vec<char> {
+        let (modifiers, key) = match s.strip_suffix("++") {
+            Some(modifiers) => (modifiers, Some('+')),
+            None => (s, None),
+        };
+
+        let mut symbols: Vec<char> = modifiers
+            .split('+')
+            .map(process_special_characters)
+            .filter_map(|part| part.chars().next())
+            .collect();
+        symbols.extend(key);
+
+        symbols
+    }
+
+    /// Read shortcuts and weights from a string containing lines with weights and shortcuts
+    /// (symbols joined with `+`, e.g. `1200 ⎈+c`).
+    pub fn from_frequencies_str(data: &str) -> Result<Self> {
+        let mut grams = AHashMap::default();
+        for line in data.lines().filter(|l| !l.trim().is_empty()) {
+            let mut parts = line.trim_start().splitn(2, ' ');
+            let weight: f64 = parts.next().unwrap().parse()?;
+            let shortcut = parts.next().unwrap_or_default();
+            let symbols = Shortcuts::parse_shortcut(shortcut);
+            if symbols.len() < 2 {
+                log::info!(
+                    "Shortcut {} does not contain a modifier: {:?}",
+                    shortcut,
+                    symbols
+                );
+                continue;
+            }
+            grams.insert_or_add_weight(symbols, weight);
+        }
+
+        Ok(Shortcuts { grams })
+    }
+
+    /// Read shortcuts and weights from a (possibly compressed) file containing lines with weights
+    /// and shortcuts.
+    pub fn from_file(filename: &str) -> Result<Self> {
+        let data = compressed::read_to_string(filename)?;
+        Shortcuts::from_frequencies_str(&data)
+    }
+
+    /// Total weight of all combined shortcuts
+    pub fn total_weight(&self) -> f64 {
+        self.grams.values().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backoff(mass: f64) -> SmoothingConfig {
+        SmoothingConfig {
+            method: SmoothingMethod::Backoff,
+            mass,
+            max_symbols: 40,
+        }
+    }
+
+    fn unigrams() -> Unigrams {
+        Unigrams {
+            grams: [('a', 5.0), ('b', 3.0), ('c', 2.0)]
+                .iter()
+                .cloned()
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn smoothing_adds_unseen_bigrams_by_unigram_frequency() {
+        let bigrams = Bigrams {
+            grams: [(('a', 'b'), 10.0)].iter().cloned().collect(),
+        };
+        let smoothed = bigrams.smoothed(&unigrams(), &backoff(0.1));
+
+        assert_eq!(smoothed.grams.len(), 9);
+        assert_eq!(smoothed.grams[&('a', 'b')], 10.0);
+        assert!((smoothed.total_weight() - 11.0).abs() < 1e-9);
+        assert!(smoothed.grams[&('a', 'a')] > smoothed.grams[&('c', 'c')]);
+    }
+
+    #[test]
+    fn smoothing_adds_trigrams_containing_unseen_bigrams() {
+        let bigrams = Bigrams {
+            grams: [(('a', 'b'), 6.0), (('b', 'a'), 4.0)]
+                .iter()
+                .cloned()
+                .collect(),
+        };
+        let trigrams = Trigrams {
+            grams: [(('a', 'b', 'a'), 10.0)].iter().cloned().collect(),
+        };
+
+        for bigrams in [
+            bigrams.clone(),
+            bigrams.smoothed(&unigrams(), &backoff(0.1)),
+        ]
+        .iter()
+        {
+            let smoothed = trigrams.smoothed(&unigrams(), bigrams, &backoff(0.1));
+            assert_eq!(smoothed.grams.len(), 27);
+            // neither "ac" nor "cc" were observed
+            assert!(smoothed.grams[&('a', 'c', 'c')] > 0.0);
+            assert!((smoothed.total_weight() - 11.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn no_smoothing_keeps_the_ngrams() {
+        let bigrams = Bigrams {
+            grams: [(('a', 'b'), 10.0)].iter().cloned().collect(),
+        };
+        let smoothed = bigrams.smoothed(&unigrams(), &SmoothingConfig::default());
+
+        assert_eq!(smoothed.grams, bigrams.grams);
+    }
+
+    #[test]
+    fn parses_case_handling() {
+        assert_eq!("keep".parse(), Ok(CaseHandling::Keep));
+        assert_eq!("fold".parse(), Ok(CaseHandling::Fold));
+        assert_eq!("shift:0.25".parse(), Ok(CaseHandling::Shift(0.25)));
+        for invalid in ["shift", "shift:1.5", "shift:x", "upper"] {
+            assert!(invalid.parse::<CaseHandling>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn case_handling_keeps_folds_or_splits_the_capitals() {
+        let unigrams = Unigrams {
+            grams: [('A', 4.0), ('a', 1.0), ('ß', 2.0)]
+                .iter()
+                .cloned()
+                .collect(),
+        };
+
+        let kept = unigrams.with_case_handling(&CaseHandling::Keep);
+        assert_eq!(kept.grams, unigrams.grams);
+
+        let folded = unigrams.with_case_handling(&CaseHandling::Fold);
+        assert_eq!(folded.grams.len(), 2);
+        assert_eq!(folded.grams[&'a'], 5.0);
+        assert_eq!(folded.grams[&'ß'], 2.0);
+
+        let shifted = unigrams.with_case_handling(&CaseHandling::Shift(0.25));
+        assert_eq!(shifted.grams[&'A'], 1.0);
+        assert_eq!(shifted.grams[&'a'], 4.0);
+        assert!((shifted.total_weight() - unigrams.total_weight()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn case_handling_splits_each_capital_of_an_ngram() {
+        let bigrams = Bigrams {
+            grams: [(('A', 'B'), 16.0)].iter().cloned().collect(),
+        };
+        let shifted = bigrams.with_case_handling(&CaseHandling::Shift(0.25));
+        assert_eq!(shifted.grams[&('A', 'B')], 1.0);
+        assert_eq!(shifted.grams[&('A', 'b')], 3.0);
+        assert_eq!(shifted.grams[&('a', 'B')], 3.0);
+        assert_eq!(shifted.grams[&('a', 'b')], 9.0);
+
+        let trigrams = Trigrams {
+            grams: [(('A', 'b', 'C'), 2.0), (('a', 'b', 'c'), 1.0)]
+                .iter()
+                .cloned()
+                .collect(),
+        };
+        let folded = trigrams.with_case_handling(&CaseHandling::Fold);
+        assert_eq!(folded.grams.len(), 1);
+        assert_eq!(folded.grams[&('a', 'b', 'c')], 3.0);
+    }
 }
@@ -1,8 +1,10 @@
 //! The `results` module contains structs representing the results of metric evaluations.
 
+use keyboard_layout::key::{Finger, Hand, HandFingerMap};
+
 use colored::Colorize;
-use serde::{Deserialize, Serialize};
-use std::{fmt, slice};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{fmt, slice, str::FromStr};
 
 /// The [`NormalizationType`] specifies how the total cost of a metric evaluation shall be normalized.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -17,6 +19,128 @@ pub enum NormalizationType {
     WeightAll(f64),
 }
 
+/// The [`CorpusObjective`] specifies how the total costs of a layout for several corpora are combined.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", content = "value")]
+#[serde(rename_all = "snake_case")]
+pub enum CorpusObjective {
+    /// Mean of the per-corpus total costs.
+    Mean,
+    /// Worst (highest) per-corpus total cost.
+    Max,
+    /// Mean of the worst given fraction of the per-corpus total costs (Conditional Value at Risk).
+    Cvar(f64),
+}
+
+impl Default for CorpusObjective {
+    fn default() -> Self {
+        CorpusObjective::Mean
+    }
+}
+
+impl FromStr for CorpusObjective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "mean" => Ok(CorpusObjective::Mean),
+            None if s == "max" => Ok(CorpusObjective::Max),
+            Some(("cvar", fraction)) => match f64::from_str(fraction) {
+                Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => {
+                    Ok(CorpusObjective::Cvar(fraction))
+                }
+                _ => Err(format!("Invalid CVaR fraction '{}'", fraction)),
+            },
+            _ => Err(format!(
+                "Unknown corpus objective '{}' (expected 'mean', 'max', or 'cvar:<fraction>')",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CorpusObjective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorpusObjective::Mean => write!(f, "mean"),
+            CorpusObjective::Max => write!(f, "max"),
+            CorpusObjective::Cvar(fraction) => write!(f, "cvar:{}", fraction),
+        }
+    }
+}
+
+impl CorpusObjective {
+    /// Combine the given per-corpus total costs into a single cost.
+    pub fn aggregate(&self, costs: &[f64]) -> f64 {
+        if costs.is_empty() {
+            return 0.0;
+        }
+
+        match self {
+            CorpusObjective::Mean => costs.iter().sum::<f64>() / costs.len() as f64,
+            CorpusObjective::Max => costs.iter().cloned().fold(f64::MIN, f64::max),
+            CorpusObjective::Cvar(fraction) => {
+                let mut sorted_costs = costs.to_vec();
+                sorted_costs.sort_by(|c1, c2| c2.partial_cmp(c1).unwrap());
+                let n = ((fraction * costs.len() as f64).ceil() as usize).clamp(1, costs.len());
+
+                sorted_costs.iter().take(n).sum::<f64>() / n as f64
+            }
+        }
+    }
+}
+
+/// The total cost of a layout for an individual corpus.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CorpusCost {
+    /// Name of the corpus.
+    pub name: String,
+    /// Total cost of the layout wrt. this corpus.
+    pub cost: f64,
+}
+
+/// The total cost of a layout on an individual keyboard (see [`CoKeyboards`]).
+///
+/// [`CoKeyboards`]: crate::co_keyboards::CoKeyboards
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyboardCost {
+    /// Name of the keyboard.
+    pub name: String,
+    /// Layout string of the layout on this keyboard.
+    pub layout: String,
+    /// Total cost of the layout on this keyboard.
+    pub cost: f64,
+    /// Weight of this keyboard in the combined cost.
+    pub weight: f64,
+    /// Cost of a good layout for this keyboard alone. Costs above it are penalized.
+    #[serde(default)]
+    pub reference_cost: Option<f64>,
+}
+
+/// Combine the total costs of a layout on several keyboards: their weighted mean plus
+/// `deviation_penalty` times the sum of the costs above the keyboards' reference costs.
+pub fn combine_keyboard_costs(keyboard_costs: &[KeyboardCost], deviation_penalty: f64) -> f64 {
+    let total_weight: f64 = keyboard_costs.iter().map(|k| k.weight).sum();
+    if total_weight <= 0.0 {
+        return 0.0;
+    }
+
+    let mean = keyboard_costs
+        .iter()
+        .map(|k| k.weight * k.cost)
+        .sum::<f64>()
+        / total_weight;
+    let deviation: f64 = keyboard_costs
+        .iter()
+        .filter_map(|k| {
+            k.reference_cost
+                .map(|reference| (k.cost - reference).max(0.0))
+        })
+        .sum();
+
+    mean + deviation_penalty * deviation
+}
+
 /// Specify which data a metric operates on.
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum MetricType {
@@ -24,6 +148,206 @@ pub enum MetricType {
     Unigram,
     Bigram,
     Trigram,
+    Quadgram,
+}
+
+/// How much of a metric's [`MetricDetails`] shall be reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetailVerbosity {
+    /// Do not report any details.
+    Off,
+    /// Only report aggregated values (e.g. the share of inward rolls), but no lists of
+    /// individual ngrams.
+    Summary,
+    /// Report all details.
+    Full,
+}
+
+impl Default for DetailVerbosity {
+    fn default() -> Self {
+        DetailVerbosity::Full
+    }
+}
+
+/// The kind of entries of a [`DetailSection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetailKind {
+    /// Named aggregated values, e.g. "Inward: 12.3%".
+    Values,
+    /// Individual items (e.g. ngrams or symbols), optionally with a value, e.g. "th (2.1%)".
+    Items,
+}
+
+/// A single entry of a [`DetailSection`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DetailEntry {
+    pub label: String,
+    pub value: Option<f64>,
+}
+
+/// A named group of entries of a metric's details.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DetailSection {
+    /// Name of the section (may be empty).
+    pub name: String,
+    pub kind: DetailKind,
+    /// Unit of the entries' values, e.g. "%".
+    pub unit: String,
+    pub entries: Vec<DetailEntry>,
+}
+
+impl fmt::Display for DetailSection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format_value = |v: f64| {
+            if v.fract() == 0.0 && v.abs() < 1e15 {
+                format!("{:.0}{}", v, self.unit)
+            } else {
+                format!("{:.2}{}", v, self.unit)
+            }
+        };
+
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|e| match (self.kind, e.value) {
+                (DetailKind::Values, Some(v)) => format!("{}: {}", e.label, format_value(v)),
+                (DetailKind::Items, Some(v)) => format!("{} ({})", e.label, format_value(v)),
+                (_, None) => e.label.clone(),
+            })
+            .collect();
+        if self.name.is_empty() {
+            write!(f, "{}", entries.join(", "))
+        } else {
+            write!(f, "{}: {}", self.name, entries.join(", "))
+        }
+    }
+}
+
+/// Structured details of a metric evaluation, e.g. the share of inward rolls or the worst ngrams.
+///
+/// In contrast to a human-oriented message, the details can be rendered by reports, JSON output,
+/// or other frontends as they see fit. The [`fmt::Display`] implementation yields a one-line summary.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct MetricDetails {
+    pub sections: Vec<DetailSection>,
+}
+
+impl MetricDetails {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a section of named values (e.g. "Inward: 12.3%").
+    pub fn values<S: Into<String>>(
+        mut self,
+        name: &str,
+        unit: &str,
+        values: Vec<(S, f64)>,
+    ) -> Self {
+        self.sections.push(DetailSection {
+            name: name.to_string(),
+            kind: DetailKind::Values,
+            unit: unit.to_string(),
+            entries: values
+                .into_iter()
+                .map(|(label, value)| DetailEntry {
+                    label: label.into(),
+                    value: Some(value),
+                })
+                .collect(),
+        });
+        self
+    }
+
+    /// Add a section with one value per finger (from the left pinky to the right pinky).
+    pub fn finger_values(self, name: &str, unit: &str, values: &HandFingerMap<f64>) -> Self {
+        let fingers = [
+            ("LP", Hand::Left, Finger::Pinky),
+            ("LR", Hand::Left, Finger::Ring),
+            ("LM", Hand::Left, Finger::Middle),
+            ("LI", Hand::Left, Finger::Index),
+            ("LT", Hand::Left, Finger::Thumb),
+            ("RT", Hand::Right, Finger::Thumb),
+            ("RI", Hand::Right, Finger::Index),
+            ("RM", Hand::Right, Finger::Middle),
+            ("RR", Hand::Right, Finger::Ring),
+            ("RP", Hand::Right, Finger::Pinky),
+        ];
+
+        self.values(
+            name,
+            unit,
+            fingers
+                .iter()
+                .map(|(label, hand, finger)| (*label, *values.get(hand, finger)))
+                .collect(),
+        )
+    }
+
+    /// Add a section of individual items with values (e.g. the worst ngrams with their share of the cost).
+    pub fn items(mut self, name: &str, unit: &str, items: Vec<(String, f64)>) -> Self {
+        if !items.is_empty() {
+            self.sections.push(DetailSection {
+                name: name.to_string(),
+                kind: DetailKind::Items,
+                unit: unit.to_string(),
+                entries: items
+                    .into_iter()
+                    .map(|(label, value)| DetailEntry {
+                        label,
+                        value: Some(value),
+                    })
+                    .collect(),
+            });
+        }
+        self
+    }
+
+    /// Add a section of individual items without values (e.g. badly placed symbols).
+    pub fn labels(mut self, name: &str, labels: Vec<String>) -> Self {
+        if !labels.is_empty() {
+            self.sections.push(DetailSection {
+                name: name.to_string(),
+                kind: DetailKind::Items,
+                unit: String::new(),
+                entries: labels
+                    .into_iter()
+                    .map(|label| DetailEntry { label, value: None })
+                    .collect(),
+            });
+        }
+        self
+    }
+
+    /// Look up a named value (e.g. "Inward") in all sections of named values.
+    pub fn value(&self, label: &str) -> Option<f64> {
+        self.sections
+            .iter()
+            .filter(|s| s.kind == DetailKind::Values)
+            .flat_map(|s| s.entries.iter())
+            .find(|e| e.label == label)
+            .and_then(|e| e.value)
+    }
+
+    /// Reduce the details to the given verbosity. Returns `None` if nothing remains.
+    pub fn with_verbosity(mut self, verbosity: DetailVerbosity) -> Option<Self> {
+        match verbosity {
+            DetailVerbosity::Off => return None,
+            DetailVerbosity::Summary => self.sections.retain(|s| s.kind == DetailKind::Values),
+            DetailVerbosity::Full => {}
+        }
+
+        (!self.sections.is_empty()).then(|| self)
+    }
+}
+
+impl fmt::Display for MetricDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections: Vec<String> = self.sections.iter().map(|s| s.to_string()).collect();
+        write!(f, "{}", sections.join(";  "))
+    }
 }
 
 /// Describes the result of an individual metric evaluation.
@@ -33,14 +357,39 @@ pub struct MetricResult {
     pub name: String,
     /// Resulting total cost value (not normalized).
     pub cost: f64,
-    /// An optional message that may contain additional details.
-    pub message: Option<String>,
+    /// Optional structured details (e.g. the worst ngrams). Results serialized before the details
+    /// were structured contain a plain `message` instead, which is read as a single label.
+    #[serde(default, alias = "message", deserialize_with = "deserialize_details")]
+    pub details: Option<MetricDetails>,
     /// The weight that shall be used when aggregating all metrics.
     pub weight: f64,
     /// The normalization type to apply.
     pub normalization: NormalizationType,
 }
 
+/// Deserializes [`MetricResult::details`] from either structured details or a plain message.
+fn deserialize_details<'de, D>(deserializer: D) -> Result<Option<MetricDetails>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum DetailsOrMessage {
+        Details(MetricDetails),
+        Message(String),
+    }
+
+    Ok(
+        match Option::<DetailsOrMessage>::deserialize(deserializer)? {
+            Some(DetailsOrMessage::Details(details)) => Some(details),
+            Some(DetailsOrMessage::Message(message)) if !message.trim().is_empty() => {
+                Some(MetricDetails::new().labels("", vec![message]))
+            }
+            _ => None,
+        },
+    )
+}
+
 /// Describes the normalized results of an individual metric evaluation
 /// taking into account the total found/not found ngram weights.
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -83,7 +432,12 @@ impl fmt::Display for MetricResults {
                 // metric_cost.unweighted_cost,
                 format!("{:>7.2}", metric_cost.weighted_cost).green(),
                 format!("{:<35}", metric_cost.core.name).bold(),
-                metric_cost.core.message.as_ref().unwrap_or(&"".to_string()),
+                metric_cost
+                    .core
+                    .details
+                    .as_ref()
+                    .map(|d| d.to_string())
+                    .unwrap_or_default(),
             )?;
         }
         Ok(())
@@ -164,19 +518,59 @@ impl MetricResults {
     }
 }
 
+/// The score maximized by the genetic optimization (the higher the better) for a total cost.
+pub fn optimization_score(total_cost: f64) -> usize {
+    (1e8 / total_cost) as usize
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EvaluationResult {
     layout: String,
     individual_results: Vec<MetricResults>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    corpus_costs: Vec<CorpusCost>,
+    #[serde(default)]
+    corpus_objective: CorpusObjective,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keyboard_costs: Vec<KeyboardCost>,
+    #[serde(default)]
+    deviation_penalty: f64,
 }
 
 impl fmt::Display for EvaluationResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.individual_results
             .iter()
-            .fold(Ok(()), |acc, results| {
-                acc.and_then(|_| writeln!(f, "{}", results))
-            })?;
+            .try_for_each(|results| writeln!(f, "{}", results))?;
+
+        if !self.corpus_costs.is_empty() {
+            let costs: Vec<String> = self
+                .corpus_costs
+                .iter()
+                .map(|c| format!("{}: {:.2}", c.name, c.cost))
+                .collect();
+            writeln!(
+                f,
+                "Corpus costs ({}): {}",
+                self.corpus_objective,
+                costs.join(", ")
+            )?;
+        }
+
+        if !self.keyboard_costs.is_empty() {
+            writeln!(
+                f,
+                "Keyboard costs (deviation penalty: {}):",
+                self.deviation_penalty
+            )?;
+            for k in self.keyboard_costs.iter() {
+                writeln!(
+                    f,
+                    "  {:<20} {:>10.2} (weight: {}) {}",
+                    k.name, k.cost, k.weight, k.layout
+                )?;
+            }
+        }
 
         writeln!(
             f,
@@ -194,10 +588,68 @@ impl EvaluationResult {
         Self {
             layout,
             individual_results,
+            corpus_costs: Vec::new(),
+            corpus_objective: CorpusObjective::default(),
+            keyboard_costs: Vec::new(),
+            deviation_penalty: 0.0,
         }
     }
 
+    /// Attach the total costs of several corpora. The total cost is then computed by
+    /// combining those according to the given objective.
+    pub fn with_corpus_costs(
+        mut self,
+        corpus_costs: Vec<CorpusCost>,
+        corpus_objective: CorpusObjective,
+    ) -> Self {
+        self.corpus_costs = corpus_costs;
+        self.corpus_objective = corpus_objective;
+        self
+    }
+
+    /// Attach the total costs on several keyboards (the first one being the keyboard of this
+    /// result). The total cost is then computed by combining those (see
+    /// [`combine_keyboard_costs`]).
+    pub fn with_keyboard_costs(
+        mut self,
+        keyboard_costs: Vec<KeyboardCost>,
+        deviation_penalty: f64,
+    ) -> Self {
+        self.keyboard_costs = keyboard_costs;
+        self.deviation_penalty = deviation_penalty;
+        self
+    }
+
+    /// The evaluated layout (as layout string).
+    pub fn layout(&self) -> &str {
+        &self.layout
+    }
+
+    /// The total costs on the individual keyboards (empty unless evaluated on several).
+    pub fn keyboard_costs(&self) -> &[KeyboardCost] {
+        &self.keyboard_costs
+    }
+
+    /// The total cost of the layout: the combined costs on all keyboards if it was evaluated on
+    /// several, otherwise the combined costs of all corpora if it was evaluated on several,
+    /// otherwise the sum of the metric costs. The keyboard costs take precedence because the cost
+    /// on each keyboard already combines the costs of its corpora (see
+    /// [`CoKeyboards::attach`](crate::co_keyboards::CoKeyboards::attach)).
     pub fn total_cost(&self) -> f64 {
+        if !self.keyboard_costs.is_empty() {
+            return combine_keyboard_costs(&self.keyboard_costs, self.deviation_penalty);
+        }
+
+        if !self.corpus_costs.is_empty() {
+            let costs: Vec<f64> = self.corpus_costs.iter().map(|c| c.cost).collect();
+            return self.corpus_objective.aggregate(&costs);
+        }
+
+        self.metrics_total_cost()
+    }
+
+    /// Compute the total cost of the individual metric results (ignoring other corpora).
+    pub fn metrics_total_cost(&self) -> f64 {
         let mut cost = 0.0;
         self.individual_results
             .iter()
@@ -207,11 +659,77 @@ impl EvaluationResult {
         cost
     }
 
+    /// The weighted cost of each evaluated metric (in the order of evaluation).
+    pub fn metric_costs(&self) -> Vec<(String, f64)> {
+        self.individual_results
+            .iter()
+            .flat_map(|results| results.metric_costs.iter())
+            .map(|mc| (mc.core.name.clone(), mc.weighted_cost))
+            .collect()
+    }
+
+    /// The summed weighted cost of the metric with the given name (`None` if it was not
+    /// evaluated).
+    pub fn metric_cost(&self, metric: &str) -> Option<f64> {
+        let costs: Vec<f64> = self
+            .individual_results
+            .iter()
+            .flat_map(|results| results.metric_costs.iter())
+            .filter(|mc| mc.core.name == metric)
+            .map(|mc| mc.weighted_cost)
+            .collect();
+
+        (!costs.is_empty()).then(|| costs.iter().sum())
+    }
+
     pub fn optimization_score(&self) -> usize {
-        (1e8 / self.total_cost()) as usize
+        optimization_score(self.total_cost())
     }
 
     pub fn iter(&self) -> slice::Iter<'_, MetricResults> {
         self.individual_results.iter()
     }
 }
+
+/// The cost of a metric attributed to a physical key. The cost of an ngram is split evenly
+/// among its keys.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyCost {
+    /// Matrix position (column, row) of the key
+    pub matrix_position: (u8, u8),
+    pub hand: Hand,
+    pub finger: Finger,
+    /// Symbols of the key on all layers (starting with the base layer)
+    pub symbols: String,
+    pub cost: f64,
+    /// Fraction of the attributed cost of the metric
+    pub share: f64,
+}
+
+/// The cost of a bigram metric attributed to a pair of consecutively pressed keys.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyPairCost {
+    pub from: (u8, u8),
+    pub to: (u8, u8),
+    /// Base layer symbols of the two keys
+    pub symbols: String,
+    pub cost: f64,
+    /// Fraction of the attributed cost of the metric
+    pub share: f64,
+}
+
+/// The costs of a metric attributed to the keys of a layout (e.g. for drawing heatmaps).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KeyCosts {
+    pub metric: String,
+    pub metric_type: MetricType,
+    /// Total cost of the metric as in its evaluation (not normalized)
+    pub metric_cost: f64,
+    /// Sum of the costs of the individual ngrams. This differs from the metric's cost for
+    /// metrics that are not a sum over ngrams (e.g. a balance of loads), which can not be
+    /// attributed to keys.
+    pub attributed_cost: f64,
+    pub keys: Vec<KeyCost>,
+    /// Costs of key pairs (only for bigram metrics)
+    pub key_pairs: Vec<KeyPairCost>,
+}
@@ -0,0 +1,67 @@
+//! The `test_utils` module provides fixtures for the tests of this crate and, with the
+//! `test_utils` feature, of the crates that depend on it.
+
+use crate::{
+    evaluation::Evaluator,
+    metrics::unigram_metrics::key_costs,
+    ngram_mapper::on_demand_ngram_mapper::{NgramMapperConfig, OnDemandNgramMapper},
+    ngrams::{Bigrams, Trigrams, Unigrams},
+    results::NormalizationType,
+};
+
+use keyboard_layout::{
+    config::LayoutConfig, keyboard::Keyboard, layout::Layout, layout_generator::LayoutGenerator,
+    neo_layout_generator::NeoLayoutGenerator,
+};
+use std::sync::Arc;
+
+/// The layout config with the given file name in `config/keyboard`
+pub fn layout_config(filename: &str) -> LayoutConfig {
+    let path = format!(
+        "{}/../config/keyboard/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        filename
+    );
+    LayoutConfig::from_yaml(&path).unwrap()
+}
+
+/// The layout generator of a layout config
+pub fn generator(config: LayoutConfig) -> NeoLayoutGenerator {
+    let keyboard = Arc::new(Keyboard::from_yaml_object(config.keyboard));
+    NeoLayoutGenerator::from_object(config.base_layout, keyboard)
+}
+
+/// The base layout of the layout config with the given file name in `config/keyboard`
+pub fn test_layout(filename: &str) -> Layout {
+    generator(layout_config(filename)).generate_base().unwrap()
+}
+
+/// An evaluator of the key costs of a pangram
+pub fn key_cost_evaluator() -> Evaluator {
+    let text = "the quick brown fox jumps over the lazy dog";
+    let config: NgramMapperConfig = serde_yaml::from_str(
+        "{split_modifiers: {enabled: true, same_key_mod_factor: 1.0}, exclude_line_breaks: true}",
+    )
+    .unwrap();
+    let ngram_mapper = OnDemandNgramMapper::with_ngrams(
+        Unigrams::from_text(text).unwrap(),
+        Bigrams::from_text(text).unwrap(),
+        Trigrams::from_text(text).unwrap(),
+        config,
+    );
+    let mut evaluator = Evaluator::default(Box::new(ngram_mapper));
+    evaluator.unigram_metric(
+        Box::new(key_costs::KeyCost::new(&key_costs::Parameters {})),
+        1.0,
+        NormalizationType::WeightAll(1.0),
+    );
+    evaluator
+}
+
+/// The standard layout's generator and base layout string with an evaluator of the key costs
+pub fn setup() -> (Box<dyn LayoutGenerator>, String, Evaluator) {
+    let generator = generator(layout_config("standard.yml"));
+    let layout_str = generator.generate_base().unwrap().as_text();
+
+    (Box::new(generator), layout_str, key_cost_evaluator())
+}
@@ -0,0 +1,166 @@
+//! The `typing_speed` module provides an analytic model that converts the costs of bigram metrics
+//! into typing times and reports an estimated typing speed in words per minute.
+//!
+//! Each bigram (transition between two keystrokes) takes `base_ms` plus `ms_per_cost` for each
+//! unit of (unweighted) cost of the configured bigram metrics. This is the same linear mapping
+//! that the `calibrate` binary uses to derive metric parameters from typing logs. Transitions
+//! that were measured often enough (see `calibrate --timing-matrix`) use the empirical time
+//! instead.
+
+use keyboard_layout::key::MatrixPosition;
+
+use ahash::AHashMap;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs::File};
+
+#[derive(Clone, Deserialize, Debug)]
+#[serde(default)]
+pub struct TypingSpeedParameters {
+    /// Time (in ms) of a transition without any cost, e.g. a hand switch
+    pub base_ms: f64,
+    /// Additional time (in ms) for each unit of bigram cost
+    pub ms_per_cost: f64,
+    /// Names of the bigram metrics whose costs are converted into time
+    pub metrics: Vec<String>,
+    /// Number of characters per word (including the space)
+    pub chars_per_word: f64,
+    /// File with measured transition times (as written by `calibrate --timing-matrix`)
+    pub timing_matrix: Option<String>,
+    /// Only use measured transition times with at least this many samples
+    pub min_samples: usize,
+}
+
+impl Default for TypingSpeedParameters {
+    fn default() -> Self {
+        TypingSpeedParameters {
+            base_ms: 140.0,
+            ms_per_cost: 20.0,
+            metrics: vec![
+                "Cluster Rolls".to_string(),
+                "Scissoring".to_string(),
+                "Movement Pattern".to_string(),
+            ],
+            chars_per_word: 5.0,
+            timing_matrix: None,
+            min_samples: 5,
+        }
+    }
+}
+
+impl TypingSpeedParameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        let params: TypingSpeedParameters = serde_yaml::from_reader(f)?;
+        Ok(params)
+    }
+}
+
+/// Measured time of the transition between two keys.
+#[derive(Clone, Deserialize, Debug)]
+pub struct TransitionTiming {
+    pub from: MatrixPosition,
+    pub to: MatrixPosition,
+    /// Median time (in ms)
+    pub ms: f64,
+    pub samples: usize,
+}
+
+/// The typing speed model, i.e. its parameters and the measured transition times.
+#[derive(Clone, Debug)]
+pub struct TypingSpeedModel {
+    pub params: TypingSpeedParameters,
+    timings: AHashMap<(MatrixPosition, MatrixPosition), f64>,
+}
+
+impl TypingSpeedModel {
+    pub fn new(params: &TypingSpeedParameters) -> Result<Self> {
+        let mut timings = AHashMap::default();
+        if let Some(filename) = &params.timing_matrix {
+            let f = File::open(filename)?;
+            let measured: Vec<TransitionTiming> = serde_yaml::from_reader(f)?;
+            measured
+                .into_iter()
+                .filter(|t| t.samples >= params.min_samples)
+                .for_each(|t| {
+                    timings.insert((t.from, t.to), t.ms);
+                });
+        }
+
+        Ok(Self {
+            params: params.clone(),
+            timings,
+        })
+    }
+
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        Self::new(&TypingSpeedParameters::from_yaml(filename)?)
+    }
+
+    /// Whether the costs of the bigram metric with the given name are converted into time.
+    pub fn uses_metric(&self, metric_name: &str) -> bool {
+        self.params.metrics.iter().any(|m| m == metric_name)
+    }
+
+    /// The measured time of the transition between the given keys (if available).
+    pub fn measured_ms(&self, from: MatrixPosition, to: MatrixPosition) -> Option<f64> {
+        self.timings.get(&(from, to)).cloned()
+    }
+
+    /// The time of a transition with the given (unweighted) bigram cost.
+    pub fn modeled_ms(&self, cost: f64) -> f64 {
+        self.params.base_ms + self.params.ms_per_cost * cost
+    }
+
+    /// Assemble the estimate from the mean time per transition and the number of keystrokes per
+    /// character.
+    pub fn estimate(
+        &self,
+        ms_per_keystroke: f64,
+        keystrokes_per_char: f64,
+        measured_share: f64,
+    ) -> TypingSpeedEstimate {
+        let ms_per_char = ms_per_keystroke * keystrokes_per_char;
+        TypingSpeedEstimate {
+            ms_per_keystroke,
+            keystrokes_per_char,
+            ms_per_char,
+            wpm: 60_000.0 / (ms_per_char * self.params.chars_per_word),
+            measured_share,
+        }
+    }
+}
+
+/// Estimated typing speed of a layout on the corpus.
+#[derive(Clone, Serialize, Debug)]
+pub struct TypingSpeedEstimate {
+    /// Mean time (in ms) between two keystrokes
+    pub ms_per_keystroke: f64,
+    /// Mean number of keystrokes (including modifiers) per character
+    pub keystrokes_per_char: f64,
+    /// Mean time (in ms) per character
+    pub ms_per_char: f64,
+    /// Estimated words per minute
+    pub wpm: f64,
+    /// Share of the transitions (by frequency) with a measured time
+    pub measured_share: f64,
+}
+
+impl fmt::Display for TypingSpeedEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Estimated typing speed: {:.1} WPM ({:.1} ms per keystroke, {:.2} keystrokes per character",
+            self.wpm, self.ms_per_keystroke, self.keystrokes_per_char,
+        )?;
+        if self.measured_share > 0.0 {
+            write!(
+                f,
+                ", {:.1}% of transitions measured",
+                100.0 * self.measured_share
+            )?;
+        }
+
+        write!(f, ")")
+    }
+}
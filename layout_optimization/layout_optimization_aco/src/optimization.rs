@@ -0,0 +1,388 @@
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+
+use layout_optimization_common::{
+    observer::{Budget, Progress, SharedObserver},
+    stats,
+    stopping::{StopReason, StoppingCriteria, StoppingMonitor},
+    swaps::SwapRestrictions,
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use rand::{distributions::WeightedIndex, prelude::*};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{
+    fs::File,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Parameters {
+    /// Number of layouts constructed in each iteration
+    pub ants: usize,
+
+    /// Maximum number of iterations
+    pub iterations: u64,
+
+    /// Exponent of the pheromone level in the probability of choosing an assignment
+    #[serde(default = "default_alpha")]
+    pub alpha: f64,
+
+    /// Fraction of the pheromone evaporating in each iteration
+    pub evaporation: f64,
+
+    /// Lower bound of the pheromone levels relative to their upper bound (keeps all assignments
+    /// possible)
+    #[serde(default = "default_min_pheromone_ratio")]
+    pub min_pheromone_ratio: f64,
+
+    /// Weight of the pheromone deposited for the best layout found so far in each iteration
+    /// (the best layout of the iteration deposits with weight 1)
+    #[serde(default)]
+    pub global_best_weight: f64,
+
+    /// Number of random key swaps tried (and kept if improving) on each constructed layout
+    #[serde(default)]
+    pub local_search_swaps: usize,
+
+    /// Restrictions of the keys that are swapped.
+    #[serde(flatten)]
+    pub swap_restrictions: SwapRestrictions,
+
+    /// Criteria for ending the optimization before `iterations`.
+    #[serde(default)]
+    pub stopping: StoppingCriteria,
+}
+
+fn default_alpha() -> f64 {
+    1.0
+}
+
+fn default_min_pheromone_ratio() -> f64 {
+    0.01
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Parameters {
+            ants: 20,
+            iterations: 500,
+            alpha: default_alpha(),
+            evaporation: 0.05,
+            min_pheromone_ratio: default_min_pheromone_ratio(),
+            global_best_weight: 0.0,
+            local_search_swaps: 0,
+            swap_restrictions: SwapRestrictions::default(),
+            stopping: StoppingCriteria::default(),
+        }
+    }
+}
+
+impl Parameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        Ok(serde_yaml::from_reader(f)?)
+    }
+
+    /// Fit the number of iterations to a wall-clock budget of `seconds` at the given evaluation
+    /// rate. Each ant evaluates its constructed layout and the swaps of its local search.
+    pub fn fit_to_time_budget(&mut self, seconds: f64, evaluations_per_second: f64) {
+        let evaluations_per_iteration = self.ants.max(1) * (1 + self.local_search_swaps);
+        let iterations = seconds * evaluations_per_second / evaluations_per_iteration as f64;
+        self.iterations = (iterations as u64).max(1);
+    }
+}
+
+/// Pheromone levels of the assignments of the permutable keys to positions in the layout
+/// (bounded as in the MAX-MIN Ant System).
+struct Pheromones {
+    levels: Vec<Vec<f64>>,
+    max: f64,
+    min: f64,
+}
+
+impl Pheromones {
+    fn new(n_keys: usize, n_positions: usize, params: &Parameters) -> Self {
+        let max = 1.0 / params.evaporation.max(1e-9);
+        Self {
+            levels: vec![vec![max; n_positions]; n_keys],
+            max,
+            min: max * params.min_pheromone_ratio,
+        }
+    }
+
+    fn evaporate(&mut self, rate: f64) {
+        let min = self.min;
+        self.levels
+            .iter_mut()
+            .flatten()
+            .for_each(|level| *level = (*level * (1.0 - rate)).max(min));
+    }
+
+    fn deposit(&mut self, permutation: &[usize], amount: f64) {
+        let max = self.max;
+        for (key, position) in permutation.iter().enumerate() {
+            let level = &mut self.levels[key][*position];
+            *level = (*level + amount).min(max);
+        }
+    }
+
+    /// Construct a layout by assigning the keys of each group (in random order) to the positions
+    /// of the group with probabilities proportional to the pheromone levels.
+    fn construct(
+        &self,
+        groups: &[Vec<usize>],
+        initial: &[usize],
+        alpha: f64,
+        rng: &mut impl Rng,
+    ) -> Vec<usize> {
+        let mut permutation = initial.to_vec();
+        for group in groups.iter() {
+            let mut free_positions: Vec<usize> = group.iter().map(|key| initial[*key]).collect();
+            let mut keys = group.clone();
+            keys.shuffle(rng);
+            for key in keys {
+                let weights = free_positions
+                    .iter()
+                    .map(|position| self.levels[key][*position].powf(alpha));
+                let chosen = WeightedIndex::new(weights)
+                    .map(|distribution| distribution.sample(rng))
+                    .unwrap_or(0);
+                permutation[key] = free_positions.swap_remove(chosen);
+            }
+        }
+
+        permutation
+    }
+}
+
+/// Performs one run of Ant Colony Optimization, then returns the best layout found.
+#[allow(clippy::too_many_arguments)]
+pub fn optimize(
+    params: &Parameters,
+    layout_str: &str,
+    fixed_characters: &str,
+    layout_generator: &dyn LayoutGenerator,
+    start_with_layout: bool,
+    evaluator: &Evaluator,
+    result_cache: Option<Cache<f64>>,
+    observer: Option<SharedObserver>,
+) -> (String, Layout, StopReason) {
+    let pm = params
+        .swap_restrictions
+        .permutator(layout_generator, layout_str, fixed_characters);
+    let groups = pm.key_groups();
+    let initial = pm.get_permutable_indices();
+    let mut pheromones = Pheromones::new(initial.len(), layout_str.chars().count(), params);
+
+    let evaluations = AtomicU64::new(0);
+    let cost = |permutation: &[usize]| -> f64 {
+        stats::record_evaluation();
+        evaluations.fetch_add(1, Ordering::Relaxed);
+        let layout_string = pm.generate_string(permutation);
+        let evaluate = || {
+            let layout = layout_generator.generate(&layout_string).unwrap();
+            evaluator.evaluate_layout(&layout).total_cost()
+        };
+        match &result_cache {
+            Some(result_cache) => result_cache.get_or_insert_with(&layout_string, evaluate),
+            None => evaluate(),
+        }
+    };
+
+    log::info!("Starting optimization with: {:?}", params);
+    stats::record_start();
+    let start = Instant::now();
+    let budget = Budget::new(params.iterations, &params.stopping);
+    let progress = |step: u64, best_cost: f64| Progress {
+        run: "ant colony".to_string(),
+        step,
+        best_cost,
+        evaluations: evaluations.load(Ordering::Relaxed),
+        elapsed: start.elapsed(),
+        budget: budget.clone(),
+    };
+    let mut stopping_monitor = StoppingMonitor::new(&params.stopping);
+    let mut stop_reason = StopReason::Completed;
+
+    // the start layout gets a head start in the pheromone levels
+    let mut best: Option<(Vec<usize>, f64)> = None;
+    if start_with_layout {
+        pheromones.evaporate(params.evaporation);
+        pheromones.deposit(&initial, 1.0);
+        best = Some((initial.clone(), cost(&initial)));
+    }
+
+    let mut last_iteration = 0;
+    for iteration in 1..=params.iterations {
+        last_iteration = iteration;
+        let ants: Vec<(Vec<usize>, f64)> = (0..params.ants.max(1))
+            .into_par_iter()
+            .map(|_| {
+                let rng = &mut thread_rng();
+                let mut permutation = pheromones.construct(&groups, &initial, params.alpha, rng);
+                let mut permutation_cost = cost(&permutation);
+                for _ in 0..params.local_search_swaps {
+                    let candidate = pm.perform_n_swaps(&permutation, 1);
+                    let candidate_cost = cost(&candidate);
+                    if candidate_cost < permutation_cost {
+                        permutation = candidate;
+                        permutation_cost = candidate_cost;
+                    }
+                }
+                (permutation, permutation_cost)
+            })
+            .collect();
+
+        let (iteration_best, iteration_best_cost) = ants
+            .iter()
+            .min_by(|(_, c1), (_, c2)| c1.partial_cmp(c2).unwrap())
+            .unwrap();
+        let mean_cost = ants.iter().map(|(_, c)| c).sum::<f64>() / ants.len() as f64;
+
+        stats::record_generation(iteration);
+        if best
+            .as_ref()
+            .map_or(true, |(_, best_cost)| iteration_best_cost < best_cost)
+        {
+            best = Some((iteration_best.clone(), *iteration_best_cost));
+            stats::record_cost(*iteration_best_cost);
+            let best_layout_str = pm.generate_string(iteration_best);
+            log::info!(
+                "{} {} ({:.4})",
+                format!("New best in iteration {}:", iteration)
+                    .green()
+                    .bold(),
+                best_layout_str,
+                iteration_best_cost,
+            );
+            if let Some(observer) = &observer {
+                observer
+                    .lock()
+                    .unwrap()
+                    .on_new_best(&progress(iteration, *iteration_best_cost), &best_layout_str);
+            }
+        }
+        let (best_permutation, best_cost) = best.as_ref().unwrap();
+
+        pheromones.evaporate(params.evaporation);
+        pheromones.deposit(iteration_best, 1.0);
+        if params.global_best_weight > 0.0 {
+            pheromones.deposit(best_permutation, params.global_best_weight);
+        }
+
+        log::info!(
+            "{} best: {:.4}, mean: {:.4}, all time best: {:.4}",
+            format!("Iteration {}:", iteration).yellow().bold(),
+            iteration_best_cost,
+            mean_cost,
+            best_cost,
+        );
+        if let Some(observer) = &observer {
+            observer
+                .lock()
+                .unwrap()
+                .on_generation(&progress(iteration, *best_cost));
+        }
+
+        if let Some(reason) =
+            stopping_monitor.check(iteration, *best_cost, evaluations.load(Ordering::Relaxed))
+        {
+            log::info!("Stopped early after iteration {} ({})", iteration, reason);
+            stop_reason = reason;
+            break;
+        }
+    }
+
+    // without any iteration (and without a head start), the start layout is the result
+    let (best_permutation, best_cost) = best.unwrap_or_else(|| {
+        log::warn!("No iterations were performed, returning the start layout");
+        (initial.clone(), cost(&initial))
+    });
+    let best_layout_str = pm.generate_string(&best_permutation);
+    let best_layout = layout_generator.generate(&best_layout_str).unwrap();
+    if let Some(observer) = &observer {
+        observer.lock().unwrap().on_finish(
+            &progress(last_iteration, best_cost),
+            &best_layout_str,
+            stop_reason,
+        );
+    }
+
+    (best_layout_str, best_layout, stop_reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> Parameters {
+        Parameters {
+            evaporation: 0.1,
+            min_pheromone_ratio: 0.1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn pheromones_stay_within_their_bounds() {
+        let mut pheromones = Pheromones::new(3, 3, &params());
+        assert_eq!(pheromones.max, 10.0);
+        assert_eq!(pheromones.min, 1.0);
+
+        pheromones.evaporate(0.1);
+        assert!(pheromones.levels.iter().flatten().all(|l| *l == 9.0));
+
+        // deposits are capped at the upper bound
+        pheromones.deposit(&[2, 0, 1], 5.0);
+        assert_eq!(pheromones.levels[0], vec![9.0, 9.0, 10.0]);
+        assert_eq!(pheromones.levels[1], vec![10.0, 9.0, 9.0]);
+        assert_eq!(pheromones.levels[2], vec![9.0, 10.0, 9.0]);
+
+        // evaporation stops at the lower bound
+        (0..100).for_each(|_| pheromones.evaporate(0.1));
+        assert!(pheromones.levels.iter().flatten().all(|l| *l == 1.0));
+    }
+
+    #[test]
+    fn construction_follows_the_pheromones_within_the_groups() {
+        let mut pheromones = Pheromones::new(4, 4, &params());
+        (0..100).for_each(|_| pheromones.evaporate(0.1));
+        pheromones.deposit(&[1, 0, 3, 2], 9.0);
+
+        // keys 0 and 1 may only exchange their positions, as do keys 2 and 3
+        let groups = vec![vec![0, 1], vec![2, 3]];
+        let rng = &mut thread_rng();
+        let constructed: Vec<Vec<usize>> = (0..200)
+            .map(|_| pheromones.construct(&groups, &[0, 1, 2, 3], 1.0, rng))
+            .collect();
+
+        for permutation in constructed.iter() {
+            let mut first: Vec<usize> = permutation[..2].to_vec();
+            first.sort_unstable();
+            assert_eq!(first, vec![0, 1]);
+            let mut second: Vec<usize> = permutation[2..].to_vec();
+            second.sort_unstable();
+            assert_eq!(second, vec![2, 3]);
+        }
+        // the reinforced assignment is ten times as likely as the other one of a group
+        let reinforced = constructed.iter().filter(|p| p[0] == 1).count();
+        assert!(reinforced > 150, "{} of 200", reinforced);
+    }
+
+    #[test]
+    fn time_budget_sets_the_iterations() {
+        let mut params = Parameters {
+            ants: 10,
+            local_search_swaps: 4,
+            ..Default::default()
+        };
+        params.fit_to_time_budget(10.0, 100.0);
+        assert_eq!(params.iterations, 20);
+        params.fit_to_time_budget(0.0, 100.0);
+        assert_eq!(params.iterations, 1);
+    }
+}
@@ -0,0 +1,310 @@
+//! Soft constraints: pinned keys (`fix`) and swap groups that the search may violate.
+//!
+//! Hard constraints restrict the moves of an optimizer, which can cut the search space into
+//! regions that are only connected through layouts violating them. Declared as soft, the
+//! constraints are instead enforced by a penalty per violated constraint that is added to the
+//! cost of a layout and grows over the course of the optimization (penalty annealing). Early on,
+//! the search can traverse infeasible layouts. Towards the end, violations become too expensive
+//! to keep. Any violations left in the final layout are repaired, so that the result satisfies
+//! all constraints exactly.
+
+use crate::LayoutPermutator;
+
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PenaltyGrowth {
+    /// The penalty grows proportionally to the progress of the optimization
+    Linear,
+    /// The penalty grows with the square of the progress (stays low for longer)
+    Quadratic,
+}
+
+impl Default for PenaltyGrowth {
+    fn default() -> Self {
+        PenaltyGrowth::Linear
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SoftConstraintParameters {
+    /// Keep these symbols in place like fixed keys, but allow the search to move them
+    #[serde(default)]
+    pub fix: String,
+
+    /// Allow swaps across the swap groups (`same_layer_swaps` and `swap_groups`) during the
+    /// search, but penalize keys outside of their group's positions
+    #[serde(default)]
+    pub swap_groups: bool,
+
+    /// Penalty per violated constraint at the start of the optimization
+    #[serde(default)]
+    pub initial_penalty: f64,
+
+    /// Penalty per violated constraint at the end of the optimization
+    #[serde(default = "default_final_penalty")]
+    pub final_penalty: f64,
+
+    /// How the penalty grows from the initial to the final penalty
+    #[serde(default)]
+    pub growth: PenaltyGrowth,
+}
+
+fn default_final_penalty() -> f64 {
+    100.0
+}
+
+impl Default for SoftConstraintParameters {
+    fn default() -> Self {
+        SoftConstraintParameters {
+            fix: String::new(),
+            swap_groups: false,
+            initial_penalty: 0.0,
+            final_penalty: default_final_penalty(),
+            growth: PenaltyGrowth::default(),
+        }
+    }
+}
+
+impl SoftConstraintParameters {
+    /// The penalty per violated constraint at the given progress (from 0 to 1) of the
+    /// optimization.
+    pub fn penalty(&self, progress: f64) -> f64 {
+        let progress = progress.clamp(0.0, 1.0);
+        let growth = match self.growth {
+            PenaltyGrowth::Linear => progress,
+            PenaltyGrowth::Quadratic => progress * progress,
+        };
+
+        self.initial_penalty + (self.final_penalty - self.initial_penalty) * growth
+    }
+}
+
+/// The soft constraints of an optimization in terms of the permutations of a
+/// [`LayoutPermutator`]. Each key (index into the permutation) belongs to a class of positions
+/// (indices into the layout string) it has to end up at: its own position for softly pinned keys,
+/// the positions of its swap group otherwise. Clones share the progress of the optimization.
+#[derive(Clone, Debug)]
+pub struct SoftConstraints {
+    params: SoftConstraintParameters,
+    /// Class of each key
+    key_classes: Vec<usize>,
+    /// Class of each position (`None` for fixed keys)
+    position_classes: Vec<Option<usize>>,
+    /// Progress of the optimization (bits of an `f64` from 0 to 1)
+    progress: Arc<AtomicU64>,
+}
+
+impl SoftConstraints {
+    /// Soft constraints keeping the keys of `grouped` within the positions of their swap groups
+    /// and the symbols of `params.fix` at their positions. Without soft swap groups, the
+    /// permutator of the search keeps the keys within their groups anyway.
+    pub fn new(params: &SoftConstraintParameters, grouped: &LayoutPermutator) -> Self {
+        let n_keys = grouped.perm_keys.len();
+        let n_positions = grouped.perm_keys.len() + grouped.fixed_keys.len();
+
+        // keys that are not in any group (of at least two keys) can not move anyway
+        let mut key_classes: Vec<usize> = (0..n_keys).collect();
+        for group in grouped.key_groups() {
+            // pinned keys form classes of their own
+            let unpinned: Vec<usize> = group
+                .into_iter()
+                .filter(|j| !params.fix.contains(grouped.perm_keys[*j]))
+                .collect();
+            if let Some(class) = unpinned.first().copied() {
+                unpinned.iter().for_each(|j| key_classes[*j] = class);
+            }
+        }
+
+        let mut position_classes = vec![None; n_positions];
+        for (j, pos) in grouped.perm_indices.iter().enumerate() {
+            position_classes[*pos] = Some(key_classes[j]);
+        }
+
+        Self {
+            params: params.clone(),
+            key_classes,
+            position_classes,
+            progress: Arc::new(AtomicU64::new(0f64.to_bits())),
+        }
+    }
+
+    /// Set the progress of the optimization (from 0 to 1), which determines the penalty.
+    pub fn set_progress(&self, progress: f64) {
+        self.progress.store(progress.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The current penalty per violated constraint.
+    pub fn penalty(&self) -> f64 {
+        self.params
+            .penalty(f64::from_bits(self.progress.load(Ordering::Relaxed)))
+    }
+
+    /// Number of keys outside of the positions of their class.
+    pub fn violations(&self, permutation: &[usize]) -> usize {
+        permutation
+            .iter()
+            .zip(self.key_classes.iter())
+            .filter(|(pos, class)| self.position_classes[**pos] != Some(**class))
+            .count()
+    }
+
+    /// The cost of a layout plus the current penalty for its violations.
+    pub fn penalized(&self, cost: f64, permutation: &[usize]) -> f64 {
+        match self.violations(permutation) {
+            0 => cost,
+            violations => cost + self.penalty() * violations as f64,
+        }
+    }
+
+    /// Move all keys outside of the positions of their class to the free positions of their
+    /// class (keys within their class stay in place).
+    pub fn repair(&self, permutation: &[usize]) -> Vec<usize> {
+        let mut repaired = permutation.to_vec();
+        let mut occupied = vec![false; self.position_classes.len()];
+        let mut misplaced = Vec::new();
+        for (j, pos) in permutation.iter().enumerate() {
+            if self.position_classes[*pos] == Some(self.key_classes[j]) {
+                occupied[*pos] = true;
+            } else {
+                misplaced.push(j);
+            }
+        }
+        for j in misplaced {
+            let class = Some(self.key_classes[j]);
+            let free = (0..self.position_classes.len())
+                .find(|pos| !occupied[*pos] && self.position_classes[*pos] == class)
+                .expect("each class has as many positions as keys");
+            occupied[free] = true;
+            repaired[j] = free;
+        }
+
+        repaired
+    }
+
+    /// Repair the candidates and return the index of the one with the lowest cost after the
+    /// repair together with its repaired permutation and cost.
+    pub fn best_repaired(
+        &self,
+        candidates: &[&[usize]],
+        cost: impl Fn(&[usize]) -> f64,
+    ) -> (usize, Vec<usize>, f64) {
+        candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| {
+                let repaired = self.repair(candidate);
+                let repaired_cost = cost(&repaired);
+                (i, repaired, repaired_cost)
+            })
+            .min_by(|(_, _, c1), (_, _, c2)| c1.partial_cmp(c2).unwrap())
+            .expect("at least one candidate")
+    }
+}
+
+/// The permutator for searching a layout with the given (hard) constraints, together with the
+/// soft constraints (if any). Softly pinned symbols are removed from the fixed characters, and
+/// soft swap groups are not applied to the permutator.
+pub fn constrained_permutator(
+    layout_str: &str,
+    fixed_characters: &str,
+    layer_size: Option<usize>,
+    swap_groups: &[String],
+    soft_constraints: Option<&SoftConstraintParameters>,
+) -> (LayoutPermutator, Option<SoftConstraints>) {
+    let soft_constraints = match soft_constraints {
+        Some(params) => params,
+        None => {
+            let pm = LayoutPermutator::new(layout_str, fixed_characters)
+                .with_swap_groups(layer_size, swap_groups);
+            return (pm, None);
+        }
+    };
+
+    let fixed_characters: String = fixed_characters
+        .chars()
+        .filter(|c| !soft_constraints.fix.contains(*c))
+        .collect();
+    let pm = LayoutPermutator::new(layout_str, &fixed_characters);
+    let grouped = pm.clone().with_swap_groups(layer_size, swap_groups);
+    let constraints = SoftConstraints::new(soft_constraints, &grouped);
+    match soft_constraints.swap_groups {
+        true => (pm, Some(constraints)),
+        false => (grouped, Some(constraints)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn soft_constraints() -> (LayoutPermutator, SoftConstraints) {
+        let params = SoftConstraintParameters {
+            fix: "a".to_string(),
+            swap_groups: true,
+            initial_penalty: 1.0,
+            final_penalty: 10.0,
+            growth: PenaltyGrowth::Linear,
+        };
+        let swap_groups = vec!["abc".to_string(), "def".to_string()];
+        let (pm, constraints) =
+            constrained_permutator("abcdefx", "ax", None, &swap_groups, Some(&params));
+
+        (pm, constraints.unwrap())
+    }
+
+    #[test]
+    fn penalizes_pinned_keys_and_keys_outside_of_their_swap_group() {
+        let (pm, constraints) = soft_constraints();
+
+        // the search may move softly pinned keys and swap keys across the soft swap groups
+        assert_eq!(pm.get_permutable_indices(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(pm.key_groups(), vec![vec![0, 1, 2, 3, 4, 5]]);
+
+        let valid = pm.permutation_for("acbdfex").unwrap();
+        assert_eq!(constraints.violations(&valid), 0);
+        assert_eq!(constraints.penalized(5.0, &valid), 5.0);
+
+        let invalid = pm.permutation_for("dbcaefx").unwrap();
+        assert_eq!(constraints.violations(&invalid), 2);
+        assert_eq!(constraints.penalized(5.0, &invalid), 7.0);
+
+        constraints.set_progress(0.5);
+        assert_eq!(constraints.penalized(5.0, &invalid), 16.0);
+    }
+
+    #[test]
+    fn repair_moves_only_misplaced_keys_back_into_their_class() {
+        let (pm, constraints) = soft_constraints();
+
+        let repaired = constraints.repair(&pm.permutation_for("dbcaefx").unwrap());
+        assert_eq!(pm.generate_string(&repaired), "abcdefx");
+
+        // the swaps within the groups are kept, the pinned key returns to its position
+        let repaired = constraints.repair(&pm.permutation_for("bacdfex").unwrap());
+        assert_eq!(pm.generate_string(&repaired), "abcdfex");
+        assert_eq!(constraints.violations(&repaired), 0);
+    }
+
+    #[test]
+    fn picks_the_best_candidate_after_the_repair() {
+        let (pm, constraints) = soft_constraints();
+
+        let first = pm.permutation_for("cbadefx").unwrap();
+        let second = pm.permutation_for("acbedfx").unwrap();
+        let candidates = [first.as_slice(), second.as_slice()];
+        // prefer layouts with "e" in front
+        let cost =
+            |permutation: &[usize]| pm.generate_string(permutation).find('e').unwrap() as f64;
+        let (best, repaired, repaired_cost) = constraints.best_repaired(&candidates, cost);
+
+        assert_eq!(best, 1);
+        assert_eq!(pm.generate_string(&repaired), "acbedfx");
+        assert_eq!(repaired_cost, 3.0);
+    }
+}
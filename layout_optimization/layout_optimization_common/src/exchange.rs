@@ -0,0 +1,215 @@
+//! Sharing of good layouts between optimizations running at the same time (e.g. the genetic
+//! algorithm and simulated annealing of a portfolio), so that each can continue from the
+//! progress of the others.
+
+use crate::observer::{OptimizationObserver, Progress};
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A layout offered to the exchange.
+#[derive(Clone, Debug)]
+pub struct SharedLayout {
+    /// Name of the participant that found the layout
+    pub source: String,
+    pub layout: String,
+    pub cost: f64,
+    /// Increases with every improvement offered to the exchange
+    version: u64,
+}
+
+#[derive(Default, Debug)]
+struct Pool {
+    /// Best layout of each participant
+    bests: Vec<SharedLayout>,
+    version: u64,
+}
+
+/// The best layouts of all participants. Clones share the same layouts.
+#[derive(Clone, Default, Debug)]
+pub struct SolutionExchange {
+    pool: Arc<Mutex<Pool>>,
+}
+
+impl SolutionExchange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A new participant that receives the layouts of the others at most once per `interval`.
+    pub fn participant(&self, name: &str, interval: Duration) -> Participant {
+        Participant {
+            exchange: self.clone(),
+            name: name.to_string(),
+            interval,
+            last_received: None,
+            seen_version: 0,
+        }
+    }
+
+    /// The best layout of all participants.
+    pub fn best(&self) -> Option<SharedLayout> {
+        self.best_where(|_| true)
+    }
+
+    /// The best layout of each participant.
+    pub fn bests(&self) -> Vec<SharedLayout> {
+        self.pool.lock().unwrap().bests.clone()
+    }
+
+    fn best_where(&self, filter: impl Fn(&SharedLayout) -> bool) -> Option<SharedLayout> {
+        self.pool
+            .lock()
+            .unwrap()
+            .bests
+            .iter()
+            .filter(|l| filter(l))
+            .min_by(|l1, l2| l1.cost.partial_cmp(&l2.cost).unwrap())
+            .cloned()
+    }
+
+    fn offer(&self, source: &str, layout: &str, cost: f64) {
+        let mut pool = self.pool.lock().unwrap();
+        let version = pool.version + 1;
+        match pool.bests.iter_mut().find(|l| l.source == source) {
+            Some(best) if best.cost <= cost => return,
+            Some(best) => {
+                best.layout = layout.to_string();
+                best.cost = cost;
+                best.version = version;
+            }
+            None => pool.bests.push(SharedLayout {
+                source: source.to_string(),
+                layout: layout.to_string(),
+                cost,
+                version,
+            }),
+        }
+        pool.version = version;
+    }
+}
+
+/// An optimization taking part in a [`SolutionExchange`]. Its new best layouts are offered to the
+/// exchange by [`Participant::offer`] or by using it as an [`OptimizationObserver`].
+#[derive(Clone, Debug)]
+pub struct Participant {
+    exchange: SolutionExchange,
+    name: String,
+    interval: Duration,
+    last_received: Option<Instant>,
+    seen_version: u64,
+}
+
+impl Participant {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Offer a layout, which replaces the participant's shared layout if it has a lower cost.
+    pub fn offer(&self, layout: &str, cost: f64) {
+        self.exchange.offer(&self.name, layout, cost);
+    }
+
+    /// The best layout of the other participants.
+    pub fn best_of_others(&self) -> Option<SharedLayout> {
+        self.exchange.best_where(|l| l.source != self.name)
+    }
+
+    /// The best layout of the other participants if it was not received before and the interval
+    /// since the last received layout has passed.
+    pub fn receive(&mut self) -> Option<SharedLayout> {
+        if self
+            .last_received
+            .map_or(false, |last| last.elapsed() < self.interval)
+        {
+            return None;
+        }
+
+        let best = self
+            .best_of_others()
+            .filter(|l| l.version > self.seen_version)?;
+        self.seen_version = best.version;
+        self.last_received = Some(Instant::now());
+        Some(best)
+    }
+}
+
+impl OptimizationObserver for Participant {
+    fn on_new_best(&mut self, progress: &Progress, layout: &str) {
+        self.offer(layout, progress.best_cost);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::Budget;
+
+    #[test]
+    fn keeps_the_best_layout_of_each_participant() {
+        let exchange = SolutionExchange::new();
+        let ga = exchange.participant("genetic", Duration::ZERO);
+        let sa = exchange.participant("annealing", Duration::ZERO);
+
+        ga.offer("abc", 3.0);
+        ga.offer("bca", 4.0);
+        sa.offer("cab", 2.0);
+        assert_eq!(exchange.bests().len(), 2);
+        assert_eq!(ga.best_of_others().unwrap().layout, "cab");
+        assert_eq!(sa.best_of_others().unwrap().layout, "abc");
+
+        ga.offer("acb", 1.0);
+        let best = exchange.best().unwrap();
+        assert_eq!(
+            (best.source.as_str(), best.layout.as_str()),
+            ("genetic", "acb")
+        );
+    }
+
+    #[test]
+    fn receives_each_improvement_of_the_others_once() {
+        let exchange = SolutionExchange::new();
+        let mut ga = exchange.participant("genetic", Duration::ZERO);
+        let sa = exchange.participant("annealing", Duration::ZERO);
+
+        // own layouts are not received
+        ga.offer("abc", 3.0);
+        assert!(ga.receive().is_none());
+
+        sa.offer("cab", 2.0);
+        assert_eq!(ga.receive().unwrap().layout, "cab");
+        assert!(ga.receive().is_none());
+
+        // worse layouts do not replace the shared one
+        sa.offer("bca", 2.5);
+        assert!(ga.receive().is_none());
+        sa.offer("bac", 1.5);
+        assert_eq!(ga.receive().unwrap().layout, "bac");
+    }
+
+    #[test]
+    fn receives_at_most_once_per_interval() {
+        let exchange = SolutionExchange::new();
+        let mut ga = exchange.participant("genetic", Duration::from_secs(3600));
+        let mut sa = exchange.participant("annealing", Duration::ZERO);
+
+        sa.offer("cab", 2.0);
+        assert!(ga.receive().is_some());
+        sa.offer("bac", 1.5);
+        assert!(ga.receive().is_none());
+
+        // observed new bests are offered
+        let progress = Progress {
+            run: "annealing".to_string(),
+            step: 1,
+            best_cost: 1.0,
+            evaluations: 1,
+            elapsed: Duration::ZERO,
+            budget: Budget::new(1, &Default::default()),
+        };
+        sa.on_new_best(&progress, "acb");
+        assert_eq!(exchange.best().unwrap().layout, "acb");
+    }
+}
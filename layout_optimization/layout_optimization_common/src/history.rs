@@ -0,0 +1,117 @@
+//! The convergence history of an optimization run, i.e. the best and mean costs (and the metric
+//! costs of the best layout) at regular steps, e.g. for convergence plots and comparisons of
+//! optimizer settings.
+
+use crate::stopping::StopReason;
+use layout_evaluation::results::EvaluationResult;
+
+use serde::Serialize;
+use std::{fs, io};
+
+/// The state of an optimization at one step.
+#[derive(Serialize, Clone, Debug)]
+pub struct HistoryEntry {
+    /// Generation (genetic algorithm) or iteration (simulated annealing)
+    pub step: u64,
+    /// Cost of the best layout found so far
+    pub best_cost: f64,
+    /// Mean cost of the population (genetic algorithm) or of the layouts visited since the
+    /// previous entry (simulated annealing)
+    pub mean_cost: f64,
+    /// Weighted costs of the individual metrics for the best layout found so far
+    pub metric_costs: Vec<(String, f64)>,
+}
+
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct ConvergenceHistory {
+    /// Label of the optimization run
+    pub run: String,
+    pub entries: Vec<HistoryEntry>,
+    /// Why the optimization ended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_reason: Option<StopReason>,
+}
+
+impl ConvergenceHistory {
+    pub fn new(run: &str) -> Self {
+        Self {
+            run: run.to_string(),
+            entries: Vec::new(),
+            stop_reason: None,
+        }
+    }
+
+    /// Record the state at the given step with the evaluation of the best layout found so far.
+    pub fn record(&mut self, step: u64, best: &EvaluationResult, mean_cost: f64) {
+        let metric_costs = best.metric_costs();
+
+        self.entries.push(HistoryEntry {
+            step,
+            best_cost: best.total_cost(),
+            mean_cost,
+            metric_costs,
+        });
+    }
+
+    /// The step of the latest entry.
+    pub fn last_step(&self) -> Option<u64> {
+        self.entries.last().map(|e| e.step)
+    }
+}
+
+/// The histories of several runs as CSV with one column per metric.
+pub fn to_csv(histories: &[ConvergenceHistory]) -> String {
+    let mut metric_names: Vec<&str> = Vec::new();
+    for (name, _) in histories
+        .iter()
+        .flat_map(|h| h.entries.iter())
+        .flat_map(|e| e.metric_costs.iter())
+    {
+        if !metric_names.contains(&name.as_str()) {
+            metric_names.push(name);
+        }
+    }
+
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    let mut header = vec![
+        "run".to_string(),
+        "step".to_string(),
+        "best_cost".to_string(),
+        "mean_cost".to_string(),
+    ];
+    header.extend(metric_names.iter().map(|name| quote(name)));
+
+    let mut lines = vec![header.join(",")];
+    for history in histories.iter() {
+        for entry in history.entries.iter() {
+            let mut fields = vec![
+                quote(&history.run),
+                entry.step.to_string(),
+                entry.best_cost.to_string(),
+                entry.mean_cost.to_string(),
+            ];
+            fields.extend(metric_names.iter().map(|name| {
+                entry
+                    .metric_costs
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map_or(String::new(), |(_, cost)| cost.to_string())
+            }));
+            lines.push(fields.join(","));
+        }
+    }
+
+    format!("{}\n", lines.join("\n"))
+}
+
+/// Write the histories of several runs to a file, as JSON if its name ends with `.json` and as
+/// CSV otherwise.
+pub fn write(histories: &[ConvergenceHistory], filename: &str) -> io::Result<()> {
+    let content = if filename.ends_with(".json") {
+        serde_json::to_string_pretty(histories)?
+    } else {
+        to_csv(histories)
+    };
+
+    fs::write(filename, content)
+}
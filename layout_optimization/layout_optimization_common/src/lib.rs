@@ -1,4 +1,13 @@
-use rand::{seq::SliceRandom, thread_rng};
+pub mod constraints;
+pub mod exchange;
+pub mod history;
+pub mod observer;
+pub mod stats;
+pub mod stopping;
+pub mod swaps;
+
+use ahash::AHashMap;
+use rand::{seq::SliceRandom, thread_rng, Rng};
 
 #[derive(Clone, Debug)]
 pub struct LayoutPermutator {
@@ -6,6 +15,7 @@ pub struct LayoutPermutator {
     perm_indices: Vec<usize>,
     fixed_keys: Vec<char>,
     fixed_indices: Vec<usize>,
+    swap_groups: Vec<Vec<usize>>,
 }
 
 impl LayoutPermutator {
@@ -29,7 +39,41 @@ impl LayoutPermutator {
             perm_indices,
             fixed_keys,
             fixed_indices,
+            swap_groups: Vec::new(),
+        }
+    }
+
+    /// Restrict swaps (and random layouts) to keys of the same group. Keys are grouped by the
+    /// segment of the layout string they start in if `layer_size` is given (see
+    /// `GroupedLayoutGenerator`) and by the given symbol groups. Symbols that are not contained
+    /// in any of the symbol groups form a group of their own.
+    pub fn with_swap_groups(mut self, layer_size: Option<usize>, symbol_groups: &[String]) -> Self {
+        if layer_size.is_none() && symbol_groups.is_empty() {
+            self.swap_groups = Vec::new();
+            return self;
         }
+
+        let mut groups: AHashMap<(usize, usize), Vec<usize>> = AHashMap::default();
+        self.perm_keys
+            .iter()
+            .zip(self.perm_indices.iter())
+            .enumerate()
+            .for_each(|(j, (c, pos))| {
+                let layer = layer_size.map_or(0, |size| pos / size.max(1));
+                let symbol_group = symbol_groups
+                    .iter()
+                    .position(|g| g.contains(*c))
+                    .unwrap_or(symbol_groups.len());
+                groups.entry((layer, symbol_group)).or_default().push(j);
+            });
+
+        // groups with a single key can not be permutated anyway
+        self.swap_groups = groups
+            .into_iter()
+            .map(|(_, g)| g)
+            .filter(|g| g.len() > 1)
+            .collect();
+        self
     }
 
     pub fn generate_string(&self, permutation: &[usize]) -> String {
@@ -50,7 +94,20 @@ impl LayoutPermutator {
 
     pub fn generate_random(&self) -> Vec<usize> {
         let mut indices: Vec<usize> = self.perm_indices.to_vec();
-        indices.shuffle(&mut thread_rng());
+        let rng = &mut thread_rng();
+        if self.swap_groups.is_empty() {
+            indices.shuffle(rng);
+        } else {
+            // only shuffle within the swap groups
+            for group in self.swap_groups.iter() {
+                let mut group_indices: Vec<usize> = group.iter().map(|j| indices[*j]).collect();
+                group_indices.shuffle(rng);
+                group
+                    .iter()
+                    .zip(group_indices)
+                    .for_each(|(j, i)| indices[*j] = i);
+            }
+        }
 
         indices
     }
@@ -64,7 +121,16 @@ impl LayoutPermutator {
 
         // Perform nr_switches switches
         for _ in 0..nr_switches {
-            let mut sw = vec.choose_multiple(rng, 2);
+            // if swap groups are given, choose a group (proportionally to its size) to swap keys in
+            let candidates = if self.swap_groups.is_empty() {
+                &vec
+            } else {
+                match self.swap_groups.choose_weighted(rng, |g| g.len()) {
+                    Ok(group) => group,
+                    Err(_) => break,
+                }
+            };
+            let mut sw = candidates.choose_multiple(rng, 2);
             let sw0 = sw.next().unwrap();
             let sw1 = sw.next().unwrap();
             indices.swap(*sw0, *sw1);
@@ -73,6 +139,112 @@ impl LayoutPermutator {
         indices
     }
 
+    /// Choose the keys (as indices into the permutation) to perform a move on: all keys or, if
+    /// swap groups are given, a group with at least `min_size` keys (proportionally to its size).
+    fn choose_candidates<'a>(&'a self, all: &'a [usize], min_size: usize) -> Option<&'a [usize]> {
+        if self.swap_groups.is_empty() {
+            return (all.len() >= min_size).then(|| all);
+        }
+
+        let groups: Vec<&Vec<usize>> = self
+            .swap_groups
+            .iter()
+            .filter(|g| g.len() >= min_size)
+            .collect();
+        groups
+            .choose_weighted(&mut thread_rng(), |g| g.len())
+            .ok()
+            .map(|g| g.as_slice())
+    }
+
+    /// Rotates the keys at three random positions [nr_rotations] times.
+    pub fn perform_n_rotations(&self, permutation: &[usize], nr_rotations: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = permutation.to_vec();
+        let all: Vec<usize> = (0..permutation.len()).collect();
+        let rng = &mut thread_rng();
+
+        for _ in 0..nr_rotations {
+            let candidates = match self.choose_candidates(&all, 3) {
+                Some(candidates) => candidates,
+                None => break,
+            };
+            let rotated: Vec<&usize> = candidates.choose_multiple(rng, 3).collect();
+            let first = indices[*rotated[0]];
+            indices[*rotated[0]] = indices[*rotated[1]];
+            indices[*rotated[1]] = indices[*rotated[2]];
+            indices[*rotated[2]] = first;
+        }
+
+        indices
+    }
+
+    /// The given keys (as indices into the permutation) ordered by their current positions in the
+    /// layout string, i.e. from left to right and top to bottom. Consecutive keys of this order
+    /// are neighbors on the keyboard (apart from fixed keys in between).
+    fn in_layout_order(indices: &[usize], candidates: &[usize]) -> Vec<usize> {
+        let mut ordered = candidates.to_vec();
+        ordered.sort_by_key(|j| indices[*j]);
+        ordered
+    }
+
+    /// Reverses the order of the keys in a random segment (of at least two and at most
+    /// [max_length] keys at consecutive positions of the layout) [nr_reversals] times.
+    pub fn perform_n_reversals(
+        &self,
+        permutation: &[usize],
+        nr_reversals: usize,
+        max_length: usize,
+    ) -> Vec<usize> {
+        let mut indices: Vec<usize> = permutation.to_vec();
+        let all: Vec<usize> = (0..permutation.len()).collect();
+        let rng = &mut thread_rng();
+
+        for _ in 0..nr_reversals {
+            let candidates = match self.choose_candidates(&all, 2) {
+                Some(candidates) => candidates,
+                None => break,
+            };
+            let candidates = Self::in_layout_order(&indices, candidates);
+            let length = rng.gen_range(2..=max_length.clamp(2, candidates.len()));
+            let start = rng.gen_range(0..=candidates.len() - length);
+            let segment = &candidates[start..start + length];
+            for k in 0..length / 2 {
+                indices.swap(segment[k], segment[length - 1 - k]);
+            }
+        }
+
+        indices
+    }
+
+    /// Swaps the keys of two random non-overlapping segments of the same length (at most
+    /// [max_length] keys at consecutive positions of the layout) [nr_swaps] times.
+    pub fn perform_n_block_swaps(
+        &self,
+        permutation: &[usize],
+        nr_swaps: usize,
+        max_length: usize,
+    ) -> Vec<usize> {
+        let mut indices: Vec<usize> = permutation.to_vec();
+        let all: Vec<usize> = (0..permutation.len()).collect();
+        let rng = &mut thread_rng();
+
+        for _ in 0..nr_swaps {
+            let candidates = match self.choose_candidates(&all, 2) {
+                Some(candidates) => candidates,
+                None => break,
+            };
+            let candidates = Self::in_layout_order(&indices, candidates);
+            let length = rng.gen_range(1..=max_length.clamp(1, candidates.len() / 2));
+            let start1 = rng.gen_range(0..=candidates.len() - 2 * length);
+            let start2 = rng.gen_range(start1 + length..=candidates.len() - length);
+            for k in 0..length {
+                indices.swap(candidates[start1 + k], candidates[start2 + k]);
+            }
+        }
+
+        indices
+    }
+
     pub fn switch_n_keys(&self, permutation: &[usize], n_keys: usize) -> Vec<usize> {
         let mut indices: Vec<usize> = permutation.to_vec();
         let rng = &mut thread_rng();
@@ -83,22 +255,321 @@ impl LayoutPermutator {
         sw_to.shuffle(rng);
 
         // Perform nr_switches switches
-        for (from, to) in sw_from.into_iter().zip(sw_to.into_iter()) {
+        for (from, to) in sw_from.into_iter().zip(sw_to) {
             indices[*to] = permutation[*from];
         }
 
         indices
     }
 
+    /// All pairs of keys (as indices into the permutation) that may be swapped, i.e. all pairs
+    /// or, if swap groups are given, all pairs within the same group.
+    pub fn swap_pairs(&self) -> Vec<(usize, usize)> {
+        let pairs_of = |keys: &[usize]| -> Vec<(usize, usize)> {
+            keys.iter()
+                .enumerate()
+                .flat_map(|(n, i)| keys[n + 1..].iter().map(move |j| (*i, *j)))
+                .collect()
+        };
+
+        if self.swap_groups.is_empty() {
+            let keys: Vec<usize> = (0..self.perm_keys.len()).collect();
+            pairs_of(&keys)
+        } else {
+            self.swap_groups.iter().flat_map(|g| pairs_of(g)).collect()
+        }
+    }
+
+    /// The groups of keys (as indices into the permutation) that may exchange their positions:
+    /// the swap groups or, if none are given, all keys.
+    pub fn key_groups(&self) -> Vec<Vec<usize>> {
+        if self.swap_groups.is_empty() {
+            vec![(0..self.perm_keys.len()).collect()]
+        } else {
+            self.swap_groups.clone()
+        }
+    }
+
+    /// A permutator that does not move the given keys (as indices into the permutation) in
+    /// swaps. Returns `None` if no pair of keys could be swapped anymore.
+    pub fn without_keys(&self, keys: &[usize]) -> Option<Self> {
+        let swap_groups: Vec<Vec<usize>> = self
+            .key_groups()
+            .into_iter()
+            .map(|g| g.into_iter().filter(|j| !keys.contains(j)).collect())
+            .filter(|g: &Vec<usize>| g.len() > 1)
+            .collect();
+        if swap_groups.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            swap_groups,
+            ..self.clone()
+        })
+    }
+
     pub fn get_permutable_indices(&self) -> Vec<usize> {
         self.perm_indices.clone()
     }
+
+    /// Find the permutation that generates the given layout string. Returns `None` if the
+    /// layout string is not a permutation of the permutable keys (with fixed keys in place).
+    pub fn permutation_for(&self, layout: &str) -> Option<Vec<usize>> {
+        let chars: Vec<char> = layout.chars().collect();
+        if chars.len() != self.fixed_keys.len() + self.perm_keys.len() {
+            return None;
+        }
+        if self
+            .fixed_indices
+            .iter()
+            .zip(self.fixed_keys.iter())
+            .any(|(i, c)| chars[*i] != *c)
+        {
+            return None;
+        }
+
+        let mut used = vec![false; chars.len()];
+        self.perm_keys
+            .iter()
+            .map(|c| {
+                let idx = self
+                    .perm_indices
+                    .iter()
+                    .find(|i| !used[**i] && chars[**i] == *c)
+                    .copied()?;
+                used[idx] = true;
+
+                Some(idx)
+            })
+            .collect()
+    }
+}
+
+/// Maps mirror-equivalent layout strings onto a canonical representative (the lexicographically
+/// smaller one). This is only meaningful if the evaluation metrics are symmetric wrt. both hands.
+///
+/// The optimizers only canonicalize the layout strings they evaluate (and cache), not their
+/// search state, so a layout and its mirror image are both still visited. The saving lies in the
+/// result cache, which holds one entry for both.
+#[derive(Clone, Debug)]
+pub struct MirrorCanonicalizer {
+    mirror_indices: Vec<usize>,
+}
+
+impl MirrorCanonicalizer {
+    /// Generate a [`MirrorCanonicalizer`] from the mirror image's index of each position in the
+    /// layout string (see [`keyboard_layout::layout::Layout::mirror_indices`]).
+    pub fn new(mirror_indices: Vec<usize>) -> Self {
+        Self { mirror_indices }
+    }
+
+    /// Mirror the layout string. Strings of unexpected length are returned unchanged.
+    pub fn mirror(&self, layout: &str) -> String {
+        let chars: Vec<char> = layout.chars().collect();
+        if chars.len() != self.mirror_indices.len() {
+            return layout.to_string();
+        }
+
+        self.mirror_indices.iter().map(|i| chars[*i]).collect()
+    }
+
+    /// Return the canonical representative of the layout string and its mirror image.
+    pub fn canonicalize(&self, layout: &str) -> String {
+        let mirrored = self.mirror(layout);
+        if mirrored.as_str() < layout {
+            mirrored
+        } else {
+            layout.to_string()
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    const LAYOUT: &str = "abcdefghij";
+    const FIXED: &str = "ce";
+
+    /// Check that the layout string of the permutation contains the symbols of `LAYOUT` with the
+    /// fixed ones at their positions.
+    fn assert_valid(pm: &LayoutPermutator, permutation: &[usize]) {
+        let layout = pm.generate_string(permutation);
+        let mut symbols: Vec<char> = layout.chars().collect();
+        symbols.sort_unstable();
+        assert_eq!(symbols.into_iter().collect::<String>(), LAYOUT);
+        for c in FIXED.chars() {
+            assert_eq!(layout.find(c), LAYOUT.find(c), "{} moved in {}", c, layout);
+        }
+    }
+
+    /// Check that no symbol left the positions of its group in `LAYOUT`.
+    fn assert_within_groups(pm: &LayoutPermutator, permutation: &[usize], groups: &[&str]) {
+        let layout = pm.generate_string(permutation);
+        for group in groups {
+            let positions: Vec<usize> = group.chars().map(|c| LAYOUT.find(c).unwrap()).collect();
+            for c in group.chars() {
+                assert!(
+                    positions.contains(&layout.find(c).unwrap()),
+                    "{} left its group in {}",
+                    c,
+                    layout
+                );
+            }
+        }
+    }
+
+    fn check_moves(pm: &LayoutPermutator, groups: &[&str]) {
+        let start = pm.get_permutable_indices();
+        for _ in 0..100 {
+            let moved = [
+                pm.perform_n_swaps(&start, 3),
+                pm.perform_n_rotations(&start, 3),
+                pm.perform_n_reversals(&start, 2, 4),
+                pm.perform_n_block_swaps(&start, 2, 2),
+                pm.switch_n_keys(&start, 4),
+                pm.generate_random(),
+            ];
+            for (n, permutation) in moved.iter().enumerate() {
+                assert_valid(pm, permutation);
+                // switching keys ignores the swap groups
+                if n != 4 {
+                    assert_within_groups(pm, permutation, groups);
+                }
+            }
+        }
+    }
+
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn moves_keep_the_fixed_keys() {
+        let pm = LayoutPermutator::new(LAYOUT, FIXED);
+        assert_eq!(pm.generate_string(&pm.get_permutable_indices()), LAYOUT);
+        check_moves(&pm, &[]);
+    }
+
+    #[test]
+    fn moves_change_the_layout() {
+        let pm = LayoutPermutator::new(LAYOUT, FIXED);
+        let start = pm.get_permutable_indices();
+        assert_ne!(pm.perform_n_swaps(&start, 1), start);
+        assert_ne!(pm.perform_n_rotations(&start, 1), start);
+        assert_ne!(pm.perform_n_reversals(&start, 1, 4), start);
+        assert_ne!(pm.perform_n_block_swaps(&start, 1, 2), start);
+    }
+
+    #[test]
+    fn reversals_and_block_swaps_move_adjacent_keys() {
+        // without fixed keys in between, segments are consecutive in the layout string
+        let pm = LayoutPermutator::new(LAYOUT, "");
+        let start = pm.get_permutable_indices();
+        for _ in 0..100 {
+            let reversed = pm.generate_string(&pm.perform_n_reversals(&start, 1, 3));
+            let changed: Vec<usize> = reversed
+                .chars()
+                .zip(LAYOUT.chars())
+                .enumerate()
+                .filter(|(_, (c1, c2))| c1 != c2)
+                .map(|(i, _)| i)
+                .collect();
+            let (first, last) = (changed[0], changed[changed.len() - 1]);
+            assert!(last - first < 3, "{}", reversed);
+            let segment: String = LAYOUT[first..=last].chars().rev().collect();
+            assert_eq!(&reversed[first..=last], segment);
+
+            let swapped = pm.generate_string(&pm.perform_n_block_swaps(&start, 1, 1));
+            let changed = swapped
+                .chars()
+                .zip(LAYOUT.chars())
+                .filter(|(c1, c2)| c1 != c2)
+                .count();
+            assert_eq!(changed, 2, "{}", swapped);
+        }
+    }
+
+    #[test]
+    fn moves_stay_within_the_swap_groups() {
+        let groups = ["abd", "fghij"];
+        let pm = LayoutPermutator::new(LAYOUT, FIXED)
+            .with_swap_groups(None, &groups.map(|g| g.to_string()));
+        check_moves(&pm, &groups);
+
+        // layers of five keys each
+        let pm = LayoutPermutator::new(LAYOUT, FIXED).with_swap_groups(Some(5), &[]);
+        check_moves(&pm, &["abd", "fghij"]);
+
+        // both
+        let pm =
+            LayoutPermutator::new(LAYOUT, FIXED).with_swap_groups(Some(5), &["aj".to_string()]);
+        assert_eq!(pm.key_groups().len(), 2);
+        check_moves(&pm, &["bd", "fghi", "a", "j"]);
+    }
+
+    #[test]
+    fn swap_pairs_are_within_the_swap_groups() {
+        let pm = LayoutPermutator::new(LAYOUT, FIXED);
+        assert_eq!(pm.swap_pairs().len(), 8 * 7 / 2);
+
+        let groups = ["abd", "fghij"];
+        let pm = LayoutPermutator::new(LAYOUT, FIXED)
+            .with_swap_groups(None, &groups.map(|g| g.to_string()));
+        let pairs = pm.swap_pairs();
+        assert_eq!(pairs.len(), 3 + 5 * 4 / 2);
+        let start = pm.get_permutable_indices();
+        for (i, j) in pairs {
+            let mut permutation = start.clone();
+            permutation.swap(i, j);
+            assert_valid(&pm, &permutation);
+            assert_within_groups(&pm, &permutation, &groups);
+        }
+    }
+
+    #[test]
+    fn without_keys_does_not_move_the_given_keys() {
+        let pm = LayoutPermutator::new(LAYOUT, FIXED);
+        // "a" and "b" are the first two permutable keys
+        let frozen = pm.without_keys(&[0, 1]).unwrap();
+        assert!(frozen.swap_pairs().iter().all(|(i, j)| *i > 1 && *j > 1));
+        check_moves(&frozen, &["a", "b", "dfghij"]);
+
+        let all: Vec<usize> = (0..7).collect();
+        assert!(pm.without_keys(&all).is_none());
+    }
+
+    #[test]
+    fn permutation_for_inverts_generate_string() {
+        let pm = LayoutPermutator::new(LAYOUT, FIXED);
+        for _ in 0..20 {
+            let permutation = pm.generate_random();
+            let layout = pm.generate_string(&permutation);
+            assert_eq!(pm.permutation_for(&layout), Some(permutation));
+        }
+
+        // moved fixed key, wrong symbols, wrong length
+        assert_eq!(pm.permutation_for("cbaedfghij"), None);
+        assert_eq!(pm.permutation_for("abcdexghij"), None);
+        assert_eq!(pm.permutation_for("abcdefghi"), None);
+    }
+
+    #[test]
+    fn mirrored_layouts_share_their_canonical_representative() {
+        // the left half "abcde" mirrors the right half "fghij"
+        let canonicalizer = MirrorCanonicalizer::new(vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(canonicalizer.mirror(LAYOUT), "jihgfedcba");
+        assert_eq!(canonicalizer.mirror(&canonicalizer.mirror(LAYOUT)), LAYOUT);
+        // strings of another length are left alone
+        assert_eq!(canonicalizer.mirror("abc"), "abc");
+
+        let pm = LayoutPermutator::new(LAYOUT, "");
+        for _ in 0..20 {
+            let layout = pm.generate_string(&pm.generate_random());
+            let canonical = canonicalizer.canonicalize(&layout);
+            assert_eq!(
+                canonicalizer.canonicalize(&canonicalizer.mirror(&layout)),
+                canonical
+            );
+            assert!(canonical == layout || canonical == canonicalizer.mirror(&layout));
+            assert!(canonical <= layout);
+        }
     }
 }
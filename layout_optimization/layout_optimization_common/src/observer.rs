@@ -0,0 +1,124 @@
+//! An interface for following the progress of an optimization, e.g. for logging or for showing
+//! the progress in a GUI.
+
+use crate::stopping::{StopReason, StoppingCriteria};
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// An observer shared between the caller and the optimization.
+pub type SharedObserver = Arc<Mutex<dyn OptimizationObserver + Send>>;
+
+/// Receives the progress of an optimization. All methods do nothing by default.
+pub trait OptimizationObserver {
+    /// Called after each generation (genetic algorithm) or iteration (simulated annealing).
+    fn on_generation(&mut self, _progress: &Progress) {}
+
+    /// Called whenever a layout with a lower cost than all previous ones was found.
+    fn on_new_best(&mut self, _progress: &Progress, _layout: &str) {}
+
+    /// Called once at the end of the optimization with its best layout.
+    fn on_finish(&mut self, _progress: &Progress, _layout: &str, _stop_reason: StopReason) {}
+}
+
+/// Forwards the progress to several observers.
+impl OptimizationObserver for Vec<Box<dyn OptimizationObserver + Send>> {
+    fn on_generation(&mut self, progress: &Progress) {
+        for observer in self.iter_mut() {
+            observer.on_generation(progress);
+        }
+    }
+
+    fn on_new_best(&mut self, progress: &Progress, layout: &str) {
+        for observer in self.iter_mut() {
+            observer.on_new_best(progress, layout);
+        }
+    }
+
+    fn on_finish(&mut self, progress: &Progress, layout: &str, stop_reason: StopReason) {
+        for observer in self.iter_mut() {
+            observer.on_finish(progress, layout, stop_reason);
+        }
+    }
+}
+
+/// The limits an optimization runs into (unless it stops early for other reasons).
+#[derive(Clone, Debug)]
+pub struct Budget {
+    /// Generation limit (genetic algorithm) or iteration limit (simulated annealing)
+    pub max_steps: u64,
+    pub max_evaluations: Option<u64>,
+    /// Wall-clock time limit in seconds
+    pub time_limit: Option<f64>,
+}
+
+impl Budget {
+    pub fn new(max_steps: u64, stopping: &StoppingCriteria) -> Self {
+        Self {
+            max_steps,
+            max_evaluations: stopping.max_evaluations,
+            time_limit: stopping.time_limit,
+        }
+    }
+}
+
+/// The state of an optimization.
+#[derive(Clone, Debug)]
+pub struct Progress {
+    /// Label of the optimization (e.g. the name of the process)
+    pub run: String,
+    /// Latest generation (genetic algorithm) or iteration (simulated annealing)
+    pub step: u64,
+    /// Cost of the best layout found so far
+    pub best_cost: f64,
+    /// Number of evaluations of this optimization (including cache hits)
+    pub evaluations: u64,
+    pub elapsed: Duration,
+    pub budget: Budget,
+}
+
+impl Progress {
+    pub fn evaluations_per_second(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => self.evaluations as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Fraction of the step budget that was used.
+    pub fn fraction_done(&self) -> f64 {
+        (self.step as f64 / self.budget.max_steps.max(1) as f64).min(1.0)
+    }
+
+    /// Estimated time until the budget (steps, evaluations, or time) is used up, based on the
+    /// rates measured so far. Early stopping criteria may end the optimization sooner.
+    pub fn eta(&self) -> Option<Duration> {
+        let elapsed = self.elapsed.as_secs_f64();
+        let by_steps = (self.step > 0).then(|| {
+            elapsed * self.budget.max_steps.saturating_sub(self.step) as f64 / self.step as f64
+        });
+        let by_evaluations = self
+            .budget
+            .max_evaluations
+            .filter(|_| self.evaluations > 0)
+            .map(|max_evaluations| {
+                elapsed * max_evaluations.saturating_sub(self.evaluations) as f64
+                    / self.evaluations as f64
+            });
+        let by_time = self
+            .budget
+            .time_limit
+            .map(|time_limit| (time_limit - elapsed).max(0.0));
+
+        [by_steps, by_evaluations, by_time]
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(None, |eta: Option<f64>, secs| {
+                Some(eta.map_or(secs, |eta| eta.min(secs)))
+            })
+            .map(Duration::from_secs_f64)
+    }
+}
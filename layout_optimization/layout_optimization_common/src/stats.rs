@@ -0,0 +1,108 @@
+//! Process-wide statistics of running optimizations (e.g. for monitoring long runs).
+//!
+//! The counters are shared by all optimizations of the process, so that optimizations running in
+//! parallel (e.g. several simulated annealing processes) are summarized together.
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Bits of `f64::INFINITY` (`f64::to_bits` is not const at the minimum supported Rust version)
+const INFINITY_BITS: u64 = 0x7ff0_0000_0000_0000;
+
+static START_MILLIS: AtomicU64 = AtomicU64::new(0);
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+static BEST_COST_BITS: AtomicU64 = AtomicU64::new(INFINITY_BITS);
+static EVALUATIONS: AtomicU64 = AtomicU64::new(0);
+static PROPOSALS: AtomicU64 = AtomicU64::new(0);
+static ACCEPTED: AtomicU64 = AtomicU64::new(0);
+static RUNS_STARTED: AtomicU64 = AtomicU64::new(0);
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// A snapshot of the statistics.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// Latest generation (genetic algorithm) or iteration (simulated annealing)
+    pub generation: u64,
+    /// Lowest cost found so far (infinite before the first evaluation)
+    pub best_cost: f64,
+    /// Number of layout evaluations (including cache hits)
+    pub evaluations: u64,
+    /// Number of proposed moves (simulated annealing)
+    pub proposals: u64,
+    /// Number of accepted moves (simulated annealing)
+    pub accepted: u64,
+    pub runs_started: u64,
+    /// Seconds since the first optimization was started
+    pub uptime: f64,
+}
+
+impl Snapshot {
+    /// Fraction of accepted moves (if any were proposed).
+    pub fn acceptance_rate(&self) -> Option<f64> {
+        (self.proposals > 0).then(|| self.accepted as f64 / self.proposals as f64)
+    }
+
+    /// Average number of evaluations per second since the first optimization was started.
+    pub fn evaluations_per_second(&self) -> f64 {
+        if self.uptime > 0.0 {
+            self.evaluations as f64 / self.uptime
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Record the start of an optimization run.
+pub fn record_start() {
+    let _ = START_MILLIS.compare_exchange(0, now_millis(), Ordering::Relaxed, Ordering::Relaxed);
+    RUNS_STARTED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record that an optimization reached the given generation (or iteration).
+pub fn record_generation(generation: u64) {
+    GENERATION.fetch_max(generation, Ordering::Relaxed);
+}
+
+/// Record the cost of a found layout (only the lowest cost is kept).
+pub fn record_cost(cost: f64) {
+    let _ = BEST_COST_BITS.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+        (cost < f64::from_bits(bits)).then(|| cost.to_bits())
+    });
+}
+
+pub fn record_evaluation() {
+    EVALUATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record whether a proposed move was accepted.
+pub fn record_proposal(accepted: bool) {
+    PROPOSALS.fetch_add(1, Ordering::Relaxed);
+    if accepted {
+        ACCEPTED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn snapshot() -> Snapshot {
+    let start = START_MILLIS.load(Ordering::Relaxed);
+    let uptime = match start {
+        0 => 0.0,
+        start => now_millis().saturating_sub(start) as f64 / 1000.0,
+    };
+
+    Snapshot {
+        generation: GENERATION.load(Ordering::Relaxed),
+        best_cost: f64::from_bits(BEST_COST_BITS.load(Ordering::Relaxed)),
+        evaluations: EVALUATIONS.load(Ordering::Relaxed),
+        proposals: PROPOSALS.load(Ordering::Relaxed),
+        accepted: ACCEPTED.load(Ordering::Relaxed),
+        runs_started: RUNS_STARTED.load(Ordering::Relaxed),
+        uptime,
+    }
+}
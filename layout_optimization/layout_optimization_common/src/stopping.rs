@@ -0,0 +1,181 @@
+//! Criteria for ending an optimization before its regular end (e.g. its generation or iteration
+//! limit), so that runs neither end long before nor long after they converged.
+
+use crate::stats;
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+/// The criteria for stopping an optimization early. Unset criteria are not checked.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct StoppingCriteria {
+    /// Stop if the best cost did not improve for this many generations (genetic algorithm) or
+    /// iterations (simulated annealing)
+    pub max_stall: Option<u64>,
+    /// Stop as soon as a layout with at most this cost was found
+    pub target_cost: Option<f64>,
+    /// Stop after this many seconds (wall-clock time)
+    pub time_limit: Option<f64>,
+    /// Stop after this many layout evaluations (including cache hits)
+    pub max_evaluations: Option<u64>,
+    /// Budget shared with other optimizations running at the same time (set by the caller, not
+    /// by configuration)
+    #[serde(skip)]
+    pub shared: Option<SharedBudget>,
+}
+
+impl StoppingCriteria {
+    pub fn is_empty(&self) -> bool {
+        self.max_stall.is_none()
+            && self.target_cost.is_none()
+            && self.time_limit.is_none()
+            && self.max_evaluations.is_none()
+            && self.shared.is_none()
+    }
+}
+
+/// A time and evaluation budget shared by several optimizations running at the same time (e.g.
+/// those of a portfolio). The time since its creation and the evaluations of all optimizations
+/// of the process count against it, and all optimizations stop once it is used up or once it was
+/// interrupted (e.g. by Ctrl+C).
+#[derive(Clone, Debug)]
+pub struct SharedBudget {
+    start: Instant,
+    initial_evaluations: u64,
+    interrupted: Arc<AtomicBool>,
+    /// Wall-clock time limit in seconds
+    pub time_limit: Option<f64>,
+    pub max_evaluations: Option<u64>,
+}
+
+impl SharedBudget {
+    pub fn new(time_limit: Option<f64>, max_evaluations: Option<u64>) -> Self {
+        Self {
+            start: Instant::now(),
+            initial_evaluations: stats::snapshot().evaluations,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            time_limit,
+            max_evaluations,
+        }
+    }
+
+    /// Number of evaluations of all optimizations since the budget was created.
+    pub fn evaluations(&self) -> u64 {
+        stats::snapshot().evaluations - self.initial_evaluations
+    }
+
+    /// Seconds since the budget was created.
+    pub fn elapsed(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    /// Stop all optimizations using the budget after their current step.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+
+    /// The reason for stopping if the budget is used up or was interrupted.
+    pub fn check(&self) -> Option<StopReason> {
+        if self.interrupted.load(Ordering::Relaxed) {
+            Some(StopReason::Interrupted)
+        } else if self.time_limit.map_or(false, |time_limit| {
+            self.start.elapsed().as_secs_f64() >= time_limit
+        }) {
+            Some(StopReason::TimeLimit)
+        } else if self.max_evaluations.map_or(false, |max_evaluations| {
+            self.evaluations() >= max_evaluations
+        }) {
+            Some(StopReason::EvaluationBudget)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why an optimization ended.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The optimizer's own end, e.g. its generation or iteration limit
+    Completed,
+    Stalled,
+    TargetCostReached,
+    TimeLimit,
+    EvaluationBudget,
+    Interrupted,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            StopReason::Completed => "completed",
+            StopReason::Stalled => "no improvement (max_stall)",
+            StopReason::TargetCostReached => "target cost reached",
+            StopReason::TimeLimit => "time limit reached",
+            StopReason::EvaluationBudget => "evaluation budget exhausted",
+            StopReason::Interrupted => "interrupted",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// Checks the [`StoppingCriteria`] against the progress of a run.
+#[derive(Clone, Debug)]
+pub struct StoppingMonitor {
+    criteria: StoppingCriteria,
+    start: Instant,
+    best_cost: f64,
+    last_improvement: u64,
+}
+
+impl StoppingMonitor {
+    pub fn new(criteria: &StoppingCriteria) -> Self {
+        Self {
+            criteria: criteria.clone(),
+            start: Instant::now(),
+            best_cost: f64::INFINITY,
+            last_improvement: 0,
+        }
+    }
+
+    /// Check the criteria after the given step (generation or iteration) with the best cost
+    /// found so far and the number of evaluations of the run.
+    pub fn check(&mut self, step: u64, best_cost: f64, evaluations: u64) -> Option<StopReason> {
+        if best_cost < self.best_cost {
+            self.best_cost = best_cost;
+            self.last_improvement = step;
+        }
+
+        let criteria = &self.criteria;
+        if criteria
+            .target_cost
+            .map_or(false, |target| best_cost <= target)
+        {
+            Some(StopReason::TargetCostReached)
+        } else if criteria
+            .max_stall
+            .map_or(false, |max_stall| step - self.last_improvement >= max_stall)
+        {
+            Some(StopReason::Stalled)
+        } else if criteria.time_limit.map_or(false, |time_limit| {
+            self.start.elapsed().as_secs_f64() >= time_limit
+        }) {
+            Some(StopReason::TimeLimit)
+        } else if criteria
+            .max_evaluations
+            .map_or(false, |max_evaluations| evaluations >= max_evaluations)
+        {
+            Some(StopReason::EvaluationBudget)
+        } else {
+            criteria.shared.as_ref().and_then(|shared| shared.check())
+        }
+    }
+}
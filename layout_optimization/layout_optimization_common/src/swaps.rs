@@ -0,0 +1,50 @@
+//! Restrictions of the keys that an optimization may exchange, shared by the parameters of all
+//! optimizers.
+
+use crate::LayoutPermutator;
+
+use keyboard_layout::layout_generator::LayoutGenerator;
+use serde::{Deserialize, Serialize};
+
+/// Restrict the exchanges of an optimization (swaps, or the keys removed and reinserted together
+/// by the large neighborhood search) to keys of the same group.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct SwapRestrictions {
+    /// Only exchange keys within the same layer (segment of the layout string, see
+    /// `GroupedLayoutGenerator`).
+    #[serde(default)]
+    pub same_layer_swaps: bool,
+
+    /// Only exchange symbols within the same of these groups (other symbols form a group of their
+    /// own).
+    #[serde(default)]
+    pub swap_groups: Vec<String>,
+}
+
+impl SwapRestrictions {
+    /// The number of symbols of a layer of the layout string if exchanges are restricted to
+    /// layers.
+    pub fn layer_size(
+        &self,
+        layout_generator: &dyn LayoutGenerator,
+        layout_str: &str,
+    ) -> Option<usize> {
+        self.same_layer_swaps.then(|| {
+            let layout = layout_generator.generate(layout_str).unwrap();
+            layout.as_text().chars().count()
+        })
+    }
+
+    /// A permutator of the layout's keys with the exchanges restricted to the groups.
+    pub fn permutator(
+        &self,
+        layout_generator: &dyn LayoutGenerator,
+        layout_str: &str,
+        fixed_characters: &str,
+    ) -> LayoutPermutator {
+        LayoutPermutator::new(layout_str, fixed_characters).with_swap_groups(
+            self.layer_size(layout_generator, layout_str),
+            &self.swap_groups,
+        )
+    }
+}
@@ -1,3 +1,4 @@
+pub mod lineage;
 pub mod optimization;
 
 #[cfg(test)]
@@ -0,0 +1,258 @@
+//! The `lineage` module records the parentage of the individuals of a genetic optimization, i.e.
+//! the seed layouts and the crossover and mutation operations that produced each individual.
+//! This allows tracing back which seed layouts and operators contributed to the final winners.
+
+use layout_optimization_common::LayoutPermutator;
+
+use ahash::{AHashMap, AHashSet};
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+type Genotype = Vec<usize>;
+
+/// The operation that produced an individual.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    /// An individual of the initial population (e.g. a warm start layout or a random layout)
+    Seed {
+        label: String,
+    },
+    Crossover {
+        operator: String,
+    },
+    Mutation {
+        operator: String,
+        /// Number of moves of the operator (e.g. swaps)
+        #[serde(alias = "n_swaps")]
+        n_moves: usize,
+    },
+}
+
+impl Origin {
+    fn operator(&self) -> Option<&str> {
+        match self {
+            Origin::Seed { .. } => None,
+            Origin::Crossover { operator } | Origin::Mutation { operator, .. } => Some(operator),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    /// Running number of the entry; parents always have lower numbers than their children
+    id: usize,
+    generation: u64,
+    origin: Origin,
+    parents: Vec<Genotype>,
+}
+
+/// Recorder of the parentage of individuals. Clones share the same records.
+///
+/// Individuals are identified by their genome, so an individual that is produced repeatedly keeps
+/// the lineage of its first occurrence.
+#[derive(Clone, Default, Debug)]
+pub struct Lineage {
+    entries: Arc<Mutex<AHashMap<Genotype, Entry>>>,
+    next_id: Arc<AtomicUsize>,
+    generation: Arc<AtomicU64>,
+}
+
+/// An ancestor of an individual in a [`LineageDump`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Ancestor {
+    pub id: usize,
+    pub layout: String,
+    pub generation: u64,
+    pub origin: Origin,
+    /// Ids of the parents
+    pub parents: Vec<usize>,
+}
+
+/// The lineage of an individual with a summary of the contributing seeds and operators.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LineageDump {
+    pub layout: String,
+    /// Seed labels with their share of the individual's pedigree (each parent of a crossover
+    /// contributes half of the child's share)
+    pub seeds: Vec<(String, f64)>,
+    /// Operators with the number of their applications in the ancestry
+    pub operators: Vec<(String, usize)>,
+    /// All ancestors (including the individual itself as the last entry)
+    pub ancestors: Vec<Ancestor>,
+}
+
+impl Lineage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the generation that subsequently recorded individuals belong to.
+    pub fn set_generation(&self, generation: u64) {
+        self.generation.store(generation, Ordering::Relaxed);
+    }
+
+    /// Record a seed of the initial population.
+    pub fn record_seed(&self, genome: &[usize], label: &str) {
+        self.record(
+            genome,
+            Vec::new(),
+            Origin::Seed {
+                label: label.to_string(),
+            },
+        );
+    }
+
+    /// Record that `child` was produced from `parents` by `origin`. Children that equal one of
+    /// their parents or that are already known are ignored.
+    pub fn record(&self, child: &[usize], parents: Vec<Genotype>, origin: Origin) {
+        if parents.iter().any(|p| p.as_slice() == child) {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(child) {
+            entries.insert(
+                child.to_vec(),
+                Entry {
+                    id: self.next_id.fetch_add(1, Ordering::Relaxed),
+                    generation: self.generation.load(Ordering::Relaxed),
+                    origin,
+                    parents,
+                },
+            );
+        }
+    }
+
+    /// The known ancestors of the given genomes (including the genomes themselves).
+    fn ancestors<'a, 'b>(
+        entries: &'a AHashMap<Genotype, Entry>,
+        genomes: impl Iterator<Item = &'b Genotype>,
+    ) -> AHashSet<&'a Genotype> {
+        let mut visited: AHashSet<&Genotype> = AHashSet::default();
+        let mut stack: Vec<&Genotype> = genomes
+            .filter_map(|g| entries.get_key_value(g).map(|(g, _)| g))
+            .collect();
+        while let Some(genome) = stack.pop() {
+            if visited.insert(genome) {
+                stack.extend(
+                    entries[genome]
+                        .parents
+                        .iter()
+                        .filter_map(|p| entries.get_key_value(p).map(|(p, _)| p)),
+                );
+            }
+        }
+
+        visited
+    }
+
+    /// Forget all individuals that are no ancestors of the given genomes (e.g. the current
+    /// population and the best individual so far) to limit the memory consumption.
+    pub fn prune<'a>(&self, keep: impl Iterator<Item = &'a Genotype>) {
+        let mut entries = self.entries.lock().unwrap();
+        let keep: AHashSet<Genotype> = Self::ancestors(&entries, keep)
+            .into_iter()
+            .cloned()
+            .collect();
+        entries.retain(|genome, _| keep.contains(genome));
+    }
+
+    /// Number of recorded individuals.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The lineage of the given genome.
+    pub fn dump(&self, genome: &Genotype, permutator: &LayoutPermutator) -> LineageDump {
+        let entries = self.entries.lock().unwrap();
+        let mut ancestors: Vec<(&Genotype, &Entry)> =
+            Self::ancestors(&entries, std::iter::once(genome))
+                .into_iter()
+                .map(|g| (g, &entries[g]))
+                .collect();
+        ancestors.sort_by_key(|(_, e)| e.id);
+
+        // distribute the pedigree share from the children to their parents
+        let mut shares: AHashMap<&Genotype, f64> = AHashMap::default();
+        shares.insert(genome, 1.0);
+        let mut seeds: AHashMap<String, f64> = AHashMap::default();
+        let mut operators: AHashMap<String, usize> = AHashMap::default();
+        for (g, entry) in ancestors.iter().rev() {
+            let share = shares.get(g).cloned().unwrap_or_default();
+            if let Origin::Seed { label } = &entry.origin {
+                *seeds.entry(label.clone()).or_default() += share;
+            }
+            if let Some(operator) = entry.origin.operator() {
+                *operators.entry(operator.to_string()).or_default() += 1;
+            }
+
+            let known_parents: Vec<&Genotype> = entry
+                .parents
+                .iter()
+                .filter(|p| entries.contains_key(*p))
+                .collect();
+            for p in known_parents.iter() {
+                *shares.entry(p).or_default() += share / known_parents.len() as f64;
+            }
+        }
+
+        let mut seeds: Vec<(String, f64)> = seeds.into_iter().collect();
+        seeds.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        let mut operators: Vec<(String, usize)> = operators.into_iter().collect();
+        operators.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+
+        let ancestors = ancestors
+            .iter()
+            .map(|(g, entry)| Ancestor {
+                id: entry.id,
+                layout: permutator.generate_string(g),
+                generation: entry.generation,
+                origin: entry.origin.clone(),
+                parents: entry
+                    .parents
+                    .iter()
+                    .filter_map(|p| entries.get(p).map(|e| e.id))
+                    .collect(),
+            })
+            .collect();
+
+        LineageDump {
+            layout: permutator.generate_string(genome),
+            seeds,
+            operators,
+            ancestors,
+        }
+    }
+}
+
+impl LineageDump {
+    /// A short textual summary of the contributing seeds and operators.
+    pub fn summary(&self) -> String {
+        let seeds: Vec<String> = self
+            .seeds
+            .iter()
+            .map(|(label, share)| format!("  {:>6.1}%  {}", 100.0 * share, label))
+            .collect();
+        let operators: Vec<String> = self
+            .operators
+            .iter()
+            .map(|(operator, n)| format!("  {:>7}  {}", n, operator))
+            .collect();
+
+        format!(
+            "Lineage of {} ({} ancestors)\nSeeds:\n{}\nOperations:\n{}",
+            self.layout,
+            self.ancestors.len(),
+            seeds.join("\n"),
+            operators.join("\n")
+        )
+    }
+}
@@ -1,24 +1,41 @@
-use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
-use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+use crate::lineage::{Lineage, LineageDump, Origin};
 
-use layout_optimization_common::LayoutPermutator;
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{cache::Cache, evaluation::Evaluator, results::optimization_score};
+
+use layout_optimization_common::{
+    constraints::{constrained_permutator, SoftConstraintParameters, SoftConstraints},
+    exchange::Participant,
+    history::ConvergenceHistory,
+    observer::{Budget, Progress, SharedObserver},
+    stats,
+    stopping::{StopReason, StoppingCriteria, StoppingMonitor},
+    swaps::SwapRestrictions,
+    LayoutPermutator, MirrorCanonicalizer,
+};
 
 use anyhow::Result;
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, sync::Arc};
+use std::{
+    fs::File,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
 
 use genevo::{
     genetic::{Children, FitnessFunction, Parents},
-    operator::{prelude::*, CrossoverOp, GeneticOperator},
+    operator::{prelude::*, CrossoverOp, GeneticOperator, MutationOp},
     population::Population,
     prelude::*,
-    random::SliceRandom,
     simulation::simulator::Simulator,
     types::fmt::Display,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Parameters {
     pub population_size: usize,
     pub generation_limit: u64,
@@ -26,6 +43,64 @@ pub struct Parameters {
     pub selection_ratio: f64,
     pub mutation_rate: f64,
     pub reinsertion_ratio: f64,
+    /// Evaluate mirror-equivalent layouts as their canonical representative, so that they share
+    /// one entry of the result cache (see [`MirrorCanonicalizer`]). Requires symmetric metrics.
+    #[serde(default)]
+    pub canonicalize_mirrors: bool,
+
+    /// Restrictions of the keys that are swapped.
+    #[serde(flatten)]
+    pub swap_restrictions: SwapRestrictions,
+
+    /// If set, treat these constraints as soft: the search may violate them at a penalty that
+    /// grows with the generations (see [`SoftConstraintParameters`]).
+    #[serde(default)]
+    pub soft_constraints: Option<SoftConstraintParameters>,
+
+    /// Record the parentage of all individuals (see [`Lineage`]).
+    #[serde(default)]
+    pub track_lineage: bool,
+
+    /// Record the best and mean costs of each generation (see [`ConvergenceHistory`]).
+    #[serde(default)]
+    pub record_history: bool,
+
+    /// Relative probabilities of the mutation operators.
+    #[serde(default)]
+    pub mutation_operators: MutationOperators,
+
+    /// Criteria for ending the optimization before the generation limit.
+    #[serde(default)]
+    pub stopping: StoppingCriteria,
+}
+
+/// Relative probabilities of the operators of the mutation stage. Each mutation applies one
+/// operator, chosen with these probabilities, as many times as the mutation rate demands.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct MutationOperators {
+    /// Swap two keys
+    pub swap: f64,
+    /// Rotate three keys
+    pub rotation: f64,
+    /// Reverse the order of a segment of keys
+    pub reversal: f64,
+    /// Swap two segments of keys
+    pub block_swap: f64,
+    /// Maximum number of keys in a segment for reversals and block swaps
+    pub max_segment_length: usize,
+}
+
+impl Default for MutationOperators {
+    fn default() -> Self {
+        MutationOperators {
+            swap: 1.0,
+            rotation: 0.0,
+            reversal: 0.0,
+            block_swap: 0.0,
+            max_segment_length: 4,
+        }
+    }
 }
 
 impl Default for Parameters {
@@ -37,6 +112,13 @@ impl Default for Parameters {
             selection_ratio: 0.7,
             mutation_rate: 0.1,
             reinsertion_ratio: 0.7,
+            canonicalize_mirrors: false,
+            swap_restrictions: SwapRestrictions::default(),
+            soft_constraints: None,
+            track_lineage: false,
+            record_history: false,
+            mutation_operators: MutationOperators::default(),
+            stopping: StoppingCriteria::default(),
         }
     }
 }
@@ -46,31 +128,64 @@ impl Parameters {
         let f = File::open(filename)?;
         Ok(serde_yaml::from_reader(f)?)
     }
+
+    /// Fit the generation limit to a wall-clock budget of `seconds` at the given evaluation rate
+    /// (assuming that each generation evaluates the whole population).
+    pub fn fit_to_time_budget(&mut self, seconds: f64, evaluations_per_second: f64) {
+        let generations = seconds * evaluations_per_second / self.population_size.max(1) as f64;
+        self.generation_limit = (generations as u64).max(1);
+    }
 }
 
 // The genotype
 type Genotype = Vec<usize>;
 
+/// Genomes waiting to replace the next mutated offspring (see [`Immigration`]).
+pub type Immigrants = Arc<Mutex<Vec<Genotype>>>;
+
+/// Layouts of other optimizations (received from a [`Participant`] of a solution exchange) that
+/// are inserted into the population as immigrants.
+pub struct Immigration {
+    pub participant: Participant,
+    /// Number of immigrants per received layout: the layout itself and mutations of it
+    pub copies: usize,
+}
+
 /// The fitness function for [`Genotype`]s.
 #[derive(Clone, Debug)]
 pub struct FitnessCalc {
     evaluator: Arc<Evaluator>,
     permutator: LayoutPermutator,
     layout_generator: Box<dyn LayoutGenerator>,
-    result_cache: Option<Cache<usize>>,
+    result_cache: Option<Cache<f64>>,
+    canonicalizer: Option<MirrorCanonicalizer>,
+    constraints: Option<SoftConstraints>,
+    /// Evaluations of this optimization (the process-wide statistics include concurrent ones)
+    evaluations: Arc<AtomicU64>,
 }
 
 impl FitnessFunction<Genotype, usize> for FitnessCalc {
     fn fitness_of(&self, genome: &Genotype) -> usize {
-        let layout_str = self.permutator.generate_string(genome);
+        stats::record_evaluation();
+        self.evaluations.fetch_add(1, Ordering::Relaxed);
+        let mut layout_str = self.permutator.generate_string(genome);
+        if let Some(canonicalizer) = &self.canonicalizer {
+            layout_str = canonicalizer.canonicalize(&layout_str);
+        }
         let l = self.layout_generator.generate(&layout_str).unwrap();
 
         // Get & return the evaluation-result
-        match &self.result_cache {
+        // total costs instead of scores are cached to share them with the other optimizers
+        let total_cost = match &self.result_cache {
             Some(result_cache) => result_cache.get_or_insert_with(&layout_str, || {
-                self.evaluator.evaluate_layout(&l).optimization_score()
+                self.evaluator.evaluate_layout(&l).total_cost()
             }),
-            None => self.evaluator.evaluate_layout(&l).optimization_score(),
+            None => self.evaluator.evaluate_layout(&l).total_cost(),
+        };
+
+        match &self.constraints {
+            Some(constraints) => optimization_score(constraints.penalized(total_cost, genome)),
+            None => optimization_score(total_cost),
         }
     }
 
@@ -88,25 +203,24 @@ impl FitnessFunction<Genotype, usize> for FitnessCalc {
 }
 
 struct LayoutBuilder {
-    indices: Vec<usize>,
+    permutator: LayoutPermutator,
 }
 
 impl LayoutBuilder {
     fn with_permutable_layout(layout_prototype: &LayoutPermutator) -> Self {
         Self {
-            indices: layout_prototype.get_permutable_indices(),
+            permutator: layout_prototype.clone(),
         }
     }
 }
 
 impl GenomeBuilder<Vec<usize>> for LayoutBuilder {
-    fn build_genome<R>(&self, _: usize, rng: &mut R) -> Vec<usize>
+    fn build_genome<R>(&self, _: usize, _rng: &mut R) -> Vec<usize>
     where
         R: Rng + Sized,
     {
-        let mut s: Vec<usize> = self.indices.clone();
-        s.shuffle(rng);
-        s
+        // respects the permutator's swap groups
+        self.permutator.generate_random()
     }
 }
 
@@ -132,6 +246,151 @@ impl GenomeBuilder<Vec<usize>> for FromGivenLayoutBuilder {
     }
 }
 
+struct FromGivenLayoutsBuilder {
+    genomes: Vec<Genotype>,
+    permutator: LayoutPermutator,
+    max_swaps: usize,
+}
+
+impl FromGivenLayoutsBuilder {
+    /// Starts with the given genomes and fills the rest of the population with mutated copies of
+    /// them (with up to the genome's length times the mutation rate swaps each).
+    fn with_genomes(
+        genomes: Vec<Genotype>,
+        permutator: &LayoutPermutator,
+        mutation_rate: f64,
+    ) -> Self {
+        let genome_length = genomes.first().map(|g| g.len()).unwrap_or(0);
+        Self {
+            genomes,
+            permutator: permutator.clone(),
+            max_swaps: ((genome_length as f64 * mutation_rate).ceil() as usize).max(1),
+        }
+    }
+}
+
+impl GenomeBuilder<Vec<usize>> for FromGivenLayoutsBuilder {
+    fn build_genome<R>(&self, index: usize, rng: &mut R) -> Vec<usize>
+    where
+        R: Rng + Sized,
+    {
+        // cycle through the given layouts, mutating all but their first occurrence to keep the
+        // population diverse
+        let genome = &self.genomes[index % self.genomes.len()];
+        if index < self.genomes.len() {
+            genome.clone()
+        } else {
+            self.permutator
+                .perform_n_swaps(genome, rng.gen_range(1..=self.max_swaps))
+        }
+    }
+}
+
+/// Mutation moving keys using the [`LayoutPermutator`] (respecting its swap groups) with one of
+/// the [`MutationOperators`]. Similar to [`SwapOrderMutator`], the number of moves is the genome's
+/// length times the mutation rate (randomly rounded).
+#[derive(Clone, Debug)]
+pub struct PermutatorMutator {
+    mutation_rate: f64,
+    operators: MutationOperators,
+    permutator: LayoutPermutator,
+    lineage: Option<Lineage>,
+    immigrants: Option<Immigrants>,
+}
+
+impl PermutatorMutator {
+    pub fn new(
+        mutation_rate: f64,
+        operators: MutationOperators,
+        permutator: LayoutPermutator,
+    ) -> Self {
+        PermutatorMutator {
+            mutation_rate,
+            operators,
+            permutator,
+            lineage: None,
+            immigrants: None,
+        }
+    }
+
+    /// Record the mutations in the given [`Lineage`].
+    pub fn with_lineage(mut self, lineage: Option<Lineage>) -> Self {
+        self.lineage = lineage;
+        self
+    }
+
+    /// Replace offspring by the waiting immigrants instead of mutating them.
+    pub fn with_immigrants(mut self, immigrants: Option<Immigrants>) -> Self {
+        self.immigrants = immigrants;
+        self
+    }
+}
+
+impl GeneticOperator for PermutatorMutator {
+    fn name() -> String {
+        "Permutator-Mutator".to_string()
+    }
+}
+
+impl MutationOp<Vec<usize>> for PermutatorMutator {
+    fn mutate<R>(&self, genome: Vec<usize>, rng: &mut R) -> Vec<usize>
+    where
+        R: Rng + Sized,
+    {
+        if let Some(immigrant) = self
+            .immigrants
+            .as_ref()
+            .and_then(|immigrants| immigrants.lock().unwrap().pop())
+        {
+            return immigrant;
+        }
+
+        let n_moves =
+            (genome.len() as f64 * self.mutation_rate + rng.gen::<f64>()).floor() as usize;
+
+        let ops = &self.operators;
+        let weights = [ops.swap, ops.rotation, ops.reversal, ops.block_swap];
+        let mut choice = rng.gen::<f64>() * weights.iter().map(|w| w.max(0.0)).sum::<f64>();
+        let operator = weights
+            .iter()
+            .position(|w| {
+                choice -= w.max(0.0);
+                choice < 0.0
+            })
+            .unwrap_or(0);
+
+        let (name, mutated) = match operator {
+            1 => (
+                "Rotation",
+                self.permutator.perform_n_rotations(&genome, n_moves),
+            ),
+            2 => (
+                "Reversal",
+                self.permutator
+                    .perform_n_reversals(&genome, n_moves, ops.max_segment_length),
+            ),
+            3 => (
+                "Block-Swap",
+                self.permutator
+                    .perform_n_block_swaps(&genome, n_moves, ops.max_segment_length),
+            ),
+            _ => ("Swap", self.permutator.perform_n_swaps(&genome, n_moves)),
+        };
+        if let Some(lineage) = &self.lineage {
+            lineage.record(
+                &mutated,
+                vec![genome],
+                Origin::Mutation {
+                    operator: format!("{} ({})", Self::name(), name),
+                    n_moves,
+                },
+            );
+        }
+
+        mutated
+    }
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq)]
 pub struct NoOpCrossover {}
 impl NoOpCrossover {
@@ -154,11 +413,19 @@ impl CrossoverOp<Vec<usize>> for NoOpCrossover {
 }
 
 // Crossover method as used in https://github.com/Coletronix/Genetic-Keyboard-Generator
-#[derive(Default, Clone, Debug, PartialEq, Eq)]
-pub struct MyCrossover {}
+#[derive(Default, Clone, Debug)]
+pub struct MyCrossover {
+    lineage: Option<Lineage>,
+}
 impl MyCrossover {
     pub fn new() -> Self {
-        MyCrossover {}
+        MyCrossover { lineage: None }
+    }
+
+    /// Record the crossovers in the given [`Lineage`].
+    pub fn with_lineage(mut self, lineage: Option<Lineage>) -> Self {
+        self.lineage = lineage;
+        self
     }
 }
 impl GeneticOperator for MyCrossover {
@@ -200,7 +467,18 @@ impl CrossoverOp<Vec<usize>> for MyCrossover {
                         }
                     });
 
-                offspring.iter().map(|val| val.unwrap()).collect()
+                let child: Vec<usize> = offspring.iter().map(|val| val.unwrap()).collect();
+                if let Some(lineage) = &self.lineage {
+                    lineage.record(
+                        &child,
+                        vec![p1.clone(), p2.clone()],
+                        Origin::Crossover {
+                            operator: Self::name(),
+                        },
+                    );
+                }
+
+                child
             })
             .collect()
     }
@@ -215,12 +493,13 @@ pub type MySimulator = Simulator<
         // PartiallyMappedCrossover,
         // MyCrossover,
         NoOpCrossover,
-        SwapOrderMutator,
+        PermutatorMutator,
         UniformReinserter, // we do not use an elitist reinserter due to performance reasons (non-parallelized evaluation)
     >,
     GenerationLimit,
 >;
 
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
 pub fn init_optimization(
     params: &Parameters,
     evaluator: &Evaluator,
@@ -228,10 +507,84 @@ pub fn init_optimization(
     layout_generator: &Box<dyn LayoutGenerator>,
     fixed_characters: &str,
     start_with_layout: bool,
-    cache_results: bool,
-) -> (MySimulator, LayoutPermutator) {
-    let pm = LayoutPermutator::new(layout_str, fixed_characters);
-    let initial_population: Population<Genotype> = if start_with_layout {
+    initial_layouts: &[String],
+    result_cache: Option<Cache<f64>>,
+    immigrants: Option<Immigrants>,
+) -> (
+    MySimulator,
+    LayoutPermutator,
+    Option<Lineage>,
+    Option<SoftConstraints>,
+) {
+    init_counted_optimization(
+        params,
+        evaluator,
+        layout_str,
+        layout_generator,
+        fixed_characters,
+        start_with_layout,
+        initial_layouts,
+        result_cache,
+        immigrants,
+        Default::default(),
+    )
+}
+
+/// Like [`init_optimization`], but counts the evaluations of the simulator in `evaluations`.
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
+fn init_counted_optimization(
+    params: &Parameters,
+    evaluator: &Evaluator,
+    layout_str: &str,
+    layout_generator: &Box<dyn LayoutGenerator>,
+    fixed_characters: &str,
+    start_with_layout: bool,
+    initial_layouts: &[String],
+    result_cache: Option<Cache<f64>>,
+    immigrants: Option<Immigrants>,
+    evaluations: Arc<AtomicU64>,
+) -> (
+    MySimulator,
+    LayoutPermutator,
+    Option<Lineage>,
+    Option<SoftConstraints>,
+) {
+    let layer_size = params
+        .swap_restrictions
+        .layer_size(layout_generator.as_ref(), layout_str);
+    let (pm, constraints) = constrained_permutator(
+        layout_str,
+        fixed_characters,
+        layer_size,
+        &params.swap_restrictions.swap_groups,
+        params.soft_constraints.as_ref(),
+    );
+    let initial_genomes: Vec<(Genotype, &String)> = initial_layouts
+        .iter()
+        .filter_map(|l| {
+            let genome = pm.permutation_for(l);
+            if genome.is_none() {
+                log::warn!("Skipping initial layout '{}': keys do not match", l);
+            }
+            genome.map(|g| (g, l))
+        })
+        .collect();
+
+    let initial_population: Population<Genotype> = if !initial_genomes.is_empty() {
+        // the start layout is seeded alongside the given layouts instead of being dropped
+        let start_genome = start_with_layout.then(|| pm.get_permutable_indices());
+        build_population()
+            .with_genome_builder(FromGivenLayoutsBuilder::with_genomes(
+                start_genome
+                    .into_iter()
+                    .chain(initial_genomes.iter().map(|(g, _)| g.clone()))
+                    .collect(),
+                &pm,
+                params.mutation_rate,
+            ))
+            .of_size(params.population_size)
+            .uniform_at_random()
+    } else if start_with_layout {
         build_population()
             .with_genome_builder(FromGivenLayoutBuilder::with_permutable_layout(&pm))
             .of_size(params.population_size)
@@ -243,8 +596,40 @@ pub fn init_optimization(
             .uniform_at_random()
     };
 
-    let result_cache = if cache_results {
-        Some(Cache::new())
+    let lineage = params.track_lineage.then(|| {
+        let lineage = Lineage::new();
+        for (i, genome) in initial_population.individuals().iter().enumerate() {
+            let label = match initial_genomes.iter().find(|(g, _)| g == genome) {
+                Some((_, l)) => format!("initial layout {}", l),
+                None if start_with_layout && *genome == pm.get_permutable_indices() => {
+                    "start layout".to_string()
+                }
+                None if !initial_genomes.is_empty() => {
+                    format!("mutated initial layout {}", i + 1)
+                }
+                None => format!("random layout {}", i + 1),
+            };
+            lineage.record_seed(genome, &label);
+        }
+        lineage
+    });
+
+    let canonicalizer = if params.canonicalize_mirrors {
+        let layout = layout_generator.generate(layout_str).unwrap();
+        match layout.mirror_indices() {
+            Ok(mirror_indices) => {
+                if result_cache.is_none() {
+                    log::warn!(
+                        "Canonicalizing mirrored layouts saves no evaluations without a result cache"
+                    );
+                }
+                Some(MirrorCanonicalizer::new(mirror_indices))
+            }
+            Err(e) => {
+                log::warn!("Not canonicalizing mirrored layouts: {}", e);
+                None
+            }
+        }
     } else {
         None
     };
@@ -256,6 +641,9 @@ pub fn init_optimization(
                 permutator: pm.clone(),
                 layout_generator: layout_generator.clone(),
                 result_cache,
+                canonicalizer,
+                constraints: constraints.clone(),
+                evaluations,
             })
             .with_selection(MaximizeSelector::new(
                 params.selection_ratio,
@@ -264,7 +652,15 @@ pub fn init_optimization(
             // .with_crossover(PartiallyMappedCrossover::new())
             // .with_crossover(MyCrossover::new())
             .with_crossover(NoOpCrossover::new())
-            .with_mutation(SwapOrderMutator::new(params.mutation_rate))
+            .with_mutation(
+                PermutatorMutator::new(
+                    params.mutation_rate,
+                    params.mutation_operators.clone(),
+                    pm.clone(),
+                )
+                .with_lineage(lineage.clone())
+                .with_immigrants(immigrants),
+            )
             .with_reinsertion(UniformReinserter::new(params.reinsertion_ratio))
             .with_initial_population(initial_population)
             .build(),
@@ -272,9 +668,21 @@ pub fn init_optimization(
     .until(GenerationLimit::new(params.generation_limit))
     .build();
 
-    (sim, pm)
+    (sim, pm, lineage, constraints)
 }
 
+/// The best layout of the genetic algorithm together with the records of its run.
+pub struct GeneticResult {
+    pub layout_str: String,
+    pub layout: Layout,
+    /// Ancestry of the best layout (if [`Parameters::track_lineage`] is set)
+    pub lineage_dump: Option<LineageDump>,
+    /// Best and mean costs of each generation (if [`Parameters::record_history`] is set)
+    pub history: Option<ConvergenceHistory>,
+    pub stop_reason: StopReason,
+}
+
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
 pub fn optimize(
     params: &Parameters,
     evaluator: &Evaluator,
@@ -282,33 +690,76 @@ pub fn optimize(
     layout_generator: &Box<dyn LayoutGenerator>,
     fixed_characters: &str,
     start_with_layout: bool,
-    cache_results: bool,
-) -> (String, Layout) {
-    let (mut sim, pm) = init_optimization(
+    initial_layouts: &[String],
+    result_cache: Option<Cache<f64>>,
+    observer: Option<SharedObserver>,
+    mut immigration: Option<Immigration>,
+) -> GeneticResult {
+    let immigrants: Option<Immigrants> = immigration.as_ref().map(|_| Default::default());
+    let evaluations = Arc::new(AtomicU64::new(0));
+    let (mut sim, pm, lineage, constraints) = init_counted_optimization(
         params,
         evaluator,
         layout_str,
         layout_generator,
         fixed_characters,
         start_with_layout,
-        cache_results,
+        initial_layouts,
+        result_cache,
+        immigrants.clone(),
+        evaluations.clone(),
     );
 
     log::info!("Starting optimization with: {:?}", params);
+    stats::record_start();
+    let mut stopping_monitor = StoppingMonitor::new(&params.stopping);
+    let mut stop_reason = StopReason::Completed;
     let mut all_time_best: Option<(usize, Genotype)> = None;
+    let mut best_cost = f64::INFINITY;
+    let mut history = params
+        .record_history
+        .then(|| ConvergenceHistory::new("genetic"));
+    let start = Instant::now();
+    let budget = Budget::new(params.generation_limit, &params.stopping);
+    let progress = |step: u64, best_cost: f64| Progress {
+        run: "genetic".to_string(),
+        step,
+        best_cost,
+        evaluations: evaluations.load(Ordering::Relaxed),
+        elapsed: start.elapsed(),
+        budget: budget.clone(),
+    };
+    let mut last_step = 0;
+    // the best individual of the last generation, see the repair of the soft constraints below
+    let mut last_best: Option<Genotype> = None;
 
     loop {
+        if let Some(constraints) = &constraints {
+            constraints.set_progress(last_step as f64 / params.generation_limit.max(1) as f64);
+        }
         let result = sim.step();
         match result {
             Ok(SimResult::Intermediate(step)) => {
+                last_step = step.iteration;
+                if let Some(lineage) = &lineage {
+                    lineage.set_generation(step.iteration);
+                }
+                stats::record_generation(step.iteration);
                 let evaluated_population = step.result.evaluated_population;
                 let best_solution = step.result.best_solution;
+                last_best = Some(best_solution.solution.genome.clone());
                 if let Some(king) = &all_time_best {
                     if best_solution.solution.fitness > king.0 {
                         let layout_str = pm.generate_string(&best_solution.solution.genome);
                         let layout = layout_generator.generate(&layout_str).unwrap();
 
                         let evaluation_result = evaluator.evaluate_layout(&layout);
+                        best_cost = evaluation_result.total_cost();
+                        if let Some(constraints) = &constraints {
+                            best_cost =
+                                constraints.penalized(best_cost, &best_solution.solution.genome);
+                        }
+                        stats::record_cost(best_cost);
                         println!(
                             "{}: {} (score: {})\n{}",
                             format!("New best in generation {}:", step.iteration)
@@ -318,6 +769,12 @@ pub fn optimize(
                             format!("{}", evaluation_result.total_cost()).yellow(),
                             layout.plot(),
                         );
+                        if let Some(observer) = &observer {
+                            observer
+                                .lock()
+                                .unwrap()
+                                .on_new_best(&progress(step.iteration, best_cost), &layout_str);
+                        }
 
                         all_time_best = Some((
                             best_solution.solution.fitness,
@@ -325,11 +782,42 @@ pub fn optimize(
                         ));
                     }
                 } else {
+                    // the optimization score is the inverse of the cost
+                    best_cost = 1e8 / best_solution.solution.fitness as f64;
+                    stats::record_cost(best_cost);
+                    if let Some(observer) = &observer {
+                        observer.lock().unwrap().on_new_best(
+                            &progress(step.iteration, best_cost),
+                            &pm.generate_string(&best_solution.solution.genome),
+                        );
+                    }
                     all_time_best = Some((
                         best_solution.solution.fitness,
                         best_solution.solution.genome.clone(),
                     ));
                 }
+                if let Some(history) = &mut history {
+                    let best_layout_str = pm.generate_string(&all_time_best.as_ref().unwrap().1);
+                    let best_layout = layout_generator.generate(&best_layout_str).unwrap();
+                    // the optimization score is the inverse of the cost
+                    let fitness_values = evaluated_population.fitness_values();
+                    let mean_cost = fitness_values.iter().map(|f| 1e8 / *f as f64).sum::<f64>()
+                        / fitness_values.len() as f64;
+                    history.record(
+                        step.iteration,
+                        &evaluator.evaluate_layout(&best_layout),
+                        mean_cost,
+                    );
+                }
+                if let Some(lineage) = &lineage {
+                    // only keep the ancestors of the current population and the best individual
+                    lineage.prune(
+                        evaluated_population
+                            .individuals()
+                            .iter()
+                            .chain(all_time_best.as_ref().map(|(_, g)| g)),
+                    );
+                }
                 log::info!(
                     "{}, average_fitness: {}, \
                      best fitness: {}, all time best: {}, duration: {}, processing_time: {}, generation's best: {}",
@@ -341,8 +829,68 @@ pub fn optimize(
                     step.processing_time.fmt(),
                     pm.generate_string(&best_solution.solution.genome)
                 );
+
+                if let Some(observer) = &observer {
+                    observer
+                        .lock()
+                        .unwrap()
+                        .on_generation(&progress(step.iteration, best_cost));
+                }
+
+                if let (Some(immigration), Some(immigrants)) = (&mut immigration, &immigrants) {
+                    if let Some(shared) = immigration.participant.receive() {
+                        match pm.permutation_for(&shared.layout) {
+                            Some(genome) => {
+                                log::info!(
+                                    "{} {} from {} ({:.4})",
+                                    format!("Generation {}:", step.iteration).yellow().bold(),
+                                    "Immigrant".blue().bold(),
+                                    shared.source,
+                                    shared.cost,
+                                );
+                                let mut immigrants = immigrants.lock().unwrap();
+                                for copy in 0..immigration.copies.max(1) {
+                                    let immigrant = match copy {
+                                        0 => genome.clone(),
+                                        _ => pm.perform_n_swaps(&genome, 1),
+                                    };
+                                    if let Some(lineage) = &lineage {
+                                        lineage.record_seed(
+                                            &immigrant,
+                                            &format!("immigrant from {}", shared.source),
+                                        );
+                                    }
+                                    immigrants.push(immigrant);
+                                }
+                            }
+                            None => log::warn!(
+                                "Skipping immigrant '{}': keys do not match",
+                                shared.layout
+                            ),
+                        }
+                    }
+                }
+
+                let evaluations = evaluations.load(Ordering::Relaxed);
+                if let Some(reason) = stopping_monitor.check(step.iteration, best_cost, evaluations)
+                {
+                    let layout_str = pm.generate_string(&all_time_best.as_ref().unwrap().1);
+                    let layout = layout_generator.generate(&layout_str).unwrap();
+                    println!(
+                        "{} after generation {} ({})\n\n{}\n\n{}\n{}",
+                        "Stopped early".green().bold(),
+                        step.iteration,
+                        reason,
+                        layout_str,
+                        layout.plot_compact(),
+                        layout.plot()
+                    );
+                    stop_reason = reason;
+                    break;
+                }
             }
             Ok(SimResult::Final(step, processing_time, duration, _stop_reason)) => {
+                last_step = step.iteration;
                 let layout_str = pm.generate_string(&all_time_best.as_ref().unwrap().1);
                 let layout = layout_generator.generate(&layout_str).unwrap();
                 println!(
@@ -364,8 +912,71 @@ pub fn optimize(
         }
     }
 
-    let best_layout_str = pm.generate_string(&all_time_best.as_ref().unwrap().1);
+    let mut best_genome = &all_time_best.as_ref().unwrap().1;
+    // the best individual was found at a lower penalty, so the last one may satisfy more
+    // constraints
+    let repaired = constraints.as_ref().map(|constraints| {
+        let mut candidates: Vec<&[usize]> = vec![best_genome];
+        candidates.extend(last_best.as_deref());
+        let (i, repaired, cost) = constraints.best_repaired(&candidates, |genome| {
+            let layout = layout_generator.generate(&pm.generate_string(genome));
+            evaluator.evaluate_layout(&layout.unwrap()).total_cost()
+        });
+        let violations = constraints.violations(candidates[i]);
+        if violations > 0 {
+            log::info!(
+                "Repaired {} violated soft constraints of the final layout",
+                violations
+            );
+        }
+        if i > 0 {
+            best_genome = last_best.as_ref().unwrap();
+        }
+        best_cost = cost;
+        repaired
+    });
+    let best_layout_str = pm.generate_string(repaired.as_ref().unwrap_or(best_genome));
     let best_layout = layout_generator.generate(&best_layout_str).unwrap();
+    // the lineage knows the individual before its repair
+    let lineage_dump = lineage.map(|lineage| lineage.dump(best_genome, &pm));
+    if let Some(history) = &mut history {
+        history.stop_reason = Some(stop_reason);
+    }
+    if let Some(observer) = &observer {
+        observer.lock().unwrap().on_finish(
+            &progress(last_step, best_cost),
+            &best_layout_str,
+            stop_reason,
+        );
+    }
+
+    GeneticResult {
+        layout_str: best_layout_str,
+        layout: best_layout,
+        lineage_dump,
+        history,
+        stop_reason,
+    }
+}
 
-    (best_layout_str, best_layout)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_the_population_beyond_the_given_layouts_with_mutated_copies() {
+        let permutator = LayoutPermutator::new("abcdefghijklmnopqrst", "");
+        let given = permutator.get_permutable_indices();
+        let builder = FromGivenLayoutsBuilder::with_genomes(vec![given.clone()], &permutator, 0.1);
+        let rng = &mut rand::thread_rng();
+
+        assert_eq!(builder.build_genome(0, rng), given);
+        let mutated: Vec<Vec<usize>> = (1..20).map(|i| builder.build_genome(i, rng)).collect();
+        for genome in mutated.iter() {
+            let mut sorted = genome.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, given);
+        }
+        assert!(mutated.iter().any(|genome| *genome != given));
+    }
 }
@@ -0,0 +1,519 @@
+use keyboard_layout::layout_generator::LayoutGenerator;
+use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+
+use layout_optimization_common::{
+    observer::{Budget, Progress, SharedObserver},
+    stats,
+    stopping::{SharedBudget, StopReason, StoppingCriteria},
+    swaps::SwapRestrictions,
+    LayoutPermutator,
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use rand::{seq::SliceRandom, thread_rng};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{
+    fmt,
+    fs::File,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+/// How a climb chooses its next move.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Evaluate all swaps and perform the best one
+    Steepest,
+    /// Evaluate swaps in random order and perform the first improving one
+    FirstImprovement,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "steepest" => Ok(Strategy::Steepest),
+            "first_improvement" => Ok(Strategy::FirstImprovement),
+            _ => Err(format!(
+                "Unknown strategy '{}' (available: steepest, first_improvement)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Strategy::Steepest => "steepest",
+            Strategy::FirstImprovement => "first_improvement",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Parameters {
+    /// Number of climbs (each from its own start)
+    pub starts: usize,
+
+    /// How to choose the next move of a climb
+    pub strategy: Strategy,
+
+    /// End a climb after this many moves even if it did not reach a local optimum
+    #[serde(default)]
+    pub max_moves: Option<u64>,
+
+    /// Restrictions of the keys that are swapped.
+    #[serde(flatten)]
+    pub swap_restrictions: SwapRestrictions,
+
+    /// End the climbs (and skip the remaining ones) once this budget is used up (set by the
+    /// caller, not by configuration)
+    #[serde(skip)]
+    pub budget: Option<SharedBudget>,
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Parameters {
+            starts: 32,
+            strategy: Strategy::Steepest,
+            max_moves: None,
+            swap_restrictions: SwapRestrictions::default(),
+            budget: None,
+        }
+    }
+}
+
+impl Parameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        Ok(serde_yaml::from_reader(f)?)
+    }
+}
+
+/// The outcome of a single climb.
+#[derive(Clone, Debug)]
+pub struct Climb {
+    pub start: String,
+    pub layout: String,
+    pub cost: f64,
+    /// Number of performed (improving) swaps
+    pub moves: u64,
+    pub evaluations: u64,
+    /// Whether no swap improves the layout (otherwise the climb reached `max_moves`)
+    pub local_optimum: bool,
+}
+
+/// A local optimum together with the climbs ending in it.
+#[derive(Clone, Debug)]
+pub struct Basin {
+    pub layout: String,
+    pub cost: f64,
+    /// Number of climbs ending in this local optimum
+    pub hits: usize,
+    /// Mean number of moves of these climbs
+    pub mean_moves: f64,
+}
+
+/// The climbs of a multi-start hill climbing.
+#[derive(Clone, Debug)]
+pub struct ClimbResults {
+    pub climbs: Vec<Climb>,
+}
+
+impl ClimbResults {
+    /// The climb that found the layout with the lowest cost.
+    pub fn best(&self) -> Option<&Climb> {
+        self.climbs
+            .iter()
+            .min_by(|c1, c2| c1.cost.partial_cmp(&c2.cost).unwrap())
+    }
+
+    /// The distinct layouts the climbs ended in, sorted by cost.
+    pub fn basins(&self) -> Vec<Basin> {
+        let mut basins: Vec<Basin> = Vec::new();
+        for climb in self.climbs.iter() {
+            match basins.iter_mut().find(|b| b.layout == climb.layout) {
+                Some(basin) => {
+                    basin.mean_moves = (basin.mean_moves * basin.hits as f64 + climb.moves as f64)
+                        / (basin.hits + 1) as f64;
+                    basin.hits += 1;
+                }
+                None => basins.push(Basin {
+                    layout: climb.layout.clone(),
+                    cost: climb.cost,
+                    hits: 1,
+                    mean_moves: climb.moves as f64,
+                }),
+            }
+        }
+        basins.sort_by(|b1, b2| b1.cost.partial_cmp(&b2.cost).unwrap());
+
+        basins
+    }
+
+    /// Statistics of the reached local optima with the `n_basins` best ones.
+    pub fn summary(&self, n_basins: usize) -> String {
+        let n = self.climbs.len();
+        if n == 0 {
+            return "No climbs".to_string();
+        }
+        let basins = self.basins();
+        let costs: Vec<f64> = self.climbs.iter().map(|c| c.cost).collect();
+        let mean_cost = costs.iter().sum::<f64>() / n as f64;
+        let sd_cost =
+            (costs.iter().map(|c| (c - mean_cost).powi(2)).sum::<f64>() / n as f64).sqrt();
+        let mean_moves = self.climbs.iter().map(|c| c.moves as f64).sum::<f64>() / n as f64;
+
+        let mut lines = vec![
+            format!(
+                "Climbs: {} ({} reached a local optimum), {} evaluations",
+                n,
+                self.climbs.iter().filter(|c| c.local_optimum).count(),
+                self.climbs.iter().map(|c| c.evaluations).sum::<u64>(),
+            ),
+            format!(
+                "Distinct end layouts: {}, best one reached by {} of {} climbs ({:.1}%)",
+                basins.len(),
+                basins[0].hits,
+                n,
+                100.0 * basins[0].hits as f64 / n as f64,
+            ),
+            format!(
+                "Costs: best {:.4}, mean {:.4}, sd {:.4}, worst {:.4}",
+                basins[0].cost,
+                mean_cost,
+                sd_cost,
+                basins[basins.len() - 1].cost,
+            ),
+            format!("Moves per climb: {:.1} on average", mean_moves),
+            String::new(),
+            format!("{:>12}  {:>5}  {:>7}  layout", "cost", "hits", "moves"),
+        ];
+        lines.extend(basins.iter().take(n_basins).map(|basin| {
+            format!(
+                "{:>12.4}  {:>5}  {:>7.1}  {}",
+                basin.cost, basin.hits, basin.mean_moves, basin.layout
+            )
+        }));
+
+        lines.join("\n")
+    }
+}
+
+/// Climb from the given start until no swap improves the cost (or `max_moves` is reached or the
+/// budget is used up).
+fn climb(
+    params: &Parameters,
+    start: Vec<usize>,
+    pm: &LayoutPermutator,
+    swap_pairs: &[(usize, usize)],
+    cost: &(dyn Fn(&[usize]) -> f64 + Sync),
+) -> Climb {
+    let mut current = start;
+    let mut current_cost = cost(&current);
+    let mut evaluations = 1;
+    let mut moves = 0;
+    let start = pm.generate_string(&current);
+
+    let mut candidate_cost = |current: &[usize], (i, j): (usize, usize)| -> f64 {
+        let mut candidate = current.to_vec();
+        candidate.swap(i, j);
+        evaluations += 1;
+        cost(&candidate)
+    };
+
+    let local_optimum = loop {
+        if params
+            .max_moves
+            .map_or(false, |max_moves| moves >= max_moves)
+            || params
+                .budget
+                .as_ref()
+                .map_or(false, |budget| budget.check().is_some())
+        {
+            break false;
+        }
+
+        let improvement = match params.strategy {
+            Strategy::Steepest => swap_pairs
+                .iter()
+                .map(|pair| (candidate_cost(&current, *pair), *pair))
+                .min_by(|(c1, _), (c2, _)| c1.partial_cmp(c2).unwrap()),
+            Strategy::FirstImprovement => {
+                let mut pairs = swap_pairs.to_vec();
+                pairs.shuffle(&mut thread_rng());
+                pairs
+                    .into_iter()
+                    .map(|pair| (candidate_cost(&current, pair), pair))
+                    .find(|(c, _)| *c < current_cost)
+            }
+        };
+
+        match improvement {
+            // small tolerance against loops due to rounding errors
+            Some((c, (i, j))) if c < current_cost - 1e-9 => {
+                current.swap(i, j);
+                current_cost = c;
+                moves += 1;
+            }
+            _ => break true,
+        }
+    };
+
+    Climb {
+        start,
+        layout: pm.generate_string(&current),
+        cost: current_cost,
+        moves,
+        evaluations,
+        local_optimum,
+    }
+}
+
+/// Performs `params.starts` climbs in parallel, starting from the given layouts (as far as they
+/// are permutations of `layout_str` with the same fixed keys) and from random layouts.
+#[allow(clippy::too_many_arguments)]
+pub fn optimize(
+    params: &Parameters,
+    layout_str: &str,
+    fixed_characters: &str,
+    layout_generator: &dyn LayoutGenerator,
+    start_layouts: &[String],
+    evaluator: &Evaluator,
+    result_cache: Option<Cache<f64>>,
+    observer: Option<SharedObserver>,
+) -> ClimbResults {
+    let pm = params
+        .swap_restrictions
+        .permutator(layout_generator, layout_str, fixed_characters);
+    let swap_pairs = pm.swap_pairs();
+
+    let mut starts: Vec<Vec<usize>> = start_layouts
+        .iter()
+        .filter_map(|layout| {
+            let permutation = pm.permutation_for(layout);
+            if permutation.is_none() {
+                log::warn!(
+                    "Skipping start layout {} (not a permutation of {} with fixed keys '{}')",
+                    layout,
+                    layout_str,
+                    fixed_characters
+                );
+            }
+            permutation
+        })
+        .collect();
+    while starts.len() < params.starts {
+        starts.push(pm.generate_random());
+    }
+
+    log::info!(
+        "Starting {} climbs ({} swaps per step) with: {:?}",
+        starts.len(),
+        swap_pairs.len(),
+        params
+    );
+    stats::record_start();
+
+    let evaluations = AtomicU64::new(0);
+    let cost = |permutation: &[usize]| -> f64 {
+        stats::record_evaluation();
+        evaluations.fetch_add(1, Ordering::Relaxed);
+        let layout_string = pm.generate_string(permutation);
+        let evaluate = || {
+            let layout = layout_generator.generate(&layout_string).unwrap();
+            evaluator.evaluate_layout(&layout).total_cost()
+        };
+        match &result_cache {
+            Some(result_cache) => result_cache.get_or_insert_with(&layout_string, evaluate),
+            None => evaluate(),
+        }
+    };
+
+    let start_time = Instant::now();
+    let budget = Budget::new(starts.len() as u64, &StoppingCriteria::default());
+    let progress = |step: u64, best_cost: f64| Progress {
+        run: "hill climbing".to_string(),
+        step,
+        best_cost,
+        evaluations: evaluations.load(Ordering::Relaxed),
+        elapsed: start_time.elapsed(),
+        budget: budget.clone(),
+    };
+
+    let n_starts = starts.len();
+    // number of finished climbs and the lowest cost so far
+    let state = Mutex::new((0u64, f64::INFINITY));
+    let climbs: Vec<Climb> = starts
+        .into_par_iter()
+        .filter_map(|start| {
+            if let Some(reason) = params.budget.as_ref().and_then(|budget| budget.check()) {
+                log::debug!("Skipping climb: {}", reason);
+                return None;
+            }
+            let climb = climb(params, start, &pm, &swap_pairs, &cost);
+
+            let mut state = state.lock().unwrap();
+            state.0 += 1;
+            stats::record_generation(state.0);
+            stats::record_cost(climb.cost);
+            log::info!(
+                "{} {} ({:.4}) after {} moves",
+                format!("Climb {}/{}:", state.0, n_starts).yellow().bold(),
+                climb.layout,
+                climb.cost,
+                climb.moves,
+            );
+            if climb.cost < state.1 {
+                state.1 = climb.cost;
+                log::info!(
+                    "{} {} ({:.4})",
+                    "New best:".green().bold(),
+                    climb.layout,
+                    climb.cost,
+                );
+                if let Some(observer) = &observer {
+                    observer
+                        .lock()
+                        .unwrap()
+                        .on_new_best(&progress(state.0, state.1), &climb.layout);
+                }
+            }
+            if let Some(observer) = &observer {
+                observer
+                    .lock()
+                    .unwrap()
+                    .on_generation(&progress(state.0, state.1));
+            }
+
+            Some(climb)
+        })
+        .collect();
+
+    let results = ClimbResults { climbs };
+    let stop_reason = params
+        .budget
+        .as_ref()
+        .and_then(|budget| budget.check())
+        .unwrap_or(StopReason::Completed);
+    if let (Some(observer), Some(best)) = (&observer, results.best()) {
+        observer.lock().unwrap().on_finish(
+            &progress(n_starts as u64, best.cost),
+            &best.layout,
+            stop_reason,
+        );
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn climb_result(layout: &str, cost: f64, moves: u64) -> Climb {
+        Climb {
+            start: String::new(),
+            layout: layout.to_string(),
+            cost,
+            moves,
+            evaluations: 10,
+            local_optimum: true,
+        }
+    }
+
+    /// Number of pairs of keys that are in the wrong order (zero for the identity permutation)
+    fn inversions(permutation: &[usize]) -> f64 {
+        permutation
+            .iter()
+            .enumerate()
+            .map(|(n, i)| permutation[n + 1..].iter().filter(|j| *j < i).count())
+            .sum::<usize>() as f64
+    }
+
+    #[test]
+    fn basins_group_climbs_by_their_end_layout() {
+        let results = ClimbResults {
+            climbs: vec![
+                climb_result("bca", 2.0, 1),
+                climb_result("abc", 1.0, 2),
+                climb_result("bca", 2.0, 3),
+                climb_result("abc", 1.0, 6),
+                climb_result("abc", 1.0, 1),
+            ],
+        };
+
+        let basins = results.basins();
+        assert_eq!(basins.len(), 2);
+        assert_eq!(basins[0].layout, "abc");
+        assert_eq!(basins[0].hits, 3);
+        assert_eq!(basins[0].mean_moves, 3.0);
+        assert_eq!(basins[1].layout, "bca");
+        assert_eq!(basins[1].hits, 2);
+        assert_eq!(basins[1].mean_moves, 2.0);
+        assert_eq!(results.best().unwrap().layout, "abc");
+
+        let summary = results.summary(1);
+        assert!(summary.contains("Climbs: 5 (5 reached a local optimum), 50 evaluations"));
+        assert!(summary.contains("best one reached by 3 of 5 climbs (60.0%)"));
+        assert!(summary.contains("abc"));
+        assert!(!summary.contains("bca"));
+        assert_eq!(ClimbResults { climbs: vec![] }.summary(1), "No climbs");
+    }
+
+    #[test]
+    fn climbs_reach_the_local_optimum() {
+        let pm = LayoutPermutator::new("abcdef", "");
+        let swap_pairs = pm.swap_pairs();
+        for strategy in [Strategy::Steepest, Strategy::FirstImprovement] {
+            let params = Parameters {
+                strategy,
+                ..Default::default()
+            };
+            let climb = climb(
+                &params,
+                vec![5, 3, 4, 0, 2, 1],
+                &pm,
+                &swap_pairs,
+                &inversions,
+            );
+
+            assert!(climb.local_optimum);
+            assert_eq!(climb.layout, "abcdef");
+            assert_eq!(climb.cost, 0.0);
+            assert_eq!(climb.start, "dfebca");
+            assert!(climb.moves > 0);
+        }
+    }
+
+    #[test]
+    fn climbs_end_after_the_maximal_number_of_moves() {
+        let pm = LayoutPermutator::new("abcdef", "");
+        let params = Parameters {
+            max_moves: Some(1),
+            ..Default::default()
+        };
+        let climb = climb(
+            &params,
+            vec![5, 4, 3, 2, 1, 0],
+            &pm,
+            &pm.swap_pairs(),
+            &inversions,
+        );
+
+        assert!(!climb.local_optimum);
+        assert_eq!(climb.moves, 1);
+        assert!(climb.cost < 15.0);
+    }
+}
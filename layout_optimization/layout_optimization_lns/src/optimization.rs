@@ -0,0 +1,469 @@
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+
+use layout_optimization_common::{
+    observer::{Budget, Progress, SharedObserver},
+    stats,
+    stopping::{StopReason, StoppingCriteria, StoppingMonitor},
+    swaps::SwapRestrictions,
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use itertools::Itertools;
+use rand::prelude::*;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{
+    fs::File,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Parameters {
+    /// Maximum number of destroy-and-repair iterations
+    pub iterations: u64,
+
+    /// Number of symbols removed from the layout in an iteration at first and after each
+    /// improvement
+    #[serde(default = "default_min_destroy")]
+    pub min_destroy: usize,
+
+    /// Largest number of symbols removed from the layout in an iteration
+    #[serde(default = "default_max_destroy")]
+    pub max_destroy: usize,
+
+    /// Number of iterations without improvement after which one more symbol is removed (starting
+    /// over at `min_destroy` after `max_destroy`)
+    #[serde(default = "default_destroy_patience")]
+    pub destroy_patience: u64,
+
+    /// Removed symbols are reinserted optimally (by evaluating all their arrangements) if at
+    /// most this many were removed, and greedily one after the other otherwise
+    #[serde(default = "default_max_optimal_repair")]
+    pub max_optimal_repair: usize,
+
+    /// Restrictions of the keys that are removed and reinserted together.
+    #[serde(flatten)]
+    pub swap_restrictions: SwapRestrictions,
+
+    /// Criteria for ending the optimization before `iterations`.
+    #[serde(default)]
+    pub stopping: StoppingCriteria,
+}
+
+fn default_min_destroy() -> usize {
+    2
+}
+
+fn default_max_destroy() -> usize {
+    8
+}
+
+fn default_destroy_patience() -> u64 {
+    50
+}
+
+fn default_max_optimal_repair() -> usize {
+    4
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Parameters {
+            iterations: 5000,
+            min_destroy: default_min_destroy(),
+            max_destroy: default_max_destroy(),
+            destroy_patience: default_destroy_patience(),
+            max_optimal_repair: default_max_optimal_repair(),
+            swap_restrictions: SwapRestrictions::default(),
+            stopping: StoppingCriteria::default(),
+        }
+    }
+}
+
+impl Parameters {
+    pub fn from_yaml(filename: &str) -> Result<Self> {
+        let f = File::open(filename)?;
+        Ok(serde_yaml::from_reader(f)?)
+    }
+
+    /// Fit the number of iterations to a wall-clock budget of `seconds` at the given evaluation
+    /// rate, assuming that most iterations remove `min_destroy` symbols (as the search starts
+    /// over at `min_destroy` after each improvement).
+    pub fn fit_to_time_budget(&mut self, seconds: f64, evaluations_per_second: f64) {
+        let k = self.min_destroy.max(2);
+        let evaluations_per_iteration = match k <= self.max_optimal_repair {
+            // all arrangements of the removed symbols
+            true => (1..=k).product::<usize>(),
+            // one evaluation per free position for each reinserted symbol
+            false => k * (k + 1) / 2,
+        };
+        let iterations = seconds * evaluations_per_second / evaluations_per_iteration as f64;
+        self.iterations = (iterations as u64).max(1);
+    }
+}
+
+/// The number of removed symbols, growing while the search stagnates.
+struct DestroySize {
+    min: usize,
+    max: usize,
+    patience: u64,
+    current: usize,
+    stall: u64,
+}
+
+impl DestroySize {
+    fn new(params: &Parameters) -> Self {
+        let min = params.min_destroy.max(2);
+        Self {
+            min,
+            max: params.max_destroy.max(min),
+            patience: params.destroy_patience.max(1),
+            current: min,
+            stall: 0,
+        }
+    }
+
+    fn update(&mut self, improved: bool) {
+        if improved {
+            self.current = self.min;
+            self.stall = 0;
+            return;
+        }
+
+        self.stall += 1;
+        if self.stall >= self.patience {
+            self.stall = 0;
+            self.current = match self.current < self.max {
+                true => self.current + 1,
+                false => self.min,
+            };
+        }
+    }
+}
+
+/// Remove `k` random keys (as indices into the permutation) of a random group (chosen
+/// proportionally to its size).
+fn destroy(groups: &[Vec<usize>], k: usize, rng: &mut impl Rng) -> Vec<usize> {
+    let group = groups
+        .choose_weighted(rng, |g| g.len())
+        .expect("No keys to optimize");
+    group
+        .choose_multiple(rng, k.min(group.len()))
+        .copied()
+        .collect()
+}
+
+/// All arrangements of the removed keys on their positions.
+fn optimal_repair(permutation: &[usize], removed: &[usize]) -> Vec<Vec<usize>> {
+    let positions: Vec<usize> = removed.iter().map(|key| permutation[*key]).collect();
+    positions
+        .iter()
+        .permutations(positions.len())
+        .map(|arrangement| {
+            let mut candidate = permutation.to_vec();
+            for (key, position) in removed.iter().zip(arrangement) {
+                candidate[*key] = *position;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// Reinsert the removed keys one after the other (in random order), each at the free position
+/// that gives the lowest cost. Keys that are not reinserted yet stay at their positions if
+/// possible and fill the remaining free positions otherwise.
+fn greedy_repair(
+    permutation: &[usize],
+    removed: &[usize],
+    cost: &(dyn Fn(&[usize]) -> f64 + Sync),
+    rng: &mut impl Rng,
+) -> (Vec<usize>, f64) {
+    let mut order = removed.to_vec();
+    order.shuffle(rng);
+
+    let arrange = |placed: &[(usize, usize)], pending: &[usize]| -> Vec<usize> {
+        let mut candidate = permutation.to_vec();
+        let taken: Vec<usize> = placed.iter().map(|(_, position)| *position).collect();
+        let mut free = removed
+            .iter()
+            .map(|key| permutation[*key])
+            .filter(|position| !taken.contains(position));
+        let displaced: Vec<usize> = pending
+            .iter()
+            .filter(|key| taken.contains(&permutation[**key]))
+            .copied()
+            .collect();
+        let kept: Vec<usize> = pending
+            .iter()
+            .map(|key| permutation[*key])
+            .filter(|position| !taken.contains(position))
+            .collect();
+        for key in displaced {
+            candidate[key] = free.find(|position| !kept.contains(position)).unwrap();
+        }
+        for (key, position) in placed {
+            candidate[*key] = *position;
+        }
+        candidate
+    };
+
+    let mut placed: Vec<(usize, usize)> = Vec::with_capacity(order.len());
+    let mut best = (permutation.to_vec(), f64::INFINITY);
+    for (n, key) in order.iter().enumerate() {
+        let pending = &order[n + 1..];
+        let free_positions: Vec<usize> = removed
+            .iter()
+            .map(|k| permutation[*k])
+            .filter(|position| placed.iter().all(|(_, p)| p != position))
+            .collect();
+        best = free_positions
+            .par_iter()
+            .map(|position| {
+                let mut candidate_placed = placed.clone();
+                candidate_placed.push((*key, *position));
+                let candidate = arrange(&candidate_placed, pending);
+                let candidate_cost = cost(&candidate);
+                (candidate, candidate_cost)
+            })
+            .min_by(|(_, c1), (_, c2)| c1.partial_cmp(c2).unwrap())
+            .unwrap();
+        placed.push((*key, best.0[*key]));
+    }
+
+    best
+}
+
+/// Performs one run of Large Neighborhood Search, then returns the best layout found.
+#[allow(clippy::too_many_arguments)]
+pub fn optimize(
+    params: &Parameters,
+    layout_str: &str,
+    fixed_characters: &str,
+    layout_generator: &dyn LayoutGenerator,
+    start_with_layout: bool,
+    evaluator: &Evaluator,
+    result_cache: Option<Cache<f64>>,
+    observer: Option<SharedObserver>,
+) -> (String, Layout, StopReason) {
+    let pm = params
+        .swap_restrictions
+        .permutator(layout_generator, layout_str, fixed_characters);
+    let groups: Vec<Vec<usize>> = pm
+        .key_groups()
+        .into_iter()
+        .filter(|g| g.len() > 1)
+        .collect();
+
+    let evaluations = AtomicU64::new(0);
+    let cost = |permutation: &[usize]| -> f64 {
+        stats::record_evaluation();
+        evaluations.fetch_add(1, Ordering::Relaxed);
+        let layout_string = pm.generate_string(permutation);
+        let evaluate = || {
+            let layout = layout_generator.generate(&layout_string).unwrap();
+            evaluator.evaluate_layout(&layout).total_cost()
+        };
+        match &result_cache {
+            Some(result_cache) => result_cache.get_or_insert_with(&layout_string, evaluate),
+            None => evaluate(),
+        }
+    };
+
+    log::info!("Starting optimization with: {:?}", params);
+    stats::record_start();
+    let start = Instant::now();
+    let budget = Budget::new(params.iterations, &params.stopping);
+    let progress = |step: u64, best_cost: f64| Progress {
+        run: "large neighborhood search".to_string(),
+        step,
+        best_cost,
+        evaluations: evaluations.load(Ordering::Relaxed),
+        elapsed: start.elapsed(),
+        budget: budget.clone(),
+    };
+    let mut stopping_monitor = StoppingMonitor::new(&params.stopping);
+    let mut stop_reason = StopReason::Completed;
+
+    let mut current = match start_with_layout {
+        true => pm.get_permutable_indices(),
+        false => pm.generate_random(),
+    };
+    let mut current_cost = cost(&current);
+    let mut best_cost = current_cost;
+    let mut destroy_size = DestroySize::new(params);
+
+    let rng = &mut thread_rng();
+    let mut last_iteration = 0;
+    for iteration in 1..=params.iterations {
+        if groups.is_empty() {
+            log::warn!("No keys to optimize");
+            break;
+        }
+        last_iteration = iteration;
+
+        let removed = destroy(&groups, destroy_size.current, rng);
+        let (candidate, candidate_cost) = match removed.len() <= params.max_optimal_repair {
+            true => optimal_repair(&current, &removed)
+                .into_par_iter()
+                .map(|candidate| {
+                    let candidate_cost = cost(&candidate);
+                    (candidate, candidate_cost)
+                })
+                .min_by(|(_, c1), (_, c2)| c1.partial_cmp(c2).unwrap())
+                .unwrap(),
+            false => greedy_repair(&current, &removed, &cost, rng),
+        };
+
+        // moves to layouts of equal cost are accepted to drift across plateaus
+        let accepted = candidate_cost <= current_cost;
+        stats::record_proposal(accepted);
+        destroy_size.update(candidate_cost < current_cost);
+        if accepted {
+            current = candidate;
+            current_cost = candidate_cost;
+        }
+
+        stats::record_generation(iteration);
+        if current_cost < best_cost {
+            best_cost = current_cost;
+            stats::record_cost(best_cost);
+            let best_layout_str = pm.generate_string(&current);
+            log::info!(
+                "{} {} ({:.4}, {} symbols removed)",
+                format!("New best in iteration {}:", iteration)
+                    .green()
+                    .bold(),
+                best_layout_str,
+                best_cost,
+                removed.len(),
+            );
+            if let Some(observer) = &observer {
+                observer
+                    .lock()
+                    .unwrap()
+                    .on_new_best(&progress(iteration, best_cost), &best_layout_str);
+            }
+        }
+
+        log::debug!(
+            "{} current: {:.4}, best: {:.4}, symbols to remove: {}",
+            format!("Iteration {}:", iteration).yellow().bold(),
+            current_cost,
+            best_cost,
+            destroy_size.current,
+        );
+        if let Some(observer) = &observer {
+            observer
+                .lock()
+                .unwrap()
+                .on_generation(&progress(iteration, best_cost));
+        }
+
+        if let Some(reason) =
+            stopping_monitor.check(iteration, best_cost, evaluations.load(Ordering::Relaxed))
+        {
+            log::info!("Stopped early after iteration {} ({})", iteration, reason);
+            stop_reason = reason;
+            break;
+        }
+    }
+
+    // the current layout is always the best one as no worsening moves are accepted
+    let best_layout_str = pm.generate_string(&current);
+    let best_layout = layout_generator.generate(&best_layout_str).unwrap();
+    if let Some(observer) = &observer {
+        observer.lock().unwrap().on_finish(
+            &progress(last_iteration, best_cost),
+            &best_layout_str,
+            stop_reason,
+        );
+    }
+
+    (best_layout_str, best_layout, stop_reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_permutation_of(candidate: &[usize], permutation: &[usize]) -> bool {
+        let mut sorted_candidate = candidate.to_vec();
+        sorted_candidate.sort_unstable();
+        let mut sorted = permutation.to_vec();
+        sorted.sort_unstable();
+        sorted_candidate == sorted
+    }
+
+    #[test]
+    fn destroy_size_grows_while_stalling() {
+        let params = Parameters {
+            min_destroy: 2,
+            max_destroy: 3,
+            destroy_patience: 2,
+            ..Default::default()
+        };
+        let mut size = DestroySize::new(&params);
+        assert_eq!(size.current, 2);
+
+        size.update(false);
+        assert_eq!(size.current, 2);
+        size.update(false);
+        assert_eq!(size.current, 3);
+
+        // starts over after the largest size
+        size.update(false);
+        size.update(false);
+        assert_eq!(size.current, 2);
+
+        size.update(false);
+        size.update(false);
+        assert_eq!(size.current, 3);
+        size.update(true);
+        assert_eq!(size.current, 2);
+        assert_eq!(size.stall, 0);
+    }
+
+    #[test]
+    fn optimal_repair_arranges_the_removed_keys_in_all_ways() {
+        let permutation = vec![4, 1, 3, 0, 2];
+        let candidates = optimal_repair(&permutation, &[0, 2, 4]);
+
+        assert_eq!(candidates.len(), 6);
+        for candidate in candidates.iter() {
+            assert!(is_permutation_of(candidate, &permutation));
+            assert_eq!(candidate[1], 1);
+            assert_eq!(candidate[3], 0);
+        }
+        let distinct: Vec<&Vec<usize>> = candidates.iter().unique().collect();
+        assert_eq!(distinct.len(), 6);
+        assert!(candidates.contains(&permutation));
+    }
+
+    #[test]
+    fn greedy_repair_keeps_a_valid_permutation() {
+        let permutation: Vec<usize> = (0..8).rev().collect();
+        let removed = vec![0, 2, 3, 5, 7];
+        // prefers keys at their own index
+        let cost = |candidate: &[usize]| -> f64 {
+            candidate
+                .iter()
+                .enumerate()
+                .filter(|(key, position)| key != *position)
+                .count() as f64
+        };
+
+        let rng = &mut thread_rng();
+        for _ in 0..20 {
+            let (candidate, candidate_cost) = greedy_repair(&permutation, &removed, &cost, rng);
+
+            assert!(is_permutation_of(&candidate, &permutation));
+            assert_eq!(candidate_cost, cost(&candidate));
+            for key in (0..8).filter(|key| !removed.contains(key)) {
+                assert_eq!(candidate[key], permutation[key]);
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Progressive freezing of keys during annealing. Keys whose position stayed the same over many
+//! accepted states are excluded from further swaps, which shrinks the search space over time and
+//! leaves the end of a run to the remaining contentious keys.
+
+use layout_optimization_common::LayoutPermutator;
+
+use colored::Colorize;
+use serde::Deserialize;
+use std::cmp::Reverse;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct FreezingParameters {
+    /// Freeze a key once its position stayed the same over this many accepted states
+    pub stable_states: u64,
+
+    /// Do not freeze keys before this fraction of `max_iters`
+    #[serde(default = "default_start_fraction")]
+    pub start_fraction: f64,
+
+    /// Always keep at least this many keys unfrozen
+    #[serde(default = "default_min_active_keys")]
+    pub min_active_keys: usize,
+}
+
+fn default_start_fraction() -> f64 {
+    0.25
+}
+
+fn default_min_active_keys() -> usize {
+    6
+}
+
+impl Default for FreezingParameters {
+    fn default() -> Self {
+        FreezingParameters {
+            stable_states: 500,
+            start_fraction: default_start_fraction(),
+            min_active_keys: default_min_active_keys(),
+        }
+    }
+}
+
+/// Tracks how long each key has kept its position and provides the permutator for the keys that
+/// are not frozen yet.
+pub struct Freezer {
+    id: String,
+    params: FreezingParameters,
+    base: LayoutPermutator,
+    active: LayoutPermutator,
+    start_iter: u64,
+    iter: u64,
+    last: Option<Vec<usize>>,
+    /// Number of accepted states since the last move of each key
+    stable: Vec<u64>,
+    frozen: Vec<usize>,
+}
+
+impl Freezer {
+    pub fn new(
+        id: &str,
+        params: &FreezingParameters,
+        permutator: &LayoutPermutator,
+        max_iters: u64,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            params: params.clone(),
+            base: permutator.clone(),
+            active: permutator.clone(),
+            start_iter: (max_iters as f64 * params.start_fraction) as u64,
+            iter: 0,
+            last: None,
+            stable: vec![0; permutator.get_permutable_indices().len()],
+            frozen: Vec::new(),
+        }
+    }
+
+    /// The permutator that only swaps keys that are not frozen.
+    pub fn permutator(&self) -> &LayoutPermutator {
+        &self.active
+    }
+
+    /// The frozen keys (as indices into the permutation) in the order they were frozen.
+    pub fn frozen_keys(&self) -> &[usize] {
+        &self.frozen
+    }
+
+    /// Register the current state of the annealing (once per iteration) and freeze the keys that
+    /// have been stable for long enough.
+    pub fn observe(&mut self, current: &[usize]) {
+        self.iter += 1;
+        match &self.last {
+            Some(last) if last.as_slice() == current => return,
+            Some(last) => last
+                .iter()
+                .zip(current.iter())
+                .zip(self.stable.iter_mut())
+                .for_each(|((before, after), stable)| match before == after {
+                    true => *stable += 1,
+                    false => *stable = 0,
+                }),
+            None => {}
+        }
+        self.last = Some(current.to_vec());
+        if self.iter < self.start_iter {
+            return;
+        }
+
+        let n_active = self.stable.len() - self.frozen.len();
+        let capacity = n_active.saturating_sub(self.params.min_active_keys);
+        let mut candidates: Vec<usize> = (0..self.stable.len())
+            .filter(|n| self.stable[*n] >= self.params.stable_states && !self.frozen.contains(n))
+            .collect();
+        if candidates.is_empty() || capacity == 0 {
+            return;
+        }
+        candidates.sort_by_key(|n| Reverse(self.stable[*n]));
+        candidates.truncate(capacity);
+
+        let mut frozen = self.frozen.clone();
+        frozen.extend(candidates.iter());
+        if let Some(active) = self.base.without_keys(&frozen) {
+            let layout: Vec<char> = self.base.generate_string(current).chars().collect();
+            log::info!(
+                "{} Froze '{}' after iteration {} ({} keys left)",
+                format!("{}:", self.id).yellow().bold(),
+                candidates
+                    .iter()
+                    .map(|n| layout[current[*n]])
+                    .collect::<String>(),
+                self.iter,
+                self.stable.len() - frozen.len(),
+            );
+            self.active = active;
+            self.frozen = frozen;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn freezer(stable_states: u64, min_active_keys: usize) -> Freezer {
+        let params = FreezingParameters {
+            stable_states,
+            start_fraction: 0.5,
+            min_active_keys,
+        };
+        Freezer::new("test", &params, &LayoutPermutator::new("abcdefgh", ""), 20)
+    }
+
+    /// Swap the keys of the given pair in `permutation` and let the freezer observe the result.
+    fn swap_and_observe(freezer: &mut Freezer, permutation: &mut [usize], (i, j): (usize, usize)) {
+        permutation.swap(i, j);
+        freezer.observe(permutation);
+    }
+
+    #[test]
+    fn freezes_keys_that_kept_their_position() {
+        let mut freezer = freezer(3, 2);
+        let mut permutation: Vec<usize> = (0..8).collect();
+        freezer.observe(&permutation);
+
+        // only keys 0 and 1 move, but not before half of the iterations
+        for _ in 0..8 {
+            swap_and_observe(&mut freezer, &mut permutation, (0, 1));
+        }
+        assert!(freezer.frozen_keys().is_empty());
+
+        swap_and_observe(&mut freezer, &mut permutation, (0, 1));
+        let mut frozen = freezer.frozen_keys().to_vec();
+        frozen.sort_unstable();
+        assert_eq!(frozen, vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(freezer.permutator().swap_pairs(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn repeated_states_do_not_count_as_stable() {
+        let mut freezer = freezer(3, 2);
+        let permutation: Vec<usize> = (0..8).collect();
+        for _ in 0..20 {
+            freezer.observe(&permutation);
+        }
+        assert!(freezer.frozen_keys().is_empty());
+    }
+
+    #[test]
+    fn keeps_the_minimal_number_of_active_keys() {
+        let mut freezer = freezer(3, 5);
+        let mut permutation: Vec<usize> = (0..8).collect();
+        freezer.observe(&permutation);
+
+        for n in 0..20 {
+            // key 0 moves in every state, key 1 in every other one, the others never
+            let other = if n % 2 == 0 { 1 } else { 7 };
+            swap_and_observe(&mut freezer, &mut permutation, (0, other));
+        }
+
+        assert_eq!(freezer.frozen_keys().len(), 3);
+        assert!(!freezer.frozen_keys().contains(&0));
+        assert!(!freezer.frozen_keys().contains(&1));
+        assert!(!freezer.frozen_keys().contains(&7));
+        assert_eq!(freezer.permutator().key_groups()[0].len(), 5);
+    }
+}
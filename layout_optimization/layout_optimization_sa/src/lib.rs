@@ -1,4 +1,7 @@
+pub mod freezing;
 pub mod optimization;
+pub mod restarts;
+pub mod schedule;
 
 #[cfg(test)]
 mod tests {
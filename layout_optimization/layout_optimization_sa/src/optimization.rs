@@ -1,13 +1,34 @@
+use crate::{
+    freezing::{Freezer, FreezingParameters},
+    restarts::RestartParameters,
+    schedule::SchedulePreset,
+};
+
 use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
-use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+use layout_evaluation::{cache::Cache, evaluation::Evaluator, results::EvaluationResult};
 
-use layout_optimization_common::LayoutPermutator;
+use layout_optimization_common::{
+    constraints::{constrained_permutator, SoftConstraintParameters, SoftConstraints},
+    history::ConvergenceHistory,
+    observer::{Budget, Progress, SharedObserver},
+    stats,
+    stopping::{StopReason, StoppingCriteria, StoppingMonitor},
+    swaps::SwapRestrictions,
+    LayoutPermutator, MirrorCanonicalizer,
+};
 
 use anyhow::Result;
 use colored::Colorize;
 use rand_xoshiro::{rand_core::SeedableRng, Xoshiro256PlusPlus};
 use serde::Deserialize;
-use std::{fs::File, sync::Arc};
+use std::{
+    fs::File,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
 
 use argmin::{
     core::{
@@ -17,7 +38,7 @@ use argmin::{
     solver::simulatedannealing::{Anneal, SATempFunc, SimulatedAnnealing},
 };
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Clone, Debug)]
 pub struct Parameters {
     /// Initial temperature. Gets eventually lowered down to (almost) zero during optimization.
     pub init_temp: Option<f64>,
@@ -27,11 +48,91 @@ pub struct Parameters {
 
     // Parameters for the solver.
     /// Stop if there was no accepted solution after this many iterations
+    #[serde(default = "default_stall_accepted")]
     pub stall_accepted: u64,
 
+    /// Factor the temperature gets multiplied with in each iteration
+    #[serde(default = "default_cooling_rate")]
+    pub cooling_rate: f64,
+
     // Parameters for the [Executor].
-    /// Set maximum number of iterations (defaults to `std::u64::MAX`)
+    /// Set maximum number of iterations
+    #[serde(default = "default_max_iters")]
     pub max_iters: u64,
+
+    /// If set, derive `init_temp` and `cooling_rate` from short probe chains before optimizing
+    #[serde(default)]
+    pub auto_tune: Option<AutoTuneParameters>,
+
+    /// If set, derive the schedule (`auto_tune`, `max_iters`, and `stall_accepted`) from this
+    /// preset (see [`Parameters::apply_preset`]). Overrides the corresponding settings.
+    #[serde(default)]
+    pub preset: Option<SchedulePreset>,
+
+    /// Evaluation budget of the preset (defaults to the preset's own budget)
+    #[serde(default)]
+    pub budget: Option<u64>,
+
+    /// Evaluate mirror-equivalent layouts as their canonical representative, so that they share
+    /// one entry of the result cache (see [`MirrorCanonicalizer`]). Requires symmetric metrics.
+    #[serde(default)]
+    pub canonicalize_mirrors: bool,
+
+    /// Restrictions of the keys that are swapped.
+    #[serde(flatten)]
+    pub swap_restrictions: SwapRestrictions,
+
+    /// If set, treat these constraints as soft: the search may violate them at a growing penalty
+    /// (see [`SoftConstraintParameters`])
+    #[serde(default)]
+    pub soft_constraints: Option<SoftConstraintParameters>,
+
+    /// If set, progressively exclude keys from swaps whose position has been stable over many
+    /// accepted states (see [`FreezingParameters`])
+    #[serde(default)]
+    pub freezing: Option<FreezingParameters>,
+
+    /// Record the best and mean costs every `history_bucket` iterations (see
+    /// [`ConvergenceHistory`]).
+    #[serde(default)]
+    pub record_history: bool,
+
+    #[serde(default = "default_history_bucket")]
+    pub history_bucket: u64,
+
+    /// If set, run several chains and report the best layout of all chains (see
+    /// [`crate::restarts`])
+    #[serde(default)]
+    pub restarts: Option<RestartParameters>,
+
+    /// Criteria for ending the optimization before `max_iters` or `stall_accepted`.
+    #[serde(default)]
+    pub stopping: StoppingCriteria,
+}
+
+/// Settings of a configuration file that are overridden by a [`SchedulePreset`]
+const PRESET_SETTINGS: [&str; 5] = [
+    "init_temp",
+    "cooling_rate",
+    "max_iters",
+    "stall_accepted",
+    "auto_tune",
+];
+
+fn default_cooling_rate() -> f64 {
+    0.998
+}
+
+fn default_stall_accepted() -> u64 {
+    5000
+}
+
+fn default_max_iters() -> u64 {
+    100_000
+}
+
+fn default_history_bucket() -> u64 {
+    100
 }
 
 impl Default for Parameters {
@@ -40,9 +141,51 @@ impl Default for Parameters {
             init_temp: Some(150.0),
             key_switches: 1,
             // Parameters for the solver.
-            stall_accepted: 5000,
+            stall_accepted: default_stall_accepted(),
+            cooling_rate: default_cooling_rate(),
             // Parameters for the [Executor].
-            max_iters: 100_000,
+            max_iters: default_max_iters(),
+            auto_tune: None,
+            preset: None,
+            budget: None,
+            canonicalize_mirrors: false,
+            swap_restrictions: SwapRestrictions::default(),
+            soft_constraints: None,
+            freezing: None,
+            record_history: false,
+            history_bucket: default_history_bucket(),
+            restarts: None,
+            stopping: StoppingCriteria::default(),
+        }
+    }
+}
+
+/// Parameters for estimating the annealing schedule from probe chains.
+///
+/// Note that the solver accepts a worse layout with probability `1 / (1 + exp(Δ / T))`,
+/// so acceptance ratios of uphill moves are always below 0.5.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AutoTuneParameters {
+    /// Number of independent probe chains (random walks)
+    pub probe_chains: usize,
+
+    /// Number of moves per probe chain
+    pub probe_length: usize,
+
+    /// Targeted acceptance ratio of uphill moves at the start of the optimization
+    pub initial_acceptance: f64,
+
+    /// Targeted acceptance ratio of uphill moves after `max_iters` iterations
+    pub final_acceptance: f64,
+}
+
+impl Default for AutoTuneParameters {
+    fn default() -> Self {
+        AutoTuneParameters {
+            probe_chains: 4,
+            probe_length: 50,
+            initial_acceptance: 0.4,
+            final_acceptance: 0.001,
         }
     }
 }
@@ -50,8 +193,58 @@ impl Default for Parameters {
 impl Parameters {
     pub fn from_yaml(filename: &str) -> Result<Self> {
         let f = File::open(filename)?;
-        Ok(serde_yaml::from_reader(f)?)
+        let value: serde_yaml::Value = serde_yaml::from_reader(f)?;
+        let mut params: Self = serde_yaml::from_value(value.clone())?;
+
+        if let Some(preset) = params.preset {
+            let overridden: Vec<&str> = PRESET_SETTINGS
+                .iter()
+                .filter(|key| value.get(key).map_or(false, |v| !v.is_null()))
+                .cloned()
+                .collect();
+            if !overridden.is_empty() {
+                log::warn!(
+                    "The schedule preset '{}' overrides {} configured in {}",
+                    preset,
+                    overridden.join(", "),
+                    filename
+                );
+            }
+        }
+
+        params.apply_preset();
+        Ok(params)
+    }
+
+    /// Overwrite the schedule with the one of the [`SchedulePreset`] (if set) for the
+    /// evaluation budget.
+    pub fn apply_preset(&mut self) {
+        if let Some(preset) = self.preset {
+            preset.apply(self, self.budget);
+        }
+    }
+
+    /// Fit the schedule to a wall-clock budget of `seconds` at the given evaluation rate (one
+    /// evaluation per iteration). A preset is spread over the evaluations fitting into the
+    /// budget. Otherwise `max_iters` is set to these evaluations and the cooling rate is
+    /// adjusted such that the final temperature stays the same (auto-tuning derives its cooling
+    /// rate from `max_iters` anyway).
+    pub fn fit_to_time_budget(&mut self, seconds: f64, evaluations_per_second: f64) {
+        let iterations = ((seconds * evaluations_per_second) as u64).max(1);
+        if self.preset.is_some() {
+            self.budget = Some(iterations);
+            self.apply_preset();
+            return;
+        }
+
+        if self.auto_tune.is_none() {
+            self.cooling_rate = self
+                .cooling_rate
+                .powf(self.max_iters as f64 / iterations as f64);
+        }
+        self.max_iters = iterations;
     }
+
     /// Makes sure that [self.init_temp] is greater than zero.
     /// => Negative values and zero get turned into `f64::MIN_POSITIVE`.
     pub fn correct_init_temp(&mut self) {
@@ -72,6 +265,13 @@ pub struct AnnealingStruct {
     layout_generator: Box<dyn LayoutGenerator>,
     key_switches: usize,
     result_cache: Option<Cache<f64>>,
+    canonicalizer: Option<MirrorCanonicalizer>,
+    /// Number of evaluations of this optimization
+    evaluations: Arc<AtomicU64>,
+    freezer: Option<Arc<Mutex<Freezer>>>,
+    /// Soft constraints, whose penalty grows with the evaluations relative to `max_iters`
+    constraints: Option<SoftConstraints>,
+    max_iters: u64,
 }
 
 impl CostFunction for AnnealingStruct {
@@ -80,19 +280,30 @@ impl CostFunction for AnnealingStruct {
 
     /// Evaluate param (= the layout-vector).
     fn cost(&self, param: &Self::Param) -> Result<Self::Output, Error> {
+        stats::record_evaluation();
+        let evaluations = self.evaluations.fetch_add(1, Ordering::Relaxed) + 1;
         let evaluate_layout_str = |layout_str: &str| -> f64 {
             let l = self.layout_generator.generate(layout_str).unwrap();
             self.evaluator.evaluate_layout(&l).total_cost()
         };
 
-        let layout_string = self.permutator.generate_string(param);
+        let mut layout_string = self.permutator.generate_string(param);
+        if let Some(canonicalizer) = &self.canonicalizer {
+            layout_string = canonicalizer.canonicalize(&layout_string);
+        }
         let evaluation_result = match &self.result_cache {
             Some(result_cache) => result_cache
                 .get_or_insert_with(&layout_string, || evaluate_layout_str(&layout_string)),
             None => evaluate_layout_str(&layout_string),
         };
 
-        Ok(evaluation_result)
+        Ok(match &self.constraints {
+            Some(constraints) => {
+                constraints.set_progress(evaluations as f64 / self.max_iters.max(1) as f64);
+                constraints.penalized(evaluation_result, param)
+            }
+            None => evaluation_result,
+        })
     }
 }
 
@@ -103,7 +314,16 @@ impl Anneal for AnnealingStruct {
 
     /// Anneal a parameter vector, slightly changing it.
     fn anneal(&self, param: &Self::Param, _temp: f64) -> Result<Self::Output, Error> {
-        Ok(self.permutator.perform_n_swaps(param, self.key_switches))
+        match &self.freezer {
+            Some(freezer) => {
+                let mut freezer = freezer.lock().unwrap();
+                freezer.observe(param);
+                Ok(freezer
+                    .permutator()
+                    .perform_n_swaps(param, self.key_switches))
+            }
+            None => Ok(self.permutator.perform_n_swaps(param, self.key_switches)),
+        }
     }
 }
 
@@ -144,6 +364,148 @@ impl Observe<SaIterState> for CustomObserver {
     }
 }
 
+/// An observer that records the progress in the process-wide optimization statistics.
+struct StatsObserver;
+
+impl Observe<SaIterState> for StatsObserver {
+    fn observe_iter(&mut self, state: &SaIterState, kv: &KV) -> Result<(), Error> {
+        stats::record_generation(state.iter);
+        stats::record_cost(state.best_cost);
+        if state.iter > 0 {
+            for (key, value) in &kv.kv {
+                if *key == "acc" {
+                    stats::record_proposal(matches!(value.to_string().as_str(), "true"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of an optimization that was stopped early, which is not available from the
+/// executor's result as stopping aborts the executor.
+#[derive(Default)]
+struct StoppingOutcome {
+    reason: Option<StopReason>,
+    best_param: Option<Vec<usize>>,
+    best_cost: Option<f64>,
+    iter: u64,
+}
+
+/// An observer that stops the optimization (by returning an error) as soon as one of the
+/// [`StoppingCriteria`] is met.
+struct StoppingObserver {
+    monitor: StoppingMonitor,
+    evaluations: Arc<AtomicU64>,
+    outcome: Arc<Mutex<StoppingOutcome>>,
+}
+
+impl Observe<SaIterState> for StoppingObserver {
+    fn observe_iter(&mut self, state: &SaIterState, _kv: &KV) -> Result<(), Error> {
+        let mut outcome = self.outcome.lock().unwrap();
+        if outcome
+            .best_cost
+            .map_or(true, |cost| state.best_cost < cost)
+        {
+            outcome.best_cost = Some(state.best_cost);
+            outcome.best_param = state.best_param.clone();
+        }
+        outcome.iter = state.iter;
+
+        let evaluations = self.evaluations.load(Ordering::Relaxed);
+        match self.monitor.check(state.iter, state.best_cost, evaluations) {
+            Some(reason) => {
+                outcome.reason = Some(reason);
+                Err(Error::msg(format!("Stopped early: {}", reason)))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// An observer that forwards the progress to an [`OptimizationObserver`].
+///
+/// [`OptimizationObserver`]: layout_optimization_common::observer::OptimizationObserver
+struct ProgressObserver {
+    observer: SharedObserver,
+    run: String,
+    budget: Budget,
+    start: Instant,
+    evaluations: Arc<AtomicU64>,
+    best_cost: f64,
+    permutator: LayoutPermutator,
+}
+
+impl ProgressObserver {
+    fn progress(&self, step: u64, best_cost: f64) -> Progress {
+        Progress {
+            run: self.run.clone(),
+            step,
+            best_cost,
+            evaluations: self.evaluations.load(Ordering::Relaxed),
+            elapsed: self.start.elapsed(),
+            budget: self.budget.clone(),
+        }
+    }
+}
+
+impl Observe<SaIterState> for ProgressObserver {
+    fn observe_iter(&mut self, state: &SaIterState, _kv: &KV) -> Result<(), Error> {
+        let progress = self.progress(state.iter, state.best_cost);
+        let mut observer = self.observer.lock().unwrap();
+        if state.best_cost < self.best_cost {
+            self.best_cost = state.best_cost;
+            if let Some(best_param) = &state.best_param {
+                observer.on_new_best(&progress, &self.permutator.generate_string(best_param));
+            }
+        }
+        observer.on_generation(&progress);
+        Ok(())
+    }
+}
+
+/// The convergence history together with the costs of the layouts visited since its last entry.
+#[derive(Default)]
+struct HistoryRecorder {
+    history: ConvergenceHistory,
+    cost_sum: f64,
+    n_costs: u64,
+}
+
+impl HistoryRecorder {
+    fn record(&mut self, step: u64, best: &EvaluationResult) {
+        let mean_cost = self.cost_sum / self.n_costs.max(1) as f64;
+        self.history.record(step, best, mean_cost);
+        self.cost_sum = 0.0;
+        self.n_costs = 0;
+    }
+}
+
+/// An observer that records the convergence history with an entry after each bucket of iterations.
+struct HistoryObserver {
+    recorder: Arc<Mutex<HistoryRecorder>>,
+    bucket: u64,
+    evaluator: Arc<Evaluator>,
+    permutator: LayoutPermutator,
+    layout_generator: Box<dyn LayoutGenerator>,
+}
+
+impl Observe<SaIterState> for HistoryObserver {
+    fn observe_iter(&mut self, state: &SaIterState, _kv: &KV) -> Result<(), Error> {
+        let mut recorder = self.recorder.lock().unwrap();
+        recorder.cost_sum += state.cost;
+        recorder.n_costs += 1;
+        if state.iter % self.bucket.max(1) == 0 {
+            if let Some(best_param) = &state.best_param {
+                let best_layout_str = self.permutator.generate_string(best_param);
+                let best_layout = self.layout_generator.generate(&best_layout_str).unwrap();
+                recorder.record(state.iter, &self.evaluator.evaluate_layout(&best_layout));
+            }
+        }
+        Ok(())
+    }
+}
+
 /// An observer that outputs important information in a more human-readable format than `Argmin`'s original implementation.
 struct IterationObserver {
     id: String,
@@ -224,7 +586,8 @@ fn mean(list: &[f64]) -> f64 {
 ///
 /// This value can then be used as the initial temperature in Simulated annealing.
 /// Reference: https://link.springer.com/content/pdf/10.1007/s10732-007-9012-8.pdf
-fn get_cost_sd(
+#[allow(clippy::borrowed_box)]
+pub fn get_cost_sd(
     initial_indices: &[usize],
     evaluator: Arc<Evaluator>,
     permutator: &LayoutPermutator,
@@ -257,8 +620,90 @@ fn get_cost_sd(
     sd
 }
 
+/// Acceptance ratio of the given (uphill) cost differences at temperature `temp`.
+pub fn acceptance_ratio(deltas: &[f64], temp: f64) -> f64 {
+    let accepted: f64 = deltas
+        .iter()
+        .map(|delta| 1.0 / (1.0 + (delta / temp).exp()))
+        .sum();
+    accepted / (deltas.len() as f64)
+}
+
+/// Finds the temperature for which the given (uphill) cost differences are accepted with
+/// the targeted ratio using a bisection in log-space.
+fn temperature_for_acceptance(deltas: &[f64], target: f64) -> f64 {
+    let max_delta = deltas.iter().cloned().fold(f64::MIN_POSITIVE, f64::max);
+    let target = target.clamp(1e-9, 0.5 - 1e-9);
+
+    let mut low = (max_delta * 1e-9).ln();
+    let mut high = (max_delta * 1e9).ln();
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        if acceptance_ratio(deltas, mid.exp()) < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    (0.5 * (low + high)).exp()
+}
+
+/// Runs short random walks through the neighborhood of the initial layout and returns the cost
+/// differences of all uphill moves.
+pub fn probe_uphill_deltas(
+    auto_tune_params: &AutoTuneParameters,
+    initial_indices: &[usize],
+    evaluator: &Evaluator,
+    permutator: &LayoutPermutator,
+    layout_generator: &dyn LayoutGenerator,
+    key_switches: usize,
+) -> Vec<f64> {
+    let cost = |indices: &[usize]| -> f64 {
+        let layout = layout_generator
+            .generate(&permutator.generate_string(indices))
+            .unwrap();
+        evaluator.evaluate_layout(&layout).total_cost()
+    };
+
+    let mut deltas: Vec<f64> = Vec::new();
+    for _ in 0..auto_tune_params.probe_chains {
+        let mut current_indices = initial_indices.to_owned();
+        let mut current_cost = cost(&current_indices);
+        for _ in 0..auto_tune_params.probe_length {
+            current_indices = permutator.perform_n_swaps(&current_indices, key_switches);
+            let new_cost = cost(&current_indices);
+            if new_cost > current_cost {
+                deltas.push(new_cost - current_cost);
+            }
+            current_cost = new_cost;
+        }
+    }
+
+    deltas
+}
+
+/// Derives an initial temperature and cooling rate from the cost differences of uphill moves
+/// such that the targeted acceptance ratios are achieved within `max_iters` iterations. Returns
+/// `None` if there are no uphill moves.
+pub fn tune_schedule(
+    auto_tune_params: &AutoTuneParameters,
+    deltas: &[f64],
+    max_iters: u64,
+) -> Option<(f64, f64)> {
+    if deltas.is_empty() {
+        return None;
+    }
+
+    let init_temp = temperature_for_acceptance(deltas, auto_tune_params.initial_acceptance);
+    let final_temp = temperature_for_acceptance(deltas, auto_tune_params.final_acceptance);
+    let cooling_rate = (final_temp / init_temp).powf(1.0 / max_iters.max(1) as f64);
+
+    Some((init_temp, cooling_rate))
+}
+
 /// Performs one run of Simulated Annealing, then returns the best layout found.
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
 pub fn optimize(
     process_name: &str,
     params: &Parameters,
@@ -270,8 +715,18 @@ pub fn optimize(
     log_everything: bool,
     result_cache: Option<Cache<f64>>,
     custom_observer: Option<CustomObserver>,
-) -> (String, Layout) {
-    let pm = LayoutPermutator::new(layout_str, fixed_characters);
+    observer: Option<SharedObserver>,
+) -> (String, Layout, Option<ConvergenceHistory>, StopReason) {
+    let layer_size = params
+        .swap_restrictions
+        .layer_size(layout_generator.as_ref(), layout_str);
+    let (pm, constraints) = constrained_permutator(
+        layout_str,
+        fixed_characters,
+        layer_size,
+        &params.swap_restrictions.swap_groups,
+        params.soft_constraints.as_ref(),
+    );
     // Get initial Layout.
     let initial_indices = match start_with_layout {
         true => pm.get_permutable_indices(),
@@ -289,7 +744,41 @@ pub fn optimize(
     }
     println!("Average init_temp: {}", mean(&init_temp_vec)); */
 
-    let init_temp = match params.init_temp {
+    let tuned_schedule = params.auto_tune.as_ref().and_then(|auto_tune_params| {
+        log::info!(
+            "{} Auto-tuning annealing schedule with: {:?}",
+            format!("{}:", process_name).yellow().bold(),
+            auto_tune_params,
+        );
+        let deltas = probe_uphill_deltas(
+            auto_tune_params,
+            &initial_indices,
+            evaluator,
+            &pm,
+            layout_generator.as_ref(),
+            params.key_switches,
+        );
+        let schedule = tune_schedule(auto_tune_params, &deltas, params.max_iters);
+        match schedule {
+            Some((init_temp, cooling_rate)) => log::info!(
+                "{} Auto-tuned schedule: initial temperature = {:.4}°, cooling rate = {:.6}, \
+                 final temperature = {:.6}° after {} iterations",
+                format!("{}:", process_name).yellow().bold(),
+                init_temp,
+                cooling_rate,
+                init_temp * cooling_rate.powf(params.max_iters as f64),
+                params.max_iters,
+            ),
+            None => log::warn!(
+                "{} Auto-tuning found no uphill moves, using configured schedule",
+                format!("{}:", process_name).yellow().bold(),
+            ),
+        }
+        schedule
+    });
+    let cooling_rate = tuned_schedule.map_or(params.cooling_rate, |(_, rate)| rate);
+
+    let init_temp = match tuned_schedule.map(|(t, _)| t).or(params.init_temp) {
         Some(t) => t,
         None => {
             log::info!(
@@ -311,12 +800,45 @@ pub fn optimize(
             init_temp
         }
     };
+    let evaluations = Arc::new(AtomicU64::new(0));
+    let freezer = params.freezing.as_ref().map(|freezing_params| {
+        Arc::new(Mutex::new(Freezer::new(
+            process_name,
+            freezing_params,
+            &pm,
+            params.max_iters,
+        )))
+    });
+    let canonicalizer = if params.canonicalize_mirrors {
+        let layout = layout_generator.generate(layout_str).unwrap();
+        match layout.mirror_indices() {
+            Ok(mirror_indices) => {
+                if result_cache.is_none() {
+                    log::warn!(
+                        "Canonicalizing mirrored layouts saves no evaluations without a result cache"
+                    );
+                }
+                Some(MirrorCanonicalizer::new(mirror_indices))
+            }
+            Err(e) => {
+                log::warn!("Not canonicalizing mirrored layouts: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
     let problem = AnnealingStruct {
         evaluator: Arc::new(evaluator.clone()),
         permutator: pm.clone(),
         layout_generator: layout_generator.clone(),
         key_switches: params.key_switches,
         result_cache,
+        canonicalizer,
+        evaluations: evaluations.clone(),
+        freezer: freezer.clone(),
+        constraints: constraints.clone(),
+        max_iters: params.max_iters,
     };
 
     // Create new SA solver with some parameters (see docs for details)
@@ -325,7 +847,7 @@ pub fn optimize(
     let solver = SimulatedAnnealing::new_with_rng(init_temp, rng)
         .unwrap()
         // Optional: Define temperature function (defaults to `SATempFunc::TemperatureFast`)
-        .with_temp_func(SATempFunc::Exponential(0.998))
+        .with_temp_func(SATempFunc::Exponential(cooling_rate))
         /////////////////////////
         // Stopping criteria   //
         /////////////////////////
@@ -341,9 +863,49 @@ pub fn optimize(
                 // Optional: Set maximum number of iterations (defaults to `std::u64::MAX`)
                 .max_iters(params.max_iters)
         })
-        .timer(false);
+        .timer(false)
+        .add_observer(StatsObserver, ObserverMode::Always);
+    let history_recorder = params.record_history.then(|| {
+        Arc::new(Mutex::new(HistoryRecorder {
+            history: ConvergenceHistory::new(process_name),
+            ..Default::default()
+        }))
+    });
+    let stopping_outcome = Arc::new(Mutex::new(StoppingOutcome::default()));
+    if !params.stopping.is_empty() {
+        let stopping_observer = StoppingObserver {
+            monitor: StoppingMonitor::new(&params.stopping),
+            evaluations: evaluations.clone(),
+            outcome: stopping_outcome.clone(),
+        };
+        executor = executor.add_observer(stopping_observer, ObserverMode::Always);
+    }
+    let start = Instant::now();
+    let budget = Budget::new(params.max_iters, &params.stopping);
+    if let Some(observer) = &observer {
+        let progress_observer = ProgressObserver {
+            observer: observer.clone(),
+            run: process_name.to_string(),
+            budget: budget.clone(),
+            start,
+            evaluations: evaluations.clone(),
+            best_cost: f64::INFINITY,
+            permutator: pm.clone(),
+        };
+        executor = executor.add_observer(progress_observer, ObserverMode::Always);
+    }
+    if let Some(recorder) = &history_recorder {
+        let history_observer = HistoryObserver {
+            recorder: recorder.clone(),
+            bucket: params.history_bucket,
+            evaluator: Arc::new(evaluator.clone()),
+            permutator: pm.clone(),
+            layout_generator: layout_generator.clone(),
+        };
+        executor = executor.add_observer(history_observer, ObserverMode::Always);
+    }
     match custom_observer {
-        // If a custom Observer was supplied, only use that Observer.
+        // If a custom Observer was supplied, only use that Observer (besides the statistics).
         Some(observer) => {
             executor = executor.add_observer(observer, ObserverMode::Always);
         }
@@ -371,16 +933,108 @@ pub fn optimize(
     }
 
     log::info!(
-        "{} Starting optimization with: initial_temperature: {:.2}°, {:?}",
+        "{} Starting optimization with: initial_temperature: {:.2}°, cooling_rate: {}, {:?}",
         format!("{}:", process_name).yellow().bold(),
         init_temp,
+        cooling_rate,
         params,
     );
-    let res = executor.run().unwrap();
+    stats::record_start();
+    let (best_layout_param, best_cost, final_iter, stop_reason, last_param) = match executor.run() {
+        Ok(res) => (
+            res.state().get_best_param().unwrap().clone(),
+            res.state().get_best_cost(),
+            res.state().get_iter(),
+            StopReason::Completed,
+            res.state().get_param().cloned(),
+        ),
+        Err(e) => {
+            let outcome = stopping_outcome.lock().unwrap();
+            match (outcome.reason, &outcome.best_param) {
+                (Some(reason), Some(best_param)) => {
+                    log::info!(
+                        "{} Stopped early after iteration {}: {}",
+                        format!("{}:", process_name).yellow().bold(),
+                        outcome.iter,
+                        reason,
+                    );
+                    (
+                        best_param.clone(),
+                        outcome.best_cost.unwrap_or(f64::INFINITY),
+                        outcome.iter,
+                        reason,
+                        None,
+                    )
+                }
+                _ => panic!("{:?}", e),
+            }
+        }
+    };
 
-    let best_layout_param = res.state().get_best_param().unwrap();
-    let best_layout_str = pm.generate_string(best_layout_param);
+    // the best layout was found at a lower penalty, so the last one may satisfy more constraints
+    let (best_layout_param, best_cost) = match &constraints {
+        Some(constraints) => {
+            let mut candidates: Vec<&[usize]> = vec![&best_layout_param];
+            candidates.extend(last_param.as_deref());
+            let (i, repaired, cost) = constraints.best_repaired(&candidates, |param| {
+                let layout = layout_generator.generate(&pm.generate_string(param));
+                evaluator.evaluate_layout(&layout.unwrap()).total_cost()
+            });
+            let violations = constraints.violations(candidates[i]);
+            if violations > 0 {
+                log::info!(
+                    "{} Repaired {} violated soft constraints of the final layout",
+                    format!("{}:", process_name).yellow().bold(),
+                    violations,
+                );
+            }
+            (repaired, cost)
+        }
+        None => (best_layout_param, best_cost),
+    };
+    let best_layout_str = pm.generate_string(&best_layout_param);
     let best_layout = layout_generator.generate(&best_layout_str).unwrap();
 
-    (best_layout_str, best_layout)
+    if let Some(freezer) = &freezer {
+        let layout: Vec<char> = best_layout_str.chars().collect();
+        let frozen: String = freezer
+            .lock()
+            .unwrap()
+            .frozen_keys()
+            .iter()
+            .map(|n| layout[best_layout_param[*n]])
+            .collect();
+        log::info!(
+            "{} {} keys were frozen during the run: '{}'",
+            format!("{}:", process_name).yellow().bold(),
+            frozen.chars().count(),
+            frozen,
+        );
+    }
+
+    let history = history_recorder.map(|recorder| {
+        let mut recorder = recorder.lock().unwrap();
+        // the last (incomplete) bucket
+        if recorder.n_costs > 0 {
+            recorder.record(final_iter, &evaluator.evaluate_layout(&best_layout));
+        }
+        recorder.history.stop_reason = Some(stop_reason);
+        recorder.history.clone()
+    });
+    if let Some(observer) = &observer {
+        let progress = Progress {
+            run: process_name.to_string(),
+            step: final_iter,
+            best_cost,
+            evaluations: evaluations.load(Ordering::Relaxed),
+            elapsed: start.elapsed(),
+            budget,
+        };
+        observer
+            .lock()
+            .unwrap()
+            .on_finish(&progress, &best_layout_str, stop_reason);
+    }
+
+    (best_layout_str, best_layout, history, stop_reason)
 }
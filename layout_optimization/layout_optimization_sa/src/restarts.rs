@@ -0,0 +1,303 @@
+//! Restarts of the annealing: several independent chains are run and the best layout of all
+//! chains is reported, which is more robust than a single long chain that may get stuck in a
+//! poor local optimum. The chains may run in parallel, and chains may start from the best layout
+//! found by the chains that finished before them (cross-seeding).
+
+use crate::optimization::{optimize, Parameters};
+
+use keyboard_layout::{layout::Layout, layout_generator::LayoutGenerator};
+use layout_evaluation::{cache::Cache, evaluation::Evaluator};
+use layout_optimization_common::{
+    history::ConvergenceHistory, observer::SharedObserver, stopping::StopReason,
+};
+
+use colored::Colorize;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Mutex, time::Instant};
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct RestartParameters {
+    /// Number of annealing chains
+    pub chains: usize,
+
+    /// Run the chains in parallel instead of one after the other
+    #[serde(default = "default_parallel")]
+    pub parallel: bool,
+
+    /// Start each chain from the best layout of the chains finished so far (chains starting
+    /// before any chain finished use the start layout or a random layout)
+    #[serde(default)]
+    pub cross_seed: bool,
+}
+
+fn default_parallel() -> bool {
+    true
+}
+
+impl Default for RestartParameters {
+    fn default() -> Self {
+        RestartParameters {
+            chains: 4,
+            parallel: default_parallel(),
+            cross_seed: false,
+        }
+    }
+}
+
+/// Statistics of a single annealing chain
+#[derive(Serialize, Clone, Debug)]
+pub struct ChainStats {
+    pub chain: usize,
+    /// Whether the chain started from the best layout of previous chains
+    pub cross_seeded: bool,
+    pub layout: String,
+    pub cost: f64,
+    pub seconds: f64,
+    pub stop_reason: StopReason,
+}
+
+/// The best layout of all chains together with the statistics of each chain
+pub struct RestartsResult {
+    pub layout_str: String,
+    pub layout: Layout,
+    pub stop_reason: StopReason,
+    /// Statistics of the chains in the order they were started
+    pub chains: Vec<ChainStats>,
+    pub histories: Vec<ConvergenceHistory>,
+}
+
+impl fmt::Display for RestartsResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>5}  {:<12} {:>10} {:>9}  Stopped",
+            "Chain", "Start", "Cost", "Time (s)"
+        )?;
+        for chain in self.chains.iter() {
+            let line = format!(
+                "{:>5}  {:<12} {:>10.2} {:>9.1}  {}",
+                chain.chain,
+                if chain.cross_seeded {
+                    "best so far"
+                } else {
+                    "start layout"
+                },
+                chain.cost,
+                chain.seconds,
+                chain.stop_reason,
+            );
+            match chain.layout == self.layout_str {
+                true => writeln!(f, "{}", line.green())?,
+                false => writeln!(f, "{}", line)?,
+            }
+        }
+
+        let costs: Vec<f64> = self.chains.iter().map(|c| c.cost).collect();
+        let n = costs.len().max(1) as f64;
+        let mean = costs.iter().sum::<f64>() / n;
+        let std = (costs.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / n).sqrt();
+        let worst = costs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let best = costs.iter().cloned().fold(f64::INFINITY, f64::min);
+        write!(
+            f,
+            "Best of {} chains: {:.2} (mean: {:.2}, standard deviation: {:.2}, worst: {:.2})",
+            costs.len(),
+            best,
+            mean,
+            std,
+            worst,
+        )
+    }
+}
+
+/// Performs `restart_params.chains` runs of Simulated Annealing, then returns the best layout
+/// of all runs and the statistics of each run.
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
+pub fn optimize_restarts(
+    restart_params: &RestartParameters,
+    params: &Parameters,
+    layout_str: &str,
+    fixed_characters: &str,
+    layout_generator: &Box<dyn LayoutGenerator>,
+    start_with_layout: bool,
+    evaluator: &Evaluator,
+    log_everything: bool,
+    result_cache: Option<Cache<f64>>,
+    observer: Option<SharedObserver>,
+) -> RestartsResult {
+    let best_so_far: Mutex<Option<(String, f64)>> = Mutex::new(None);
+
+    let run_chain = |chain: usize| {
+        let seed = match restart_params.cross_seed {
+            true => best_so_far.lock().unwrap().clone(),
+            false => None,
+        };
+        let (start_layout, start_from_layout) = match &seed {
+            Some((best_layout, _)) => (best_layout.as_str(), true),
+            None => (layout_str, start_with_layout),
+        };
+        if let Some((_, best_cost)) = &seed {
+            log::info!(
+                "{} Starting from the best layout so far ({:.4})",
+                format!("Chain {:>3}:", chain).yellow().bold(),
+                best_cost,
+            );
+        }
+
+        let start = Instant::now();
+        let (chain_layout_str, chain_layout, history, stop_reason) = optimize(
+            &format!("Chain {:>3}", chain),
+            params,
+            start_layout,
+            fixed_characters,
+            layout_generator,
+            start_from_layout,
+            evaluator,
+            log_everything,
+            result_cache.clone(),
+            None,
+            observer.clone(),
+        );
+        let cost = evaluator.evaluate_layout(&chain_layout).total_cost();
+
+        {
+            let mut best = best_so_far.lock().unwrap();
+            if best
+                .as_ref()
+                .map_or(true, |(_, best_cost)| cost < *best_cost)
+            {
+                *best = Some((chain_layout_str.clone(), cost));
+            }
+        }
+
+        let stats = ChainStats {
+            chain,
+            cross_seeded: seed.is_some(),
+            layout: chain_layout_str,
+            cost,
+            seconds: start.elapsed().as_secs_f64(),
+            stop_reason,
+        };
+        (stats, chain_layout, history)
+    };
+
+    let chains = restart_params.chains.max(1);
+    let results: Vec<(ChainStats, Layout, Option<ConvergenceHistory>)> =
+        match restart_params.parallel {
+            true => (0..chains).into_par_iter().map(run_chain).collect(),
+            false => (0..chains).map(run_chain).collect(),
+        };
+
+    let best = results
+        .iter()
+        .enumerate()
+        .min_by(|(_, (s1, _, _)), (_, (s2, _, _))| s1.cost.partial_cmp(&s2.cost).unwrap())
+        .map(|(i, _)| i)
+        .unwrap();
+    let mut chain_stats = Vec::with_capacity(results.len());
+    let mut histories = Vec::new();
+    let mut best_layout = None;
+    for (i, (stats, layout, history)) in results.into_iter().enumerate() {
+        if i == best {
+            best_layout = Some(layout);
+        }
+        histories.extend(history);
+        chain_stats.push(stats);
+    }
+
+    RestartsResult {
+        layout_str: chain_stats[best].layout.clone(),
+        layout: best_layout.unwrap(),
+        stop_reason: chain_stats[best].stop_reason,
+        chains: chain_stats,
+        histories,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use layout_evaluation::test_utils::setup;
+
+    #[test]
+    fn reports_the_best_chain_and_seeds_later_chains_with_it() {
+        let (generator, layout_str, evaluator) = setup();
+        let params = Parameters {
+            init_temp: Some(1.0),
+            max_iters: 50,
+            ..Default::default()
+        };
+        let restart_params = RestartParameters {
+            chains: 3,
+            parallel: false,
+            cross_seed: true,
+        };
+
+        let result = optimize_restarts(
+            &restart_params,
+            &params,
+            &layout_str,
+            "",
+            &generator,
+            false,
+            &evaluator,
+            false,
+            None,
+            None,
+        );
+
+        assert_eq!(result.chains.len(), 3);
+        let cross_seeded: Vec<bool> = result.chains.iter().map(|c| c.cross_seeded).collect();
+        assert_eq!(cross_seeded, vec![false, true, true]);
+
+        let best = result
+            .chains
+            .iter()
+            .min_by(|c1, c2| c1.cost.partial_cmp(&c2.cost).unwrap())
+            .unwrap();
+        assert_eq!(result.layout_str, best.layout);
+        assert_eq!(result.layout.as_text(), best.layout);
+        let cost = |layout: &str| {
+            let layout = generator.generate(layout).unwrap();
+            evaluator.evaluate_layout(&layout).total_cost()
+        };
+        assert_eq!(cost(&result.layout_str), best.cost);
+        // a cross-seeded chain never ends worse than its start (the best earlier chain)
+        assert!(result.chains[1].cost <= result.chains[0].cost);
+        assert!(result.chains[2].cost <= result.chains[1].cost);
+    }
+
+    #[test]
+    fn chains_without_cross_seeding_start_from_the_start_layout() {
+        let (generator, layout_str, evaluator) = setup();
+        let params = Parameters {
+            init_temp: Some(1.0),
+            max_iters: 20,
+            ..Default::default()
+        };
+        let restart_params = RestartParameters {
+            chains: 2,
+            parallel: true,
+            cross_seed: false,
+        };
+
+        let result = optimize_restarts(
+            &restart_params,
+            &params,
+            &layout_str,
+            "",
+            &generator,
+            true,
+            &evaluator,
+            false,
+            None,
+            None,
+        );
+
+        assert_eq!(result.chains.len(), 2);
+        assert!(result.chains.iter().all(|c| !c.cross_seeded));
+        assert!(result.chains.iter().any(|c| c.layout == result.layout_str));
+    }
+}
@@ -0,0 +1,192 @@
+//! Named annealing schedules for common evaluation budgets and the curves of temperature and
+//! expected acceptance ratio of a schedule (e.g. for checking a schedule before a long run).
+
+use crate::optimization::{acceptance_ratio, AutoTuneParameters, Parameters};
+
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+/// A named annealing schedule. The schedule is auto-tuned from probe chains (see
+/// [`AutoTuneParameters`]) and spread over the evaluation budget.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SchedulePreset {
+    /// A quick run for trying out configurations (minutes)
+    Fast,
+    /// A careful run for comparing layouts (up to an hour)
+    Thorough,
+    /// A long run exploring the search space extensively (hours)
+    Overnight,
+}
+
+impl SchedulePreset {
+    pub const ALL: [SchedulePreset; 3] = [
+        SchedulePreset::Fast,
+        SchedulePreset::Thorough,
+        SchedulePreset::Overnight,
+    ];
+
+    /// Number of evaluations if no budget is given.
+    pub fn default_budget(&self) -> u64 {
+        match self {
+            SchedulePreset::Fast => 20_000,
+            SchedulePreset::Thorough => 200_000,
+            SchedulePreset::Overnight => 5_000_000,
+        }
+    }
+
+    pub fn auto_tune_parameters(&self) -> AutoTuneParameters {
+        match self {
+            SchedulePreset::Fast => AutoTuneParameters {
+                probe_chains: 2,
+                probe_length: 25,
+                initial_acceptance: 0.25,
+                final_acceptance: 0.005,
+            },
+            SchedulePreset::Thorough => AutoTuneParameters {
+                probe_chains: 4,
+                probe_length: 50,
+                initial_acceptance: 0.4,
+                final_acceptance: 0.001,
+            },
+            SchedulePreset::Overnight => AutoTuneParameters {
+                probe_chains: 8,
+                probe_length: 100,
+                initial_acceptance: 0.45,
+                final_acceptance: 0.0001,
+            },
+        }
+    }
+
+    /// Fraction of the iterations without an accepted move after which the optimization stops.
+    fn stall_fraction(&self) -> f64 {
+        match self {
+            SchedulePreset::Fast | SchedulePreset::Thorough => 0.1,
+            SchedulePreset::Overnight => 0.05,
+        }
+    }
+
+    /// Overwrite the schedule of the parameters (`init_temp`, `auto_tune`, `max_iters`, and
+    /// `stall_accepted`) such that the optimization (including the probe chains) uses the
+    /// evaluation budget.
+    pub fn apply(&self, params: &mut Parameters, budget: Option<u64>) {
+        let budget = budget.unwrap_or_else(|| self.default_budget());
+        let auto_tune = self.auto_tune_parameters();
+        // each probe chain evaluates its start and all of its moves
+        let probe_evaluations = (auto_tune.probe_chains * (auto_tune.probe_length + 1)) as u64;
+
+        params.max_iters = budget.saturating_sub(probe_evaluations).max(1);
+        params.stall_accepted = ((params.max_iters as f64 * self.stall_fraction()) as u64).max(100);
+        params.init_temp = None;
+        params.auto_tune = Some(auto_tune);
+    }
+}
+
+impl FromStr for SchedulePreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SchedulePreset::ALL
+            .iter()
+            .find(|preset| preset.to_string() == s)
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "Unknown schedule preset '{}' (available: {})",
+                    s,
+                    SchedulePreset::ALL
+                        .iter()
+                        .map(|p| p.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for SchedulePreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SchedulePreset::Fast => "fast",
+            SchedulePreset::Thorough => "thorough",
+            SchedulePreset::Overnight => "overnight",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The state of a schedule at one iteration.
+#[derive(Clone, Debug)]
+pub struct SchedulePoint {
+    pub iteration: u64,
+    pub temperature: f64,
+    /// Expected acceptance ratio of uphill moves (see [`acceptance_ratio`])
+    pub acceptance: f64,
+}
+
+/// The temperature and expected acceptance ratio of the given (uphill) cost differences at
+/// `n_points` evenly spaced iterations of an exponential schedule.
+pub fn schedule_curve(
+    init_temp: f64,
+    cooling_rate: f64,
+    max_iters: u64,
+    deltas: &[f64],
+    n_points: usize,
+) -> Vec<SchedulePoint> {
+    let n_points = n_points.max(2);
+    (0..n_points)
+        .map(|i| {
+            let iteration = max_iters * i as u64 / (n_points - 1) as u64;
+            let temperature = init_temp * cooling_rate.powf(iteration as f64);
+            let acceptance = if deltas.is_empty() {
+                0.0
+            } else {
+                acceptance_ratio(deltas, temperature)
+            };
+
+            SchedulePoint {
+                iteration,
+                temperature,
+                acceptance,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_spread_the_schedule_over_the_budget() {
+        let mut params = Parameters::default();
+        SchedulePreset::Fast.apply(&mut params, Some(10_000));
+
+        // two probe chains of 25 moves evaluate 52 layouts
+        assert_eq!(params.max_iters, 10_000 - 52);
+        assert_eq!(params.stall_accepted, (10_000 - 52) / 10);
+        assert_eq!(params.init_temp, None);
+        assert_eq!(params.auto_tune.as_ref().unwrap().probe_chains, 2);
+
+        SchedulePreset::Overnight.apply(&mut params, None);
+        assert_eq!(params.max_iters, 5_000_000 - 8 * 101);
+        assert_eq!(params.stall_accepted, (5_000_000 - 8 * 101) / 20);
+    }
+
+    #[test]
+    fn presets_keep_a_minimal_schedule_for_tiny_budgets() {
+        let mut params = Parameters::default();
+        SchedulePreset::Thorough.apply(&mut params, Some(10));
+
+        assert_eq!(params.max_iters, 1);
+        assert_eq!(params.stall_accepted, 100);
+    }
+
+    #[test]
+    fn presets_are_parsed_from_their_names() {
+        for preset in SchedulePreset::ALL {
+            assert_eq!(preset.to_string().parse::<SchedulePreset>(), Ok(preset));
+        }
+        assert!("slow".parse::<SchedulePreset>().is_err());
+    }
+}
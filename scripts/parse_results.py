@@ -290,7 +290,7 @@ def parse_layout_diagram(text: str) -> list[str]:
         return []
 
     for line in lines[start_idx:]:
-        if "Layout string" in line:
+        if "Layout string" in line or line.startswith("Layout (layer"):
             break
         if line.strip():
             layout_lines.append(line)
@@ -300,7 +300,7 @@ def parse_layout_diagram(text: str) -> list[str]:
 
 def export_svg(layout_lines: list[str], output_path: Path) -> None:
     """Create SVG representation of the keyboard layout using Rich."""
-    console = Console(record=True, width=64)
+    console = Console(record=True, width=96)
 
     for line in layout_lines:
         styled_line = ""
@@ -178,7 +178,8 @@ impl LayoutEvaluator {
             .map_err(|e| format!("Could not read evaluation parameters: {:?}", e))?;
 
         let evaluator = Evaluator::default(Box::new(ngram_provider.ngram_provider.clone()))
-            .default_metrics(&eval_params.metrics);
+            .default_metrics(&eval_params.metrics)
+            .map_err(|e| format!("Could not initialize the metrics: {:?}", e))?;
 
         Ok(LayoutEvaluator {
             layout_generator,
@@ -249,14 +250,16 @@ impl LayoutOptimizer {
         let layout_generator: Box<dyn LayoutGenerator> =
             Box::new(layout_evaluator.layout_generator.clone());
 
-        let (simulator, permutator) = genevo_optimization::init_optimization(
+        let (simulator, permutator, _, _) = genevo_optimization::init_optimization(
             &parameters,
             &layout_evaluator.evaluator,
             &layout_str,
             &layout_generator,
             fixed_characters,
             start_with_layout,
-            true,
+            &[],
+            Some(Cache::new()),
+            None,
         );
 
         Ok(LayoutOptimizer {
@@ -370,6 +373,7 @@ pub fn sa_optimize(
     let mut parameters: sa_optimization::Parameters = serde_yaml::from_str(optimization_params_str)
         .map_err(|e| format!("Could not read optimization params: {:?}", e))
         .unwrap();
+    parameters.apply_preset();
     // Make sure the initial temperature is greater than zero.
     parameters.correct_init_temp();
 
@@ -396,7 +400,7 @@ pub fn sa_optimize(
 
     let layout_str: String = layout_str.chars().filter(|c| !c.is_whitespace()).collect();
 
-    let _: (String, Layout) = sa_optimization::optimize(
+    let _: (String, Layout, _, _) = sa_optimization::optimize(
         /* Thread_name: */ "Web optimization",
         &parameters,
         &layout_str,
@@ -407,6 +411,7 @@ pub fn sa_optimize(
         /* log_everything: */ false,
         Some(Cache::new()),
         Some(SaCustomObserver(Box::new(observer))),
+        None,
     );
     let minus_one = JsValue::from(-1);
     let _ = update_callback.call1(&this, &minus_one);